@@ -0,0 +1,16 @@
+//! Midi files: metadata, duration, ending check and title.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sfontplayer_core::player::playlist::midi_meta::{parse_midi, MidiMeta};
+
+// Parser panics that the player catches are kept from libfuzzer's hook the same way they are
+// kept quiet for users, so only panics that get out count.
+fuzz_target!(|data: &[u8]| {
+    let mut song = MidiMeta::from_bytes("fuzz.mid".into(), data);
+    let _ = song.get_status();
+    song.check_ending();
+    song.read_title();
+    let _ = parse_midi(data);
+});
@@ -0,0 +1,15 @@
+//! Playlist json, as in `.midpl` files: loading, and saving again.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde_json::Value;
+use sfontplayer_core::player::playlist::Playlist;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(json) = serde_json::from_slice::<Value>(data) else {
+        return;
+    };
+    let playlist = Playlist::from(json);
+    let _ = Value::from(&playlist).to_string();
+});
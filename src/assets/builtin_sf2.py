@@ -0,0 +1,373 @@
+#!/usr/bin/env python3
+"""Generates builtin.sf2, the fallback soundfont embedded in the app.
+
+Run from this folder: `python3 builtin_sf2.py`. The output is the same every time, so the
+committed file can be checked against it.
+
+This isn't a real General MIDI font, just enough to tell the parts of a song apart. Each of the 16
+GM instrument families gets one instrument: a looped wave of a few partials with its own envelope,
+and vibrato or tremolo where it helps. All 128 programs of bank 0 are there, and every program of
+a family plays that family's instrument. Bank 128 preset 0 is a drum kit of kick, snare, toms,
+hi-hats, cymbals and a pitched click for the rest of the percussion keys. Everything is generated
+here, so the font is public domain.
+"""
+
+import math
+import random
+import struct
+
+# The spec wants at least this many zeros after each sample.
+PADDING = 46
+# Waves are four cycles of 100 samples at 44 kHz, which is key 69.
+WAVE_RATE = 44000
+WAVE_KEY = 69
+DRUM_RATE = 22050
+
+# Generator numbers from the spec
+VIB_LFO_TO_PITCH = 6
+MOD_LFO_TO_VOLUME = 13
+FREQ_MOD_LFO = 22
+DELAY_VIB_LFO = 23
+FREQ_VIB_LFO = 24
+ATTACK_VOL_ENV = 34
+DECAY_VOL_ENV = 36
+SUSTAIN_VOL_ENV = 37
+RELEASE_VOL_ENV = 38
+INSTRUMENT = 41
+KEY_RANGE = 43
+INITIAL_ATTENUATION = 48
+SAMPLE_ID = 53
+SAMPLE_MODES = 54
+SCALE_TUNING = 56
+OVERRIDING_ROOT_KEY = 58
+# Sustain attenuation that's silent, for sounds that die away while the key is held
+DIES_AWAY = 1000
+
+
+def timecents(seconds):
+    return round(1200 * math.log2(seconds))
+
+
+def lfo_cents(hertz):
+    """LFO frequency, in cents from 8.176 Hz"""
+    return round(1200 * math.log2(hertz / 8.176))
+
+
+def harmonic_wave(partials):
+    """Partials are {harmonic: amplitude}. Scaled so the peak is the same for every wave."""
+    samples = []
+    for i in range(400):
+        x = 2 * math.pi * i / 100
+        samples.append(sum(amp * math.sin(n * x) for n, amp in partials.items()))
+    peak = max(abs(s) for s in samples)
+    return [int(20000 * s / peak) for s in samples]
+
+
+def saw(count, slope=1.0):
+    return {n: 1 / n**slope for n in range(1, count + 1)}
+
+
+def odd(count):
+    return {n: 1 / n for n in range(1, count + 1, 2)}
+
+
+def seconds(length):
+    return range(int(length * DRUM_RATE))
+
+
+def noise(seed, length, decay=None, highpass=False):
+    rng = random.Random(seed)
+    samples = [rng.uniform(-1, 1) for _ in seconds(length)]
+    if highpass:
+        samples = [b - a for a, b in zip([0.0] + samples, samples)]
+    if decay:
+        samples = [s * math.exp(-i / DRUM_RATE / decay) for i, s in enumerate(samples)]
+    return samples
+
+
+def sweep(length, low, high, drop, decay):
+    """Sine that falls from high to low pitch, like a drum head"""
+    phase = 0.0
+    samples = []
+    for i in seconds(length):
+        t = i / DRUM_RATE
+        phase += 2 * math.pi * (low + (high - low) * math.exp(-t / drop)) / DRUM_RATE
+        samples.append(math.sin(phase) * math.exp(-t / decay))
+    return samples
+
+
+def scaled(samples, level=24000):
+    peak = max(abs(s) for s in samples)
+    return [int(level * s / peak) for s in samples]
+
+
+def snare():
+    rattle = noise(2, 0.3, decay=0.08)
+    head = sweep(0.3, 180, 260, 0.01, 0.05)
+    return [0.7 * a + b for a, b in zip(rattle, head)]
+
+
+# Name, wave, and instrument generators besides the sample. Family order is the GM order, so
+# programs 8 * i .. 8 * i + 7 belong to family i.
+FAMILIES = [
+    (
+        "Piano",
+        harmonic_wave({1: 1, 2: 0.5, 3: 0.3, 4: 0.2, 5: 0.12, 6: 0.08, 7: 0.05}),
+        [(DECAY_VOL_ENV, timecents(2.5)), (SUSTAIN_VOL_ENV, DIES_AWAY), (RELEASE_VOL_ENV, timecents(0.3))],
+    ),
+    (
+        "Chromatic Perc.",
+        harmonic_wave({1: 1, 3: 0.4, 5: 0.25, 8: 0.15}),
+        [(DECAY_VOL_ENV, timecents(1.2)), (SUSTAIN_VOL_ENV, DIES_AWAY), (RELEASE_VOL_ENV, timecents(0.5))],
+    ),
+    (
+        "Organ",
+        harmonic_wave({1: 1, 2: 0.8, 3: 0.6, 4: 0.5, 6: 0.4, 8: 0.3}),
+        [(RELEASE_VOL_ENV, timecents(0.08))],
+    ),
+    (
+        "Guitar",
+        harmonic_wave({1: 1, 2: 0.6, 3: 0.4, 4: 0.3, 5: 0.25, 6: 0.2, 7: 0.15, 8: 0.1}),
+        [(DECAY_VOL_ENV, timecents(1.5)), (SUSTAIN_VOL_ENV, DIES_AWAY), (RELEASE_VOL_ENV, timecents(0.2))],
+    ),
+    (
+        "Bass",
+        harmonic_wave({1: 1, 2: 0.5, 3: 0.2}),
+        [(DECAY_VOL_ENV, timecents(1.2)), (SUSTAIN_VOL_ENV, 200), (RELEASE_VOL_ENV, timecents(0.1))],
+    ),
+    (
+        "Strings",
+        harmonic_wave(saw(16)),
+        [
+            (ATTACK_VOL_ENV, timecents(0.15)),
+            (RELEASE_VOL_ENV, timecents(0.4)),
+            (DELAY_VIB_LFO, timecents(0.3)),
+            (FREQ_VIB_LFO, lfo_cents(5)),
+            (VIB_LFO_TO_PITCH, 10),
+        ],
+    ),
+    (
+        "Ensemble",
+        harmonic_wave(saw(12, slope=1.3)),
+        [
+            (ATTACK_VOL_ENV, timecents(0.25)),
+            (RELEASE_VOL_ENV, timecents(0.6)),
+            (FREQ_VIB_LFO, lfo_cents(6)),
+            (VIB_LFO_TO_PITCH, 15),
+        ],
+    ),
+    (
+        "Brass",
+        harmonic_wave(saw(14, slope=0.8)),
+        [(ATTACK_VOL_ENV, timecents(0.06)), (RELEASE_VOL_ENV, timecents(0.15))],
+    ),
+    (
+        "Reed",
+        harmonic_wave({**odd(11), 2: 0.1, 4: 0.05}),
+        [(ATTACK_VOL_ENV, timecents(0.04)), (RELEASE_VOL_ENV, timecents(0.1))],
+    ),
+    (
+        "Pipe",
+        harmonic_wave({1: 1, 2: 0.12, 3: 0.05}),
+        [
+            (ATTACK_VOL_ENV, timecents(0.08)),
+            (RELEASE_VOL_ENV, timecents(0.15)),
+            (FREQ_VIB_LFO, lfo_cents(5)),
+            (VIB_LFO_TO_PITCH, 8),
+        ],
+    ),
+    (
+        "Synth Lead",
+        harmonic_wave(odd(25)),
+        [(RELEASE_VOL_ENV, timecents(0.08)), (INITIAL_ATTENUATION, 40)],
+    ),
+    (
+        "Synth Pad",
+        harmonic_wave(saw(20)),
+        [(ATTACK_VOL_ENV, timecents(0.8)), (RELEASE_VOL_ENV, timecents(1.5))],
+    ),
+    (
+        "Synth Effects",
+        harmonic_wave({1: 1, 2: 0.7, 5: 0.5, 7: 0.4, 11: 0.3}),
+        [
+            (ATTACK_VOL_ENV, timecents(0.3)),
+            (RELEASE_VOL_ENV, timecents(1.0)),
+            (FREQ_MOD_LFO, lfo_cents(3)),
+            (MOD_LFO_TO_VOLUME, 60),
+        ],
+    ),
+    (
+        "Ethnic",
+        harmonic_wave({1: 1, 2: 0.8, 3: 0.7, 4: 0.5, 5: 0.45, 6: 0.35, 7: 0.3, 9: 0.2}),
+        [(DECAY_VOL_ENV, timecents(0.9)), (SUSTAIN_VOL_ENV, DIES_AWAY), (RELEASE_VOL_ENV, timecents(0.2))],
+    ),
+    (
+        "Percussive",
+        harmonic_wave({1: 1, 2: 0.3, 4: 0.15}),
+        [(DECAY_VOL_ENV, timecents(0.25)), (SUSTAIN_VOL_ENV, DIES_AWAY), (RELEASE_VOL_ENV, timecents(0.1))],
+    ),
+    (
+        "Sound Effects",
+        scaled(noise(1, 4000 / DRUM_RATE), 16000),
+        [(ATTACK_VOL_ENV, timecents(0.05)), (RELEASE_VOL_ENV, timecents(0.5))],
+    ),
+]
+
+# Name, samples, root key, whether the pitch follows the key, release
+DRUMS = {
+    "Kick": (scaled(sweep(0.3, 50, 150, 0.04, 0.15)), 36, False, 0.3),
+    "Snare": (scaled(snare()), 38, False, 0.3),
+    "Tom": (scaled(sweep(0.4, 100, 160, 0.05, 0.15)), 45, True, 0.4),
+    "Closed Hat": (scaled(noise(3, 0.15, decay=0.03, highpass=True), 16000), 42, False, 0.15),
+    "Open Hat": (scaled(noise(4, 0.6, decay=0.25, highpass=True), 16000), 46, False, 0.6),
+    "Cymbal": (scaled(noise(5, 1.0, decay=0.4, highpass=True), 18000), 49, False, 1.0),
+    "Click": (scaled(sweep(0.1, 800, 1200, 0.005, 0.03)), 60, True, 0.1),
+}
+DRUM_KEYS = {
+    "Kick": [35, 36],
+    "Snare": [37, 38, 39, 40],
+    "Tom": [41, 43, 45, 47, 48, 50],
+    "Closed Hat": [42, 44],
+    "Open Hat": [46],
+    "Cymbal": [49, 51, 52, 53, 55, 57, 59],
+}
+# Keys of the GM percussion map that aren't above are played as clicks
+DRUM_RANGE = range(27, 88)
+
+
+def drum_zones():
+    """Runs of neighbouring keys that play the same drum, as (drum, low key, high key)"""
+    drum_of = {key: drum for drum, keys in DRUM_KEYS.items() for key in keys}
+    zones = []
+    for key in DRUM_RANGE:
+        drum = drum_of.get(key, "Click")
+        if zones and zones[-1][0] == drum and zones[-1][2] == key - 1:
+            zones[-1] = (drum, zones[-1][1], key)
+        else:
+            zones.append((drum, key, key))
+    return zones
+
+
+def chunk(chunk_id, data):
+    if len(data) % 2:
+        data += b"\0"
+    return chunk_id + struct.pack("<I", len(data)) + data
+
+
+def riff_list(kind, chunks):
+    return chunk(b"LIST", kind + b"".join(chunks))
+
+
+def text(value):
+    """Zero terminated, and padded to even length by `chunk`"""
+    return value.encode() + b"\0"
+
+
+def name(value):
+    assert len(value) < 20
+    return value.encode().ljust(20, b"\0")
+
+
+def generator(kind, amount):
+    if kind == KEY_RANGE:
+        low, high = amount
+        return struct.pack("<HBB", kind, low, high)
+    return struct.pack("<Hh", kind, amount)
+
+
+def bags(generator_indices):
+    # No modulators
+    return b"".join(struct.pack("<HH", index, 0) for index in generator_indices)
+
+
+def main():
+    # Name, samples, rate, root key
+    waves = [(family, wave, WAVE_RATE, WAVE_KEY) for family, wave, _ in FAMILIES[:-1]]
+    waves.append(("Noise", FAMILIES[-1][1], DRUM_RATE, 60))
+    waves += [(drum, samples, DRUM_RATE, key) for drum, (samples, key, _, _) in DRUMS.items()]
+    sample_ids = {wave[0]: index for index, wave in enumerate(waves)}
+
+    smpl = b""
+    shdr = b""
+    for wave_name, samples, rate, key in waves:
+        start = len(smpl) // 2
+        end = start + len(samples)
+        smpl += struct.pack(f"<{len(samples)}h", *samples) + bytes(PADDING * 2)
+        shdr += name(wave_name) + struct.pack("<IIIIIBbHH", start, end, start, end, rate, key, 0, 0, 1)
+    shdr += name("EOS") + bytes(26)
+
+    # Instrument zones, each a list of generators. Sample id goes last.
+    instruments = []
+    for index, (family, _, generators) in enumerate(FAMILIES):
+        wave = waves[index][0]
+        zone = [*generators, (SAMPLE_MODES, 1), (SAMPLE_ID, sample_ids[wave])]
+        instruments.append((family, [zone]))
+    drum_zone_list = []
+    for drum, low, high in drum_zones():
+        _, key, pitched, release = DRUMS[drum]
+        zone = [(KEY_RANGE, (low, high)), (RELEASE_VOL_ENV, timecents(release))]
+        if not pitched:
+            zone += [(SCALE_TUNING, 0), (OVERRIDING_ROOT_KEY, key)]
+        zone.append((SAMPLE_ID, sample_ids[drum]))
+        drum_zone_list.append(zone)
+    instruments.append(("Drum Kit", drum_zone_list))
+
+    inst = b""
+    ibag = []
+    igen = b""
+    generator_count = 0
+    for inst_name, zones in instruments:
+        inst += name(inst_name) + struct.pack("<H", len(ibag))
+        for zone in zones:
+            ibag.append(generator_count)
+            igen += b"".join(generator(kind, amount) for kind, amount in zone)
+            generator_count += len(zone)
+    inst += name("EOI") + struct.pack("<H", len(ibag))
+    ibag.append(generator_count)
+    igen += generator(0, 0)
+
+    # One preset per GM program, and the drum kit. Each has one zone with one generator.
+    presets = [(FAMILIES[program // 8][0], program, 0, program // 8) for program in range(128)]
+    presets.append(("Drums", 0, 128, len(FAMILIES)))
+    phdr = b""
+    pgen = b""
+    for index, (preset_name, program, bank, instrument) in enumerate(presets):
+        # Program, bank, bag, then library, genre and morphology
+        phdr += name(preset_name) + struct.pack("<HHHIII", program, bank, index, 0, 0, 0)
+        pgen += generator(INSTRUMENT, instrument)
+    phdr += name("EOP") + struct.pack("<HHHIII", 0, 0, len(presets), 0, 0, 0)
+    pgen += generator(0, 0)
+
+    info = riff_list(
+        b"INFO",
+        [
+            chunk(b"ifil", struct.pack("<HH", 2, 1)),
+            chunk(b"isng", text("EMU8000")),
+            chunk(b"INAM", text("sfontplayer built-in")),
+            chunk(
+                b"ICMT",
+                text("Generated tones, one per GM instrument family, and a simple drum kit. Public domain."),
+            ),
+        ],
+    )
+    sdta = riff_list(b"sdta", [chunk(b"smpl", smpl)])
+    pdta = riff_list(
+        b"pdta",
+        [
+            chunk(b"phdr", phdr),
+            chunk(b"pbag", bags(range(len(presets) + 1))),
+            chunk(b"pmod", bytes(10)),
+            chunk(b"pgen", pgen),
+            chunk(b"inst", inst),
+            chunk(b"ibag", bags(ibag)),
+            chunk(b"imod", bytes(10)),
+            chunk(b"igen", igen),
+            chunk(b"shdr", shdr),
+        ],
+    )
+    with open("builtin.sf2", "wb") as file:
+        file.write(chunk(b"RIFF", b"sfbk" + info + sdta + pdta))
+
+
+if __name__ == "__main__":
+    main()
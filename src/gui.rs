@@ -1,34 +1,70 @@
 pub mod actions;
+pub mod commands;
 pub mod conversions;
 mod cooltoolbar;
 pub mod custom_controls;
 pub mod keyboard_shortcuts;
+mod live_input;
+mod lyrics;
 mod midi_inspector;
 pub mod modals;
-mod playback_controls;
+pub mod notifications;
+mod now_playing;
+pub mod playback_controls;
 mod playlist_fonts;
 mod playlist_songs;
+mod problems;
 pub mod soundfont_library;
+pub mod storage;
 mod tabs;
+mod visualizers;
 
 use crate::midi_inspector::MidiInspector;
-use crate::player::Player;
+use crate::player::{
+    audio::export::ExportJob, global_hotkeys::HotkeyAction, problems::ProblemLocation, Player,
+};
 use crate::SfontPlayer;
 use cooltoolbar::toolbar;
-use eframe::egui::{vec2, CentralPanel, Context, Frame, SidePanel, TopBottomPanel, Ui};
+use eframe::egui::{
+    style::ScrollStyle, vec2, Align, Button, CentralPanel, Context, Frame, Layout, RichText,
+    SidePanel, Spacing, TextStyle, TopBottomPanel, Ui, Vec2, ViewportBuilder, ViewportCommand,
+    ViewportId, WindowLevel,
+};
 use egui_notify::Toasts;
-use keyboard_shortcuts::consume_shortcuts;
+use keyboard_shortcuts::{consume_shortcuts, GUI_COMPACT};
+use live_input::live_input_panel;
+use lyrics::lyrics_panel;
 use midi_inspector::midi_inspector;
+use modals::command_palette::command_palette;
+use modals::confirm_dialog::{confirm_dialog, ConfirmAction, Confirmations};
 use modals::{about_modal::about_modal, settings::settings_modal, shortcuts::shortcut_modal};
-use modals::{unsaved_close_dialog, unsaved_quit_dialog};
-use playback_controls::playback_panel;
+use modals::{
+    end_action_dialog, export_dialog, export_progress, state_recovery_dialog, unsaved_close_dialog,
+    unsaved_quit_dialog,
+};
+use now_playing::now_playing;
+use playback_controls::{playback_panel, MouseGestures, TimeReadout};
 use playlist_fonts::soundfont_table;
 use playlist_songs::playlist_song_panel;
+use problems::problems_panel;
 use soundfont_library::soundfont_library;
-use std::path::PathBuf;
-use tabs::playlist_tabs;
+use std::{ops::RangeInclusive, path::PathBuf};
+use tabs::{playlist_tabs, TabStripState};
+use visualizers::Visualizers;
 
+/// Default height of table rows
 const TBL_ROW_H: f32 = 16.;
+/// Default text size of tables, same as egui body text
+const TBL_TEXT_SIZE: f32 = 12.5;
+pub const TBL_ROW_H_RANGE: RangeInclusive<f32> = 12.0..=48.0;
+pub const TBL_TEXT_SIZE_RANGE: RangeInclusive<f32> = 8.0..=32.0;
+/// Space above and below table text, so rows don't get shorter than their text
+const TBL_TEXT_PADDING: f32 = 4.;
+/// Table rows and controls big enough for a finger
+const TOUCH_ROW_H: f32 = 32.;
+pub const MIN_WINDOW_SIZE: Vec2 = vec2(300., 220.);
+const COMPACT_WINDOW_SIZE: Vec2 = vec2(520., 40.);
+const COMPACT_MIN_WINDOW_SIZE: Vec2 = vec2(320., 32.);
 
 /// For gui stuff that doesn't count as app logic.
 #[derive(Default, serde::Deserialize, serde::Serialize)]
@@ -36,15 +72,95 @@ const TBL_ROW_H: f32 = 16.;
 pub struct GuiState {
     pub show_playlist_fonts: bool,
     pub show_font_library: bool,
+    /// Files with errors, above the playback bar
+    pub show_problems: bool,
+    /// MIDI input port controls, above the playback bar
+    pub show_live_input: bool,
+    /// Karaoke lyrics of the playing song, above the playback bar
+    pub show_lyrics: bool,
+    /// Row that was jumped to from the problems panel
+    #[serde(skip)]
+    pub problem_target: Option<ProblemLocation>,
     #[serde(skip)]
     pub show_about_modal: bool,
     #[serde(skip)]
     pub show_settings_modal: bool,
+    /// Filter text of the settings window
+    #[serde(skip)]
+    pub settings_search: String,
+    /// Output devices listed while the device picker is open
+    #[serde(skip)]
+    pub output_devices: Option<Vec<String>>,
+    /// Stereo pairs of the output device, counted while the pair picker is open
+    #[serde(skip)]
+    pub output_pair_count: Option<u16>,
     #[serde(skip)]
     pub show_shortcut_modal: bool,
     #[serde(skip)]
     pub show_unsaved_quit_modal: bool,
+    #[serde(skip)]
+    pub show_command_palette: bool,
+    /// Filter text of the command palette
+    #[serde(skip)]
+    pub palette_search: String,
+    /// Highlighted command palette entry, run with Enter
+    #[serde(skip)]
+    pub palette_selected: usize,
+    /// Name typed in the sessions menu
+    #[serde(skip)]
+    pub session_name: String,
     pub show_developer_options: bool,
+    /// Large display mode instead of the usual layout
+    pub show_now_playing: bool,
+    /// Desktop notification when a new song starts
+    pub notify_song_change: bool,
+    /// Notify even when the window is focused
+    pub notify_when_focused: bool,
+    /// Mini-player: only the playback bar, always on top
+    pub compact_mode: bool,
+    /// Bigger controls and table rows for touch screens
+    pub touch_mode: bool,
+    /// Single click on a song only selects it, double click plays
+    pub double_click_to_play: bool,
+    /// Song selected with a single click: playlist and song index
+    #[serde(skip)]
+    pub selected_song: Option<(usize, usize)>,
+    /// Row height and text size of song and soundfont tables
+    pub table_density: TableDensity,
+    /// Format of the time next to the seek bar
+    pub time_readout: TimeReadout,
+    /// Wheel and middle click actions of the playback bar
+    pub mouse_gestures: MouseGestures,
+    /// Window size to restore when leaving compact mode
+    #[serde(skip)]
+    pub full_window_size: Option<Vec2>,
+    /// Which destructive actions ask first
+    pub confirmations: Confirmations,
+    /// Action waiting for the user to confirm
+    #[serde(skip)]
+    pub pending_confirmation: Option<ConfirmAction>,
+    /// "Don't ask again" checkbox state of the open confirmation dialog
+    #[serde(skip)]
+    pub dont_ask_again: bool,
+    /// Show MIDI inspector in its own window instead of the main window.
+    pub inspector_detached: bool,
+    #[serde(skip)]
+    pub tab_strip: TabStripState,
+    /// Global hotkey waiting for a key press in settings
+    #[serde(skip)]
+    pub recording_hotkey: Option<HotkeyAction>,
+    /// Now Playing visualizers
+    #[serde(skip)]
+    pub visualizers: Visualizers,
+    /// Format choice before a piano roll export
+    #[serde(skip)]
+    pub show_export_dialog: bool,
+    /// Piano roll export in progress
+    #[serde(skip)]
+    pub export_job: Option<ExportJob>,
+    /// Seed text field in shuffle menu
+    #[serde(skip)]
+    pub shuffle_seed_input: String,
     /// Bypass unsaved files check on close.
     #[serde(skip)]
     pub force_quit: bool,
@@ -56,29 +172,82 @@ pub struct GuiState {
 }
 
 impl GuiState {
+    pub fn open_command_palette(&mut self) {
+        self.show_command_palette = true;
+        self.palette_search.clear();
+        self.palette_selected = 0;
+    }
     pub fn toast_error<S: AsRef<str>>(&mut self, caption: S) {
         self.toasts
             .error(caption.as_ref())
             .show_progress_bar(false)
             .closable(true);
     }
+    pub fn toast_info<S: AsRef<str>>(&mut self, caption: S) {
+        self.toasts
+            .info(caption.as_ref())
+            .show_progress_bar(false)
+            .closable(true);
+    }
     pub fn toast_success<S: AsRef<str>>(&mut self, caption: S) {
         self.toasts
             .success(caption.as_ref())
             .show_progress_bar(false)
             .closable(true);
     }
+    /// Row jumped to from the problems panel, highlighted while the panel is open
+    pub fn is_problem_target(&self, location: ProblemLocation) -> bool {
+        self.show_problems && self.problem_target == Some(location)
+    }
+    /// Table row height. Always fits the text, and a finger in touch mode.
+    pub fn row_height(&self) -> f32 {
+        let height = self
+            .table_density
+            .row_height
+            .max(self.table_density.text_size + TBL_TEXT_PADDING);
+        if self.touch_mode {
+            height.max(TOUCH_ROW_H)
+        } else {
+            height
+        }
+    }
+    /// Use the table text size for the rest of this ui
+    pub fn apply_table_text_size(&self, ui: &mut Ui) {
+        let size = self.table_density.text_size;
+        for style in [TextStyle::Body, TextStyle::Button] {
+            if let Some(font) = ui.style_mut().text_styles.get_mut(&style) {
+                font.size = size;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct TableDensity {
+    pub row_height: f32,
+    pub text_size: f32,
+}
+impl Default for TableDensity {
+    fn default() -> Self {
+        Self {
+            row_height: TBL_ROW_H,
+            text_size: TBL_TEXT_SIZE,
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct UpdateFlags {
     pub scroll_to_song: bool,
+    pub scroll_to_problem: bool,
     pub open_midi_inspector: Option<PathBuf>,
     pub close_midi_inspector: bool,
 }
 impl UpdateFlags {
     pub fn clear(&mut self) {
         self.scroll_to_song = false;
+        self.scroll_to_problem = false;
         self.open_midi_inspector = None;
         self.close_midi_inspector = false;
     }
@@ -92,8 +261,31 @@ pub fn draw_gui(ctx: &Context, app: &mut SfontPlayer) {
     about_modal(ctx, gui);
     settings_modal(ctx, player, gui);
     shortcut_modal(ctx, gui);
-    unsaved_close_dialog(ctx, player);
+    command_palette(ctx, player, gui);
+    unsaved_close_dialog(ctx, player, gui);
+    confirm_dialog(ctx, player, gui);
     unsaved_quit_dialog(ctx, player, gui);
+    state_recovery_dialog(ctx, player, gui);
+    export_dialog(ctx, player, gui);
+    export_progress(ctx, gui);
+    end_action_dialog(ctx, player);
+
+    if gui.compact_mode {
+        compact_player(ctx, player, gui);
+        gui.toasts.show(ctx);
+        consume_shortcuts(ctx, player, gui);
+        return;
+    }
+    if gui.show_now_playing {
+        CentralPanel::default().show(ctx, |ui| {
+            disable_if_modal(ui, gui);
+
+            now_playing(ui, player, gui);
+        });
+        gui.toasts.show(ctx);
+        consume_shortcuts(ctx, player, gui);
+        return;
+    }
 
     TopBottomPanel::top("top_bar")
         .resizable(false)
@@ -111,6 +303,33 @@ pub fn draw_gui(ctx: &Context, app: &mut SfontPlayer) {
         playback_panel(ui, player, gui);
     });
 
+    if gui.show_live_input {
+        TopBottomPanel::bottom("live_input").show(ctx, |ui| {
+            disable_if_modal(ui, gui);
+
+            live_input_panel(ui, player, gui);
+        });
+    }
+
+    if gui.show_lyrics {
+        TopBottomPanel::bottom("lyrics").show(ctx, |ui| {
+            disable_if_modal(ui, gui);
+
+            lyrics_panel(ui, player, gui);
+        });
+    }
+
+    if gui.show_problems {
+        TopBottomPanel::bottom("problems")
+            .resizable(true)
+            .default_height(128.)
+            .show(ctx, |ui| {
+                disable_if_modal(ui, gui);
+
+                problems_panel(ui, player, gui);
+            });
+    }
+
     if gui.show_font_library {
         SidePanel::right("soundfont_library")
             .exact_width(256.)
@@ -133,26 +352,137 @@ pub fn draw_gui(ctx: &Context, app: &mut SfontPlayer) {
             });
     }
 
-    if let Some(inspector) = &mut app.midi_inspector {
-        midi_inspector_panel(ctx, inspector, gui);
-    } else {
-        playlist_panel(ctx, player, gui);
+    match &mut app.midi_inspector {
+        Some(inspector) if gui.inspector_detached => {
+            midi_inspector_window(ctx, inspector, player, gui);
+            playlist_panel(ctx, player, gui);
+        }
+        Some(inspector) => midi_inspector_panel(ctx, inspector, player, gui),
+        None => playlist_panel(ctx, player, gui),
     }
     gui.toasts.show(ctx);
     consume_shortcuts(ctx, player, gui);
-    handle_dropped_files(ctx);
+    handle_dropped_files(ctx, player, gui);
+}
+
+/// Minimal layout for compact mode
+fn compact_player(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
+    CentralPanel::default()
+        .frame(Frame::central_panel(&ctx.style()).inner_margin(vec2(8., 2.)))
+        .show(ctx, |ui| {
+            disable_if_modal(ui, gui);
+
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                if ui
+                    .add(Button::new(RichText::new("🗖").size(16.)).frame(false))
+                    .on_hover_text(format!(
+                        "Leave compact mode ({})",
+                        ctx.format_shortcut(&GUI_COMPACT)
+                    ))
+                    .clicked()
+                {
+                    set_compact_mode(ctx, gui, false);
+                }
+                ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+                    playback_panel(ui, player, gui);
+                });
+            });
+        });
 }
 
-fn midi_inspector_panel(ctx: &Context, inspector: &mut MidiInspector, gui: &mut GuiState) {
+/// Shrink the window to the playback bar and keep it on top, or restore the full window.
+pub fn set_compact_mode(ctx: &Context, gui: &mut GuiState, compact: bool) {
+    if compact == gui.compact_mode {
+        return;
+    }
+    gui.compact_mode = compact;
+    if compact {
+        gui.full_window_size = Some(ctx.screen_rect().size());
+        apply_window_mode(ctx, gui);
+        ctx.send_viewport_cmd(ViewportCommand::InnerSize(COMPACT_WINDOW_SIZE));
+    } else {
+        apply_window_mode(ctx, gui);
+        let size = gui
+            .full_window_size
+            .take()
+            .unwrap_or(vec2(720., 480.))
+            .max(MIN_WINDOW_SIZE);
+        ctx.send_viewport_cmd(ViewportCommand::InnerSize(size));
+    }
+}
+
+/// Control sizes for touch mode, or the defaults. Context menus open with a long press either way.
+pub fn apply_touch_mode(ctx: &Context, gui: &GuiState) {
+    let touch_mode = gui.touch_mode;
+    ctx.all_styles_mut(|style| {
+        let default = Spacing::default();
+        style.spacing = if touch_mode {
+            Spacing {
+                interact_size: vec2(default.interact_size.x, TOUCH_ROW_H),
+                button_padding: vec2(12., 8.),
+                item_spacing: vec2(12., 8.),
+                icon_width: 24.,
+                icon_width_inner: 12.,
+                slider_rail_height: 12.,
+                scroll: ScrollStyle {
+                    bar_width: 16.,
+                    ..ScrollStyle::solid()
+                },
+                ..default
+            }
+        } else {
+            default
+        };
+    });
+}
+
+/// Window minimum size and level for current mode. Compact mode stays on top of other windows.
+pub fn apply_window_mode(ctx: &Context, gui: &GuiState) {
+    let (min_size, level) = if gui.compact_mode {
+        (COMPACT_MIN_WINDOW_SIZE, WindowLevel::AlwaysOnTop)
+    } else {
+        (MIN_WINDOW_SIZE, WindowLevel::Normal)
+    };
+    ctx.send_viewport_cmd(ViewportCommand::MinInnerSize(min_size));
+    ctx.send_viewport_cmd(ViewportCommand::WindowLevel(level));
+}
+
+fn midi_inspector_panel(
+    ctx: &Context,
+    inspector: &mut MidiInspector,
+    player: &mut Player,
+    gui: &mut GuiState,
+) {
     CentralPanel::default()
         .frame(Frame::central_panel(&ctx.style()).inner_margin(vec2(8., 2.)))
         .show(ctx, |ui| {
             disable_if_modal(ui, gui);
 
-            midi_inspector(ui, inspector, gui);
+            midi_inspector(ui, inspector, player, gui);
         });
 }
 
+/// Inspector in a separate OS window, so the main window stays usable.
+fn midi_inspector_window(
+    ctx: &Context,
+    inspector: &mut MidiInspector,
+    player: &mut Player,
+    gui: &mut GuiState,
+) {
+    ctx.show_viewport_immediate(
+        ViewportId::from_hash_of("midi_inspector"),
+        ViewportBuilder::default()
+            .with_title("MIDI Inspector")
+            .with_inner_size(vec2(640., 480.)),
+        |ctx, _| {
+            midi_inspector_panel(ctx, inspector, player, gui);
+            if ctx.input(|i| i.viewport().close_requested()) {
+                gui.update_flags.close_midi_inspector = true;
+            }
+        },
+    );
+}
+
 fn playlist_panel(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
     TopBottomPanel::top("tab_bar")
         .resizable(false)
@@ -170,14 +500,31 @@ fn playlist_panel(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
         });
 }
 
-/// TODO: Drag files into the window to add them
-/// <https://github.com/sevonj/sfontplayer/issues/7>
-fn handle_dropped_files(ctx: &Context) {
-    ctx.input(|i| {
-        for file in i.raw.dropped_files.clone() {
-            println!("{file:?}");
+/// Add files dragged into the window to the open playlist. On the web there are no paths, only
+/// the contents.
+fn handle_dropped_files(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
+    #[cfg_attr(not(target_arch = "wasm32"), allow(unused_mut))]
+    let mut files = ctx.input(|i| i.raw.dropped_files.clone());
+    // Files opened in the browser arrive read, like dropped ones
+    #[cfg(target_arch = "wasm32")]
+    files.extend(storage::take_picked().into_iter().map(|(name, bytes)| {
+        eframe::egui::DroppedFile {
+            name: name.display().to_string(),
+            bytes: Some(bytes),
+            ..Default::default()
         }
-    });
+    }));
+    for file in files {
+        let playlist = player.get_playlist_mut();
+        let result = match (file.path, file.bytes) {
+            (Some(path), _) => playlist.add_file(path),
+            (None, Some(bytes)) => playlist.add_file_data(file.name.into(), bytes),
+            (None, None) => continue,
+        };
+        if let Err(e) = result {
+            gui.toast_error(e.to_string());
+        }
+    }
 }
 
 /// This will disable the UI if a modal window is open
@@ -185,7 +532,9 @@ fn disable_if_modal(ui: &mut Ui, gui: &GuiState) {
     if gui.show_about_modal
         || gui.show_settings_modal
         || gui.show_shortcut_modal
+        || gui.show_command_palette
         || gui.show_unsaved_quit_modal
+        || gui.pending_confirmation.is_some()
     {
         ui.disable();
     }
@@ -4,29 +4,39 @@
 use std::path::{Path, PathBuf};
 
 use eframe::egui::{Button, ComboBox, Label, TextEdit, Ui, Widget};
-use rfd::FileDialog;
 
 use super::{
     custom_controls::circle_button,
     keyboard_shortcuts::{
-        PLAYLIST, PLAYLIST_CREATE, PLAYLIST_DUPLICATE, PLAYLIST_MOVELEFT, PLAYLIST_MOVERIGHT,
-        PLAYLIST_OPEN, PLAYLIST_REMOVE, PLAYLIST_REOPEN, PLAYLIST_SAVE, PLAYLIST_SAVEAS,
-        PLAYLIST_SWITCHLEFT, PLAYLIST_SWITCHRIGHT,
+        PLAYBACK_GOTO_PLAYING, PLAYBACK_RANDOM, PLAYLIST, PLAYLIST_CREATE, PLAYLIST_DUPLICATE,
+        PLAYLIST_MOVELEFT, PLAYLIST_MOVERIGHT, PLAYLIST_OPEN, PLAYLIST_REMOVE, PLAYLIST_SAVE,
+        PLAYLIST_SAVEAS, PLAYLIST_SWITCHLEFT, PLAYLIST_SWITCHRIGHT, PLAYLIST_UNDO,
     },
-    modals::file_dialogs,
+    modals::{
+        confirm_dialog::{request_action, ConfirmAction},
+        file_dialogs,
+    },
+    storage::{self, FileDialog},
     GuiState,
 };
-use crate::player::{playlist::enums::FileListMode, Player};
+use crate::player::{
+    playlist::{
+        enums::{BundleFormat, FileListMode, SongListFormat},
+        font_meta::FONT_EXTENSIONS,
+    },
+    Player,
+};
 
 // --- Common File Actions --- //
 
 pub fn open_file_dir(ui: &mut Ui, filepath: &Path, gui: &mut GuiState) {
     if ui.button("Go to directory").clicked() {
-        let Some(dir) = filepath.parent() else {
+        // Nearest real directory, so songs inside archives work too
+        let Some(dir) = filepath.ancestors().skip(1).find(|path| path.is_dir()) else {
             gui.toast_error("Failed to get file parent.");
             return;
         };
-        if let Err(e) = open::that(dir) {
+        if let Err(e) = storage::open_path(dir) {
             gui.toast_error(e.to_string());
         }
         ui.close_menu();
@@ -39,25 +49,21 @@ pub fn pick_dir_button(dir: Option<&PathBuf>, ui: &mut Ui) -> Option<PathBuf> {
         .on_hover_text("Select directory")
         .clicked()
     {
-        return FileDialog::new().pick_folder();
+        return storage::pick_folder(FileDialog::new());
     }
     None
 }
 
 pub fn pick_soundfonts_button(ui: &mut Ui) -> Option<Vec<PathBuf>> {
     if circle_button("➕", ui).on_hover_text("Add").clicked() {
-        return FileDialog::new()
-            .add_filter("Soundfonts", &["sf2"])
-            .pick_files();
+        return storage::pick_files(FileDialog::new().add_filter("Soundfonts", &FONT_EXTENSIONS));
     }
     None
 }
 
 pub fn pick_midifiles_button(ui: &mut Ui) -> Option<Vec<PathBuf>> {
     if circle_button("➕", ui).on_hover_text("Add").clicked() {
-        return FileDialog::new()
-            .add_filter("Midi files", &["mid"])
-            .pick_files();
+        return storage::pick_files(FileDialog::new().add_filter("Midi files", &["mid", "zip"]));
     }
     None
 }
@@ -88,7 +94,7 @@ pub fn open_playlist(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
 
 pub fn save_playlist(ui: &mut Ui, player: &mut Player, index: usize, gui: &mut GuiState) {
     ui.add_enabled_ui(
-        player.get_playlists()[index].is_portable() && !player.autosave,
+        player.get_playlists()[index].is_portable() && !player.autosave_portable,
         |ui| {
             let hover_text = get_save_playlist_tooltip(player, index);
             if ui
@@ -108,7 +114,7 @@ pub fn save_playlist(ui: &mut Ui, player: &mut Player, index: usize, gui: &mut G
 
 pub fn save_current_playlist(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
     ui.add_enabled_ui(
-        player.get_playlist().is_portable() && !player.autosave,
+        player.get_playlist().is_portable() && !player.autosave_portable,
         |ui| {
             let hover_text = get_save_playlist_tooltip(player, player.get_playlist_idx());
             if ui
@@ -126,10 +132,33 @@ pub fn save_current_playlist(ui: &mut Ui, player: &mut Player, gui: &mut GuiStat
     );
 }
 
+pub fn revert_playlist(ui: &mut Ui, player: &mut Player, index: usize, gui: &mut GuiState) {
+    if ui
+        .add_enabled(
+            player.get_playlists()[index].has_unsaved_changes(),
+            Button::new("Reset to saved"),
+        )
+        .on_hover_text("Discard unsaved changes")
+        .on_disabled_hover_text("No unsaved changes.")
+        .clicked()
+    {
+        request_action(
+            player,
+            gui,
+            ConfirmAction::RevertPlaylist { playlist: index },
+        );
+        ui.close_menu();
+    }
+}
+
+pub fn revert_current_playlist(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    revert_playlist(ui, player, player.get_playlist_idx(), gui);
+}
+
 fn get_save_playlist_tooltip(player: &Player, index: usize) -> &str {
     if !player.get_playlists()[index].is_portable() {
         "Playlists in app memory are saved automatically."
-    } else if player.autosave {
+    } else if player.autosave_portable {
         "Autosave is enabled."
     } else {
         "Save unsaved changes."
@@ -158,6 +187,22 @@ pub fn save_current_playlist_as(ui: &mut Ui, player: &mut Player, gui: &mut GuiS
     }
 }
 
+/// Save a copy that can be moved to another computer
+pub fn export_playlist_bundle(ui: &mut Ui, player: &Player, index: usize, gui: &mut GuiState) {
+    ui.menu_button("Export with files", |ui| {
+        for format in BundleFormat::ALL {
+            if ui
+                .button(format!("As {format}"))
+                .on_hover_text("Copy the songs and soundfonts along with the playlist")
+                .clicked()
+            {
+                file_dialogs::export_playlist_bundle(player, index, format, gui);
+                ui.close_menu();
+            }
+        }
+    });
+}
+
 pub fn duplicate_playlist(ui: &mut Ui, player: &mut Player, index: usize) {
     if ui
         .add(Button::new("Duplicate"))
@@ -180,6 +225,171 @@ pub fn duplicate_current_playlist(ui: &mut Ui, player: &mut Player) {
     }
 }
 
+pub fn go_to_playing_song(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    if ui
+        .add_enabled(
+            !player.is_empty(),
+            Button::new("Go to playing song")
+                .shortcut_text(ui.ctx().format_shortcut(&PLAYBACK_GOTO_PLAYING)),
+        )
+        .on_hover_text("Show the song that is playing")
+        .clicked()
+    {
+        let _ = player.go_to_playing_song();
+        gui.update_flags.scroll_to_song = true;
+        ui.close_menu();
+    }
+}
+
+pub fn play_random_song(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    if ui
+        .add_enabled(
+            !player.get_playlist().get_songs().is_empty(),
+            Button::new("Play random song")
+                .shortcut_text(ui.ctx().format_shortcut(&PLAYBACK_RANDOM)),
+        )
+        .on_hover_text("Jump to a random song in this playlist")
+        .clicked()
+    {
+        if let Err(e) = player.play_random_song() {
+            gui.toast_error(e.to_string());
+        }
+        ui.close_menu();
+    }
+}
+
+pub fn export_piano_roll(ui: &mut Ui, player: &Player, gui: &mut GuiState) {
+    if ui
+        .add_enabled(
+            gui.export_job.is_none() && player.get_playing_playlist().get_song_idx().is_some(),
+            Button::new("Export piano roll video…"),
+        )
+        .on_hover_text("Render the selected song to an audio file and PNG frames")
+        .clicked()
+    {
+        gui.show_export_dialog = true;
+        ui.close_menu();
+    }
+}
+
+pub fn record_output(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    if player.is_recording() {
+        if ui
+            .button("Stop recording")
+            .on_hover_text("Finish the wav file")
+            .clicked()
+        {
+            player.stop_recording();
+            ui.close_menu();
+        }
+        return;
+    }
+    if ui
+        .button("Record output…")
+        .on_hover_text("Record everything that plays into a wav file")
+        .clicked()
+    {
+        file_dialogs::record_output(player, gui);
+        ui.close_menu();
+    }
+}
+
+pub fn live_input(ui: &mut Ui, gui: &mut GuiState) {
+    if ui
+        .checkbox(&mut gui.show_live_input, "Live MIDI input")
+        .on_hover_text("Play a keyboard through the current soundfont")
+        .clicked()
+    {
+        ui.close_menu();
+    }
+}
+
+pub fn lyrics(ui: &mut Ui, gui: &mut GuiState) {
+    if ui
+        .checkbox(&mut gui.show_lyrics, "Lyrics")
+        .on_hover_text("Sing along to songs that have lyrics")
+        .clicked()
+    {
+        ui.close_menu();
+    }
+}
+
+pub fn play_history(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    ui.add_enabled_ui(!player.get_play_history().is_empty(), |ui| {
+        ui.menu_button("History", |ui| {
+            let mut replay = None;
+            for (index, entry) in player.get_play_history().iter().enumerate() {
+                ui.menu_button(entry.get_song_name(), |ui| {
+                    ui.label(format!("{} ({})", entry.font.get_name(), entry.font_link));
+                    ui.label(format!("Speed {:.2}×", entry.speed));
+                    if ui
+                        .button("Play again like that")
+                        .on_hover_text(
+                            "Play with the same soundfont and speed, whatever the settings now",
+                        )
+                        .clicked()
+                    {
+                        replay = Some(index);
+                    }
+                });
+            }
+            ui.separator();
+            if ui.button("Clear history").clicked() {
+                player.clear_play_history();
+                ui.close_menu();
+            }
+            if let Some(index) = replay {
+                if let Err(e) = player.replay_history_entry(index) {
+                    gui.toast_error(e.to_string());
+                }
+                ui.close_menu();
+            }
+        });
+    });
+}
+
+pub fn play_all_playlists(ui: &mut Ui, player: &mut Player) {
+    let text = if player.get_shuffle() {
+        "Shuffle all playlists"
+    } else {
+        "Play all playlists"
+    };
+    if ui
+        .add(Button::new(text))
+        .on_hover_text("Play songs from every open playlist in one queue")
+        .clicked()
+    {
+        player.start_party_mix();
+        ui.close_menu();
+    }
+}
+
+pub fn auto_dj(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    if let Some(auto_dj) = player.get_auto_dj() {
+        if ui
+            .button("Stop Auto-DJ")
+            .on_hover_text(format!("Playing from {}", auto_dj.get_dir().display()))
+            .clicked()
+        {
+            player.stop_auto_dj();
+            ui.close_menu();
+        }
+        return;
+    }
+    if ui
+        .button("Auto-DJ…")
+        .on_hover_text("Play random songs from a directory, without end")
+        .clicked()
+    {
+        if let Some(dir) = storage::pick_folder(FileDialog::new()) {
+            if let Err(e) = player.start_auto_dj(dir) {
+                gui.toast_error(e.to_string());
+            }
+        }
+        ui.close_menu();
+    }
+}
+
 pub fn close_playlist(ui: &mut Ui, player: &mut Player, index: usize) {
     if ui
         .add(Button::new("Close"))
@@ -191,6 +401,34 @@ pub fn close_playlist(ui: &mut Ui, player: &mut Player, index: usize) {
     }
 }
 
+pub fn close_other_playlists(ui: &mut Ui, player: &mut Player, index: usize) {
+    let count = player.get_playlists().len();
+    if ui
+        .add_enabled(count > 1, Button::new("Close others"))
+        .on_hover_text("Close all other playlists")
+        .clicked()
+    {
+        for i in (0..count).filter(|&i| i != index) {
+            let _ = player.remove_playlist(i);
+        }
+        ui.close_menu();
+    }
+}
+
+pub fn close_playlists_to_right(ui: &mut Ui, player: &mut Player, index: usize) {
+    let count = player.get_playlists().len();
+    if ui
+        .add_enabled(index + 1 < count, Button::new("Close all to the right"))
+        .on_hover_text("Close playlists to the right of this one")
+        .clicked()
+    {
+        for i in index + 1..count {
+            let _ = player.remove_playlist(i);
+        }
+        ui.close_menu();
+    }
+}
+
 pub fn close_current_playlist(ui: &mut Ui, player: &mut Player) {
     if ui
         .add(Button::new("Close").shortcut_text(ui.ctx().format_shortcut(&PLAYLIST_REMOVE)))
@@ -205,18 +443,68 @@ pub fn close_current_playlist(ui: &mut Ui, player: &mut Player) {
 pub fn reopen_playlist(ui: &mut Ui, player: &mut Player) {
     if ui
         .add_enabled(
-            player.has_removed_playlist(),
-            Button::new("Reopen closed").shortcut_text(ui.ctx().format_shortcut(&PLAYLIST_REOPEN)),
+            player.has_removal_history(),
+            Button::new("Undo remove").shortcut_text(ui.ctx().format_shortcut(&PLAYLIST_UNDO)),
         )
-        .on_hover_text("Reopen last closed playlist")
-        .on_disabled_hover_text("Reopen last closed playlist")
+        .on_hover_text("Reopen last closed playlist or cleared list")
+        .on_disabled_hover_text("Reopen last closed playlist or cleared list")
         .clicked()
     {
-        player.reopen_removed_playlist();
+        player.undo_removal();
         ui.close_menu();
     }
 }
 
+pub fn sessions(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    ui.menu_button("Sessions", |ui| {
+        ui.horizontal(|ui| {
+            ui.add(
+                TextEdit::singleline(&mut gui.session_name)
+                    .hint_text("Session name")
+                    .desired_width(128.),
+            );
+            if ui
+                .add_enabled(!gui.session_name.trim().is_empty(), Button::new("Save"))
+                .on_hover_text("Save open playlists, queues and playback position")
+                .clicked()
+            {
+                match player.save_session(&gui.session_name) {
+                    Ok(()) => gui.toast_success(format!("Saved session {}", gui.session_name)),
+                    Err(e) => gui.toast_error(e.to_string()),
+                }
+                ui.close_menu();
+            }
+        });
+
+        let sessions = Player::list_sessions();
+        if sessions.is_empty() {
+            ui.label("No saved sessions");
+            return;
+        }
+        ui.separator();
+        for name in sessions {
+            ui.horizontal(|ui| {
+                if ui.button("🗑").on_hover_text("Delete session").clicked() {
+                    if let Err(e) = Player::delete_session(&name) {
+                        gui.toast_error(e.to_string());
+                    }
+                }
+                if ui
+                    .button(&name)
+                    .on_hover_text("Replace open playlists with this session")
+                    .clicked()
+                {
+                    match player.load_session(&name) {
+                        Ok(()) => gui.session_name = name,
+                        Err(e) => gui.toast_error(e.to_string()),
+                    }
+                    ui.close_menu();
+                }
+            });
+        }
+    });
+}
+
 // --- Playlist Content Actions --- //
 
 pub fn rename_playlist(ui: &mut Ui, player: &mut Player, index: usize) {
@@ -238,8 +526,8 @@ pub fn rename_current_playlist(ui: &mut Ui, player: &mut Player) {
     });
 }
 
-pub fn refresh_playlist(player: &mut Player, index: usize, ui: &mut Ui) {
-    let playlist = &mut player.get_playlists_mut()[index];
+pub fn refresh_playlist(player: &mut Player, index: usize, ui: &mut Ui, gui: &mut GuiState) {
+    let playlist = &player.get_playlists()[index];
     let can_refresh = playlist.get_font_list_mode() != FileListMode::Manual
         || playlist.get_song_list_mode() != FileListMode::Manual;
     ui.add_enabled_ui(can_refresh, |ui| {
@@ -249,14 +537,17 @@ pub fn refresh_playlist(player: &mut Player, index: usize, ui: &mut Ui) {
             .on_disabled_hover_text("This playlist uses manual listing.")
             .clicked()
         {
-            playlist.refresh_font_list();
-            playlist.refresh_song_list();
+            request_action(
+                player,
+                gui,
+                ConfirmAction::RefreshContent { playlist: index },
+            );
             ui.close_menu();
         }
     });
 }
 
-pub fn refresh_current_playlist(player: &mut Player, ui: &mut Ui) {
+pub fn refresh_current_playlist(player: &mut Player, ui: &mut Ui, gui: &mut GuiState) {
     let can_refresh = player.get_playlist().get_font_list_mode() != FileListMode::Manual
         || player.get_playlist().get_song_list_mode() != FileListMode::Manual;
     if ui
@@ -268,21 +559,20 @@ pub fn refresh_current_playlist(player: &mut Player, ui: &mut Ui) {
         .on_disabled_hover_text("This playlist uses manual listing.")
         .clicked()
     {
-        player.get_playlist_mut().refresh_font_list();
-        player.get_playlist_mut().refresh_song_list();
+        let playlist = player.get_playlist_idx();
+        request_action(player, gui, ConfirmAction::RefreshContent { playlist });
         ui.close_menu();
     }
 }
 
-pub fn current_playlist_fonts_action(ui: &mut Ui, player: &mut Player) {
+pub fn current_playlist_fonts_action(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
     ui.menu_button("Soundfonts", |ui| {
         let mut list_mode = player.get_playlist().get_font_list_mode();
         ui.add_enabled_ui(list_mode == FileListMode::Manual, |ui| {
             if ui.button("Add soundfonts").clicked() {
-                if let Some(paths) = FileDialog::new()
-                    .add_filter("Soundfonts", &["sf2"])
-                    .pick_files()
-                {
+                if let Some(paths) = storage::pick_files(
+                    FileDialog::new().add_filter("Soundfonts", &FONT_EXTENSIONS),
+                ) {
                     for path in paths {
                         let _ = player.get_playlist_mut().add_font(path);
                     }
@@ -290,7 +580,8 @@ pub fn current_playlist_fonts_action(ui: &mut Ui, player: &mut Player) {
                 }
             }
             if ui.button("Clear soundfonts").clicked() {
-                player.get_playlist_mut().clear_fonts();
+                let playlist = player.get_playlist_idx();
+                request_action(player, gui, ConfirmAction::ClearFonts { playlist });
                 ui.close_menu();
             }
         });
@@ -307,14 +598,13 @@ pub fn current_playlist_fonts_action(ui: &mut Ui, player: &mut Player) {
         }
     });
 }
-pub fn current_playlist_songs_action(ui: &mut Ui, player: &mut Player) {
+pub fn current_playlist_songs_action(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
     ui.menu_button("Songs", |ui| {
         let mut list_mode = player.get_playlist().get_song_list_mode();
         ui.add_enabled_ui(list_mode == FileListMode::Manual, |ui| {
             if ui.button("Add songs").clicked() {
-                if let Some(paths) = FileDialog::new()
-                    .add_filter("Midi files", &["mid"])
-                    .pick_files()
+                if let Some(paths) =
+                    storage::pick_files(FileDialog::new().add_filter("Midi files", &["mid", "zip"]))
                 {
                     for path in paths {
                         let _ = player.get_playlist_mut().add_song(path);
@@ -323,10 +613,24 @@ pub fn current_playlist_songs_action(ui: &mut Ui, player: &mut Player) {
                 }
             }
             if ui.button("Clear songs").clicked() {
-                player.get_playlist_mut().clear_songs();
+                let playlist = player.get_playlist_idx();
+                request_action(player, gui, ConfirmAction::ClearSongs { playlist });
                 ui.close_menu();
             }
         });
+        export_song_list_menu(ui, player, gui);
+        if ui
+            .button("Check endings")
+            .on_hover_text("Find songs with notes cut off at the end")
+            .clicked()
+        {
+            match player.get_playlist_mut().check_song_endings() {
+                0 => gui.toast_success("No abrupt endings found"),
+                1 => gui.toast_error("1 song ends abruptly"),
+                count => gui.toast_error(format!("{count} songs end abruptly")),
+            }
+            ui.close_menu();
+        }
         ui.label("Content mode");
         let response1 = ui.radio_value(&mut list_mode, FileListMode::Manual, "Manual");
         let response2 = ui.radio_value(&mut list_mode, FileListMode::Directory, "Directory");
@@ -338,6 +642,77 @@ pub fn current_playlist_songs_action(ui: &mut Ui, player: &mut Player) {
         if response1.clicked() || response2.clicked() || response3.clicked() {
             player.get_playlist_mut().set_song_list_mode(list_mode);
         }
+        let mut autoplay = player.get_playlist().get_autoplay_new_files();
+        ui.add_enabled_ui(list_mode != FileListMode::Manual, |ui| {
+            if ui
+                .checkbox(&mut autoplay, "Auto-play new files")
+                .on_hover_text("Play songs that appear in the directory next")
+                .changed()
+            {
+                player.get_playlist_mut().set_autoplay_new_files(autoplay);
+            }
+        });
+    });
+}
+
+pub fn current_playlist_audio_profile_action(ui: &mut Ui, player: &mut Player) {
+    ui.menu_button("Audio profile", |ui| {
+        let index = player.get_playlist_idx();
+        let current = player
+            .get_playlist()
+            .get_audio_profile()
+            .map(ToOwned::to_owned);
+        let mut selected = current.clone();
+        ui.radio_value(&mut selected, None, "None");
+        let names: Vec<String> = player
+            .get_audio_profiles()
+            .iter()
+            .map(|profile| profile.name.clone())
+            .collect();
+        for name in names {
+            let label = name.clone();
+            ui.radio_value(&mut selected, Some(name), label);
+        }
+        if player.get_audio_profiles().is_empty() {
+            ui.weak("Create profiles in settings.");
+        }
+        if selected != current {
+            let _ = player.set_playlist_audio_profile(index, selected);
+            ui.close_menu();
+        }
+    });
+}
+
+/// Copy app, system and playback info for bug reports
+pub fn copy_diagnostics(ui: &mut Ui, player: &Player, gui: &mut GuiState) {
+    if ui
+        .button("Copy diagnostics")
+        .on_hover_text("Copy version, system and playback info for a bug report")
+        .clicked()
+    {
+        ui.ctx().copy_text(player.get_diagnostics());
+        gui.toast_success("Diagnostics copied to clipboard.");
+        ui.close_menu();
+    }
+}
+
+/// Copy or save the song list for sharing
+pub fn export_song_list_menu(ui: &mut Ui, player: &Player, gui: &mut GuiState) {
+    ui.menu_button("Export song list", |ui| {
+        for format in SongListFormat::ALL {
+            if ui.button(format!("Copy as {format}")).clicked() {
+                ui.ctx()
+                    .copy_text(player.get_playlist().export_song_list(format));
+                ui.close_menu();
+            }
+        }
+        ui.separator();
+        for format in SongListFormat::ALL {
+            if ui.button(format!("Save as {format}")).clicked() {
+                file_dialogs::export_song_list(player, format, gui);
+                ui.close_menu();
+            }
+        }
     });
 }
 
@@ -0,0 +1,517 @@
+//! Registry of app actions
+//!
+//! Keyboard shortcuts and the command palette both run these, so anything with a shortcut is
+//! also reachable by name, and the shortcut shows up next to it.
+
+use eframe::egui::{Context, KeyboardShortcut, ViewportCommand};
+
+use super::{
+    apply_touch_mode,
+    keyboard_shortcuts::{
+        GUI_COMPACT, GUI_NOWPLAYING, GUI_PALETTE, GUI_QUIT, GUI_SETTINGS, GUI_SHORTCUTS,
+        GUI_SHOWFONTS, PLAYBACK_GOTO_PLAYING, PLAYBACK_PLAYPAUSE, PLAYBACK_RANDOM, PLAYBACK_REPEAT,
+        PLAYBACK_SHUFFLE, PLAYBACK_SKIP, PLAYBACK_SKIPBACK, PLAYBACK_STARTSTOP, PLAYBACK_VOLDN,
+        PLAYBACK_VOLDN_FINE, PLAYBACK_VOLUP, PLAYBACK_VOLUP_FINE, PLAYLIST, PLAYLIST_CREATE,
+        PLAYLIST_DUPLICATE, PLAYLIST_MOVELEFT, PLAYLIST_MOVERIGHT, PLAYLIST_OPEN, PLAYLIST_RECENT,
+        PLAYLIST_REMOVE, PLAYLIST_REOPEN, PLAYLIST_SAVE, PLAYLIST_SAVEALL, PLAYLIST_SAVEAS,
+        PLAYLIST_SWITCHLEFT, PLAYLIST_SWITCHRIGHT, PLAYLIST_UNDO, VOLUME_FINE_STEP, VOLUME_STEP,
+    },
+    modals::{
+        confirm_dialog::{request_action, ConfirmAction},
+        file_dialogs,
+    },
+    set_compact_mode, GuiState,
+};
+use crate::player::{
+    playlist::enums::{BundleFormat, SongListFormat},
+    Player,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Playback,
+    Playlist,
+    View,
+    Export,
+}
+impl Category {
+    pub const fn get_name(self) -> &'static str {
+        match self {
+            Self::Playback => "Playback",
+            Self::Playlist => "Playlist",
+            Self::View => "View",
+            Self::Export => "Export",
+        }
+    }
+}
+
+pub struct Command {
+    pub category: Category,
+    pub name: &'static str,
+    /// Other words the command can be found with
+    pub keywords: &'static str,
+    pub shortcut: Option<KeyboardShortcut>,
+    pub run: fn(&Context, &mut Player, &mut GuiState),
+}
+
+pub const COMMANDS: &[Command] = &[
+    // --- Playback
+    Command {
+        category: Category::Playback,
+        name: "Play / Pause",
+        keywords: "resume",
+        shortcut: Some(PLAYBACK_PLAYPAUSE),
+        run: |_, player, _| {
+            if !player.is_paused() {
+                player.pause();
+            } else if !player.is_empty() {
+                player.play();
+            }
+        },
+    },
+    Command {
+        category: Category::Playback,
+        name: "Start / Stop",
+        keywords: "",
+        shortcut: Some(PLAYBACK_STARTSTOP),
+        run: |_, player, _| {
+            if player.is_empty() {
+                player.start();
+            } else {
+                player.stop();
+            }
+        },
+    },
+    Command {
+        category: Category::Playback,
+        name: "Skip",
+        keywords: "next song",
+        shortcut: Some(PLAYBACK_SKIP),
+        run: |_, player, _| player.skip(),
+    },
+    Command {
+        category: Category::Playback,
+        name: "Skip back",
+        keywords: "previous song",
+        shortcut: Some(PLAYBACK_SKIPBACK),
+        run: |_, player, _| player.skip_back(),
+    },
+    Command {
+        category: Category::Playback,
+        name: "Toggle shuffle",
+        keywords: "random order",
+        shortcut: Some(PLAYBACK_SHUFFLE),
+        run: |_, player, _| player.toggle_shuffle(),
+    },
+    Command {
+        category: Category::Playback,
+        name: "Cycle repeat",
+        keywords: "loop",
+        shortcut: Some(PLAYBACK_REPEAT),
+        run: |_, player, _| player.cycle_repeat(),
+    },
+    Command {
+        category: Category::Playback,
+        name: "Go to playing song",
+        keywords: "jump scroll current",
+        shortcut: Some(PLAYBACK_GOTO_PLAYING),
+        run: |_, player, gui| {
+            let _ = player.go_to_playing_song();
+            gui.update_flags.scroll_to_song = true;
+        },
+    },
+    Command {
+        category: Category::Playback,
+        name: "Play random song",
+        keywords: "",
+        shortcut: Some(PLAYBACK_RANDOM),
+        run: |_, player, gui| {
+            if let Err(e) = player.play_random_song() {
+                gui.toast_error(e.to_string());
+            }
+        },
+    },
+    Command {
+        category: Category::Playback,
+        name: "Increase volume",
+        keywords: "louder",
+        shortcut: Some(PLAYBACK_VOLUP),
+        run: |_, player, _| player.set_volume(player.get_volume() + VOLUME_STEP),
+    },
+    Command {
+        category: Category::Playback,
+        name: "Decrease volume",
+        keywords: "quieter",
+        shortcut: Some(PLAYBACK_VOLDN),
+        run: |_, player, _| player.set_volume(player.get_volume() - VOLUME_STEP),
+    },
+    Command {
+        category: Category::Playback,
+        name: "Increase volume by 1%",
+        keywords: "louder fine",
+        shortcut: Some(PLAYBACK_VOLUP_FINE),
+        run: |_, player, _| player.set_volume(player.get_volume() + VOLUME_FINE_STEP),
+    },
+    Command {
+        category: Category::Playback,
+        name: "Decrease volume by 1%",
+        keywords: "quieter fine",
+        shortcut: Some(PLAYBACK_VOLDN_FINE),
+        run: |_, player, _| player.set_volume(player.get_volume() - VOLUME_FINE_STEP),
+    },
+    // --- Playlist
+    Command {
+        category: Category::Playlist,
+        name: "New playlist",
+        keywords: "create tab",
+        shortcut: Some(PLAYLIST_CREATE),
+        run: |_, player, _| {
+            player.new_playlist();
+            let _ = player.switch_to_playlist(player.get_playlists().len() - 1);
+        },
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Open playlist",
+        keywords: "file import pls vpl m3u",
+        shortcut: Some(PLAYLIST_OPEN),
+        run: |_, player, gui| file_dialogs::open_playlist(player, gui),
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Save playlist",
+        keywords: "file",
+        shortcut: Some(PLAYLIST_SAVE),
+        run: |_, player, gui| {
+            if player.autosave_portable {
+                return;
+            }
+            if let Err(e) = player.save_portable_playlist(player.get_playlist_idx()) {
+                gui.toast_error(e.to_string());
+            }
+        },
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Save playlist as",
+        keywords: "file copy",
+        shortcut: Some(PLAYLIST_SAVEAS),
+        run: |_, player, gui| {
+            file_dialogs::save_playlist_as(player, player.get_playlist_idx(), gui);
+        },
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Save all playlists",
+        keywords: "files",
+        shortcut: Some(PLAYLIST_SAVEALL),
+        run: |_, player, gui| {
+            if let Err(e) = player.save_all_portable_playlists() {
+                gui.toast_error(e.to_string());
+            }
+        },
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Reset playlist to saved",
+        keywords: "revert discard unsaved changes reload",
+        shortcut: None,
+        run: |_, player, gui| {
+            let playlist = player.get_playlist_idx();
+            if player.get_playlist().has_unsaved_changes() {
+                request_action(player, gui, ConfirmAction::RevertPlaylist { playlist });
+            }
+        },
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Duplicate playlist",
+        keywords: "copy",
+        shortcut: Some(PLAYLIST_DUPLICATE),
+        run: |_, player, _| {
+            let _ = player.duplicate_playlist(player.get_playlist_idx());
+        },
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Refresh content",
+        keywords: "reload rescan directory",
+        shortcut: Some(PLAYLIST),
+        run: |_, player, gui| {
+            let playlist = player.get_playlist_idx();
+            request_action(player, gui, ConfirmAction::RefreshContent { playlist });
+        },
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Clear songs",
+        keywords: "remove all",
+        shortcut: None,
+        run: |_, player, gui| {
+            let playlist = player.get_playlist_idx();
+            request_action(player, gui, ConfirmAction::ClearSongs { playlist });
+        },
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Clear soundfonts",
+        keywords: "remove all fonts",
+        shortcut: None,
+        run: |_, player, gui| {
+            let playlist = player.get_playlist_idx();
+            request_action(player, gui, ConfirmAction::ClearFonts { playlist });
+        },
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Close playlist",
+        keywords: "remove tab",
+        shortcut: Some(PLAYLIST_REMOVE),
+        run: |_, player, _| {
+            let _ = player.remove_playlist(player.get_playlist_idx());
+        },
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Reopen closed playlist",
+        keywords: "restore tab",
+        shortcut: Some(PLAYLIST_REOPEN),
+        run: |_, player, _| player.undo_removal(),
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Undo removal",
+        keywords: "restore clear close",
+        shortcut: Some(PLAYLIST_UNDO),
+        run: |_, player, _| player.undo_removal(),
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Switch to previous playlist",
+        keywords: "left tab",
+        shortcut: Some(PLAYLIST_SWITCHLEFT),
+        run: |_, player, gui| {
+            if let Err(e) = player.switch_playlist_left() {
+                gui.toast_error(e.to_string());
+            }
+        },
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Switch to next playlist",
+        keywords: "right tab",
+        shortcut: Some(PLAYLIST_SWITCHRIGHT),
+        run: |_, player, gui| {
+            if let Err(e) = player.switch_playlist_right() {
+                gui.toast_error(e.to_string());
+            }
+        },
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Switch to recent playlist",
+        keywords: "tab last",
+        shortcut: Some(PLAYLIST_RECENT),
+        run: |_, player, _| player.cycle_recent_playlists(),
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Move playlist left",
+        keywords: "tab rearrange",
+        shortcut: Some(PLAYLIST_MOVELEFT),
+        run: |_, player, gui| {
+            if let Err(e) = player.move_playlist_left() {
+                gui.toast_error(e.to_string());
+            }
+        },
+    },
+    Command {
+        category: Category::Playlist,
+        name: "Move playlist right",
+        keywords: "tab rearrange",
+        shortcut: Some(PLAYLIST_MOVERIGHT),
+        run: |_, player, gui| {
+            if let Err(e) = player.move_playlist_right() {
+                gui.toast_error(e.to_string());
+            }
+        },
+    },
+    // --- View
+    Command {
+        category: Category::View,
+        name: "Command palette",
+        keywords: "search actions",
+        shortcut: Some(GUI_PALETTE),
+        run: |_, _, gui| gui.open_command_palette(),
+    },
+    Command {
+        category: Category::View,
+        name: "Settings",
+        keywords: "preferences options",
+        shortcut: Some(GUI_SETTINGS),
+        run: |_, _, gui| gui.show_settings_modal = true,
+    },
+    Command {
+        category: Category::View,
+        name: "Keyboard shortcuts",
+        keywords: "keys help",
+        shortcut: Some(GUI_SHORTCUTS),
+        run: |_, _, gui| gui.show_shortcut_modal = true,
+    },
+    Command {
+        category: Category::View,
+        name: "About",
+        keywords: "version license",
+        shortcut: None,
+        run: |_, _, gui| gui.show_about_modal = true,
+    },
+    Command {
+        category: Category::View,
+        name: "Toggle soundfont library",
+        keywords: "sidebar fonts panel",
+        shortcut: Some(GUI_SHOWFONTS),
+        run: |_, _, gui| gui.show_font_library = !gui.show_font_library,
+    },
+    Command {
+        category: Category::View,
+        name: "Toggle problems panel",
+        keywords: "errors missing broken invalid files",
+        shortcut: None,
+        run: |_, _, gui| gui.show_problems = !gui.show_problems,
+    },
+    Command {
+        category: Category::View,
+        name: "Toggle live MIDI input panel",
+        keywords: "keyboard port audition soundfont play",
+        shortcut: None,
+        run: |_, _, gui| gui.show_live_input = !gui.show_live_input,
+    },
+    Command {
+        category: Category::View,
+        name: "Toggle lyrics panel",
+        keywords: "karaoke sing text words",
+        shortcut: None,
+        run: |_, _, gui| gui.show_lyrics = !gui.show_lyrics,
+    },
+    Command {
+        category: Category::View,
+        name: "Toggle now playing",
+        keywords: "fullscreen visualizer",
+        shortcut: Some(GUI_NOWPLAYING),
+        run: |_, _, gui| gui.show_now_playing = !gui.show_now_playing,
+    },
+    Command {
+        category: Category::View,
+        name: "Toggle compact mode",
+        keywords: "mini player small window",
+        shortcut: Some(GUI_COMPACT),
+        run: |ctx, _, gui| set_compact_mode(ctx, gui, !gui.compact_mode),
+    },
+    Command {
+        category: Category::View,
+        name: "Toggle touch mode",
+        keywords: "big controls tablet",
+        shortcut: None,
+        run: |ctx, _, gui| {
+            gui.touch_mode = !gui.touch_mode;
+            apply_touch_mode(ctx, gui);
+        },
+    },
+    Command {
+        category: Category::View,
+        name: "Quit",
+        keywords: "exit close",
+        shortcut: Some(GUI_QUIT),
+        run: |ctx, _, _| ctx.send_viewport_cmd(ViewportCommand::Close),
+    },
+    // --- Export
+    Command {
+        category: Category::Export,
+        name: "Record output",
+        keywords: "render wav audio",
+        shortcut: None,
+        run: |_, player, gui| {
+            if !player.is_recording() {
+                file_dialogs::record_output(player, gui);
+            }
+        },
+    },
+    Command {
+        category: Category::Export,
+        name: "Stop recording",
+        keywords: "wav audio",
+        shortcut: None,
+        run: |_, player, _| player.stop_recording(),
+    },
+    Command {
+        category: Category::Export,
+        name: "Export piano roll video",
+        keywords: "render png frames wav flac ogg",
+        shortcut: None,
+        run: |_, player, gui| {
+            if gui.export_job.is_some() {
+                gui.toast_error("An export is already running.");
+            } else if player.get_playing_playlist().get_song_idx().is_none() {
+                gui.toast_error("Select a song first.");
+            } else {
+                gui.show_export_dialog = true;
+            }
+        },
+    },
+    Command {
+        category: Category::Export,
+        name: "Export playlist with files",
+        keywords: "bundle portable copy songs soundfonts folder",
+        shortcut: None,
+        run: |_, player, gui| {
+            file_dialogs::export_playlist_bundle(
+                player,
+                player.get_playlist_idx(),
+                BundleFormat::Folder,
+                gui,
+            );
+        },
+    },
+    Command {
+        category: Category::Export,
+        name: "Export playlist with files as zip",
+        keywords: "bundle portable copy songs soundfonts archive",
+        shortcut: None,
+        run: |_, player, gui| {
+            file_dialogs::export_playlist_bundle(
+                player,
+                player.get_playlist_idx(),
+                BundleFormat::Zip,
+                gui,
+            );
+        },
+    },
+    Command {
+        category: Category::Export,
+        name: "Export song list as text",
+        keywords: "set list txt",
+        shortcut: None,
+        run: |_, player, gui| file_dialogs::export_song_list(player, SongListFormat::Text, gui),
+    },
+    Command {
+        category: Category::Export,
+        name: "Export song list as Markdown",
+        keywords: "set list md table",
+        shortcut: None,
+        run: |_, player, gui| {
+            file_dialogs::export_song_list(player, SongListFormat::Markdown, gui);
+        },
+    },
+    Command {
+        category: Category::Export,
+        name: "Export song list as CSV",
+        keywords: "set list spreadsheet",
+        shortcut: None,
+        run: |_, player, gui| file_dialogs::export_song_list(player, SongListFormat::Csv, gui),
+    },
+];
+
+/// Command by its name, as stored in settings
+pub fn find_command(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
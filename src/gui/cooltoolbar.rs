@@ -2,7 +2,10 @@ use eframe::egui::{Align, Button, Layout, RichText, Ui, ViewportCommand};
 
 use super::{
     actions,
-    keyboard_shortcuts::{GUI_QUIT, GUI_SETTINGS, GUI_SHORTCUTS},
+    keyboard_shortcuts::{
+        GUI_COMPACT, GUI_NOWPLAYING, GUI_PALETTE, GUI_QUIT, GUI_SETTINGS, GUI_SHORTCUTS,
+    },
+    set_compact_mode,
 };
 use crate::{player::Player, GuiState};
 
@@ -11,12 +14,15 @@ pub fn toolbar(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
     ui.horizontal(|ui| {
         file_menu(ui, player, gui);
 
+        playback_menu(ui, player, gui);
+
         options_menu(ui, gui);
 
-        help_menu(ui, gui);
+        help_menu(ui, player, gui);
 
         ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
             sidebar_toggle(ui, gui);
+            problems_toggle(ui, player, gui);
         });
     });
 }
@@ -26,17 +32,25 @@ fn file_menu(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
         actions::new_playlist(ui, player);
         actions::open_playlist(ui, player, gui);
         actions::save_current_playlist(ui, player, gui);
+        actions::revert_current_playlist(ui, player, gui);
         actions::save_current_playlist_as(ui, player, gui);
+        actions::export_playlist_bundle(ui, player, player.get_playlist_idx(), gui);
         actions::duplicate_current_playlist(ui, player);
         actions::close_current_playlist(ui, player);
         actions::reopen_playlist(ui, player);
+        actions::sessions(ui, player, gui);
 
         ui.separator();
 
         actions::rename_current_playlist(ui, player);
-        actions::refresh_current_playlist(player, ui);
-        actions::current_playlist_fonts_action(ui, player);
-        actions::current_playlist_songs_action(ui, player);
+        actions::refresh_current_playlist(player, ui, gui);
+        actions::current_playlist_fonts_action(ui, player, gui);
+        actions::current_playlist_songs_action(ui, player, gui);
+        actions::current_playlist_audio_profile_action(ui, player);
+
+        ui.separator();
+
+        actions::export_piano_roll(ui, player, gui);
 
         ui.separator();
 
@@ -56,8 +70,35 @@ fn file_menu(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
     });
 }
 
+fn playback_menu(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    ui.menu_button("Playback", |ui| {
+        actions::go_to_playing_song(ui, player, gui);
+        actions::play_random_song(ui, player, gui);
+        actions::play_history(ui, player, gui);
+        actions::play_all_playlists(ui, player);
+        actions::auto_dj(ui, player, gui);
+
+        ui.separator();
+
+        actions::record_output(ui, player, gui);
+        actions::live_input(ui, gui);
+        actions::lyrics(ui, gui);
+    });
+}
+
 fn options_menu(ui: &mut Ui, gui: &mut GuiState) {
     ui.menu_button("Options", |ui| {
+        if ui
+            .add(
+                Button::new("Command palette")
+                    .shortcut_text(ui.ctx().format_shortcut(&GUI_PALETTE)),
+            )
+            .on_hover_text("Search and run any action")
+            .clicked()
+        {
+            gui.open_command_palette();
+            ui.close_menu();
+        }
         if ui
             .add(Button::new("Settings").shortcut_text(ui.ctx().format_shortcut(&GUI_SETTINGS)))
             .clicked()
@@ -65,11 +106,30 @@ fn options_menu(ui: &mut Ui, gui: &mut GuiState) {
             gui.show_settings_modal = true;
             ui.close_menu();
         }
+        if ui
+            .add(
+                Button::new("Now Playing screen")
+                    .shortcut_text(ui.ctx().format_shortcut(&GUI_NOWPLAYING)),
+            )
+            .clicked()
+        {
+            gui.show_now_playing = true;
+            ui.close_menu();
+        }
+        if ui
+            .add(Button::new("Compact mode").shortcut_text(ui.ctx().format_shortcut(&GUI_COMPACT)))
+            .on_hover_text("Shrink to the playback bar and stay on top")
+            .clicked()
+        {
+            set_compact_mode(ui.ctx(), gui, true);
+            ui.close_menu();
+        }
     });
 }
 
-fn help_menu(ui: &mut Ui, gui: &mut GuiState) {
+fn help_menu(ui: &mut Ui, player: &Player, gui: &mut GuiState) {
     ui.menu_button("Help", |ui| {
+        actions::copy_diagnostics(ui, player, gui);
         if ui.button("About").clicked() {
             gui.show_about_modal = true;
             ui.close_menu();
@@ -105,3 +165,21 @@ fn sidebar_toggle(ui: &mut Ui, gui: &mut GuiState) {
         gui.show_font_library = !gui.show_font_library;
     };
 }
+
+/// Count of files with errors. Hidden when there are none.
+fn problems_toggle(ui: &mut Ui, player: &Player, gui: &mut GuiState) {
+    let count = player.get_problems().len();
+    if count == 0 {
+        return;
+    }
+    if ui
+        .add(
+            Button::new(RichText::new(format!("⚠ {count}")).color(ui.visuals().warn_fg_color))
+                .frame(false),
+        )
+        .on_hover_text("Problems")
+        .clicked()
+    {
+        gui.show_problems = !gui.show_problems;
+    }
+}
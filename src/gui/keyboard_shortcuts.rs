@@ -1,6 +1,11 @@
-use eframe::egui::{Context, Key, KeyboardShortcut, Modifiers, ViewportCommand};
+use std::{cmp::Reverse, time::Duration};
 
-use super::{modals::file_dialogs, GuiState};
+use eframe::egui::{Context, Event, Key, KeyboardShortcut, Modifiers};
+
+use super::{
+    commands::{Command, COMMANDS},
+    GuiState,
+};
 use crate::player::Player;
 
 const CTRL_SHIFT: Modifiers = Modifiers::CTRL.plus(Modifiers::SHIFT);
@@ -14,6 +19,19 @@ pub const PLAYBACK_REPEAT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::N
 pub const PLAYBACK_SHUFFLE: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::S);
 pub const PLAYBACK_VOLUP: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::ArrowUp);
 pub const PLAYBACK_VOLDN: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::ArrowDown);
+pub const PLAYBACK_GOTO_PLAYING: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::J);
+pub const PLAYBACK_RANDOM: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::R);
+pub const PLAYBACK_VOLUP_FINE: KeyboardShortcut = KeyboardShortcut::new(CTRL_SHIFT, Key::ArrowUp);
+pub const PLAYBACK_VOLDN_FINE: KeyboardShortcut = KeyboardShortcut::new(CTRL_SHIFT, Key::ArrowDown);
+
+/// Volume percentage per step
+pub const VOLUME_STEP: f32 = 5.;
+/// Volume percentage per fine step
+pub const VOLUME_FINE_STEP: f32 = 1.;
+/// Arrow key step of a focused seek bar
+pub const SEEK_STEP: Duration = Duration::from_secs(5);
+/// Shift + arrow or page key step of a focused seek bar
+pub const SEEK_LARGE_STEP: Duration = Duration::from_secs(30);
 
 pub const PLAYLIST_SWITCHLEFT: KeyboardShortcut =
     KeyboardShortcut::new(Modifiers::ALT, Key::ArrowLeft);
@@ -30,145 +48,82 @@ pub const PLAYLIST_SAVEAS: KeyboardShortcut = KeyboardShortcut::new(CTRL_SHIFT,
 pub const PLAYLIST_SAVEALL: KeyboardShortcut = KeyboardShortcut::new(CTRL_ALT, Key::S);
 pub const PLAYLIST_DUPLICATE: KeyboardShortcut = KeyboardShortcut::new(CTRL_SHIFT, Key::D);
 pub const PLAYLIST_REOPEN: KeyboardShortcut = KeyboardShortcut::new(CTRL_SHIFT, Key::T);
+pub const PLAYLIST_UNDO: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::Z);
+pub const PLAYLIST_RECENT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::Tab);
+/// Arrives as a paste event, only listed for the shortcut modal
+pub const PLAYLIST_PASTE: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::V);
 
 pub const GUI_QUIT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::Q);
 pub const GUI_SHOWFONTS: KeyboardShortcut = KeyboardShortcut::new(Modifiers::ALT, Key::S);
 pub const GUI_SETTINGS: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::Comma);
 pub const GUI_SHORTCUTS: KeyboardShortcut =
     KeyboardShortcut::new(Modifiers::CTRL, Key::Questionmark);
+pub const GUI_NOWPLAYING: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::F11);
+pub const GUI_COMPACT: KeyboardShortcut = KeyboardShortcut::new(CTRL_SHIFT, Key::M);
+pub const GUI_PALETTE: KeyboardShortcut = KeyboardShortcut::new(CTRL_SHIFT, Key::P);
 
 /// Check and act on shortcuts
 pub fn consume_shortcuts(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
+    if !ctx.input(|input| input.modifiers.ctrl) {
+        player.end_recent_cycle();
+    }
     if ctx.wants_keyboard_input() {
         return;
     }
-    consume_2_modifiers(ctx, player, gui);
-    consume_1_modifier(ctx, player, gui);
-    consume_no_modifiers(ctx, player, gui);
+    consume_commands(ctx, player, gui);
+    consume_paste(ctx, player, gui);
+    if gui.show_now_playing
+        && ctx.input_mut(|input| input.consume_key(Modifiers::NONE, Key::Escape))
+    {
+        gui.show_now_playing = false;
+    }
 }
 
-fn consume_2_modifiers(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
-    ctx.input_mut(|input| {
-        if input.consume_shortcut(&PLAYLIST_MOVELEFT) {
-            if let Err(e) = player.move_playlist_left() {
-                gui.toast_error(e.to_string());
-            }
-        }
-        if input.consume_shortcut(&PLAYLIST_MOVERIGHT) {
-            if let Err(e) = player.move_playlist_right() {
-                gui.toast_error(e.to_string());
-            }
-        }
-        if input.consume_shortcut(&PLAYLIST_SAVEAS) {
-            file_dialogs::save_playlist_as(player, player.get_playlist_idx(), gui);
-        }
-        if input.consume_shortcut(&PLAYLIST_SAVEALL) {
-            if let Err(e) = player.save_all_portable_playlists() {
-                gui.toast_error(e.to_string());
-            }
-        }
-        if input.consume_shortcut(&PLAYLIST_DUPLICATE) {
-            let _ = player.duplicate_playlist(player.get_playlist_idx());
-        }
-        if input.consume_shortcut(&PLAYLIST_REOPEN) {
-            player.reopen_removed_playlist();
-        }
+/// Run the commands whose shortcuts were pressed
+fn consume_commands(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
+    // Shortcuts with more modifiers go first, so Ctrl+S doesn't eat Ctrl+Shift+S.
+    let mut commands: Vec<(&Command, KeyboardShortcut)> = COMMANDS
+        .iter()
+        .filter_map(|command| Some((command, command.shortcut?)))
+        .collect();
+    commands.sort_by_key(|(_, shortcut)| {
+        let modifiers = shortcut.modifiers;
+        Reverse(u8::from(modifiers.ctrl) + u8::from(modifiers.shift) + u8::from(modifiers.alt))
     });
-}
-
-fn consume_1_modifier(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
-    let mut quit = false;
 
+    let mut pressed = vec![];
     ctx.input_mut(|input| {
-        if input.consume_shortcut(&PLAYBACK_STARTSTOP) {
-            if player.is_empty() {
-                player.start();
-            } else {
-                player.stop();
-            }
-        }
-        if input.consume_shortcut(&PLAYBACK_VOLUP) {
-            let volume = player.get_volume();
-            player.set_volume(volume + 5.);
-        }
-        if input.consume_shortcut(&PLAYBACK_VOLDN) {
-            let volume = player.get_volume();
-            player.set_volume(volume - 5.);
-        }
-
-        if input.consume_shortcut(&PLAYLIST_SWITCHLEFT) {
-            if let Err(e) = player.switch_playlist_left() {
-                gui.toast_error(e.to_string());
-            }
-        }
-        if input.consume_shortcut(&PLAYLIST_SWITCHRIGHT) {
-            if let Err(e) = player.switch_playlist_right() {
-                gui.toast_error(e.to_string());
-            }
-        }
-        if input.consume_shortcut(&PLAYLIST_CREATE) {
-            player.new_playlist();
-            let _ = player.switch_to_playlist(player.get_playlists().len() - 1);
-        }
-        if input.consume_shortcut(&PLAYLIST_REMOVE) {
-            let _ = player.remove_playlist(player.get_playlist_idx());
-        }
-        if input.consume_shortcut(&PLAYLIST_OPEN) {
-            file_dialogs::open_playlist(player, gui);
-        }
-        if input.consume_shortcut(&PLAYLIST_SAVE) {
-            if player.autosave {
-                return;
+        for (command, shortcut) in commands {
+            if input.consume_shortcut(&shortcut) {
+                pressed.push(command);
             }
-            if let Err(e) = player.save_portable_playlist(player.get_playlist_idx()) {
-                gui.toast_error(e.to_string());
-            }
-        }
-
-        if input.consume_shortcut(&GUI_QUIT) {
-            quit = true;
-        }
-        if input.consume_shortcut(&GUI_SHOWFONTS) {
-            gui.show_font_library = !gui.show_font_library;
-        }
-        if input.consume_shortcut(&GUI_SETTINGS) {
-            gui.show_settings_modal = true;
-        }
-        if input.consume_shortcut(&GUI_SHORTCUTS) {
-            gui.show_shortcut_modal = true;
         }
     });
-
-    // This is down here because sending the command from the input closure hangs the program.
-    if quit {
-        ctx.send_viewport_cmd(ViewportCommand::Close);
+    // Commands run outside the input closure: sending viewport commands from it hangs the
+    // program.
+    for command in pressed {
+        (command.run)(ctx, player, gui);
     }
 }
 
-fn consume_no_modifiers(ctx: &Context, player: &mut Player, _gui: &GuiState) {
+/// Pasted file paths are added to the song list
+fn consume_paste(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
+    let mut pasted = vec![];
     ctx.input_mut(|input| {
-        if input.consume_shortcut(&PLAYBACK_PLAYPAUSE) {
-            if !player.is_paused() {
-                player.pause();
-            } else if !player.is_empty() {
-                player.play();
-            }
-        }
-        if input.consume_shortcut(&PLAYBACK_SKIP) {
-            player.skip();
-        }
-        if input.consume_shortcut(&PLAYBACK_SKIPBACK) {
-            player.skip_back();
-        }
-        if input.consume_shortcut(&PLAYBACK_SHUFFLE) {
-            player.toggle_shuffle();
-        }
-        if input.consume_shortcut(&PLAYBACK_REPEAT) {
-            player.cycle_repeat();
-        }
-        if input.consume_shortcut(&PLAYLIST) {
-            player.get_playlist_mut().refresh_font_list();
-            player.get_playlist_mut().refresh_song_list();
-        }
+        input.events.retain(|event| {
+            let Event::Paste(text) = event else {
+                return true;
+            };
+            pasted.push(text.clone());
+            false
+        });
     });
+    for text in pasted {
+        match player.get_playlist_mut().paste_songs(&text) {
+            Ok(0) => gui.toast_error("Pasted text has no midi files."),
+            Ok(1) => gui.toast_success("Added 1 song."),
+            Ok(count) => gui.toast_success(format!("Added {count} songs.")),
+            Err(e) => gui.toast_error(e.to_string()),
+        }
+    }
 }
@@ -0,0 +1,51 @@
+use eframe::egui::{Align, Button, Layout, RichText, Ui};
+
+use super::custom_controls::subheading;
+use crate::{
+    player::{
+        audio::midi_in::{HAS_VIRTUAL_PORT, INPUT_PORT_NAME},
+        playlist::font_meta::FontMeta,
+        Player,
+    },
+    GuiState,
+};
+
+/// Play a keyboard or another app through the MIDI input port, to audition soundfonts
+pub fn live_input_panel(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    ui.horizontal(|ui| {
+        ui.add(subheading("Live MIDI input"));
+        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+            if ui.add(Button::new("❌").frame(false)).clicked() {
+                gui.show_live_input = false;
+            }
+        });
+    });
+    ui.separator();
+
+    ui.horizontal(|ui| {
+        let mut listening = player.is_live_input();
+        if ui
+            .toggle_value(&mut listening, "🎹 Listen")
+            .on_hover_text("Open the input port and play what comes in")
+            .changed()
+        {
+            if let Err(e) = player.set_live_input(listening) {
+                gui.toast_error(format!("Couldn't start live input: {e}"));
+            }
+        }
+        if player.is_live_input() {
+            let font = player
+                .get_live_font()
+                .map_or_else(|| "No soundfont".into(), FontMeta::get_name);
+            ui.label(RichText::new(font).strong());
+        }
+    });
+    let connect = if HAS_VIRTUAL_PORT {
+        format!("Connect a keyboard or an app to the \"{INPUT_PORT_NAME}\" MIDI port.")
+    } else {
+        "Keyboards that are plugged in when you start listening are connected.".into()
+    };
+    ui.label(format!(
+        "{connect} Live input plays with the soundfont of the playing song, or the selected one."
+    ));
+}
@@ -0,0 +1,73 @@
+use eframe::egui::{
+    text::LayoutJob, Align, Button, Color32, FontId, Label, Layout, RichText, TextFormat, Ui,
+};
+
+use super::custom_controls::subheading;
+use crate::{player::audio::lyrics::Syllable, player::Player, GuiState};
+
+const LINE_SIZE: f32 = 20.;
+
+/// Karaoke view: the line being sung, with the current syllable highlighted, and the next line.
+pub fn lyrics_panel(ui: &mut Ui, player: &Player, gui: &mut GuiState) {
+    ui.horizontal(|ui| {
+        ui.add(subheading("Lyrics"));
+        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+            if ui.add(Button::new("❌").frame(false)).clicked() {
+                gui.show_lyrics = false;
+            }
+        });
+    });
+    ui.separator();
+
+    let lines = player.get_lyrics().get_lines();
+    if lines.is_empty() {
+        ui.label(RichText::new("No lyrics in this song").weak());
+        return;
+    }
+    // Before the first syllable, the first line is shown unsung.
+    let (line, syllable) = player
+        .get_lyrics()
+        .find(player.get_playback_position())
+        .map_or((0, None), |(line, syllable)| (line, Some(syllable)));
+
+    ui.vertical_centered(|ui| {
+        ui.add(Label::new(sung_line(ui, &lines[line], syllable)).wrap());
+        let next = lines
+            .get(line + 1)
+            .map_or(String::new(), |next| line_text(next));
+        ui.label(RichText::new(next).weak());
+    });
+}
+
+/// Sung syllables in strong color, the current one highlighted, and the rest weak.
+fn sung_line(ui: &Ui, line: &[Syllable], current: Option<usize>) -> LayoutJob {
+    let visuals = ui.visuals();
+    let mut job = LayoutJob::default();
+    for (index, syllable) in line.iter().enumerate() {
+        let color = match current {
+            Some(current) if index == current => visuals.selection.stroke.color,
+            Some(current) if index < current => visuals.strong_text_color(),
+            _ => visuals.weak_text_color(),
+        };
+        let background = if current == Some(index) {
+            visuals.selection.bg_fill
+        } else {
+            Color32::TRANSPARENT
+        };
+        job.append(
+            &syllable.text,
+            0.,
+            TextFormat {
+                font_id: FontId::proportional(LINE_SIZE),
+                color,
+                background,
+                ..Default::default()
+            },
+        );
+    }
+    job
+}
+
+fn line_text(line: &[Syllable]) -> String {
+    line.iter().map(|syllable| syllable.text.as_str()).collect()
+}
@@ -1,40 +1,176 @@
-use super::{custom_controls::collapse_button, GuiState};
-use crate::midi_inspector::{MidiInspector, MidiInspectorTrack};
-use eframe::egui::{Color32, Frame, Label, RichText, ScrollArea, Style, TextWrapMode, Ui};
+use super::{
+    custom_controls::collapse_button,
+    storage::{self, FileDialog},
+    GuiState,
+};
+use crate::{
+    midi_inspector::{
+        compare::Comparison, note_name, AutomationSource, MidiInspector, MidiInspectorTrack,
+        TrackStats,
+    },
+    player::{
+        general_midi,
+        program_lock::{CHANNEL_COUNT, DRUM_CHANNEL},
+        Player,
+    },
+};
+use eframe::egui::{
+    pos2, vec2, Align2, CollapsingHeader, Color32, ComboBox, FontId, Frame, Label, Pos2, RichText,
+    ScrollArea, Sense, Shape, Stroke, Style, TextWrapMode, Ui,
+};
 use egui_extras::{Column, TableBuilder};
-use midi_msg::{MidiMsg, Track};
+use midi_msg::{ChannelVoiceMsg, MidiMsg, Track};
 use std::path::Path;
 
 const TRACKHEAD_WIDTH: f32 = 128.;
+const AUTOMATION_GRAPH_HEIGHT: f32 = 120.;
 
-pub fn midi_inspector(ui: &mut Ui, inspector: &mut MidiInspector, gui: &mut GuiState) {
-    inspector_toolbar(ui, gui);
+pub fn midi_inspector(
+    ui: &mut Ui,
+    inspector: &mut MidiInspector,
+    player: &mut Player,
+    gui: &mut GuiState,
+) {
+    inspector_toolbar(ui, inspector, player, gui);
     ui.separator();
 
     ScrollArea::vertical().show(ui, |ui| {
         ui.set_width(ui.available_width());
 
         header_panel(ui, &inspector.header, &inspector.filepath);
+        if let Some(comparison) = &mut inspector.comparison {
+            comparison_panel(ui, &mut inspector.tracks, comparison);
+            return;
+        }
+        automation_panel(ui, inspector);
         for i in 0..inspector.tracks.len() {
-            let track = &mut inspector.tracks[i];
             ui.separator();
-            ui.push_id(format!("track_ui_{i}"), |ui| match &track.track {
-                Track::Midi(..) => midi_track_panel(ui, i, track),
-                Track::AlienChunk(..) => nonstandard_track_panel(ui, i, track),
-            });
+            track_panel(ui, i, &mut inspector.tracks[i], None);
         }
     });
 }
 
-fn inspector_toolbar(ui: &mut Ui, gui: &mut GuiState) {
+fn track_panel(ui: &mut Ui, i: usize, track: &mut MidiInspectorTrack, changed: Option<&[bool]>) {
+    ui.push_id(format!("track_ui_{i}"), |ui| match &track.track {
+        Track::Midi(..) => midi_track_panel(ui, i, track, changed),
+        Track::AlienChunk(..) => nonstandard_track_panel(ui, i, track),
+    });
+}
+
+/// Inspected file on the left, compared file on the right. Changed events are highlighted.
+fn comparison_panel(ui: &mut Ui, tracks: &mut [MidiInspectorTrack], comparison: &mut Comparison) {
+    ui.label(format!("Compared with: {}", comparison.filepath.display()));
+    match comparison.count_changed_tracks() {
+        0 => ui.label("No differences"),
+        1 => ui.label("1 track differs"),
+        count => ui.label(format!("{count} tracks differ")),
+    };
+
+    for i in 0..comparison.diffs.len() {
+        let diff = &comparison.diffs[i];
+        ui.separator();
+        ui.columns(2, |columns| {
+            columns[0].push_id("left", |ui| match tracks.get_mut(i) {
+                Some(track) => track_panel(ui, i, track, Some(&diff.left)),
+                None => missing_track_panel(ui, i),
+            });
+            columns[1].push_id("right", |ui| match comparison.tracks.get_mut(i) {
+                Some(track) => track_panel(ui, i, track, Some(&diff.right)),
+                None => missing_track_panel(ui, i),
+            });
+        });
+    }
+}
+
+fn missing_track_panel(ui: &mut Ui, i: usize) {
+    Frame::group(ui.style()).show(ui, |ui| {
+        ui.set_width(ui.available_width());
+        ui.label(format!("Track {i} [MISSING]"));
+    });
+}
+
+fn inspector_toolbar(
+    ui: &mut Ui,
+    inspector: &mut MidiInspector,
+    player: &mut Player,
+    gui: &mut GuiState,
+) {
     ui.horizontal(|ui| {
         ui.label("MIDI Inspector");
         if ui.button("close").clicked() {
             gui.update_flags.close_midi_inspector = true;
         }
+        let detach_text = if gui.inspector_detached {
+            "attach"
+        } else {
+            "detach"
+        };
+        if ui
+            .button(detach_text)
+            .on_hover_text("Toggle between separate window and main window")
+            .clicked()
+        {
+            gui.inspector_detached = !gui.inspector_detached;
+        }
+        ui.separator();
+        preview_controls(ui, inspector, player, gui);
+        ui.separator();
+        compare_controls(ui, inspector, gui);
     });
 }
 
+fn compare_controls(ui: &mut Ui, inspector: &mut MidiInspector, gui: &mut GuiState) {
+    if inspector.comparison.is_some() {
+        if ui.button("end compare").clicked() {
+            inspector.comparison = None;
+        }
+    } else if ui
+        .button("compare…")
+        .on_hover_text("Show another MIDI file side by side, with differences highlighted")
+        .clicked()
+    {
+        let mut dialog = FileDialog::new().add_filter("Midi files", &["mid"]);
+        if let Some(dir) = inspector.filepath.parent() {
+            dialog = dialog.set_directory(dir);
+        }
+        if let Some(path) = storage::pick_file(dialog) {
+            if let Err(e) = inspector.compare_with(&path) {
+                gui.toast_error(e.to_string());
+            }
+        }
+    }
+}
+
+/// Play the inspected file on the preview player
+fn preview_controls(
+    ui: &mut Ui,
+    inspector: &MidiInspector,
+    player: &mut Player,
+    gui: &mut GuiState,
+) {
+    if player.is_preview_playing() {
+        if ui.button("stop preview").clicked() {
+            player.stop_preview();
+        }
+        let mut audible = player.is_preview_audible();
+        if ui
+            .checkbox(&mut audible, "Hear preview")
+            .on_hover_text("Off: hear playlist playback instead")
+            .changed()
+        {
+            player.set_preview_audible(audible);
+        }
+    } else if ui
+        .button("preview")
+        .on_hover_text("Play this file without interrupting the playlist")
+        .clicked()
+    {
+        if let Err(e) = player.start_preview(inspector.filepath.clone()) {
+            gui.toast_error(e.to_string());
+        }
+    }
+}
+
 /// MIDI Header
 fn header_panel(ui: &mut Ui, header: &midi_msg::Header, filepath: &Path) {
     Frame::group(ui.style())
@@ -49,6 +185,103 @@ fn header_panel(ui: &mut Ui, header: &midi_msg::Header, filepath: &Path) {
         });
 }
 
+/// Graph of a controller over time, for one channel
+fn automation_panel(ui: &mut Ui, inspector: &mut MidiInspector) {
+    let response = CollapsingHeader::new("Automation")
+        .open(Some(inspector.automation.open))
+        .show(ui, |ui| {
+            let view = &mut inspector.automation;
+            ui.horizontal(|ui| {
+                ComboBox::from_id_salt("automation_channel")
+                    .selected_text(format!("Channel {}", view.channel + 1))
+                    .show_ui(ui, |ui| {
+                        for channel in 0..CHANNEL_COUNT as u8 {
+                            ui.selectable_value(
+                                &mut view.channel,
+                                channel,
+                                format!("Channel {}", channel + 1),
+                            );
+                        }
+                    });
+                ComboBox::from_id_salt("automation_source")
+                    .selected_text(view.source.get_name())
+                    .show_ui(ui, |ui| {
+                        for source in AutomationSource::ALL {
+                            ui.selectable_value(&mut view.source, source, source.get_name());
+                        }
+                    });
+            });
+
+            let view = inspector.automation;
+            let points = inspector.get_automation(view.channel, view.source);
+            if points.is_empty() {
+                ui.label("No events. The default value is shown.");
+            }
+            automation_graph(ui, &points, view.source, inspector.get_length());
+        });
+    if response.header_response.clicked() {
+        inspector.automation.open = !inspector.automation.open;
+    }
+}
+
+/// Step graph: values hold until the next event.
+fn automation_graph(ui: &mut Ui, points: &[(f32, f32)], source: AutomationSource, length: f32) {
+    let (rect, response) = ui.allocate_exact_size(
+        vec2(ui.available_width(), AUTOMATION_GRAPH_HEIGHT),
+        Sense::hover(),
+    );
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+    painter.rect_filled(rect, 2., visuals.extreme_bg_color);
+
+    let length = length.max(1.);
+    let to_screen = |time: f32, value: f32| {
+        pos2(
+            rect.width().mul_add(time / length, rect.left()),
+            rect.height().mul_add(-value, rect.bottom()),
+        )
+    };
+
+    let mut line: Vec<Pos2> = vec![to_screen(0., source.get_default())];
+    let mut value = source.get_default();
+    for &(time, new_value) in points {
+        line.push(to_screen(time, value));
+        line.push(to_screen(time, new_value));
+        value = new_value;
+    }
+    line.push(to_screen(length, value));
+    painter.add(Shape::line(
+        line,
+        Stroke::new(1.5, visuals.selection.bg_fill),
+    ));
+
+    let text_color = visuals.weak_text_color();
+    painter.text(
+        rect.left_top() + vec2(4., 2.),
+        Align2::LEFT_TOP,
+        "max",
+        FontId::proportional(10.),
+        text_color,
+    );
+    painter.text(
+        rect.right_bottom() - vec2(4., 2.),
+        Align2::RIGHT_BOTTOM,
+        format!("{length:.0}"),
+        FontId::proportional(10.),
+        text_color,
+    );
+
+    if let Some(hover) = response.hover_pos() {
+        let time = (hover.x - rect.left()) / rect.width() * length;
+        let value = points
+            .iter()
+            .take_while(|(point_time, _)| *point_time <= time)
+            .last()
+            .map_or_else(|| source.get_default(), |(_, value)| *value);
+        response.on_hover_text_at_pointer(format!("time: {time:.1}\nvalue: {:.0}%", value * 100.));
+    }
+}
+
 /// MIDI Track - Unknown track type placeholder.
 fn nonstandard_track_panel(ui: &mut Ui, i: usize, track: &MidiInspectorTrack) {
     Frame::group(ui.style()).show(ui, |ui| {
@@ -62,8 +295,39 @@ fn nonstandard_track_panel(ui: &mut Ui, i: usize, track: &MidiInspectorTrack) {
     });
 }
 
-/// MIDI Track - Normal
-fn midi_track_panel(ui: &mut Ui, i: usize, track: &mut MidiInspectorTrack) {
+/// Note summary in the track header
+fn track_stats(ui: &mut Ui, stats: &TrackStats) {
+    ui.label(format!("Notes:    {}", stats.note_count))
+        .on_hover_text("Note on events");
+    if let Some((low, high)) = stats.pitch_range {
+        ui.label(format!("Range:    {}–{}", note_name(low), note_name(high)))
+            .on_hover_text(format!("Lowest and highest note: {low}–{high}"));
+    }
+    if !stats.channels.is_empty() {
+        let channels: Vec<String> = stats
+            .channels
+            .iter()
+            .map(|channel| (channel + 1).to_string())
+            .collect();
+        ui.add(
+            Label::new(format!("Channels: {}", channels.join(", ")))
+                .wrap_mode(TextWrapMode::Truncate),
+        )
+        .on_hover_text("Channels the track sends to");
+    }
+    if let Some(program) = stats.common_program {
+        ui.label(format!("Program:  {program}"))
+            .on_hover_text("Most common program change, 0-based");
+    }
+}
+
+/// MIDI Track - Normal. When comparing, changed events are highlighted.
+fn midi_track_panel(
+    ui: &mut Ui,
+    i: usize,
+    track: &mut MidiInspectorTrack,
+    changed: Option<&[bool]>,
+) {
     let content = track.track.events();
     let bgcol = ui.visuals().code_bg_color;
 
@@ -84,6 +348,11 @@ fn midi_track_panel(ui: &mut Ui, i: usize, track: &mut MidiInspectorTrack) {
                     });
                 });
                 ui.label(format!("Events:   {:?}", content.len()));
+                track_stats(ui, &track.stats);
+                if let Some(changed) = changed {
+                    let count = changed.iter().filter(|&&changed| changed).count();
+                    ui.label(format!("Changed:  {count}"));
+                }
             });
         });
 
@@ -127,6 +396,7 @@ fn midi_track_panel(ui: &mut Ui, i: usize, track: &mut MidiInspectorTrack) {
                     let delta_t = track_event.delta_time;
                     let beat_or_frame = track_event.beat_or_frame;
                     let event = &track_event.event;
+                    row.set_selected(changed.is_some_and(|changed| changed[index]));
 
                     row.col(|ui| {
                         ui.label(format!("{index}"));
@@ -144,6 +414,9 @@ fn midi_track_panel(ui: &mut Ui, i: usize, track: &mut MidiInspectorTrack) {
                                 ui.set_width(ui.available_width());
                                 ui.horizontal(|ui| {
                                     ui.strong(format!("{event:?}"));
+                                    if let Some(name) = program_change_name(event) {
+                                        ui.strong(name);
+                                    }
                                     ui.strong(format!("raw: {:02X?}", event.to_midi()));
                                 });
                             });
@@ -154,6 +427,23 @@ fn midi_track_panel(ui: &mut Ui, i: usize, track: &mut MidiInspectorTrack) {
     });
 }
 
+/// General MIDI instrument or drum kit of a program change
+fn program_change_name(msg: &MidiMsg) -> Option<&'static str> {
+    let (MidiMsg::ChannelVoice { channel, msg } | MidiMsg::RunningChannelVoice { channel, msg }) =
+        msg
+    else {
+        return None;
+    };
+    let ChannelVoiceMsg::ProgramChange { program } = msg else {
+        return None;
+    };
+    Some(if *channel as usize == DRUM_CHANNEL {
+        general_midi::drum_kit_name(*program)
+    } else {
+        general_midi::program_name(*program)
+    })
+}
+
 fn event_color(style: &Style, msg: &MidiMsg) -> Color32 {
     let color = match msg {
         MidiMsg::ChannelVoice { .. } => Color32::from_hex("#458588"),
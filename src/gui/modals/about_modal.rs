@@ -97,7 +97,7 @@ fn dependency_item(ui: &mut Ui, crate_info: &CrateInfo) {
         ui.label(RichText::new("Authors:").strong());
         for author in &crate_info.authors {
             // Remove email
-            ui.label(author.split('<').take(1).collect::<Vec<_>>()[0]);
+            ui.label(author.split('<').next().unwrap_or_default());
         }
         if crate_info.authors.is_empty() {
             ui.label("(this crate did not specify its authors!)");
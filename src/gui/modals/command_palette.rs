@@ -0,0 +1,170 @@
+//! Fuzzy search over every command and setting
+
+use eframe::egui::{
+    vec2, Align, Align2, Button, Context, Key, Modifiers, ScrollArea, TextEdit, Window,
+};
+
+use super::settings::setting_labels;
+use crate::{
+    gui::{
+        commands::{Command, COMMANDS},
+        GuiState,
+    },
+    player::Player,
+};
+
+#[derive(Clone, Copy)]
+enum Entry {
+    Command(&'static Command),
+    /// Opens the settings window filtered to this setting
+    Setting(&'static str),
+}
+impl Entry {
+    fn get_title(self) -> String {
+        match self {
+            Self::Command(command) => format!("{}: {}", command.category.get_name(), command.name),
+            Self::Setting(label) => format!("Settings: {label}"),
+        }
+    }
+
+    /// How well a lowercase query matches. Matches in the name beat matches in the keywords.
+    fn score(self, query: &str) -> Option<u32> {
+        let keywords = match self {
+            Self::Command(command) => command.keywords,
+            Self::Setting(_) => "",
+        };
+        let title = self.get_title().to_lowercase();
+        fuzzy_score(query, &title)
+            .map(|score| score * 2)
+            .or_else(|| fuzzy_score(query, &format!("{title} {keywords}")))
+    }
+
+    fn run(self, ctx: &Context, player: &mut Player, gui: &mut GuiState) {
+        match self {
+            Self::Command(command) => (command.run)(ctx, player, gui),
+            Self::Setting(label) => {
+                label.clone_into(&mut gui.settings_search);
+                gui.show_settings_modal = true;
+            }
+        }
+    }
+}
+
+/// Score of the query letters appearing in order in the text. Consecutive letters and letters
+/// at the start of a word count extra. None if the text doesn't have them all.
+fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let mut score = 0;
+    let mut text = text.chars();
+    let mut prev = ' ';
+    let mut prev_matched = false;
+    for wanted in query.chars().filter(|c| !c.is_whitespace()) {
+        let mut consecutive = prev_matched;
+        loop {
+            let c = text.next()?;
+            let word_start = !prev.is_alphanumeric();
+            prev = c;
+            if c == wanted {
+                score += 1 + 2 * u32::from(consecutive) + 3 * u32::from(word_start);
+                break;
+            }
+            consecutive = false;
+        }
+        prev_matched = true;
+    }
+    Some(score)
+}
+
+pub fn command_palette(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
+    if !gui.show_command_palette {
+        return;
+    }
+
+    let query = gui.palette_search.trim().to_lowercase();
+    let mut entries: Vec<(Entry, u32)> = COMMANDS
+        .iter()
+        .map(Entry::Command)
+        .chain(
+            setting_labels(gui.show_developer_options)
+                .into_iter()
+                .map(Entry::Setting),
+        )
+        .filter_map(|entry| Some((entry, entry.score(&query)?)))
+        .collect();
+    // Stable, so equal scores stay in registry order
+    entries.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    gui.palette_selected = gui.palette_selected.min(entries.len().saturating_sub(1));
+
+    // Taken before the text field sees them
+    let (up, down, enter, escape) = ctx.input_mut(|input| {
+        (
+            input.consume_key(Modifiers::NONE, Key::ArrowUp),
+            input.consume_key(Modifiers::NONE, Key::ArrowDown),
+            input.consume_key(Modifiers::NONE, Key::Enter),
+            input.consume_key(Modifiers::NONE, Key::Escape),
+        )
+    });
+    let moved = up || down;
+    if up {
+        gui.palette_selected = gui.palette_selected.saturating_sub(1);
+    }
+    if down && gui.palette_selected + 1 < entries.len() {
+        gui.palette_selected += 1;
+    }
+
+    let mut chosen = enter
+        .then(|| entries.get(gui.palette_selected).map(|(entry, _)| *entry))
+        .flatten();
+    let response = Window::new("Command palette")
+        .title_bar(false)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_TOP, vec2(0., 48.))
+        .show(ctx, |ui| {
+            ui.set_width(420.);
+            let search = ui.add(
+                TextEdit::singleline(&mut gui.palette_search)
+                    .hint_text("Type to search commands and settings")
+                    .desired_width(f32::INFINITY),
+            );
+            search.request_focus();
+            if search.changed() {
+                gui.palette_selected = 0;
+            }
+            ui.separator();
+
+            ScrollArea::vertical().max_height(320.).show(ui, |ui| {
+                if entries.is_empty() {
+                    ui.label("No matches.");
+                }
+                for (i, (entry, _)) in entries.iter().enumerate() {
+                    let selected = i == gui.palette_selected;
+                    let mut button = Button::new(entry.get_title())
+                        .selected(selected)
+                        .frame(selected)
+                        .min_size(vec2(ui.available_width(), 0.));
+                    if let Entry::Command(Command {
+                        shortcut: Some(shortcut),
+                        ..
+                    }) = entry
+                    {
+                        button = button.shortcut_text(ctx.format_shortcut(shortcut));
+                    }
+                    let response = ui.add(button);
+                    if selected && moved {
+                        response.scroll_to_me(Some(Align::Center));
+                    }
+                    if response.clicked() {
+                        chosen = Some(*entry);
+                    }
+                }
+            });
+        });
+
+    let clicked_outside = response.is_some_and(|response| response.response.clicked_elsewhere());
+    if escape || clicked_outside || chosen.is_some() {
+        gui.show_command_palette = false;
+    }
+    if let Some(entry) = chosen {
+        entry.run(ctx, player, gui);
+    }
+}
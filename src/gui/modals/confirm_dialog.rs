@@ -0,0 +1,206 @@
+//! Confirmation dialogs for destructive and risky actions
+
+use eframe::egui::{vec2, Align, Align2, Context, Layout, RichText, Window};
+use serde::{Deserialize, Serialize};
+
+use super::{add_dialog_button, DialogButtonStyle};
+use crate::{gui::GuiState, player::Player};
+
+/// Refreshing playlists with more files than this asks first.
+const LARGE_REFRESH_THRESHOLD: usize = 1000;
+
+/// Which actions ask for confirmation
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Confirmations {
+    pub clear_songs: bool,
+    pub clear_fonts: bool,
+    pub close_unsaved_playlist: bool,
+    pub large_refresh: bool,
+    pub revert_playlist: bool,
+    pub play_huge_song: bool,
+}
+impl Default for Confirmations {
+    fn default() -> Self {
+        Self {
+            clear_songs: true,
+            clear_fonts: true,
+            close_unsaved_playlist: true,
+            large_refresh: true,
+            revert_playlist: true,
+            play_huge_song: true,
+        }
+    }
+}
+
+/// Action waiting for confirmation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmAction {
+    ClearSongs { playlist: usize },
+    ClearFonts { playlist: usize },
+    RefreshContent { playlist: usize },
+    RevertPlaylist { playlist: usize },
+    PlayHugeSong { playlist: usize, index: usize },
+}
+impl ConfirmAction {
+    const fn get_playlist_idx(self) -> usize {
+        match self {
+            Self::ClearSongs { playlist }
+            | Self::ClearFonts { playlist }
+            | Self::RefreshContent { playlist }
+            | Self::RevertPlaylist { playlist }
+            | Self::PlayHugeSong { playlist, .. } => playlist,
+        }
+    }
+
+    const fn get_question(self) -> &'static str {
+        match self {
+            Self::ClearSongs { .. } => "Remove all songs from this playlist? You can undo this.",
+            Self::ClearFonts { .. } => {
+                "Remove all soundfonts from this playlist? You can undo this."
+            }
+            Self::RefreshContent { .. } => "This playlist has a lot of files. Refresh anyway?",
+            Self::RevertPlaylist { .. } => "Discard unsaved changes and reload the saved file?",
+            Self::PlayHugeSong { .. } => {
+                "This song is huge, and playing it may freeze the player. Play anyway?"
+            }
+        }
+    }
+
+    const fn get_confirm_text(self) -> &'static str {
+        match self {
+            Self::ClearSongs { .. } | Self::ClearFonts { .. } => "Clear",
+            Self::RefreshContent { .. } => "Refresh",
+            Self::RevertPlaylist { .. } => "Reset",
+            Self::PlayHugeSong { .. } => "Play",
+        }
+    }
+
+    fn needs_confirmation(self, player: &Player, confirmations: Confirmations) -> bool {
+        match self {
+            Self::ClearSongs { .. } => confirmations.clear_songs,
+            Self::ClearFonts { .. } => confirmations.clear_fonts,
+            Self::RefreshContent { playlist } => {
+                let playlist = &player.get_playlists()[playlist];
+                confirmations.large_refresh
+                    && playlist.get_fonts().len() + playlist.get_songs().len()
+                        > LARGE_REFRESH_THRESHOLD
+            }
+            Self::RevertPlaylist { .. } => confirmations.revert_playlist,
+            Self::PlayHugeSong { playlist, index } => {
+                confirmations.play_huge_song && player.check_huge_song(playlist, index).is_some()
+            }
+        }
+    }
+
+    const fn set_confirmation(self, confirmations: &mut Confirmations, value: bool) {
+        match self {
+            Self::ClearSongs { .. } => confirmations.clear_songs = value,
+            Self::ClearFonts { .. } => confirmations.clear_fonts = value,
+            Self::RefreshContent { .. } => confirmations.large_refresh = value,
+            Self::RevertPlaylist { .. } => confirmations.revert_playlist = value,
+            Self::PlayHugeSong { .. } => confirmations.play_huge_song = value,
+        }
+    }
+
+    fn run(self, player: &mut Player) {
+        match self {
+            Self::ClearSongs { playlist } => {
+                let _ = player.clear_songs(playlist);
+            }
+            Self::ClearFonts { playlist } => {
+                let _ = player.clear_fonts(playlist);
+            }
+            Self::RefreshContent { playlist } => {
+                if let Some(playlist) = player.get_playlists_mut().get_mut(playlist) {
+                    playlist.refresh_font_list();
+                    playlist.refresh_song_list();
+                }
+            }
+            Self::RevertPlaylist { playlist } => {
+                let _ = player.revert_portable_playlist(playlist);
+            }
+            Self::PlayHugeSong { playlist, index } => {
+                if player.switch_to_playlist(playlist).is_ok()
+                    && player.get_playlist_mut().set_song_idx(Some(index)).is_ok()
+                {
+                    player.start();
+                }
+            }
+        }
+    }
+}
+
+/// Run the action, or ask first if the user wants to be asked.
+pub fn request_action(player: &mut Player, gui: &mut GuiState, action: ConfirmAction) {
+    if action.get_playlist_idx() >= player.get_playlists().len() {
+        return;
+    }
+    if action.needs_confirmation(player, gui.confirmations) {
+        gui.pending_confirmation = Some(action);
+    } else {
+        action.run(player);
+    }
+}
+
+pub fn confirm_dialog(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
+    let Some(action) = gui.pending_confirmation else {
+        return;
+    };
+    let Some(name) = player
+        .get_playlists()
+        .get(action.get_playlist_idx())
+        .map(|playlist| playlist.name.clone())
+    else {
+        gui.pending_confirmation = None;
+        return;
+    };
+
+    Window::new("Confirm")
+        .collapsible(false)
+        .title_bar(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, vec2(0., 0.))
+        .show(ctx, |ui| {
+            ui.set_width(420.);
+
+            ui.add_space(12.);
+
+            ui.horizontal(|ui| {
+                ui.add_space(16.);
+                ui.label(RichText::new("🎵").size(60.0));
+                ui.vertical(|ui| {
+                    ui.add_space(10.);
+                    ui.heading("Are you sure?");
+                    ui.label(action.get_question());
+                    ui.label(format!("Playlist: {name}"));
+                    ui.checkbox(&mut gui.dont_ask_again, "Don't ask again");
+                });
+                ui.add_space(16.);
+            });
+
+            ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                ui.add_space(12.);
+
+                if add_dialog_button(
+                    ui,
+                    action.get_confirm_text(),
+                    &DialogButtonStyle::Destructive,
+                )
+                .clicked()
+                {
+                    if gui.dont_ask_again {
+                        action.set_confirmation(&mut gui.confirmations, false);
+                    }
+                    action.run(player);
+                    gui.pending_confirmation = None;
+                    gui.dont_ask_again = false;
+                }
+                if add_dialog_button(ui, "Cancel", &DialogButtonStyle::None).clicked() {
+                    gui.pending_confirmation = None;
+                    gui.dont_ask_again = false;
+                }
+            });
+            ui.add_space(4.);
+        });
+}
@@ -1,15 +1,38 @@
+use std::fs;
+
 use crate::{
-    gui::GuiState,
-    player::{soundfont_library::FontLibrary, Player},
+    gui::{
+        storage::{self, FileDialog},
+        GuiState,
+    },
+    player::{
+        playlist::{
+            enums::{BundleFormat, SongListFormat},
+            font_meta::FONT_EXTENSIONS,
+            import::ImportFormat,
+        },
+        soundfont_library::FontLibrary,
+        Player,
+    },
 };
-use rfd::FileDialog;
 
+/// Open a playlist file. Other players' playlists are imported.
 pub fn open_playlist(player: &mut Player, gui: &mut GuiState) {
-    if let Some(path) = FileDialog::new()
-        .add_filter("Midi playlist", &["midpl"])
-        .pick_file()
-    {
-        if let Err(e) = player.open_portable_playlist(path) {
+    let all_extensions: Vec<&str> = std::iter::once("midpl")
+        .chain(ImportFormat::EXTENSIONS)
+        .collect();
+    if let Some(path) = storage::pick_file(
+        FileDialog::new()
+            .add_filter("All playlists", &all_extensions)
+            .add_filter("Midi playlist", &["midpl"])
+            .add_filter("Other players' playlists", &ImportFormat::EXTENSIONS),
+    ) {
+        let result = if ImportFormat::from_path(&path).is_some() {
+            player.import_playlist(&path)
+        } else {
+            player.open_portable_playlist(path)
+        };
+        if let Err(e) = result {
             gui.toast_error(e.to_string());
         }
     }
@@ -28,13 +51,74 @@ pub fn save_playlist_as(player: &mut Player, idx: usize, gui: &mut GuiState) {
     }
 }
 
+pub fn export_piano_roll(player: &mut Player, gui: &mut GuiState) {
+    if let Some(dir) = FileDialog::new()
+        .set_title("Export piano roll to directory")
+        .pick_folder()
+    {
+        match player.export_piano_roll(dir) {
+            Ok(job) => gui.export_job = Some(job),
+            Err(e) => gui.toast_error(e.to_string()),
+        }
+    }
+}
+
+pub fn record_output(player: &mut Player, gui: &mut GuiState) {
+    if let Some(filepath) = FileDialog::new()
+        .add_filter("Wav", &["wav"])
+        .set_title("Record output to")
+        .set_file_name("recording.wav")
+        .save_file()
+    {
+        if let Err(e) = player.start_recording(&filepath) {
+            gui.toast_error(e.to_string());
+        }
+    }
+}
+
+pub fn export_song_list(player: &Player, format: SongListFormat, gui: &mut GuiState) {
+    let extension = format.get_extension();
+    if let Some(filepath) = FileDialog::new()
+        .add_filter(format.to_string(), &[extension])
+        .set_title("Export song list")
+        .set_file_name(format!("{}.{extension}", &player.get_playlist().name))
+        .save_file()
+    {
+        if let Err(e) = fs::write(filepath, player.get_playlist().export_song_list(format)) {
+            gui.toast_error(e.to_string());
+        }
+    }
+}
+
+/// Save a playlist along with copies of its songs and soundfonts
+pub fn export_playlist_bundle(
+    player: &Player,
+    idx: usize,
+    format: BundleFormat,
+    gui: &mut GuiState,
+) {
+    let playlist = &player.get_playlists()[idx];
+    let extension = format.get_extension();
+    if let Some(filepath) = FileDialog::new()
+        .add_filter(format.to_string(), &[extension])
+        .set_title("Export playlist with files")
+        .set_file_name(format!("{}.{extension}", &playlist.name))
+        .save_file()
+    {
+        match playlist.export_bundle(&filepath, format) {
+            Ok(()) => gui.toast_success(format!("Exported to {}", filepath.display())),
+            Err(e) => gui.toast_error(e.to_string()),
+        }
+    }
+}
+
 // Add files and add dirs are separate because file dialog doesn't support mixed picking.
 pub fn add_font_lib_files(font_lib: &mut FontLibrary /* , gui: &mut GuiState */) {
-    if let Some(paths) = FileDialog::new()
-        .add_filter("Soundfonts", &["sf2"])
-        .set_title("Add files")
-        .pick_files()
-    {
+    if let Some(paths) = storage::pick_files(
+        FileDialog::new()
+            .add_filter("Soundfonts", &FONT_EXTENSIONS)
+            .set_title("Add files"),
+    ) {
         for path in paths {
             if let Err(_e) = font_lib.add_path(path) {
                 // gui.toast_error(e.to_string());
@@ -44,10 +128,7 @@ pub fn add_font_lib_files(font_lib: &mut FontLibrary /* , gui: &mut GuiState */)
     }
 }
 pub fn add_font_lib_dirs(font_lib: &mut FontLibrary /* , gui: &mut GuiState */) {
-    if let Some(paths) = FileDialog::new()
-        .set_title("Add directories")
-        .pick_folders()
-    {
+    if let Some(paths) = storage::pick_folders(FileDialog::new().set_title("Add directories")) {
         for path in paths {
             if let Err(_e) = font_lib.add_path(path) {
                 // gui.toast_error(e.to_string());
@@ -1,11 +1,15 @@
 use super::GuiState;
-use crate::player::Player;
+use crate::player::{audio::export::AudioFormat, end_action::EndActionKind, Player};
 use eframe::egui::{
-    vec2, Align, Align2, Button, Color32, Context, Layout, Response, RichText, Ui, ViewportCommand,
-    WidgetText, Window,
+    vec2, Align, Align2, Button, Color32, ComboBox, Context, Layout, ProgressBar, Response,
+    RichText, Slider, Ui, ViewportCommand, WidgetText, Window,
 };
+use std::{mem, time::Duration};
+use web_time::Instant;
 
 pub mod about_modal;
+pub mod command_palette;
+pub mod confirm_dialog;
 pub mod file_dialogs;
 pub mod settings;
 pub mod shortcuts;
@@ -17,10 +21,14 @@ enum DialogButtonStyle {
 }
 
 /// Playlist close confirm with unsaved changes
-pub fn unsaved_close_dialog(ctx: &Context, player: &mut Player) {
+pub fn unsaved_close_dialog(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
     let Some(index) = player.get_playlist_waiting_for_discard() else {
         return;
     };
+    if !gui.confirmations.close_unsaved_playlist {
+        let _ = player.force_remove_playlist(index);
+        return;
+    }
     let name = player.get_playlists()[index].name.clone();
 
     Window::new("Unsaved changes")
@@ -41,6 +49,7 @@ pub fn unsaved_close_dialog(ctx: &Context, player: &mut Player) {
                     ui.heading("Unsaved changes");
                     ui.label("You have unsaved changes. Close this playlist?");
                     ui.label(format!("Playlist: {name}"));
+                    ui.checkbox(&mut gui.dont_ask_again, "Don't ask again");
                 });
                 ui.add_space(16.);
             });
@@ -49,6 +58,10 @@ pub fn unsaved_close_dialog(ctx: &Context, player: &mut Player) {
                 ui.add_space(12.);
 
                 if add_dialog_button(ui, "Discard", &DialogButtonStyle::Destructive).clicked() {
+                    if gui.dont_ask_again {
+                        gui.confirmations.close_unsaved_playlist = false;
+                    }
+                    gui.dont_ask_again = false;
                     let _ = player.force_remove_playlist(index);
                 };
 
@@ -59,6 +72,7 @@ pub fn unsaved_close_dialog(ctx: &Context, player: &mut Player) {
                 });
 
                 if add_dialog_button(ui, "Cancel", &DialogButtonStyle::None).clicked() {
+                    gui.dont_ask_again = false;
                     let _ = player.cancel_remove_playlist(index);
                 };
             });
@@ -66,6 +80,84 @@ pub fn unsaved_close_dialog(ctx: &Context, player: &mut Player) {
         });
 }
 
+/// State files failed to load. Saving is blocked until a choice is made.
+pub fn state_recovery_dialog(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
+    if player.get_broken_state_files().is_empty() {
+        return;
+    }
+
+    Window::new("Couldn't load app state")
+        .collapsible(false)
+        .title_bar(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, vec2(0., 0.))
+        .show(ctx, |ui| {
+            ui.set_width(480.);
+
+            ui.add_space(12.);
+
+            ui.horizontal(|ui| {
+                ui.add_space(16.);
+                ui.label(RichText::new("⚠").size(60.0));
+                ui.vertical(|ui| {
+                    ui.add_space(10.);
+                    ui.heading("Couldn't load app state");
+                    ui.label(
+                        "Some files are damaged. Copies of them were saved with a .corrupt \
+                         extension. Nothing is saved until you choose what to do.",
+                    );
+                    ui.add_space(4.);
+                    for file in player.get_broken_state_files() {
+                        ui.label(RichText::new(file.path.display().to_string()).strong());
+                        ui.weak(&file.error);
+                    }
+                    ui.add_space(4.);
+                    ui.label(format!(
+                        "Recovered {} playlists.",
+                        player.get_playlists().len()
+                    ));
+                });
+                ui.add_space(16.);
+            });
+
+            ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                ui.add_space(12.);
+
+                if add_dialog_button(ui, "Keep recovered", &DialogButtonStyle::Suggested)
+                    .on_hover_text("Continue with what could be loaded")
+                    .clicked()
+                {
+                    player.keep_recovered_state();
+                }
+                if add_dialog_button(ui, "Start fresh", &DialogButtonStyle::Destructive)
+                    .on_hover_text("Drop recovered playlists and soundfont library")
+                    .clicked()
+                {
+                    player.start_fresh_state();
+                }
+                ui.add_enabled_ui(Player::has_state_backup(), |ui| {
+                    if add_dialog_button(ui, "Restore backup", &DialogButtonStyle::None)
+                        .on_hover_text("Go back to the last state that loaded without problems")
+                        .clicked()
+                    {
+                        if let Err(e) = player.restore_state_backup() {
+                            gui.toast_error(e.to_string());
+                        }
+                    }
+                });
+                if add_dialog_button(ui, "Retry", &DialogButtonStyle::None)
+                    .on_hover_text("Load again, e.g. after fixing the files by hand")
+                    .clicked()
+                {
+                    if let Err(e) = player.retry_load_state() {
+                        gui.toast_error(e.to_string());
+                    }
+                }
+            });
+            ui.add_space(4.);
+        });
+}
+
 /// App quit confirm with unsaved changes
 pub fn unsaved_quit_dialog(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
     if gui.show_unsaved_quit_modal {
@@ -115,6 +207,145 @@ pub fn unsaved_quit_dialog(ctx: &Context, player: &mut Player, gui: &mut GuiStat
     }
 }
 
+/// Countdown of quitting, suspending or a command after playback finished
+pub fn end_action_dialog(ctx: &Context, player: &mut Player) {
+    let Some((kind, deadline)) = player.get_pending_end_action() else {
+        return;
+    };
+    let remaining = deadline.saturating_duration_since(Instant::now());
+
+    Window::new("Playback finished")
+        .collapsible(false)
+        .title_bar(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, vec2(0., 0.))
+        .show(ctx, |ui| {
+            ui.set_width(420.);
+
+            ui.add_space(12.);
+
+            ui.horizontal(|ui| {
+                ui.add_space(16.);
+                ui.label(RichText::new("🎵").size(60.0));
+                ui.vertical(|ui| {
+                    ui.add_space(10.);
+                    ui.heading("Playback finished");
+                    ui.label(format!(
+                        "{} in {} seconds.",
+                        kind.get_name(),
+                        remaining.as_secs_f32().ceil()
+                    ));
+                    if kind == EndActionKind::Command {
+                        ui.label(RichText::new(&player.end_action.command).code());
+                    }
+                });
+                ui.add_space(16.);
+            });
+
+            ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                ui.add_space(12.);
+                if add_dialog_button(ui, "Now", &DialogButtonStyle::Destructive).clicked() {
+                    player.confirm_end_action();
+                }
+                if add_dialog_button(ui, "Cancel", &DialogButtonStyle::None).clicked() {
+                    player.cancel_end_action();
+                }
+            });
+            ui.add_space(4.);
+        });
+    ctx.request_repaint_after(Duration::from_millis(250));
+}
+
+/// Audio format of a piano roll export, then the folder
+pub fn export_dialog(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
+    if !gui.show_export_dialog {
+        return;
+    }
+    let mut open = true;
+    let mut start = false;
+
+    Window::new("Export piano roll video")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, vec2(0., 0.))
+        .open(&mut open)
+        .show(ctx, |ui| {
+            ui.set_width(320.);
+            ui.horizontal(|ui| {
+                ui.label("Audio format");
+                ComboBox::from_id_salt("export_format")
+                    .selected_text(player.export_format.get_name())
+                    .show_ui(ui, |ui| {
+                        for format in AudioFormat::ALL {
+                            let selected = mem::discriminant(&player.export_format)
+                                == mem::discriminant(&format);
+                            if ui.selectable_label(selected, format.get_name()).clicked()
+                                && !selected
+                            {
+                                player.export_format = format;
+                            }
+                        }
+                    });
+            });
+            if let AudioFormat::Ogg { quality } = &mut player.export_format {
+                ui.horizontal(|ui| {
+                    ui.label("Quality");
+                    ui.add(Slider::new(quality, 0..=AudioFormat::OGG_QUALITY_MAX))
+                        .on_hover_text("Higher sounds better and makes a bigger file");
+                });
+            }
+            ui.label("FLAC and OGG files are tagged with the song name.");
+            ui.add_space(8.);
+
+            ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                if add_dialog_button(ui, "Export…", &DialogButtonStyle::Suggested).clicked() {
+                    start = true;
+                }
+                if add_dialog_button(ui, "Cancel", &DialogButtonStyle::None).clicked() {
+                    gui.show_export_dialog = false;
+                }
+            });
+        });
+
+    if !open {
+        gui.show_export_dialog = false;
+    }
+    if start {
+        gui.show_export_dialog = false;
+        file_dialogs::export_piano_roll(player, gui);
+    }
+}
+
+/// Progress of a running piano roll export
+pub fn export_progress(ctx: &Context, gui: &mut GuiState) {
+    let Some(job) = &gui.export_job else {
+        return;
+    };
+    if let Some(result) = job.poll() {
+        gui.export_job = None;
+        match result {
+            Ok(dir) => gui.toast_success(format!("Exported to {}", dir.display())),
+            Err(e) => gui.toast_error(e.to_string()),
+        }
+        return;
+    }
+
+    Window::new("Exporting")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::RIGHT_BOTTOM, vec2(-16., -64.))
+        .show(ctx, |ui| {
+            ui.set_width(240.);
+            ui.add(ProgressBar::new(job.get_progress()).show_percentage());
+            ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                if add_dialog_button(ui, "Cancel", &DialogButtonStyle::None).clicked() {
+                    job.cancel();
+                }
+            });
+        });
+    ctx.request_repaint_after(Duration::from_millis(100));
+}
+
 fn add_dialog_button<S>(ui: &mut Ui, text: S, style: &DialogButtonStyle) -> Response
 where
     WidgetText: From<S>,
@@ -1,18 +1,335 @@
 use eframe::egui::{
-    lerp, pos2, vec2, Align, Align2, Button, CollapsingHeader, Context, InputState, Label, Layout,
-    RichText, ScrollArea, Sense, Stroke, TextWrapMode, Ui, Vec2, Widget, WidgetInfo, WidgetType,
-    Window,
+    lerp, pos2, vec2, Align, Align2, Button, CollapsingHeader, ComboBox, Context, DragValue, Event,
+    Grid, InputState, Key, Label, Layout, RichText, ScrollArea, Sense, Slider, Stroke, TextEdit,
+    TextWrapMode, Ui, Vec2, Widget, WidgetInfo, WidgetType, Window,
 };
 use egui_extras::{Column, TableBuilder};
 
 use crate::{
-    gui::actions,
-    player::{soundfont_library::FontLibrary, Player},
+    gui::{
+        actions, apply_touch_mode,
+        commands::COMMANDS,
+        playback_controls::{MouseGestures, SEEK_WHEEL_RANGE, VOLUME_WHEEL_RANGE},
+        storage, TableDensity, TBL_ROW_H_RANGE, TBL_TEXT_SIZE_RANGE,
+    },
+    player::{
+        audio::{
+            device_watch::output_device_names,
+            net_stream::local_ip,
+            routing::{pair_count, pair_name},
+        },
+        audio_profile::{AudioProfile, EQ_BAND_NAMES, GAIN_RANGE},
+        channel_colors::ChannelPalette,
+        end_action::{EndActionKind, EndTrigger, END_ACTION_DELAY},
+        font_chain::{FontChain, FontChainLink},
+        general_midi,
+        global_hotkeys::{Hotkey, HotkeyAction},
+        playlist::{
+            enums::{FileListMode, SongSort},
+            font_meta::PresetInfo,
+        },
+        playlist_template::{PlaylistTemplate, NAME_COUNTER},
+        program_lock::{DrumKit, ProgramLocks, CHANNEL_COUNT, DRUM_BANK_OFFSET, DRUM_CHANNEL},
+        scripting::script_path,
+        serialize_player::{config_dir, data_dir, state_dir},
+        soundfont_library::FontLibrary,
+        soundfont_list::FontSort,
+        FontChangeMode, Player, AUTOSAVE_INTERVAL_RANGE,
+    },
     GuiState,
 };
 
 use super::file_dialogs;
 
+/// Sections of the settings window, in display order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    General,
+    FontLibrary,
+    Playback,
+    Developer,
+}
+impl Category {
+    const ALL: [Self; 4] = [
+        Self::General,
+        Self::FontLibrary,
+        Self::Playback,
+        Self::Developer,
+    ];
+
+    const fn get_title(self) -> &'static str {
+        match self {
+            Self::General => "General Settings",
+            Self::FontLibrary => "Soundfont library",
+            Self::Playback => "Playback",
+            Self::Developer => "Developer / Debug Settings",
+        }
+    }
+}
+
+/// One searchable option or group of options in the settings window
+struct SettingsEntry {
+    category: Category,
+    label: &'static str,
+    /// Other words the entry can be found with
+    keywords: &'static str,
+    show: fn(&mut Ui, &mut Player, &mut GuiState),
+}
+impl SettingsEntry {
+    /// Every word of a lowercase query is found in the label, keywords or category.
+    fn matches(&self, query: &str) -> bool {
+        let haystack = format!(
+            "{} {} {}",
+            self.label,
+            self.keywords,
+            self.category.get_title()
+        )
+        .to_lowercase();
+        query.split_whitespace().all(|word| haystack.contains(word))
+    }
+}
+
+const SETTINGS: &[SettingsEntry] = &[
+    SettingsEntry {
+        category: Category::General,
+        label: "Theme",
+        keywords: "dark light appearance",
+        show: |ui, _, _| theme_control(ui),
+    },
+    SettingsEntry {
+        category: Category::General,
+        label: "Autosave",
+        keywords: "save playlist files app memory interval",
+        show: |ui, player, _| autosave_controls(ui, player),
+    },
+    SettingsEntry {
+        category: Category::General,
+        label: "Confirmations",
+        keywords: "ask confirm dialog delete clear remove refresh",
+        show: |ui, _, gui| confirmation_controls(ui, gui),
+    },
+    SettingsEntry {
+        category: Category::General,
+        label: "Touch mode",
+        keywords: "touch screen tablet finger big controls",
+        show: |ui, _, gui| touch_mode_control(ui, gui),
+    },
+    SettingsEntry {
+        category: Category::General,
+        label: "Double click to play",
+        keywords: "single click select song table browse",
+        show: |ui, _, gui| {
+            ui.add(toggle_row(
+                "Double click to play",
+                "A single click on a song only selects it, so browsing doesn't change the song",
+                &mut gui.double_click_to_play,
+            ));
+            ui.add_space(8.);
+        },
+    },
+    SettingsEntry {
+        category: Category::General,
+        label: "Table density",
+        keywords: "row height font text size readability compact",
+        show: |ui, _, gui| table_density_controls(ui, gui),
+    },
+    SettingsEntry {
+        category: Category::General,
+        label: "Mouse gestures",
+        keywords: "wheel scroll middle click volume seek sensitivity playback bar",
+        show: |ui, _, gui| mouse_gesture_controls(ui, gui),
+    },
+    SettingsEntry {
+        category: Category::General,
+        label: "Song change notifications",
+        keywords: "desktop notify focused",
+        show: |ui, _, gui| notification_controls(ui, gui),
+    },
+    SettingsEntry {
+        category: Category::General,
+        label: "Channel colors",
+        keywords: "palette visualizer now playing piano roll",
+        show: |ui, player, _| channel_color_controls(ui, player),
+    },
+    SettingsEntry {
+        category: Category::General,
+        label: "New playlists",
+        keywords: "template default name song list sort soundfont",
+        show: |ui, player, _| playlist_template_controls(ui, player),
+    },
+    SettingsEntry {
+        category: Category::General,
+        label: "User script",
+        keywords: "scripting rhai hooks automation",
+        show: scripting_controls,
+    },
+    SettingsEntry {
+        category: Category::General,
+        label: "Show developer settings",
+        keywords: "debug",
+        show: |ui, _, gui| {
+            ui.add(toggle_row(
+                "Show developer settings",
+                "These are not useful to normal users",
+                &mut gui.show_developer_options,
+            ));
+        },
+    },
+    SettingsEntry {
+        category: Category::FontLibrary,
+        label: "Paths",
+        keywords: "directories folders files add",
+        show: |ui, player, gui| font_lib_paths(ui, &mut player.font_lib, gui),
+    },
+    SettingsEntry {
+        category: Category::FontLibrary,
+        label: "Search subdirectories",
+        keywords: "recursive folders",
+        show: |ui, player, _| {
+            if ui
+                .add(toggle_row(
+                    "Search subdirectories",
+                    "Also search all subdirectories",
+                    &mut player.font_lib.crawl_subdirs,
+                ))
+                .changed()
+            {
+                player.font_lib.refresh();
+            };
+        },
+    },
+    SettingsEntry {
+        category: Category::FontLibrary,
+        label: "Soundfont fallback order",
+        keywords: "chain default",
+        show: |ui, player, _| font_chain_control(ui, &mut player.font_chain),
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "Change soundfont instantly",
+        keywords: "switch next song",
+        show: |ui, player, _| font_change_control(ui, player),
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "Auto-engage limiter",
+        keywords: "clip clipping peak volume",
+        show: |ui, player, _| auto_limiter_control(ui, player),
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "Pause automatically",
+        keywords: "auto suspend sleep headphones unplug disconnect device",
+        show: |ui, player, _| auto_pause_control(ui, player),
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "Huge songs",
+        keywords: "black midi large file size notes freeze limit skip",
+        show: |ui, player, _| huge_song_controls(ui, player),
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "When playback finishes",
+        keywords: "end queue song stop quit exit suspend sleep shutdown command timer",
+        show: |ui, player, _| end_action_controls(ui, player),
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "Follow file changes",
+        keywords: "reload live preview composer modified",
+        show: |ui, player, _| {
+            ui.add(toggle_row(
+                "Follow file changes",
+                "When the playing or inspected song is modified, reload it and keep the position",
+                &mut player.follow_file,
+            ));
+            ui.add_space(8.);
+        },
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "Program lock",
+        keywords: "instrument channel program change auto map preset soundfont",
+        show: |ui, player, gui| program_lock_controls(ui, player, gui),
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "Drum kit",
+        keywords: "percussion channel 10",
+        show: |ui, player, _| drum_kit_control(ui, player),
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "Audio profiles",
+        keywords: "reverb eq equalizer gain bass treble effects",
+        show: |ui, player, _| audio_profile_controls(ui, player),
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "Send MIDI clock",
+        keywords: "sync sequencer daw transport output port",
+        show: |ui, player, _| midi_clock_control(ui, player),
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "MIDI thru",
+        keywords: "output port forward events visualizer lighting",
+        show: |ui, player, _| midi_thru_control(ui, player),
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "Output device",
+        keywords: "audio sound card speakers headphones dac switch channels pair interface routing",
+        show: output_device_control,
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "Network stream",
+        keywords: "output http lan radio listen other device ogg port token",
+        show: stream_output_controls,
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "Remote control",
+        keywords: "network tcp api automation footswitch script port lan token",
+        show: remote_control_controls,
+    },
+    SettingsEntry {
+        category: Category::Playback,
+        label: "Global hotkeys",
+        keywords: "keyboard shortcuts keys",
+        show: global_hotkey_controls,
+    },
+    SettingsEntry {
+        category: Category::Developer,
+        label: "Block saving",
+        keywords: "debug",
+        show: |ui, player, _| {
+            ui.add(toggle_row(
+                "Block saving",
+                "Turning this on will prevent anything being saved",
+                &mut player.debug_block_saving,
+            ));
+        },
+    },
+    SettingsEntry {
+        category: Category::Developer,
+        label: "App directories",
+        keywords: "data state config folder",
+        show: |ui, _, gui| app_dir_controls(ui, gui),
+    },
+];
+
+/// Labels of the settings that can be shown, to find them from elsewhere
+pub fn setting_labels(show_developer_options: bool) -> Vec<&'static str> {
+    SETTINGS
+        .iter()
+        .filter(|entry| entry.category != Category::Developer || show_developer_options)
+        .map(|entry| entry.label)
+        .collect()
+}
+
 pub fn settings_modal(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
     let window_size = ctx.input(InputState::screen_rect).size() - Vec2 { x: 32., y: 64. };
     let modal_size = window_size.min(Vec2 { x: 600., y: 800. });
@@ -24,6 +341,14 @@ pub fn settings_modal(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
         .anchor(Align2::CENTER_CENTER, vec2(0., 0.))
         .open(&mut show_settings_modal)
         .show(ctx, |ui| {
+            ui.add(
+                TextEdit::singleline(&mut gui.settings_search)
+                    .hint_text("🔍 Search settings")
+                    .desired_width(f32::INFINITY),
+            );
+            ui.add_space(4.);
+            let query = gui.settings_search.trim().to_lowercase();
+
             ScrollArea::vertical().show(ui, |ui| {
                 ui.horizontal(|ui| {
                     ui.add_space(8.);
@@ -31,73 +356,993 @@ pub fn settings_modal(ctx: &Context, player: &mut Player, gui: &mut GuiState) {
                         ui.set_width(ui.available_width() - 8.);
                         ui.add_space(8.);
 
-                        category_heading(ui, "General Settings");
+                        let mut found = false;
+                        for category in Category::ALL {
+                            if category == Category::Developer && !gui.show_developer_options {
+                                continue;
+                            }
+                            let entries: Vec<&SettingsEntry> = SETTINGS
+                                .iter()
+                                .filter(|entry| entry.category == category && entry.matches(&query))
+                                .collect();
+                            if entries.is_empty() {
+                                continue;
+                            }
+                            found = true;
+                            category_heading(ui, category.get_title());
+                            for entry in entries {
+                                (entry.show)(ui, player, gui);
+                            }
+                        }
+                        if !found {
+                            ui.label("No settings match the search.");
+                        }
+                    });
+                });
+            });
+        });
+    gui.show_settings_modal = show_settings_modal;
+}
 
-                        theme_control(ui);
-                        ui.add(toggle_row(
-                            "Autosave",
-                            "Disable manual saving and use autosave for all playlists",
-                            &mut player.autosave,
-                        ));
-                        ui.add(toggle_row(
-                            "Show developer settings",
-                            "These are not useful to normal users",
-                            &mut gui.show_developer_options,
-                        ));
+fn app_dir_controls(ui: &mut Ui, gui: &mut GuiState) {
+    ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+        ui.vertical(|ui| {
+            ui.set_width(ui.available_width() - 32.);
+            ui.heading("App directories");
+            ui.label("Configuration and app memory");
+        });
+        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+            if ui.button("data dir").clicked() {
+                if let Err(e) = storage::open_path(&data_dir()) {
+                    gui.toast_error(e.to_string());
+                }
+            }
+            if ui.button("state dir").clicked() {
+                if let Err(e) = storage::open_path(&state_dir()) {
+                    gui.toast_error(e.to_string());
+                }
+            }
+            if ui.button("config dir").clicked() {
+                if let Err(e) = storage::open_path(&config_dir()) {
+                    gui.toast_error(e.to_string());
+                }
+            }
+        });
+    });
+}
 
-                        category_heading(ui, "Soundfont library");
+fn autosave_controls(ui: &mut Ui, player: &mut Player) {
+    ui.add(toggle_row(
+        "Autosave playlist files",
+        "Disable manual saving and save portable playlists automatically",
+        &mut player.autosave_portable,
+    ));
+    ui.add(toggle_row(
+        "Autosave app playlists",
+        "Save playlists stored in app memory periodically. Off: only on exit",
+        &mut player.autosave_app,
+    ));
+    ui.horizontal(|ui| {
+        ui.label("Autosave interval");
+        let mut seconds = player.get_autosave_interval().as_secs();
+        if ui
+            .add(
+                DragValue::new(&mut seconds)
+                    .range(AUTOSAVE_INTERVAL_RANGE)
+                    .suffix(" s"),
+            )
+            .changed()
+        {
+            player.set_autosave_interval(seconds);
+        }
+    });
+    ui.add_space(8.);
+}
 
-                        font_lib_paths(ui, &mut player.font_lib, gui);
+fn font_change_control(ui: &mut Ui, player: &mut Player) {
+    let mut instant = player.font_change_mode == FontChangeMode::Instant;
+    if ui
+        .add(toggle_row(
+            "Change soundfont instantly",
+            "Switch during playback without restarting the song. Off: use it from the next song",
+            &mut instant,
+        ))
+        .changed()
+    {
+        player.font_change_mode = if instant {
+            FontChangeMode::Instant
+        } else {
+            FontChangeMode::NextSong
+        };
+    }
+    ui.add_space(8.);
+}
+
+fn huge_song_controls(ui: &mut Ui, player: &mut Player) {
+    ui.label(
+        "Songs over a limit may freeze the synth, so playing one asks first. 0 turns a limit off.",
+    );
+    let limits = &mut player.huge_song_limits;
+    ui.horizontal(|ui| {
+        ui.label("File size");
+        ui.add(DragValue::new(&mut limits.max_size_mb).suffix(" MB"));
+    });
+    ui.horizontal(|ui| {
+        ui.label("Notes");
+        ui.add(DragValue::new(&mut limits.max_notes).speed(1000));
+    });
+    ui.add(toggle_row(
+        "Skip huge songs",
+        "Don't play them when the queue moves on by itself, or in party mode",
+        &mut limits.skip_in_queue,
+    ));
+    ui.add_space(8.);
+}
 
+fn end_action_controls(ui: &mut Ui, player: &mut Player) {
+    let end_action = &mut player.end_action;
+    ui.horizontal(|ui| {
+        ui.label("When");
+        ComboBox::from_id_salt("end_trigger")
+            .selected_text(end_action.trigger.get_name())
+            .show_ui(ui, |ui| {
+                for trigger in [EndTrigger::Queue, EndTrigger::Song] {
+                    ui.selectable_value(&mut end_action.trigger, trigger, trigger.get_name());
+                }
+            });
+        ComboBox::from_id_salt("end_action")
+            .selected_text(end_action.kind.get_name())
+            .show_ui(ui, |ui| {
+                for kind in EndActionKind::ALL {
+                    ui.selectable_value(&mut end_action.kind, kind, kind.get_name());
+                }
+            });
+    });
+    if end_action.kind == EndActionKind::Command {
+        ui.add(
+            TextEdit::singleline(&mut end_action.command)
+                .code_editor()
+                .hint_text("Shell command"),
+        );
+    }
+    if end_action.kind.is_destructive() {
+        ui.label(format!(
+            "Waits {} seconds first, so it can be cancelled.",
+            END_ACTION_DELAY.as_secs()
+        ));
+    }
+    ui.add_space(8.);
+}
+
+fn auto_limiter_control(ui: &mut Ui, player: &mut Player) {
+    let mut auto_limiter = player.get_auto_limiter();
+    if ui
+        .add(toggle_row(
+            "Auto-engage limiter",
+            "When a song clips, limit it for the rest of the song, even at normal volume",
+            &mut auto_limiter,
+        ))
+        .changed()
+    {
+        player.set_auto_limiter(auto_limiter);
+    }
+    ui.add_space(8.);
+}
+
+fn auto_pause_control(ui: &mut Ui, player: &mut Player) {
+    let mut auto_pause = player.get_auto_pause();
+    if ui
+        .add(toggle_row(
+            "Pause automatically",
+            "Pause when the computer goes to sleep, or headphones or the audio device are \
+            unplugged. Suspend and headphones are noticed on Linux only",
+            &mut auto_pause,
+        ))
+        .changed()
+    {
+        player.set_auto_pause(auto_pause);
+    }
+    ui.add_space(8.);
+}
+
+fn midi_clock_control(ui: &mut Ui, player: &mut Player) {
+    let mut midi_clock = player.get_midi_clock();
+    if ui
+        .add(toggle_row(
+            "Send MIDI clock",
+            "Sync other apps and gear to playback through the SfontPlayer MIDI port. Linux only",
+            &mut midi_clock,
+        ))
+        .changed()
+    {
+        player.set_midi_clock(midi_clock);
+    }
+    ui.add_space(8.);
+}
+
+fn midi_thru_control(ui: &mut Ui, player: &mut Player) {
+    let mut midi_thru = player.get_midi_thru();
+    if ui
+        .add(toggle_row(
+            "MIDI thru",
+            "Forward the played events to the SfontPlayer MIDI port. Linux only",
+            &mut midi_thru,
+        ))
+        .changed()
+    {
+        player.set_midi_thru(midi_thru);
+    }
+    ui.add_space(8.);
+}
+
+fn output_device_control(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    ui.horizontal(|ui| {
+        ui.label("Output device");
+        let selected = player.output_device.as_deref().unwrap_or("System default");
+        let open = ComboBox::from_id_salt("output_device")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                // Listing devices can be slow, so only once per opening
+                let devices = gui.output_devices.get_or_insert_with(output_device_names);
+                ui.selectable_value(&mut player.output_device, None, "System default");
+                for name in devices.iter() {
+                    ui.selectable_value(&mut player.output_device, Some(name.clone()), name);
+                }
+            })
+            .inner
+            .is_some();
+        if !open {
+            gui.output_devices = None;
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Outputs");
+        let open = ComboBox::from_id_salt("output_pair")
+            .selected_text(pair_name(player.output_pair))
+            .show_ui(ui, |ui| {
+                let device = player.output_device.as_deref();
+                let count = *gui
+                    .output_pair_count
+                    .get_or_insert_with(|| pair_count(device));
+                for pair in 0..count.max(player.output_pair + 1) {
+                    ui.selectable_value(&mut player.output_pair, pair, pair_name(pair));
+                }
+            })
+            .inner
+            .is_some();
+        if !open {
+            gui.output_pair_count = None;
+        }
+    });
+    ui.label(
+        "If the device is missing, the default one plays instead. \
+        Multi-channel interfaces can play to outputs other than 1/2.",
+    );
+    ui.add_space(8.);
+}
+
+fn stream_output_controls(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    let mut stream_output = player.get_stream_output();
+    if ui
+        .add(toggle_row(
+            "Network stream",
+            "Play as an OGG stream over HTTP instead of this device's audio output",
+            &mut stream_output,
+        ))
+        .changed()
+    {
+        if let Err(e) = player.set_stream_output(stream_output) {
+            gui.toast_error(format!("Couldn't start the network stream: {e}"));
+        }
+    }
+    ui.horizontal(|ui| {
+        ui.add(Label::new("Port").selectable(false));
+        let mut port = player.get_stream_port();
+        if ui
+            .add_enabled(
+                !player.get_stream_output(),
+                DragValue::new(&mut port).range(1024..=u16::MAX),
+            )
+            .changed()
+        {
+            player.set_stream_port(port);
+        }
+        if player.get_stream_output() {
+            let url = player.get_stream_url();
+            ui.label(&url);
+            if ui.button("Copy").clicked() {
+                ui.ctx().copy_text(url);
+            }
+        }
+    });
+    let mut stream_lan = player.get_stream_lan();
+    ui.add_enabled_ui(!player.get_stream_output(), |ui| {
+        if ui
+            .add(toggle_row(
+                "Allow other devices",
+                "Listen on every network interface. The URL includes a token.",
+                &mut stream_lan,
+            ))
+            .changed()
+        {
+            player.set_stream_lan(stream_lan);
+        }
+    });
+    if player.get_stream_lan() {
+        ui.horizontal(|ui| {
+            ui.add(Label::new("Token").selectable(false));
+            let token = player.get_stream_token();
+            if token.is_empty() {
+                ui.weak("Made when the stream starts");
+            } else {
+                ui.monospace(token);
+            }
+            if ui
+                .add_enabled(!player.get_stream_output(), Button::new("New token"))
+                .on_hover_text("Devices that used the old one can't connect anymore")
+                .clicked()
+            {
+                player.renew_stream_token();
+            }
+        });
+    }
+    ui.add_space(8.);
+}
+
+fn remote_control_controls(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    let mut remote_control = player.get_remote_control();
+    if ui
+        .add(toggle_row(
+            "Remote control",
+            "Take play, pause, skip, volume and playlist commands over TCP from this computer",
+            &mut remote_control,
+        ))
+        .changed()
+    {
+        if let Err(e) = player.set_remote_control(remote_control) {
+            gui.toast_error(format!("Couldn't start remote control: {e}"));
+        }
+    }
+    ui.horizontal(|ui| {
+        ui.add(Label::new("Port").selectable(false));
+        let mut port = player.get_remote_port();
+        if ui
+            .add_enabled(
+                !player.get_remote_control(),
+                DragValue::new(&mut port).range(1024..=u16::MAX),
+            )
+            .changed()
+        {
+            player.set_remote_port(port);
+        }
+        if player.get_remote_control() {
+            let host = if player.get_remote_lan() {
+                local_ip().map_or_else(|| "localhost".into(), |ip| ip.to_string())
+            } else {
+                "localhost".into()
+            };
+            ui.label(format!("{host}:{port}"));
+        }
+    });
+    let mut remote_lan = player.get_remote_lan();
+    ui.add_enabled_ui(!player.get_remote_control(), |ui| {
+        if ui
+            .add(toggle_row(
+                "Allow other computers",
+                "Listen on every network interface. Clients have to send \"auth <token>\" first.",
+                &mut remote_lan,
+            ))
+            .changed()
+        {
+            player.set_remote_lan(remote_lan);
+        }
+    });
+    if player.get_remote_lan() {
+        ui.horizontal(|ui| {
+            ui.add(Label::new("Token").selectable(false));
+            let token = player.get_remote_token().to_owned();
+            if token.is_empty() {
+                ui.weak("Made when the server starts");
+            } else {
+                ui.monospace(&token);
+                if ui.button("Copy").clicked() {
+                    ui.ctx().copy_text(token);
+                }
+            }
+            if ui
+                .add_enabled(!player.get_remote_control(), Button::new("New token"))
+                .on_hover_text("Clients that used the old one can't connect anymore")
+                .clicked()
+            {
+                player.renew_remote_token();
+            }
+        });
+    }
+    ui.add_space(8.);
+}
+
+fn scripting_controls(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    let mut scripting = player.get_scripting();
+    if ui
+        .add(toggle_row(
+            "User script",
+            "Run the hooks of script.rhai in the config dir when songs start and end",
+            &mut scripting,
+        ))
+        .changed()
+    {
+        player.set_scripting(scripting);
+    }
+    ui.add_enabled_ui(scripting, |ui| {
+        ui.horizontal(|ui| {
+            if ui.button("Edit script").clicked() {
+                if let Err(e) = storage::open_path(&script_path()) {
+                    gui.toast_error(e.to_string());
+                }
+            }
+            if ui.button("Reload script").clicked() {
+                player.reload_script();
+            }
+        });
+    });
+    ui.add_space(8.);
+}
+
+fn program_lock_controls(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    CollapsingHeader::new("Program lock").show(ui, |ui| {
+        ui.label("Locked channels ignore program changes in songs.");
+        ui.label("Playlist locks take priority over the ones for all playlists.");
+        ui.add_space(4.);
+
+        ui.label(RichText::new("All playlists").strong());
+        let mut program_locks = player.get_program_locks();
+        if program_lock_grid(ui, "global_program_locks", &mut program_locks) {
+            player.set_program_locks(program_locks);
+        }
+        ui.add_space(4.);
+
+        let index = player.get_playlist_idx();
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(format!("Playlist: {}", player.get_playlist().name)).strong());
+            let has_song = player.get_playlist().get_song_idx().is_some();
+            if ui
+                .add_enabled(has_song, Button::new("Auto-map"))
+                .on_hover_text(
+                    "Lock channels to the soundfont's presets that look like the selected \
+                    song's instruments. For soundfonts that don't follow General MIDI.",
+                )
+                .clicked()
+            {
+                match player.auto_map_program_locks(index) {
+                    Ok(0) => gui.toast_info("No presets look like the song's instruments."),
+                    Ok(count) => gui.toast_success(format!("Locked {count} channels.")),
+                    Err(e) => gui.toast_error(e.to_string()),
+                }
+            }
+        });
+        let mut program_locks = player.get_playlist().get_program_locks();
+        if program_lock_grid(ui, "playlist_program_locks", &mut program_locks) {
+            let _ = player.set_playlist_program_locks(index, program_locks);
+        }
+    });
+    ui.add_space(8.);
+}
+
+fn global_hotkey_controls(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    let mut global_hotkeys = player.get_global_hotkeys().clone();
+    let mut changed = false;
+
+    CollapsingHeader::new("Global hotkeys").show(ui, |ui| {
+        changed |= ui
+            .add(toggle_row(
+                "Enable global hotkeys",
+                "Control playback while another app is focused. Needs X11",
+                &mut global_hotkeys.enabled,
+            ))
+            .changed();
+
+        if let Some(action) = gui.recording_hotkey {
+            if let Some(hotkey) = record_hotkey(ui, gui) {
+                global_hotkeys.set_binding(action, Some(hotkey));
+                changed = true;
+            }
+        }
+
+        Grid::new("global_hotkeys")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for action in HotkeyAction::ALL {
+                    ui.label(action.get_name());
+                    let text = if gui.recording_hotkey == Some(action) {
+                        "Press keys…".into()
+                    } else {
+                        global_hotkeys
+                            .get_binding(action)
+                            .map_or_else(|| "None".into(), ToString::to_string)
+                    };
+                    if ui
+                        .button(text)
+                        .on_hover_text("Click, then press the key combination. Esc cancels.")
+                        .clicked()
+                    {
+                        gui.recording_hotkey = Some(action);
+                    }
+                    if ui
+                        .add_enabled(
+                            global_hotkeys.get_binding(action).is_some(),
+                            Button::new("🗙"),
+                        )
+                        .on_hover_text("Clear")
+                        .clicked()
+                    {
+                        global_hotkeys.set_binding(action, None);
+                        changed = true;
+                    }
+                    ui.end_row();
+                }
+            });
+    });
+    ui.add_space(8.);
+
+    if changed {
+        player.set_global_hotkeys(global_hotkeys);
+    }
+}
+
+/// Wait for a hotkey press. Returns the new binding once a usable combination is pressed.
+fn record_hotkey(ui: &Ui, gui: &mut GuiState) -> Option<Hotkey> {
+    ui.input_mut(|input| {
+        let (key, modifiers) = input.events.iter().find_map(|event| match event {
+            Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } => Some((*key, *modifiers)),
+            _ => None,
+        })?;
+        input.consume_key(modifiers, key);
+        if key == Key::Escape {
+            gui.recording_hotkey = None;
+            return None;
+        }
+        let hotkey = Hotkey {
+            ctrl: modifiers.command,
+            alt: modifiers.alt,
+            shift: modifiers.shift,
+            key: key.name().into(),
+        };
+        // Plain letters would make typing impossible elsewhere
+        let has_modifier = hotkey.ctrl || hotkey.alt;
+        let is_function_key = hotkey.key.len() > 1 && hotkey.key.starts_with('F');
+        if !Hotkey::is_supported_key(&hotkey.key) || !(has_modifier || is_function_key) {
+            return None;
+        }
+        gui.recording_hotkey = None;
+        Some(hotkey)
+    })
+}
+
+/// Force a percussion preset of the current soundfont on channel 10
+fn drum_kit_control(ui: &mut Ui, player: &mut Player) {
+    let current = player.get_drum_kit();
+    let kits: Vec<PresetInfo> = player
+        .get_active_font()
+        .map(|(_, font)| {
+            font.get_presets()
+                .iter()
+                .filter(|preset| preset.bank >= DRUM_BANK_OFFSET)
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    let kit_name = |drum_kit: DrumKit| {
+        let name = kits
+            .iter()
+            .find(|preset| preset.bank == drum_kit.bank && preset.program == drum_kit.program)
+            .map_or_else(
+                || general_midi::drum_kit_name(drum_kit.program),
+                |preset| preset.name.as_str(),
+            );
+        format!("{}:{} {name}", drum_kit.bank, drum_kit.program)
+    };
+
+    ui.horizontal(|ui| {
+        ui.label("Drum kit");
+        let selected_text = current.map_or_else(|| "From song".to_owned(), kit_name);
+        ComboBox::from_id_salt("drum_kit")
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                if ui
+                    .selectable_label(current.is_none(), "From song")
+                    .clicked()
+                {
+                    player.set_drum_kit(None);
+                }
+                for preset in &kits {
+                    let drum_kit = DrumKit {
+                        bank: preset.bank,
+                        program: preset.program,
+                    };
+                    if ui
+                        .selectable_label(current == Some(drum_kit), kit_name(drum_kit))
+                        .clicked()
+                    {
+                        player.set_drum_kit(Some(drum_kit));
+                    }
+                }
+            })
+            .response
+            .on_hover_text(
+                "Use this percussion preset on channel 10, ignoring bank and program changes.\n\
+                Kits are listed from the soundfont that is playing.",
+            );
+    });
+    if player.get_active_font().is_none() {
+        ui.weak("Play a song to list the kits of its soundfont.");
+    }
+    ui.add_space(8.);
+}
+
+/// Checkbox and program number for each channel. Returns true if something changed.
+fn program_lock_grid(ui: &mut Ui, id: &str, program_locks: &mut ProgramLocks) -> bool {
+    let mut changed = false;
+    Grid::new(id).num_columns(4).show(ui, |ui| {
+        for channel in 0..CHANNEL_COUNT {
+            let mut locked = program_locks.get(channel).is_some();
+            let mut program = program_locks.get(channel).unwrap_or(0);
+            let mut channel_changed = ui
+                .checkbox(&mut locked, format!("Channel {}", channel + 1))
+                .changed();
+            ui.add_enabled_ui(locked, |ui| {
+                let name = if channel == DRUM_CHANNEL {
+                    general_midi::drum_kit_name(program)
+                } else {
+                    general_midi::program_name(program)
+                };
+                channel_changed |= ui
+                    .add(DragValue::new(&mut program).range(0..=127))
+                    .on_hover_text(format!("Program number: {name}"))
+                    .changed();
+            });
+            if channel_changed {
+                program_locks.set(channel, locked.then_some(program));
+                changed = true;
+            }
+            if channel % 2 == 1 {
+                ui.end_row();
+            }
+        }
+    });
+    changed
+}
+
+fn touch_mode_control(ui: &mut Ui, gui: &mut GuiState) {
+    if ui
+        .add(toggle_row(
+            "Touch mode",
+            "Bigger controls and rows for touch screens. Long press opens context menus.",
+            &mut gui.touch_mode,
+        ))
+        .changed()
+    {
+        apply_touch_mode(ui.ctx(), gui);
+    }
+    ui.add_space(8.);
+}
+
+fn table_density_controls(ui: &mut Ui, gui: &mut GuiState) {
+    ui.label("Rows of the song and soundfont tables");
+    let density = &mut gui.table_density;
+    ui.horizontal(|ui| {
+        ui.label("Row height");
+        ui.add(Slider::new(&mut density.row_height, TBL_ROW_H_RANGE).suffix(" px"));
+    });
+    ui.horizontal(|ui| {
+        ui.label("Text size");
+        ui.add(Slider::new(&mut density.text_size, TBL_TEXT_SIZE_RANGE).suffix(" pt"))
+            .on_hover_text("Rows grow to fit the text");
+    });
+    if ui
+        .add_enabled(*density != TableDensity::default(), Button::new("Reset"))
+        .clicked()
+    {
+        *density = TableDensity::default();
+    }
+    ui.add_space(8.);
+}
+
+fn mouse_gesture_controls(ui: &mut Ui, gui: &mut GuiState) {
+    ui.label("Mouse wheel and middle click on the playback bar. Zero turns a wheel action off.");
+    let gestures = &mut gui.mouse_gestures;
+    ui.horizontal(|ui| {
+        ui.label("Wheel over volume");
+        ui.add(Slider::new(&mut gestures.volume_step, VOLUME_WHEEL_RANGE).suffix(" %"))
+            .on_hover_text("Volume change per wheel step. Hold ctrl for 1% steps");
+    });
+    ui.horizontal(|ui| {
+        ui.label("Wheel over seek bar");
+        ui.add(Slider::new(&mut gestures.seek_step, SEEK_WHEEL_RANGE).suffix(" s"))
+            .on_hover_text("Position change per wheel step");
+    });
+    ui.horizontal(|ui| {
+        ui.label("Middle click");
+        ComboBox::from_id_salt("middle_click")
+            .selected_text(gestures.middle_click.as_deref().unwrap_or("Nothing"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut gestures.middle_click, None, "Nothing");
+                for command in COMMANDS {
+                    ui.selectable_value(
+                        &mut gestures.middle_click,
+                        Some(command.name.to_owned()),
+                        command.name,
+                    );
+                }
+            });
+    });
+    if ui
+        .add_enabled(*gestures != MouseGestures::default(), Button::new("Reset"))
+        .clicked()
+    {
+        *gestures = MouseGestures::default();
+    }
+    ui.add_space(8.);
+}
+
+fn notification_controls(ui: &mut Ui, gui: &mut GuiState) {
+    ui.add(toggle_row(
+        "Song change notifications",
+        "Show a desktop notification when a new song starts",
+        &mut gui.notify_song_change,
+    ));
+    ui.add_enabled_ui(gui.notify_song_change, |ui| {
+        ui.add(toggle_row(
+            "Notify when focused",
+            "Also show notifications when the player window is focused",
+            &mut gui.notify_when_focused,
+        ));
+    });
+    ui.add_space(8.);
+}
+
+fn channel_color_controls(ui: &mut Ui, player: &mut Player) {
+    CollapsingHeader::new("Channel colors").show(ui, |ui| {
+        ui.label("Colors of MIDI channels in Now Playing and piano roll export.");
+        ui.add_space(4.);
+
+        let colors = &mut player.channel_colors;
+        ui.horizontal(|ui| {
+            ui.label("Palette");
+            ComboBox::from_id_salt("channel_palette")
+                .selected_text(colors.get_palette().get_name())
+                .show_ui(ui, |ui| {
+                    for palette in ChannelPalette::ALL {
                         if ui
-                            .add(toggle_row(
-                                "Search subdirectories",
-                                "Also search all subdirectories",
-                                &mut player.font_lib.crawl_subdirs,
-                            ))
-                            .changed()
+                            .selectable_label(colors.get_palette() == palette, palette.get_name())
+                            .clicked()
                         {
-                            player.font_lib.refresh();
-                        };
-
-                        if !gui.show_developer_options {
-                            return;
+                            colors.set_palette(palette);
                         }
-                        category_heading(ui, "Developer / Debug Settings");
-
-                        ui.add(toggle_row(
-                            "Block saving",
-                            "Turning this on will prevent anything being saved",
-                            &mut player.debug_block_saving,
-                        ));
-                        ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
-                            ui.vertical(|ui| {
-                                ui.set_width(ui.available_width() - 32.);
-                                ui.heading("App directories");
-                                ui.label("Configuration and app memory");
-                            });
-                            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                                if ui.button("data dir").clicked() {
-                                    if let Err(e) = open::that(
-                                        crate::player::serialize_player::data_dir().as_path(),
-                                    ) {
-                                        gui.toast_error(e.to_string());
-                                    }
-                                }
-                                if ui.button("state dir").clicked() {
-                                    if let Err(e) = open::that(
-                                        crate::player::serialize_player::state_dir().as_path(),
-                                    ) {
-                                        gui.toast_error(e.to_string());
-                                    }
-                                }
-                            });
+                    }
+                });
+        });
+        Grid::new("channel_colors").num_columns(8).show(ui, |ui| {
+            for channel in 0..CHANNEL_COUNT {
+                let mut color = colors.get_color(channel);
+                ui.horizontal(|ui| {
+                    ui.label(format!("{:>2}", channel + 1));
+                    if ui.color_edit_button_srgb(&mut color).changed() {
+                        colors.set_color(channel, color);
+                    }
+                });
+                if channel % 8 == 7 {
+                    ui.end_row();
+                }
+            }
+        });
+    });
+    ui.add_space(8.);
+}
+
+fn audio_profile_controls(ui: &mut Ui, player: &mut Player) {
+    CollapsingHeader::new("Audio profiles").show(ui, |ui| {
+        ui.label("Reverb, EQ and gain presets. Choose one per playlist in the File menu.");
+        ui.add_space(4.);
+
+        let mut audio_profiles = player.get_audio_profiles().clone();
+        let mut changed = false;
+        let mut remove = None;
+        for (index, profile) in audio_profiles.iter_mut().enumerate() {
+            CollapsingHeader::new(&profile.name)
+                .id_salt(("audio_profile", index))
+                .show(ui, |ui| {
+                    Grid::new(("audio_profile_grid", index))
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Name");
+                            changed |= ui
+                                .add(TextEdit::singleline(&mut profile.name).desired_width(128.))
+                                .changed();
+                            ui.end_row();
+                            ui.label("Synth effects");
+                            changed |= ui
+                                .checkbox(&mut profile.synth_effects, "Soundfont reverb & chorus")
+                                .changed();
+                            ui.end_row();
+                            ui.label("Gain");
+                            changed |= ui
+                                .add(Slider::new(&mut profile.gain, GAIN_RANGE).suffix(" dB"))
+                                .changed();
+                            ui.end_row();
+                            for (band, name) in profile.eq.iter_mut().zip(EQ_BAND_NAMES) {
+                                ui.label(name);
+                                changed |= ui
+                                    .add(Slider::new(band, GAIN_RANGE).suffix(" dB"))
+                                    .changed();
+                                ui.end_row();
+                            }
+                            ui.label("Reverb");
+                            changed |= ui
+                                .add(Slider::new(&mut profile.reverb_mix, 0.0..=1.0))
+                                .changed();
+                            ui.end_row();
+                            ui.label("Room size");
+                            changed |= ui
+                                .add(Slider::new(&mut profile.room_size, 0.0..=1.0))
+                                .changed();
+                            ui.end_row();
+                        });
+                    if ui.button("Remove").clicked() {
+                        remove = Some(index);
+                    }
+                });
+        }
+        if let Some(index) = remove {
+            audio_profiles.remove(index);
+            changed = true;
+        }
+        if ui.button("Add profile").clicked() {
+            let name = format!("Profile {}", audio_profiles.len() + 1);
+            audio_profiles.push(AudioProfile::new(name));
+            changed = true;
+        }
+        if changed {
+            player.set_audio_profiles(audio_profiles);
+        }
+    });
+    ui.add_space(8.);
+}
+
+fn playlist_template_controls(ui: &mut Ui, player: &mut Player) {
+    CollapsingHeader::new("New playlists").show(ui, |ui| {
+        ui.label("Defaults of newly created playlists.");
+        ui.add_space(4.);
+
+        let font_lib = &player.font_lib;
+        let template: &mut PlaylistTemplate = &mut player.playlist_template;
+        Grid::new("playlist_template")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Name");
+                ui.text_edit_singleline(&mut template.name_pattern)
+                    .on_hover_text(format!("{NAME_COUNTER} is replaced with a number"));
+                ui.end_row();
+
+                ui.label("Song list");
+                enum_combo(
+                    ui,
+                    "template_song_list_mode",
+                    &mut template.song_list_mode,
+                    &FileListMode::ALL,
+                );
+                ui.end_row();
+
+                ui.label("Song sort");
+                enum_combo(
+                    ui,
+                    "template_song_sort",
+                    &mut template.song_sort,
+                    &SongSort::ALL,
+                );
+                ui.end_row();
+
+                ui.label("Soundfont list");
+                enum_combo(
+                    ui,
+                    "template_font_list_mode",
+                    &mut template.font_list_mode,
+                    &FileListMode::ALL,
+                );
+                ui.end_row();
+
+                ui.label("Soundfont sort");
+                enum_combo(
+                    ui,
+                    "template_font_sort",
+                    &mut template.font_sort,
+                    &FontSort::ALL,
+                );
+                ui.end_row();
+
+                ui.label("Soundfont");
+                let selected_text = template.default_font.as_ref().map_or_else(
+                    || "None".to_owned(),
+                    |path| {
+                        path.file_stem()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into()
+                    },
+                );
+                ui.add_enabled_ui(template.font_list_mode == FileListMode::Manual, |ui| {
+                    ComboBox::from_id_salt("template_font")
+                        .selected_text(selected_text)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut template.default_font, None, "None");
+                            for font in font_lib.get_fonts() {
+                                ui.selectable_value(
+                                    &mut template.default_font,
+                                    Some(font.get_path()),
+                                    font.get_name(),
+                                );
+                            }
                         });
-                    });
                 });
+                ui.end_row();
             });
+    });
+    ui.add_space(8.);
+}
+
+fn enum_combo<T>(ui: &mut Ui, id: &str, value: &mut T, options: &[T])
+where
+    T: PartialEq + Copy + std::fmt::Display,
+{
+    ComboBox::from_id_salt(id)
+        .selected_text(value.to_string())
+        .show_ui(ui, |ui| {
+            for option in options {
+                ui.selectable_value(value, *option, option.to_string());
+            }
         });
-    gui.show_settings_modal = show_settings_modal;
+}
+
+fn confirmation_controls(ui: &mut Ui, gui: &mut GuiState) {
+    CollapsingHeader::new("Confirmations").show(ui, |ui| {
+        let confirmations = &mut gui.confirmations;
+        ui.add(toggle_row(
+            "Clear songs",
+            "Ask before removing all songs from a playlist",
+            &mut confirmations.clear_songs,
+        ));
+        ui.add(toggle_row(
+            "Clear soundfonts",
+            "Ask before removing all soundfonts from a playlist",
+            &mut confirmations.clear_fonts,
+        ));
+        ui.add(toggle_row(
+            "Close with unsaved changes",
+            "Ask before closing a playlist with unsaved changes. Off: discard them",
+            &mut confirmations.close_unsaved_playlist,
+        ));
+        ui.add(toggle_row(
+            "Refresh large playlists",
+            "Ask before refreshing a playlist with a lot of files",
+            &mut confirmations.large_refresh,
+        ));
+        ui.add(toggle_row(
+            "Reset to saved",
+            "Ask before discarding the unsaved changes of a playlist file",
+            &mut confirmations.revert_playlist,
+        ));
+        ui.add(toggle_row(
+            "Play huge songs",
+            "Ask before playing a song over the huge song limits",
+            &mut confirmations.play_huge_song,
+        ));
+    });
+    ui.add_space(8.);
 }
 
 fn category_heading<S>(ui: &mut Ui, title: S)
@@ -225,6 +1470,53 @@ fn font_lib_paths(ui: &mut Ui, font_lib: &mut FontLibrary, gui: &mut GuiState) {
     ui.add_space(8.);
 }
 
+fn font_chain_control(ui: &mut Ui, font_chain: &mut FontChain) {
+    ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+        ui.vertical(|ui| {
+            ui.set_width(ui.available_width() - 32.);
+            ui.heading("Soundfont fallback order");
+            ui.label("Where to look for a soundfont, from first to last");
+        });
+    });
+
+    for index in 0..font_chain.get_entries().len() {
+        let entry = font_chain.get_entries()[index];
+        let last = font_chain.get_entries().len() - 1;
+        ui.horizontal(|ui| {
+            let mut enabled = entry.enabled;
+            if ui.checkbox(&mut enabled, "").changed() {
+                let _ = font_chain.set_enabled(index, enabled);
+            }
+            if ui
+                .add_enabled(index > 0, Button::new("⏶").frame(false))
+                .on_hover_text("Try earlier")
+                .clicked()
+            {
+                let _ = font_chain.move_entry(index, index - 1);
+            }
+            if ui
+                .add_enabled(index < last, Button::new("⏷").frame(false))
+                .on_hover_text("Try later")
+                .clicked()
+            {
+                let _ = font_chain.move_entry(index, index + 1);
+            }
+            let label = ui.add_enabled(
+                entry.enabled,
+                Label::new(format!("{}. {}", index + 1, entry.link)).selectable(false),
+            );
+            if entry.link == FontChainLink::BuiltIn {
+                label.on_hover_text(
+                    "Simple synthesized tones, one per General MIDI instrument family, and a \
+                    basic drum kit. Enough to hear a song, but it won't sound like the real thing.",
+                );
+            }
+        });
+    }
+
+    ui.add_space(8.);
+}
+
 fn font_lib_table(ui: &mut Ui, font_lib: &mut FontLibrary, gui: &mut GuiState) {
     let tablebuilder = TableBuilder::new(ui)
         .striped(true)
@@ -3,11 +3,14 @@ use egui_extras::{Column, TableBuilder};
 
 use crate::{
     gui::keyboard_shortcuts::{
-        GUI_QUIT, GUI_SETTINGS, GUI_SHORTCUTS, GUI_SHOWFONTS, PLAYBACK_PLAYPAUSE, PLAYBACK_REPEAT,
+        GUI_COMPACT, GUI_NOWPLAYING, GUI_PALETTE, GUI_QUIT, GUI_SETTINGS, GUI_SHORTCUTS,
+        GUI_SHOWFONTS, PLAYBACK_GOTO_PLAYING, PLAYBACK_PLAYPAUSE, PLAYBACK_RANDOM, PLAYBACK_REPEAT,
         PLAYBACK_SHUFFLE, PLAYBACK_SKIP, PLAYBACK_SKIPBACK, PLAYBACK_STARTSTOP, PLAYBACK_VOLDN,
-        PLAYBACK_VOLUP, PLAYLIST, PLAYLIST_CREATE, PLAYLIST_DUPLICATE, PLAYLIST_MOVELEFT,
-        PLAYLIST_MOVERIGHT, PLAYLIST_OPEN, PLAYLIST_REMOVE, PLAYLIST_REOPEN, PLAYLIST_SAVE,
-        PLAYLIST_SAVEALL, PLAYLIST_SAVEAS, PLAYLIST_SWITCHLEFT, PLAYLIST_SWITCHRIGHT,
+        PLAYBACK_VOLDN_FINE, PLAYBACK_VOLUP, PLAYBACK_VOLUP_FINE, PLAYLIST, PLAYLIST_CREATE,
+        PLAYLIST_DUPLICATE, PLAYLIST_MOVELEFT, PLAYLIST_MOVERIGHT, PLAYLIST_OPEN, PLAYLIST_PASTE,
+        PLAYLIST_RECENT, PLAYLIST_REMOVE, PLAYLIST_REOPEN, PLAYLIST_SAVE, PLAYLIST_SAVEALL,
+        PLAYLIST_SAVEAS, PLAYLIST_SWITCHLEFT, PLAYLIST_SWITCHRIGHT, PLAYLIST_UNDO, SEEK_LARGE_STEP,
+        SEEK_STEP,
     },
     GuiState,
 };
@@ -86,6 +89,22 @@ pub fn shortcut_modal(ctx: &Context, gui: &mut GuiState) {
                                 ui.label(ctx.format_shortcut(&PLAYBACK_REPEAT));
                             });
                         });
+                        body.row(16., |mut row| {
+                            row.col(|ui| {
+                                add_shortcut_title(ui, "Go to playing song");
+                            });
+                            row.col(|ui| {
+                                ui.label(ctx.format_shortcut(&PLAYBACK_GOTO_PLAYING));
+                            });
+                        });
+                        body.row(16., |mut row| {
+                            row.col(|ui| {
+                                add_shortcut_title(ui, "Play random song");
+                            });
+                            row.col(|ui| {
+                                ui.label(ctx.format_shortcut(&PLAYBACK_RANDOM));
+                            });
+                        });
                         body.row(16., |mut row| {
                             row.col(|ui| {
                                 add_shortcut_title(ui, "Increase volume");
@@ -102,6 +121,34 @@ pub fn shortcut_modal(ctx: &Context, gui: &mut GuiState) {
                                 ui.label(ctx.format_shortcut(&PLAYBACK_VOLDN));
                             });
                         });
+                        body.row(16., |mut row| {
+                            row.col(|ui| {
+                                add_shortcut_title(ui, "Increase volume by 1%");
+                            });
+                            row.col(|ui| {
+                                ui.label(ctx.format_shortcut(&PLAYBACK_VOLUP_FINE));
+                            });
+                        });
+                        body.row(16., |mut row| {
+                            row.col(|ui| {
+                                add_shortcut_title(ui, "Decrease volume by 1%");
+                            });
+                            row.col(|ui| {
+                                ui.label(ctx.format_shortcut(&PLAYBACK_VOLDN_FINE));
+                            });
+                        });
+                        body.row(16., |mut row| {
+                            row.col(|ui| {
+                                add_shortcut_title(ui, "Seek (seek bar focused)");
+                            });
+                            row.col(|ui| {
+                                ui.label(format!(
+                                    "←/→: {} s, Shift: {} s",
+                                    SEEK_STEP.as_secs(),
+                                    SEEK_LARGE_STEP.as_secs()
+                                ));
+                            });
+                        });
 
                         // --- Playlists
 
@@ -215,6 +262,30 @@ pub fn shortcut_modal(ctx: &Context, gui: &mut GuiState) {
                                 ui.label(ctx.format_shortcut(&PLAYLIST_REOPEN));
                             });
                         });
+                        body.row(16., |mut row| {
+                            row.col(|ui| {
+                                add_shortcut_title(ui, "Undo remove / clear");
+                            });
+                            row.col(|ui| {
+                                ui.label(ctx.format_shortcut(&PLAYLIST_UNDO));
+                            });
+                        });
+                        body.row(16., |mut row| {
+                            row.col(|ui| {
+                                add_shortcut_title(ui, "Cycle recently used playlists");
+                            });
+                            row.col(|ui| {
+                                ui.label(ctx.format_shortcut(&PLAYLIST_RECENT));
+                            });
+                        });
+                        body.row(16., |mut row| {
+                            row.col(|ui| {
+                                add_shortcut_title(ui, "Paste file paths as songs");
+                            });
+                            row.col(|ui| {
+                                ui.label(ctx.format_shortcut(&PLAYLIST_PASTE));
+                            });
+                        });
 
                         // --- GUI
 
@@ -248,6 +319,30 @@ pub fn shortcut_modal(ctx: &Context, gui: &mut GuiState) {
                                 ui.label(ctx.format_shortcut(&GUI_SHORTCUTS));
                             });
                         });
+                        body.row(16., |mut row| {
+                            row.col(|ui| {
+                                add_shortcut_title(ui, "Command palette");
+                            });
+                            row.col(|ui| {
+                                ui.label(ctx.format_shortcut(&GUI_PALETTE));
+                            });
+                        });
+                        body.row(16., |mut row| {
+                            row.col(|ui| {
+                                add_shortcut_title(ui, "Toggle Now Playing screen");
+                            });
+                            row.col(|ui| {
+                                ui.label(ctx.format_shortcut(&GUI_NOWPLAYING));
+                            });
+                        });
+                        body.row(16., |mut row| {
+                            row.col(|ui| {
+                                add_shortcut_title(ui, "Toggle compact mode");
+                            });
+                            row.col(|ui| {
+                                ui.label(ctx.format_shortcut(&GUI_COMPACT));
+                            });
+                        });
                         body.row(16., |mut row| {
                             row.col(|ui| {
                                 add_shortcut_title(ui, "Quit the app");
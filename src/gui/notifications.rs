@@ -0,0 +1,61 @@
+//! Desktop notifications through the freedesktop notification service
+//!
+//! TODO: Other platforms
+
+#[cfg(target_os = "linux")]
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Id of the last notification, so a new one replaces it instead of piling up.
+#[cfg(target_os = "linux")]
+static LAST_NOTIFICATION: AtomicU32 = AtomicU32::new(0);
+
+/// Show the song that started playing. Sent from a thread, so a slow service won't stall the gui.
+pub fn notify_song_change(song: String, soundfont: Option<String>) {
+    #[cfg(target_os = "linux")]
+    std::thread::spawn(move || {
+        let body = soundfont.map_or_else(String::new, |font| format!("Soundfont: {font}"));
+        if let Err(e) = send_notification(&song, &body) {
+            println!("Couldn't show notification: {e}");
+        }
+    });
+    #[cfg(not(target_os = "linux"))]
+    let _ = (song, soundfont);
+}
+
+#[cfg(target_os = "linux")]
+fn send_notification(summary: &str, body: &str) -> Result<(), dbus::Error> {
+    use dbus::{
+        arg::{PropMap, Variant},
+        blocking::Connection,
+    };
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+    const EXPIRE_MS: i32 = 5000;
+
+    let connection = Connection::new_session()?;
+    let proxy = connection.with_proxy(
+        "org.freedesktop.Notifications",
+        "/org/freedesktop/Notifications",
+        TIMEOUT,
+    );
+    let mut hints = PropMap::new();
+    // Song changes aren't worth keeping in the notification history
+    hints.insert("transient".into(), Variant(Box::new(true)));
+    let (id,): (u32,) = proxy.method_call(
+        "org.freedesktop.Notifications",
+        "Notify",
+        (
+            "SfontPlayer",
+            LAST_NOTIFICATION.load(Ordering::Relaxed),
+            "audio-x-generic",
+            summary,
+            body,
+            Vec::<String>::new(),
+            hints,
+            EXPIRE_MS,
+        ),
+    )?;
+    LAST_NOTIFICATION.store(id, Ordering::Relaxed);
+    Ok(())
+}
@@ -0,0 +1,131 @@
+//! Now Playing screen: large display mode, readable from across the room.
+
+use eframe::egui::{
+    include_image, vec2, Align, Button, Image, ImageSource, Layout, Rect, Response, RichText,
+    Sense, Ui, UiBuilder,
+};
+
+use super::{
+    keyboard_shortcuts::GUI_NOWPLAYING,
+    playback_controls::{density_strip, seek_markers, seek_slider, time_readout},
+    GuiState,
+};
+use crate::player::{playlist::midi_meta::MidiMeta, Player};
+
+const TITLE_SIZE: f32 = 40.;
+const INFO_SIZE: f32 = 20.;
+const TRANSPORT_ICON_SIZE: f32 = 64.;
+
+pub fn now_playing(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    gui.visualizers.update(player);
+
+    ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+        if ui
+            .add(Button::new("🗙 Exit").frame(false))
+            .on_hover_text(format!(
+                "Leave Now Playing ({})",
+                ui.ctx().format_shortcut(&GUI_NOWPLAYING)
+            ))
+            .clicked()
+        {
+            gui.show_now_playing = false;
+        }
+        gui.visualizers.picker(ui);
+    });
+
+    ui.vertical_centered(|ui| {
+        ui.add_space(ui.available_height() * 0.1);
+        song_info(ui, player);
+        ui.add_space(32.);
+        gui.visualizers.draw(ui, player);
+        ui.add_space(32.);
+        progress(ui, player, gui);
+        ui.add_space(16.);
+        transport(ui, player);
+    });
+}
+
+fn song_info(ui: &mut Ui, player: &Player) {
+    let playlist = player.get_playing_playlist();
+    let title = player
+        .get_playing_song_idx()
+        .or_else(|| playlist.get_song_idx())
+        .and_then(|index| playlist.get_songs().get(index))
+        .map_or_else(|| "Nothing playing".into(), MidiMeta::get_name);
+    ui.label(RichText::new(title).size(TITLE_SIZE).strong());
+    ui.label(RichText::new(&playlist.name).size(INFO_SIZE).weak());
+
+    let font = player
+        .get_active_font()
+        .map_or_else(|| "No soundfont".into(), |(_, font)| font.get_name());
+    ui.label(RichText::new(format!("🎹 {font}")).size(INFO_SIZE));
+}
+
+fn progress(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    ui.spacing_mut().slider_width = ui.available_width().min(720.);
+    let response = seek_slider(ui, player, "now_playing");
+    density_strip(ui, player, response.rect);
+    seek_markers(ui, player, response.rect);
+
+    let text = gui
+        .time_readout
+        .format(player.get_playback_position(), player.get_playback_length());
+    time_readout(ui, gui, RichText::new(text).size(INFO_SIZE).monospace());
+}
+
+fn transport(ui: &mut Ui, player: &mut Player) {
+    let width = TRANSPORT_ICON_SIZE.mul_add(4., ui.spacing().item_spacing.x * 3.);
+    let rect = Rect::from_center_size(
+        [
+            ui.max_rect().center().x,
+            ui.cursor().top() + TRANSPORT_ICON_SIZE / 2.,
+        ]
+        .into(),
+        vec2(width, TRANSPORT_ICON_SIZE),
+    );
+    ui.allocate_new_ui(UiBuilder::new().max_rect(rect), |ui| {
+        ui.horizontal(|ui| {
+            if big_icon_button(ui, include_image!("../assets/icon_prev.svg"), "np_back").clicked() {
+                player.skip_back();
+            }
+            if player.is_paused() {
+                if big_icon_button(ui, include_image!("../assets/icon_play.svg"), "np_play")
+                    .clicked()
+                {
+                    if player.is_empty() {
+                        player.start();
+                    } else {
+                        player.play();
+                    }
+                }
+            } else if big_icon_button(ui, include_image!("../assets/icon_pause.svg"), "np_pause")
+                .clicked()
+            {
+                player.pause();
+            }
+            if big_icon_button(ui, include_image!("../assets/icon_next.svg"), "np_skip").clicked() {
+                player.skip();
+            }
+            ui.add_enabled_ui(!player.is_empty(), |ui| {
+                if big_icon_button(ui, include_image!("../assets/icon_stop.svg"), "np_stop")
+                    .clicked()
+                {
+                    player.stop();
+                }
+            });
+        });
+    });
+}
+
+/// Large version of the playback panel icon buttons
+fn big_icon_button(ui: &mut Ui, source: ImageSource, id: &str) -> Response {
+    ui.scope_builder(UiBuilder::new().id_salt(id).sense(Sense::click()), |ui| {
+        let color = ui.style().interact(&ui.response()).text_color();
+        ui.add(
+            Image::new(source)
+                .fit_to_exact_size(vec2(TRANSPORT_ICON_SIZE, TRANSPORT_ICON_SIZE))
+                .tint(color),
+        );
+    })
+    .response
+}
@@ -1,41 +1,148 @@
 use eframe::egui::{
-    include_image, Button, Image, ImageSource, Response, RichText, SelectableLabel, Sense, Slider,
-    Ui, UiBuilder,
+    include_image, pos2, vec2, Button, Id, Image, ImageSource, Key, Label, Modifiers,
+    PointerButton, ProgressBar, Rect, Response, RichText, SelectableLabel, Sense, Slider, Stroke,
+    TextEdit, Ui, UiBuilder,
 };
-use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Write, ops::RangeInclusive, time::Duration};
 
 use crate::{
-    player::{Player, RepeatMode},
+    player::{
+        audio::{markers::MarkerKind, speed::SPEED_RANGE},
+        playlist::font_meta::FontMeta,
+        Player, RepeatMode, MAX_VOLUME,
+    },
     GuiState,
 };
 
 const ICON_SIZE: f32 = 20.;
+/// Clickable width of a seek bar marker
+const MARKER_WIDTH: f32 = 6.;
+/// Height of the note density strip under the position slider
+const DENSITY_STRIP_HEIGHT: f32 = 3.;
+pub const VOLUME_WHEEL_RANGE: RangeInclusive<f32> = 0.0..=20.0;
+pub const SEEK_WHEEL_RANGE: RangeInclusive<f32> = 0.0..=60.0;
+
+use super::{
+    commands::find_command,
+    conversions::format_duration,
+    keyboard_shortcuts::{
+        PLAYBACK_RANDOM, SEEK_LARGE_STEP, SEEK_STEP, VOLUME_FINE_STEP, VOLUME_STEP,
+    },
+};
+
+/// What the time readout next to the seek bar shows. Click the readout to cycle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeReadout {
+    /// "01:23 / 04:56"
+    #[default]
+    Total,
+    /// "01:23"
+    Elapsed,
+    /// "-03:33"
+    Remaining,
+}
+impl TimeReadout {
+    pub fn format(self, pos: Duration, len: Duration) -> String {
+        match self {
+            Self::Total => format!("{} / {}", format_duration(pos), format_duration(len)),
+            Self::Elapsed => format_duration(pos),
+            Self::Remaining => format!("-{}", format_duration(len.saturating_sub(pos))),
+        }
+    }
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Total => Self::Elapsed,
+            Self::Elapsed => Self::Remaining,
+            Self::Remaining => Self::Total,
+        }
+    }
+}
+
+/// Mouse actions of the playback bar
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseGestures {
+    /// Volume percentage per wheel step over the volume control, 0 is off
+    pub volume_step: f32,
+    /// Seconds per wheel step over the seek bar, 0 is off
+    pub seek_step: f32,
+    /// Name of the command a middle click runs
+    pub middle_click: Option<String>,
+}
+impl Default for MouseGestures {
+    fn default() -> Self {
+        Self {
+            volume_step: VOLUME_STEP,
+            seek_step: SEEK_STEP.as_secs_f32(),
+            middle_click: Some("Play / Pause".into()),
+        }
+    }
+}
 
-use super::conversions::format_duration;
 pub fn playback_panel(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
     ui.horizontal(|ui| {
         playback_controls(ui, player, gui);
 
-        let slider_width = f32::max(ui.available_width() - 144., 64.);
-        position_control(ui, player, slider_width);
+        let slider_width = f32::max(ui.available_width() - 250., 64.);
+        position_control(ui, player, gui, slider_width);
 
-        volume_control(ui, player);
+        favorite_font_menu(ui, player, gui);
+        speed_control(ui, player);
+        volume_control(ui, player, gui.mouse_gestures.volume_step);
+        clip_indicator(ui, player);
     });
+    if player.get_cue().is_some() {
+        ui.horizontal(|ui| cue_controls(ui, player));
+    }
+    middle_click_action(ui, player, gui);
 }
 
-fn playback_controls(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
-    let (back_enabled, skip_enabled) = if player.get_playing_playlist().queue.is_empty() {
-        (false, false)
-    } else if player.get_repeat() == RepeatMode::Queue && player.is_playing() {
-        (true, true)
-    } else if let Some(idx) = player.get_playing_playlist().queue_idx {
-        (idx > 0, idx < player.get_playing_playlist().queue.len() - 1)
-    } else {
-        (false, false)
+/// Run the middle click command when the bar is middle-clicked anywhere
+fn middle_click_action(ui: &Ui, player: &mut Player, gui: &mut GuiState) {
+    let clicked = ui.input(|input| input.pointer.button_clicked(PointerButton::Middle));
+    if !clicked || !ui.rect_contains_pointer(ui.min_rect()) {
+        return;
+    }
+    let Some(command) = gui
+        .mouse_gestures
+        .middle_click
+        .as_deref()
+        .and_then(find_command)
+    else {
+        return;
     };
+    (command.run)(&ui.ctx().clone(), player, gui);
+}
+
+/// Cued song, and the action to bring it in
+fn cue_controls(ui: &mut Ui, player: &mut Player) {
+    let Some(cue) = player.get_cue() else {
+        return;
+    };
+    let crossfading = cue.is_crossfading();
+    ui.label(format!("Cued: {}", cue.get_name()));
+    if crossfading {
+        ui.add(ProgressBar::new(cue.get_progress()).desired_width(96.));
+        return;
+    }
+    if ui
+        .button("Crossfade now")
+        .on_hover_text("Fade the playing song out and the cued song in")
+        .clicked()
+    {
+        player.crossfade_now();
+    }
+    if ui.button("✖").on_hover_text("Stop cue").clicked() {
+        player.cancel_cue();
+    }
+}
+
+fn playback_controls(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    let (back_enabled, skip_enabled) = skip_availability(player);
 
     // Current song info
-    let current_hover_text = format!(
+    let mut current_hover_text = format!(
         "Currently {}: {}",
         if player.is_empty() {
             "selected"
@@ -47,6 +154,20 @@ fn playback_controls(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
             |index| player.get_playing_playlist().get_songs()[index].get_name()
         )
     );
+    if let Some((index, len)) = player.get_party_position() {
+        let _ = write!(
+            current_hover_text,
+            "\nPlaying all playlists: {} / {len}",
+            index + 1
+        );
+    }
+    if let Some((link, font)) = player.get_active_font() {
+        let _ = write!(
+            current_hover_text,
+            "\nSoundfont: {} ({link})",
+            font.get_name()
+        );
+    }
     if ui
         .add_enabled(
             player.get_playing_playlist().get_song_idx().is_some(),
@@ -55,20 +176,22 @@ fn playback_controls(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
         .on_hover_text(current_hover_text)
         .clicked()
     {
-        let _ = player.switch_to_playlist(player.get_playing_playlist_idx());
+        let _ = player.go_to_playing_song();
         gui.update_flags.scroll_to_song = true;
     }
 
     // Shuffle button
-    if ui
+    let shuffle_response = ui
         .add(SelectableLabel::new(
             player.get_shuffle(),
             RichText::new("🔀").size(ICON_SIZE),
         ))
-        .clicked()
-    {
+        .on_hover_text("Shuffle\nRight-click for seed");
+    if shuffle_response.clicked() {
         player.toggle_shuffle();
     };
+    shuffle_response.context_menu(|ui| shuffle_seed_menu(ui, player, gui));
+    random_song_button(ui, player, gui);
     // Repeat
     let repeat_text = if player.get_repeat() == RepeatMode::Song {
         "🔂"
@@ -117,6 +240,80 @@ fn playback_controls(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
     });
 }
 
+fn random_song_button(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    if ui
+        .add_enabled(
+            !player.get_playlist().get_songs().is_empty(),
+            Button::new(RichText::new("🎲").size(ICON_SIZE)).frame(false),
+        )
+        .on_hover_text(format!(
+            "Play random song ({})",
+            ui.ctx().format_shortcut(&PLAYBACK_RANDOM)
+        ))
+        .clicked()
+    {
+        if let Err(e) = player.play_random_song() {
+            gui.toast_error(e.to_string());
+        }
+    }
+}
+
+/// Are (back, skip) buttons usable
+fn skip_availability(player: &Player) -> (bool, bool) {
+    if let Some((idx, len)) = player.get_party_position() {
+        if player.get_repeat() == RepeatMode::Queue {
+            (true, true)
+        } else {
+            (idx > 0, idx + 1 < len)
+        }
+    } else if player.get_playing_playlist().get_queue().is_empty() {
+        (false, false)
+    } else if player.get_repeat() == RepeatMode::Queue && player.is_playing() {
+        (true, true)
+    } else if let Some(idx) = player.get_playing_playlist().get_queue().get_position() {
+        (
+            idx > 0,
+            idx + 1 < player.get_playing_playlist().get_queue().len(),
+        )
+    } else {
+        (false, false)
+    }
+}
+
+/// Show current shuffle seed and allow shuffling with a chosen one
+fn shuffle_seed_menu(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    if let Some(seed) = player.get_playing_playlist().get_shuffle_seed() {
+        ui.horizontal(|ui| {
+            ui.label(format!("Seed: {seed}"));
+            if ui.small_button("Copy").clicked() {
+                ui.ctx().copy_text(seed.to_string());
+                ui.close_menu();
+            }
+        });
+    } else {
+        ui.label("Queue is not shuffled");
+    }
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.add(
+            TextEdit::singleline(&mut gui.shuffle_seed_input)
+                .hint_text("Seed")
+                .desired_width(160.),
+        );
+        let seed = gui.shuffle_seed_input.trim().parse::<u64>().ok();
+        if ui
+            .add_enabled(seed.is_some(), Button::new("Shuffle"))
+            .on_disabled_hover_text("Enter a whole number")
+            .clicked()
+        {
+            if let Some(seed) = seed {
+                player.shuffle_with_seed(seed);
+            }
+            ui.close_menu();
+        }
+    });
+}
+
 /// Icon Button that reacts to hovering.
 /// Image should be monochromatic (white) as it'll be tinted to intended color.
 fn icon_button(ui: &mut Ui, source: ImageSource, id: &str) -> Response {
@@ -129,7 +326,38 @@ fn icon_button(ui: &mut Ui, source: ImageSource, id: &str) -> Response {
 }
 
 /// Song position slider
-fn position_control(ui: &mut Ui, player: &mut Player, width: f32) {
+fn position_control(ui: &mut Ui, player: &mut Player, gui: &mut GuiState, width: f32) {
+    ui.horizontal(|ui| {
+        ui.spacing_mut().slider_width = width;
+        let response = seek_slider(ui, player, "playback_panel");
+        density_strip(ui, player, response.rect);
+        seek_markers(ui, player, response.rect);
+        loop_region(ui, player, gui, &response);
+        if response.hovered() {
+            wheel_seek(ui, player, gui.mouse_gestures.seek_step);
+        }
+    });
+
+    let text = gui
+        .time_readout
+        .format(player.get_playback_position(), player.get_playback_length());
+    time_readout(ui, gui, RichText::new(text));
+}
+
+/// Clickable time label that cycles the readout format
+pub fn time_readout(ui: &mut Ui, gui: &mut GuiState, text: RichText) {
+    if ui
+        .add(Label::new(text).sense(Sense::click()))
+        .on_hover_text("Click to switch between total, elapsed and remaining time")
+        .clicked()
+    {
+        gui.time_readout = gui.time_readout.next();
+    }
+}
+
+/// Position slider that seeks the playing song. When focused, arrow keys seek in steps of
+/// `SEEK_STEP`, and with shift or page keys in steps of `SEEK_LARGE_STEP`.
+pub fn seek_slider(ui: &mut Ui, player: &mut Player, id_salt: &str) -> Response {
     let len = player.get_playback_length();
     let pos = player.get_playback_position();
     let mut pos_float = pos.as_secs_f64();
@@ -137,46 +365,343 @@ fn position_control(ui: &mut Ui, player: &mut Player, width: f32) {
     // This stops the slider from showing halfway if len is zero.
     let slider_len = if len.is_zero() { 1. } else { len.as_secs_f64() };
 
-    ui.horizontal(|ui| {
-        ui.spacing_mut().slider_width = width;
-        if ui
-            .add_enabled(
-                !len.is_zero(),
-                Slider::new(&mut pos_float, 0.0..=slider_len)
-                    .show_value(false)
-                    .trailing_fill(true),
-            )
-            .changed()
+    // The slider's own arrow key handling moves by pixels. Keys are taken before it sees them.
+    let slider_id_key = Id::new("seek_slider").with(id_salt);
+    let has_focus = ui
+        .data(|data| data.get_temp::<Id>(slider_id_key))
+        .is_some_and(|id| ui.memory(|memory| memory.has_focus(id)));
+    if has_focus && !len.is_zero() {
+        if let Some(target) = seek_key_target(ui, pos, len) {
+            player.seek_to(target);
+        }
+    }
+
+    let response = ui.add_enabled(
+        !len.is_zero(),
+        Slider::new(&mut pos_float, 0.0..=slider_len)
+            .show_value(false)
+            .trailing_fill(true),
+    );
+    ui.data_mut(|data| data.insert_temp(slider_id_key, response.id));
+    if response.changed() {
+        player.seek_to(Duration::from_secs_f64(pos_float));
+    }
+    response
+}
+
+/// Wheel up seeks forward
+fn wheel_seek(ui: &Ui, player: &mut Player, step: f32) {
+    let scroll = ui.input(|input| input.raw_scroll_delta.y);
+    if scroll == 0. || step <= 0. || player.is_empty() {
+        return;
+    }
+    let pos = player.get_playback_position();
+    let step = Duration::from_secs_f32(step);
+    let target = if scroll > 0. {
+        (pos + step).min(player.get_playback_length())
+    } else {
+        pos.saturating_sub(step)
+    };
+    player.seek_to(target);
+}
+
+/// Consume seek keys, and return where they lead.
+fn seek_key_target(ui: &Ui, pos: Duration, len: Duration) -> Option<Duration> {
+    ui.input_mut(|input| {
+        // Most specific first; shift is ignored when matching keys.
+        if input.consume_key(Modifiers::SHIFT, Key::ArrowLeft)
+            || input.consume_key(Modifiers::NONE, Key::PageDown)
+        {
+            Some(pos.saturating_sub(SEEK_LARGE_STEP))
+        } else if input.consume_key(Modifiers::SHIFT, Key::ArrowRight)
+            || input.consume_key(Modifiers::NONE, Key::PageUp)
         {
-            player.seek_to(Duration::from_secs_f64(pos_float));
+            Some((pos + SEEK_LARGE_STEP).min(len))
+        } else if input.consume_key(Modifiers::NONE, Key::ArrowLeft) {
+            Some(pos.saturating_sub(SEEK_STEP))
+        } else if input.consume_key(Modifiers::NONE, Key::ArrowRight) {
+            Some((pos + SEEK_STEP).min(len))
+        } else if input.consume_key(Modifiers::NONE, Key::Home) {
+            Some(Duration::ZERO)
+        } else if input.consume_key(Modifiers::NONE, Key::End) {
+            Some(len)
+        } else {
+            None
+        }
+    })
+}
+
+/// Heat strip under a position slider, brighter where more notes start.
+pub fn density_strip(ui: &Ui, player: &Player, slider_rect: Rect) {
+    let len = player.get_playback_length();
+    let density = player.get_note_density();
+    let max = density.get_max();
+    if len.is_zero() || max == 0 {
+        return;
+    }
+    let handle_radius = slider_rect.height() / 2.5;
+    let track = slider_rect.shrink2(vec2(handle_radius, 0.));
+    let second_width = track.width() / len.as_secs_f32();
+    let color = ui.visuals().selection.bg_fill;
+
+    for (second, &notes) in density.get_seconds().iter().enumerate() {
+        if notes == 0 {
+            continue;
+        }
+        let left = second_width.mul_add(second as f32, track.left());
+        let rect = Rect::from_min_max(
+            pos2(left, slider_rect.bottom() - DENSITY_STRIP_HEIGHT),
+            pos2(
+                (left + second_width).min(track.right()),
+                slider_rect.bottom(),
+            ),
+        );
+        let heat = notes as f32 / max as f32;
+        ui.painter()
+            .rect_filled(rect, 0., color.gamma_multiply(heat * 0.6));
+    }
+}
+
+/// Shade of the A-B loop on a position slider, and a right click menu to set it.
+fn loop_region(ui: &Ui, player: &mut Player, gui: &mut GuiState, response: &Response) {
+    let len = player.get_playback_length();
+    if len.is_zero() {
+        return;
+    }
+    let slider_rect = response.rect;
+    let handle_radius = slider_rect.height() / 2.5;
+    let track = slider_rect.shrink2(vec2(handle_radius, 0.));
+    let x_of = |time: Duration| {
+        track
+            .width()
+            .mul_add(time.div_duration_f32(len).min(1.), track.left())
+    };
+
+    let loop_points = player.get_loop_points();
+    if let Some((a, b)) = loop_points {
+        let rect = Rect::from_x_y_ranges(x_of(a)..=x_of(b), slider_rect.y_range());
+        ui.painter()
+            .rect_filled(rect, 0., ui.visuals().selection.bg_fill.gamma_multiply(0.3));
+    }
+
+    // Time under the pointer when the menu was opened
+    let time_id = response.id.with("loop_menu_time");
+    if response.secondary_clicked() {
+        if let Some(pointer) = response.interact_pointer_pos() {
+            let fraction = ((pointer.x - track.left()) / track.width()).clamp(0., 1.);
+            ui.data_mut(|data| data.insert_temp(time_id, len.mul_f32(fraction)));
+        }
+    }
+    response.context_menu(|ui| {
+        let time = ui.data(|data| data.get_temp::<Duration>(time_id));
+        let Some(time) = time else {
+            ui.close_menu();
+            return;
         };
+        let set_a = ui.button(format!("Set loop start (A) at {}", format_duration(time)));
+        let set_b = ui.button(format!("Set loop end (B) at {}", format_duration(time)));
+        let clear = ui.add_enabled(loop_points.is_some(), Button::new("Clear loop"));
+        let new_points = if set_a.clicked() {
+            let b = loop_points
+                .map(|(_, b)| b)
+                .filter(|&b| b > time)
+                .unwrap_or(len);
+            Some(Some((time, b)))
+        } else if set_b.clicked() {
+            let a = loop_points
+                .map(|(a, _)| a)
+                .filter(|&a| a < time)
+                .unwrap_or_default();
+            Some(Some((a, time)))
+        } else if clear.clicked() {
+            Some(None)
+        } else {
+            None
+        };
+        if let Some(points) = new_points {
+            if let Err(e) = player.set_loop_points(points) {
+                gui.toast_error(e.to_string());
+            }
+            ui.close_menu();
+        }
     });
+}
 
-    ui.label(format!("{}/{}", format_duration(pos), format_duration(len)));
+/// Marks for tempo changes and marker events on a position slider. Click to seek.
+pub fn seek_markers(ui: &Ui, player: &mut Player, slider_rect: Rect) {
+    let len = player.get_playback_length();
+    if len.is_zero() {
+        return;
+    }
+    // Slider handle doesn't reach the ends of the rect
+    let handle_radius = slider_rect.height() / 2.5;
+    let track = slider_rect.shrink2(vec2(handle_radius, 0.));
+    let mut seek = None;
+
+    for (index, marker) in player.get_song_markers().iter().enumerate() {
+        let x = track
+            .width()
+            .mul_add(marker.time.div_duration_f32(len), track.left());
+        let rect = Rect::from_center_size(
+            pos2(x, slider_rect.center().y),
+            vec2(MARKER_WIDTH, slider_rect.height()),
+        );
+        let response = ui
+            .interact(rect, ui.id().with(("seek_marker", index)), Sense::click())
+            .on_hover_text(format!(
+                "{}\n{}",
+                marker.label,
+                format_duration(marker.time)
+            ));
+        let color = if response.hovered() {
+            ui.visuals().strong_text_color()
+        } else {
+            ui.visuals().weak_text_color()
+        };
+        let height = match marker.kind {
+            MarkerKind::Tempo => slider_rect.height() / 4.,
+            MarkerKind::Text => slider_rect.height() / 2.,
+        };
+        ui.painter().line_segment(
+            [
+                pos2(x, slider_rect.bottom() - height),
+                pos2(x, slider_rect.bottom()),
+            ],
+            Stroke::new(1.5, color),
+        );
+        if response.clicked() {
+            seek = Some(marker.time);
+        }
+    }
+    if let Some(time) = seek {
+        player.seek_to(time);
+    }
 }
 
-fn volume_control(ui: &mut Ui, player: &mut Player) {
-    let speaker_icon_str = match player.get_volume() {
-        x if x == 0.0 => "🔇",
-        x if (0.0..33.0).contains(&x) => "🔈",
-        x if (33.0..66.0).contains(&x) => "🔉",
-        _ => "🔊",
-    };
+/// Quick-pick from favorite library soundfonts
+fn favorite_font_menu(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    let mut hover_text = "Favorite soundfonts".to_owned();
+    if player.get_playlist().get_font_idx().is_some() {
+        hover_text += "\nSoundfont is overridden by current playlist.";
+    }
+    let mut picked = None;
+    ui.menu_button(RichText::new("★").size(ICON_SIZE), |ui| {
+        let favorites = player.font_lib.get_favorite_fonts();
+        if favorites.is_empty() {
+            ui.label("Star soundfonts in the library to pick them here.");
+        }
+        let selected = player.font_lib.get_selected().map(FontMeta::get_path);
+        for font in favorites {
+            let path = font.get_path();
+            if ui
+                .selectable_label(selected.as_ref() == Some(&path), font.get_name())
+                .on_hover_text(path.to_string_lossy())
+                .clicked()
+            {
+                picked = Some(path);
+                ui.close_menu();
+            }
+        }
+    })
+    .response
+    .on_hover_text(hover_text);
 
-    ui.menu_button(RichText::new(speaker_icon_str).size(ICON_SIZE), |ui| {
-        let mut volume = player.get_volume();
+    if let Some(path) = picked {
+        if let Err(e) = player.select_library_font(path) {
+            gui.toast_error(e.to_string());
+        }
+    }
+}
+
+/// Tempo multiplier. Shows the speed, and opens a slider.
+fn speed_control(ui: &mut Ui, player: &mut Player) {
+    let speed = player.get_speed();
+    let text = RichText::new(format!("{speed:.2}×"));
+    let text = if (speed - 1.).abs() < f32::EPSILON {
+        text.weak()
+    } else {
+        text.strong()
+    };
+    ui.menu_button(text, |ui| {
+        let mut speed = player.get_speed();
         if ui
             .add(
-                Slider::new(&mut volume, 0.0..=100.)
+                Slider::new(&mut speed, SPEED_RANGE)
                     .vertical()
-                    .show_value(false)
-                    .trailing_fill(true),
+                    .logarithmic(true)
+                    .fixed_decimals(2)
+                    .suffix("×"),
             )
+            .on_hover_text("Playback speed. The pitch stays the same.")
             .changed()
         {
-            player.set_volume(volume);
+            player.set_speed(speed);
+        }
+        if ui.button("Reset").clicked() {
+            player.set_speed(1.);
         }
     });
+}
+
+/// `wheel_step`: volume percentage per wheel step over the control
+fn volume_control(ui: &mut Ui, player: &mut Player, wheel_step: f32) {
+    let speaker_icon_str = match player.get_volume() {
+        x if x == 0.0 => "🔇",
+        x if (0.0..33.0).contains(&x) => "🔈",
+        x if (33.0..66.0).contains(&x) => "🔉",
+        _ => "🔊",
+    };
+
+    let button_response = ui
+        .menu_button(RichText::new(speaker_icon_str).size(ICON_SIZE), |ui| {
+            let mut volume = player.get_volume();
+            if ui
+                .add(
+                    Slider::new(&mut volume, 0.0..=MAX_VOLUME)
+                        .vertical()
+                        .show_value(false)
+                        .trailing_fill(true),
+                )
+                .on_hover_text("Above 100%, a limiter keeps loud parts from clipping.")
+                .changed()
+            {
+                player.set_volume(volume);
+            }
+        })
+        .response;
+    let label_response = ui.label(format!("{:00}", player.get_volume()));
+
+    // Scroll over the volume control: ctrl for fine steps
+    if wheel_step > 0. && (button_response.hovered() || label_response.hovered()) {
+        let (scroll, fine) = ui.input(|input| (input.raw_scroll_delta.y, input.modifiers.ctrl));
+        if scroll != 0. {
+            let step = if fine { VOLUME_FINE_STEP } else { wheel_step };
+            player.set_volume(step.mul_add(scroll.signum(), player.get_volume()));
+        }
+    }
+}
 
-    ui.label(format!("{:00}", player.get_volume()));
+/// Lights up when the output has clipped. Click to reset.
+fn clip_indicator(ui: &mut Ui, player: &Player) {
+    let meter = player.get_clip_meter();
+    let clips = meter.get_clip_count();
+    let (text, color) = if clips > 0 {
+        (format!("● {clips}"), ui.visuals().error_fg_color)
+    } else {
+        ("●".into(), ui.visuals().weak_text_color())
+    };
+    let peak_db = 20. * meter.get_peak().max(f32::EPSILON).log10();
+    let mut hover_text = format!("Clipped samples: {clips}\nPeak: {peak_db:.1} dBFS");
+    if meter.is_limiting() {
+        hover_text += "\nLimiter engaged";
+    }
+    hover_text += "\n\nClick to reset";
+
+    if ui
+        .add(Button::new(RichText::new(text).color(color)).frame(false))
+        .on_hover_text(hover_text)
+        .clicked()
+    {
+        meter.reset();
+    }
 }
@@ -1,18 +1,22 @@
-use eframe::egui::{Button, Label, Layout, RichText, Sense, TextWrapMode, Ui};
+use eframe::egui::{Align, Button, Id, Label, Layout, RichText, Sense, TextWrapMode, Ui};
 use egui_extras::{Column, TableBuilder};
 use size_format::SizeFormatterBinary;
 
 use super::{
     actions,
     custom_controls::{circle_button, collapse_button, subheading},
-    GuiState, TBL_ROW_H,
+    GuiState,
 };
 use crate::player::{
     playlist::{enums::FileListMode, font_meta::FontMeta},
+    problems::ProblemLocation,
     soundfont_list::FontSort,
     Player,
 };
 
+/// Drag and drop payload: index of the dragged font
+struct FontDrag(usize);
+
 #[allow(clippy::too_many_lines)]
 pub fn soundfont_table(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
     ui.horizontal(|ui| {
@@ -40,18 +44,31 @@ pub fn soundfont_table(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
     }
     let manual_files = player.get_playlist().get_font_list_mode() == FileListMode::Manual;
 
-    let name_w = ui.available_width() - 64.;
+    gui.apply_table_text_size(ui);
+    let name_w = ui.available_width() - 80.;
 
-    let tablebuilder = TableBuilder::new(ui)
+    let mut tablebuilder = TableBuilder::new(ui)
         .striped(true)
         .sense(Sense::click())
+        .column(Column::exact(16.))
         .column(Column::exact(if manual_files { 16. } else { 0. }))
         .column(Column::exact(name_w))
         .column(Column::remainder());
+    if gui.update_flags.scroll_to_problem {
+        if let Some(ProblemLocation::Font { index, .. }) = gui.problem_target {
+            // The first row is the default font
+            tablebuilder = tablebuilder.scroll_to_row(index + 1, Some(Align::Center));
+        }
+    }
+    let playlist_idx = player.get_playlist_idx();
 
     let table = tablebuilder.header(20.0, |mut header| {
         let font_sort = player.get_playlist().get_font_sort();
 
+        header.col(|ui| {
+            ui.add(Label::new("☰").selectable(false))
+                .on_hover_text(format!("{}\nDrag fonts to reorder", FontSort::Custom));
+        });
         header.col(|_| {});
 
         header.col(|ui| {
@@ -94,7 +111,7 @@ pub fn soundfont_table(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
 
     table.body(|body| {
         body.rows(
-            TBL_ROW_H,
+            gui.row_height(),
             player.get_playlist().get_fonts().len() + 1,
             |mut row| {
                 if row.index() == 0 {
@@ -109,7 +126,19 @@ pub fn soundfont_table(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
                 let status = fontref.get_status();
 
                 row.set_selected(Some(index) == player.get_playlist().get_font_idx());
+                row.set_hovered(gui.is_problem_target(ProblemLocation::Font {
+                    playlist: playlist_idx,
+                    index,
+                }));
 
+                // Drag handle
+                row.col(|ui| {
+                    ui.dnd_drag_source(Id::new(("playlist_font", index)), FontDrag(index), |ui| {
+                        ui.add(Label::new("☰").selectable(false));
+                    })
+                    .response
+                    .on_hover_text("Drag to reorder");
+                });
                 // Remove button
                 row.col(|ui| {
                     if manual_files
@@ -153,10 +182,42 @@ pub fn soundfont_table(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
                 // Select
                 if row.response().clicked() {
                     let _ = player.get_playlist_mut().set_font_idx(Some(index));
-                    let _ = player.reload_font();
+                    if let Err(e) = player.apply_font_change() {
+                        gui.toast_error(e.to_string());
+                    }
+                }
+                // Reorder
+                let response = row.response();
+                if let Some(dragged) = response.dnd_hover_payload::<FontDrag>() {
+                    // The font lands below this one when dragged down
+                    let y = if dragged.0 < index {
+                        response.rect.bottom()
+                    } else {
+                        response.rect.top()
+                    };
+                    let stroke = response.ctx.style().visuals.selection.stroke;
+                    response.ctx.layer_painter(response.layer_id).hline(
+                        response.rect.x_range(),
+                        y,
+                        stroke,
+                    );
+                }
+                if let Some(dragged) = response.dnd_release_payload::<FontDrag>() {
+                    let _ = player.get_playlist_mut().move_font(dragged.0, index);
                 }
                 // Context menu
                 row.response().context_menu(|ui| {
+                    if ui
+                        .add_enabled(!player.is_empty(), Button::new("Change font now"))
+                        .on_hover_text("Switch without restarting the song")
+                        .clicked()
+                    {
+                        let _ = player.get_playlist_mut().set_font_idx(Some(index));
+                        if let Err(e) = player.reload_font() {
+                            gui.toast_error(e.to_string());
+                        }
+                        ui.close_menu();
+                    }
                     if ui.button("Refresh").clicked() {
                         player.get_playlist_mut().get_fonts_mut()[index].refresh();
                         ui.close_menu();
@@ -270,6 +331,8 @@ fn content_controls(ui: &mut Ui, player: &mut Player) {
 fn default_font_item(row: &mut egui_extras::TableRow<'_, '_>, player: &mut Player) {
     row.set_selected(player.get_playlist().get_font_idx().is_none());
 
+    // Drag handle
+    row.col(|_| {});
     // Remove button
     row.col(|_| {});
     // Filename
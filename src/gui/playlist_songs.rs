@@ -1,20 +1,28 @@
-use eframe::egui::{Align, Button, Label, Layout, RichText, Sense, TextWrapMode, Ui};
+use eframe::egui::{Align, Button, DragValue, Label, Layout, RichText, Sense, TextWrapMode, Ui};
 use egui_extras::{Column, TableBuilder};
 use size_format::SizeFormatterBinary;
-use std::time::Duration;
+use std::{fmt::Write, path::Path, time::Duration};
 
 use super::{
     actions,
     conversions::format_duration,
     custom_controls::{circle_button, subheading},
-    GuiState, TBL_ROW_H,
+    modals::confirm_dialog::{request_action, ConfirmAction},
+    storage::{self, FileDialog},
+    GuiState,
 };
-use crate::player::{
-    playlist::enums::{FileListMode, SongSort},
-    Player,
+use crate::{
+    midi_split,
+    player::{
+        playlist::{
+            enums::{FileListMode, SongSort},
+            font_meta::FONT_EXTENSIONS,
+        },
+        problems::ProblemLocation,
+        Player,
+    },
 };
 
-#[allow(clippy::too_many_lines)]
 pub fn playlist_song_panel(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
     ui.horizontal(|ui| {
         ui.add(subheading("Playlist"));
@@ -23,6 +31,38 @@ pub fn playlist_song_panel(ui: &mut Ui, player: &mut Player, gui: &mut GuiState)
 
     ui.separator();
 
+    // Status line goes first, so the table gets the remaining space.
+    ui.with_layout(Layout::bottom_up(Align::LEFT), |ui| {
+        song_summary(ui, player);
+        ui.separator();
+        ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
+            song_table(ui, player, gui);
+        });
+    });
+}
+
+/// Song count and total duration
+fn song_summary(ui: &mut Ui, player: &Player) {
+    let summary = player.get_playlist().get_song_summary();
+    let mut text = match summary.count {
+        1 => "1 song".to_owned(),
+        count => format!("{count} songs"),
+    };
+    if summary.count > 0 {
+        let _ = write!(text, ", {}", format_duration(summary.duration));
+    }
+    if summary.unknown > 0 {
+        let _ = write!(text, " + {} unknown", summary.unknown);
+    }
+    ui.add(
+        Label::new(RichText::new(text).small().weak())
+            .wrap_mode(TextWrapMode::Truncate)
+            .selectable(false),
+    );
+}
+
+#[allow(clippy::too_many_lines)]
+fn song_table(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
     let is_active_playlist =
         !player.is_playing() || player.get_playlist_idx() == player.get_playing_playlist_idx();
     if !is_active_playlist {
@@ -31,6 +71,7 @@ pub fn playlist_song_panel(ui: &mut Ui, player: &mut Player, gui: &mut GuiState)
         ui.style_mut().visuals.selection.stroke = ui.style().visuals.widgets.active.fg_stroke;
     }
 
+    gui.apply_table_text_size(ui);
     let width = ui.available_width() - 192.;
 
     let mut tablebuilder = TableBuilder::new(ui)
@@ -46,6 +87,12 @@ pub fn playlist_song_panel(ui: &mut Ui, player: &mut Player, gui: &mut GuiState)
             tablebuilder = tablebuilder.scroll_to_row(index, Some(Align::Center));
         }
     }
+    if gui.update_flags.scroll_to_problem {
+        if let Some(ProblemLocation::Song { index, .. }) = gui.problem_target {
+            tablebuilder = tablebuilder.scroll_to_row(index, Some(Align::Center));
+        }
+    }
+    let playlist_idx = player.get_playlist_idx();
 
     let table = tablebuilder.header(20.0, |mut header| {
         let song_sort = player.get_playlist().get_song_sort();
@@ -116,7 +163,7 @@ pub fn playlist_song_panel(ui: &mut Ui, player: &mut Player, gui: &mut GuiState)
 
     table.body(|body| {
         body.rows(
-            TBL_ROW_H,
+            gui.row_height(),
             player.get_playlist().get_songs().len(),
             |mut row| {
                 let index = row.index();
@@ -125,6 +172,7 @@ pub fn playlist_song_panel(ui: &mut Ui, player: &mut Player, gui: &mut GuiState)
                 let filepath = midiref.get_path();
                 let filesize = midiref.get_size();
                 let status = midiref.get_status();
+                let ending_issue = midiref.get_ending_issue();
                 let manual_files =
                     player.get_playlist().get_song_list_mode() == FileListMode::Manual;
 
@@ -133,6 +181,13 @@ pub fn playlist_song_panel(ui: &mut Ui, player: &mut Player, gui: &mut GuiState)
                     .unwrap_or(Duration::ZERO);
 
                 row.set_selected(Some(index) == player.get_playlist().get_song_idx());
+                row.set_hovered(
+                    gui.is_problem_target(ProblemLocation::Song {
+                        playlist: playlist_idx,
+                        index,
+                    }) || (gui.double_click_to_play
+                        && gui.selected_song == Some((playlist_idx, index))),
+                );
 
                 // Remove button
                 row.col(|ui| {
@@ -151,6 +206,10 @@ pub fn playlist_song_panel(ui: &mut Ui, player: &mut Player, gui: &mut GuiState)
                         if let Err(e) = &status {
                             ui.label(RichText::new("？")).on_hover_text(e.to_string());
                         }
+                        if let Some(issue) = ending_issue {
+                            ui.label(RichText::new("⚠").color(ui.visuals().warn_fg_color))
+                                .on_hover_text(issue.to_string());
+                        }
                         ui.add_enabled(
                             status.is_ok(),
                             Label::new(filename)
@@ -183,23 +242,57 @@ pub fn playlist_song_panel(ui: &mut Ui, player: &mut Player, gui: &mut GuiState)
                 });
 
                 // Select
-                if row.response().clicked() {
-                    let _ = player.get_playlist_mut().set_song_idx(Some(index));
-                    player.start();
+                let play = if gui.double_click_to_play {
+                    if row.response().clicked() {
+                        gui.selected_song = Some((playlist_idx, index));
+                    }
+                    row.response().double_clicked()
+                } else {
+                    row.response().clicked()
+                };
+                if play {
+                    request_action(
+                        player,
+                        gui,
+                        ConfirmAction::PlayHugeSong {
+                            playlist: playlist_idx,
+                            index,
+                        },
+                    );
                 }
 
                 // Context menu
                 row.response().context_menu(|ui| {
                     ui.add_enabled_ui(status.is_ok(), |ui| {
+                        if ui
+                            .button("Cue")
+                            .on_hover_text("Pre-listen while the playing song continues")
+                            .clicked()
+                        {
+                            if let Err(e) = player.cue_song(index) {
+                                gui.toast_error(e.to_string());
+                            }
+                            ui.close_menu();
+                        }
                         if ui.button("Open in inspector").clicked() {
                             gui.update_flags.open_midi_inspector = Some(filepath.clone());
                             ui.close_menu();
                         }
+                        if ui
+                            .button("Split by markers")
+                            .on_hover_text("Save each marked section as its own file")
+                            .clicked()
+                        {
+                            split_song(player, &filepath, gui);
+                            ui.close_menu();
+                        }
                     });
                     if ui.button("Refresh").clicked() {
                         player.get_playlist_mut().get_songs_mut()[index].refresh();
                         ui.close_menu();
                     }
+                    song_soundfont_menu(ui, player, index);
+                    song_range_menu(ui, player, index);
                     ui.add_enabled_ui(
                         player.get_playlist().get_song_list_mode() == FileListMode::Manual,
                         |ui| {
@@ -263,6 +356,115 @@ pub fn playlist_song_panel(ui: &mut Ui, player: &mut Player, gui: &mut GuiState)
     });
 }
 
+/// Split a medley into files. The parts are added to a manual playlist.
+fn split_song(player: &mut Player, filepath: &Path, gui: &mut GuiState) {
+    let mut dialog = FileDialog::new();
+    if let Some(dir) = filepath.parent() {
+        dialog = dialog.set_directory(dir);
+    }
+    let Some(out_dir) = dialog.pick_folder() else {
+        return;
+    };
+    match midi_split::split_file(filepath, &out_dir) {
+        Ok(paths) => {
+            gui.toast_success(format!("Split into {} songs", paths.len()));
+            let playlist = player.get_playlist_mut();
+            if playlist.get_song_list_mode() == FileListMode::Manual {
+                for path in paths {
+                    let _ = playlist.add_song(path);
+                }
+            }
+        }
+        Err(e) => gui.toast_error(e.to_string()),
+    }
+}
+
+/// Assign a soundfont to a single song
+fn song_soundfont_menu(ui: &mut Ui, player: &mut Player, index: usize) {
+    ui.menu_button("Song soundfont", |ui| {
+        let current = player.get_playlist().get_songs()[index]
+            .get_soundfont()
+            .cloned();
+        if let Some(path) = &current {
+            ui.label(
+                path.file_name()
+                    .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy()),
+            )
+            .on_hover_text(path.to_string_lossy());
+            ui.separator();
+        }
+        if ui.button("Choose file").clicked() {
+            if let Some(path) =
+                storage::pick_file(FileDialog::new().add_filter("Soundfonts", &FONT_EXTENSIONS))
+            {
+                let _ = player
+                    .get_playlist_mut()
+                    .set_song_soundfont(index, Some(path));
+            }
+            ui.close_menu();
+        }
+        if ui
+            .add_enabled(current.is_some(), Button::new("Clear"))
+            .on_hover_text("Use the fallback chain instead")
+            .clicked()
+        {
+            let _ = player.get_playlist_mut().set_song_soundfont(index, None);
+            ui.close_menu();
+        }
+    });
+}
+
+/// Play only part of a song
+fn song_range_menu(ui: &mut Ui, player: &mut Player, index: usize) {
+    ui.menu_button("Playback range", |ui| {
+        let song = &player.get_playlist().get_songs()[index];
+        let length = song.get_duration().unwrap_or_default().as_secs_f64();
+        let mut start = song.get_start().map(|start| start.as_secs_f64());
+        let mut end = song.get_end().map(|end| end.as_secs_f64());
+
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            let mut enabled = start.is_some();
+            changed |= ui.checkbox(&mut enabled, "Start").changed();
+            let mut value = start.unwrap_or(0.);
+            let max = end.unwrap_or(length);
+            changed |= ui
+                .add_enabled(
+                    enabled,
+                    DragValue::new(&mut value)
+                        .range(0.0..=max)
+                        .speed(0.1)
+                        .suffix(" s"),
+                )
+                .changed();
+            start = enabled.then_some(value);
+        });
+        ui.horizontal(|ui| {
+            let mut enabled = end.is_some();
+            changed |= ui.checkbox(&mut enabled, "End").changed();
+            let mut value = end.unwrap_or(length);
+            let min = start.unwrap_or(0.);
+            changed |= ui
+                .add_enabled(
+                    enabled,
+                    DragValue::new(&mut value)
+                        .range(min..=length)
+                        .speed(0.1)
+                        .suffix(" s"),
+                )
+                .changed();
+            end = enabled.then_some(value);
+        });
+        if changed {
+            let _ = player.get_playlist_mut().set_song_range(
+                index,
+                start.map(Duration::from_secs_f64),
+                end.map(Duration::from_secs_f64),
+            );
+        }
+    });
+}
+
 fn content_controls(ui: &mut Ui, player: &mut Player) {
     ui.horizontal(|ui| {
         let mut list_mode = player.get_playlist().get_song_list_mode();
@@ -0,0 +1,133 @@
+use eframe::egui::{Align, Button, Label, Layout, RichText, Sense, TextWrapMode, Ui};
+use egui_extras::{Column, TableBuilder};
+
+use super::custom_controls::subheading;
+use crate::{
+    player::{
+        problems::{Problem, ProblemLocation},
+        Player,
+    },
+    GuiState,
+};
+
+/// Files with errors, each linking to its row
+pub fn problems_panel(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    let problems = player.get_problems();
+
+    ui.horizontal(|ui| {
+        ui.add(subheading(format!("Problems ({})", problems.len())));
+        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+            if ui.add(Button::new("❌").frame(false)).clicked() {
+                gui.show_problems = false;
+            }
+            if ui
+                .button("Clear")
+                .on_hover_text("Forget files that failed to play")
+                .clicked()
+            {
+                player.clear_render_failures();
+            }
+        });
+    });
+    ui.separator();
+
+    if problems.is_empty() {
+        ui.label("No problems found.");
+        return;
+    }
+    problem_table(ui, player, gui, &problems);
+}
+
+fn problem_table(ui: &mut Ui, player: &mut Player, gui: &mut GuiState, problems: &[Problem]) {
+    let table = TableBuilder::new(ui)
+        .striped(true)
+        .auto_shrink(false)
+        .column(Column::initial(128.).resizable(true))
+        .column(Column::initial(192.).resizable(true))
+        .column(Column::remainder())
+        .sense(Sense::click());
+
+    table.body(|body| {
+        body.rows(gui.row_height(), problems.len(), |mut row| {
+            let problem = &problems[row.index()];
+            let place = place_name(player, problem.location);
+            let filename = problem.path.file_name().map_or_else(
+                || problem.path.to_string_lossy().to_string(),
+                |name| name.to_string_lossy().to_string(),
+            );
+
+            row.set_selected(
+                problem
+                    .location
+                    .is_some_and(|location| gui.is_problem_target(location)),
+            );
+
+            row.col(|ui| {
+                ui.add(
+                    Label::new(place)
+                        .wrap_mode(TextWrapMode::Truncate)
+                        .selectable(false),
+                );
+            });
+            row.col(|ui| {
+                ui.add(
+                    Label::new(filename)
+                        .wrap_mode(TextWrapMode::Truncate)
+                        .selectable(false),
+                )
+                .on_hover_text(problem.path.to_string_lossy());
+            });
+            row.col(|ui| {
+                ui.add(
+                    Label::new(RichText::new(&problem.message).color(ui.visuals().error_fg_color))
+                        .wrap_mode(TextWrapMode::Truncate)
+                        .selectable(false),
+                )
+                .on_hover_text(&problem.message);
+            });
+
+            if let Some(location) = problem.location {
+                if row.response().clicked() {
+                    go_to(player, gui, location);
+                }
+            }
+        });
+    });
+}
+
+/// Where the file is listed
+fn place_name(player: &Player, location: Option<ProblemLocation>) -> String {
+    match location {
+        Some(ProblemLocation::Song { playlist, .. }) => {
+            format!("Songs of {}", player.get_playlists()[playlist].name)
+        }
+        Some(ProblemLocation::Font { playlist, .. }) => {
+            format!("Fonts of {}", player.get_playlists()[playlist].name)
+        }
+        Some(ProblemLocation::Library { .. }) => "Soundfont library".into(),
+        None => "Not listed".into(),
+    }
+}
+
+/// Open the list that has the file and scroll to it
+fn go_to(player: &mut Player, gui: &mut GuiState, location: ProblemLocation) {
+    match location {
+        ProblemLocation::Song { playlist, .. } => {
+            if let Err(e) = player.switch_to_playlist(playlist) {
+                gui.toast_error(e.to_string());
+                return;
+            }
+        }
+        ProblemLocation::Font { playlist, .. } => {
+            if let Err(e) = player.switch_to_playlist(playlist) {
+                gui.toast_error(e.to_string());
+                return;
+            }
+            gui.show_font_library = true;
+            gui.show_playlist_fonts = true;
+        }
+        ProblemLocation::Library { .. } => gui.show_font_library = true,
+    }
+    gui.problem_target = Some(location);
+    gui.update_flags.scroll_to_problem = true;
+}
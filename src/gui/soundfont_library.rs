@@ -1,11 +1,14 @@
-use eframe::egui::{Button, Label, RichText, Sense, TextWrapMode, Ui};
+use std::path::PathBuf;
+
+use eframe::egui::{Align, Button, Label, RichText, Sense, Spinner, TextWrapMode, Ui};
 use egui_extras::{Column, TableBuilder};
 use size_format::SizeFormatterBinary;
 
-use super::{actions, TBL_ROW_H};
+use super::actions;
 use crate::{
     player::{
         playlist::{enums::FileListMode, font_meta::FontMeta},
+        problems::ProblemLocation,
         soundfont_list::FontSort,
         Player,
     },
@@ -17,12 +20,41 @@ pub fn soundfont_library(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
     ui.separator();
 
     if player.font_lib.get_fonts().is_empty() {
-        empty_lib_placeholder(ui, gui);
+        if player.is_library_loading() {
+            loading_placeholder(ui);
+        } else {
+            empty_lib_placeholder(ui, gui);
+        }
     } else {
         soundfont_table(ui, player, gui);
     }
 }
 
+/// Star a font for the quick-pick menu
+fn favorite_button(ui: &mut Ui, player: &mut Player, filepath: PathBuf) {
+    let favorite = player.font_lib.is_favorite(&filepath);
+    let (icon, hover_text) = if favorite {
+        ("★", "Remove from favorites")
+    } else {
+        ("☆", "Add to favorites")
+    };
+    if ui
+        .add(Button::new(icon).frame(false))
+        .on_hover_text(hover_text)
+        .clicked()
+    {
+        player.font_lib.set_favorite(filepath, !favorite);
+    }
+}
+
+fn loading_placeholder(ui: &mut Ui) {
+    ui.vertical_centered(|ui| {
+        ui.add_space(24.);
+        ui.add(Spinner::new());
+        ui.label("Loading soundfonts…");
+    });
+}
+
 fn empty_lib_placeholder(ui: &mut Ui, gui: &mut GuiState) {
     ui.vertical_centered(|ui| {
         ui.add_space(24.);
@@ -43,12 +75,18 @@ fn soundfont_table(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
         ui.style_mut().visuals.selection.stroke = ui.style().visuals.widgets.active.fg_stroke;
     }
 
+    gui.apply_table_text_size(ui);
     let name_w = ui.available_width() - 64.;
-    let tablebuilder = TableBuilder::new(ui)
+    let mut tablebuilder = TableBuilder::new(ui)
         .striped(true)
         .sense(Sense::click())
         .column(Column::exact(name_w))
         .column(Column::remainder());
+    if gui.update_flags.scroll_to_problem {
+        if let Some(ProblemLocation::Library { index }) = gui.problem_target {
+            tablebuilder = tablebuilder.scroll_to_row(index, Some(Align::Center));
+        }
+    }
 
     let table = tablebuilder.header(20.0, |mut header| {
         let font_sort = player.font_lib.get_sort();
@@ -92,112 +130,132 @@ fn soundfont_table(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
     });
 
     table.body(|body| {
-        body.rows(TBL_ROW_H, player.font_lib.get_fonts().len(), |mut row| {
-            let index = row.index();
-            let fontref = &player.font_lib.get_fonts()[index];
-            let filename = fontref.get_name();
-            let filepath = fontref.get_path();
-            let filesize = fontref.get_size();
-            let status = fontref.get_status();
-
-            row.set_selected(Some(index) == player.font_lib.get_selected_index());
-
-            // Filename
-            row.col(|ui| {
-                ui.horizontal(|ui| {
-                    if let Err(e) = &status {
-                        ui.label(RichText::new("？")).on_hover_text(e.to_string());
-                    }
-                    let label_resp = ui
-                        .add_enabled(
-                            status.is_ok(),
-                            Label::new(filename)
-                                .wrap_mode(TextWrapMode::Truncate)
-                                .selectable(false),
-                        )
-                        .on_hover_text(filepath.to_string_lossy())
-                        .on_disabled_hover_text(filepath.to_string_lossy());
-                    if playlist_font_override {
-                        label_resp.on_hover_text("Soundfont is overridden by current playlist.");
-                    }
+        body.rows(
+            gui.row_height(),
+            player.font_lib.get_fonts().len(),
+            |mut row| {
+                let index = row.index();
+                let fontref = &player.font_lib.get_fonts()[index];
+                let filename = fontref.get_name();
+                let filepath = fontref.get_path();
+                let filesize = fontref.get_size();
+                let status = fontref.get_status();
+
+                row.set_selected(Some(index) == player.font_lib.get_selected_index());
+                row.set_hovered(gui.is_problem_target(ProblemLocation::Library { index }));
+
+                // Filename
+                row.col(|ui| {
+                    ui.horizontal(|ui| {
+                        favorite_button(ui, player, filepath.clone());
+                        if let Err(e) = &status {
+                            ui.label(RichText::new("？")).on_hover_text(e.to_string());
+                        }
+                        let label_resp = ui
+                            .add_enabled(
+                                status.is_ok(),
+                                Label::new(filename)
+                                    .wrap_mode(TextWrapMode::Truncate)
+                                    .selectable(false),
+                            )
+                            .on_hover_text(filepath.to_string_lossy())
+                            .on_disabled_hover_text(filepath.to_string_lossy());
+                        if playlist_font_override {
+                            label_resp
+                                .on_hover_text("Soundfont is overridden by current playlist.");
+                        }
+                    });
+                });
+
+                // File size
+                row.col(|ui| {
+                    let size_str = filesize.map_or_else(
+                        || "??".into(),
+                        |size| format!("{}B", SizeFormatterBinary::new(size)),
+                    );
+                    ui.add(
+                        Label::new(size_str)
+                            .wrap_mode(TextWrapMode::Extend)
+                            .selectable(false),
+                    );
                 });
-            });
-
-            // File size
-            row.col(|ui| {
-                let size_str = filesize.map_or_else(
-                    || "??".into(),
-                    |size| format!("{}B", SizeFormatterBinary::new(size)),
-                );
-                ui.add(
-                    Label::new(size_str)
-                        .wrap_mode(TextWrapMode::Extend)
-                        .selectable(false),
-                );
-            });
-
-            // Select
-            if row.response().clicked() {
-                let _ = player.font_lib.select(Some(index));
-                let _ = player.reload_font();
-            }
 
-            // Context menu
-            row.response().context_menu(|ui| {
-                if ui.button("Refresh").clicked() {
-                    if let Ok(font) = player.font_lib.get_font_mut(index) {
-                        font.refresh();
+                // Select
+                if row.response().clicked() {
+                    let _ = player.font_lib.select(Some(index));
+                    if let Err(e) = player.apply_font_change() {
+                        gui.toast_error(e.to_string());
                     }
-                    ui.close_menu();
                 }
-                actions::open_file_dir(ui, &player.font_lib.get_fonts()[index].get_path(), gui);
-
-                ui.menu_button("Add to playlist", |ui| {
-                    let Ok(filepath) = player.font_lib.get_font(index).map(FontMeta::get_path)
-                    else {
-                        ui.label("Failed to get font");
-                        return;
-                    };
-                    if ui.button("➕ New playlist").clicked() {
-                        player.new_playlist();
-                        let playlist_index = player.get_playlists().len() - 1;
-                        let _ =
-                            player.get_playlists_mut()[playlist_index].add_font(filepath.clone());
+
+                // Context menu
+                row.response().context_menu(|ui| {
+                    if ui
+                        .add_enabled(!player.is_empty(), Button::new("Change font now"))
+                        .on_hover_text("Switch without restarting the song")
+                        .clicked()
+                    {
+                        let _ = player.font_lib.select(Some(index));
+                        if let Err(e) = player.reload_font() {
+                            gui.toast_error(e.to_string());
+                        }
+                        ui.close_menu();
                     }
-                    for i in 0..player.get_playlists().len() {
-                        let playlist = &player.get_playlists_mut()[i];
-
-                        let already_contains = playlist.contains_font(&filepath);
-                        let dir_list = playlist.get_font_list_mode() != FileListMode::Manual;
-
-                        let hovertext = if dir_list {
-                            "Can't manually add files to directory list."
-                        } else if already_contains {
-                            "Playlist already contains this file."
-                        } else {
-                            ""
+                    if ui.button("Refresh").clicked() {
+                        if let Ok(font) = player.font_lib.get_font_mut(index) {
+                            font.refresh();
+                        }
+                        ui.close_menu();
+                    }
+                    actions::open_file_dir(ui, &player.font_lib.get_fonts()[index].get_path(), gui);
+
+                    ui.menu_button("Add to playlist", |ui| {
+                        let Ok(filepath) = player.font_lib.get_font(index).map(FontMeta::get_path)
+                        else {
+                            ui.label("Failed to get font");
+                            return;
                         };
+                        if ui.button("➕ New playlist").clicked() {
+                            player.new_playlist();
+                            let playlist_index = player.get_playlists().len() - 1;
+                            let _ = player.get_playlists_mut()[playlist_index]
+                                .add_font(filepath.clone());
+                        }
+                        for i in 0..player.get_playlists().len() {
+                            let playlist = &player.get_playlists_mut()[i];
 
-                        if ui
-                            .add_enabled(
-                                !already_contains && !dir_list,
-                                Button::new(&playlist.name),
-                            )
-                            .on_disabled_hover_text(hovertext)
-                            .clicked()
-                        {
-                            let _ = player.get_playlists_mut()[i].add_font(filepath.clone());
-                            ui.close_menu();
+                            let already_contains = playlist.contains_font(&filepath);
+                            let dir_list = playlist.get_font_list_mode() != FileListMode::Manual;
+
+                            let hovertext = if dir_list {
+                                "Can't manually add files to directory list."
+                            } else if already_contains {
+                                "Playlist already contains this file."
+                            } else {
+                                ""
+                            };
+
+                            if ui
+                                .add_enabled(
+                                    !already_contains && !dir_list,
+                                    Button::new(&playlist.name),
+                                )
+                                .on_disabled_hover_text(hovertext)
+                                .clicked()
+                            {
+                                let _ = player.get_playlists_mut()[i].add_font(filepath.clone());
+                                ui.close_menu();
+                            }
                         }
+                    });
+
+                    if ui.button("Copy path").clicked() {
+                        ui.output_mut(|o| o.copied_text = filepath.to_string_lossy().into());
+                        ui.close_menu();
+                        gui.toast_success("Copied");
                     }
                 });
-
-                if ui.button("Copy path").clicked() {
-                    ui.output_mut(|o| o.copied_text = filepath.to_string_lossy().into());
-                    ui.close_menu();
-                    gui.toast_success("Copied");
-                }
-            });
-        });
+            },
+        );
     });
 }
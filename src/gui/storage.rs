@@ -0,0 +1,181 @@
+//! File access that also works in a sandbox
+//!
+//! Flatpak apps only see the files the user picked, through the document portal at
+//! `/run/user/$UID/doc/`. The file chooser portal exports picks just for the running session, so
+//! picked paths are exported again as persistent documents before the app remembers them.
+//! Outside a sandbox, paths are used as they are.
+//!
+//! The browser doesn't hand out paths at all. There, picked files are read into memory, and
+//! [`take_picked`] hands them over to be added to the open playlist like dropped files.
+
+use std::path::{Path, PathBuf};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use rfd::FileDialog;
+#[cfg(target_arch = "wasm32")]
+pub use web::{take_picked, FileDialog};
+
+pub fn pick_file(dialog: FileDialog) -> Option<PathBuf> {
+    dialog.pick_file().map(persist)
+}
+pub fn pick_files(dialog: FileDialog) -> Option<Vec<PathBuf>> {
+    dialog
+        .pick_files()
+        .map(|paths| paths.into_iter().map(persist).collect())
+}
+pub fn pick_folder(dialog: FileDialog) -> Option<PathBuf> {
+    dialog.pick_folder().map(persist)
+}
+pub fn pick_folders(dialog: FileDialog) -> Option<Vec<PathBuf>> {
+    dialog
+        .pick_folders()
+        .map(|paths| paths.into_iter().map(persist).collect())
+}
+
+/// Open a file or directory in its default app
+pub fn open_path(path: &Path) -> anyhow::Result<()> {
+    #[cfg(target_os = "linux")]
+    if portal::is_sandboxed() {
+        return pollster::block_on(portal::open_path(path));
+    }
+    #[cfg(target_arch = "wasm32")]
+    anyhow::bail!("Can't open {} from the browser", path.display());
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        open::that(path)?;
+        Ok(())
+    }
+}
+
+/// Path that stays accessible after a restart. Falls back to the original path.
+#[cfg_attr(not(target_os = "linux"), allow(clippy::missing_const_for_fn))]
+fn persist(path: PathBuf) -> PathBuf {
+    #[cfg(target_os = "linux")]
+    if portal::is_sandboxed() {
+        return match pollster::block_on(portal::persist(&path)) {
+            Ok(persistent) => persistent,
+            Err(e) => {
+                println!("Couldn't keep access to {}: {e}", path.display());
+                path
+            }
+        };
+    }
+    path
+}
+
+#[cfg(target_os = "linux")]
+mod portal {
+    use std::{
+        fs::File,
+        path::{Path, PathBuf},
+        sync::OnceLock,
+    };
+
+    use anyhow::Context;
+    use ashpd::{
+        desktop::open_uri::{OpenDirectoryRequest, OpenFileRequest},
+        documents::{DocumentFlags, Documents},
+    };
+
+    pub fn is_sandboxed() -> bool {
+        static SANDBOXED: OnceLock<bool> = OnceLock::new();
+        *SANDBOXED.get_or_init(|| Path::new("/.flatpak-info").exists())
+    }
+
+    pub async fn open_path(path: &Path) -> anyhow::Result<()> {
+        let file = File::open(path)?;
+        if path.is_dir() {
+            OpenDirectoryRequest::default().send(&file).await?;
+        } else {
+            OpenFileRequest::default().send_file(&file).await?;
+        }
+        Ok(())
+    }
+
+    /// Export the path as a persistent document. Paths the sandbox can see directly aren't
+    /// portal documents, and stay as they are.
+    pub async fn persist(path: &Path) -> anyhow::Result<PathBuf> {
+        let documents = Documents::new().await?;
+        let mount_point = documents.mount_point().await?;
+        let mount_point: &Path = mount_point.as_ref();
+        if !path.starts_with(mount_point) {
+            return Ok(path.to_owned());
+        }
+        let name = path.file_name().context("No file name")?;
+
+        let mut flags = DocumentFlags::ReuseExisting | DocumentFlags::Persistent;
+        if path.is_dir() {
+            flags |= DocumentFlags::ExportDirectory;
+        }
+        let (ids, _) = documents
+            .add_full(&[File::open(path)?], flags, None, &[])
+            .await?;
+        let id = ids.first().context("No document id")?;
+        Ok(mount_point.join(id.as_ref()).join(name))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use std::{
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use parking_lot::{const_mutex, Mutex};
+    use rfd::AsyncFileDialog;
+
+    /// Files read since last call, as (name, contents)
+    static PICKED: Mutex<Vec<(PathBuf, Arc<[u8]>)>> = const_mutex(Vec::new());
+
+    /// Stand-in for the native dialog. Picks that would return paths give nothing, except
+    /// picking files, which reads them for [`take_picked`].
+    pub struct FileDialog(AsyncFileDialog);
+    impl FileDialog {
+        pub fn new() -> Self {
+            Self(AsyncFileDialog::new())
+        }
+        pub fn add_filter(self, name: impl Into<String>, extensions: &[impl ToString]) -> Self {
+            Self(self.0.add_filter(name, extensions))
+        }
+        pub fn set_title(self, title: impl Into<String>) -> Self {
+            Self(self.0.set_title(title))
+        }
+        pub fn set_file_name(self, file_name: impl Into<String>) -> Self {
+            Self(self.0.set_file_name(file_name))
+        }
+        pub fn set_directory<P: AsRef<Path>>(self, path: P) -> Self {
+            Self(self.0.set_directory(path))
+        }
+
+        pub fn pick_files(self) -> Option<Vec<PathBuf>> {
+            wasm_bindgen_futures::spawn_local(async move {
+                for file in self.0.pick_files().await.unwrap_or_default() {
+                    let bytes = file.read().await;
+                    PICKED.lock().push((file.file_name().into(), bytes.into()));
+                }
+            });
+            None
+        }
+        #[allow(clippy::unused_self)]
+        pub fn pick_file(self) -> Option<PathBuf> {
+            None
+        }
+        #[allow(clippy::unused_self)]
+        pub fn pick_folder(self) -> Option<PathBuf> {
+            None
+        }
+        #[allow(clippy::unused_self)]
+        pub fn pick_folders(self) -> Option<Vec<PathBuf>> {
+            None
+        }
+        #[allow(clippy::unused_self)]
+        pub fn save_file(self) -> Option<PathBuf> {
+            None
+        }
+    }
+
+    pub fn take_picked() -> Vec<(PathBuf, Arc<[u8]>)> {
+        std::mem::take(&mut *PICKED.lock())
+    }
+}
@@ -1,48 +1,143 @@
 use super::{actions, GuiState};
 use crate::player::Player;
 use eframe::egui::{
-    scroll_area::ScrollBarVisibility, vec2, Button, Color32, Frame, Label, RichText, ScrollArea,
-    Sense, Shadow, Stroke, Ui, UiBuilder,
+    scroll_area::ScrollBarVisibility, vec2, Align, Button, Color32, Frame, Key, Label, Layout,
+    RichText, ScrollArea, Sense, Shadow, Spinner, Stroke, TextEdit, Ui, UiBuilder,
 };
 
+/// How far the overflow chevrons scroll the tab strip
+const TAB_SCROLL_STEP: f32 = 160.0;
+
+/// Scroll and filter state of the tab strip. Not persisted.
+#[derive(Default)]
+pub struct TabStripState {
+    /// Scroll offset from last frame
+    scroll_offset: f32,
+    /// Tabs didn't fit last frame
+    overflowing: bool,
+    /// Scroll to this offset next frame
+    scroll_to: Option<f32>,
+    /// Last current tab that was scrolled into view
+    shown_tab: Option<usize>,
+    /// Playlist list filter text
+    filter: String,
+}
+
 pub fn playlist_tabs(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
-    ScrollArea::horizontal()
+    ui.horizontal(|ui| {
+        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+            ui.style_mut().spacing.item_spacing.x = 2.0;
+            playlist_list_menu(ui, player, gui);
+            if gui.tab_strip.overflowing
+                && ui
+                    .add(Button::new("⏵").frame(false))
+                    .on_hover_text("Scroll right")
+                    .clicked()
+            {
+                gui.tab_strip.scroll_to = Some(gui.tab_strip.scroll_offset + TAB_SCROLL_STEP);
+            }
+
+            ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+                if gui.tab_strip.overflowing
+                    && ui
+                        .add(Button::new("⏴").frame(false))
+                        .on_hover_text("Scroll left")
+                        .clicked()
+                {
+                    gui.tab_strip.scroll_to =
+                        Some((gui.tab_strip.scroll_offset - TAB_SCROLL_STEP).max(0.0));
+                }
+                tab_strip(ui, player, gui);
+            });
+        })
+    });
+}
+
+fn tab_strip(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    let mut scroll_area = ScrollArea::horizontal()
         .scroll_bar_visibility(ScrollBarVisibility::AlwaysHidden)
         .drag_to_scroll(true)
-        .enable_scrolling(true)
-        .show(ui, |ui| {
-            ui.vertical(|ui| {
-                ui.style_mut().spacing.item_spacing = vec2(0.0, 0.0);
-                ui.horizontal(|ui| {
-                    ui.allocate_space(vec2(0.0, 26.0));
-                    for i in 0..player.get_playlists().len() {
-                        playlist_tab(ui, player, i, gui);
-                    }
-                    ui.add_space(6.0);
-                    if ui
-                        .add(Button::new("➕").frame(false))
-                        .on_hover_text("Create new playlist")
-                        .clicked()
-                    {
-                        player.new_playlist();
-                        let _ = player.switch_to_playlist(player.get_playlists().len() - 1);
-                    }
-                });
-                ui.add_space(1.0);
+        .enable_scrolling(true);
+    if let Some(offset) = gui.tab_strip.scroll_to.take() {
+        scroll_area = scroll_area.horizontal_scroll_offset(offset);
+    }
+    let output = scroll_area.show(ui, |ui| {
+        ui.vertical(|ui| {
+            ui.style_mut().spacing.item_spacing = vec2(0.0, 0.0);
+            ui.horizontal(|ui| {
+                ui.allocate_space(vec2(0.0, 26.0));
+                for i in 0..player.get_playlists().len() {
+                    playlist_tab(ui, player, i, gui);
+                }
+                ui.add_space(6.0);
+                if ui
+                    .add(Button::new("➕").frame(false))
+                    .on_hover_text("Create new playlist")
+                    .clicked()
+                {
+                    player.new_playlist();
+                    let _ = player.switch_to_playlist(player.get_playlists().len() - 1);
+                }
             });
+            ui.add_space(1.0);
         });
+    });
+    gui.tab_strip.scroll_offset = output.state.offset.x;
+    gui.tab_strip.overflowing = output.content_size.x > output.inner_rect.width();
+}
+
+/// Dropdown listing all playlists, filterable by name
+fn playlist_list_menu(ui: &mut Ui, player: &mut Player, gui: &mut GuiState) {
+    ui.menu_button("⏷", |ui| {
+        let filter_response = ui.add(
+            TextEdit::singleline(&mut gui.tab_strip.filter)
+                .hint_text("Filter playlists")
+                .desired_width(200.0),
+        );
+        filter_response.request_focus();
+        let accept = ui.input(|input| input.key_pressed(Key::Enter));
+
+        let filter = gui.tab_strip.filter.to_lowercase();
+        let matches: Vec<usize> = player
+            .get_playlists()
+            .iter()
+            .enumerate()
+            .filter(|(_, playlist)| playlist.name.to_lowercase().contains(&filter))
+            .map(|(i, _)| i)
+            .collect();
+
+        ui.separator();
+
+        let mut selected = None;
+        ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+            if matches.is_empty() {
+                ui.weak("No matching playlists");
+            }
+            for &i in &matches {
+                let current = player.get_playlist_idx() == i;
+                let name = &player.get_playlists()[i].name;
+                if ui.selectable_label(current, name).clicked() {
+                    selected = Some(i);
+                }
+            }
+        });
+        if accept {
+            selected = selected.or_else(|| matches.first().copied());
+        }
+
+        if let Some(index) = selected {
+            let _ = player.switch_to_playlist(index);
+            gui.tab_strip.filter.clear();
+            ui.close_menu();
+        }
+    })
+    .response
+    .on_hover_text("All playlists");
 }
 
 fn playlist_tab(ui: &mut Ui, player: &mut Player, index: usize, gui: &mut GuiState) {
-    let mut playlist_title = player.get_playlists()[index].name.clone();
-    if !player.is_paused() && player.get_playing_playlist_idx() == index {
-        playlist_title = "▶ ".to_owned() + &playlist_title;
-    } else if !player.is_empty() && player.get_playing_playlist_idx() == index {
-        playlist_title = "⏸ ".to_owned() + &playlist_title;
-    }
-    if player.get_playlists()[index].is_portable() {
-        playlist_title = "🖹 ".to_owned() + &playlist_title; // File icon
-    }
+    let (playlist_title, tooltip) = tab_title(player, index);
+    let unsaved = player.get_playlists()[index].has_unsaved_changes();
     let current_tab = player.get_playlist_idx() == index;
 
     ui.style_mut().spacing.item_spacing.x = 1.0;
@@ -84,14 +179,17 @@ fn playlist_tab(ui: &mut Ui, player: &mut Player, index: usize, gui: &mut GuiSta
                     )
                     .selectable(false),
                 );
+                if player.is_refresh_pending(index) {
+                    ui.add_space(4.0);
+                    ui.add(Spinner::new().size(12.0));
+                }
 
                 ui.add_space(6.0);
 
-                let unsaved = player.get_playlists()[index].has_unsaved_changes();
                 if !(response.hovered() || current_tab || unsaved) {
                     ui.style_mut().visuals.widgets.inactive.fg_stroke.color = Color32::TRANSPARENT;
                 }
-                let close_symbol = if unsaved && !player.autosave {
+                let close_symbol = if unsaved && !player.autosave_portable {
                     "⊗"
                 } else {
                     "❌"
@@ -106,13 +204,22 @@ fn playlist_tab(ui: &mut Ui, player: &mut Player, index: usize, gui: &mut GuiSta
                 ui.add_space(2.0);
             });
 
+        let response = response.on_hover_text(if unsaved {
+            tooltip + "\nUnsaved changes"
+        } else {
+            tooltip
+        });
         if response.clicked() {
             let _ = player.switch_to_playlist(index);
         }
+        if current_tab && gui.tab_strip.shown_tab != Some(index) {
+            gui.tab_strip.shown_tab = Some(index);
+            response.scroll_to_me(None);
+        }
 
         response.context_menu(|ui| {
             actions::rename_playlist(ui, player, index);
-            actions::refresh_playlist(player, index, ui);
+            actions::refresh_playlist(player, index, ui, gui);
             if let Some(filepath) = player.get_playlists()[index].get_portable_path() {
                 actions::open_file_dir(ui, &filepath, gui);
             }
@@ -120,9 +227,13 @@ fn playlist_tab(ui: &mut Ui, player: &mut Player, index: usize, gui: &mut GuiSta
             ui.separator();
 
             actions::save_playlist(ui, player, index, gui);
+            actions::revert_playlist(ui, player, index, gui);
             actions::save_playlist_as(ui, player, index, gui);
+            actions::export_playlist_bundle(ui, player, index, gui);
             actions::duplicate_playlist(ui, player, index);
             actions::close_playlist(ui, player, index);
+            actions::close_other_playlists(ui, player, index);
+            actions::close_playlists_to_right(ui, player, index);
 
             ui.separator();
 
@@ -131,3 +242,30 @@ fn playlist_tab(ui: &mut Ui, player: &mut Player, index: usize, gui: &mut GuiSta
         });
     });
 }
+
+/// Tab title with status icons, and the tooltip
+fn tab_title(player: &Player, index: usize) -> (String, String) {
+    let mut playlist_title = player.get_playlists()[index].name.clone();
+    if !player.is_paused() && player.get_playing_playlist_idx() == index {
+        playlist_title = "▶ ".to_owned() + playlist_title.as_str();
+    } else if !player.is_empty() && player.get_playing_playlist_idx() == index {
+        playlist_title = "⏸ ".to_owned() + playlist_title.as_str();
+    }
+    let playlist = &player.get_playlists()[index];
+    let tooltip = if let Some(filepath) = playlist.get_portable_path() {
+        playlist_title = "🖹 ".to_owned() + playlist_title.as_str(); // File icon
+        format!("Portable playlist\n{}", filepath.display())
+    } else {
+        playlist_title = "🏠 ".to_owned() + playlist_title.as_str(); // In-app icon
+        "Stored in app memory".to_owned()
+    };
+    if playlist.has_unsaved_changes() {
+        playlist_title += " ●";
+    }
+    let tooltip = if player.is_refresh_pending(index) {
+        tooltip + "\nChecking files…"
+    } else {
+        tooltip
+    };
+    (playlist_title, tooltip)
+}
@@ -0,0 +1,83 @@
+//! Now Playing visualizers.
+//!
+//! Each visualizer is fed the notes and audio the player produced since the previous frame, and
+//! draws itself into the space it's given. To add one, implement `Visualizer` and list it in
+//! `Visualizers::default`.
+
+use eframe::egui::{vec2, ComboBox, Sense, Ui, UiBuilder};
+
+use crate::player::{audio::activity::NoteEvent, Player};
+
+mod channel_bars;
+mod oscilloscope;
+mod piano_keys;
+
+const VISUALIZER_WIDTH: f32 = 720.;
+const VISUALIZER_HEIGHT: f32 = 160.;
+
+pub trait Visualizer {
+    fn name(&self) -> &'static str;
+    /// Note ons and offs since the previous frame
+    fn note_events(&mut self, _events: &[NoteEvent]) {}
+    /// Latest output frames, oldest first. Stereo.
+    fn audio_frames(&mut self, _frames: &[[f32; 2]]) {}
+    /// Draw into the whole `ui.max_rect()`
+    fn draw(&mut self, ui: &mut Ui, player: &Player);
+}
+
+/// Every available visualizer, and which one is shown.
+pub struct Visualizers {
+    list: Vec<Box<dyn Visualizer>>,
+    selected: usize,
+}
+impl Default for Visualizers {
+    fn default() -> Self {
+        Self {
+            list: vec![
+                Box::new(channel_bars::ChannelBars),
+                Box::new(piano_keys::PianoKeys::default()),
+                Box::new(oscilloscope::Oscilloscope::default()),
+            ],
+            selected: 0,
+        }
+    }
+}
+
+impl Visualizers {
+    /// Feed player output to every visualizer, so the hidden ones stay up to date.
+    pub fn update(&mut self, player: &Player) {
+        let events = player.take_note_events();
+        let frames = player.get_output_frames();
+        for visualizer in &mut self.list {
+            visualizer.note_events(&events);
+            visualizer.audio_frames(&frames);
+        }
+    }
+
+    pub fn picker(&mut self, ui: &mut Ui) {
+        let selected_name = self.list.get(self.selected).map_or("", |v| v.name());
+        ComboBox::from_id_salt("visualizer_picker")
+            .selected_text(selected_name)
+            .show_ui(ui, |ui| {
+                for (index, visualizer) in self.list.iter().enumerate() {
+                    ui.selectable_value(&mut self.selected, index, visualizer.name());
+                }
+            });
+    }
+
+    /// Draw the selected visualizer
+    pub fn draw(&mut self, ui: &mut Ui, player: &Player) {
+        let width = ui.available_width().min(VISUALIZER_WIDTH);
+        let (rect, _) = ui.allocate_exact_size(vec2(width, VISUALIZER_HEIGHT), Sense::hover());
+        ui.painter_at(rect)
+            .rect_filled(rect, 4., ui.visuals().extreme_bg_color);
+
+        let Some(visualizer) = self.list.get_mut(self.selected) else {
+            return;
+        };
+        ui.allocate_new_ui(UiBuilder::new().max_rect(rect), |ui| {
+            ui.set_clip_rect(rect);
+            visualizer.draw(ui, player);
+        });
+    }
+}
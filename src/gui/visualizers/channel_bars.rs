@@ -0,0 +1,62 @@
+use eframe::egui::{Align2, Color32, FontId, Rect, Sense, Ui};
+
+use super::Visualizer;
+use crate::player::{
+    general_midi::preset_name,
+    program_lock::{CHANNEL_COUNT, DRUM_CHANNEL},
+    Player,
+};
+
+/// Seconds for a channel bar to fall after its notes end
+const DECAY: f32 = 0.3;
+
+/// A bar for each MIDI channel, height by the velocity of its latest note. Hover shows the
+/// instrument.
+pub struct ChannelBars;
+
+impl Visualizer for ChannelBars {
+    fn name(&self) -> &'static str {
+        "Channels"
+    }
+
+    fn draw(&mut self, ui: &mut Ui, player: &Player) {
+        let levels = player.get_channel_levels();
+        let rect = ui.max_rect();
+        let painter = ui.painter();
+
+        let slot_width = rect.width() / CHANNEL_COUNT as f32;
+        for (channel, level) in levels.into_iter().enumerate() {
+            let target = f32::from(level) / 127.;
+            let height = ui.ctx().animate_value_with_time(
+                ui.id().with(("channel_level", channel)),
+                target,
+                if target > 0. { 0. } else { DECAY },
+            );
+            let left = (channel as f32).mul_add(slot_width, rect.left());
+            let bar = Rect::from_min_max(
+                [left + 2., rect.bottom() - height * rect.height()].into(),
+                [left + slot_width - 2., rect.bottom()].into(),
+            );
+            let [r, g, b] = player.channel_colors.get_color(channel);
+            painter.rect_filled(bar, 2., Color32::from_rgb(r, g, b));
+            painter.text(
+                [left + slot_width / 2., rect.bottom() - 2.].into(),
+                Align2::CENTER_BOTTOM,
+                (channel + 1).to_string(),
+                FontId::proportional(10.),
+                Color32::from_gray(160),
+            );
+        }
+
+        let response = ui.interact(rect, ui.id().with("channel_bars"), Sense::hover());
+        if let Some(pos) = response.hover_pos() {
+            let channel = (((pos.x - rect.left()) / slot_width) as usize).min(CHANNEL_COUNT - 1);
+            let (bank, program) = player.get_channel_programs()[channel];
+            let presets = player
+                .get_active_font()
+                .map_or(&[][..], |(_, font)| font.get_presets());
+            let name = preset_name(presets, bank, program, channel == DRUM_CHANNEL);
+            response.on_hover_text_at_pointer(format!("Channel {}: {name}", channel + 1));
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use eframe::egui::{Color32, Pos2, Shape, Stroke, Ui};
+
+use super::Visualizer;
+use crate::player::Player;
+
+/// Output waveform, left and right channel on top of each other.
+#[derive(Default)]
+pub struct Oscilloscope {
+    frames: Vec<[f32; 2]>,
+}
+
+impl Visualizer for Oscilloscope {
+    fn name(&self) -> &'static str {
+        "Oscilloscope"
+    }
+
+    fn audio_frames(&mut self, frames: &[[f32; 2]]) {
+        self.frames.clear();
+        self.frames.extend_from_slice(frames);
+    }
+
+    fn draw(&mut self, ui: &mut Ui, _player: &Player) {
+        let rect = ui.max_rect();
+        let painter = ui.painter();
+        painter.hline(
+            rect.x_range(),
+            rect.center().y,
+            Stroke::new(1., ui.visuals().weak_text_color()),
+        );
+        if self.frames.len() < 2 {
+            return;
+        }
+
+        let step = rect.width() / (self.frames.len() - 1) as f32;
+        let half_height = rect.height() / 2.;
+        let colors = [
+            Color32::from_rgb(0x60, 0xa0, 0xff),
+            Color32::from_rgb(0xff, 0x80, 0x60),
+        ];
+        for (side, color) in colors.into_iter().enumerate() {
+            let points: Vec<Pos2> = self
+                .frames
+                .iter()
+                .enumerate()
+                .map(|(i, frame)| {
+                    let sample = frame[side].clamp(-1., 1.);
+                    [
+                        (i as f32).mul_add(step, rect.left()),
+                        sample.mul_add(-half_height, rect.center().y),
+                    ]
+                    .into()
+                })
+                .collect();
+            painter.add(Shape::line(points, Stroke::new(1.5, color)));
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use eframe::egui::{Color32, Rect, Ui};
+
+use super::Visualizer;
+use crate::player::{audio::activity::NoteEvent, program_lock::CHANNEL_COUNT, Player};
+
+/// A0 to C8, the range of a piano
+const LOWEST_KEY: u8 = 21;
+const HIGHEST_KEY: u8 = 108;
+
+/// Keyboard with held notes lit in the color of their channel.
+pub struct PianoKeys {
+    /// Velocity of each held note by channel, 0 when released
+    held: [[u8; 128]; CHANNEL_COUNT],
+}
+impl Default for PianoKeys {
+    fn default() -> Self {
+        Self {
+            held: [[0; 128]; CHANNEL_COUNT],
+        }
+    }
+}
+
+impl Visualizer for PianoKeys {
+    fn name(&self) -> &'static str {
+        "Keys"
+    }
+
+    fn note_events(&mut self, events: &[NoteEvent]) {
+        for event in events {
+            if let Some(note) = self
+                .held
+                .get_mut(event.channel)
+                .and_then(|notes| notes.get_mut(event.note as usize))
+            {
+                *note = event.velocity;
+            }
+        }
+    }
+
+    fn draw(&mut self, ui: &mut Ui, player: &Player) {
+        // Notes off can be missed, e.g. on stop or all notes off. Trust the channel levels.
+        for (notes, level) in self.held.iter_mut().zip(player.get_channel_levels()) {
+            if level == 0 {
+                notes.fill(0);
+            }
+        }
+
+        let rect = ui.max_rect();
+        let painter = ui.painter();
+        let key_count = HIGHEST_KEY - LOWEST_KEY + 1;
+        let key_width = rect.width() / f32::from(key_count);
+        for key in LOWEST_KEY..=HIGHEST_KEY {
+            let left = f32::from(key - LOWEST_KEY).mul_add(key_width, rect.left());
+            let black = matches!(key % 12, 1 | 3 | 6 | 8 | 10);
+            let bottom = if black {
+                rect.height().mul_add(-0.35, rect.bottom())
+            } else {
+                rect.bottom()
+            };
+            let key_rect = Rect::from_min_max(
+                [left + 0.5, rect.top()].into(),
+                [left + key_width - 0.5, bottom].into(),
+            );
+
+            // Loudest channel holding the key
+            let holder = (0..CHANNEL_COUNT)
+                .filter(|&channel| self.held[channel][key as usize] > 0)
+                .max_by_key(|&channel| self.held[channel][key as usize]);
+            let color = holder.map_or_else(
+                || {
+                    if black {
+                        Color32::from_gray(30)
+                    } else {
+                        Color32::from_gray(220)
+                    }
+                },
+                |channel| {
+                    let [r, g, b] = player.channel_colors.get_color(channel);
+                    Color32::from_rgb(r, g, b)
+                },
+            );
+            painter.rect_filled(key_rect, 1., color);
+        }
+    }
+}
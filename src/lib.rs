@@ -0,0 +1,9 @@
+//! Player core: playlists, soundfont and MIDI metadata, playback and rendering, without the GUI.
+//! The `sfontplayer` binary is an egui frontend on top of this.
+
+pub mod midi_inspector;
+pub mod midi_split;
+pub mod player;
+pub mod scan;
+#[cfg(test)]
+mod test_support;
@@ -1,27 +1,46 @@
-use eframe::egui::{mutex::Mutex, Context, ViewportBuilder, ViewportCommand};
-use gui::{draw_gui, GuiState};
+use eframe::egui::{mutex::Mutex, Context, ViewportCommand};
+use gui::{
+    apply_touch_mode, apply_window_mode, draw_gui, notifications::notify_song_change, GuiState,
+};
 use midi_inspector::MidiInspector;
-use player::{playlist::Playlist, Player};
-use rodio::{OutputStream, Sink};
+use player::{
+    audio::{
+        device_watch::{default_device_name, find_output_device, DeviceWatcher},
+        routing::{open_stream, pair_name, RoutedSink},
+    },
+    playlist::{import::ImportFormat, Playlist},
+    Player, PlayerEvent,
+};
+use rodio::{
+    cpal::{
+        self,
+        traits::{DeviceTrait, HostTrait},
+    },
+    OutputStream, Sink,
+};
+use sfontplayer_core::{midi_inspector, midi_split, player};
 use std::{
-    env,
-    sync::Arc,
-    thread,
-    time::{Duration, Instant},
+    sync::{mpsc::Receiver, Arc},
+    time::Duration,
 };
 
 mod gui;
-mod midi_inspector;
-mod player;
 
+/// Start with default settings and no playlists, and don't save anything.
+const SAFE_MODE_FLAG: &str = "--safe-mode";
+
+#[cfg(not(target_arch = "wasm32"))]
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(exit_code) = sfontplayer_core::scan::run(&args) {
+        std::process::exit(exit_code);
+    }
 
     let native_options = eframe::NativeOptions {
-        viewport: ViewportBuilder::default()
+        viewport: eframe::egui::ViewportBuilder::default()
             .with_app_id("jyls_sfontplayer")
             .with_inner_size([400.0, 300.0])
-            .with_min_inner_size([300.0, 220.0]),
+            .with_min_inner_size(gui::MIN_WINDOW_SIZE),
         ..Default::default()
     };
     let _ = eframe::run_native(
@@ -31,6 +50,33 @@ fn main() {
     );
 }
 
+/// Id of the canvas in `index.html`
+#[cfg(target_arch = "wasm32")]
+const CANVAS_ID: &str = "sfontplayer_canvas";
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    use eframe::{wasm_bindgen::JsCast, web_sys};
+
+    wasm_bindgen_futures::spawn_local(async {
+        let canvas = web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.get_element_by_id(CANVAS_ID))
+            .and_then(|element| element.dyn_into::<web_sys::HtmlCanvasElement>().ok())
+            .expect("index.html should have the canvas");
+        let result = eframe::WebRunner::new()
+            .start(
+                canvas,
+                eframe::WebOptions::default(),
+                Box::new(|cc| Ok(Box::new(SfontPlayer::new(cc, &[])))),
+            )
+            .await;
+        if let Err(e) = result {
+            web_sys::console::error_1(&e);
+        }
+    });
+}
+
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)] // if we add new fields, give them default values when deserializing old state
 struct SfontPlayer {
@@ -39,43 +85,155 @@ struct SfontPlayer {
     #[serde(skip)]
     midi_inspector: Option<MidiInspector>,
     #[serde(skip)]
-    stream: OutputStream,
+    stream: Option<OutputStream>,
+    #[serde(skip)]
+    device_watcher: Option<DeviceWatcher>,
+    /// Output device and pair choice of the player that the stream was opened for
+    #[serde(skip)]
+    opened_output: (Option<String>, u16),
+    #[serde(skip)]
+    player_events: Receiver<PlayerEvent>,
+    /// Audio is opened on the first click in the browser
+    #[cfg(target_arch = "wasm32")]
+    #[serde(skip)]
+    audio_started: bool,
     gui_state: GuiState,
 }
 impl Default for SfontPlayer {
     fn default() -> Self {
-        let (stream, stream_handle) = OutputStream::try_default().expect("Could not create stream");
-        let sink = Sink::try_new(&stream_handle).expect("Could not create sink");
-
         let mut player = Player::default();
         if let Err(e) = player.load_state() {
             println!("{e}");
         }
-        let sfontplayer = Self {
+        Self::with_player(player)
+    }
+}
+
+impl SfontPlayer {
+    fn with_player(mut player: Player) -> Self {
+        let opened_output = (player.output_device.clone(), player.output_pair);
+        let player_events = player.subscribe();
+        #[cfg_attr(target_arch = "wasm32", allow(unused_mut))]
+        let mut sfontplayer = Self {
             player: Arc::new(Mutex::new(player)),
             midi_inspector: None,
             gui_state: GuiState::default(),
-            stream,
+            stream: None,
+            device_watcher: None,
+            opened_output,
+            player_events,
+            #[cfg(target_arch = "wasm32")]
+            audio_started: false,
         };
-        sfontplayer.player.lock().set_sink(Some(sink));
+        // Browsers don't allow sound before the page has been interacted with, so the web build
+        // opens the output on the first click instead.
+        #[cfg(not(target_arch = "wasm32"))]
+        sfontplayer.set_output(
+            open_output(
+                sfontplayer.opened_output.0.as_deref(),
+                sfontplayer.opened_output.1,
+            )
+            .expect("Could not open audio output"),
+        );
+        sfontplayer
+    }
+    fn set_output(&mut self, output: AudioOutput) {
+        let mut player = self.player.lock();
+        player.set_sink(Some(Box::new(output.sink)));
+        player.set_preview_sink(Some(Box::new(output.preview_sink)));
+        player.set_live_sink(Some(Box::new(output.live_sink)));
+        drop(player);
+        self.stream = Some(output.stream);
+    }
+
+    /// Open the chosen output device again, after the previous one went away or the choice
+    /// changed, and keep playing there. Falls back to the default device.
+    fn reopen_audio(&mut self, ctx: &Context) {
+        self.opened_output = self.output_choice();
+        match open_output(self.opened_output.0.as_deref(), self.opened_output.1) {
+            Ok(output) => {
+                let mut player = self.player.lock();
+                player.replace_sinks(Box::new(output.sink), Box::new(output.preview_sink));
+                player.set_live_sink(Some(Box::new(output.live_sink)));
+                drop(player);
+                self.stream = Some(output.stream);
+                let name = output.device.as_deref().unwrap_or("default device");
+                let message = if output.pair == 0 {
+                    format!("Audio output switched to {name}.")
+                } else {
+                    format!(
+                        "Audio output switched to {name}, outputs {}.",
+                        pair_name(output.pair)
+                    )
+                };
+                self.gui_state.toast_info(message);
+                self.watch_device(ctx, output.device);
+            }
+            Err(e) => {
+                // Nothing to switch to. Wait for a device to appear.
+                if self.stream.take().is_some() {
+                    self.gui_state
+                        .toast_error(format!("Audio device lost, and no other device: {e}"));
+                }
+                self.watch_device(ctx, None);
+            }
+        }
+    }
+    fn output_choice(&self) -> (Option<String>, u16) {
+        let player = self.player.lock();
+        (player.output_device.clone(), player.output_pair)
+    }
+    fn watch_device(&mut self, ctx: &Context, device: Option<String>) {
+        let ctx = ctx.clone();
+        self.device_watcher = Some(DeviceWatcher::new(device, move || ctx.request_repaint()));
+    }
+
+    /// Fresh state that is never saved, for recovering from broken state files
+    fn safe_mode() -> Self {
+        let mut player = Player::default();
+        player.debug_block_saving = true;
+        let mut sfontplayer = Self::with_player(player);
+        sfontplayer
+            .gui_state
+            .toasts
+            .warning("Safe mode: settings and playlists weren't loaded, and nothing will be saved.")
+            .duration(None)
+            .closable(true);
         sfontplayer
     }
-}
 
-impl SfontPlayer {
     fn new(cc: &eframe::CreationContext<'_>, args: &[String]) -> Self {
         // Customize egui here with cc.egui_ctx.set_fonts and cc.egui_ctx.set_visuals.
         // Restore app state using cc.storage (requires the "persistence" feature).
         // Use the cc.gl (a glow::Context) to create graphics shaders and buffers that you can use
         // for e.g. egui::PaintCallback.
 
-        let mut sfontplayer = cc.storage.map_or_else(Self::default, |storage| {
-            eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
-        });
+        let mut sfontplayer = if args.iter().any(|arg| arg == SAFE_MODE_FLAG) {
+            Self::safe_mode()
+        } else {
+            cc.storage.map_or_else(Self::default, |storage| {
+                eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
+            })
+        };
         sfontplayer.handle_launch_args(args);
+        if sfontplayer.gui_state.compact_mode {
+            apply_window_mode(&cc.egui_ctx, &sfontplayer.gui_state);
+        }
+        if sfontplayer.gui_state.touch_mode {
+            apply_touch_mode(&cc.egui_ctx, &sfontplayer.gui_state);
+        }
 
-        let player_clone = Arc::clone(&sfontplayer.player);
-        update_thread(player_clone);
+        // The web build has no threads. `update` runs every frame, and there are no folders to
+        // check for changes.
+        #[cfg(not(target_arch = "wasm32"))]
+        update_thread(Arc::clone(&sfontplayer.player));
+        let device = sfontplayer
+            .opened_output
+            .0
+            .clone()
+            .filter(|name| find_output_device(name).is_some())
+            .or_else(default_device_name);
+        sfontplayer.watch_device(&cc.egui_ctx, device);
 
         sfontplayer
     }
@@ -86,16 +244,21 @@ impl SfontPlayer {
         new_playlist.name = "Opened files".into();
 
         for (i, arg) in args.iter().enumerate() {
-            if i == 0 {
+            if i == 0 || arg == SAFE_MODE_FLAG {
                 continue;
             }
-            if std::path::Path::new(arg)
+            let path = std::path::Path::new(arg);
+            if path
                 .extension()
-                .map_or(false, |ext| ext.eq_ignore_ascii_case("midpl"))
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("midpl"))
             {
                 if let Err(e) = player.open_portable_playlist(arg.into()) {
                     self.gui_state.toast_error(e.to_string());
                 }
+            } else if ImportFormat::from_path(path).is_some() {
+                if let Err(e) = player.import_playlist(path) {
+                    self.gui_state.toast_error(e.to_string());
+                }
             } else if let Err(e) = new_playlist.add_file(arg.into()) {
                 self.gui_state.toast_error(e.to_string());
             }
@@ -119,7 +282,7 @@ impl SfontPlayer {
             return;
         }
         let player = self.player.lock();
-        if player.autosave {
+        if player.autosave_portable {
             return;
         }
         if self.gui_state.force_quit {
@@ -143,18 +306,55 @@ impl eframe::App for SfontPlayer {
         }
         eframe::set_value(storage, eframe::APP_KEY, self);
 
-        if let Err(e) = player.save_state() {
+        if let Err(e) = player.autosave_state() {
             self.gui_state
                 .toast_error(format!("Saving app state failed: {e}"));
         }
     }
 
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        let mut player = self.player.lock();
+        if player.debug_block_saving {
+            return;
+        }
+        // Periodic saves may have skipped in-app playlists.
+        let _ = player.save_state();
+    }
+
+    fn auto_save_interval(&self) -> Duration {
+        self.player.lock().get_autosave_interval()
+    }
+
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        #[cfg(target_arch = "wasm32")]
+        if !self.audio_started && ctx.input(|i| i.pointer.any_pressed()) {
+            self.audio_started = true;
+            match open_output(self.opened_output.0.as_deref(), self.opened_output.1) {
+                Ok(output) => self.set_output(output),
+                Err(e) => self
+                    .gui_state
+                    .toast_error(format!("Couldn't start audio: {e}")),
+            }
+        }
+        let device_changed = self
+            .device_watcher
+            .as_ref()
+            .is_some_and(DeviceWatcher::has_changed);
+        // Lost, not switched to by choice. Playing on would move the sound to the speakers.
+        if device_changed && self.stream.is_some() {
+            self.player
+                .lock()
+                .auto_pause("the audio device was disconnected");
+        }
+        if device_changed || self.output_choice() != self.opened_output {
+            self.reopen_audio(ctx);
+        }
+
         // App logic
         {
             let mut player = self.player.lock();
             player.update();
-            handle_events(&mut player, &mut self.gui_state, ctx);
+            handle_events(&self.player_events, &mut self.gui_state, ctx);
             // Repaint continuously while playing
             if !player.is_paused() {
                 ctx.request_repaint();
@@ -165,8 +365,18 @@ impl eframe::App for SfontPlayer {
         egui_extras::install_image_loaders(ctx);
         draw_gui(ctx, self);
 
+        let follow_file = self.player.lock().follow_file;
+        if let Some(inspector) = &mut self.midi_inspector {
+            if follow_file && inspector.has_changed() {
+                if let Err(e) = inspector.reload() {
+                    self.gui_state.toast_error(e.to_string());
+                }
+            }
+        }
+
         if self.gui_state.update_flags.close_midi_inspector {
             self.midi_inspector = None;
+            self.player.lock().stop_preview();
         } else if let Some(filepath) = &self.gui_state.update_flags.open_midi_inspector {
             if let Ok(insp) = MidiInspector::new(filepath) {
                 self.midi_inspector = Some(insp);
@@ -175,36 +385,109 @@ impl eframe::App for SfontPlayer {
 
         self.gui_state.update_flags.clear();
         self.quit_check(ctx);
+        // Once the first frame is up
+        self.player.lock().start_deferred_loading();
     }
 }
 
-fn handle_events(player: &mut Player, gui: &mut GuiState, ctx: &Context) {
-    let event_queue = player.get_event_queue();
-    while !event_queue.is_empty() {
-        match event_queue.remove(0) {
-            player::PlayerEvent::Raise => {
+/// Audio stream, with sinks for the player, the inspector preview and live input
+struct AudioOutput {
+    stream: OutputStream,
+    sink: RoutedSink,
+    preview_sink: RoutedSink,
+    live_sink: RoutedSink,
+    /// Name of the device it opened on
+    device: Option<String>,
+    /// Stereo pair it plays to
+    pair: u16,
+}
+
+/// Open the given device and pair. Falls back to the default device, and then to its first
+/// pair.
+fn open_output(device: Option<&str>, pair: u16) -> anyhow::Result<AudioOutput> {
+    let chosen = device.and_then(find_output_device);
+    let default = cpal::default_host().default_output_device();
+    let opened = chosen.into_iter().chain(default).find_map(|device| {
+        let (stream, stream_handle, channels) = open_stream(&device, pair).ok()?;
+        Some((stream, stream_handle, channels, pair, device.name().ok()))
+    });
+    let (stream, stream_handle, channels, pair, device) = if let Some(opened) = opened {
+        opened
+    } else {
+        let (stream, stream_handle) = OutputStream::try_default()?;
+        (stream, stream_handle, 2, 0, default_device_name())
+    };
+    Ok(AudioOutput {
+        sink: RoutedSink::new(Sink::try_new(&stream_handle)?, channels, pair),
+        preview_sink: RoutedSink::new(Sink::try_new(&stream_handle)?, channels, pair),
+        live_sink: RoutedSink::new(Sink::try_new(&stream_handle)?, channels, pair),
+        stream,
+        device,
+        pair,
+    })
+}
+
+fn handle_events(events: &Receiver<PlayerEvent>, gui: &mut GuiState, ctx: &Context) {
+    while let Ok(event) = events.try_recv() {
+        match event {
+            PlayerEvent::Raise => {
                 ctx.send_viewport_cmd(ViewportCommand::Minimized(false));
                 ctx.send_viewport_cmd(ViewportCommand::Focus);
             }
-            player::PlayerEvent::Quit => ctx.send_viewport_cmd(ViewportCommand::Close),
-            player::PlayerEvent::NotifyError(message) => gui.toast_error(message),
+            PlayerEvent::Quit => ctx.send_viewport_cmd(ViewportCommand::Close),
+            PlayerEvent::NotifyError(message) => gui.toast_error(message),
+            PlayerEvent::NotifyInfo(message) => gui.toast_info(message),
+            PlayerEvent::AutoPaused(reason) => gui.toast_info(format!("Paused: {reason}.")),
+            // Stays on the list until dealt with, unlike a toast
+            PlayerEvent::ProblemFound(_) => gui.show_problems = true,
+            PlayerEvent::SongChanged { song, soundfont } => {
+                let focused = ctx.input(|i| i.viewport().focused.unwrap_or(false));
+                if gui.notify_song_change && (gui.notify_when_focused || !focused) {
+                    notify_song_change(song, soundfont);
+                }
+            }
+            PlayerEvent::SongReloaded { song } => gui.toast_success(format!("Reloaded {song}")),
+            // Changes made from the update thread or media controls need a redraw.
+            PlayerEvent::QueueChanged
+            | PlayerEvent::FontChanged { .. }
+            | PlayerEvent::PositionTick { .. }
+            | PlayerEvent::PlaylistListChanged
+            | PlayerEvent::FilesRefreshed
+            | PlayerEvent::EndActionPending(_) => ctx.request_repaint(),
         }
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 const THREAD_SLEEP: Duration = Duration::from_millis(200);
+#[cfg(not(target_arch = "wasm32"))]
 const FILELIST_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+/// Playlists that auto-play new files and the followed song file are checked more often
+#[cfg(not(target_arch = "wasm32"))]
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
 
+#[cfg(not(target_arch = "wasm32"))]
 fn update_thread(player: Arc<Mutex<Player>>) {
+    use std::{thread, time::Instant};
+
     thread::spawn(move || {
         let mut t_since_file_refresh = Duration::ZERO;
+        let mut t_since_watch = Duration::ZERO;
         let mut prev_update = Instant::now();
 
         loop {
             player.lock().update();
+            player.lock().refresh_pending_playlist();
 
             let now = Instant::now();
             t_since_file_refresh += now - prev_update;
+            t_since_watch += now - prev_update;
+            // Before the slow refresh, so it doesn't pick up new files first
+            if t_since_watch >= WATCH_INTERVAL {
+                t_since_watch -= WATCH_INTERVAL;
+                player.lock().watch_new_songs();
+                player.lock().follow_playing_file();
+            }
             if t_since_file_refresh >= FILELIST_REFRESH_INTERVAL {
                 t_since_file_refresh -= FILELIST_REFRESH_INTERVAL;
                 player.lock().get_playlist_mut().refresh_font_list();
@@ -1,26 +1,100 @@
-use midi_msg::{Header, Meta, MidiFile, MidiMsg, Track};
+pub mod compare;
+
+use compare::Comparison;
+use midi_msg::{ChannelVoiceMsg, Header, Meta, MidiMsg, Track};
 use std::{
     fs,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
+use crate::player::playlist::midi_meta::parse_midi;
+
 pub struct MidiInspectorTrack {
     pub track: Track,
     pub open: bool,
     pub name: Option<String>,
+    pub stats: TrackStats,
 }
 impl MidiInspectorTrack {
+    #[must_use]
     pub fn new(track: Track) -> Self {
         let name = get_track_name(&track);
+        let stats = TrackStats::new(&track);
 
         Self {
             track,
             open: false,
             name,
+            stats,
+        }
+    }
+}
+
+/// Note summary of a track. Counted once when the track is read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackStats {
+    pub note_count: usize,
+    /// Lowest and highest note
+    pub pitch_range: Option<(u8, u8)>,
+    /// 0-based channels the track sends to
+    pub channels: Vec<u8>,
+    /// Program changed to most often
+    pub common_program: Option<u8>,
+}
+impl TrackStats {
+    fn new(track: &Track) -> Self {
+        let mut stats = Self::default();
+        let mut channels = [false; 16];
+        let mut program_counts = [0_usize; 128];
+        for track_event in track.events() {
+            let (channel, msg) = match &track_event.event {
+                MidiMsg::ChannelVoice { channel, msg }
+                | MidiMsg::RunningChannelVoice { channel, msg } => (*channel, Some(*msg)),
+                MidiMsg::ChannelMode { channel, .. }
+                | MidiMsg::RunningChannelMode { channel, .. } => (*channel, None),
+                _ => continue,
+            };
+            channels[channel as usize] = true;
+            match msg {
+                Some(ChannelVoiceMsg::NoteOn { note, velocity }) if velocity > 0 => {
+                    stats.note_count += 1;
+                    stats.pitch_range = Some(
+                        stats
+                            .pitch_range
+                            .map_or((note, note), |(low, high)| (low.min(note), high.max(note))),
+                    );
+                }
+                Some(ChannelVoiceMsg::ProgramChange { program }) => {
+                    program_counts[program as usize] += 1;
+                }
+                _ => (),
+            }
         }
+        stats.channels = (0..16)
+            .filter(|&channel| channels[channel as usize])
+            .collect();
+        // Lowest program wins ties
+        stats.common_program = program_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .rev()
+            .max_by_key(|(_, &count)| count)
+            .map(|(program, _)| program as u8);
+        stats
     }
 }
 
+/// Note name with octave, middle C (60) is C4
+#[must_use]
+pub fn note_name(note: u8) -> String {
+    const NAMES: [&str; 12] = [
+        "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+    ];
+    format!("{}{}", NAMES[note as usize % 12], i32::from(note) / 12 - 1)
+}
+
 fn get_track_name(track: &Track) -> Option<String> {
     for trackevent in track.events() {
         let MidiMsg::Meta { msg } = &trackevent.event else {
@@ -34,28 +108,267 @@ fn get_track_name(track: &Track) -> Option<String> {
     None
 }
 
+/// Controller that can be plotted over time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutomationSource {
+    Volume,
+    Expression,
+    Modulation,
+    PitchBend,
+}
+impl AutomationSource {
+    pub const ALL: [Self; 4] = [
+        Self::Volume,
+        Self::Expression,
+        Self::Modulation,
+        Self::PitchBend,
+    ];
+
+    #[must_use]
+    pub const fn get_name(self) -> &'static str {
+        match self {
+            Self::Volume => "Volume (CC7)",
+            Self::Expression => "Expression (CC11)",
+            Self::Modulation => "Modulation (CC1)",
+            Self::PitchBend => "Pitch bend",
+        }
+    }
+
+    /// Value before any events, 0.0..=1.0
+    #[must_use]
+    pub const fn get_default(self) -> f32 {
+        match self {
+            Self::Volume => 100. / 127.,
+            Self::Expression => 1.,
+            Self::Modulation => 0.,
+            Self::PitchBend => 0.5,
+        }
+    }
+
+    /// Value of this controller in the message, 0.0..=1.0
+    fn get_value(self, msg: ChannelVoiceMsg) -> Option<f32> {
+        let control_number = match self {
+            Self::Volume => 7,
+            Self::Expression => 11,
+            Self::Modulation => 1,
+            Self::PitchBend => {
+                let ChannelVoiceMsg::PitchBend { bend } = msg else {
+                    return None;
+                };
+                return Some(f32::from(bend) / 16383.);
+            }
+        };
+        match msg {
+            ChannelVoiceMsg::ControlChange { control } if control.control() == control_number => {
+                Some(f32::from(control.value()) / 127.)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Automation graph settings
+#[derive(Debug, Clone, Copy)]
+pub struct AutomationView {
+    pub open: bool,
+    /// 0-based
+    pub channel: u8,
+    pub source: AutomationSource,
+}
+impl Default for AutomationView {
+    fn default() -> Self {
+        Self {
+            open: false,
+            channel: 0,
+            source: AutomationSource::Volume,
+        }
+    }
+}
+
 pub struct MidiInspector {
     pub filepath: PathBuf,
     pub header: Header,
     pub tracks: Vec<MidiInspectorTrack>,
+    pub automation: AutomationView,
+    /// Another file shown side by side, with differences highlighted
+    pub comparison: Option<Comparison>,
+    /// Modification time of the file when it was read
+    modified: Option<SystemTime>,
+}
+
+fn read_tracks(filepath: &Path) -> anyhow::Result<(Header, Vec<MidiInspectorTrack>)> {
+    let bytes = fs::read(filepath)?;
+    let midifile = parse_midi(&bytes)?;
+    let tracks = midifile
+        .tracks
+        .into_iter()
+        .map(MidiInspectorTrack::new)
+        .collect();
+    Ok((midifile.header, tracks))
 }
 
 impl MidiInspector {
+    /// Open a midi file for inspection
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read or isn't a valid midi file.
     pub fn new(filepath: &Path) -> anyhow::Result<Self> {
-        let bytes = fs::read(filepath)?;
-        let midifile = MidiFile::from_midi(bytes.as_slice())?;
-
-        let filepath = filepath.to_owned();
-        let header = midifile.header;
-        let mut tracks = vec![];
-        for track in midifile.tracks {
-            tracks.push(MidiInspectorTrack::new(track));
-        }
+        let modified = fs::metadata(filepath).and_then(|meta| meta.modified()).ok();
+        let (header, tracks) = read_tracks(filepath)?;
 
         Ok(Self {
-            filepath,
+            filepath: filepath.to_owned(),
             header,
             tracks,
+            automation: AutomationView::default(),
+            comparison: None,
+            modified,
         })
     }
+
+    /// Has the file been modified since it was read?
+    #[must_use]
+    pub fn has_changed(&self) -> bool {
+        let modified = fs::metadata(&self.filepath)
+            .and_then(|meta| meta.modified())
+            .ok();
+        modified.is_some() && modified != self.modified
+    }
+
+    /// Read the file again. Open tracks, the automation view and the compared file stay as
+    /// they were.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file, or the one it's compared with, can't be read or parsed anymore.
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        let mut inspector = Self::new(&self.filepath)?;
+        for (track, old_track) in inspector.tracks.iter_mut().zip(&self.tracks) {
+            track.open = old_track.open;
+        }
+        inspector.automation = self.automation;
+        let comparison = self.comparison.take();
+        *self = inspector;
+        if let Some(comparison) = comparison {
+            self.compare_with(&comparison.filepath)?;
+        }
+        Ok(())
+    }
+
+    /// Show another file side by side
+    ///
+    /// # Errors
+    ///
+    /// Fails if the other file can't be read or isn't a valid midi file.
+    pub fn compare_with(&mut self, filepath: &Path) -> anyhow::Result<()> {
+        self.comparison = Some(Comparison::new(filepath, &self.tracks)?);
+        Ok(())
+    }
+
+    /// Controller values of a channel in all tracks as (beat or frame, value), in time order.
+    #[must_use]
+    pub fn get_automation(&self, channel: u8, source: AutomationSource) -> Vec<(f32, f32)> {
+        let mut points: Vec<(f32, f32)> = self
+            .tracks
+            .iter()
+            .flat_map(|track| track.track.events())
+            .filter_map(|track_event| match &track_event.event {
+                MidiMsg::ChannelVoice { channel: ch, msg }
+                | MidiMsg::RunningChannelVoice { channel: ch, msg }
+                    if *ch as u8 == channel =>
+                {
+                    source
+                        .get_value(*msg)
+                        .map(|value| (track_event.beat_or_frame, value))
+                }
+                _ => None,
+            })
+            .collect();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        points
+    }
+
+    /// Time of the last event in beats or frames
+    pub fn get_length(&self) -> f32 {
+        self.tracks
+            .iter()
+            .flat_map(|track| track.track.events())
+            .map(|track_event| track_event.beat_or_frame)
+            .fold(0., f32::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use midi_msg::{Channel, ControlChange, TrackEvent};
+
+    use super::*;
+
+    fn event(beat_or_frame: f32, channel: Channel, msg: ChannelVoiceMsg) -> TrackEvent {
+        TrackEvent {
+            delta_time: 0,
+            event: MidiMsg::ChannelVoice { channel, msg },
+            beat_or_frame,
+        }
+    }
+
+    #[test]
+    fn test_get_automation() {
+        let volume = |value| ChannelVoiceMsg::ControlChange {
+            control: ControlChange::CC { control: 7, value },
+        };
+        let first = Track::Midi(vec![
+            event(4., Channel::Ch1, volume(0)),
+            event(1., Channel::Ch2, volume(64)),
+        ]);
+        let second = Track::Midi(vec![
+            event(2., Channel::Ch1, volume(127)),
+            event(3., Channel::Ch1, ChannelVoiceMsg::PitchBend { bend: 16383 }),
+        ]);
+        let inspector = MidiInspector {
+            filepath: PathBuf::new(),
+            header: Header::default(),
+            tracks: vec![
+                MidiInspectorTrack::new(first),
+                MidiInspectorTrack::new(second),
+            ],
+            automation: AutomationView::default(),
+            comparison: None,
+            modified: None,
+        };
+
+        let points = inspector.get_automation(0, AutomationSource::Volume);
+        assert_eq!(points, vec![(2., 1.), (4., 0.)]);
+        let points = inspector.get_automation(0, AutomationSource::PitchBend);
+        assert_eq!(points, vec![(3., 1.)]);
+        assert!(inspector
+            .get_automation(1, AutomationSource::Expression)
+            .is_empty());
+        assert!((inspector.get_length() - 4.).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_track_stats() {
+        let note_on = |note, velocity| ChannelVoiceMsg::NoteOn { note, velocity };
+        let program = |program| ChannelVoiceMsg::ProgramChange { program };
+        let track = Track::Midi(vec![
+            event(0., Channel::Ch10, program(5)),
+            event(0., Channel::Ch1, program(40)),
+            event(0., Channel::Ch1, note_on(64, 100)),
+            event(1., Channel::Ch1, note_on(64, 0)),
+            event(1., Channel::Ch10, note_on(36, 90)),
+            event(2., Channel::Ch1, note_on(72, 80)),
+        ]);
+        let stats = MidiInspectorTrack::new(track).stats;
+        assert_eq!(stats.note_count, 3, "velocity 0 is a note off");
+        assert_eq!(stats.pitch_range, Some((36, 72)));
+        assert_eq!(stats.channels, [0, 9]);
+        assert_eq!(stats.common_program, Some(5));
+
+        assert_eq!(TrackStats::new(&Track::Midi(vec![])), TrackStats::default());
+        assert_eq!(note_name(60), "C4");
+        assert_eq!(note_name(0), "C-1");
+        assert_eq!(note_name(127), "G9");
+    }
 }
@@ -0,0 +1,211 @@
+//! Differences between two MIDI files, track by track.
+//!
+//! Tracks are paired by index. Events are the same if they have the same message at the same
+//! time, so an inserted event doesn't make the rest of the track look different.
+
+use std::path::{Path, PathBuf};
+
+use midi_msg::{Track, TrackEvent};
+
+use super::{read_tracks, MidiInspectorTrack};
+
+/// Past this many inserted and removed events, the rest of a track is just marked as changed.
+const MAX_EDITS: usize = 2000;
+
+/// Changed events of a track pair. A track missing from one file is entirely changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackDiff {
+    /// Per event of the inspected file's track
+    pub left: Vec<bool>,
+    /// Per event of the compared file's track
+    pub right: Vec<bool>,
+}
+impl TrackDiff {
+    #[must_use]
+    pub fn is_changed(&self) -> bool {
+        self.left.iter().chain(&self.right).any(|&changed| changed)
+    }
+}
+
+/// Second file, shown next to the inspected one
+pub struct Comparison {
+    pub filepath: PathBuf,
+    pub tracks: Vec<MidiInspectorTrack>,
+    /// One per track index in either file
+    pub diffs: Vec<TrackDiff>,
+}
+
+impl Comparison {
+    /// Read another file and diff its tracks against these ones
+    ///
+    /// # Errors
+    ///
+    /// Fails if the other file can't be read or parsed.
+    pub fn new(filepath: &Path, tracks: &[MidiInspectorTrack]) -> anyhow::Result<Self> {
+        let (_, other_tracks) = read_tracks(filepath)?;
+        let diffs = (0..tracks.len().max(other_tracks.len()))
+            .map(|i| {
+                diff_tracks(
+                    tracks.get(i).map(|track| &track.track),
+                    other_tracks.get(i).map(|track| &track.track),
+                )
+            })
+            .collect();
+        Ok(Self {
+            filepath: filepath.to_owned(),
+            tracks: other_tracks,
+            diffs,
+        })
+    }
+
+    /// Number of track pairs with differences
+    #[must_use]
+    pub fn count_changed_tracks(&self) -> usize {
+        self.diffs.iter().filter(|diff| diff.is_changed()).count()
+    }
+}
+
+fn diff_tracks(left: Option<&Track>, right: Option<&Track>) -> TrackDiff {
+    let left = left.map_or(&[][..], Track::events);
+    let right = right.map_or(&[][..], Track::events);
+    let (left, right) = diff_events(left, right);
+    TrackDiff { left, right }
+}
+
+fn same_event(a: &TrackEvent, b: &TrackEvent) -> bool {
+    a.beat_or_frame.total_cmp(&b.beat_or_frame).is_eq() && a.event == b.event
+}
+
+/// Changed flags for both sides. Common start and end are skipped, the middle goes through
+/// Myers' diff.
+fn diff_events(a: &[TrackEvent], b: &[TrackEvent]) -> (Vec<bool>, Vec<bool>) {
+    let prefix = a
+        .iter()
+        .zip(b)
+        .take_while(|(left, right)| same_event(left, right))
+        .count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(left, right)| same_event(left, right))
+        .count();
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+
+    let mut left = vec![false; a.len()];
+    let mut right = vec![false; b.len()];
+    let (left_mid, right_mid) =
+        myers(a_mid, b_mid).unwrap_or_else(|| (vec![true; a_mid.len()], vec![true; b_mid.len()]));
+    left[prefix..prefix + a_mid.len()].copy_from_slice(&left_mid);
+    right[prefix..prefix + b_mid.len()].copy_from_slice(&right_mid);
+    (left, right)
+}
+
+/// Shortest edit script between `a` and `b` as removed and inserted flags. None if it takes
+/// more than `MAX_EDITS`.
+#[allow(clippy::cast_possible_wrap, clippy::many_single_char_names)]
+fn myers(a: &[TrackEvent], b: &[TrackEvent]) -> Option<(Vec<bool>, Vec<bool>)> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (a.len() + b.len()).min(MAX_EDITS) as isize;
+    // Furthest x on each diagonal k = x - y, offset by max + 1
+    let offset = max + 1;
+    let mut v = vec![0_isize; 2 * max as usize + 3];
+    // v of each round for diagonals -d..=d, to walk back the path
+    let mut trace: Vec<Vec<isize>> = vec![];
+
+    let mut end = None;
+    'rounds: for d in 0..=max {
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let i = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) {
+                v[i + 1]
+            } else {
+                v[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && same_event(&a[x as usize], &b[y as usize]) {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                end = Some(d);
+                break 'rounds;
+            }
+        }
+    }
+    let end = end?;
+
+    let mut removed = vec![false; a.len()];
+    let mut inserted = vec![false; b.len()];
+    let (mut x, mut y) = (n, m);
+    for d in (1..=end).rev() {
+        let v = &trace[d as usize];
+        let k = x - y;
+        let i = (k + d) as usize;
+        let prev_k = if k == -d || (k != d && v[i - 1] < v[i + 1]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + d) as usize];
+        let prev_y = prev_x - prev_k;
+        // One edit from the previous round's end, then matching events up to (x, y)
+        if prev_k == k + 1 {
+            inserted[prev_y as usize] = true;
+        } else {
+            removed[prev_x as usize] = true;
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    Some((removed, inserted))
+}
+
+#[cfg(test)]
+mod tests {
+    use midi_msg::{Channel, ChannelVoiceMsg, MidiMsg};
+
+    use super::*;
+
+    fn note(beat: f32, note: u8) -> TrackEvent {
+        TrackEvent {
+            delta_time: 0,
+            event: MidiMsg::ChannelVoice {
+                channel: Channel::Ch1,
+                msg: ChannelVoiceMsg::NoteOn {
+                    note,
+                    velocity: 100,
+                },
+            },
+            beat_or_frame: beat,
+        }
+    }
+
+    #[test]
+    fn test_diff_events() {
+        let a = [note(0., 60), note(1., 62), note(2., 64), note(3., 65)];
+        // 62 removed, 63 inserted, 65 changed
+        let b = [note(0., 60), note(1.5, 63), note(2., 64), note(3., 66)];
+        let (left, right) = diff_events(&a, &b);
+        assert_eq!(left, [false, true, false, true]);
+        assert_eq!(right, [false, true, false, true]);
+
+        let (left, right) = diff_events(&a, &a);
+        assert!(!left.contains(&true) && !right.contains(&true));
+
+        let (left, right) = diff_events(&a, &[]);
+        assert_eq!(left, [true; 4]);
+        assert!(right.is_empty());
+    }
+
+    #[test]
+    fn test_diff_tracks() {
+        let track = Track::Midi(vec![note(0., 60), note(1., 62)]);
+        assert!(!diff_tracks(Some(&track), Some(&track)).is_changed());
+        let diff = diff_tracks(None, Some(&track));
+        assert_eq!(diff.right, [true, true]);
+    }
+}
@@ -0,0 +1,369 @@
+//! Split medley MIDI files into separate songs at marker meta events.
+//!
+//! Every part keeps the setup of the whole file (tempo, programs, controllers...) up to its
+//! start, so it plays the same as it did in the medley.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::bail;
+use midi_msg::{Channel, ChannelVoiceMsg, Meta, MidiFile, MidiMsg, Track, TrackEvent};
+
+use crate::player::playlist::midi_meta::parse_midi;
+
+/// One song cut out of a medley
+pub struct SplitPart {
+    pub label: String,
+    pub midifile: MidiFile,
+}
+
+/// A point where a new part begins
+struct Boundary {
+    tick: u64,
+    label: String,
+}
+
+/// Cut a file at its markers and cue points. Parts without notes are left out.
+/// Returns nothing if there's less than two parts.
+pub fn split_by_markers(midifile: &MidiFile) -> Vec<SplitPart> {
+    let tracks: Vec<Vec<(u64, MidiMsg)>> = midifile
+        .tracks
+        .iter()
+        .filter(|track| matches!(track, Track::Midi(_)))
+        .map(absolute_events)
+        .collect();
+
+    let mut boundaries = vec![Boundary {
+        tick: 0,
+        label: "Intro".into(),
+    }];
+    let mut markers: Vec<(u64, &str)> = tracks
+        .iter()
+        .flatten()
+        .filter_map(|(tick, msg)| match msg {
+            MidiMsg::Meta {
+                msg: Meta::Marker(text) | Meta::CuePoint(text),
+            } if !text.trim().is_empty() => Some((*tick, text.trim())),
+            _ => None,
+        })
+        .collect();
+    markers.sort_by_key(|(tick, _)| *tick);
+    for (tick, label) in markers {
+        match boundaries.last_mut() {
+            Some(last) if last.tick == tick => {
+                // First label wins, but a marker at the very start replaces "Intro".
+                if tick == 0 && last.label == "Intro" {
+                    label.clone_into(&mut last.label);
+                }
+            }
+            _ => boundaries.push(Boundary {
+                tick,
+                label: label.to_owned(),
+            }),
+        }
+    }
+
+    let song_end = tracks
+        .iter()
+        .flatten()
+        .map(|(tick, _)| *tick + 1)
+        .max()
+        .unwrap_or_default();
+    let parts: Vec<SplitPart> = boundaries
+        .iter()
+        .enumerate()
+        .filter_map(|(i, boundary)| {
+            let end = boundaries.get(i + 1).map_or(song_end, |next| next.tick);
+            let has_notes = tracks.iter().flatten().any(|(tick, msg)| {
+                (boundary.tick..end).contains(tick) && get_note(msg).is_some_and(|n| n.2)
+            });
+            has_notes.then(|| SplitPart {
+                label: boundary.label.clone(),
+                midifile: cut(midifile, &tracks, boundary.tick, end),
+            })
+        })
+        .collect();
+
+    if parts.len() < 2 {
+        return vec![];
+    }
+    parts
+}
+
+/// Split a file and write the parts into a directory. Returns paths of the new files.
+///
+/// # Errors
+///
+/// Fails if the file can't be parsed, has no markers, or a part can't be written.
+pub fn split_file(filepath: &Path, out_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let bytes = fs::read(filepath)?;
+    let midifile = parse_midi(&bytes)?;
+    let parts = split_by_markers(&midifile);
+    if parts.is_empty() {
+        bail!("No markers to split at.");
+    }
+
+    let stem = filepath
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut paths = vec![];
+    for (i, part) in parts.iter().enumerate() {
+        let filename = format!("{stem} - {:02} {}.mid", i + 1, sanitize(&part.label));
+        let path = out_dir.join(filename);
+        fs::write(&path, part.midifile.to_midi())?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Keep labels usable as file names
+fn sanitize(label: &str) -> String {
+    label
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || " -_().,'&".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Events with absolute tick times. Running status is dropped, since a part may not begin
+/// with the message it runs from.
+fn absolute_events(track: &Track) -> Vec<(u64, MidiMsg)> {
+    let mut tick = 0;
+    track
+        .events()
+        .iter()
+        .map(|track_event| {
+            tick += u64::from(track_event.delta_time);
+            let msg = match track_event.event.clone() {
+                MidiMsg::RunningChannelVoice { channel, msg } => {
+                    MidiMsg::ChannelVoice { channel, msg }
+                }
+                MidiMsg::RunningChannelMode { channel, msg } => {
+                    MidiMsg::ChannelMode { channel, msg }
+                }
+                msg => msg,
+            };
+            (tick, msg)
+        })
+        .collect()
+}
+
+/// (channel, note, is note on)
+const fn get_note(msg: &MidiMsg) -> Option<(Channel, u8, bool)> {
+    match msg {
+        MidiMsg::ChannelVoice {
+            channel,
+            msg: ChannelVoiceMsg::NoteOn { note, velocity },
+        } => Some((*channel, *note, *velocity > 0)),
+        MidiMsg::ChannelVoice {
+            channel,
+            msg: ChannelVoiceMsg::NoteOff { note, .. },
+        } => Some((*channel, *note, false)),
+        _ => None,
+    }
+}
+
+/// Events that only make sense at the time they happen
+const fn is_momentary(msg: &MidiMsg) -> bool {
+    matches!(
+        msg,
+        MidiMsg::ChannelVoice {
+            msg: ChannelVoiceMsg::NoteOn { .. }
+                | ChannelVoiceMsg::NoteOff { .. }
+                | ChannelVoiceMsg::PolyPressure { .. },
+            ..
+        } | MidiMsg::Meta {
+            msg: Meta::Marker(_) | Meta::CuePoint(_) | Meta::Lyric(_) | Meta::EndOfTrack,
+        }
+    )
+}
+
+/// New file from ticks `start..end`
+fn cut(midifile: &MidiFile, tracks: &[Vec<(u64, MidiMsg)>], start: u64, end: u64) -> MidiFile {
+    let mut header = midifile.header.clone();
+    header.num_tracks = u16::try_from(tracks.len()).unwrap_or(u16::MAX);
+    let division = header.division;
+
+    let tracks = tracks
+        .iter()
+        .map(|events| {
+            let mut part: Vec<(u64, MidiMsg)> = vec![];
+            let mut held = HashSet::new();
+            for (tick, msg) in events {
+                if *tick >= end {
+                    break;
+                }
+                if *tick < start {
+                    // Setup before the part begins
+                    if !is_momentary(msg) {
+                        part.push((0, msg.clone()));
+                    }
+                    continue;
+                }
+                if matches!(
+                    msg,
+                    MidiMsg::Meta {
+                        msg: Meta::EndOfTrack
+                    }
+                ) {
+                    continue;
+                }
+                if let Some((channel, note, on)) = get_note(msg) {
+                    if on {
+                        held.insert((channel as u8, note));
+                    } else if !held.remove(&(channel as u8, note)) {
+                        // Release of a note that started in an earlier part
+                        continue;
+                    }
+                }
+                part.push((tick - start, msg.clone()));
+            }
+
+            // Release notes that continue into the next part
+            let last = end.saturating_sub(start).saturating_sub(1);
+            for (channel, note) in held {
+                part.push((
+                    last,
+                    MidiMsg::ChannelVoice {
+                        channel: Channel::from_u8(channel),
+                        msg: ChannelVoiceMsg::NoteOff { note, velocity: 0 },
+                    },
+                ));
+            }
+            let last = part.last().map_or(0, |(tick, _)| *tick);
+            part.push((
+                last,
+                MidiMsg::Meta {
+                    msg: Meta::EndOfTrack,
+                },
+            ));
+
+            let mut previous = 0;
+            Track::Midi(
+                part.into_iter()
+                    .map(|(tick, event)| {
+                        let delta_time = u32::try_from(tick - previous).unwrap_or(u32::MAX);
+                        previous = tick;
+                        TrackEvent {
+                            delta_time,
+                            event,
+                            beat_or_frame: division
+                                .ticks_to_beats_or_frames(u32::try_from(tick).unwrap_or(u32::MAX)),
+                        }
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+
+    MidiFile { header, tracks }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midi_msg::{Division, Header, SMFFormat};
+
+    fn event(delta_time: u32, event: MidiMsg) -> TrackEvent {
+        TrackEvent {
+            delta_time,
+            event,
+            beat_or_frame: 0.,
+        }
+    }
+    fn note(on: bool, note: u8) -> MidiMsg {
+        let velocity = if on { 100 } else { 0 };
+        MidiMsg::ChannelVoice {
+            channel: Channel::Ch1,
+            msg: ChannelVoiceMsg::NoteOn { note, velocity },
+        }
+    }
+    fn marker(text: &str) -> MidiMsg {
+        MidiMsg::Meta {
+            msg: Meta::Marker(text.into()),
+        }
+    }
+
+    fn medley() -> MidiFile {
+        let program = MidiMsg::ChannelVoice {
+            channel: Channel::Ch1,
+            msg: ChannelVoiceMsg::ProgramChange { program: 40 },
+        };
+        let track = Track::Midi(vec![
+            event(0, marker("First")),
+            event(0, program),
+            event(0, note(true, 60)),
+            event(96, note(false, 60)),
+            // Held over the boundary
+            event(0, note(true, 62)),
+            event(96, marker("Second")),
+            event(0, note(true, 64)),
+            event(48, note(false, 62)),
+            event(48, note(false, 64)),
+            event(
+                0,
+                MidiMsg::Meta {
+                    msg: Meta::EndOfTrack,
+                },
+            ),
+        ]);
+        MidiFile {
+            header: Header {
+                format: SMFFormat::SingleTrack,
+                num_tracks: 1,
+                division: Division::TicksPerQuarterNote(96),
+            },
+            tracks: vec![track],
+        }
+    }
+
+    #[test]
+    fn test_split_by_markers() {
+        let parts = split_by_markers(&medley());
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].label, "First");
+        assert_eq!(parts[1].label, "Second");
+
+        let second = absolute_events(&parts[1].midifile.tracks[0]);
+        // Program change carried over from the first part
+        assert!(second.iter().any(|(tick, msg)| *tick == 0
+            && matches!(
+                msg,
+                MidiMsg::ChannelVoice {
+                    msg: ChannelVoiceMsg::ProgramChange { program: 40 },
+                    ..
+                }
+            )));
+        // Release of the held note isn't carried over
+        let notes: Vec<_> = second.iter().filter_map(|(_, msg)| get_note(msg)).collect();
+        assert_eq!(notes.len(), 2);
+        assert!(notes.iter().all(|(_, note, _)| *note == 64));
+
+        // Held note is released at the end of the first part
+        let first = absolute_events(&parts[0].midifile.tracks[0]);
+        assert!(first
+            .iter()
+            .any(|(_, msg)| get_note(msg) == Some((Channel::Ch1, 62, false))));
+
+        // Output is a valid file
+        let bytes = parts[1].midifile.to_midi();
+        assert!(MidiFile::from_midi(&bytes).is_ok());
+    }
+
+    #[test]
+    fn test_nothing_to_split() {
+        let mut midifile = medley();
+        midifile.tracks[0] =
+            Track::Midi(vec![event(0, note(true, 60)), event(96, note(false, 60))]);
+        assert!(split_by_markers(&midifile).is_empty());
+    }
+}
@@ -1,33 +1,163 @@
 //! Player app logic module
 
 use anyhow::bail;
-use audio::AudioPlayer;
-use eframe::egui::mutex::Mutex;
+use audio::{
+    activity::NoteEvent,
+    backend::AudioBackend,
+    clip_meter::ClipMeter,
+    export::{AudioFormat, ExportJob, ExportSettings, ExportSource},
+    lyrics::Lyrics,
+    markers::{NoteDensity, SongMarker},
+    midi_out::MidiPort,
+    net_stream::{self, NetSink},
+    system_watch::SystemWatcher,
+    AudioPlayer,
+};
+use audio_profile::AudioProfile;
+use auto_dj::{AutoDj, AUTO_DJ_LOOKAHEAD};
+use channel_colors::ChannelColors;
+use cue::Cue;
+use diagnostics::EventLog;
+use end_action::{EndAction, EndActionKind, EndTrigger};
+use font_chain::{FontChain, FontChainLink};
+use gapless::GaplessNext;
+use global_hotkeys::{GlobalHotkeys, HotkeyListener};
+use huge_songs::HugeSongLimits;
+use live_input::LiveInput;
 #[cfg(not(target_os = "windows"))]
+#[cfg(all(not(target_os = "windows"), feature = "gui"))]
 use mediacontrols::create_mediacontrols;
-use playlist::{font_meta::FontMeta, DeletionStatus, Playlist};
-use rodio::Sink;
+use mediacontrols::ControlEvent;
+use party_mix::PartyQueue;
+use play_history::{HistoryEntry, PlayHistory};
+use play_stats::PlayStats;
+use playlist::{
+    enums::FileListMode,
+    font_meta::{FontMeta, PresetInfo},
+    midi_meta::{parse_midi, MidiMeta},
+    DeletionStatus, Playlist,
+};
+use playlist_template::PlaylistTemplate;
+use program_lock::{DrumKit, ProgramLocks};
+use queue::Queue;
+use remote::RemoteServer;
+use scripting::{ScriptHook, ScriptHost};
 use serde_json::Value;
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use serialize_player::{BrokenStateFile, WrittenFiles};
 use soundfont_library::FontLibrary;
-use souvlaki::{MediaControlEvent, MediaControls};
-use std::{error, fmt, fs::File, io::Write, path::PathBuf, sync::Arc, time::Duration, vec};
+#[cfg(all(not(target_os = "windows"), feature = "gui"))]
+use souvlaki::MediaControls;
+use std::{
+    error, fmt,
+    fs::{self, File},
+    io::Write,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    time::{Duration, SystemTime},
+    vec,
+};
+use web_time::Instant;
 
 pub mod audio;
-mod mediacontrols;
+pub mod audio_profile;
+pub mod auto_dj;
+mod auto_pause;
+pub mod channel_colors;
+#[cfg(feature = "gui")]
+mod cover_art;
+pub mod cue;
+pub mod diagnostics;
+pub mod end_action;
+pub mod font_chain;
+mod gapless;
+pub mod general_midi;
+pub mod global_hotkeys;
+pub mod huge_songs;
+mod live_input;
+pub mod mediacontrols;
+pub mod party_mix;
+pub mod play_history;
+pub mod play_stats;
 pub mod playlist;
+pub mod playlist_template;
+pub mod problems;
+pub mod program_lock;
+pub mod queue;
+pub mod remote;
+pub mod scripting;
 pub mod serialize_player;
+pub mod session;
 pub mod soundfont_library;
 pub mod soundfont_list;
 
-const REMOVED_PLAYLIST_HISTORY_LEN: usize = 100;
+const REMOVAL_HISTORY_LEN: usize = 100;
+/// Name of the playlist Auto-DJ plays into
+const AUTO_DJ_NAME: &str = "Auto-DJ";
+/// Percentage. Above 100, a limiter keeps the boosted audio from clipping.
+pub const MAX_VOLUME: f32 = 200.;
+/// Volume curve: 0..=100% spans this many decibels below full amplitude.
+const VOLUME_RANGE_DB: f32 = 60.;
+/// Volume curve: 100..=`MAX_VOLUME` spans up to this. 6.02 dB doubles the amplitude.
+const VOLUME_BOOST_DB: f32 = 6.0206;
+/// Seconds
+const DEFAULT_AUTOSAVE_INTERVAL: u64 = 30;
+/// Seconds
+pub const AUTOSAVE_INTERVAL_RANGE: RangeInclusive<u64> = 5..=600;
+
+/// Something removed that can be brought back
+enum Removal {
+    Playlist(Box<Playlist>),
+    Songs {
+        playlist_id: usize,
+        songs: Vec<MidiMeta>,
+    },
+    Fonts {
+        playlist_id: usize,
+        fonts: Vec<FontMeta>,
+    },
+}
 
-/// To be handled in gui
+/// Player state changes. Subscribe with [`Player::subscribe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PlayerEvent {
     /// Bring window to focus
     Raise,
     Quit,
     NotifyError(String),
+    /// A new song started playing
+    SongChanged {
+        song: String,
+        soundfont: Option<String>,
+    },
+    /// Play order was rebuilt
+    QueueChanged,
+    /// A different soundfont is in use, or none when stopped
+    FontChanged {
+        soundfont: Option<String>,
+    },
+    /// Playback reached the next whole second
+    PositionTick {
+        position: Duration,
+        length: Duration,
+    },
+    /// Playlists were opened, closed, or rearranged
+    PlaylistListChanged,
+    /// The playing song's file changed on disk and was read again
+    SongReloaded {
+        song: String,
+    },
+    /// Files of the library or a playlist were checked after startup
+    FilesRefreshed,
+    /// Message for the user, e.g. from the user script
+    NotifyInfo(String),
+    /// A file couldn't be played. It's on the problem list.
+    ProblemFound(String),
+    /// Playback finished, and the end action waits to be cancelled
+    EndActionPending(String),
+    /// Paused because of the system, e.g. headphones were unplugged. Tells why.
+    AutoPaused(String),
 }
 
 #[derive(Serialize_repr, Deserialize_repr, PartialEq, Eq, Default, Clone, Copy)]
@@ -51,9 +181,21 @@ impl TryFrom<u8> for RepeatMode {
     }
 }
 
+/// When a newly picked soundfont takes effect
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Eq, Default, Clone, Copy, Debug)]
+#[repr(u8)]
+pub enum FontChangeMode {
+    /// Switch during playback, keeping the position
+    #[default]
+    Instant = 0,
+    /// Keep the current soundfont until the next song starts
+    NextSong = 1,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum PlayerError {
     InvalidPlaylistIndex { index: usize },
+    InvalidSongIndex { index: usize },
     CantMovePlaylist,
     CantSwitchPlaylist,
     NoQueueIndex,
@@ -61,6 +203,9 @@ pub enum PlayerError {
     PlaylistAlreadyOpen,
     PlaylistSaveFailed,
     DebugBlockSaving,
+    StateRecoveryPending,
+    NothingToPlay,
+    NoSongSelected,
 }
 impl error::Error for PlayerError {}
 impl fmt::Display for PlayerError {
@@ -69,6 +214,7 @@ impl fmt::Display for PlayerError {
             Self::InvalidPlaylistIndex { index } => {
                 write!(f, "Playlist index {index} is out of bounds.")
             }
+            Self::InvalidSongIndex { index } => write!(f, "Song index {index} is out of bounds."),
             Self::CantMovePlaylist => write!(f, "Can't move this playlist further."),
             Self::CantSwitchPlaylist => write!(f, "Can't switch playlists further."),
             Self::NoQueueIndex => write!(f, "No queue index!"),
@@ -76,6 +222,14 @@ impl fmt::Display for PlayerError {
             Self::PlaylistAlreadyOpen => write!(f, "Playlist is already open."),
             Self::PlaylistSaveFailed => write!(f, "Failed to save playlist."),
             Self::DebugBlockSaving => write!(f, "debug_block_saving == true"),
+            Self::StateRecoveryPending => {
+                write!(
+                    f,
+                    "Saving is paused until broken state files are dealt with."
+                )
+            }
+            Self::NothingToPlay => write!(f, "There are no songs to play."),
+            Self::NoSongSelected => write!(f, "No song selected."),
         }
     }
 }
@@ -86,61 +240,247 @@ pub struct Player {
     audioplayer: AudioPlayer,
     /// Is there playback going on? Paused playback also counts.
     is_playing: bool,
+    /// Separate lightweight player for the MIDI inspector. Runs independently of the playlists.
+    preview_audioplayer: AudioPlayer,
+    /// When both are playing, only one of them is heard. True means preview.
+    preview_audible: bool,
+    /// Next song playing on the preview player, waiting to be crossfaded in
+    cue: Option<Cue>,
+    /// MIDI input port played live
+    live_input: LiveInput,
+    /// Quit, suspend or command counting down after playback finished, and when it runs
+    pending_end_action: Option<(EndActionKind, Instant)>,
+    /// Suspend and command processes report failures here once they exit
+    end_action_errors: Receiver<String>,
+    end_action_sender: Sender<String>,
+    /// Listens for suspend and unplugged headphones while auto-pause is on
+    system_watcher: Option<SystemWatcher>,
 
     // -- Control
-    /// Ranges 0.0..=100.0 as in percentage.
+    /// Ranges 0.0..=`MAX_VOLUME` as in percentage.
     volume: f32,
-    /// OS integration
-    #[cfg(not(target_os = "windows"))]
-    mediacontrol: MediaControls,
+    /// OS integration. None if it couldn't be set up.
+    #[cfg(all(not(target_os = "windows"), feature = "gui"))]
+    mediacontrol: Option<MediaControls>,
     /// Events from system to the player.
-    mediacontrol_events: Arc<Mutex<Vec<MediaControlEvent>>>,
-    /// Events from player to the gui.
-    #[allow(clippy::struct_field_names)]
-    player_events: Vec<PlayerEvent>,
+    mediacontrol_events: Receiver<ControlEvent>,
+    /// Media control and global hotkey threads send their events here.
+    mediacontrol_sender: Sender<ControlEvent>,
+    /// Hotkeys that work while another app is focused
+    global_hotkeys: GlobalHotkeys,
+    /// Holds the hotkeys while they are enabled
+    hotkey_listener: Option<HotkeyListener>,
+    /// Virtual MIDI output, open while something is sent to it
+    midi_port: Option<MidiPort>,
+    /// Errors of the output recording, until its file is done
+    recording_errors: Option<Receiver<String>>,
+    /// Audio device sink, put aside while streaming to the network
+    local_sink: Option<Box<dyn AudioBackend>>,
+    /// Running while remote control is on
+    remote: Option<RemoteServer>,
+    /// Event stream listeners
+    subscribers: Vec<Sender<PlayerEvent>>,
+    /// Second of the last position tick
+    last_position_tick: Option<u64>,
+    /// Playing song file and its modification time, for noticing when it changes
+    followed_file: Option<(PathBuf, SystemTime)>,
+    /// Recent events for diagnostics
+    log: EventLog,
+    /// User script and the hooks waiting to run
+    scripts: ScriptHost,
 
     // -- Data
     pub font_lib: FontLibrary,
+    /// Where soundfonts are looked for, in order
+    pub font_chain: FontChain,
+    /// The soundfont in use and which chain link provided it
+    active_font: Option<(FontChainLink, FontMeta)>,
     playlists: Vec<Playlist>,
     /// Which playlist is open
     playlist_idx: usize,
     /// Which playlist was last playing music
     playing_playlist_idx: usize,
-    /// For undo closing
-    removed_playlists: Vec<Playlist>,
+    /// Loaded playlists whose files haven't been checked yet, by id. The last one goes first.
+    pending_refresh: Vec<usize>,
+    /// The slow part of loading waits until the window is open
+    deferred_loading_started: bool,
+    /// Combined queue of all playlists, when playing all of them
+    party_queue: Option<PartyQueue>,
+    /// Endless random play into its own playlist
+    auto_dj: Option<AutoDj>,
+    /// Play counts, for avoiding repeats
+    play_stats: PlayStats,
+    /// Songs played this session
+    play_history: PlayHistory,
+    /// Files that failed to play, and why
+    render_failures: Vec<(PathBuf, String)>,
+    /// Soundfont for the next song, instead of the fallback chain
+    font_override: Option<(FontChainLink, FontMeta)>,
+    /// Song queued to follow the current one without a gap
+    gapless_next: Option<GaplessNext>,
+    /// Channels that ignore program changes, unless the playlist overrides
+    program_locks: ProgramLocks,
+    /// Percussion preset forced on channel 10
+    drum_kit: Option<DrumKit>,
+    /// Named reverb/EQ/gain presets that playlists can use
+    audio_profiles: Vec<AudioProfile>,
+    /// State files that failed to load. Saving is blocked while there are any.
+    broken_state: Vec<BrokenStateFile>,
+    /// What was last saved, so unchanged state files aren't written again
+    written_files: WrittenFiles,
+    /// For undoing closed playlists and cleared lists
+    removal_history: Vec<Removal>,
+    /// Playlist ids, most recently used first
+    recent_playlists: Vec<usize>,
+    /// Position in `recent_playlists` while cycling through them
+    recent_cycle_pos: Option<usize>,
 
     // -- settings
     shuffle: bool,
     repeat: RepeatMode,
-    pub autosave: bool,
+    /// Save portable playlist files automatically instead of manually
+    pub autosave_portable: bool,
+    /// Save in-app playlists periodically, not just on exit
+    pub autosave_app: bool,
+    pub font_change_mode: FontChangeMode,
+    /// Engage the limiter when the output clips
+    auto_limiter: bool,
+    /// Send MIDI clock and transport to the MIDI output port
+    midi_clock: bool,
+    /// Forward played events to the MIDI output port
+    midi_thru: bool,
+    /// Output device to play on, by name. None plays on the system default.
+    pub output_device: Option<String>,
+    /// Stereo pair of the output device to play on. 0 is outputs 1/2.
+    pub output_pair: u16,
+    /// Play to a network stream instead of the audio device
+    stream_output: bool,
+    stream_port: u16,
+    /// Let other devices listen to the stream, with the token
+    stream_lan: bool,
+    stream_token: String,
+    remote_port: u16,
+    /// Let other computers use remote control, with the token
+    remote_lan: bool,
+    remote_token: String,
+    /// Seconds between periodic saves
+    autosave_interval: u64,
+    /// Channel colors of visualizers
+    pub channel_colors: ChannelColors,
+    /// Defaults of new playlists
+    pub playlist_template: PlaylistTemplate,
+    /// Reload the playing song when its file changes
+    pub follow_file: bool,
+    /// Audio file format of piano roll exports
+    pub export_format: AudioFormat,
+    /// What counts as a song too heavy to play without asking
+    pub huge_song_limits: HugeSongLimits,
+    /// What to do when a song or the queue finishes
+    pub end_action: EndAction,
+    /// Run hooks of the user script
+    scripting: bool,
     pub debug_block_saving: bool,
 }
 
 impl Default for Player {
     fn default() -> Self {
-        let mediacontrol_events = Arc::new(Mutex::new(vec![]));
-        #[cfg(not(target_os = "windows"))]
-        let mediacontrol = create_mediacontrols(Arc::clone(&mediacontrol_events));
+        let player = Self::headless();
+        #[cfg(all(not(target_os = "windows"), feature = "gui"))]
+        let player = Self {
+            mediacontrol: create_mediacontrols(player.mediacontrol_sender.clone())
+                .inspect_err(|e| println!("Media controls aren't available: {e:?}"))
+                .ok(),
+            ..player
+        };
+        player
+    }
+}
+
+impl Player {
+    /// Player without OS media controls, for tests with a null sink and for embedding the core.
+    #[must_use]
+    pub fn headless() -> Self {
+        let (mediacontrol_sender, mediacontrol_events) = mpsc::channel();
+        let (end_action_sender, end_action_errors) = mpsc::channel();
 
         Self {
             audioplayer: AudioPlayer::default(),
             is_playing: false,
+            preview_audioplayer: AudioPlayer::default(),
+            live_input: LiveInput::default(),
+            pending_end_action: None,
+            end_action_errors,
+            end_action_sender,
+            system_watcher: None,
+            preview_audible: false,
+            cue: None,
 
             volume: 100.,
-            #[cfg(not(target_os = "windows"))]
-            mediacontrol,
+            #[cfg(all(not(target_os = "windows"), feature = "gui"))]
+            mediacontrol: None,
             mediacontrol_events,
-            player_events: vec![],
+            mediacontrol_sender,
+            global_hotkeys: GlobalHotkeys::default(),
+            hotkey_listener: None,
+            midi_port: None,
+            recording_errors: None,
+            local_sink: None,
+            remote: None,
+            subscribers: vec![],
+            last_position_tick: None,
+            followed_file: None,
+            log: EventLog::default(),
+            scripts: ScriptHost::default(),
 
             font_lib: FontLibrary::default(),
+            font_chain: FontChain::default(),
+            active_font: None,
             playlists: vec![],
             playlist_idx: 0,
             playing_playlist_idx: 0,
-            removed_playlists: vec![],
+            pending_refresh: vec![],
+            deferred_loading_started: false,
+            party_queue: None,
+            auto_dj: None,
+            play_stats: PlayStats::default(),
+            play_history: PlayHistory::default(),
+            render_failures: vec![],
+            font_override: None,
+            gapless_next: None,
+            program_locks: ProgramLocks::new(),
+            drum_kit: None,
+            audio_profiles: vec![],
+            broken_state: vec![],
+            written_files: WrittenFiles::default(),
+            removal_history: vec![],
+            recent_playlists: vec![],
+            recent_cycle_pos: None,
 
             shuffle: false,
             repeat: RepeatMode::Disabled,
-            autosave: true,
+            autosave_portable: true,
+            autosave_app: true,
+            font_change_mode: FontChangeMode::default(),
+            auto_limiter: false,
+            midi_clock: false,
+            output_device: None,
+            output_pair: 0,
+            stream_output: false,
+            stream_port: net_stream::DEFAULT_PORT,
+            stream_lan: false,
+            stream_token: String::new(),
+            remote_port: remote::DEFAULT_PORT,
+            remote_lan: false,
+            remote_token: String::new(),
+            midi_thru: false,
+            autosave_interval: DEFAULT_AUTOSAVE_INTERVAL,
+            channel_colors: ChannelColors::default(),
+            playlist_template: PlaylistTemplate::default(),
+            follow_file: false,
+            export_format: AudioFormat::default(),
+            huge_song_limits: HugeSongLimits::default(),
+            end_action: EndAction::default(),
+            scripting: false,
             debug_block_saving: false,
         }
     }
@@ -148,8 +488,31 @@ impl Default for Player {
 
 impl Player {
     /// You need to give the audio player a sink before it can do anything.
-    pub fn set_sink(&mut self, value: Option<Sink>) {
-        self.audioplayer.set_sink(value);
+    /// While streaming to the network, it's kept for when that stops.
+    pub fn set_sink(&mut self, value: Option<Box<dyn AudioBackend>>) {
+        if self.stream_output {
+            self.local_sink = value;
+        } else {
+            self.audioplayer.set_sink(value);
+        }
+    }
+    /// Sink for the inspector preview player. Should be separate from the main one.
+    pub fn set_preview_sink(&mut self, value: Option<Box<dyn AudioBackend>>) {
+        self.preview_audioplayer.set_sink(value);
+    }
+    /// Move playback to new sinks, e.g. on another output device, and continue where it was.
+    pub fn replace_sinks(
+        &mut self,
+        sink: Box<dyn AudioBackend>,
+        preview_sink: Box<dyn AudioBackend>,
+    ) {
+        if self.stream_output {
+            self.local_sink = Some(sink);
+        } else {
+            self.audioplayer.move_to_sink(sink);
+        }
+        self.preview_audioplayer.move_to_sink(preview_sink);
+        self.update_volume();
     }
 
     pub fn get_default_soundfont(&self) -> Option<&FontMeta> {
@@ -159,18 +522,57 @@ impl Player {
     /// GUI frame update
     pub fn update(&mut self) {
         self.ensure_playlist_existence();
+        self.update_cue();
 
+        self.update_gapless();
         if !self.is_paused() && self.is_empty() {
-            if let Err(e) = self.advance_queue() {
-                self.push_error(e.to_string());
+            self.scripts.queue_song_ended();
+            if !self.trigger_end_action(EndTrigger::Song) {
+                if let Err(e) = self.advance_queue() {
+                    self.push_playback_error(&e);
+                }
             }
         }
+        self.update_end_action();
+        // Preview ended, give the audio back to playlist playback
+        if self.preview_audible && self.preview_audioplayer.is_empty() {
+            self.set_preview_audible(false);
+        }
 
         self.get_playlist_mut().delete_queued();
+        let scanning = self.font_lib.is_scanning();
         self.font_lib.update();
+        if scanning && !self.font_lib.is_scanning() {
+            self.emit(&PlayerEvent::FilesRefreshed);
+        }
         self.delete_queued_playlists();
+        self.update_recent_playlists();
 
+        self.handle_hotkey_errors();
+        self.handle_midi_port_errors();
+        self.update_live_input();
+        self.update_auto_pause();
+        self.handle_recording_errors();
         self.mediacontrol_handle_events();
+        self.handle_remote_commands();
+        self.emit_position_tick();
+        self.run_script_hooks();
+    }
+
+    fn emit_position_tick(&mut self) {
+        if self.is_paused() {
+            return;
+        }
+        let position = self.get_playback_position();
+        let second = position.as_secs();
+        if self.last_position_tick == Some(second) {
+            return;
+        }
+        self.last_position_tick = Some(second);
+        self.emit(&PlayerEvent::PositionTick {
+            position,
+            length: self.get_playback_length(),
+        });
     }
 
     fn delete_queued_playlists(&mut self) {
@@ -187,14 +589,13 @@ impl Player {
                 DeletionStatus::QueuedDiscard => (),
             }
 
-            if self.autosave && playlist.is_portable() {
+            if self.autosave_portable && playlist.is_portable() {
                 let _ = playlist.save_portable();
             }
 
-            self.removed_playlists.push(self.playlists.remove(index));
-            while self.removed_playlists.len() > REMOVED_PLAYLIST_HISTORY_LEN {
-                self.removed_playlists.remove(0);
-            }
+            let playlist = self.playlists.remove(index);
+            self.push_removal(Removal::Playlist(Box::new(playlist)));
+            self.emit(&PlayerEvent::PlaylistListChanged);
 
             let last_selected = self.playlist_idx == self.playlists.len();
             // First selected: Never decrement
@@ -215,74 +616,664 @@ impl Player {
 
     /// Start playing (from a fully stopped state)
     pub fn start(&mut self) {
-        self.playing_playlist_idx = self.playlist_idx;
+        self.start_playlist(self.playlist_idx);
+    }
+    /// Start playing from the selected song of a playlist
+    fn start_playlist(&mut self, playlist_index: usize) {
+        self.party_queue = None;
+        self.playing_playlist_idx = playlist_index;
         let shuffle = self.shuffle;
-        self.get_playing_playlist_mut().rebuild_queue(shuffle);
+        let playlist = self.get_playing_playlist_mut();
+        if !playlist.take_restored_queue() {
+            playlist.rebuild_queue(shuffle);
+            self.emit(&PlayerEvent::QueueChanged);
+        }
         if let Err(e) = self.play_selected_song() {
             println!("{e}");
-            self.push_error(e.to_string());
+            self.push_playback_error(&e);
         }
     }
 
-    fn get_soundfont(&mut self) -> Result<&mut FontMeta, PlayerError> {
-        if let Some(font_index) = self.get_playing_playlist().get_font_idx() {
-            return Ok(&mut self.get_playing_playlist_mut().get_fonts_mut()[font_index]);
+    /// Index of the song that is playing in the playing playlist. Selection may differ.
+    pub fn get_playing_song_idx(&self) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        self.get_playing_playlist().get_queue().get_current()
+    }
+    /// Switch to the playing playlist and select the playing song.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the playing playlist is gone.
+    pub fn go_to_playing_song(&mut self) -> anyhow::Result<()> {
+        let song_idx = self.get_playing_song_idx();
+        self.switch_to_playlist(self.playing_playlist_idx)?;
+        if song_idx.is_some() {
+            let _ = self.get_playlist_mut().set_song_idx(song_idx);
+        }
+        Ok(())
+    }
+    /// Jump to a random song of the open playlist, regardless of shuffle.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the open playlist has no songs.
+    pub fn play_random_song(&mut self) -> anyhow::Result<()> {
+        let len = self.get_playlist().get_songs().len();
+        if len == 0 {
+            bail!(PlayerError::NothingToPlay);
+        }
+        let current = if self.playlist_idx == self.playing_playlist_idx {
+            self.get_playing_song_idx()
+        } else {
+            None
+        };
+        let index = random_song_idx(len, current, rand::random());
+        self.get_playlist_mut().set_song_idx(Some(index))?;
+        self.start();
+        Ok(())
+    }
+    /// Start playing songs from every open playlist in one queue, shuffled if shuffle is on.
+    pub fn start_party_mix(&mut self) {
+        let shuffle_seed = self.shuffle.then(rand::random);
+        let queue = PartyQueue::build(&self.playlists, shuffle_seed);
+        if queue.is_empty() {
+            self.push_error(PlayerError::NothingToPlay.to_string());
+            return;
+        }
+        self.party_queue = Some(queue);
+        self.emit(&PlayerEvent::QueueChanged);
+        if let Err(e) = self.play_party_entry() {
+            self.push_playback_error(&e);
         }
-        self.font_lib
-            .get_selected_mut()
-            .ok_or(PlayerError::NoSoundfont)
+    }
+    /// Position and length of the combined queue, when playing all playlists
+    pub fn get_party_position(&self) -> Option<(usize, usize)> {
+        self.party_queue
+            .as_ref()
+            .map(|queue| (queue.get_index(), queue.len()))
+    }
+    /// Switch to the playlist of current combined queue entry and play it. Entries whose
+    /// playlist or song has gone away are dropped.
+    fn play_party_entry(&mut self) -> anyhow::Result<()> {
+        loop {
+            let Some(queue) = &self.party_queue else {
+                bail!(PlayerError::NoQueueIndex);
+            };
+            let Some(entry) = queue.get_current().cloned() else {
+                self.stop();
+                bail!(PlayerError::NothingToPlay);
+            };
+            let found = self
+                .find_playlist(entry.playlist_id)
+                .and_then(|playlist_idx| {
+                    self.playlists[playlist_idx]
+                        .get_songs()
+                        .iter()
+                        .position(|song| song.get_path() == entry.song)
+                        .map(|song_idx| (playlist_idx, song_idx))
+                });
+            let huge = found.is_some_and(|(playlist_idx, song_idx)| {
+                self.huge_song_limits.skip_in_queue
+                    && self.check_huge_song(playlist_idx, song_idx).is_some()
+            });
+            let Some((playlist_idx, song_idx)) = found.filter(|_| !huge) else {
+                if let Some(queue) = &mut self.party_queue {
+                    queue.remove_current();
+                }
+                continue;
+            };
+
+            // The playlist's own queue holds just this song, so its font gets used.
+            self.playing_playlist_idx = playlist_idx;
+            *self.playlists[playlist_idx].get_queue_mut() = Queue::single(song_idx);
+            return self.play_selected_song();
+        }
+    }
+    /// Endless random play from a directory. Songs are added to a new playlist as they're needed.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there are no midi files in the directory.
+    pub fn start_auto_dj(&mut self, dir: PathBuf) -> anyhow::Result<()> {
+        let mut auto_dj = AutoDj::new(dir);
+        if auto_dj.get_song_count() == 0 {
+            bail!(PlayerError::NothingToPlay);
+        }
+        self.new_playlist();
+        let index = self.playlists.len() - 1;
+        let playlist = &mut self.playlists[index];
+        AUTO_DJ_NAME.clone_into(&mut playlist.name);
+        playlist.set_song_list_mode(FileListMode::Manual);
+        auto_dj.set_playlist_id(playlist.get_id());
+        self.auto_dj = Some(auto_dj);
+        self.fill_auto_dj_queue();
+
+        self.switch_to_playlist(index)?;
+        self.get_playlist_mut().set_song_idx(Some(0))?;
+        self.start();
+        Ok(())
+    }
+    /// Stop adding songs. The playlist stays.
+    pub fn stop_auto_dj(&mut self) {
+        self.auto_dj = None;
+    }
+    pub const fn get_auto_dj(&self) -> Option<&AutoDj> {
+        self.auto_dj.as_ref()
+    }
+    /// Keep a few songs queued after the playing one in the Auto-DJ playlist.
+    fn fill_auto_dj_queue(&mut self) {
+        let Some(auto_dj) = &self.auto_dj else {
+            return;
+        };
+        let Some(index) = self.find_playlist(auto_dj.get_playlist_id()) else {
+            // Playlist was closed
+            self.auto_dj = None;
+            return;
+        };
+        let playlist = &mut self.playlists[index];
+        // Queue is empty before playback starts
+        let upcoming = |playlist: &Playlist| -> Vec<PathBuf> {
+            playlist.get_queue().get_position().map_or_else(
+                || {
+                    playlist
+                        .get_songs()
+                        .iter()
+                        .map(MidiMeta::get_path)
+                        .collect()
+                },
+                |queue_idx| {
+                    let mut paths = playlist.get_queue_paths();
+                    paths.drain(..queue_idx.min(paths.len()));
+                    paths
+                },
+            )
+        };
+        loop {
+            let queued = upcoming(playlist);
+            if queued.len() > AUTO_DJ_LOOKAHEAD {
+                break;
+            }
+            let Some(song) = auto_dj.pick(&self.play_stats, &queued, rand::random()) else {
+                break;
+            };
+            let result = if playlist.get_queue().get_position().is_some() {
+                playlist.enqueue_song(&song)
+            } else {
+                playlist.add_song(song)
+            };
+            // Nothing new to add
+            if result.is_err() || upcoming(playlist).len() == queued.len() {
+                break;
+            }
+        }
+        if index == self.playing_playlist_idx {
+            self.emit(&PlayerEvent::QueueChanged);
+        }
+    }
+    /// Rescan playlists that auto-play new files. New songs of the playing playlist are queued
+    /// to play next. If nothing is playing, new songs of the open playlist start playing.
+    pub fn watch_new_songs(&mut self) {
+        for index in 0..self.playlists.len() {
+            if !self.playlists[index].get_autoplay_new_files() {
+                continue;
+            }
+            let new_songs = self.playlists[index].scan_new_songs();
+            let Some(first) = new_songs.first() else {
+                continue;
+            };
+            if self.is_playing {
+                if index == self.playing_playlist_idx && self.party_queue.is_none() {
+                    self.playlists[index].enqueue_next(&new_songs);
+                    self.emit(&PlayerEvent::QueueChanged);
+                }
+            } else if index == self.playlist_idx {
+                let playlist = &mut self.playlists[index];
+                let song_idx = playlist
+                    .get_songs()
+                    .iter()
+                    .position(|song| song.get_path() == *first);
+                if playlist.set_song_idx(song_idx).is_ok() {
+                    self.start();
+                }
+            }
+        }
+    }
+    /// If `follow_file` is on and the playing song's file has changed, reload it and continue
+    /// from the same position.
+    pub fn follow_playing_file(&mut self) {
+        if !self.follow_file || self.is_empty() {
+            return;
+        }
+        let Some((path, time)) = &self.followed_file else {
+            return;
+        };
+        let Some(new_time) = modified_time(path).filter(|new_time| new_time != time) else {
+            return;
+        };
+        self.followed_file = Some((path.clone(), new_time));
+        let Some(song_idx) = self.get_playing_song_idx() else {
+            return;
+        };
+        let song = &mut self.get_playing_playlist_mut().get_songs_mut()[song_idx];
+        song.refresh();
+        // Might be caught halfway through writing, try again on the next change.
+        if let Err(e) = song.get_status() {
+            self.push_error(e.to_string());
+            return;
+        }
+        let song = song.clone();
+        let name = song.get_name();
+        self.audioplayer.reload_midifile(song);
+        self.emit(&PlayerEvent::SongReloaded { song: name });
+    }
+    /// Start the slow part of loading state: scanning the library and checking playlist files.
+    /// To be called once the window is open.
+    pub fn start_deferred_loading(&mut self) {
+        if self.deferred_loading_started {
+            return;
+        }
+        self.deferred_loading_started = true;
+        self.font_lib.refresh_in_background();
+        // Open playlist first
+        let open_id = self.playlists.get(self.playlist_idx).map(Playlist::get_id);
+        if let Some(position) = self
+            .pending_refresh
+            .iter()
+            .position(|&id| Some(id) == open_id)
+        {
+            let id = self.pending_refresh.remove(position);
+            self.pending_refresh.push(id);
+        }
+    }
+    /// Check the files of one loaded playlist. Called from the update thread until all are
+    /// done.
+    pub fn refresh_pending_playlist(&mut self) {
+        if !self.deferred_loading_started {
+            return;
+        }
+        let Some(id) = self.pending_refresh.pop() else {
+            return;
+        };
+        if let Some(playlist) = self
+            .playlists
+            .iter_mut()
+            .find(|playlist| playlist.get_id() == id)
+        {
+            playlist.refresh();
+            self.emit(&PlayerEvent::FilesRefreshed);
+        }
+    }
+    /// Library fonts haven't been found yet
+    pub const fn is_library_loading(&self) -> bool {
+        !self.font_lib.get_paths().is_empty()
+            && (!self.deferred_loading_started || self.font_lib.is_scanning())
+    }
+    /// Playlist was loaded, but its files haven't been checked yet
+    pub fn is_refresh_pending(&self, index: usize) -> bool {
+        self.playlists
+            .get(index)
+            .is_some_and(|playlist| self.pending_refresh.contains(&playlist.get_id()))
+    }
+    /// Move in the combined queue. Returns false if there was nowhere to go.
+    fn step_party_queue(&mut self, forward: bool) -> bool {
+        let wrap = self.repeat == RepeatMode::Queue;
+        let Some(queue) = &mut self.party_queue else {
+            return false;
+        };
+        let moved = if forward {
+            queue.advance(wrap)
+        } else {
+            queue.go_back(wrap)
+        };
+        if moved {
+            if let Err(e) = self.play_party_entry() {
+                self.push_playback_error(&e);
+            }
+        }
+        moved
+    }
+
+    /// Walk the fallback chain and return the first soundfont that works.
+    fn resolve_soundfont(
+        &mut self,
+        song_index: Option<usize>,
+    ) -> anyhow::Result<(FontChainLink, FontMeta)> {
+        let playlist_index = if self.is_playing {
+            self.playing_playlist_idx
+        } else {
+            self.playlist_idx
+        };
+        self.resolve_soundfont_in(playlist_index, song_index)
+    }
+    /// Like `resolve_soundfont`, but for a song of any playlist.
+    fn resolve_soundfont_in(
+        &mut self,
+        playlist_index: usize,
+        song_index: Option<usize>,
+    ) -> anyhow::Result<(FontChainLink, FontMeta)> {
+        let mut last_error = None;
+        for link in self.font_chain.get_enabled_links() {
+            let font = match link {
+                FontChainLink::Song => song_index
+                    .and_then(|index| self.playlists[playlist_index].get_songs().get(index))
+                    .and_then(|song| song.get_soundfont().cloned())
+                    .map(FontMeta::new),
+                FontChainLink::Playlist => {
+                    let playlist = &mut self.playlists[playlist_index];
+                    playlist.get_font_idx().map(|font_index| {
+                        let font = &mut playlist.get_fonts_mut()[font_index];
+                        font.refresh();
+                        font.clone()
+                    })
+                }
+                FontChainLink::Library => self.font_lib.get_selected_mut().map(|font| {
+                    font.refresh();
+                    font.clone()
+                }),
+                FontChainLink::BuiltIn => Some(FontMeta::builtin()),
+            };
+            let Some(font) = font else {
+                continue;
+            };
+            match font.get_status() {
+                Ok(()) => return Ok((link, font)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        if let Some(e) = last_error {
+            return Err(e);
+        }
+        bail!(PlayerError::NoSoundfont)
     }
 
     /// Load currently selected song & font from playlist and start playing
     fn play_selected_song(&mut self) -> anyhow::Result<()> {
+        let font_override = self.font_override.take();
         self.audioplayer.stop_playback()?;
-        let Some(queue_index) = self.get_playing_playlist().queue_idx else {
+        let Some(midi_index) = self.get_playing_playlist().get_queue().get_current() else {
             bail!(PlayerError::NoQueueIndex);
         };
-        let midi_index = self.get_playing_playlist().queue[queue_index];
 
-        let sf = self.get_soundfont()?;
-        let sf_path = sf.get_path();
-        sf.refresh();
-        sf.get_status()?;
+        let (font_link, font) = match font_override {
+            Some(font) => font,
+            None => self.resolve_soundfont(Some(midi_index))?,
+        };
 
         let mid = &mut self.get_playing_playlist_mut().get_songs_mut()[midi_index];
-        let mid_path = mid.get_path();
         mid.refresh();
         mid.get_status()?;
+        let song = mid.clone();
 
         let playlist = self.get_playing_playlist_mut();
         playlist.set_song_idx(Some(midi_index))?;
 
         // Play
-        self.audioplayer.set_soundfont(sf_path);
-        self.audioplayer.set_midifile(mid_path);
+        self.apply_program_locks();
+        self.apply_audio_profile();
+        self.set_active_font(Some((font_link, font.clone())));
+        let font_path = font.get_path();
+        self.audioplayer.set_soundfont(font);
+        self.audioplayer.set_midifile(song);
         self.is_playing = true;
 
         self.update_volume();
-        self.audioplayer.start_playback()?;
-
-        self.mediacontrol_update_song();
-
+        let song_path = self.get_playing_playlist().get_songs()[midi_index].get_path();
+        if let Err(e) = self.audioplayer.start_playback() {
+            self.record_render_failure(&e, &song_path, &font_path);
+            return Err(e);
+        }
+        self.clear_render_failure(&song_path, &font_path);
+        self.song_started(midi_index);
         Ok(())
     }
-
+    /// Bookkeeping of a song of the playing playlist that just started
+    fn song_started(&mut self, midi_index: usize) {
+        let song_path = self.get_playing_playlist().get_songs()[midi_index].get_path();
+        self.followed_file = modified_time(&song_path).map(|time| (song_path.clone(), time));
+        self.play_stats.record_play(&song_path, play_stats::now());
+        if let Some((font_link, font)) = self.active_font.clone() {
+            self.play_history.push(HistoryEntry {
+                playlist_id: self.get_playing_playlist().get_id(),
+                song: song_path,
+                font,
+                font_link,
+                speed: self.get_speed(),
+            });
+        }
+        self.fill_auto_dj_queue();
+
+        self.last_position_tick = None;
+        self.emit(&PlayerEvent::SongChanged {
+            song: self.get_playing_playlist().get_songs()[midi_index].get_name(),
+            soundfont: self.active_font.as_ref().map(|(_, font)| font.get_name()),
+        });
+        self.scripts.queue(ScriptHook::SongStarted {
+            song: self.get_playing_playlist().get_songs()[midi_index]
+                .get_path()
+                .display()
+                .to_string(),
+            soundfont: self
+                .active_font
+                .as_ref()
+                .map(|(_, font)| font.get_path().display().to_string())
+                .unwrap_or_default(),
+        });
+    }
+
+    /// Select a soundfont in the library and apply it according to `font_change_mode`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the path isn't in the library, or if no soundfont can be loaded.
+    pub fn select_library_font(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        self.font_lib.select_by_path(path)?;
+        self.apply_font_change()
+    }
+    /// Call after changing soundfont selection. Depending on `font_change_mode`, the change is
+    /// heard now or from the next song.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no soundfont in the fallback chain can be loaded.
+    pub fn apply_font_change(&mut self) -> anyhow::Result<()> {
+        if self.font_change_mode == FontChangeMode::NextSong && !self.is_empty() {
+            return Ok(());
+        }
+        self.reload_font()
+    }
     /// For changing soundfont on the go.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no soundfont in the fallback chain can be loaded.
     pub fn reload_font(&mut self) -> anyhow::Result<()> {
-        let sf = self.get_soundfont()?;
-        let sf_path = sf.get_path();
-        sf.refresh();
-        sf.get_status()?;
-        self.audioplayer.set_soundfont(sf_path);
+        let song_index = self.get_playing_playlist().get_song_idx();
+        let (font_link, font) = self.resolve_soundfont(song_index)?;
+        self.set_active_font(Some((font_link, font.clone())));
+        self.audioplayer.set_soundfont(font);
         Ok(())
     }
+    fn set_active_font(&mut self, active_font: Option<(FontChainLink, FontMeta)>) {
+        let changed = self.active_font.as_ref().map(|(_, font)| font.get_path())
+            != active_font.as_ref().map(|(_, font)| font.get_path());
+        self.active_font = active_font;
+        if changed {
+            self.emit(&PlayerEvent::FontChanged {
+                soundfont: self.active_font.as_ref().map(|(_, font)| font.get_name()),
+            });
+        }
+    }
+    /// Render the selected song of the playing playlist into an audio file and a piano roll PNG
+    /// sequence.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no song is selected, or if no soundfont can be loaded for it. Rendering errors come
+    /// from the job.
+    pub fn export_piano_roll(&mut self, out_dir: PathBuf) -> anyhow::Result<ExportJob> {
+        let Some(song_index) = self.get_playing_playlist().get_song_idx() else {
+            bail!(PlayerError::NoSongSelected);
+        };
+        let (_, soundfont) = self.resolve_soundfont(Some(song_index))?;
+        let playlist = self.get_playing_playlist();
+        let source = ExportSource {
+            soundfont,
+            song: playlist.get_songs()[song_index].clone(),
+            program_locks: playlist.get_program_locks().or(self.program_locks),
+            drum_kit: self.drum_kit,
+            audio_profile: self.resolve_audio_profile(self.playing_playlist_idx),
+            colors: self.channel_colors,
+        };
+        let settings = ExportSettings {
+            audio_format: self.export_format,
+            ..Default::default()
+        };
+        Ok(ExportJob::start(source, out_dir, settings))
+    }
+    pub const fn get_program_locks(&self) -> ProgramLocks {
+        self.program_locks
+    }
+    pub fn set_program_locks(&mut self, program_locks: ProgramLocks) {
+        self.program_locks = program_locks;
+        self.apply_program_locks();
+    }
+    /// Set the program locks of a playlist. Applied right away if it's playing.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no playlist at `index`.
+    pub fn set_playlist_program_locks(
+        &mut self,
+        index: usize,
+        program_locks: ProgramLocks,
+    ) -> Result<(), PlayerError> {
+        let Some(playlist) = self.playlists.get_mut(index) else {
+            return Err(PlayerError::InvalidPlaylistIndex { index });
+        };
+        playlist.set_program_locks(program_locks);
+        self.apply_program_locks();
+        Ok(())
+    }
+    /// Lock the channels of a playlist to the presets of the selected song's soundfont that look
+    /// most like the song's instruments, for soundfonts that don't follow General MIDI. Locks
+    /// only change the program, so presets outside bank 0 aren't used. Other channels are kept.
+    /// Returns how many channels were locked.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no playlist at `index`, no song is selected in it, or the song or its
+    /// soundfont can't be read.
+    pub fn auto_map_program_locks(&mut self, index: usize) -> anyhow::Result<usize> {
+        let Some(playlist) = self.playlists.get(index) else {
+            bail!(PlayerError::InvalidPlaylistIndex { index });
+        };
+        let Some(song_index) = playlist.get_song_idx() else {
+            bail!(PlayerError::NoSongSelected);
+        };
+        let song_path = playlist.get_songs()[song_index].get_path();
+        let (_, soundfont) = self.resolve_soundfont_in(index, Some(song_index))?;
+        let presets: Vec<PresetInfo> = soundfont
+            .get_presets()
+            .iter()
+            .filter(|preset| preset.bank == 0)
+            .cloned()
+            .collect();
+        let channel_programs = general_midi::song_programs(&parse_midi(&fs::read(song_path)?)?);
+        let programs: Vec<u8> = channel_programs.iter().flatten().copied().collect();
+        let mapping = general_midi::auto_map(&programs, &presets);
+
+        let mut program_locks = self.playlists[index].get_program_locks();
+        let mut count = 0;
+        for (channel, program) in channel_programs.iter().enumerate() {
+            let Some((_, preset)) = mapping.iter().find(|(mapped, _)| Some(*mapped) == *program)
+            else {
+                continue;
+            };
+            program_locks.set(channel, Some(preset.program));
+            count += 1;
+        }
+        self.set_playlist_program_locks(index, program_locks)?;
+        Ok(count)
+    }
+    pub const fn get_drum_kit(&self) -> Option<DrumKit> {
+        self.drum_kit
+    }
+    pub fn set_drum_kit(&mut self, drum_kit: Option<DrumKit>) {
+        self.drum_kit = drum_kit;
+        self.apply_program_locks();
+    }
+    /// Send playing playlist's locks, filled in with global ones, and the drum kit to the
+    /// audio player.
+    fn apply_program_locks(&mut self) {
+        let program_locks = self
+            .get_playing_playlist()
+            .get_program_locks()
+            .or(self.program_locks);
+        self.audioplayer.set_program_locks(program_locks);
+        self.audioplayer.set_drum_kit(self.drum_kit);
+    }
+    pub const fn get_audio_profiles(&self) -> &Vec<AudioProfile> {
+        &self.audio_profiles
+    }
+    /// Replace the profile list. Applied to the playing song right away.
+    pub fn set_audio_profiles(&mut self, audio_profiles: Vec<AudioProfile>) {
+        self.audio_profiles = audio_profiles;
+        self.apply_audio_profile();
+    }
+    /// Assign an audio profile to a playlist by name. None means no processing.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no playlist at `index`.
+    pub fn set_playlist_audio_profile(
+        &mut self,
+        index: usize,
+        name: Option<String>,
+    ) -> Result<(), PlayerError> {
+        let Some(playlist) = self.playlists.get_mut(index) else {
+            return Err(PlayerError::InvalidPlaylistIndex { index });
+        };
+        playlist.set_audio_profile(name);
+        self.apply_audio_profile();
+        Ok(())
+    }
+    /// Profile of a playlist. A missing profile is treated as no processing.
+    fn resolve_audio_profile(&self, playlist_index: usize) -> AudioProfile {
+        self.playlists
+            .get(playlist_index)
+            .and_then(Playlist::get_audio_profile)
+            .and_then(|name| {
+                self.audio_profiles
+                    .iter()
+                    .find(|profile| profile.name == name)
+            })
+            .cloned()
+            .unwrap_or_default()
+    }
+    /// Send playing playlist's audio profile to the audio player.
+    fn apply_audio_profile(&mut self) {
+        let audio_profile = self.resolve_audio_profile(self.playing_playlist_idx);
+        self.audioplayer.set_audio_profile(&audio_profile);
+    }
+    /// The soundfont currently in use, and which link of the fallback chain provided it.
+    pub const fn get_active_font(&self) -> Option<&(FontChainLink, FontMeta)> {
+        self.active_font.as_ref()
+    }
 
     /// Stop playback
     pub fn stop(&mut self) {
+        self.party_queue = None;
         let _ = self.audioplayer.stop_playback();
-        self.get_playing_playlist_mut().queue_idx = None;
+        self.get_playing_playlist_mut()
+            .get_queue_mut()
+            .set_position(None);
         let _ = self.get_playing_playlist_mut().set_song_idx(None);
         self.is_playing = false;
+        self.set_active_font(None);
+        self.last_position_tick = None;
+        self.followed_file = None;
 
         self.mediacontrol_update_song();
     }
@@ -291,6 +1282,32 @@ impl Player {
             self.push_error(e.to_string());
         }
     }
+    /// Tempo multiplier, 1.0 is the song's own tempo
+    pub fn get_speed(&self) -> f32 {
+        self.audioplayer.get_speed()
+    }
+    /// Play faster or slower without changing pitch. Kept within `SPEED_RANGE`.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.audioplayer.set_speed(speed);
+    }
+    /// A-B loop of the playing song
+    pub fn get_loop_points(&self) -> Option<(Duration, Duration)> {
+        self.audioplayer.get_loop_points()
+    }
+    /// Loop part of the playing song, and remember it in the playlist. None turns it off.
+    ///
+    /// # Errors
+    ///
+    /// Fails if nothing is playing.
+    pub fn set_loop_points(&mut self, points: Option<(Duration, Duration)>) -> anyhow::Result<()> {
+        let Some(index) = self.get_playing_song_idx() else {
+            bail!(PlayerError::NothingToPlay);
+        };
+        self.get_playing_playlist_mut()
+            .set_song_loop(index, points)?;
+        self.audioplayer.set_loop_points(points);
+        Ok(())
+    }
     /// Unpause
     pub fn play(&mut self) {
         if self.is_playing {
@@ -305,39 +1322,212 @@ impl Player {
     }
     /// Play previous song
     pub fn skip_back(&mut self) {
-        if let Some(mut index) = self.get_playing_playlist().queue_idx {
-            if index > 0 {
-                index -= 1;
-                self.get_playing_playlist_mut().queue_idx = Some(index);
-                if let Err(e) = self.play_selected_song() {
-                    self.push_error(e.to_string());
-                }
-            } else if self.repeat == RepeatMode::Queue {
-                index = self.get_playing_playlist().queue.len() - 1;
-                self.get_playing_playlist_mut().queue_idx = Some(index);
-                if let Err(e) = self.play_selected_song() {
-                    self.push_error(e.to_string());
-                }
+        if self.party_queue.is_some() {
+            self.step_party_queue(false);
+            return;
+        }
+        let wrap = self.repeat == RepeatMode::Queue;
+        if self
+            .get_playing_playlist_mut()
+            .get_queue_mut()
+            .go_back(wrap)
+        {
+            if let Err(e) = self.play_selected_song() {
+                self.push_playback_error(&e);
             }
         }
     }
     /// Play next song
     pub fn skip(&mut self) {
-        if let Some(index) = self.get_playing_playlist().queue_idx {
-            if index < self.get_playing_playlist().queue.len() - 1 {
-                self.get_playing_playlist_mut().queue_idx = Some(index + 1);
-                if let Err(e) = self.play_selected_song() {
-                    self.push_error(e.to_string());
-                }
+        if self.party_queue.is_some() {
+            self.step_party_queue(true);
+            return;
+        }
+        let wrap = self.repeat == RepeatMode::Queue;
+        if self
+            .get_playing_playlist_mut()
+            .get_queue_mut()
+            .advance(wrap)
+        {
+            if let Err(e) = self.play_selected_song() {
+                self.push_playback_error(&e);
             }
-            if self.repeat == RepeatMode::Queue {
-                self.get_playing_playlist_mut().queue_idx = Some(0);
-                if let Err(e) = self.play_selected_song() {
-                    self.push_error(e.to_string());
+        }
+    }
+    pub const fn get_autosave_interval(&self) -> Duration {
+        Duration::from_secs(self.autosave_interval)
+    }
+    /// Seconds, clamped to `AUTOSAVE_INTERVAL_RANGE`
+    pub fn set_autosave_interval(&mut self, seconds: u64) {
+        self.autosave_interval = seconds.clamp(
+            *AUTOSAVE_INTERVAL_RANGE.start(),
+            *AUTOSAVE_INTERVAL_RANGE.end(),
+        );
+    }
+    pub const fn get_global_hotkeys(&self) -> &GlobalHotkeys {
+        &self.global_hotkeys
+    }
+    pub fn set_global_hotkeys(&mut self, global_hotkeys: GlobalHotkeys) {
+        self.global_hotkeys = global_hotkeys;
+        self.apply_global_hotkeys();
+    }
+    /// Register the hotkeys with the OS, or release them if disabled.
+    fn apply_global_hotkeys(&mut self) {
+        if !self.global_hotkeys.enabled {
+            self.hotkey_listener = None;
+            return;
+        }
+        let event_queue = self.mediacontrol_sender.clone();
+        self.hotkey_listener
+            .get_or_insert_with(|| HotkeyListener::start(event_queue))
+            .set_bindings(&self.global_hotkeys);
+    }
+    fn handle_hotkey_errors(&mut self) {
+        let Some(listener) = &self.hotkey_listener else {
+            return;
+        };
+        for error in listener.take_errors() {
+            self.push_error(error);
+        }
+    }
+
+    pub const fn get_midi_clock(&self) -> bool {
+        self.midi_clock
+    }
+    pub fn set_midi_clock(&mut self, value: bool) {
+        self.midi_clock = value;
+        self.apply_midi_output();
+    }
+    pub const fn get_midi_thru(&self) -> bool {
+        self.midi_thru
+    }
+    pub fn set_midi_thru(&mut self, value: bool) {
+        self.midi_thru = value;
+        self.apply_midi_output();
+    }
+    /// Open the MIDI output port if something uses it, or close it.
+    fn apply_midi_output(&mut self) {
+        if !self.midi_clock && !self.midi_thru {
+            self.audioplayer.set_midi_clock(None);
+            self.audioplayer.set_midi_thru(None);
+            self.midi_port = None;
+            return;
+        }
+        let out = self.midi_port.get_or_insert_with(MidiPort::open).get_out();
+        self.audioplayer
+            .set_midi_clock(self.midi_clock.then(|| out.clone()));
+        self.audioplayer
+            .set_midi_thru(self.midi_thru.then_some(out));
+    }
+    fn handle_midi_port_errors(&mut self) {
+        let Some(port) = &self.midi_port else {
+            return;
+        };
+        for error in port.take_errors() {
+            self.push_error(error);
+        }
+    }
+
+    pub const fn get_stream_output(&self) -> bool {
+        self.stream_output
+    }
+    /// Switch between the audio device and a network stream. Playback continues where it was.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the stream can't listen on its port, e.g. because it's taken.
+    pub fn set_stream_output(&mut self, enabled: bool) -> anyhow::Result<()> {
+        if enabled == self.stream_output {
+            return Ok(());
+        }
+        if enabled {
+            let token = if self.stream_lan {
+                if self.stream_token.is_empty() {
+                    self.stream_token = remote::new_token();
                 }
+                Some(self.stream_token.clone())
+            } else {
+                None
+            };
+            let sink = NetSink::start(self.stream_port, token)?;
+            self.local_sink = self.audioplayer.move_to_sink(Box::new(sink));
+        } else if let Some(sink) = self.local_sink.take() {
+            self.audioplayer.move_to_sink(sink);
+        } else {
+            self.audioplayer.set_sink(None);
+        }
+        self.stream_output = enabled;
+        self.update_volume();
+        Ok(())
+    }
+    pub const fn get_stream_port(&self) -> u16 {
+        self.stream_port
+    }
+    /// Takes effect when the stream starts
+    pub const fn set_stream_port(&mut self, port: u16) {
+        self.stream_port = port;
+    }
+    pub const fn get_stream_lan(&self) -> bool {
+        self.stream_lan
+    }
+    /// Takes effect when the stream starts
+    pub const fn set_stream_lan(&mut self, enabled: bool) {
+        self.stream_lan = enabled;
+    }
+    /// Other devices need this in the URL. Empty until first needed.
+    pub fn get_stream_token(&self) -> &str {
+        &self.stream_token
+    }
+    /// Listeners that used the old token can't connect anymore. Takes effect when the stream
+    /// starts.
+    pub fn renew_stream_token(&mut self) {
+        self.stream_token = remote::new_token();
+    }
+    /// Address to listen at, with the token if other devices are allowed
+    pub fn get_stream_url(&self) -> String {
+        let token = self.stream_lan.then_some(self.stream_token.as_str());
+        net_stream::stream_url(self.stream_port, token)
+    }
+
+    /// Record everything that plays, including font and mixer changes, into a wav file.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be created.
+    pub fn start_recording(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.audioplayer.stop_recording();
+        self.recording_errors = Some(self.audioplayer.start_recording(path)?);
+        Ok(())
+    }
+    pub fn stop_recording(&self) {
+        self.audioplayer.stop_recording();
+    }
+    pub fn is_recording(&self) -> bool {
+        self.audioplayer.is_recording()
+    }
+    fn handle_recording_errors(&mut self) {
+        let Some(errors) = &self.recording_errors else {
+            return;
+        };
+        let mut failed = vec![];
+        let finished = loop {
+            match errors.try_recv() {
+                Ok(error) => failed.push(error),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
             }
+        };
+        if finished {
+            self.recording_errors = None;
+        }
+        if !failed.is_empty() {
+            self.audioplayer.stop_recording();
+        }
+        for error in failed {
+            self.push_error(error);
         }
     }
+
     pub const fn get_shuffle(&self) -> bool {
         self.shuffle
     }
@@ -346,6 +1536,14 @@ impl Player {
         let shuffle = !self.shuffle;
         self.shuffle = shuffle;
         self.get_playing_playlist_mut().rebuild_queue(shuffle);
+        self.emit(&PlayerEvent::QueueChanged);
+    }
+    /// Enable shuffle and rebuild queue from a known seed
+    pub fn shuffle_with_seed(&mut self, seed: u64) {
+        self.shuffle = true;
+        self.get_playing_playlist_mut()
+            .rebuild_queue_seeded(Some(seed));
+        self.emit(&PlayerEvent::QueueChanged);
     }
     pub const fn get_repeat(&self) -> RepeatMode {
         self.repeat
@@ -361,21 +1559,214 @@ impl Player {
         self.volume
     }
     pub fn set_volume(&mut self, volume: f32) {
-        self.volume = f32::clamp(volume, 0., 100.);
+        self.volume = f32::clamp(volume, 0., MAX_VOLUME);
         self.update_volume();
         self.mediacontrol_update_volume();
     }
     /// Sends current volume setting to backend
     pub fn update_volume(&self) {
-        // Not dividing the volume by 100 is a mistake you only make once.
-        let _ = self.audioplayer.set_volume(self.volume * 0.01);
+        let volume = volume_to_amplitude(self.volume);
+        let (main_volume, preview_volume) = self.cue.as_ref().map_or_else(
+            || {
+                if self.preview_audible {
+                    (0., volume)
+                } else {
+                    (volume, 0.)
+                }
+            },
+            |cue| {
+                let (main_gain, cue_gain) = cue.get_gains();
+                (volume * main_gain, volume * cue_gain)
+            },
+        );
+        let _ = self.audioplayer.set_volume(main_volume);
+        let _ = self.preview_audioplayer.set_volume(preview_volume);
+        self.update_live_volume();
+    }
+
+    // --- Inspector Preview
+
+    /// Play a file on the preview player. Uses the current soundfont, or whatever the fallback
+    /// chain provides.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file isn't a readable midi file, no soundfont can be loaded, or the preview
+    /// player can't start.
+    pub fn start_preview(&mut self, filepath: PathBuf) -> anyhow::Result<()> {
+        self.cue = None;
+        self.preview_audioplayer.stop_playback()?;
+        let song = MidiMeta::new(filepath);
+        song.get_status()?;
+        let font = match &self.active_font {
+            Some((_, font)) => font.clone(),
+            None => self.resolve_soundfont(None)?.1,
+        };
+        self.preview_audioplayer.set_soundfont(font);
+        self.preview_audioplayer.set_midifile(song);
+        self.preview_audioplayer.start_playback()?;
+        self.set_preview_audible(true);
+        Ok(())
+    }
+    /// Stop the inspector preview. A cued song keeps playing.
+    pub fn stop_preview(&mut self) {
+        if self.cue.is_some() {
+            return;
+        }
+        let _ = self.preview_audioplayer.stop_playback();
+        self.set_preview_audible(false);
+    }
+    /// Inspector preview is playing
+    pub fn is_preview_playing(&self) -> bool {
+        self.cue.is_none() && !self.preview_audioplayer.is_empty()
+    }
+    pub const fn is_preview_audible(&self) -> bool {
+        self.preview_audible
+    }
+    /// Choose which player is heard
+    pub fn set_preview_audible(&mut self, value: bool) {
+        self.preview_audible = value;
+        self.update_volume();
+    }
+
+    // --- Cue
+
+    /// Pre-listen a song of the open playlist on the preview player, while the main one keeps
+    /// playing. Replaces the inspector preview.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no song at `song_index`, or it or its soundfont can't be read.
+    pub fn cue_song(&mut self, song_index: usize) -> anyhow::Result<()> {
+        let playlist_index = self.playlist_idx;
+        let Some(song) = self.playlists[playlist_index]
+            .get_songs_mut()
+            .get_mut(song_index)
+        else {
+            bail!(PlayerError::InvalidSongIndex { index: song_index });
+        };
+        song.refresh();
+        song.get_status()?;
+        let song = song.clone();
+        let (_, font) = self.resolve_soundfont_in(playlist_index, Some(song_index))?;
+
+        let playlist = &self.playlists[playlist_index];
+        let cue = Cue::new(playlist.get_id(), song.get_path(), song.get_name());
+        let program_locks = playlist.get_program_locks().or(self.program_locks);
+        let audio_profile = self.resolve_audio_profile(playlist_index);
+
+        self.preview_audioplayer.stop_playback()?;
+        self.preview_audioplayer.set_program_locks(program_locks);
+        self.preview_audioplayer.set_drum_kit(self.drum_kit);
+        self.preview_audioplayer.set_audio_profile(&audio_profile);
+        self.preview_audioplayer.set_soundfont(font);
+        self.preview_audioplayer.set_midifile(song);
+        self.preview_audioplayer.start_playback()?;
+        self.preview_audible = false;
+        self.cue = Some(cue);
+        self.update_volume();
+        Ok(())
+    }
+    pub const fn get_cue(&self) -> Option<&Cue> {
+        self.cue.as_ref()
+    }
+    pub fn cancel_cue(&mut self) {
+        if self.cue.take().is_some() {
+            let _ = self.preview_audioplayer.stop_playback();
+            self.update_volume();
+        }
+    }
+    /// Fade the main player out and the cued song in. If nothing else is playing, the cued song
+    /// takes over right away.
+    pub fn crossfade_now(&mut self) {
+        let Some(cue) = &mut self.cue else {
+            return;
+        };
+        cue.start_crossfade();
+        if self.is_empty() || self.is_paused() {
+            self.finish_crossfade();
+        }
+    }
+    fn update_cue(&mut self) {
+        let Some(cue) = &self.cue else {
+            return;
+        };
+        if !cue.is_crossfading() {
+            // Cued song ended before it was needed
+            if self.preview_audioplayer.is_empty() {
+                self.cancel_cue();
+            }
+            return;
+        }
+        if cue.get_progress() >= 1. || self.is_empty() {
+            self.finish_crossfade();
+        } else {
+            self.update_volume();
+        }
+    }
+    /// Continue the cued song on the main player from where the preview is.
+    fn finish_crossfade(&mut self) {
+        let Some(cue) = self.cue.clone() else {
+            return;
+        };
+        let Some((playlist_idx, song_idx)) = self.find_song(cue.playlist_id, &cue.song) else {
+            self.cancel_cue();
+            self.push_error(PlayerError::NothingToPlay.to_string());
+            return;
+        };
+        let _ = self.playlists[playlist_idx].set_song_idx(Some(song_idx));
+        // The preview keeps sounding while the main player loads.
+        self.start_playlist(playlist_idx);
+        let pos = self.preview_audioplayer.get_midi_position();
+        self.seek_to(pos);
+        self.cancel_cue();
+    }
+
+    // --- Play History
+
+    /// Songs played this session, newest first
+    pub const fn get_play_history(&self) -> &PlayHistory {
+        &self.play_history
+    }
+    pub fn clear_play_history(&mut self) {
+        self.play_history.clear();
+    }
+    /// Play a song from history with the soundfont and speed it had then, even if the settings
+    /// have changed since. The speed stays for the songs after it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the entry or its song is gone, or its soundfont can't be read anymore.
+    pub fn replay_history_entry(&mut self, index: usize) -> anyhow::Result<()> {
+        let Some(entry) = self.play_history.get(index).cloned() else {
+            bail!(PlayerError::NothingToPlay);
+        };
+        let Some((playlist_idx, song_idx)) = self.find_song(entry.playlist_id, &entry.song) else {
+            bail!(PlayerError::NothingToPlay);
+        };
+        entry.font.get_status()?;
+        self.playlists[playlist_idx].set_song_idx(Some(song_idx))?;
+        self.font_override = Some((entry.font_link, entry.font));
+        self.set_speed(entry.speed);
+        self.start_playlist(playlist_idx);
+        Ok(())
     }
+
     // When previous song has ended, advance queue or stop.
     fn advance_queue(&mut self) -> anyhow::Result<()> {
         let repeat = self.repeat;
+        if self.party_queue.is_some() {
+            if repeat == RepeatMode::Song {
+                return self.play_party_entry();
+            }
+            if !self.step_party_queue(true) && !self.trigger_end_action(EndTrigger::Queue) {
+                self.stop();
+            }
+            return Ok(());
+        }
         let playlist = self.get_playing_playlist_mut();
 
-        let Some(mut queue_index) = playlist.queue_idx else {
+        let Some(song_idx) = playlist.get_queue().get_current() else {
             self.stop();
             bail!(PlayerError::NoQueueIndex)
         };
@@ -383,29 +1774,26 @@ impl Player {
         // Replay the same song
         if repeat == RepeatMode::Song {
             playlist
-                .set_song_idx(Some(playlist.queue[queue_index]))
+                .set_song_idx(Some(song_idx))
                 .expect("advance_queue: repeat song idx failed?!");
             self.play_selected_song()?;
             return Ok(());
         }
 
-        queue_index += 1;
-
         // Queue end reached, back to start or bail out
-        if queue_index == playlist.queue.len() {
-            if repeat == RepeatMode::Queue {
-                queue_index = 0;
-            } else {
-                let _ = playlist.set_song_idx(None);
+        if !self.advance_past_huge_songs() {
+            if !self.trigger_end_action(EndTrigger::Queue) {
+                let _ = self.get_playing_playlist_mut().set_song_idx(None);
                 self.stop();
-                return Ok(());
             }
+            return Ok(());
         }
 
         // Play next song in queue
-        playlist.queue_idx = Some(queue_index);
+        let playlist = self.get_playing_playlist_mut();
+        let song_idx = playlist.get_queue().get_current();
         playlist
-            .set_song_idx(Some(playlist.queue[queue_index]))
+            .set_song_idx(song_idx)
             .expect("advance_queue: next song idx failed?!");
         self.play_selected_song()?;
         Ok(())
@@ -434,6 +1822,46 @@ impl Player {
     pub fn get_playback_position(&self) -> Duration {
         self.audioplayer.get_midi_position()
     }
+    /// Tempo changes and marker events of the playing song
+    pub fn get_song_markers(&self) -> &[SongMarker] {
+        self.audioplayer.get_markers()
+    }
+    /// Notes per second of the playing song
+    pub const fn get_note_density(&self) -> &NoteDensity {
+        self.audioplayer.get_note_density()
+    }
+    /// Lyrics of the playing song
+    pub const fn get_lyrics(&self) -> &Lyrics {
+        self.audioplayer.get_lyrics()
+    }
+    /// Latest note velocity of each MIDI channel that is sounding, 0 for silent channels.
+    pub fn get_channel_levels(&self) -> [u8; program_lock::CHANNEL_COUNT] {
+        self.audioplayer.get_channel_levels()
+    }
+    /// Bank select and program of each channel
+    pub fn get_channel_programs(&self) -> [(u8, u8); program_lock::CHANNEL_COUNT] {
+        self.audioplayer.get_channel_programs()
+    }
+    /// Note ons and offs since the last call, for visualizers
+    pub fn take_note_events(&self) -> Vec<NoteEvent> {
+        self.audioplayer.take_note_events()
+    }
+    /// Latest output frames, oldest first
+    pub fn get_output_frames(&self) -> Vec<[f32; 2]> {
+        self.audioplayer.get_output_frames()
+    }
+    /// Clip counter and peak of the playing song
+    pub const fn get_clip_meter(&self) -> &ClipMeter {
+        self.audioplayer.get_clip_meter()
+    }
+    pub const fn get_auto_limiter(&self) -> bool {
+        self.auto_limiter
+    }
+    /// Engage the limiter when the output clips, even at normal volume
+    pub fn set_auto_limiter(&mut self, value: bool) {
+        self.auto_limiter = value;
+        self.audioplayer.get_clip_meter().set_auto_limit(value);
+    }
 
     // --- Manage Playlists
 
@@ -478,6 +1906,10 @@ impl Player {
         &mut self.playlists[self.playlist_idx]
     }
     /// Switch to another playlist
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no playlist at `index`.
     pub fn switch_to_playlist(&mut self, index: usize) -> anyhow::Result<()> {
         if index >= self.playlists.len() {
             bail!(PlayerError::InvalidPlaylistIndex { index });
@@ -487,6 +1919,47 @@ impl Player {
         self.get_playlist_mut().refresh_song_list();
         Ok(())
     }
+    /// Switch to the next most recently used playlist. Repeated calls go further back, until
+    /// `end_recent_cycle` is called.
+    pub fn cycle_recent_playlists(&mut self) {
+        self.prune_recent_playlists();
+        if self.recent_playlists.len() < 2 {
+            return;
+        }
+        let pos = self
+            .recent_cycle_pos
+            .map_or(1, |pos| (pos + 1) % self.recent_playlists.len());
+        if let Some(index) = self.find_playlist(self.recent_playlists[pos]) {
+            let _ = self.switch_to_playlist(index);
+        }
+        self.recent_cycle_pos = Some(pos);
+    }
+    /// Stop cycling and count the current playlist as most recently used.
+    pub const fn end_recent_cycle(&mut self) {
+        self.recent_cycle_pos = None;
+    }
+    fn update_recent_playlists(&mut self) {
+        if self.recent_cycle_pos.is_some() {
+            return;
+        }
+        let current_id = self.get_playlist().get_id();
+        if self.recent_playlists.first() == Some(&current_id) {
+            return;
+        }
+        self.recent_playlists.retain(|&id| id != current_id);
+        self.recent_playlists.insert(0, current_id);
+        self.prune_recent_playlists();
+    }
+    /// Forget playlists that have been closed
+    fn prune_recent_playlists(&mut self) {
+        let open_ids: Vec<usize> = self.playlists.iter().map(Playlist::get_id).collect();
+        self.recent_playlists.retain(|id| open_ids.contains(id));
+    }
+    /// Switch to the playlist on the left.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the first playlist is open.
     pub fn switch_playlist_left(&mut self) -> anyhow::Result<()> {
         if self.playlist_idx == 0 {
             bail!(PlayerError::CantSwitchPlaylist);
@@ -494,6 +1967,11 @@ impl Player {
         self.playlist_idx -= 1;
         Ok(())
     }
+    /// Switch to the playlist on the right.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the last playlist is open.
     pub fn switch_playlist_right(&mut self) -> anyhow::Result<()> {
         if self.playlist_idx >= self.playlists.len() - 1 {
             bail!(PlayerError::CantSwitchPlaylist);
@@ -501,11 +1979,22 @@ impl Player {
         self.playlist_idx += 1;
         Ok(())
     }
-    /// Create a new playlist
+    /// Create a new playlist from the template
     pub fn new_playlist(&mut self) {
-        self.playlists.push(Playlist::default());
+        let names: Vec<&str> = self
+            .playlists
+            .iter()
+            .map(|playlist| playlist.name.as_str())
+            .collect();
+        let playlist = self.playlist_template.build(&names);
+        self.playlists.push(playlist);
+        self.emit(&PlayerEvent::PlaylistListChanged);
     }
     /// Remove a playlist by index
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no playlist at `index`.
     pub fn remove_playlist(&mut self, index: usize) -> Result<(), PlayerError> {
         if index >= self.playlists.len() {
             return Err(PlayerError::InvalidPlaylistIndex { index });
@@ -514,6 +2003,10 @@ impl Player {
         Ok(())
     }
     /// Remove a playlist by index, override unsaved check
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no playlist at `index`.
     pub fn force_remove_playlist(&mut self, index: usize) -> Result<(), PlayerError> {
         if index >= self.playlists.len() {
             return Err(PlayerError::InvalidPlaylistIndex { index });
@@ -521,6 +2014,11 @@ impl Player {
         self.playlists[index].deletion_status = DeletionStatus::QueuedDiscard;
         Ok(())
     }
+    /// Keep a playlist that was waiting to be removed.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no playlist at `index`.
     pub fn cancel_remove_playlist(&mut self, index: usize) -> Result<(), PlayerError> {
         if index >= self.playlists.len() {
             return Err(PlayerError::InvalidPlaylistIndex { index });
@@ -538,26 +2036,104 @@ impl Player {
         }
         None
     }
-    pub fn has_removed_playlist(&self) -> bool {
-        !self.removed_playlists.is_empty()
+    pub const fn has_removal_history(&self) -> bool {
+        !self.removal_history.is_empty()
     }
-    pub fn reopen_removed_playlist(&mut self) {
-        if !self.removed_playlists.is_empty() {
-            let last_index = self.removed_playlists.len() - 1;
-            let mut playlist = self.removed_playlists.remove(last_index);
-            playlist.deletion_status = DeletionStatus::None;
-
-            if let Some(filepath) = playlist.get_portable_path() {
-                if self.is_portable_playlist_open(&filepath) {
-                    self.reopen_removed_playlist();
+    /// Bring back the last closed playlist or cleared list
+    pub fn undo_removal(&mut self) {
+        let Some(removal) = self.removal_history.pop() else {
+            return;
+        };
+        match removal {
+            Removal::Playlist(mut playlist) => {
+                playlist.deletion_status = DeletionStatus::None;
+
+                if let Some(filepath) = playlist.get_portable_path() {
+                    if self.is_portable_playlist_open(&filepath) {
+                        self.undo_removal();
+                        return;
+                    }
                 }
-            }
 
-            self.playlists.push(playlist);
-            self.playlist_idx = self.playlists.len() - 1;
+                self.playlists.push(*playlist);
+                self.playlist_idx = self.playlists.len() - 1;
+                self.emit(&PlayerEvent::PlaylistListChanged);
+            }
+            // The playlist may have been closed since. Then there's nothing to restore into, so
+            // undo the removal before it instead.
+            Removal::Songs { playlist_id, songs } => {
+                let Some(index) = self.find_playlist(playlist_id) else {
+                    self.undo_removal();
+                    return;
+                };
+                self.playlists[index].restore_songs(songs);
+                self.playlist_idx = index;
+            }
+            Removal::Fonts { playlist_id, fonts } => {
+                let Some(index) = self.find_playlist(playlist_id) else {
+                    self.undo_removal();
+                    return;
+                };
+                self.playlists[index].restore_fonts(fonts);
+                self.playlist_idx = index;
+            }
+        }
+    }
+    /// Clear playlist songs. Can be undone.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no playlist at `index`.
+    pub fn clear_songs(&mut self, index: usize) -> anyhow::Result<()> {
+        let Some(playlist) = self.playlists.get_mut(index) else {
+            bail!(PlayerError::InvalidPlaylistIndex { index });
+        };
+        let songs = playlist.get_songs().clone();
+        playlist.clear_songs();
+        let playlist_id = playlist.get_id();
+        self.push_removal(Removal::Songs { playlist_id, songs });
+        Ok(())
+    }
+    /// Clear playlist soundfonts. Can be undone.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no playlist at `index`.
+    pub fn clear_fonts(&mut self, index: usize) -> anyhow::Result<()> {
+        let Some(playlist) = self.playlists.get_mut(index) else {
+            bail!(PlayerError::InvalidPlaylistIndex { index });
+        };
+        let fonts = playlist.get_fonts().clone();
+        playlist.clear_fonts();
+        let playlist_id = playlist.get_id();
+        self.push_removal(Removal::Fonts { playlist_id, fonts });
+        Ok(())
+    }
+    fn push_removal(&mut self, removal: Removal) {
+        self.removal_history.push(removal);
+        while self.removal_history.len() > REMOVAL_HISTORY_LEN {
+            self.removal_history.remove(0);
         }
     }
+    /// Playlist and song index of a song, if it's still there
+    fn find_song(&self, playlist_id: usize, song: &Path) -> Option<(usize, usize)> {
+        let playlist_idx = self.find_playlist(playlist_id)?;
+        self.playlists[playlist_idx]
+            .get_songs()
+            .iter()
+            .position(|meta| meta.get_path() == song)
+            .map(|song_idx| (playlist_idx, song_idx))
+    }
+    fn find_playlist(&self, playlist_id: usize) -> Option<usize> {
+        self.playlists
+            .iter()
+            .position(|playlist| playlist.get_id() == playlist_id)
+    }
     /// Rearrange playlists
+    ///
+    /// # Errors
+    ///
+    /// Fails if either index is out of range.
     pub fn move_playlist(&mut self, old_index: usize, new_index: usize) -> anyhow::Result<()> {
         if old_index >= self.playlists.len() {
             bail!(PlayerError::InvalidPlaylistIndex { index: old_index });
@@ -584,36 +2160,70 @@ impl Player {
         } else if new_index <= self.playing_playlist_idx && self.playing_playlist_idx < old_index {
             self.playing_playlist_idx += 1;
         }
+        self.emit(&PlayerEvent::PlaylistListChanged);
 
         Ok(())
     }
     /// Move current playlist left
+    ///
+    /// # Errors
+    ///
+    /// Fails if the open playlist is already first.
     pub fn move_playlist_left(&mut self) -> anyhow::Result<()> {
         if self.playlist_idx == 0 {
             bail!(PlayerError::CantMovePlaylist);
         }
         self.move_playlist(self.playlist_idx, self.playlist_idx - 1)
-            .expect("move_playlist_left out of bounds?!");
-        Ok(())
     }
     /// Move current playlist right
+    ///
+    /// # Errors
+    ///
+    /// Fails if the open playlist is already last.
     pub fn move_playlist_right(&mut self) -> anyhow::Result<()> {
         if self.playlist_idx >= self.playlists.len() - 1 {
             bail!(PlayerError::CantMovePlaylist);
         }
         self.move_playlist(self.playlist_idx, self.playlist_idx + 1)
-            .expect("move_playlist_right out of bounds?!");
-        Ok(())
     }
+    /// Open a playlist file. It stays a file and changes are saved to it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the playlist is already open, or the file can't be read.
     pub fn open_portable_playlist(&mut self, filepath: PathBuf) -> anyhow::Result<()> {
         if self.is_portable_playlist_open(&filepath) {
             bail!("Playlist is already open")
         }
         let playlist = Playlist::open_portable(filepath)?;
+        self.scripts.queue(ScriptHook::PlaylistLoaded {
+            name: playlist.name.clone(),
+        });
+        self.playlists.push(playlist);
+        self.playlist_idx = self.playlists.len() - 1;
+        self.emit(&PlayerEvent::PlaylistListChanged);
+        Ok(())
+    }
+    /// Open another player's playlist as a new playlist in app memory.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the format isn't known, or the file can't be read.
+    pub fn import_playlist(&mut self, filepath: &Path) -> anyhow::Result<()> {
+        let playlist = Playlist::import(filepath)?;
+        self.scripts.queue(ScriptHook::PlaylistLoaded {
+            name: playlist.name.clone(),
+        });
         self.playlists.push(playlist);
         self.playlist_idx = self.playlists.len() - 1;
+        self.emit(&PlayerEvent::PlaylistListChanged);
         Ok(())
     }
+    /// Save the changes of a portable playlist into its file.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no playlist at `index`, saving is blocked, or the file can't be written.
     pub fn save_portable_playlist(&mut self, index: usize) -> Result<(), PlayerError> {
         if index >= self.playlists.len() {
             return Err(PlayerError::InvalidPlaylistIndex { index });
@@ -626,6 +2236,28 @@ impl Player {
         }
         Ok(())
     }
+    /// Discard unsaved changes of a portable playlist. Playback from it stops.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no playlist at `index`, it isn't portable, or its file can't be read.
+    pub fn revert_portable_playlist(&mut self, index: usize) -> anyhow::Result<()> {
+        if index >= self.playlists.len() {
+            bail!(PlayerError::InvalidPlaylistIndex { index });
+        }
+        if self.is_playing && index == self.playing_playlist_idx {
+            self.stop();
+        }
+        self.playlists[index].revert_portable()?;
+        self.emit(&PlayerEvent::PlaylistListChanged);
+        Ok(())
+    }
+    /// Save the changes of every portable playlist into their files.
+    ///
+    /// # Errors
+    ///
+    /// Fails if saving is blocked, or a file can't be written. Playlists after that one aren't
+    /// saved.
     pub fn save_all_portable_playlists(&mut self) -> Result<(), PlayerError> {
         if self.debug_block_saving {
             return Err(PlayerError::DebugBlockSaving);
@@ -638,6 +2270,11 @@ impl Player {
         Ok(())
     }
     /// Save playlist into a portable file.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no playlist at `index`, saving is blocked, a playlist from that file is
+    /// already open, or the file can't be written.
     pub fn save_playlist_as(&mut self, index: usize, filepath: PathBuf) -> Result<(), PlayerError> {
         if index >= self.playlists.len() {
             return Err(PlayerError::InvalidPlaylistIndex { index });
@@ -648,15 +2285,11 @@ impl Player {
         if self.is_portable_playlist_open(&filepath) {
             return Err(PlayerError::PlaylistAlreadyOpen);
         }
-        let mut new_playlist = self.playlists[index].clone();
+        let mut new_playlist = self.playlists[index].duplicate();
         new_playlist.set_portable_path(Some(filepath.clone()));
         new_playlist.name = filepath.file_stem().map_or_else(
             || format!("{} (Copy)", self.playlists[index].name),
-            |stem| {
-                stem.to_str()
-                    .expect("save_playlist_as(): stem.to_str()")
-                    .to_owned()
-            },
+            |stem| stem.to_string_lossy().into_owned(),
         );
 
         let Ok(mut file) = File::create(filepath) else {
@@ -671,9 +2304,14 @@ impl Player {
 
         self.playlists.push(new_playlist);
         let _ = self.switch_to_playlist(self.playlists.len() - 1);
+        self.emit(&PlayerEvent::PlaylistListChanged);
         Ok(())
     }
     /// New playlist is stored to app data.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no playlist at `index`.
     pub fn duplicate_playlist(&mut self, index: usize) -> anyhow::Result<()> {
         if index >= self.playlists.len() {
             bail!(PlayerError::InvalidPlaylistIndex { index });
@@ -684,6 +2322,7 @@ impl Player {
 
         self.playlists.push(new_playlist);
         let _ = self.switch_to_playlist(self.playlists.len() - 1);
+        self.emit(&PlayerEvent::PlaylistListChanged);
         Ok(())
     }
     /// Make sure at least one playlist exists!
@@ -707,22 +2346,71 @@ impl Player {
 
     // --- Other
 
-    pub fn get_event_queue(&mut self) -> &mut Vec<PlayerEvent> {
-        &mut self.player_events
+    /// New listener for player events. Dropping the receiver unsubscribes.
+    pub fn subscribe(&mut self) -> Receiver<PlayerEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+    fn emit(&mut self, event: &PlayerEvent) {
+        self.log.observe(event);
+        self.mediacontrol_handle_player_event(event);
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
     }
     fn push_error(&mut self, message: String) {
-        self.player_events.push(PlayerEvent::NotifyError(message));
+        self.emit(&PlayerEvent::NotifyError(message));
+    }
+}
+
+/// Last modification time of a file, if it can be read
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Pick a song from `0..len` with a random number, avoiding the current song if there are others.
+const fn random_song_idx(len: usize, current: Option<usize>, random: usize) -> usize {
+    match current {
+        Some(current) if len > 1 && current < len => {
+            let index = random % (len - 1);
+            if index >= current {
+                index + 1
+            } else {
+                index
+            }
+        }
+        _ => random % len,
     }
 }
 
+/// Volume percentage to amplitude. The control is perceptual: steps are even in decibels, not in
+/// amplitude. 100% is 1.0, `MAX_VOLUME` is 2.0.
+#[must_use]
+pub fn volume_to_amplitude(volume: f32) -> f32 {
+    if volume <= 0. {
+        return 0.;
+    }
+    let decibels = if volume <= 100. {
+        -VOLUME_RANGE_DB * (1. - volume / 100.)
+    } else {
+        VOLUME_BOOST_DB * (volume.min(MAX_VOLUME) - 100.) / (MAX_VOLUME - 100.)
+    };
+    10_f32.powf(decibels / 20.)
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::test_support::{
+        micro_soundfont, note_off, note_on, program_change, short_song, song,
+    };
+    use audio::backend::NullSink;
+    use std::{fs, thread};
 
     #[test]
     fn test_rearrange_playlists_cur_wksp_index() {
-        let mut player = Player::default();
+        let mut player = Player::headless();
         player.new_playlist();
         player.new_playlist();
         player.new_playlist();
@@ -761,7 +2449,7 @@ mod tests {
 
     #[test]
     fn test_rearrange_playlists_cur_wksp_index_outofbounds() {
-        let mut player = Player::default();
+        let mut player = Player::headless();
         player.new_playlist();
         player.new_playlist();
         player.new_playlist();
@@ -786,7 +2474,7 @@ mod tests {
 
     #[test]
     fn test_rearrange_playlists_playing_wksp_index() {
-        let mut player = Player::default();
+        let mut player = Player::headless();
         player.new_playlist();
         player.new_playlist();
         player.new_playlist();
@@ -826,7 +2514,7 @@ mod tests {
 
     #[test]
     fn test_rearrange_playlists_playing_wksp_index_outofbounds() {
-        let mut player = Player::default();
+        let mut player = Player::headless();
         player.new_playlist();
         player.new_playlist();
         player.new_playlist();
@@ -853,7 +2541,7 @@ mod tests {
 
     #[test]
     fn test_remove_last_playlist_decreases_index() {
-        let mut player = Player::default();
+        let mut player = Player::headless();
         player.new_playlist();
         player.new_playlist();
         player.new_playlist();
@@ -866,7 +2554,7 @@ mod tests {
 
     #[test]
     fn test_remove_last_playlist_decreases_playing_index() {
-        let mut player = Player::default();
+        let mut player = Player::headless();
         player.new_playlist();
         player.new_playlist();
         player.new_playlist();
@@ -879,7 +2567,7 @@ mod tests {
 
     #[test]
     fn test_remove_nonlast_playlist_keeps_index() {
-        let mut player = Player::default();
+        let mut player = Player::headless();
         player.new_playlist();
         player.new_playlist();
         player.new_playlist();
@@ -890,9 +2578,27 @@ mod tests {
         assert_eq!(player.playlist_idx, 1)
     }
 
+    #[test]
+    fn test_undo_skips_closed_playlists() {
+        let mut player = Player::headless();
+        player.new_playlist();
+        player.new_playlist();
+        for index in 0..2 {
+            player.playlists[index]
+                .add_file_data("song.mid".into(), short_song().into())
+                .unwrap();
+            player.clear_songs(index).unwrap();
+        }
+        player.playlists.remove(1);
+
+        player.undo_removal();
+        assert_eq!(player.playlists[0].get_songs().len(), 1);
+        assert!(!player.has_removal_history());
+    }
+
     #[test]
     fn test_debug_block_saving() {
-        let mut player = Player::default();
+        let mut player = Player::headless();
         player.debug_block_saving = true;
 
         player.new_playlist();
@@ -918,4 +2624,592 @@ mod tests {
             PlayerError::DebugBlockSaving.to_string()
         );
     }
+
+    #[test]
+    fn test_cycle_recent_playlists() {
+        let mut player = Player::headless();
+        player.new_playlist();
+        player.new_playlist();
+        player.new_playlist();
+
+        player.switch_to_playlist(0).unwrap();
+        player.update_recent_playlists();
+        player.switch_to_playlist(2).unwrap();
+        player.update_recent_playlists();
+        player.switch_to_playlist(1).unwrap();
+        player.update_recent_playlists();
+
+        // Most recent first: 1, 2, 0
+        player.cycle_recent_playlists();
+        assert_eq!(player.playlist_idx, 2);
+        player.update_recent_playlists();
+        player.cycle_recent_playlists();
+        assert_eq!(player.playlist_idx, 0);
+        player.end_recent_cycle();
+        player.update_recent_playlists();
+
+        // Most recent first: 0, 1, 2
+        player.cycle_recent_playlists();
+        assert_eq!(player.playlist_idx, 1);
+    }
+
+    #[test]
+    fn test_autosave_interval_clamped() {
+        let mut player = Player::headless();
+        player.set_autosave_interval(0);
+        assert_eq!(
+            player.get_autosave_interval(),
+            Duration::from_secs(*AUTOSAVE_INTERVAL_RANGE.start())
+        );
+        player.set_autosave_interval(u64::MAX);
+        assert_eq!(
+            player.get_autosave_interval(),
+            Duration::from_secs(*AUTOSAVE_INTERVAL_RANGE.end())
+        );
+    }
+
+    #[test]
+    fn test_random_song_idx() {
+        for random in 0..20 {
+            assert_ne!(random_song_idx(5, Some(2), random), 2);
+            assert!(random_song_idx(5, Some(2), random) < 5);
+            assert!(random_song_idx(5, None, random) < 5);
+        }
+        assert_eq!(random_song_idx(1, Some(0), 7), 0);
+        assert_eq!(random_song_idx(2, Some(0), 7), 1);
+    }
+
+    #[test]
+    fn test_volume_to_amplitude() {
+        assert!(volume_to_amplitude(0.).abs() < f32::EPSILON);
+        assert!((volume_to_amplitude(100.) - 1.).abs() < 0.001);
+        assert!((volume_to_amplitude(MAX_VOLUME) - 2.).abs() < 0.001);
+        assert!((volume_to_amplitude(50.) - 0.031_6).abs() < 0.001);
+        assert!(volume_to_amplitude(1.) > 0.);
+        assert!(volume_to_amplitude(1.) < volume_to_amplitude(2.));
+    }
+
+    /// Half a second of middle C
+    /// Player with a few songs, playing to a null sink
+    fn null_player(name: &str, song_count: usize) -> (Player, NullSink, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("sfontplayer_{name}_{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("temp dir");
+        let sink = NullSink::default();
+        let mut player = Player::headless();
+        player.set_sink(Some(Box::new(sink.clone())));
+        player.new_playlist();
+        for i in 0..song_count {
+            let path = dir.join(format!("song{i}.mid"));
+            fs::write(&path, short_song()).expect("write song");
+            player.get_playlist_mut().add_song(path).expect("add song");
+        }
+        (player, sink, dir)
+    }
+
+    #[test]
+    fn test_auto_map_program_locks() {
+        let (mut player, _, dir) = null_player("auto_map", 0);
+        let song_path = dir.join("leads.mid");
+        let midi = song(&[
+            (0., program_change(0, 80)),
+            (0., program_change(1, 0)),
+            (0., note_on(0, 60, 100)),
+            (1., note_off(0, 60)),
+        ]);
+        fs::write(&song_path, midi.to_midi()).expect("write song");
+        let font_path = dir.join("micro.sf2");
+        fs::write(&font_path, micro_soundfont()).expect("write soundfont");
+        let index = player.get_playlist_idx();
+        let playlist = player.get_playlist_mut();
+        playlist.add_song(song_path).expect("add song");
+        playlist.add_font(font_path).expect("add soundfont");
+        playlist.set_song_idx(Some(0)).expect("select song");
+        playlist.set_font_idx(Some(0)).expect("select soundfont");
+        let mut program_locks = ProgramLocks::new();
+        program_locks.set(5, Some(7));
+        player
+            .set_playlist_program_locks(index, program_locks)
+            .expect("playlist");
+
+        assert_eq!(player.auto_map_program_locks(index).expect("auto-map"), 1);
+        let program_locks = player.get_playlist().get_program_locks();
+        // Lead 1 (square) is the soundfont's "square"
+        assert_eq!(program_locks.get(0), Some(1));
+        // Nothing looks like a piano
+        assert_eq!(program_locks.get(1), None);
+        // Edited before, kept
+        assert_eq!(program_locks.get(5), Some(7));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    fn playing_song(player: &Player) -> Option<usize> {
+        player.get_playing_song_idx()
+    }
+
+    #[test]
+    fn test_queue_advance() {
+        let (mut player, sink, dir) = null_player("queue_advance", 3);
+        player.start();
+        assert_eq!(playing_song(&player), Some(0));
+        assert!(!player.is_paused());
+
+        sink.finish();
+        player.update();
+        assert_eq!(playing_song(&player), Some(1));
+
+        // Not finished yet
+        sink.advance(Duration::from_millis(100));
+        player.update();
+        assert_eq!(playing_song(&player), Some(1));
+
+        sink.finish();
+        player.update();
+        sink.finish();
+        player.update();
+        // Queue end without repeat
+        assert!(player.is_empty());
+        assert!(player.is_paused());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_repeat_modes() {
+        let (mut player, sink, dir) = null_player("repeat_modes", 2);
+        player.cycle_repeat();
+        assert!(player.get_repeat() == RepeatMode::Queue);
+        player.start();
+        sink.finish();
+        player.update();
+        sink.finish();
+        player.update();
+        assert_eq!(playing_song(&player), Some(0));
+
+        player.cycle_repeat();
+        assert!(player.get_repeat() == RepeatMode::Song);
+        sink.finish();
+        player.update();
+        assert_eq!(playing_song(&player), Some(0));
+        assert!(!player.is_empty());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    /// Skipping used to jump back to the first song whenever repeat was on.
+    #[test]
+    fn test_skip_with_repeat() {
+        let (mut player, _sink, dir) = null_player("skip_with_repeat", 3);
+        player.cycle_repeat();
+        assert!(player.get_repeat() == RepeatMode::Queue);
+        player.start();
+        player.skip();
+        assert_eq!(playing_song(&player), Some(1));
+        player.skip();
+        assert_eq!(playing_song(&player), Some(2));
+        // Wraps only at the end
+        player.skip();
+        assert_eq!(playing_song(&player), Some(0));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_end_action() {
+        let (mut player, sink, dir) = null_player("end_action", 2);
+        player.end_action.kind = EndActionKind::Stop;
+        player.end_action.trigger = EndTrigger::Song;
+        player.start();
+        sink.finish();
+        player.update();
+        // Stopped after one song
+        assert!(player.is_empty());
+        assert_eq!(player.get_pending_end_action(), None);
+
+        player.end_action.kind = EndActionKind::Quit;
+        player.end_action.trigger = EndTrigger::Queue;
+        player.start();
+        sink.finish();
+        player.update();
+        assert_eq!(playing_song(&player), Some(1));
+        sink.finish();
+        player.update();
+        // Quitting waits for the countdown
+        assert!(player.is_empty());
+        assert!(player.get_pending_end_action().is_some());
+        let events = player.subscribe();
+        player.confirm_end_action();
+        assert!(events.try_iter().any(|event| event == PlayerEvent::Quit));
+        assert_eq!(player.get_pending_end_action(), None);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_failing_end_command() {
+        let mut player = Player::headless();
+        player.end_action.command = "exit 3".into();
+        player.pending_end_action = Some((EndActionKind::Command, Instant::now()));
+        let events = player.subscribe();
+        player.update();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let error = loop {
+            player.update();
+            if let Some(PlayerEvent::NotifyError(error)) = events.try_iter().next() {
+                break error;
+            }
+            assert!(Instant::now() < deadline, "no error reported");
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert!(error.contains("run a command"), "{error}");
+        assert!(error.contains('3'), "{error}");
+    }
+
+    #[test]
+    fn test_auto_pause() {
+        let (mut player, _sink, dir) = null_player("auto_pause", 1);
+        let events = player.subscribe();
+        // Nothing to pause
+        player.auto_pause("headphones were unplugged");
+        player.start();
+        player.auto_pause("headphones were unplugged");
+        player.auto_pause("headphones were unplugged");
+        assert!(player.is_paused());
+        assert!(player.is_playing());
+        let paused = events
+            .try_iter()
+            .filter(|event| matches!(event, PlayerEvent::AutoPaused(_)))
+            .count();
+        assert_eq!(paused, 1);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_playback_speed() {
+        let (mut player, sink, dir) = null_player("playback_speed", 1);
+        player.start();
+        sink.advance(Duration::from_millis(100));
+        player.set_speed(2.);
+        sink.advance(Duration::from_millis(100));
+        assert_eq!(player.get_playback_position(), Duration::from_millis(300));
+
+        player.seek_to(Duration::from_millis(50));
+        sink.advance(Duration::from_millis(100));
+        assert_eq!(player.get_playback_position(), Duration::from_millis(250));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_loop_points() {
+        let (mut player, sink, dir) = null_player("loop_points", 1);
+        player.start();
+        let len = player.get_playback_length();
+        let (a, b) = (len / 4, len / 2);
+        player
+            .set_loop_points(Some((a, b)))
+            .expect("song is playing");
+        assert_eq!(player.get_loop_points(), Some((a, b)));
+        assert_eq!(
+            player.get_playlist().get_songs()[0].get_loop_points(),
+            Some((a, b))
+        );
+
+        // The sink counts on past B, while the song went back to A
+        sink.advance(b + (b - a) / 2);
+        assert_eq!(player.get_playback_position(), a + (b - a) / 2);
+        player.seek_to(len);
+        assert_eq!(player.get_playback_position(), a);
+
+        assert!(player.set_loop_points(Some((b, a))).is_err());
+        player.set_loop_points(None).expect("song is playing");
+        assert_eq!(player.get_loop_points(), None);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_gapless() {
+        let (mut player, sink, dir) = null_player("gapless", 3);
+        player.start();
+        // The song is short enough to queue the next one right away
+        player.update();
+        assert!(player.audioplayer.has_next());
+
+        sink.finish();
+        assert!(!player.is_empty());
+        player.update();
+        assert_eq!(playing_song(&player), Some(1));
+        assert_eq!(player.get_playlist().get_song_idx(), Some(1));
+
+        // The queued song changes with the queue
+        player.update();
+        assert!(player.audioplayer.has_next());
+        player.toggle_shuffle();
+        player.update();
+        let next = player.get_playing_playlist().get_queue().peek_next(false);
+        assert_eq!(player.audioplayer.has_next(), next.is_some());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_event_subscription() {
+        let (mut player, sink, dir) = null_player("events", 2);
+        let events = player.subscribe();
+        player.new_playlist();
+        player.switch_to_playlist(0).expect("switch");
+        player.start();
+        sink.advance(Duration::from_millis(100));
+        player.update();
+        let received: Vec<PlayerEvent> = events.try_iter().collect();
+        let _ = fs::remove_dir_all(dir);
+
+        assert_eq!(received[0], PlayerEvent::PlaylistListChanged);
+        assert!(received.contains(&PlayerEvent::QueueChanged));
+        assert!(received
+            .iter()
+            .any(|event| matches!(event, PlayerEvent::SongChanged { .. })));
+        assert!(received.contains(&PlayerEvent::PositionTick {
+            position: Duration::from_millis(100),
+            length: player.get_playback_length(),
+        }));
+
+        // Dropped receivers are unsubscribed
+        drop(events);
+        player.new_playlist();
+        assert!(player.subscribers.is_empty());
+    }
+
+    #[test]
+    fn test_seek() {
+        let (mut player, sink, dir) = null_player("seek", 1);
+        player.start();
+        // Half a second, plus the last tick
+        let len = player.get_playback_length();
+        assert!(len >= Duration::from_millis(500) && len < Duration::from_millis(600));
+
+        player.seek_to(Duration::from_millis(300));
+        assert_eq!(player.get_playback_position(), Duration::from_millis(300));
+        player.pause();
+        sink.advance(Duration::from_millis(100));
+        assert_eq!(player.get_playback_position(), Duration::from_millis(300));
+        player.play();
+        sink.advance(Duration::from_millis(100));
+        assert_eq!(player.get_playback_position(), Duration::from_millis(400));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_replace_sinks() {
+        let (mut player, sink, dir) = null_player("replace_sinks", 1);
+        player.set_volume(50.);
+        player.start();
+        sink.advance(Duration::from_millis(200));
+        player.pause();
+
+        let new_sink = NullSink::default();
+        player.replace_sinks(Box::new(new_sink.clone()), Box::new(NullSink::default()));
+        assert!(sink.empty());
+        assert!(!new_sink.empty());
+        assert!(player.is_paused());
+        assert_eq!(player.get_playback_position(), Duration::from_millis(200));
+        assert!((new_sink.get_volume() - sink.get_volume()).abs() < f32::EPSILON);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_auto_dj() {
+        let (mut player, sink, dir) = null_player("auto_dj", 5);
+        player.start_auto_dj(dir.clone()).expect("start auto-dj");
+        assert_eq!(player.get_playlist().name, AUTO_DJ_NAME);
+        assert_eq!(
+            player.get_playlist().get_songs().len(),
+            AUTO_DJ_LOOKAHEAD + 1
+        );
+        assert!(!player.is_empty());
+
+        for _ in 0..3 {
+            sink.finish();
+            player.update();
+            assert!(!player.is_empty());
+        }
+        let playlist = player.get_playing_playlist();
+        let queue = playlist.get_queue();
+        let queue_idx = queue.get_position().expect("queue index");
+        assert_eq!(queue.len() - queue_idx, AUTO_DJ_LOOKAHEAD + 1);
+        assert_eq!(playlist.get_songs().len(), 5);
+        // Played songs were recorded
+        for &index in &queue.get_entries()[..=queue_idx] {
+            let path = playlist.get_songs()[index].get_path();
+            assert!(player.play_stats.get(&path).is_some());
+        }
+
+        player.stop_auto_dj();
+        assert!(player.get_auto_dj().is_none());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_song_range() {
+        let (mut player, sink, dir) = null_player("song_range", 2);
+        player
+            .get_playlist_mut()
+            .set_song_range(
+                0,
+                Some(Duration::from_millis(100)),
+                Some(Duration::from_millis(300)),
+            )
+            .expect("set range");
+        player.start();
+        assert_eq!(player.get_playback_position(), Duration::from_millis(100));
+
+        sink.advance(Duration::from_millis(150));
+        player.update();
+        assert_eq!(playing_song(&player), Some(0));
+
+        // Ends at the range end, not the song end
+        sink.advance(Duration::from_millis(50));
+        player.update();
+        assert_eq!(playing_song(&player), Some(1));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_watch_new_songs() {
+        let (mut player, sink, dir) = null_player("watch_new_songs", 3);
+        let playlist = player.get_playlist_mut();
+        playlist.set_song_list_mode(FileListMode::Directory);
+        playlist.set_song_dir(dir.clone());
+        playlist.set_autoplay_new_files(true);
+        player.start();
+        assert_eq!(playing_song(&player), Some(0));
+
+        // Nothing new
+        player.watch_new_songs();
+        assert_eq!(
+            player.get_playing_playlist().get_queue().get_entries(),
+            [0, 1, 2]
+        );
+
+        // Played next, not at the end of the queue
+        fs::write(dir.join("song3.mid"), short_song()).expect("write song");
+        player.watch_new_songs();
+        assert_eq!(
+            player.get_playing_playlist().get_queue().get_entries(),
+            [0, 3, 1, 2]
+        );
+        sink.finish();
+        player.update();
+        assert_eq!(playing_song(&player), Some(3));
+
+        // Starts playing when stopped
+        player.stop();
+        fs::write(dir.join("song4.mid"), short_song()).expect("write song");
+        player.watch_new_songs();
+        assert_eq!(playing_song(&player), Some(4));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_follow_playing_file() {
+        let (mut player, sink, dir) = null_player("follow_file", 1);
+        player.follow_file = true;
+        let events = player.subscribe();
+        player.start();
+        sink.advance(Duration::from_millis(200));
+
+        // Unchanged
+        player.follow_playing_file();
+        assert!(!events
+            .try_iter()
+            .any(|event| matches!(event, PlayerEvent::SongReloaded { .. })));
+
+        let file = File::options()
+            .write(true)
+            .open(dir.join("song0.mid"))
+            .expect("open song");
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .expect("set modified");
+        player.pause();
+        player.follow_playing_file();
+        assert!(events
+            .try_iter()
+            .any(|event| matches!(event, PlayerEvent::SongReloaded { .. })));
+        assert_eq!(player.get_playback_position(), Duration::from_millis(200));
+        assert!(player.is_paused());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_cue_crossfade() {
+        let (mut player, sink, dir) = null_player("cue", 3);
+        let preview_sink = NullSink::default();
+        player.set_preview_sink(Some(Box::new(preview_sink.clone())));
+        player.start();
+        player.cue_song(2).expect("cue song");
+
+        // Main keeps playing, and stays louder
+        assert_eq!(playing_song(&player), Some(0));
+        assert!(player.get_cue().is_some());
+        assert!(!player.is_preview_playing());
+        assert!(sink.get_volume() > preview_sink.get_volume());
+
+        preview_sink.advance(Duration::from_millis(200));
+        player.crossfade_now();
+        assert!(player.get_cue().is_some_and(Cue::is_crossfading));
+
+        // Main ran out mid-fade: the cued song takes over from where the preview was
+        sink.finish();
+        player.update();
+        assert_eq!(playing_song(&player), Some(2));
+        assert!(player.get_cue().is_none());
+        assert_eq!(player.get_playback_position(), Duration::from_millis(200));
+
+        // Cueing while stopped starts right away
+        player.stop();
+        player.cue_song(1).expect("cue song");
+        player.crossfade_now();
+        assert_eq!(playing_song(&player), Some(1));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_replay_history() {
+        let (mut player, _sink, dir) = null_player("history", 2);
+        player.set_speed(1.5);
+        player.start();
+        player.set_speed(1.);
+        player.skip();
+        assert_eq!(player.get_play_history().iter().count(), 2);
+        let first = player
+            .get_play_history()
+            .get(1)
+            .cloned()
+            .expect("first play");
+        assert_eq!(first.song, dir.join("song0.mid"));
+
+        player.replay_history_entry(1).expect("replay");
+        assert_eq!(playing_song(&player), Some(0));
+        assert_eq!(
+            player.get_active_font().map(|(link, _)| *link),
+            Some(first.font_link)
+        );
+        assert!((player.get_speed() - 1.5).abs() < f32::EPSILON);
+        assert_eq!(player.get_play_history().iter().count(), 3);
+        assert!(player.replay_history_entry(10).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_deferred_loading() {
+        let mut player = Player::headless();
+        player.playlists = vec![Playlist::default(), Playlist::default()];
+        player.pending_refresh = player.playlists.iter().map(Playlist::get_id).collect();
+        player.playlist_idx = 0;
+
+        // Waits for the window
+        player.refresh_pending_playlist();
+        assert!(player.is_refresh_pending(0));
+        assert!(player.is_refresh_pending(1));
+
+        player.start_deferred_loading();
+        player.refresh_pending_playlist();
+        assert!(!player.is_refresh_pending(0));
+        assert!(player.is_refresh_pending(1));
+        player.refresh_pending_playlist();
+        assert!(!player.is_refresh_pending(1));
+    }
 }
@@ -1,63 +1,228 @@
 //! Audio backend module
 
 use std::{
-    fs::{self, File},
-    path::PathBuf,
-    sync::Arc,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::{mpsc::Receiver, Arc},
     time::Duration,
 };
 
+use ab_loop::LoopRegion;
+use activity::{ChannelActivity, NoteEvent};
+use backend::{AudioBackend, BoxedSource};
+use clip_meter::ClipMeter;
+use effects::Effects;
 use error::PlayerError;
+use gapless::{Cancellable, NextSong};
+use limiter::{Limiter, SharedGain};
+use lyrics::Lyrics;
+use markers::{NoteDensity, SongMarker};
+use midi_clock::MidiClock;
 use midi_msg::MidiFile;
+use midi_out::MidiOut;
 use midisource::MidiSource;
-use rodio::Sink;
+use recorder::{Recorder, Tee};
 use rustysynth::SoundFont;
+use scope::{Scope, ScopeTap};
+use speed::{PlaybackSpeed, SpeedAnchor};
 
-mod error;
+use super::{
+    audio_profile::AudioProfile,
+    playlist::{
+        font_meta::{sf3, FontMeta},
+        midi_meta::{parse_midi, MidiMeta},
+    },
+    program_lock::{DrumKit, ProgramLocks, CHANNEL_COUNT},
+};
+
+mod ab_loop;
+pub mod activity;
+pub mod backend;
+pub mod clip_meter;
+pub mod device_watch;
+mod effects;
+pub mod error;
+pub mod export;
+mod flac;
+mod gapless;
+mod limiter;
+pub mod livesource;
+pub mod lyrics;
+pub mod markers;
+mod midi_clock;
+pub mod midi_in;
+pub mod midi_out;
 mod midisequencer;
 mod midisource;
 mod midisynth;
+pub mod net_stream;
+mod recorder;
+pub mod routing;
+mod scope;
+pub mod speed;
+pub mod system_watch;
+#[cfg(not(target_arch = "wasm32"))]
+mod vorbis;
+
+/// What's known of a song once its source is built
+struct SongInfo {
+    duration: Duration,
+    /// Seek bar markers
+    markers: Vec<SongMarker>,
+    /// Notes per second
+    note_density: NoteDensity,
+    lyrics: Lyrics,
+    loop_region: LoopRegion,
+}
 
 /// Audio backend struct
 #[derive(Default)]
 pub struct AudioPlayer {
-    path_soundfont: Option<PathBuf>,
-    path_midifile: Option<PathBuf>,
+    soundfont: Option<FontMeta>,
+    /// Last SF3 and its size, since decoding the samples takes a while
+    sf3_cache: Option<(PathBuf, Option<u64>, Arc<SoundFont>)>,
+    midifile: Option<MidiMeta>,
     midifile_duration: Option<Duration>,
+    /// Seek bar markers of current midi file
+    markers: Vec<SongMarker>,
+    /// Notes per second of current midi file
+    note_density: NoteDensity,
+    /// Lyrics of current midi file
+    lyrics: Lyrics,
+    /// A-B loop of current midi file
+    loop_region: LoopRegion,
+    /// Tempo multiplier, shared with the playing source
+    speed: PlaybackSpeed,
+    /// Where the song was when the speed last changed, or the sink last seeked
+    speed_anchor: SpeedAnchor,
+    program_locks: ProgramLocks,
+    drum_kit: Option<DrumKit>,
+    /// Reverb, EQ and gain
+    audio_profile: AudioProfile,
+    /// Volume above 1.0, applied through the limiter
+    boost: SharedGain,
+    /// Note activity of the playing song
+    activity: ChannelActivity,
+    /// Clipping of the output
+    clip_meter: ClipMeter,
+    /// Where MIDI clock is sent, if anywhere
+    midi_clock: Option<MidiOut>,
+    /// Where played events are forwarded, if anywhere
+    midi_thru: Option<MidiOut>,
+    /// Copies the output into a file while recording
+    recorder: Recorder,
+    /// Latest output, for visualizers
+    scope: Scope,
+    /// Song queued in the sink after the current one
+    next: Option<NextSong>,
 
     // We need to keep this alive or the sink goes silent.
     //#[allow(dead_code)]
     //stream: OutputStream,
     /// Audio sink, controls the output
-    sink: Option<Sink>,
+    sink: Option<Box<dyn AudioBackend>>,
 }
 
 impl AudioPlayer {
-    pub(crate) fn set_sink(&mut self, value: Option<Sink>) {
+    pub(crate) fn set_sink(&mut self, value: Option<Box<dyn AudioBackend>>) {
         if let Some(ref sink) = value {
             sink.pause();
         }
+        self.next = None;
         self.sink = value;
     }
+    /// Switch to another sink, e.g. when the output device was lost. Continues from the same
+    /// position, and stays paused if paused. Returns the old sink.
+    pub(crate) fn move_to_sink(
+        &mut self,
+        value: Box<dyn AudioBackend>,
+    ) -> Option<Box<dyn AudioBackend>> {
+        let resume = self
+            .sink
+            .as_ref()
+            .filter(|sink| !sink.empty())
+            .map(|sink| (self.get_midi_position(), sink.is_paused()));
+        if let Some(sink) = &self.sink {
+            sink.clear();
+        }
+        let old = self.sink.take();
+        self.set_sink(Some(value));
+        if let Some((pos, paused)) = resume {
+            let _ = self.start_playback();
+            let _ = self.seek_to(pos);
+            if paused {
+                let _ = self.pause();
+            }
+        }
+        old
+    }
 
     // --- File Management
 
     /// Choose new soundfont
-    pub(crate) fn set_soundfont(&mut self, path: PathBuf) {
-        self.path_soundfont = Some(path);
-
+    pub(crate) fn set_soundfont(&mut self, font: FontMeta) {
+        self.soundfont = Some(font);
+        self.restart_playback();
+    }
+    /// Choose channels that ignore program changes
+    pub(crate) fn set_program_locks(&mut self, program_locks: ProgramLocks) {
+        if self.program_locks == program_locks {
+            return;
+        }
+        self.program_locks = program_locks;
+        self.restart_playback();
+    }
+    /// Choose percussion preset that ignores bank and program changes
+    pub(crate) fn set_drum_kit(&mut self, drum_kit: Option<DrumKit>) {
+        if self.drum_kit == drum_kit {
+            return;
+        }
+        self.drum_kit = drum_kit;
+        self.restart_playback();
+    }
+    /// Choose post-processing
+    pub(crate) fn set_audio_profile(&mut self, audio_profile: &AudioProfile) {
+        if self.audio_profile.sounds_like(audio_profile) {
+            return;
+        }
+        self.audio_profile = audio_profile.clone();
+        self.restart_playback();
+    }
+    /// Send MIDI clock and transport to an output port, or stop sending
+    pub(crate) fn set_midi_clock(&mut self, out: Option<MidiOut>) {
+        self.midi_clock = out;
+        self.restart_playback();
+    }
+    /// Forward played events to an output port, or stop forwarding
+    pub(crate) fn set_midi_thru(&mut self, out: Option<MidiOut>) {
+        self.midi_thru = out;
+        self.restart_playback();
+    }
+    /// Restart from the same position to apply new settings
+    fn restart_playback(&mut self) {
         if let Some(sink) = &self.sink {
             if !sink.empty() {
-                let pos = sink.get_pos();
+                let pos = self.get_midi_position();
                 sink.clear();
+                self.next = None;
                 let _ = self.start_playback();
                 let _ = self.seek_to(pos);
             }
         };
     }
     /// Choose new midi file
-    pub(crate) fn set_midifile(&mut self, path: PathBuf) {
-        self.path_midifile = Some(path);
+    pub(crate) fn set_midifile(&mut self, song: MidiMeta) {
+        self.midifile = Some(song);
+        self.clip_meter.reset();
+    }
+    /// Read the midi file again and continue from the same position. Stays paused if paused.
+    pub(crate) fn reload_midifile(&mut self, song: MidiMeta) {
+        let paused = self.is_paused();
+        self.midifile = Some(song);
+        self.restart_playback();
+        if paused {
+            let _ = self.pause();
+        }
     }
 
     // --- Playback Control
@@ -68,6 +233,9 @@ impl AudioPlayer {
             anyhow::bail!(PlayerError::NoSink);
         };
         sink.play();
+        if let Some(out) = &self.midi_clock {
+            midi_clock::send_continue(out);
+        }
         Ok(())
     }
     /// Pause
@@ -76,56 +244,235 @@ impl AudioPlayer {
             anyhow::bail!(PlayerError::NoSink);
         };
         sink.pause();
+        if let Some(out) = &self.midi_clock {
+            midi_clock::send_stop(out);
+        }
+        if let Some(out) = &self.midi_thru {
+            out.all_notes_off();
+        }
         Ok(())
     }
-    /// Standard volume range is 0.0..=1.0
+    /// Standard volume range is 0.0..=1.0. Above that, the soft limiter is engaged.
     pub(crate) fn set_volume(&self, volume: f32) -> anyhow::Result<()> {
         let Some(sink) = &self.sink else {
             anyhow::bail!(PlayerError::NoSink);
         };
-        sink.set_volume(volume);
+        sink.set_volume(volume.min(1.0));
+        self.boost.set(volume.max(1.0));
         Ok(())
     }
     /// Load currently selected midi & font and start playing
     pub(crate) fn start_playback(&mut self) -> anyhow::Result<()> {
-        let Some(path_sf) = &self.path_soundfont else {
+        let Some(font) = self.soundfont.clone() else {
             anyhow::bail!(PlayerError::NoFont);
         };
-        let Some(path_mid) = &self.path_midifile else {
+        let Some(song) = self.midifile.clone() else {
             anyhow::bail!(PlayerError::NoMidi);
         };
+        if self.sink.is_none() {
+            anyhow::bail!(PlayerError::NoSink);
+        }
+
+        let (source, info) = self.build_source(&font, &song, self.activity.clone())?;
+        self.midifile_duration = Some(info.duration);
+        self.markers = info.markers;
+        self.note_density = info.note_density;
+        self.lyrics = info.lyrics;
+        self.loop_region = info.loop_region;
+
         let Some(sink) = &self.sink else {
             anyhow::bail!(PlayerError::NoSink);
         };
+        sink.append(source);
+        if let Some(start) = song.get_start() {
+            sink.try_seek(start);
+        }
+        self.speed_anchor = SpeedAnchor::at(song.get_start().unwrap_or_default());
+        sink.play();
+        Ok(())
+    }
+    /// Synth and effects chain of a song
+    fn build_source(
+        &mut self,
+        font: &FontMeta,
+        song: &MidiMeta,
+        activity: ChannelActivity,
+    ) -> anyhow::Result<(BoxedSource, SongInfo)> {
+        let soundfont = load_soundfont(font, &mut self.sf3_cache)?;
+        let midifile = load_midifile(song)?;
+
+        let mut source = MidiSource::new(
+            &soundfont,
+            midifile,
+            self.program_locks,
+            self.drum_kit,
+            activity,
+            self.audio_profile.synth_effects,
+        );
+        source.set_end(song.get_end());
+        let loop_region = LoopRegion::new(song.get_loop_points());
+        source.set_loop(loop_region.clone());
+        source.set_speed(self.speed.clone());
+        if let Some(out) = &self.midi_clock {
+            source.set_clock(MidiClock::new(out.clone()));
+        }
+        if let Some(out) = &self.midi_thru {
+            source.set_thru(out.clone());
+        }
+        let info = SongInfo {
+            duration: source.get_song_length(),
+            markers: source.get_markers().to_vec(),
+            note_density: source.get_note_density().clone(),
+            lyrics: source.get_lyrics().clone(),
+            loop_region,
+        };
 
-        let soundfont = Arc::new(load_soundfont(path_sf)?);
-        let midifile = load_midifile(path_mid)?;
+        let source = Effects::new(source, &self.audio_profile);
+        let channels = rodio::Source::channels(&source);
+        let source = Limiter::new(source, self.boost.clone())
+            .with_clip_meter(self.clip_meter.clone(), channels);
+        let source = ScopeTap::new(source, self.scope.clone());
+        Ok((Box::new(Tee::new(source, self.recorder.clone())), info))
+    }
 
-        let source = MidiSource::new(&soundfont, midifile);
-        self.midifile_duration = Some(source.get_song_length());
+    // --- Gapless
 
-        sink.append(source);
-        sink.play();
+    /// Append a song to start right when the current one ends. The song can't have a start
+    /// point, because the sink counts the position from where the source begins.
+    pub(crate) fn queue_next(&mut self, song: MidiMeta, font: FontMeta) -> anyhow::Result<()> {
+        self.cancel_next();
+        if self.sink.is_none() {
+            anyhow::bail!(PlayerError::NoSink);
+        }
+        if song.get_start().is_some() {
+            anyhow::bail!("Songs with a start point can't be queued");
+        }
+        if self.midi_clock.is_some() {
+            anyhow::bail!("MIDI clock restarts with each song");
+        }
+        let activity = ChannelActivity::default();
+        let (source, info) = self.build_source(&font, &song, activity.clone())?;
+        let (next, cancelled) = NextSong::new(song, font, info, activity);
+        if let Some(sink) = &self.sink {
+            sink.append(Box::new(Cancellable::new(source, cancelled)));
+        }
+        self.next = Some(next);
         Ok(())
     }
+    pub(crate) const fn has_next(&self) -> bool {
+        self.next.is_some()
+    }
+    /// Drop the queued song. Returns false if it's playing already.
+    pub(crate) fn cancel_next(&mut self) -> bool {
+        if self.sink.as_ref().is_some_and(|sink| sink.queue_len() < 2) && self.next.is_some() {
+            return false;
+        }
+        if let Some(next) = self.next.take() {
+            next.cancel();
+        }
+        true
+    }
+    /// The queued song started, and is now the current song. True only once per song.
+    pub(crate) fn take_started_next(&mut self) -> bool {
+        let Some(sink) = &self.sink else {
+            return false;
+        };
+        if sink.queue_len() > 1 {
+            return false;
+        }
+        let Some(next) = self.next.take() else {
+            return false;
+        };
+        self.midifile = Some(next.song);
+        self.soundfont = Some(next.font);
+        self.midifile_duration = Some(next.info.duration);
+        self.markers = next.info.markers;
+        self.note_density = next.info.note_density;
+        self.lyrics = next.info.lyrics;
+        self.loop_region = next.info.loop_region;
+        self.speed_anchor = SpeedAnchor::default();
+        self.activity = next.activity;
+        self.clip_meter.reset();
+        true
+    }
     /// Full stop.
     pub(crate) fn stop_playback(&mut self) -> anyhow::Result<()> {
         let Some(sink) = &self.sink else {
             anyhow::bail!(PlayerError::NoSink);
         };
         self.midifile_duration = None;
+        self.markers.clear();
+        self.note_density.clear();
+        self.lyrics.clear();
+        self.loop_region = LoopRegion::default();
+        self.speed_anchor = SpeedAnchor::default();
+        self.activity.clear();
+        self.scope.clear();
+        self.next = None;
         sink.clear();
         sink.pause();
         Ok(())
     }
-    pub(crate) fn seek_to(&self, pos: Duration) -> anyhow::Result<()> {
+    /// Past the end of an A-B loop goes to A
+    pub(crate) fn seek_to(&mut self, pos: Duration) -> anyhow::Result<()> {
         let Some(sink) = &self.sink else {
             anyhow::bail!(PlayerError::NoSink);
         };
-        let _ = sink.try_seek(pos);
+        let pos = match self.loop_region.get() {
+            Some((a, b)) if pos >= b => a,
+            _ => pos,
+        };
+        sink.try_seek(pos);
+        self.speed_anchor = SpeedAnchor::at(pos);
         Ok(())
     }
 
+    // --- A-B loop
+
+    pub(crate) fn get_loop_points(&self) -> Option<(Duration, Duration)> {
+        self.loop_region.get()
+    }
+    /// Loop the playing song. B is kept within the song.
+    pub(crate) fn set_loop_points(&mut self, points: Option<(Duration, Duration)>) {
+        let length = self.midifile_duration.unwrap_or_default();
+        let points = points.map(|(a, b)| (a, b.min(length)));
+        // Kept for restarts
+        if let Some(song) = &mut self.midifile {
+            song.set_loop_points(points);
+        }
+        let Some(sink) = &self.sink else {
+            return;
+        };
+        let raw_pos = self.get_unfolded_position(sink.as_ref());
+        let pos = self.loop_region.fold(raw_pos);
+        self.loop_region.set(points);
+        // The sink's position only matches the song again after a seek
+        let seek = match self.loop_region.get() {
+            Some((a, b)) if pos >= b => Some(a),
+            _ if raw_pos != pos => Some(pos),
+            _ => None,
+        };
+        if let Some(seek) = seek {
+            sink.try_seek(seek);
+            self.speed_anchor = SpeedAnchor::at(seek);
+        }
+    }
+
+    // --- Recording
+
+    /// Write everything that plays into a wav file. The receiver gets write errors, and
+    /// disconnects when the file is done.
+    pub(crate) fn start_recording(&self, path: &Path) -> anyhow::Result<Receiver<String>> {
+        self.recorder
+            .start(path, midisource::CHANNELS, midisource::SAMPLERATE)
+    }
+    pub(crate) fn stop_recording(&self) {
+        self.recorder.stop();
+    }
+    pub(crate) fn is_recording(&self) -> bool {
+        self.recorder.is_recording()
+    }
+
     // --- Playback State
 
     /// Pause status. Fully stopped should also always be paused.
@@ -142,7 +489,52 @@ impl AudioPlayer {
         };
         sink.empty()
     }
+    /// Tempo changes and marker events of current midi file
+    #[must_use]
+    pub fn get_markers(&self) -> &[SongMarker] {
+        &self.markers
+    }
+    /// Notes per second of current midi file
+    #[must_use]
+    pub const fn get_note_density(&self) -> &NoteDensity {
+        &self.note_density
+    }
+    /// Lyrics of current midi file
+    #[must_use]
+    pub const fn get_lyrics(&self) -> &Lyrics {
+        &self.lyrics
+    }
+    /// Latest velocity of each sounding channel
+    #[must_use]
+    pub fn get_channel_levels(&self) -> [u8; CHANNEL_COUNT] {
+        self.activity.get_levels()
+    }
+    /// Bank select and program of each channel
+    #[must_use]
+    pub fn get_channel_programs(&self) -> [(u8, u8); CHANNEL_COUNT] {
+        self.activity.get_programs()
+    }
+    /// Note ons and offs since the last call
+    #[must_use]
+    pub fn take_note_events(&self) -> Vec<NoteEvent> {
+        self.activity.take_note_events()
+    }
+    /// Latest output frames, oldest first
+    #[must_use]
+    pub fn get_output_frames(&self) -> Vec<[f32; 2]> {
+        self.scope.get_frames()
+    }
+    /// Audio host and output device
+    pub(crate) fn get_backend_name(&self) -> Option<String> {
+        self.sink.as_ref().map(|sink| sink.get_name())
+    }
+    /// Clip counter and peak of the playing song
+    #[must_use]
+    pub const fn get_clip_meter(&self) -> &ClipMeter {
+        &self.clip_meter
+    }
     /// Current midi file duration, if midi file exists
+    #[must_use]
     pub const fn get_midi_length(&self) -> Option<Duration> {
         self.midifile_duration
     }
@@ -151,26 +543,67 @@ impl AudioPlayer {
         let Some(sink) = &self.sink else {
             return Duration::ZERO;
         };
-        sink.get_pos()
+        self.loop_region
+            .fold(self.get_unfolded_position(sink.as_ref()))
+    }
+    /// Song position that keeps counting past the B of an A-B loop
+    fn get_unfolded_position(&self, sink: &dyn AudioBackend) -> Duration {
+        self.speed_anchor
+            .song_position(sink.get_pos(), self.speed.get())
+    }
+
+    // --- Speed
+
+    pub(crate) fn get_speed(&self) -> f32 {
+        self.speed.get()
+    }
+    /// Tempo multiplier. Takes effect while playing, without changing pitch.
+    pub(crate) fn set_speed(&mut self, speed: f32) {
+        if let Some(sink) = &self.sink {
+            let sink_pos = sink.get_pos();
+            let song_pos = self.get_unfolded_position(sink.as_ref());
+            self.speed_anchor = SpeedAnchor::new(sink_pos, song_pos);
+        }
+        self.speed.set(speed);
     }
 }
 
 // --- Private --- //
 
-fn load_soundfont(path: &PathBuf) -> anyhow::Result<SoundFont> {
-    match File::open(path) {
-        Ok(mut file) => match SoundFont::new(&mut file) {
-            Ok(soundfont) => Ok(soundfont),
-            Err(e) => anyhow::bail!(PlayerError::InvalidFont { source: e }),
-        },
+pub(super) fn load_soundfont(
+    font: &FontMeta,
+    sf3_cache: &mut Option<(PathBuf, Option<u64>, Arc<SoundFont>)>,
+) -> anyhow::Result<Arc<SoundFont>> {
+    if let Some((path, size, soundfont)) = sf3_cache {
+        if *path == font.get_path() && *size == font.get_size() {
+            return Ok(Arc::clone(soundfont));
+        }
+    }
+    let mut bytes = match font.read_bytes() {
+        Ok(bytes) => bytes,
         Err(e) => anyhow::bail!(PlayerError::CantAccessFile {
-            path: path.clone(),
+            path: font.get_path(),
             source: e,
         }),
+    };
+    let compressed = sf3::is_compressed(&bytes);
+    if compressed {
+        bytes = match sf3::to_sf2(&bytes, true) {
+            Ok(bytes) => bytes.into(),
+            Err(e) => anyhow::bail!(PlayerError::InvalidCompressedFont { source: e }),
+        };
+    }
+    let soundfont = match SoundFont::new(&mut Cursor::new(bytes)) {
+        Ok(soundfont) => Arc::new(soundfont),
+        Err(e) => anyhow::bail!(PlayerError::InvalidFont { source: e }),
+    };
+    if compressed {
+        *sf3_cache = Some((font.get_path(), font.get_size(), Arc::clone(&soundfont)));
     }
+    Ok(soundfont)
 }
 
-fn load_midifile(filepath: &PathBuf) -> anyhow::Result<MidiFile> {
-    let bytes = fs::read(filepath)?;
-    Ok(midi_msg::MidiFile::from_midi(bytes.as_slice())?)
+fn load_midifile(song: &MidiMeta) -> anyhow::Result<MidiFile> {
+    let bytes = song.read_bytes()?;
+    parse_midi(&bytes)
 }
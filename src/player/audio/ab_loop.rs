@@ -0,0 +1,57 @@
+//! A-B loop: when playback reaches B, the source jumps back to A by itself.
+//!
+//! The sink doesn't know about the jump and counts its position on past B, so positions read
+//! from the sink are folded back into the loop.
+
+use std::{sync::Arc, time::Duration};
+
+use parking_lot::Mutex;
+
+/// Loop points of one song, shared with its source
+#[derive(Debug, Clone, Default)]
+pub struct LoopRegion(Arc<Mutex<Option<(Duration, Duration)>>>);
+
+impl LoopRegion {
+    pub fn new(points: Option<(Duration, Duration)>) -> Self {
+        Self(Arc::new(Mutex::new(points.filter(|(a, b)| a < b))))
+    }
+    pub fn get(&self) -> Option<(Duration, Duration)> {
+        *self.0.lock()
+    }
+    /// Empty or backwards loops turn the loop off
+    pub fn set(&self, points: Option<(Duration, Duration)>) {
+        *self.0.lock() = points.filter(|(a, b)| a < b);
+    }
+
+    /// Song position of a position that kept counting past B
+    pub fn fold(&self, pos: Duration) -> Duration {
+        let Some((a, b)) = self.get() else {
+            return pos;
+        };
+        let len = b.saturating_sub(a).as_nanos();
+        if pos < b || len == 0 {
+            return pos;
+        }
+        let into_loop = pos.saturating_sub(a).as_nanos() % len;
+        a + Duration::from_nanos(into_loop as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold() {
+        let secs = Duration::from_secs;
+        let region = LoopRegion::new(Some((secs(10), secs(20))));
+        assert_eq!(region.fold(secs(5)), secs(5));
+        assert_eq!(region.fold(secs(15)), secs(15));
+        assert_eq!(region.fold(secs(20)), secs(10));
+        assert_eq!(region.fold(secs(35)), secs(15));
+
+        region.set(Some((secs(20), secs(10))));
+        assert_eq!(region.get(), None);
+        assert_eq!(region.fold(secs(35)), secs(35));
+    }
+}
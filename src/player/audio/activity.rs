@@ -0,0 +1,260 @@
+//! Per-channel note activity and programs, shared from the audio thread to the GUI for visualization.
+//!
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+};
+
+use midi_msg::{ChannelModeMsg, ChannelVoiceMsg, MidiMsg};
+use parking_lot::Mutex;
+
+use crate::player::program_lock::CHANNEL_COUNT;
+
+/// Note events kept until the GUI reads them. Oldest are dropped after this.
+const NOTE_LOG_LEN: usize = 512;
+
+#[derive(Debug, Default)]
+struct ChannelState {
+    /// Notes held down
+    held: AtomicU8,
+    /// Velocity of the latest note on
+    velocity: AtomicU8,
+    /// Bank select MSB
+    bank: AtomicU8,
+    program: AtomicU8,
+}
+
+/// A note on or off that was sent to the synth
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteEvent {
+    pub channel: usize,
+    pub note: u8,
+    /// 0 for note off
+    pub velocity: u8,
+}
+
+/// Which channels are sounding, and how loud they were hit.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelActivity {
+    channels: Arc<[ChannelState; CHANNEL_COUNT]>,
+    /// Recent note events, oldest first
+    notes: Arc<Mutex<VecDeque<NoteEvent>>>,
+}
+
+impl ChannelActivity {
+    /// Track note ons and offs, and program changes of a message that was sent to the synth.
+    pub fn observe(&self, event: &MidiMsg) {
+        match event {
+            MidiMsg::ChannelVoice { channel, msg }
+            | MidiMsg::RunningChannelVoice { channel, msg } => {
+                let channel = *channel as usize;
+                let state = &self.channels[channel];
+                match msg {
+                    ChannelVoiceMsg::NoteOn { note, velocity } if *velocity > 0 => {
+                        note_on(state, *velocity);
+                        self.log_note(channel, *note, *velocity);
+                    }
+                    ChannelVoiceMsg::HighResNoteOn { note, velocity } if *velocity > 0 => {
+                        let velocity = (velocity >> 9) as u8;
+                        note_on(state, velocity);
+                        self.log_note(channel, *note, velocity);
+                    }
+                    ChannelVoiceMsg::NoteOn { note, .. }
+                    | ChannelVoiceMsg::HighResNoteOn { note, .. }
+                    | ChannelVoiceMsg::NoteOff { note, .. }
+                    | ChannelVoiceMsg::HighResNoteOff { note, .. } => {
+                        let _ =
+                            state
+                                .held
+                                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |held| {
+                                    Some(held.saturating_sub(1))
+                                });
+                        self.log_note(channel, *note, 0);
+                    }
+                    ChannelVoiceMsg::ProgramChange { program } => {
+                        state.program.store(*program, Ordering::Relaxed);
+                    }
+                    ChannelVoiceMsg::ControlChange { control } if control.control() == 0 => {
+                        state.bank.store(control.value(), Ordering::Relaxed);
+                    }
+                    _ => (),
+                }
+            }
+            MidiMsg::ChannelMode {
+                channel,
+                msg: ChannelModeMsg::AllNotesOff | ChannelModeMsg::AllSoundOff,
+            }
+            | MidiMsg::RunningChannelMode {
+                channel,
+                msg: ChannelModeMsg::AllNotesOff | ChannelModeMsg::AllSoundOff,
+            } => {
+                self.channels[*channel as usize]
+                    .held
+                    .store(0, Ordering::Relaxed);
+            }
+            _ => (),
+        }
+    }
+
+    fn log_note(&self, channel: usize, note: u8, velocity: u8) {
+        let mut notes = self.notes.lock();
+        if notes.len() >= NOTE_LOG_LEN {
+            notes.pop_front();
+        }
+        notes.push_back(NoteEvent {
+            channel,
+            note,
+            velocity,
+        });
+    }
+    /// Note events since the last call, oldest first
+    #[must_use]
+    pub fn take_note_events(&self) -> Vec<NoteEvent> {
+        self.notes.lock().drain(..).collect()
+    }
+
+    /// Silence all channels and reset programs, e.g. on stop.
+    pub fn clear(&self) {
+        for state in self.channels.iter() {
+            state.held.store(0, Ordering::Relaxed);
+            state.bank.store(0, Ordering::Relaxed);
+            state.program.store(0, Ordering::Relaxed);
+        }
+        self.notes.lock().clear();
+    }
+
+    /// Latest velocity of each channel that has notes held, 0 for silent ones.
+    #[must_use]
+    pub fn get_levels(&self) -> [u8; CHANNEL_COUNT] {
+        std::array::from_fn(|channel| {
+            let state = &self.channels[channel];
+            if state.held.load(Ordering::Relaxed) == 0 {
+                0
+            } else {
+                state.velocity.load(Ordering::Relaxed)
+            }
+        })
+    }
+
+    /// Bank select and program of each channel
+    #[must_use]
+    pub fn get_programs(&self) -> [(u8, u8); CHANNEL_COUNT] {
+        std::array::from_fn(|channel| {
+            let state = &self.channels[channel];
+            (
+                state.bank.load(Ordering::Relaxed),
+                state.program.load(Ordering::Relaxed),
+            )
+        })
+    }
+}
+
+fn note_on(state: &ChannelState, velocity: u8) {
+    let _ = state
+        .held
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |held| {
+            Some(held.saturating_add(1))
+        });
+    state.velocity.store(velocity, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use midi_msg::{Channel, ControlChange};
+
+    use super::*;
+
+    fn voice(channel: Channel, msg: ChannelVoiceMsg) -> MidiMsg {
+        MidiMsg::ChannelVoice { channel, msg }
+    }
+
+    #[test]
+    fn test_note_on_off() {
+        let activity = ChannelActivity::default();
+        activity.observe(&voice(
+            Channel::Ch1,
+            ChannelVoiceMsg::NoteOn {
+                note: 60,
+                velocity: 100,
+            },
+        ));
+        activity.observe(&voice(
+            Channel::Ch1,
+            ChannelVoiceMsg::NoteOn {
+                note: 64,
+                velocity: 80,
+            },
+        ));
+        assert_eq!(activity.get_levels()[0], 80);
+        assert_eq!(activity.get_levels()[1], 0);
+
+        // Velocity 0 is a note off
+        activity.observe(&voice(
+            Channel::Ch1,
+            ChannelVoiceMsg::NoteOn {
+                note: 60,
+                velocity: 0,
+            },
+        ));
+        assert_eq!(activity.get_levels()[0], 80);
+        activity.observe(&voice(
+            Channel::Ch1,
+            ChannelVoiceMsg::NoteOff {
+                note: 64,
+                velocity: 0,
+            },
+        ));
+        assert_eq!(activity.get_levels()[0], 0);
+
+        let events = activity.take_note_events();
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[1],
+            NoteEvent {
+                channel: 0,
+                note: 64,
+                velocity: 80
+            }
+        );
+        assert_eq!(events[2].velocity, 0);
+        assert!(activity.take_note_events().is_empty());
+    }
+
+    #[test]
+    fn test_clear() {
+        let activity = ChannelActivity::default();
+        activity.observe(&voice(
+            Channel::Ch10,
+            ChannelVoiceMsg::NoteOn {
+                note: 36,
+                velocity: 127,
+            },
+        ));
+        assert_eq!(activity.get_levels()[9], 127);
+        activity.clear();
+        assert_eq!(activity.get_levels(), [0; CHANNEL_COUNT]);
+    }
+
+    #[test]
+    fn test_programs() {
+        let activity = ChannelActivity::default();
+        activity.observe(&voice(
+            Channel::Ch2,
+            ChannelVoiceMsg::ControlChange {
+                control: ControlChange::BankSelect(8 << 7),
+            },
+        ));
+        activity.observe(&voice(
+            Channel::Ch2,
+            ChannelVoiceMsg::ProgramChange { program: 40 },
+        ));
+        assert_eq!(activity.get_programs()[1], (8, 40));
+        assert_eq!(activity.get_programs()[0], (0, 0));
+        activity.clear();
+        assert_eq!(activity.get_programs()[1], (0, 0));
+    }
+}
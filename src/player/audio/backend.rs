@@ -0,0 +1,163 @@
+//! Audio output backends: rodio sink for real output, and a null sink for tests.
+//!
+
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use parking_lot::Mutex;
+use rodio::{
+    cpal::traits::{DeviceTrait, HostTrait},
+    Sink, Source,
+};
+
+/// Boxed audio source that a backend plays.
+pub type BoxedSource = Box<dyn Source<Item = f32> + Send>;
+
+/// Where the audio player sends its audio. Mirrors the parts of [`rodio::Sink`] that are used.
+pub trait AudioBackend: Send {
+    /// Queue a source for playback
+    fn append(&self, source: BoxedSource);
+    fn play(&self);
+    fn pause(&self);
+    fn is_paused(&self) -> bool;
+    /// Drop all queued sources
+    fn clear(&self);
+    /// Nothing is queued
+    fn empty(&self) -> bool;
+    /// Number of queued sources, the playing one included
+    fn queue_len(&self) -> usize;
+    fn set_volume(&self, volume: f32);
+    fn get_pos(&self) -> Duration;
+    fn try_seek(&self, pos: Duration);
+    /// Host and device, for diagnostics
+    fn get_name(&self) -> String;
+}
+
+impl AudioBackend for Sink {
+    fn append(&self, source: BoxedSource) {
+        Self::append(self, source);
+    }
+    fn play(&self) {
+        Self::play(self);
+    }
+    fn pause(&self) {
+        Self::pause(self);
+    }
+    fn is_paused(&self) -> bool {
+        Self::is_paused(self)
+    }
+    fn clear(&self) {
+        Self::clear(self);
+    }
+    fn empty(&self) -> bool {
+        Self::empty(self)
+    }
+    fn queue_len(&self) -> usize {
+        Self::len(self)
+    }
+    fn set_volume(&self, volume: f32) {
+        Self::set_volume(self, volume);
+    }
+    fn get_pos(&self) -> Duration {
+        Self::get_pos(self)
+    }
+    fn try_seek(&self, pos: Duration) {
+        let _ = Self::try_seek(self, pos);
+    }
+    fn get_name(&self) -> String {
+        let host = rodio::cpal::default_host();
+        let device = host
+            .default_output_device()
+            .and_then(|device| device.name().ok())
+            .unwrap_or_else(|| "no output device".into());
+        format!("{} ({device})", host.id().name())
+    }
+}
+
+#[derive(Debug, Default)]
+struct NullState {
+    paused: bool,
+    /// Lengths of the appended sources, the playing one first
+    sources: VecDeque<Duration>,
+    pos: Duration,
+    volume: f32,
+}
+
+/// Backend without audio output. Time only passes when told to, so playback logic can be
+/// tested without an audio device. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct NullSink(Arc<Mutex<NullState>>);
+
+#[allow(dead_code)] // Used in tests
+impl NullSink {
+    /// Move playback forward. The source ends when its length is reached.
+    pub fn advance(&self, delta_t: Duration) {
+        let mut state = self.0.lock();
+        let Some(len) = state.sources.front().copied() else {
+            return;
+        };
+        if state.paused {
+            return;
+        }
+        state.pos += delta_t;
+        if state.pos >= len {
+            state.sources.pop_front();
+            state.pos = Duration::ZERO;
+        }
+    }
+    /// Play the current source to the end.
+    pub fn finish(&self) {
+        let pos = self.0.lock().pos;
+        self.advance(Duration::MAX.saturating_sub(pos));
+    }
+    #[must_use]
+    pub fn get_volume(&self) -> f32 {
+        self.0.lock().volume
+    }
+}
+
+impl AudioBackend for NullSink {
+    fn append(&self, source: BoxedSource) {
+        let mut state = self.0.lock();
+        if state.sources.is_empty() {
+            state.pos = Duration::ZERO;
+        }
+        state
+            .sources
+            .push_back(source.total_duration().unwrap_or_default());
+    }
+    fn play(&self) {
+        self.0.lock().paused = false;
+    }
+    fn pause(&self) {
+        self.0.lock().paused = true;
+    }
+    fn is_paused(&self) -> bool {
+        self.0.lock().paused
+    }
+    fn clear(&self) {
+        let mut state = self.0.lock();
+        state.sources.clear();
+        state.pos = Duration::ZERO;
+    }
+    fn empty(&self) -> bool {
+        self.0.lock().sources.is_empty()
+    }
+    fn queue_len(&self) -> usize {
+        self.0.lock().sources.len()
+    }
+    fn set_volume(&self, volume: f32) {
+        self.0.lock().volume = volume;
+    }
+    fn get_pos(&self) -> Duration {
+        self.0.lock().pos
+    }
+    fn try_seek(&self, pos: Duration) {
+        let mut state = self.0.lock();
+        if let Some(len) = state.sources.front().copied() {
+            state.pos = pos.min(len);
+        }
+    }
+    fn get_name(&self) -> String {
+        "null".into()
+    }
+}
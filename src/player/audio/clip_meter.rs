@@ -0,0 +1,157 @@
+//! Clip detection of the final output, shared from the audio thread to the GUI.
+//!
+//! Besides sample peaks, peaks between samples are estimated, since the DAC can overshoot
+//! even when no sample is above 0 dBFS.
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+    Arc,
+};
+
+#[derive(Debug, Default)]
+struct ClipState {
+    /// Samples above 0 dBFS since reset
+    clips: AtomicU64,
+    /// Highest true peak since reset, as f32 bits
+    peak: AtomicU32,
+    /// Engage the limiter on the first clip
+    auto_limit: AtomicBool,
+    /// Limiter was auto-engaged
+    limiting: AtomicBool,
+}
+
+/// Clip counter and peak level of the playing song
+#[derive(Debug, Clone, Default)]
+pub struct ClipMeter(Arc<ClipState>);
+
+impl ClipMeter {
+    /// Samples above 0 dBFS since reset
+    #[must_use]
+    pub fn get_clip_count(&self) -> u64 {
+        self.0.clips.load(Ordering::Relaxed)
+    }
+    /// Highest true peak since reset. 1.0 is 0 dBFS.
+    #[must_use]
+    pub fn get_peak(&self) -> f32 {
+        f32::from_bits(self.0.peak.load(Ordering::Relaxed))
+    }
+    /// Has the limiter been engaged because of clipping
+    #[must_use]
+    pub fn is_limiting(&self) -> bool {
+        self.0.limiting.load(Ordering::Relaxed)
+    }
+    pub fn set_auto_limit(&self, value: bool) {
+        self.0.auto_limit.store(value, Ordering::Relaxed);
+        if !value {
+            self.0.limiting.store(false, Ordering::Relaxed);
+        }
+    }
+    /// Clear the counter, peak, and auto-engaged limiter
+    pub fn reset(&self) {
+        self.0.clips.store(0, Ordering::Relaxed);
+        self.0.peak.store(0, Ordering::Relaxed);
+        self.0.limiting.store(false, Ordering::Relaxed);
+    }
+
+    fn record(&self, peak: f32) {
+        // Bit patterns of positive floats sort like the floats themselves.
+        self.0.peak.fetch_max(peak.to_bits(), Ordering::Relaxed);
+        if peak > 1.0 {
+            self.0.clips.fetch_add(1, Ordering::Relaxed);
+            if self.0.auto_limit.load(Ordering::Relaxed) {
+                self.0.limiting.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Per-source state for measuring interleaved samples
+pub struct PeakDetector {
+    meter: ClipMeter,
+    /// Last four samples of each channel, oldest first
+    history: Vec<[f32; 4]>,
+    channel: usize,
+}
+
+impl PeakDetector {
+    #[must_use]
+    pub fn new(meter: ClipMeter, channels: u16) -> Self {
+        Self {
+            meter,
+            history: vec![[0.; 4]; usize::from(channels.max(1))],
+            channel: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn is_limiting(&self) -> bool {
+        self.meter.is_limiting()
+    }
+    /// Measure the next output sample
+    pub fn process(&mut self, sample: f32) {
+        let channel_count = self.history.len();
+        let history = &mut self.history[self.channel];
+        self.channel = (self.channel + 1) % channel_count;
+
+        history.rotate_left(1);
+        history[3] = sample;
+        let peak = sample.abs().max(midpoint(history).abs());
+        self.meter.record(peak);
+    }
+}
+
+/// Catmull-Rom estimate of the signal halfway between the middle two samples
+fn midpoint(history: &[f32; 4]) -> f32 {
+    let [p0, p1, p2, p3] = *history;
+    9.0f32.mul_add(p1 + p2, -p0 - p3) / 16.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_peak() {
+        let meter = ClipMeter::default();
+        let mut detector = PeakDetector::new(meter.clone(), 1);
+        for sample in [0.1, 0.5, -0.25, 0.] {
+            detector.process(sample);
+        }
+        assert_eq!(meter.get_clip_count(), 0);
+        assert!((meter.get_peak() - 0.5).abs() < 0.1);
+
+        detector.process(-1.5);
+        assert!(meter.get_clip_count() > 0);
+        assert!(meter.get_peak() >= 1.5);
+
+        meter.reset();
+        assert_eq!(meter.get_clip_count(), 0);
+        assert!(meter.get_peak().abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_true_peak() {
+        // No sample is above 0 dBFS, but the wave between them is.
+        let meter = ClipMeter::default();
+        let mut detector = PeakDetector::new(meter.clone(), 1);
+        for sample in [-0.7, 0.98, 0.98, -0.7] {
+            detector.process(sample);
+        }
+        assert!(meter.get_peak() > 1.0);
+        assert!(meter.get_clip_count() > 0);
+    }
+
+    #[test]
+    fn test_auto_limit() {
+        let meter = ClipMeter::default();
+        let mut detector = PeakDetector::new(meter.clone(), 2);
+        detector.process(1.2);
+        assert!(!meter.is_limiting());
+
+        meter.set_auto_limit(true);
+        detector.process(1.2);
+        assert!(meter.is_limiting());
+        meter.set_auto_limit(false);
+        assert!(!meter.is_limiting());
+    }
+}
@@ -0,0 +1,84 @@
+//! Notices when the output device goes away, like an unplugged USB DAC. Also finds output
+//! devices by name, for playing on a device other than the default.
+//!
+//! The audio stream doesn't tell about that, it just goes quiet. So the device list is polled
+//! from a background thread, because listing devices can take a while on some hosts.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use rodio::cpal::{
+    self,
+    traits::{DeviceTrait, HostTrait},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Name of the device that a new default stream opens on
+#[must_use]
+pub fn default_device_name() -> Option<String> {
+    cpal::default_host().default_output_device()?.name().ok()
+}
+
+fn has_device(name: &str) -> bool {
+    find_output_device(name).is_some()
+}
+
+/// Names of the output devices to choose from
+#[must_use]
+pub fn output_device_names() -> Vec<String> {
+    cpal::default_host()
+        .output_devices()
+        .map(|devices| devices.filter_map(|device| device.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+#[must_use]
+pub fn find_output_device(name: &str) -> Option<cpal::Device> {
+    cpal::default_host()
+        .output_devices()
+        .ok()?
+        .find(|device| device.name().is_ok_and(|n| n == name))
+}
+
+/// Polls output devices until something changes. The thread stops when the watcher is dropped.
+pub struct DeviceWatcher {
+    changed: Arc<AtomicBool>,
+}
+impl DeviceWatcher {
+    /// Watch for the device being removed. If there's no device, watch for one to appear.
+    /// `on_change` runs once from the watcher thread, e.g. to wake up the GUI.
+    pub fn new(device: Option<String>, on_change: impl Fn() + Send + 'static) -> Self {
+        let changed = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&changed);
+        // Without threads (web build), changes aren't noticed.
+        let _ = thread::Builder::new().spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+            // Watcher dropped
+            if Arc::strong_count(&flag) == 1 {
+                return;
+            }
+            let is_changed = device
+                .as_ref()
+                .map_or_else(|| default_device_name().is_some(), |name| !has_device(name));
+            if is_changed {
+                flag.store(true, Ordering::Relaxed);
+                on_change();
+                return;
+            }
+        });
+        Self { changed }
+    }
+
+    /// The watched device is gone, or a device became available
+    #[must_use]
+    pub fn has_changed(&self) -> bool {
+        self.changed.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,387 @@
+//! Post-processing of an [`AudioProfile`]: gain, 3-band EQ, and reverb.
+//!
+//! Filters are RBJ cookbook biquads. The reverb is a small Freeverb: parallel comb filters
+//! followed by allpass filters, with a slightly different tuning for the right channel.
+
+use std::{f32::consts::PI, time::Duration};
+
+use super::limiter::soft_limit;
+use crate::player::audio_profile::{AudioProfile, EQ_BANDS};
+
+/// Center or corner frequency of each EQ band
+const EQ_FREQUENCIES: [f32; EQ_BANDS] = [250., 1000., 4000.];
+const EQ_Q: f32 = 0.7;
+
+/// Comb filter delays in samples at 44.1 kHz
+const COMB_TUNING: [usize; 4] = [1116, 1188, 1277, 1356];
+const ALLPASS_TUNING: [usize; 2] = [556, 441];
+/// Right channel delay offset, for width
+const STEREO_SPREAD: usize = 23;
+const COMB_DAMPING: f32 = 0.2;
+const ALLPASS_FEEDBACK: f32 = 0.5;
+/// Comb input level, so that the sum of combs stays in range
+const REVERB_INPUT_GAIN: f32 = 0.015;
+
+fn db_to_gain(db: f32) -> f32 {
+    10_f32.powf(db / 20.)
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+#[allow(clippy::suboptimal_flops)] // Kept in the cookbook's form
+impl Biquad {
+    /// Coefficients are given unnormalized, as in the cookbook
+    fn new(b: [f32; 3], a: [f32; 3]) -> Self {
+        Self {
+            b0: b[0] / a[0],
+            b1: b[1] / a[0],
+            b2: b[2] / a[0],
+            a1: a[1] / a[0],
+            a2: a[2] / a[0],
+            ..Default::default()
+        }
+    }
+
+    fn low_shelf(sample_rate: f32, freq: f32, db: f32) -> Self {
+        let a = 10_f32.powf(db / 40.);
+        let w0 = 2. * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / 2. * 2_f32.sqrt();
+        let sqrt_a = 2. * a.sqrt() * alpha;
+        Self::new(
+            [
+                a * ((a + 1.) - (a - 1.) * cos + sqrt_a),
+                2. * a * ((a - 1.) - (a + 1.) * cos),
+                a * ((a + 1.) - (a - 1.) * cos - sqrt_a),
+            ],
+            [
+                (a + 1.) + (a - 1.) * cos + sqrt_a,
+                -2. * ((a - 1.) + (a + 1.) * cos),
+                (a + 1.) + (a - 1.) * cos - sqrt_a,
+            ],
+        )
+    }
+
+    fn high_shelf(sample_rate: f32, freq: f32, db: f32) -> Self {
+        let a = 10_f32.powf(db / 40.);
+        let w0 = 2. * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / 2. * 2_f32.sqrt();
+        let sqrt_a = 2. * a.sqrt() * alpha;
+        Self::new(
+            [
+                a * ((a + 1.) + (a - 1.) * cos + sqrt_a),
+                -2. * a * ((a - 1.) + (a + 1.) * cos),
+                a * ((a + 1.) + (a - 1.) * cos - sqrt_a),
+            ],
+            [
+                (a + 1.) - (a - 1.) * cos + sqrt_a,
+                2. * ((a - 1.) - (a + 1.) * cos),
+                (a + 1.) - (a - 1.) * cos - sqrt_a,
+            ],
+        )
+    }
+
+    fn peaking(sample_rate: f32, freq: f32, db: f32) -> Self {
+        let a = 10_f32.powf(db / 40.);
+        let w0 = 2. * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2. * EQ_Q);
+        Self::new(
+            [1. + alpha * a, -2. * cos, 1. - alpha * a],
+            [1. + alpha / a, -2. * cos, 1. - alpha / a],
+        )
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1
+            - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+
+    const fn reset(&mut self) {
+        self.x1 = 0.;
+        self.x2 = 0.;
+        self.y1 = 0.;
+        self.y2 = 0.;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Comb {
+    buffer: Vec<f32>,
+    pos: usize,
+    filter_state: f32,
+}
+impl Comb {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.; len.max(1)],
+            pos: 0,
+            filter_state: 0.,
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32) -> f32 {
+        let output = self.buffer[self.pos];
+        self.filter_state = output.mul_add(1. - COMB_DAMPING, self.filter_state * COMB_DAMPING);
+        self.buffer[self.pos] = self.filter_state.mul_add(feedback, input);
+        self.pos = (self.pos + 1) % self.buffer.len();
+        output
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Allpass {
+    buffer: Vec<f32>,
+    pos: usize,
+}
+impl Allpass {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.; len.max(1)],
+            pos: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let buffered = self.buffer[self.pos];
+        self.buffer[self.pos] = buffered.mul_add(ALLPASS_FEEDBACK, input);
+        self.pos = (self.pos + 1) % self.buffer.len();
+        buffered - input
+    }
+}
+
+/// Reverb of one channel
+#[derive(Debug, Clone)]
+struct Reverb {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+    feedback: f32,
+}
+impl Reverb {
+    fn new(sample_rate: u32, room_size: f32, spread: usize) -> Self {
+        let scale = |len: usize| (len + spread) * sample_rate as usize / 44100;
+        Self {
+            combs: COMB_TUNING
+                .iter()
+                .map(|len| Comb::new(scale(*len)))
+                .collect(),
+            allpasses: ALLPASS_TUNING
+                .iter()
+                .map(|len| Allpass::new(scale(*len)))
+                .collect(),
+            feedback: room_size.clamp(0., 1.).mul_add(0.28, 0.7),
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let input = input * REVERB_INPUT_GAIN;
+        let mut output = self
+            .combs
+            .iter_mut()
+            .map(|comb| comb.process(input, self.feedback))
+            .sum();
+        for allpass in &mut self.allpasses {
+            output = allpass.process(output);
+        }
+        output
+    }
+
+    fn reset(&mut self) {
+        for comb in &mut self.combs {
+            comb.buffer.fill(0.);
+            comb.filter_state = 0.;
+        }
+        for allpass in &mut self.allpasses {
+            allpass.buffer.fill(0.);
+        }
+    }
+}
+
+/// Processing state of one channel
+#[derive(Debug, Clone)]
+struct ChannelFx {
+    eq: [Biquad; EQ_BANDS],
+    reverb: Option<Reverb>,
+}
+
+/// Source wrapper that applies an audio profile. Neutral profiles pass samples through.
+pub struct Effects<S> {
+    source: S,
+    /// None when the profile is neutral
+    channels: Option<Vec<ChannelFx>>,
+    /// Channel of the next sample
+    channel: usize,
+    gain: f32,
+    reverb_mix: f32,
+}
+impl<S> Effects<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    pub fn new(source: S, profile: &AudioProfile) -> Self {
+        let channel_count = usize::from(source.channels().max(1));
+        let sample_rate = source.sample_rate();
+        let channels = (!profile.is_neutral()).then(|| {
+            (0..channel_count)
+                .map(|channel| {
+                    #[allow(clippy::cast_precision_loss)] // Sample rates are small
+                    let rate = sample_rate as f32;
+                    ChannelFx {
+                        eq: [
+                            Biquad::low_shelf(rate, EQ_FREQUENCIES[0], profile.eq[0]),
+                            Biquad::peaking(rate, EQ_FREQUENCIES[1], profile.eq[1]),
+                            Biquad::high_shelf(rate, EQ_FREQUENCIES[2], profile.eq[2]),
+                        ],
+                        reverb: (profile.reverb_mix > 0.).then(|| {
+                            Reverb::new(sample_rate, profile.room_size, channel * STEREO_SPREAD)
+                        }),
+                    }
+                })
+                .collect()
+        });
+        Self {
+            source,
+            channels,
+            channel: 0,
+            gain: db_to_gain(profile.gain),
+            reverb_mix: profile.reverb_mix.clamp(0., 1.),
+        }
+    }
+}
+
+impl<S> Iterator for Effects<S>
+where
+    S: Iterator<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        let Some(channels) = &mut self.channels else {
+            return Some(sample);
+        };
+        let channel_count = channels.len();
+        let fx = &mut channels[self.channel];
+        self.channel = (self.channel + 1) % channel_count;
+
+        let mut sample = sample * self.gain;
+        for band in &mut fx.eq {
+            sample = band.process(sample);
+        }
+        if let Some(reverb) = &mut fx.reverb {
+            let wet = reverb.process(sample);
+            sample = sample.mul_add(1. - self.reverb_mix, wet * self.reverb_mix);
+        }
+        Some(soft_limit(sample))
+    }
+}
+
+impl<S> rodio::Source for Effects<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.source.try_seek(pos)?;
+        self.channel = 0;
+        if let Some(channels) = &mut self.channels {
+            for fx in channels {
+                fx.eq.iter_mut().for_each(Biquad::reset);
+                if let Some(reverb) = &mut fx.reverb {
+                    reverb.reset();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::{buffer::SamplesBuffer, Source};
+
+    use super::*;
+
+    fn impulse() -> SamplesBuffer<f32> {
+        let mut samples = vec![0.; 44100];
+        samples[0] = 0.5;
+        samples[1] = 0.5;
+        SamplesBuffer::new(2, 44100, samples)
+    }
+
+    #[test]
+    fn test_neutral_passthrough() {
+        let dry: Vec<f32> = impulse().collect();
+        let processed: Vec<f32> = Effects::new(impulse(), &AudioProfile::default()).collect();
+        assert_eq!(dry, processed);
+    }
+
+    #[test]
+    fn test_gain() {
+        let profile = AudioProfile {
+            gain: -6.,
+            ..Default::default()
+        };
+        let processed: Vec<f32> = Effects::new(impulse(), &profile).collect();
+        let expected = 0.5 * db_to_gain(-6.);
+        assert!((processed[0] - expected).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_reverb_tail() {
+        let profile = AudioProfile {
+            reverb_mix: 0.5,
+            ..Default::default()
+        };
+        let effects = Effects::new(impulse(), &profile);
+        assert_eq!(effects.channels(), 2);
+        let processed: Vec<f32> = effects.collect();
+        // Silence after the impulse is filled with reverb
+        assert!(processed[4000..].iter().any(|sample| sample.abs() > 1e-5));
+    }
+
+    #[test]
+    fn test_eq_boost() {
+        let profile = AudioProfile {
+            eq: [12., 0., 0.],
+            ..Default::default()
+        };
+        // Constant signal is all bass
+        let samples = vec![0.1_f32; 8000];
+        let processed: Vec<f32> =
+            Effects::new(SamplesBuffer::new(2, 44100, samples), &profile).collect();
+        assert!(processed[7999] > 0.3);
+    }
+}
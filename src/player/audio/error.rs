@@ -2,6 +2,8 @@ use midi_msg::MidiFileParseError;
 use rustysynth::SoundFontError;
 use std::{fmt, path::PathBuf};
 
+use crate::player::playlist::font_meta::sf3::Sf3Error;
+
 #[derive(Debug)]
 pub enum PlayerError {
     NoFont,
@@ -17,6 +19,9 @@ pub enum PlayerError {
     InvalidFont {
         source: SoundFontError,
     },
+    InvalidCompressedFont {
+        source: Sf3Error,
+    },
     InvalidMidi {
         source: MidiFileParseError,
     },
@@ -36,6 +41,9 @@ impl fmt::Display for PlayerError {
             Self::InvalidFont { source } => {
                 write!(f, "Invalid soundfont: {source}")
             }
+            Self::InvalidCompressedFont { source } => {
+                write!(f, "Invalid soundfont: {source}")
+            }
             Self::InvalidMidi { source } => {
                 write!(f, "Invalid midi file: {source}")
             }
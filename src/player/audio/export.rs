@@ -0,0 +1,557 @@
+//! Offline export: render a song into an audio file and a piano roll PNG frame sequence.
+//!
+//! The two can be joined into a video with e.g.
+//! `ffmpeg -framerate 30 -i song_%06d.png -i song.wav -pix_fmt yuv420p song.mp4`
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::bail;
+use image::{Rgb, RgbImage};
+use midi_msg::{ChannelModeMsg, ChannelVoiceMsg, MidiFile, MidiMsg};
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::vorbis::VorbisWriter;
+use super::{
+    activity::ChannelActivity,
+    effects::Effects,
+    flac::FlacWriter,
+    load_midifile, load_soundfont,
+    midisequencer::{MidiSequencer, MidiSink},
+    midisource::MidiSource,
+};
+use crate::player::{
+    audio_profile::AudioProfile,
+    channel_colors::ChannelColors,
+    playlist::{font_meta::FontMeta, midi_meta::MidiMeta},
+    program_lock::{DrumKit, ProgramLocks},
+};
+
+/// Seconds of music visible in a frame
+const VISIBLE_SECONDS: f32 = 5.;
+/// Playhead position from the left edge, as a fraction of frame width
+const PLAYHEAD_POS: f32 = 0.25;
+const BACKGROUND: Rgb<u8> = Rgb([24, 24, 28]);
+const PLAYHEAD: Rgb<u8> = Rgb([200, 200, 200]);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AudioFormat {
+    /// 32-bit float
+    #[default]
+    Wav,
+    Flac16,
+    Flac24,
+    Ogg {
+        /// 0..=`OGG_QUALITY_MAX`, like oggenc
+        quality: u8,
+    },
+}
+impl AudioFormat {
+    pub const OGG_QUALITY_MAX: u8 = 10;
+    pub const ALL: [Self; 4] = [
+        Self::Wav,
+        Self::Flac16,
+        Self::Flac24,
+        Self::Ogg { quality: 6 },
+    ];
+
+    #[must_use]
+    pub const fn get_name(self) -> &'static str {
+        match self {
+            Self::Wav => "WAV (32-bit float)",
+            Self::Flac16 => "FLAC (16-bit)",
+            Self::Flac24 => "FLAC (24-bit)",
+            Self::Ogg { .. } => "OGG Vorbis",
+        }
+    }
+    #[must_use]
+    pub const fn get_extension(self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            Self::Flac16 | Self::Flac24 => "flac",
+            Self::Ogg { .. } => "ogg",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ExportSettings {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub audio_format: AudioFormat,
+}
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            fps: 30,
+            audio_format: AudioFormat::default(),
+        }
+    }
+}
+
+/// Audio file being written
+enum AudioWriter {
+    Wav(hound::WavWriter<BufWriter<File>>),
+    Flac(Box<FlacWriter<BufWriter<File>>>),
+    #[cfg(not(target_arch = "wasm32"))]
+    Ogg(Box<VorbisWriter<BufWriter<File>>>),
+}
+impl AudioWriter {
+    fn create(
+        path: &Path,
+        format: AudioFormat,
+        channels: u16,
+        sample_rate: u32,
+        tags: &[(&str, String)],
+    ) -> anyhow::Result<Self> {
+        let bits = match format {
+            AudioFormat::Wav => {
+                let spec = hound::WavSpec {
+                    channels,
+                    sample_rate,
+                    bits_per_sample: 32,
+                    sample_format: hound::SampleFormat::Float,
+                };
+                return Ok(Self::Wav(hound::WavWriter::create(path, spec)?));
+            }
+            AudioFormat::Flac16 => 16,
+            AudioFormat::Flac24 => 24,
+            #[cfg(target_arch = "wasm32")]
+            AudioFormat::Ogg { .. } => bail!("OGG export isn't available in the web build"),
+            #[cfg(not(target_arch = "wasm32"))]
+            AudioFormat::Ogg { quality } => {
+                let file = BufWriter::new(File::create(path)?);
+                let quality = f32::from(quality.min(AudioFormat::OGG_QUALITY_MAX))
+                    / f32::from(AudioFormat::OGG_QUALITY_MAX);
+                return Ok(Self::Ogg(Box::new(VorbisWriter::new(
+                    file,
+                    channels,
+                    sample_rate,
+                    quality,
+                    tags,
+                )?)));
+            }
+        };
+        let file = BufWriter::new(File::create(path)?);
+        Ok(Self::Flac(Box::new(FlacWriter::new(
+            file,
+            channels,
+            sample_rate,
+            bits,
+            tags,
+        )?)))
+    }
+    fn write_sample(&mut self, sample: f32) -> anyhow::Result<()> {
+        match self {
+            Self::Wav(writer) => writer.write_sample(sample)?,
+            Self::Flac(writer) => writer.write_sample(sample)?,
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Ogg(writer) => writer.write_sample(sample)?,
+        }
+        Ok(())
+    }
+    fn finalize(self) -> anyhow::Result<()> {
+        match self {
+            Self::Wav(writer) => writer.finalize()?,
+            Self::Flac(writer) => writer.finalize()?,
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Ogg(writer) => {
+                writer.finalize()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Everything needed to render a song without the player.
+pub struct ExportSource {
+    pub soundfont: FontMeta,
+    pub song: MidiMeta,
+    pub program_locks: ProgramLocks,
+    pub drum_kit: Option<DrumKit>,
+    pub audio_profile: AudioProfile,
+    pub colors: ChannelColors,
+}
+
+/// Export running in the background.
+pub struct ExportJob {
+    /// Progress 0..=1000
+    progress: Arc<AtomicU32>,
+    cancel: Arc<AtomicBool>,
+    result: Receiver<anyhow::Result<PathBuf>>,
+}
+impl ExportJob {
+    #[must_use]
+    pub fn start(source: ExportSource, out_dir: PathBuf, settings: ExportSettings) -> Self {
+        let progress = Arc::new(AtomicU32::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (result_tx, result_rx) = mpsc::channel();
+        let job_progress = progress.clone();
+        let job_cancel = cancel.clone();
+        let thread_result = result_tx.clone();
+        if let Err(e) = thread::Builder::new().spawn(move || {
+            let result = export(&source, &out_dir, settings, &job_progress, &job_cancel);
+            let _ = thread_result.send(result.map(|()| out_dir));
+        }) {
+            let _ = result_tx.send(Err(anyhow::anyhow!("Couldn't start export: {e}")));
+        }
+        Self {
+            progress,
+            cancel,
+            result: result_rx,
+        }
+    }
+
+    /// Progress 0.0..=1.0
+    #[must_use]
+    pub fn get_progress(&self) -> f32 {
+        self.progress.load(Ordering::Relaxed) as f32 / 1000.
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    /// Output directory or error, once finished.
+    #[must_use]
+    pub fn poll(&self) -> Option<anyhow::Result<PathBuf>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(anyhow::anyhow!("Export stopped"))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RollNote {
+    start: f32,
+    end: f32,
+    key: u8,
+    channel: usize,
+}
+
+fn export(
+    source: &ExportSource,
+    out_dir: &Path,
+    settings: ExportSettings,
+    progress: &AtomicU32,
+    cancel: &AtomicBool,
+) -> anyhow::Result<()> {
+    let name = source
+        .song
+        .get_path()
+        .file_stem()
+        .map_or_else(|| "song".into(), |stem| stem.to_string_lossy().to_string());
+    let soundfont = load_soundfont(&source.soundfont, &mut None)?;
+
+    // Audio: first half of progress
+    let midi_source = MidiSource::new(
+        &soundfont,
+        load_midifile(&source.song)?,
+        source.program_locks,
+        source.drum_kit,
+        ChannelActivity::default(),
+        source.audio_profile.synth_effects,
+    );
+    let song_len = midi_source.get_song_length();
+    let channels = midi_source.channels();
+    let sample_rate = midi_source.sample_rate();
+    let total_samples = song_len.as_secs_f64() * f64::from(sample_rate * 2);
+    let format = settings.audio_format;
+    let tags = [
+        ("TITLE", name.clone()),
+        (
+            "COMMENT",
+            format!("Soundfont: {}", source.soundfont.get_name()),
+        ),
+    ];
+    let mut writer = AudioWriter::create(
+        &out_dir.join(format!("{name}.{}", format.get_extension())),
+        format,
+        channels,
+        sample_rate,
+        &tags,
+    )?;
+    let mut written = 0;
+    for (index, sample) in Effects::new(midi_source, &source.audio_profile).enumerate() {
+        writer.write_sample(sample)?;
+        written += 1;
+        if index % 65536 == 0 {
+            if cancel.load(Ordering::Relaxed) {
+                bail!("Export cancelled");
+            }
+            let done = (index as f64 / total_samples).min(1.);
+            progress.store((done * 500.) as u32, Ordering::Relaxed);
+        }
+    }
+    // The source may end between the left and right sample
+    if written % usize::from(channels) != 0 {
+        writer.write_sample(0_f32)?;
+    }
+    writer.finalize()?;
+
+    // Frames: second half
+    let notes = collect_notes(load_midifile(&source.song)?, sample_rate);
+    let keys = get_key_range(&notes);
+    let frame_count = (song_len.as_secs_f32() * settings.fps as f32).ceil() as u32;
+    for frame in 0..frame_count {
+        if cancel.load(Ordering::Relaxed) {
+            bail!("Export cancelled");
+        }
+        let time = frame as f32 / settings.fps as f32;
+        draw_frame(&notes, &keys, time, &source.colors, settings)
+            .save(out_dir.join(format!("{name}_{frame:06}.png")))?;
+        progress.store(500 + frame * 500 / frame_count, Ordering::Relaxed);
+    }
+    progress.store(1000, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Records note spans instead of making sound.
+#[derive(Default)]
+struct NoteRecorder {
+    time: f32,
+    held: HashMap<(usize, u8), f32>,
+    notes: Vec<RollNote>,
+}
+impl NoteRecorder {
+    fn note_off(&mut self, channel: usize, key: u8) {
+        if let Some(start) = self.held.remove(&(channel, key)) {
+            self.notes.push(RollNote {
+                start,
+                end: self.time,
+                key,
+                channel,
+            });
+        }
+    }
+    fn all_notes_off(&mut self, channel: Option<usize>) {
+        let keys: Vec<_> = self
+            .held
+            .keys()
+            .filter(|(ch, _)| channel.is_none_or(|channel| *ch == channel))
+            .copied()
+            .collect();
+        for (channel, key) in keys {
+            self.note_off(channel, key);
+        }
+    }
+}
+impl MidiSink for NoteRecorder {
+    fn receive_midi(&mut self, msg: &MidiMsg) -> Result<(), ()> {
+        match msg {
+            MidiMsg::ChannelVoice { channel, msg }
+            | MidiMsg::RunningChannelVoice { channel, msg } => {
+                let channel = *channel as usize;
+                match msg {
+                    ChannelVoiceMsg::NoteOn { note, velocity } if *velocity > 0 => {
+                        // Retriggered note ends the previous one
+                        self.note_off(channel, *note);
+                        self.held.insert((channel, *note), self.time);
+                    }
+                    ChannelVoiceMsg::NoteOn { note, .. }
+                    | ChannelVoiceMsg::NoteOff { note, .. } => {
+                        self.note_off(channel, *note);
+                    }
+                    _ => (),
+                }
+            }
+            MidiMsg::ChannelMode {
+                channel,
+                msg: ChannelModeMsg::AllNotesOff | ChannelModeMsg::AllSoundOff,
+            }
+            | MidiMsg::RunningChannelMode {
+                channel,
+                msg: ChannelModeMsg::AllNotesOff | ChannelModeMsg::AllSoundOff,
+            } => self.all_notes_off(Some(*channel as usize)),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.all_notes_off(None);
+    }
+}
+
+/// Walk through the song at the audio sample rate and record every note.
+fn collect_notes(midifile: MidiFile, sample_rate: u32) -> Vec<RollNote> {
+    let delta_t = Duration::from_secs_f64(1. / f64::from(sample_rate));
+    let mut sequencer = MidiSequencer::new();
+    sequencer.play(midifile);
+    let mut recorder = NoteRecorder::default();
+    while !sequencer.end_of_sequence() {
+        recorder.time = sequencer.get_song_position().as_secs_f32();
+        sequencer.update_events(&mut recorder, delta_t);
+    }
+    recorder.time = sequencer.get_song_position().as_secs_f32();
+    recorder.reset();
+    recorder.notes.sort_by(|a, b| a.start.total_cmp(&b.start));
+    recorder.notes
+}
+
+/// Lowest and highest key of the song, with some room around
+fn get_key_range(notes: &[RollNote]) -> std::ops::RangeInclusive<u8> {
+    let low = notes.iter().map(|note| note.key).min().unwrap_or(48);
+    let high = notes.iter().map(|note| note.key).max().unwrap_or(72);
+    low.saturating_sub(2)..=high.saturating_add(2).min(127)
+}
+
+fn draw_frame(
+    notes: &[RollNote],
+    keys: &std::ops::RangeInclusive<u8>,
+    time: f32,
+    colors: &ChannelColors,
+    settings: ExportSettings,
+) -> RgbImage {
+    let mut image = RgbImage::from_pixel(settings.width, settings.height, BACKGROUND);
+    let width = settings.width as f32;
+    let height = settings.height as f32;
+    let row_height = height / f32::from(keys.end() - keys.start() + 1);
+    let pixels_per_second = width / VISIBLE_SECONDS;
+    let playhead_x = width * PLAYHEAD_POS;
+    let view_start = time - playhead_x / pixels_per_second;
+    let view_end = view_start + VISIBLE_SECONDS;
+
+    for note in notes {
+        if note.start > view_end {
+            break;
+        }
+        if note.end < view_start {
+            continue;
+        }
+        let left = (note.start - view_start) * pixels_per_second;
+        let right = ((note.end - view_start) * pixels_per_second).max(left + 2.);
+        let bottom = f32::from(note.key - keys.start()).mul_add(-row_height, height);
+        let top = bottom - row_height;
+        let sounding = note.start <= time && time < note.end;
+        let color = note_color(colors, note.channel, sounding);
+        fill_rect(&mut image, left, top + 1., right, bottom - 1., color);
+    }
+    fill_rect(
+        &mut image,
+        playhead_x,
+        0.,
+        playhead_x + 2.,
+        height,
+        PLAYHEAD,
+    );
+    image
+}
+
+/// Channel color, lighter while the note is sounding
+const fn note_color(colors: &ChannelColors, channel: usize, sounding: bool) -> Rgb<u8> {
+    let [r, g, b] = colors.get_color(channel);
+    if sounding {
+        Rgb([
+            r.saturating_add(40),
+            g.saturating_add(40),
+            b.saturating_add(40),
+        ])
+    } else {
+        Rgb([r, g, b])
+    }
+}
+
+fn fill_rect(image: &mut RgbImage, left: f32, top: f32, right: f32, bottom: f32, color: Rgb<u8>) {
+    let x_range = left.max(0.) as u32..(right.max(0.) as u32).min(image.width());
+    let y_range = top.max(0.) as u32..(bottom.max(0.) as u32).min(image.height());
+    for y in y_range {
+        for x in x_range.clone() {
+            image.put_pixel(x, y, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use midi_msg::Channel;
+
+    use super::*;
+
+    fn voice(msg: ChannelVoiceMsg) -> MidiMsg {
+        MidiMsg::ChannelVoice {
+            channel: Channel::Ch2,
+            msg,
+        }
+    }
+
+    #[test]
+    fn test_note_recorder() {
+        let mut recorder = NoteRecorder::default();
+        let _ = recorder.receive_midi(&voice(ChannelVoiceMsg::NoteOn {
+            note: 60,
+            velocity: 100,
+        }));
+        recorder.time = 1.;
+        let _ = recorder.receive_midi(&voice(ChannelVoiceMsg::NoteOn {
+            note: 64,
+            velocity: 100,
+        }));
+        recorder.time = 2.;
+        // Velocity 0 is a note off
+        let _ = recorder.receive_midi(&voice(ChannelVoiceMsg::NoteOn {
+            note: 60,
+            velocity: 0,
+        }));
+        recorder.time = 3.;
+        recorder.reset();
+
+        assert_eq!(
+            recorder.notes,
+            vec![
+                RollNote {
+                    start: 0.,
+                    end: 2.,
+                    key: 60,
+                    channel: 1
+                },
+                RollNote {
+                    start: 1.,
+                    end: 3.,
+                    key: 64,
+                    channel: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_draw_frame() {
+        let settings = ExportSettings {
+            width: 100,
+            height: 50,
+            ..Default::default()
+        };
+        let notes = [RollNote {
+            start: 0.,
+            end: 10.,
+            key: 60,
+            channel: 0,
+        }];
+        let keys = get_key_range(&notes);
+        let colors = ChannelColors::default();
+        let image = draw_frame(&notes, &keys, 1., &colors, settings);
+        assert_eq!(image.dimensions(), (100, 50));
+        // Middle of the note row, right of the playhead
+        assert_eq!(*image.get_pixel(80, 25), note_color(&colors, 0, true));
+        assert_eq!(*image.get_pixel(80, 2), BACKGROUND);
+    }
+}
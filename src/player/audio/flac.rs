@@ -0,0 +1,248 @@
+//! FLAC export on top of `flacenc`. Frames are encoded as the samples come in, so a long song
+//! isn't held in memory.
+//!
+//! STREAMINFO is rewritten with the sample count and checksum on `finalize`, so the output has to
+//! be seekable.
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+use flacenc::{
+    bitsink::ByteSink,
+    component::{BitRepr, MetadataBlockData, Stream, StreamInfo},
+    config, encode_fixed_size_frame,
+    error::{Verified, Verify},
+    source::{Fill, FrameBuf},
+};
+use md5::{Digest, Md5};
+
+/// Samples per channel in a frame
+const BLOCK_SIZE: usize = 4096;
+/// Metadata block type of a Vorbis comment
+const VORBIS_COMMENT: u8 = 4;
+const VENDOR: &str = "SfontPlayer";
+
+pub struct FlacWriter<W: Write + Seek> {
+    out: W,
+    config: Verified<config::Encoder>,
+    stream_info: StreamInfo,
+    comment: Vec<u8>,
+    bits: u8,
+    /// Interleaved samples of the frame being collected
+    block: Vec<i32>,
+    frame_buf: FrameBuf,
+    frame_number: usize,
+    total_samples: usize,
+    md5: Md5,
+}
+
+impl<W: Write + Seek> FlacWriter<W> {
+    /// Writes the stream header right away. `tags` go into the Vorbis comment, e.g. `TITLE`.
+    pub fn new(
+        out: W,
+        channels: u16,
+        sample_rate: u32,
+        bits: u8,
+        tags: &[(&str, String)],
+    ) -> io::Result<Self> {
+        if !(1..=8).contains(&channels) || !matches!(bits, 16 | 24) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unsupported FLAC format",
+            ));
+        }
+        let channels = usize::from(channels);
+        let mut stream_info = StreamInfo::new(sample_rate as usize, channels, usize::from(bits))
+            .map_err(io::Error::other)?;
+        // Until the frames are in. The smallest frame size stays 0, unknown.
+        stream_info
+            .set_block_sizes(BLOCK_SIZE, BLOCK_SIZE)
+            .map_err(io::Error::other)?;
+        stream_info
+            .set_frame_sizes(0, 0)
+            .map_err(io::Error::other)?;
+
+        let mut comment = vec![];
+        push_string(&mut comment, VENDOR)?;
+        comment.extend_from_slice(&len_u32(tags.len())?.to_le_bytes());
+        for (key, value) in tags {
+            push_string(&mut comment, &format!("{key}={value}"))?;
+        }
+
+        let mut writer = Self {
+            out,
+            config: config::Encoder::default()
+                .into_verified()
+                .map_err(|(_, e)| io::Error::other(e))?,
+            stream_info,
+            comment,
+            bits,
+            block: Vec::with_capacity(BLOCK_SIZE * channels),
+            frame_buf: FrameBuf::with_size(channels, BLOCK_SIZE).map_err(io::Error::other)?,
+            frame_number: 0,
+            total_samples: 0,
+            md5: Md5::new(),
+        };
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    /// Add one interleaved sample, -1.0..=1.0
+    pub fn write_sample(&mut self, sample: f32) -> io::Result<()> {
+        let scale = ((1_i32 << (self.bits - 1)) - 1) as f32;
+        self.block
+            .push((sample.clamp(-1., 1.) * scale).round() as i32);
+        if self.block.len() == BLOCK_SIZE * self.frame_buf.channels() {
+            self.write_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Write the last frame and the final STREAMINFO.
+    pub fn finalize(mut self) -> io::Result<()> {
+        let channels = self.frame_buf.channels();
+        // The source may end between channels
+        while !self.block.len().is_multiple_of(channels) {
+            self.block.push(0);
+        }
+        if !self.block.is_empty() {
+            self.frame_buf.resize(self.block.len() / channels);
+            self.write_frame()?;
+        }
+        // Only the last frame may be shorter, that doesn't make the block size variable
+        self.stream_info
+            .set_block_sizes(BLOCK_SIZE, BLOCK_SIZE)
+            .map_err(io::Error::other)?;
+        self.stream_info.set_total_samples(self.total_samples);
+        self.stream_info
+            .set_md5_digest(&self.md5.finalize_reset().into());
+        self.out.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.out.flush()
+    }
+
+    /// "fLaC", STREAMINFO and the Vorbis comment. Always the same length.
+    fn write_header(&mut self) -> io::Result<()> {
+        let mut stream = Stream::with_stream_info(self.stream_info.clone());
+        stream.add_metadata_block(
+            MetadataBlockData::new_unknown(VORBIS_COMMENT, &self.comment)
+                .map_err(io::Error::other)?,
+        );
+        let mut sink = ByteSink::new();
+        stream
+            .write(&mut sink)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        self.out.write_all(sink.as_slice())
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        let bytes = usize::from(self.bits / 8);
+        for sample in &self.block {
+            self.md5.update(&sample.to_le_bytes()[..bytes]);
+        }
+        self.frame_buf
+            .fill_interleaved(&self.block)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let frame = encode_fixed_size_frame(
+            &self.config,
+            &self.frame_buf,
+            self.frame_number,
+            &self.stream_info,
+        )
+        .map_err(|e| io::Error::other(format!("{e:?}")))?;
+        let mut sink = ByteSink::new();
+        frame
+            .write(&mut sink)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        self.out.write_all(sink.as_slice())?;
+
+        self.stream_info.update_frame_info(&frame);
+        self.total_samples += self.frame_buf.size();
+        self.frame_number += 1;
+        self.block.clear();
+        Ok(())
+    }
+}
+
+fn len_u32(len: usize) -> io::Result<u32> {
+    u32::try_from(len).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too long"))
+}
+
+fn push_string(out: &mut Vec<u8>, string: &str) -> io::Result<()> {
+    out.extend_from_slice(&len_u32(string.len())?.to_le_bytes());
+    out.extend_from_slice(string.as_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Encode, then decode with claxon, which checks the CRCs
+    fn roundtrip(bits: u8) {
+        let input: Vec<f32> = (0..BLOCK_SIZE * 2 + 100)
+            .flat_map(|i| {
+                let left = (i as f32 * 0.01).sin() * 0.8;
+                // Noise-like, ends up verbatim or order 0
+                let right = ((i * 7919 % 1000) as f32 / 500.) - 1.;
+                [left, right]
+            })
+            // Clipped and silent frames
+            .chain((0..BLOCK_SIZE).flat_map(|i| [if i % 2 == 0 { 2. } else { -2. }, 0.]))
+            .collect();
+        let mut out = Cursor::new(vec![]);
+        let tags = [("TITLE", "Song".into()), ("COMMENT", "Ääni".into())];
+        let mut writer = FlacWriter::new(&mut out, 2, 44100, bits, &tags).expect("valid format");
+        for &sample in &input {
+            writer.write_sample(sample).expect("write");
+        }
+        writer.finalize().expect("finalize");
+
+        out.set_position(0);
+        let mut reader = claxon::FlacReader::new(out).expect("valid FLAC");
+        let info = reader.streaminfo();
+        assert_eq!(info.samples, Some(input.len() as u64 / 2));
+        assert_eq!(info.channels, 2);
+        assert_eq!(info.sample_rate, 44100);
+        assert_eq!(info.bits_per_sample, u32::from(bits));
+        assert_eq!(reader.get_tag("TITLE").collect::<Vec<_>>(), ["Song"]);
+        assert_eq!(reader.get_tag("COMMENT").collect::<Vec<_>>(), ["Ääni"]);
+
+        let scale = ((1 << (bits - 1)) - 1) as f32;
+        let expected: Vec<i32> = input
+            .iter()
+            .map(|sample| (sample.clamp(-1., 1.) * scale).round() as i32)
+            .collect();
+        let decoded: Vec<i32> = reader.samples().map(|sample| sample.unwrap()).collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_roundtrip_16() {
+        roundtrip(16);
+    }
+
+    #[test]
+    fn test_roundtrip_24() {
+        roundtrip(24);
+    }
+
+    #[test]
+    fn test_short_and_empty() {
+        for len in [0, 1, 15, BLOCK_SIZE] {
+            let mut out = Cursor::new(vec![]);
+            let mut writer = FlacWriter::new(&mut out, 1, 44100, 16, &[]).expect("valid format");
+            for _ in 0..len {
+                writer.write_sample(0.25).expect("write");
+            }
+            writer.finalize().expect("finalize");
+
+            out.set_position(0);
+            let mut reader = claxon::FlacReader::new(out).expect("valid FLAC");
+            // No samples reads as unknown
+            assert_eq!(reader.streaminfo().samples.unwrap_or(0), len as u64);
+            assert_eq!(reader.samples().count(), len);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! The next song is appended to the sink before the current one ends, so there's no gap.
+//!
+//! A source can't be taken back out of the sink. If the next song changes in the meantime, the
+//! queued source is cancelled instead, and it ends without playing anything.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use super::{activity::ChannelActivity, SongInfo};
+use crate::player::playlist::{font_meta::FontMeta, midi_meta::MidiMeta};
+
+/// Song waiting in the sink after the current one
+pub struct NextSong {
+    pub song: MidiMeta,
+    pub font: FontMeta,
+    pub info: SongInfo,
+    /// Its own, so the playing song's activity isn't cleared early
+    pub activity: ChannelActivity,
+    cancelled: Arc<AtomicBool>,
+}
+impl NextSong {
+    /// Also returns the flag for its source
+    pub fn new(
+        song: MidiMeta,
+        font: FontMeta,
+        info: SongInfo,
+        activity: ChannelActivity,
+    ) -> (Self, Arc<AtomicBool>) {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let next = Self {
+            song,
+            font,
+            info,
+            activity,
+            cancelled: Arc::clone(&cancelled),
+        };
+        (next, cancelled)
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Source wrapper that ends right away once cancelled
+pub struct Cancellable<S> {
+    source: S,
+    cancelled: Arc<AtomicBool>,
+}
+impl<S> Cancellable<S> {
+    pub const fn new(source: S, cancelled: Arc<AtomicBool>) -> Self {
+        Self { source, cancelled }
+    }
+}
+
+impl<S> Iterator for Cancellable<S>
+where
+    S: Iterator<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.source.next()
+    }
+}
+
+impl<S> rodio::Source for Cancellable<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Some(0);
+        }
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.source.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::{buffer::SamplesBuffer, Source};
+
+    #[test]
+    fn test_cancellable() {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut source = Cancellable::new(
+            SamplesBuffer::new(2, 44100, vec![0.5; 8]),
+            Arc::clone(&cancelled),
+        );
+        assert_eq!(source.next(), Some(0.5));
+        cancelled.store(true, Ordering::Relaxed);
+        assert_eq!(source.current_frame_len(), Some(0));
+        assert_eq!(source.next(), None);
+    }
+}
@@ -0,0 +1,160 @@
+//! Gain above 100% with a soft limiter, so boosted audio bends instead of clipping.
+//!
+
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use super::clip_meter::{ClipMeter, PeakDetector};
+
+/// Samples below this are passed through as is.
+const THRESHOLD: f32 = 0.8;
+
+/// Gain shared between the audio player and the playing source. Stored as f32 bits.
+#[derive(Debug, Clone)]
+pub struct SharedGain(Arc<AtomicU32>);
+impl Default for SharedGain {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU32::new(1.0_f32.to_bits())))
+    }
+}
+impl SharedGain {
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+    pub fn set(&self, gain: f32) {
+        self.0.store(gain.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Compress everything above `THRESHOLD` smoothly towards 1.0.
+pub fn soft_limit(sample: f32) -> f32 {
+    let magnitude = sample.abs();
+    if magnitude <= THRESHOLD {
+        return sample;
+    }
+    let headroom = 1.0 - THRESHOLD;
+    let limited = THRESHOLD + headroom * ((magnitude - THRESHOLD) / headroom).tanh();
+    limited.copysign(sample)
+}
+
+/// Source wrapper that applies gain. The limiter is only engaged when gain is above 1.0, or
+/// when the clip meter asks for it.
+pub struct Limiter<S> {
+    source: S,
+    gain: SharedGain,
+    /// Measures the final output
+    detector: Option<PeakDetector>,
+}
+impl<S> Limiter<S> {
+    pub const fn new(source: S, gain: SharedGain) -> Self {
+        Self {
+            source,
+            gain,
+            detector: None,
+        }
+    }
+    /// Report clipping of the output
+    pub fn with_clip_meter(mut self, meter: ClipMeter, channels: u16) -> Self {
+        self.detector = Some(PeakDetector::new(meter, channels));
+        self
+    }
+}
+
+impl<S> Iterator for Limiter<S>
+where
+    S: Iterator<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        let gain = self.gain.get();
+        let Some(detector) = &mut self.detector else {
+            if gain <= 1.0 {
+                return Some(sample);
+            }
+            return Some(soft_limit(sample * gain));
+        };
+        let sample = if gain > 1.0 || detector.is_limiting() {
+            soft_limit(sample * gain)
+        } else {
+            sample
+        };
+        detector.process(sample);
+        Some(sample)
+    }
+}
+
+impl<S> rodio::Source for Limiter<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.source.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soft_limit() {
+        assert!((soft_limit(0.5) - 0.5).abs() < f32::EPSILON);
+        assert!((soft_limit(-0.5) + 0.5).abs() < f32::EPSILON);
+        assert!(soft_limit(0.9) < 0.9);
+        assert!(soft_limit(0.9) > THRESHOLD);
+        assert!(soft_limit(10.0) <= 1.0);
+        assert!(soft_limit(-10.0) >= -1.0);
+        assert!(soft_limit(2.0) > soft_limit(1.0));
+    }
+
+    #[test]
+    fn test_gain() {
+        let gain = SharedGain::default();
+        let samples = vec![0.1_f32, 0.5, -0.5];
+        let unity: Vec<f32> = Limiter::new(samples.clone().into_iter(), gain.clone()).collect();
+        assert_eq!(unity, samples);
+
+        gain.set(2.0);
+        let boosted: Vec<f32> = Limiter::new(samples.into_iter(), gain).collect();
+        assert!((boosted[0] - 0.2).abs() < f32::EPSILON);
+        assert!(boosted[1] < 1.0 && boosted[1] > THRESHOLD);
+        assert!(boosted[2] > -1.0 && boosted[2] < -THRESHOLD);
+    }
+
+    #[test]
+    fn test_auto_limit() {
+        let meter = ClipMeter::default();
+        meter.set_auto_limit(true);
+        let samples = vec![1.5_f32, 1.5];
+        let limited: Vec<f32> = Limiter::new(samples.into_iter(), SharedGain::default())
+            .with_clip_meter(meter.clone(), 1)
+            .collect();
+        // The first clip gets through, then the limiter is engaged.
+        assert!((limited[0] - 1.5).abs() < f32::EPSILON);
+        assert!(limited[1] <= 1.0);
+        assert!(meter.is_limiting());
+    }
+}
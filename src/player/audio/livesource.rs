@@ -0,0 +1,133 @@
+//! Audio source that plays MIDI messages as they arrive, e.g. from a keyboard on the input port.
+//!
+//! It never ends on its own: it plays silence until the next message. It stops when every
+//! sender is gone.
+
+use std::{
+    sync::{
+        mpsc::{Receiver, TryRecvError},
+        Arc,
+    },
+    time::Duration,
+};
+
+use midi_msg::MidiMsg;
+use rustysynth::{SoundFont, Synthesizer};
+
+use super::{
+    midisequencer::MidiSink,
+    midisource::{CHANNELS, SAMPLERATE},
+    midisynth::{new_synthesizer, render_frame},
+};
+
+/// What the live source is told
+pub enum LiveMessage {
+    /// Raw MIDI message
+    Midi(Vec<u8>),
+    /// Play with another soundfont from now on
+    Font {
+        soundfont: Arc<SoundFont>,
+        synth_effects: bool,
+    },
+}
+
+pub struct LiveSource {
+    synthesizer: Synthesizer,
+    messages: Receiver<LiveMessage>,
+    /// R channel sample of the frame being played
+    cached_sample: Option<f32>,
+}
+impl LiveSource {
+    #[must_use]
+    pub fn new(
+        soundfont: &Arc<SoundFont>,
+        synth_effects: bool,
+        messages: Receiver<LiveMessage>,
+    ) -> Self {
+        Self {
+            synthesizer: new_synthesizer(soundfont, synth_effects),
+            messages,
+            cached_sample: None,
+        }
+    }
+
+    /// Apply waiting messages. False once every sender is gone.
+    fn receive(&mut self) -> bool {
+        loop {
+            match self.messages.try_recv() {
+                Ok(LiveMessage::Midi(raw)) => {
+                    if let Ok((msg, _)) = MidiMsg::from_midi(&raw) {
+                        let _ = self.synthesizer.receive_midi(&msg);
+                    }
+                }
+                Ok(LiveMessage::Font {
+                    soundfont,
+                    synth_effects,
+                }) => self.synthesizer = new_synthesizer(&soundfont, synth_effects),
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+    }
+}
+
+impl Iterator for LiveSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(right) = self.cached_sample.take() {
+            return Some(right);
+        }
+        if !self.receive() {
+            return None;
+        }
+        let (left, right) = render_frame(&mut self.synthesizer);
+        self.cached_sample = Some(right);
+        Some(left)
+    }
+}
+
+impl rodio::Source for LiveSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        CHANNELS
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLERATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, sync::mpsc};
+
+    use super::*;
+    use crate::player::playlist::font_meta::FontMeta;
+
+    #[test]
+    fn test_live_source() {
+        let font = FontMeta::builtin();
+        let bytes = font.read_bytes().unwrap();
+        let soundfont = Arc::new(SoundFont::new(&mut Cursor::new(bytes)).unwrap());
+        let (sender, receiver) = mpsc::channel();
+        let mut source = LiveSource::new(&soundfont, false, receiver);
+
+        // Silent until a note is played
+        let silence: Vec<f32> = source.by_ref().take(64).collect();
+        assert!(silence.iter().all(|&sample| sample == 0.));
+        sender.send(LiveMessage::Midi(vec![0x90, 60, 100])).unwrap();
+        let note: Vec<f32> = source.by_ref().take(4096).collect();
+        assert!(note.iter().any(|&sample| sample != 0.));
+
+        drop(sender);
+        assert_eq!(source.next(), None);
+    }
+}
@@ -0,0 +1,171 @@
+//! Karaoke lyrics: lyric meta events, split into lines of timed syllables.
+//!
+//! Lyric events are used when the song has them. Otherwise text events are, which is how
+//! .kar files store their lyrics. Lines break on "\r" or "\n" at the end of a syllable, and on
+//! the karaoke "/" (new line) and "\" (new paragraph) prefixes.
+
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Syllable {
+    pub time: Duration,
+    pub text: String,
+}
+
+/// Lyrics of a song. Lines aren't empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lyrics(Vec<Vec<Syllable>>);
+impl Lyrics {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(vec![])
+    }
+    #[must_use]
+    pub fn get_lines(&self) -> &[Vec<Syllable>] {
+        &self.0
+    }
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+    /// Line and syllable index of the last syllable sung by `time`, if any.
+    #[must_use]
+    pub fn find(&self, time: Duration) -> Option<(usize, usize)> {
+        let line = self
+            .0
+            .partition_point(|line| line[0].time <= time)
+            .checked_sub(1)?;
+        let syllable = self.0[line].partition_point(|syllable| syllable.time <= time) - 1;
+        Some((line, syllable))
+    }
+}
+
+/// Collects lyrics while walking through a song.
+#[derive(Default)]
+pub struct LyricCollector {
+    lyrics: Vec<Syllable>,
+    texts: Vec<Syllable>,
+}
+impl LyricCollector {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            lyrics: vec![],
+            texts: vec![],
+        }
+    }
+
+    /// Lyric meta event
+    pub fn lyric(&mut self, time: Duration, text: &str) {
+        self.lyrics.push(Syllable {
+            time,
+            text: text.to_owned(),
+        });
+    }
+
+    /// Text meta event
+    pub fn text(&mut self, time: Duration, text: &str) {
+        // Karaoke files keep their headers in "@" texts.
+        if text.starts_with('@') {
+            return;
+        }
+        self.texts.push(Syllable {
+            time,
+            text: text.to_owned(),
+        });
+    }
+
+    #[must_use]
+    pub fn finish(self) -> Lyrics {
+        let mut syllables = if self.lyrics.is_empty() {
+            self.texts
+        } else {
+            self.lyrics
+        };
+        syllables.sort_by_key(|syllable| syllable.time);
+
+        let mut lines = vec![];
+        let mut line = vec![];
+        for Syllable { time, text } in syllables {
+            let breaks_before = text.starts_with(['/', '\\']);
+            let breaks_after = text.ends_with(['\r', '\n']);
+            let text = text
+                .trim_start_matches(['/', '\\'])
+                .trim_end_matches(['\r', '\n']);
+            if breaks_before && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !text.is_empty() {
+                line.push(Syllable {
+                    time,
+                    text: text.to_owned(),
+                });
+            }
+            if breaks_after && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        Lyrics(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_texts(lyrics: &Lyrics) -> Vec<String> {
+        lyrics
+            .get_lines()
+            .iter()
+            .map(|line| line.iter().map(|syllable| syllable.text.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_lyric_lines() {
+        let mut collector = LyricCollector::new();
+        collector.lyric(Duration::from_secs(1), "Twin");
+        collector.lyric(Duration::from_secs(2), "kle ");
+        collector.lyric(Duration::from_secs(3), "star\r");
+        collector.lyric(Duration::from_secs(4), "How ");
+        collector.lyric(Duration::from_secs(5), "I\n");
+        // Ignored, since there are lyric events
+        collector.text(Duration::from_secs(4), "Title");
+        let lyrics = collector.finish();
+
+        assert_eq!(line_texts(&lyrics), vec!["Twinkle star", "How I"]);
+    }
+
+    #[test]
+    fn test_karaoke_text() {
+        let mut collector = LyricCollector::new();
+        collector.text(Duration::ZERO, "@TSong");
+        collector.text(Duration::from_secs(1), "\\Row ");
+        collector.text(Duration::from_secs(2), "row");
+        collector.text(Duration::from_secs(3), "/Gent");
+        collector.text(Duration::from_secs(4), "ly");
+        let lyrics = collector.finish();
+
+        assert_eq!(line_texts(&lyrics), vec!["Row row", "Gently"]);
+    }
+
+    #[test]
+    fn test_find_syllable() {
+        let mut collector = LyricCollector::new();
+        collector.lyric(Duration::from_secs(1), "a\r");
+        collector.lyric(Duration::from_secs(2), "b");
+        collector.lyric(Duration::from_secs(3), "c");
+        let lyrics = collector.finish();
+
+        assert_eq!(lyrics.find(Duration::ZERO), None);
+        assert_eq!(lyrics.find(Duration::from_millis(1500)), Some((0, 0)));
+        assert_eq!(lyrics.find(Duration::from_secs(2)), Some((1, 0)));
+        assert_eq!(lyrics.find(Duration::from_secs(9)), Some((1, 1)));
+    }
+}
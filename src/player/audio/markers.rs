@@ -0,0 +1,159 @@
+//! Song structure for the seek bar: tempo changes, marker meta events, and note density.
+//!
+
+use std::time::Duration;
+
+/// Tempo changes smaller than this (relative to the last marked tempo) are not marked.
+const MIN_TEMPO_CHANGE: f64 = 0.1;
+/// Gradual tempo changes would otherwise produce a dense row of markers.
+const MIN_TEMPO_MARKER_GAP: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerKind {
+    Tempo,
+    /// Marker or cue point meta event
+    Text,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SongMarker {
+    pub time: Duration,
+    pub kind: MarkerKind,
+    pub label: String,
+}
+
+/// Collects markers while walking through a song.
+pub struct MarkerCollector {
+    markers: Vec<SongMarker>,
+    /// Tempo at the last tempo marker, or at the start
+    marked_bpm: f64,
+    last_tempo_marker: Option<Duration>,
+}
+impl MarkerCollector {
+    #[must_use]
+    pub const fn new(initial_bpm: f64) -> Self {
+        Self {
+            markers: vec![],
+            marked_bpm: initial_bpm,
+            last_tempo_marker: None,
+        }
+    }
+
+    pub fn tempo(&mut self, time: Duration, bpm: f64) {
+        // Tempo set at the very start is just the song's tempo.
+        if time.is_zero() {
+            self.marked_bpm = bpm;
+            return;
+        }
+        if ((bpm - self.marked_bpm) / self.marked_bpm).abs() < MIN_TEMPO_CHANGE {
+            return;
+        }
+        if self
+            .last_tempo_marker
+            .is_some_and(|last| time.saturating_sub(last) < MIN_TEMPO_MARKER_GAP)
+        {
+            return;
+        }
+        self.marked_bpm = bpm;
+        self.last_tempo_marker = Some(time);
+        self.markers.push(SongMarker {
+            time,
+            kind: MarkerKind::Tempo,
+            label: format!("{bpm:.0} BPM"),
+        });
+    }
+
+    pub fn text(&mut self, time: Duration, label: &str) {
+        let label = label.trim();
+        if label.is_empty() {
+            return;
+        }
+        self.markers.push(SongMarker {
+            time,
+            kind: MarkerKind::Text,
+            label: label.to_owned(),
+        });
+    }
+
+    #[must_use]
+    pub fn finish(mut self) -> Vec<SongMarker> {
+        self.markers.sort_by_key(|marker| marker.time);
+        self.markers
+    }
+}
+
+/// Number of notes started during each second of a song
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NoteDensity(Vec<u32>);
+impl NoteDensity {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(vec![])
+    }
+    pub fn add_note(&mut self, time: Duration) {
+        let second = time.as_secs() as usize;
+        if self.0.len() <= second {
+            self.0.resize(second + 1, 0);
+        }
+        self.0[second] += 1;
+    }
+    /// Notes per second
+    #[must_use]
+    pub fn get_seconds(&self) -> &[u32] {
+        &self.0
+    }
+    #[must_use]
+    pub fn get_max(&self) -> u32 {
+        self.0.iter().copied().max().unwrap_or_default()
+    }
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_density() {
+        let mut density = NoteDensity::new();
+        density.add_note(Duration::from_millis(100));
+        density.add_note(Duration::from_millis(900));
+        density.add_note(Duration::from_millis(3500));
+        assert_eq!(density.get_seconds(), &[2, 0, 0, 1]);
+        assert_eq!(density.get_max(), 2);
+    }
+
+    #[test]
+    fn test_tempo_markers() {
+        let mut collector = MarkerCollector::new(120.);
+        collector.tempo(Duration::ZERO, 100.);
+        // Small change
+        collector.tempo(Duration::from_secs(10), 105.);
+        collector.tempo(Duration::from_secs(20), 140.);
+        // Too close to previous
+        collector.tempo(Duration::from_secs(21), 80.);
+        collector.tempo(Duration::from_secs(30), 80.);
+        let markers = collector.finish();
+
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers[0].time, Duration::from_secs(20));
+        assert_eq!(markers[0].label, "140 BPM");
+        assert_eq!(markers[1].time, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_text_markers() {
+        let mut collector = MarkerCollector::new(120.);
+        collector.text(Duration::from_secs(5), " Verse ");
+        collector.text(Duration::from_secs(1), "Intro");
+        collector.text(Duration::from_secs(9), "  ");
+        let markers = collector.finish();
+
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers[0].label, "Intro");
+        assert_eq!(markers[1].label, "Verse");
+        assert_eq!(markers[1].kind, MarkerKind::Text);
+    }
+}
@@ -0,0 +1,114 @@
+//! MIDI clock and transport output, so external gear can follow the playing song.
+//!
+
+use super::midi_out::MidiOut;
+
+const CLOCK: u8 = 0xF8;
+const START: u8 = 0xFA;
+const CONTINUE: u8 = 0xFB;
+const STOP: u8 = 0xFC;
+const SONG_POSITION: u8 = 0xF2;
+
+/// Clock pulses per quarter note
+const PULSES_PER_QUARTER: f64 = 24.;
+/// Song position pointer counts sixteenth notes, which are 6 pulses
+const PULSES_PER_SIXTEENTH: u64 = 6;
+/// Largest position the 14-bit song position pointer can hold
+const MAX_SONG_POSITION: u64 = 0x3FFF;
+
+/// Transport messages for pausing and unpausing
+pub fn send_stop(out: &MidiOut) {
+    out.send(&[STOP]);
+}
+pub fn send_continue(out: &MidiOut) {
+    out.send(&[CONTINUE]);
+}
+
+/// Turns song position into clock pulses. Owned by the sequencer of one song.
+pub struct MidiClock {
+    out: MidiOut,
+    /// Pulses sent since start or the last position change
+    pulses: u64,
+}
+impl MidiClock {
+    pub const fn new(out: MidiOut) -> Self {
+        Self { out, pulses: 0 }
+    }
+
+    /// Song starts from the beginning
+    pub fn start(&mut self) {
+        self.pulses = 0;
+        self.out.send(&[START]);
+    }
+
+    /// Send the pulses due by this position. The first pulse marks the position itself.
+    pub fn update(&mut self, quarter_notes: f64) {
+        let due = (quarter_notes.max(0.) * PULSES_PER_QUARTER) as u64 + 1;
+        while self.pulses < due {
+            self.out.send(&[CLOCK]);
+            self.pulses += 1;
+        }
+    }
+
+    /// Playback jumped. Followers are moved to the closest sixteenth note.
+    pub fn seek(&mut self, quarter_notes: f64) {
+        let sixteenths = ((quarter_notes.max(0.) * 4.) as u64).min(MAX_SONG_POSITION);
+        self.out.send(&[STOP]);
+        self.out.send(&[
+            SONG_POSITION,
+            (sixteenths & 0x7F) as u8,
+            (sixteenths >> 7) as u8,
+        ]);
+        self.out.send(&[CONTINUE]);
+        self.pulses = sixteenths * PULSES_PER_SIXTEENTH;
+    }
+}
+impl Drop for MidiClock {
+    /// Song ended or was stopped
+    fn drop(&mut self) {
+        self.out.send(&[STOP]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_pulses() {
+        let (out, received) = MidiOut::capture();
+        let mut clock = MidiClock::new(out);
+        clock.start();
+        clock.update(0.);
+        assert_eq!(
+            received.try_iter().collect::<Vec<_>>(),
+            [vec![START], vec![CLOCK]]
+        );
+
+        // One quarter note later
+        clock.update(1.);
+        assert_eq!(received.try_iter().count(), 24);
+        clock.update(1.01);
+        assert_eq!(received.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_clock_seek() {
+        let (out, received) = MidiOut::capture();
+        let mut clock = MidiClock::new(out);
+        clock.start();
+        // 200 sixteenths: 72 + 1 * 128
+        clock.seek(50.1);
+        let messages: Vec<Vec<u8>> = received.try_iter().collect();
+        assert_eq!(
+            messages[1..],
+            [vec![STOP], vec![SONG_POSITION, 72, 1], vec![CONTINUE]]
+        );
+        // Catches up within the sixteenth
+        clock.update(50.1);
+        assert_eq!(received.try_iter().count(), 3);
+
+        drop(clock);
+        assert_eq!(received.try_iter().collect::<Vec<_>>(), [vec![STOP]]);
+    }
+}
@@ -0,0 +1,86 @@
+//! MIDI input port that keyboards and other apps can connect to, for playing a soundfont live.
+//!
+//! Where the system has virtual ports (ALSA, Core MIDI), a port is created for others to connect
+//! to. Windows doesn't have them, so there every input device that's present when the port opens
+//! is connected instead. Messages arrive on midir's thread as raw MIDI bytes. The web build has
+//! no MIDI input.
+
+/// Name of the port, as seen by other apps
+pub const INPUT_PORT_NAME: &str = "SfontPlayer Input";
+/// Can other apps connect to the port, or does it read the devices itself
+pub const HAS_VIRTUAL_PORT: bool = cfg!(unix);
+
+/// Open connections. The port closes when this is dropped.
+pub struct MidiInPort {
+    #[cfg(not(target_arch = "wasm32"))]
+    _connections: Vec<midir::MidiInputConnection<()>>,
+}
+impl MidiInPort {
+    /// `on_message` runs on the port thread for every complete message.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the system MIDI service isn't available, or the port can't be created. Without
+    /// virtual ports, also fails if there are no input devices to read.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open(on_message: impl FnMut(&[u8]) + Send + 'static) -> anyhow::Result<Self> {
+        use parking_lot::Mutex;
+        use std::sync::Arc;
+
+        // Shared by the connections to every device
+        let on_message = Arc::new(Mutex::new(on_message));
+        let callback = move |_timestamp: u64, message: &[u8], (): &mut ()| {
+            (on_message.lock())(message);
+        };
+        Ok(Self {
+            _connections: connect(callback)?,
+        })
+    }
+
+    /// # Errors
+    ///
+    /// Always, there's no MIDI input in the browser.
+    #[cfg(target_arch = "wasm32")]
+    pub fn open(_on_message: impl FnMut(&[u8]) + Send + 'static) -> anyhow::Result<Self> {
+        anyhow::bail!("MIDI input isn't available in the web build")
+    }
+}
+
+/// Sysex, clock and active sensing aren't played, so they're dropped here.
+#[cfg(not(target_arch = "wasm32"))]
+fn new_input() -> anyhow::Result<midir::MidiInput> {
+    let mut input = midir::MidiInput::new(INPUT_PORT_NAME)
+        .map_err(|e| anyhow::anyhow!("Couldn't open MIDI input: {e}"))?;
+    input.ignore(midir::Ignore::All);
+    Ok(input)
+}
+
+#[cfg(unix)]
+fn connect(
+    callback: impl FnMut(u64, &[u8], &mut ()) + Send + 'static,
+) -> anyhow::Result<Vec<midir::MidiInputConnection<()>>> {
+    use midir::os::unix::VirtualInput;
+
+    let connection = new_input()?
+        .create_virtual(INPUT_PORT_NAME, callback, ())
+        .map_err(|e| anyhow::anyhow!("Couldn't open MIDI input port: {e}"))?;
+    Ok(vec![connection])
+}
+
+#[cfg(not(any(unix, target_arch = "wasm32")))]
+fn connect(
+    callback: impl FnMut(u64, &[u8], &mut ()) + Clone + Send + 'static,
+) -> anyhow::Result<Vec<midir::MidiInputConnection<()>>> {
+    let ports = new_input()?.ports();
+    if ports.is_empty() {
+        anyhow::bail!("No MIDI input devices found");
+    }
+    ports
+        .iter()
+        .map(|port| {
+            new_input()?
+                .connect(port, INPUT_PORT_NAME, callback.clone(), ())
+                .map_err(|e| anyhow::anyhow!("Couldn't connect to a MIDI input device: {e}"))
+        })
+        .collect()
+}
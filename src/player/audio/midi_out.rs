@@ -0,0 +1,147 @@
+//! Virtual MIDI output port that other apps can connect to.
+//!
+//! The port lives in the ALSA sequencer, so this is Linux only for now.
+//! Messages are written from a background thread, so the audio thread never waits for it.
+
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+/// Name of the port, as seen by other apps
+#[cfg(target_os = "linux")]
+const PORT_NAME: &str = "SfontPlayer";
+
+/// Sends raw MIDI messages to the port. Clones send to the same port.
+#[derive(Clone)]
+pub struct MidiOut {
+    messages: Sender<Vec<u8>>,
+}
+impl MidiOut {
+    /// Queue one complete message
+    pub fn send(&self, message: &[u8]) {
+        let _ = self.messages.send(message.to_vec());
+    }
+    /// Silence hanging notes and reset controllers on every channel
+    pub fn all_notes_off(&self) {
+        for channel in 0..16 {
+            let status = 0xB0 | channel;
+            // All notes off, reset all controllers
+            self.send(&[status, 123, 0]);
+            self.send(&[status, 121, 0]);
+        }
+    }
+}
+
+/// Background thread that holds the port open. The port closes when this and every [`MidiOut`]
+/// are dropped.
+pub struct MidiPort {
+    out: MidiOut,
+    errors: Receiver<String>,
+}
+impl MidiPort {
+    #[must_use]
+    pub fn open() -> Self {
+        let (messages_tx, messages_rx) = mpsc::channel();
+        let (errors_tx, errors_rx) = mpsc::channel();
+        let thread_errors = errors_tx.clone();
+        if let Err(e) =
+            thread::Builder::new().spawn(move || write_port(&messages_rx, &thread_errors))
+        {
+            let _ = errors_tx.send(format!("Couldn't open MIDI output: {e}"));
+        }
+        Self {
+            out: MidiOut {
+                messages: messages_tx,
+            },
+            errors: errors_rx,
+        }
+    }
+
+    #[must_use]
+    pub fn get_out(&self) -> MidiOut {
+        self.out.clone()
+    }
+
+    /// Port problems since last call
+    #[must_use]
+    pub fn take_errors(&self) -> Vec<String> {
+        self.errors.try_iter().collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn write_port(messages_rx: &Receiver<Vec<u8>>, errors_tx: &Sender<String>) {
+    use alsa::{
+        seq::{MidiEvent, PortCap, PortType, Seq},
+        Direction,
+    };
+    use std::ffi::CString;
+
+    /// Longest message the encoder can hold. Sysex isn't sent, so this is plenty.
+    const ENCODER_BUFFER: u32 = 256;
+
+    let port_name = CString::new(PORT_NAME).expect("port name has no nul");
+    let opened = Seq::open(None, Some(Direction::Playback), false).and_then(|seq| {
+        seq.set_client_name(&port_name)?;
+        let port = seq.create_simple_port(
+            &port_name,
+            PortCap::READ | PortCap::SUBS_READ,
+            PortType::MIDI_GENERIC | PortType::APPLICATION,
+        )?;
+        let encoder = MidiEvent::new(ENCODER_BUFFER)?;
+        encoder.enable_running_status(false);
+        Ok((seq, port, encoder))
+    });
+    let (seq, port, mut encoder) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            let _ = errors_tx.send(format!("Couldn't open MIDI output port: {e}"));
+            return;
+        }
+    };
+
+    // Ends when every sender is dropped
+    let mut reported = false;
+    for message in messages_rx {
+        let mut rest = message.as_slice();
+        while !rest.is_empty() {
+            let Ok((consumed, event)) = encoder.encode(rest) else {
+                break;
+            };
+            if let Some(mut event) = event {
+                event.set_source(port);
+                event.set_subs();
+                event.set_direct();
+                if let Err(e) = seq.event_output_direct(&mut event) {
+                    // Once is enough, this could happen for every clock pulse.
+                    if !reported {
+                        let _ = errors_tx.send(format!("MIDI output failed: {e}"));
+                        reported = true;
+                    }
+                }
+            }
+            if consumed == 0 {
+                break;
+            }
+            rest = &rest[consumed..];
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn write_port(messages_rx: &Receiver<Vec<u8>>, errors_tx: &Sender<String>) {
+    let _ = errors_tx.send("MIDI output isn't supported on this platform yet.".into());
+    // Keep the senders from piling up messages
+    for _ in messages_rx {}
+}
+
+#[cfg(test)]
+impl MidiOut {
+    /// Output that can be read back
+    #[must_use]
+    pub fn capture() -> (Self, Receiver<Vec<u8>>) {
+        let (messages, received) = mpsc::channel();
+        (Self { messages }, received)
+    }
+}
@@ -1,6 +1,18 @@
-use midi_msg::{ChannelVoiceMsg, Division, Meta, MidiFile, MidiMsg, TimeCodeType, TrackEvent};
+use midi_msg::{
+    Channel, ChannelVoiceMsg, ControlChange, Division, Meta, MidiFile, MidiMsg, TimeCodeType,
+    TrackEvent,
+};
 use std::{fmt::Display, time::Duration};
 
+use super::{
+    activity::ChannelActivity,
+    lyrics::{LyricCollector, Lyrics},
+    markers::{MarkerCollector, NoteDensity, SongMarker},
+    midi_clock::MidiClock,
+    midi_out::MidiOut,
+};
+use crate::player::program_lock::{DrumKit, ProgramLocks, DRUM_CHANNEL};
+
 /// Ability to receive messages
 pub trait MidiSink {
     /// Returns Err if event couldn't be used.
@@ -35,6 +47,26 @@ pub struct MidiSequencer {
     since_last_tick: Duration,
     song_len: Duration,
     song_pos: Duration,
+    /// Sequence ends here instead of the end of the song
+    end: Option<Duration>,
+    /// Tempo changes and marker events, for the seek bar
+    markers: Vec<SongMarker>,
+    /// Notes per second, for the seek bar
+    note_density: NoteDensity,
+    /// Lyric or text events, for karaoke
+    lyrics: Lyrics,
+    /// Channels that ignore program changes
+    program_locks: ProgramLocks,
+    /// Drum channel ignores bank and program changes
+    drum_kit: Option<DrumKit>,
+    /// Have the locked programs been sent since start or reset
+    program_locks_sent: bool,
+    /// Note activity for visualization
+    activity: Option<ChannelActivity>,
+    /// Clock output for external gear
+    clock: Option<MidiClock>,
+    /// Played events are forwarded here
+    thru: Option<MidiOut>,
 }
 impl MidiSequencer {
     pub const fn new() -> Self {
@@ -46,15 +78,145 @@ impl MidiSequencer {
             since_last_tick: Duration::ZERO,
             song_len: Duration::ZERO,
             song_pos: Duration::ZERO,
+            end: None,
+            markers: vec![],
+            note_density: NoteDensity::new(),
+            lyrics: Lyrics::new(),
+            program_locks: ProgramLocks::new(),
+            drum_kit: None,
+            program_locks_sent: false,
+            activity: None,
+            clock: None,
+            thru: None,
+        }
+    }
+
+    pub fn set_activity(&mut self, activity: ChannelActivity) {
+        activity.clear();
+        self.activity = Some(activity);
+    }
+
+    /// Send clock from the current position on
+    pub fn set_clock(&mut self, mut clock: MidiClock) {
+        clock.start();
+        if !self.song_pos.is_zero() {
+            clock.seek(self.get_quarter_notes());
+        }
+        self.clock = Some(clock);
+    }
+
+    /// Forward played events to an output port
+    pub fn set_thru(&mut self, thru: MidiOut) {
+        self.thru = Some(thru);
+        self.program_locks_sent = false;
+    }
+
+    /// The synth got a message. Send it to the thru port, if there is one, and the activity meters.
+    fn forward(&self, msg: &MidiMsg) {
+        if let Some(thru) = &self.thru {
+            thru.send(&msg.to_midi());
+        }
+        if let Some(activity) = &self.activity {
+            activity.observe(msg);
+        }
+    }
+
+    pub const fn set_program_locks(&mut self, program_locks: ProgramLocks) {
+        self.program_locks = program_locks;
+        self.program_locks_sent = false;
+    }
+
+    pub const fn set_drum_kit(&mut self, drum_kit: Option<DrumKit>) {
+        self.drum_kit = drum_kit;
+        self.program_locks_sent = false;
+    }
+
+    /// Set locked programs and drum kit on their channels, once.
+    fn send_program_locks<R>(&mut self, event_sink: &mut R)
+    where
+        R: MidiSink,
+    {
+        if self.program_locks_sent {
+            return;
+        }
+        self.program_locks_sent = true;
+        for (channel, program) in self.program_locks.iter() {
+            let msg = MidiMsg::ChannelVoice {
+                channel: Channel::from_u8(channel as u8),
+                msg: ChannelVoiceMsg::ProgramChange { program },
+            };
+            let _ = event_sink.receive_midi(&msg);
+            self.forward(&msg);
+        }
+        if let Some(drum_kit) = self.drum_kit {
+            let channel = Channel::from_u8(DRUM_CHANNEL as u8);
+            let bank_select = MidiMsg::ChannelVoice {
+                channel,
+                msg: ChannelVoiceMsg::ControlChange {
+                    control: ControlChange::CC {
+                        control: 0,
+                        value: drum_kit.get_bank_select(),
+                    },
+                },
+            };
+            let program_change = MidiMsg::ChannelVoice {
+                channel,
+                msg: ChannelVoiceMsg::ProgramChange {
+                    program: drum_kit.program,
+                },
+            };
+            let _ = event_sink.receive_midi(&bank_select);
+            let _ = event_sink.receive_midi(&program_change);
+            self.forward(&bank_select);
+            self.forward(&program_change);
+        }
+    }
+
+    /// Program or bank change on a locked channel
+    fn is_locked_out(&self, event: &MidiMsg) -> bool {
+        match event {
+            MidiMsg::ChannelVoice {
+                channel,
+                msg: ChannelVoiceMsg::ProgramChange { .. },
+            }
+            | MidiMsg::RunningChannelVoice {
+                channel,
+                msg: ChannelVoiceMsg::ProgramChange { .. },
+            } => {
+                self.program_locks.get(*channel as usize).is_some()
+                    || (*channel as usize == DRUM_CHANNEL && self.drum_kit.is_some())
+            }
+            MidiMsg::ChannelVoice {
+                channel,
+                msg: ChannelVoiceMsg::ControlChange { control },
+            }
+            | MidiMsg::RunningChannelVoice {
+                channel,
+                msg: ChannelVoiceMsg::ControlChange { control },
+            } => {
+                // Bank select MSB / LSB
+                *channel as usize == DRUM_CHANNEL
+                    && self.drum_kit.is_some()
+                    && matches!(control.control(), 0 | 32)
+            }
+            _ => false,
         }
     }
 
+    /// Stop before the end of the song. None plays to the end.
+    pub const fn set_end(&mut self, end: Option<Duration>) {
+        self.end = end;
+    }
+
     /// Are there no more messages left?
     pub fn end_of_sequence(&self) -> bool {
         let Some(midifile) = &self.midifile else {
             println!("bailed: no midi");
             return true;
         };
+        if self.end.is_some_and(|end| self.song_pos >= end) {
+            return true;
+        }
         for (i, track) in midifile.tracks.iter().enumerate() {
             if self.track_positions[i] < track.events().len() {
                 return false;
@@ -75,6 +237,7 @@ impl MidiSequencer {
         self.tick = 0;
         self.track_positions = vec![0; midifile.tracks.len()];
         self.midifile = Some(midifile);
+        self.program_locks_sent = false;
 
         self.update_song_length();
     }
@@ -86,6 +249,7 @@ impl MidiSequencer {
         let Some(events) = self.get_events() else {
             return;
         };
+        self.send_program_locks(event_sink);
 
         self.song_pos += delta_t;
         self.since_last_tick += delta_t;
@@ -101,15 +265,24 @@ impl MidiSequencer {
                 | MidiMsg::RunningChannelVoice { .. }
                 | MidiMsg::ChannelMode { .. }
                 | MidiMsg::RunningChannelMode { .. } => {
+                    if self.is_locked_out(&wrap.track_event.event) {
+                        continue;
+                    }
                     if event_sink.receive_midi(&wrap.track_event.event).is_err() {
                         println!("Unhandled: {wrap}");
                     }
+                    self.forward(&wrap.track_event.event);
                 }
 
                 midi_msg::MidiMsg::Meta { msg } => self.handle_meta_event(&msg),
                 _ => (),
             }
         }
+
+        let quarter_notes = self.get_quarter_notes();
+        if let Some(clock) = &mut self.clock {
+            clock.update(quarter_notes);
+        }
     }
 
     /// For seeking. Ignore `NoteOn`.
@@ -120,6 +293,7 @@ impl MidiSequencer {
         let Some(events) = self.get_events() else {
             return;
         };
+        self.send_program_locks(event_sink);
 
         self.song_pos += self.get_current_tick_duration();
         self.tick += 1;
@@ -129,13 +303,16 @@ impl MidiSequencer {
                 MidiMsg::ChannelVoice { msg, .. } | MidiMsg::RunningChannelVoice { msg, .. } => {
                     match msg {
                         ChannelVoiceMsg::NoteOn { .. } | ChannelVoiceMsg::HighResNoteOn { .. } => {}
+                        _ if self.is_locked_out(&wrap.track_event.event) => {}
                         _ => {
                             let _ = event_sink.receive_midi(&wrap.track_event.event);
+                            self.forward(&wrap.track_event.event);
                         }
                     }
                 }
                 MidiMsg::ChannelMode { .. } | MidiMsg::RunningChannelMode { .. } => {
                     let _ = event_sink.receive_midi(&wrap.track_event.event);
+                    self.forward(&wrap.track_event.event);
                 }
                 midi_msg::MidiMsg::Meta { msg } => self.handle_meta_event(&msg),
                 _ => (),
@@ -186,6 +363,18 @@ impl MidiSequencer {
         }
     }
 
+    /// Song position in quarter notes, for the clock output
+    fn get_quarter_notes(&self) -> f64 {
+        let Some(midifile) = &self.midifile else {
+            return 0.;
+        };
+        match midifile.header.division {
+            Division::TicksPerQuarterNote(ticks) => self.tick as f64 / f64::from(ticks),
+            // No beats in the file, go by the tempo
+            Division::TimeCode { .. } => self.song_pos.as_secs_f64() * self.bpm / 60.,
+        }
+    }
+
     fn get_current_tick_duration(&self) -> Duration {
         let Some(midifile) = &self.midifile else {
             return Duration::ZERO;
@@ -210,6 +399,9 @@ impl MidiSequencer {
     fn update_song_length(&mut self) {
         let Some(midifile) = &self.midifile else {
             self.song_len = Duration::ZERO;
+            self.markers.clear();
+            self.note_density.clear();
+            self.lyrics.clear();
             return;
         };
 
@@ -217,6 +409,9 @@ impl MidiSequencer {
         let mut tick = 0;
         let mut duration = Duration::ZERO;
         let mut bpm = 120.;
+        let mut markers = MarkerCollector::new(bpm);
+        let mut note_density = NoteDensity::new();
+        let mut lyrics = LyricCollector::new();
         loop {
             let mut done = true;
             for (i, track) in midifile.tracks.iter().enumerate() {
@@ -235,11 +430,31 @@ impl MidiSequencer {
                         as usize;
                     if tick >= event_tick {
                         track_positions[i] += 1;
-                        #[allow(clippy::collapsible_match)]
-                        if let MidiMsg::Meta { msg } = &event.event {
-                            if let Meta::SetTempo(tempo) = msg {
+                        match &event.event {
+                            MidiMsg::Meta {
+                                msg: Meta::SetTempo(tempo),
+                            } => {
                                 bpm = 60_000_000. / f64::from(*tempo);
+                                markers.tempo(duration, bpm);
+                            }
+                            MidiMsg::Meta {
+                                msg: Meta::Marker(text) | Meta::CuePoint(text),
+                            } => markers.text(duration, text),
+                            MidiMsg::Meta {
+                                msg: Meta::Lyric(text),
+                            } => lyrics.lyric(duration, text),
+                            MidiMsg::Meta {
+                                msg: Meta::Text(text),
+                            } => lyrics.text(duration, text),
+                            MidiMsg::ChannelVoice {
+                                msg: ChannelVoiceMsg::NoteOn { velocity, .. },
+                                ..
                             }
+                            | MidiMsg::RunningChannelVoice {
+                                msg: ChannelVoiceMsg::NoteOn { velocity, .. },
+                                ..
+                            } if *velocity > 0 => note_density.add_note(duration),
+                            _ => (),
                         }
                     } else {
                         break;
@@ -267,12 +482,32 @@ impl MidiSequencer {
             }
         }
         self.song_len = duration;
+        self.markers = markers.finish();
+        self.note_density = note_density;
+        self.lyrics = lyrics.finish();
+    }
+
+    pub fn get_markers(&self) -> &[SongMarker] {
+        &self.markers
+    }
+
+    pub const fn get_note_density(&self) -> &NoteDensity {
+        &self.note_density
+    }
+
+    pub const fn get_lyrics(&self) -> &Lyrics {
+        &self.lyrics
     }
 
     pub const fn get_song_length(&self) -> Duration {
         self.song_len
     }
 
+    /// Length of the part that gets played
+    pub fn get_play_length(&self) -> Duration {
+        self.end.map_or(self.song_len, |end| end.min(self.song_len))
+    }
+
     pub const fn get_song_position(&self) -> Duration {
         self.song_pos
     }
@@ -284,6 +519,9 @@ impl MidiSequencer {
         let Some(midifile) = &self.midifile else {
             return;
         };
+        if let Some(activity) = &self.activity {
+            activity.clear();
+        }
 
         if pos < self.song_pos {
             self.bpm = 120.;
@@ -291,6 +529,10 @@ impl MidiSequencer {
             self.tick = 0;
             self.song_pos = Duration::ZERO;
             event_sink.reset();
+            if let Some(thru) = &self.thru {
+                thru.all_notes_off();
+            }
+            self.program_locks_sent = false;
         }
 
         self.since_last_tick = Duration::ZERO;
@@ -298,5 +540,19 @@ impl MidiSequencer {
         while self.song_pos < pos {
             self.update_events_quiet(event_sink);
         }
+
+        let quarter_notes = self.get_quarter_notes();
+        if let Some(clock) = &mut self.clock {
+            clock.seek(quarter_notes);
+        }
+    }
+}
+
+impl Drop for MidiSequencer {
+    /// Notes left on in the thru port would keep playing
+    fn drop(&mut self) {
+        if let Some(thru) = &self.thru {
+            thru.all_notes_off();
+        }
     }
 }
@@ -1,10 +1,22 @@
 use midi_msg::MidiFile;
-use rustysynth::{SoundFont, Synthesizer, SynthesizerSettings};
+use rustysynth::{SoundFont, Synthesizer};
 use std::{sync::Arc, time::Duration};
 
-use super::midisequencer::MidiSequencer;
+use super::{
+    ab_loop::LoopRegion,
+    activity::ChannelActivity,
+    lyrics::Lyrics,
+    markers::{NoteDensity, SongMarker},
+    midi_clock::MidiClock,
+    midi_out::MidiOut,
+    midisequencer::MidiSequencer,
+    midisynth::{new_synthesizer, render_frame},
+    speed::PlaybackSpeed,
+};
+use crate::player::program_lock::{DrumKit, ProgramLocks};
 
-const SAMPLERATE: u32 = 44100;
+pub const SAMPLERATE: u32 = 44100;
+pub const CHANNELS: u16 = 2;
 
 #[derive(PartialEq)]
 enum Channel {
@@ -25,17 +37,28 @@ pub struct MidiSource {
     cached_sample: f32,
     /// Which channel was played last
     next_ch: Channel,
+    /// Jump back to A at B
+    loop_region: LoopRegion,
+    /// Tempo multiplier. The song moves this much per sample time.
+    speed: PlaybackSpeed,
 }
 
 impl MidiSource {
-    /// New `MidiSource` that immediately starts playing.
-    #[allow(clippy::cast_possible_wrap)] // It's ok to cast here
-    pub fn new(sf: &Arc<SoundFont>, midifile: MidiFile) -> Self {
-        let settings = SynthesizerSettings::new(SAMPLERATE as i32);
-        let mut synthesizer =
-            Synthesizer::new(sf, &settings).expect("Could not create synthesizer");
-        synthesizer.set_master_volume(1.0);
+    /// New `MidiSource` that immediately starts playing. `synth_effects` enables the
+    /// synthesizer's reverb and chorus.
+    pub fn new(
+        sf: &Arc<SoundFont>,
+        midifile: MidiFile,
+        program_locks: ProgramLocks,
+        drum_kit: Option<DrumKit>,
+        activity: ChannelActivity,
+        synth_effects: bool,
+    ) -> Self {
+        let synthesizer = new_synthesizer(sf, synth_effects);
         let mut sequencer = MidiSequencer::new();
+        sequencer.set_program_locks(program_locks);
+        sequencer.set_drum_kit(drum_kit);
+        sequencer.set_activity(activity);
         sequencer.play(midifile);
 
         let delta_t = Duration::from_secs_f64(1. / f64::from(synthesizer.get_sample_rate()));
@@ -45,12 +68,61 @@ impl MidiSource {
             sequencer,
             next_ch: Channel::L,
             cached_sample: 0.,
+            loop_region: LoopRegion::default(),
+            speed: PlaybackSpeed::default(),
         }
     }
 
     pub const fn get_song_length(&self) -> Duration {
         self.sequencer.get_song_length()
     }
+
+    /// Stop before the end of the song. None plays to the end.
+    pub const fn set_end(&mut self, end: Option<Duration>) {
+        self.sequencer.set_end(end);
+    }
+
+    /// Loop between two points. The region can be changed while playing.
+    pub fn set_loop(&mut self, loop_region: LoopRegion) {
+        self.loop_region = loop_region;
+    }
+
+    /// Back to A if B is reached, or the song ended before it
+    fn update_loop(&mut self) {
+        let Some((a, b)) = self.loop_region.get() else {
+            return;
+        };
+        if self.sequencer.get_song_position() >= b || self.sequencer.end_of_sequence() {
+            self.sequencer.seek_to(&mut self.synthesizer, a);
+        }
+    }
+
+    /// Play faster or slower. The speed can be changed while playing.
+    pub fn set_speed(&mut self, speed: PlaybackSpeed) {
+        self.speed = speed;
+    }
+
+    /// Send MIDI clock that follows the song
+    pub fn set_clock(&mut self, clock: MidiClock) {
+        self.sequencer.set_clock(clock);
+    }
+
+    /// Forward played events to an output port
+    pub fn set_thru(&mut self, thru: MidiOut) {
+        self.sequencer.set_thru(thru);
+    }
+
+    pub fn get_markers(&self) -> &[SongMarker] {
+        self.sequencer.get_markers()
+    }
+
+    pub const fn get_note_density(&self) -> &NoteDensity {
+        self.sequencer.get_note_density()
+    }
+
+    pub const fn get_lyrics(&self) -> &Lyrics {
+        self.sequencer.get_lyrics()
+    }
 }
 
 // Rodio requires Iterator implementation.
@@ -59,6 +131,9 @@ impl Iterator for MidiSource {
     type Item = f32;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.next_ch == Channel::L {
+            self.update_loop();
+        }
         if self.sequencer.end_of_sequence() {
             return None;
         }
@@ -70,15 +145,12 @@ impl Iterator for MidiSource {
         if self.next_ch == Channel::L {
             self.next_ch = Channel::R;
 
-            self.sequencer
-                .update_events(&mut self.synthesizer, self.delta_t);
+            let delta_t = self.delta_t.mul_f64(f64::from(self.speed.get()));
+            self.sequencer.update_events(&mut self.synthesizer, delta_t);
 
-            let mut left = [0.];
-            let mut right = [0.];
-            self.synthesizer.render(&mut left, &mut right);
-
-            self.cached_sample = right[0] / 10.;
-            Some(left[0] / 10.)
+            let (left, right) = render_frame(&mut self.synthesizer);
+            self.cached_sample = right;
+            Some(left)
         }
         // Right: Generate nothing and return cached R ch. sample.
         else {
@@ -91,13 +163,17 @@ impl Iterator for MidiSource {
 
 impl rodio::Source for MidiSource {
     fn current_frame_len(&self) -> Option<usize> {
-        let time_left = self.sequencer.get_song_length() - self.sequencer.get_song_position();
-        let samples_left = time_left.as_secs_f64() * f64::from(self.synthesizer.get_sample_rate());
+        let time_left = self
+            .sequencer
+            .get_play_length()
+            .saturating_sub(self.sequencer.get_song_position());
+        let samples_left = time_left.as_secs_f64() / f64::from(self.speed.get())
+            * f64::from(self.synthesizer.get_sample_rate());
         Some(samples_left as usize)
     }
 
     fn channels(&self) -> u16 {
-        2
+        CHANNELS
     }
 
     fn sample_rate(&self) -> u32 {
@@ -105,7 +181,7 @@ impl rodio::Source for MidiSource {
     }
 
     fn total_duration(&self) -> Option<Duration> {
-        Some(self.sequencer.get_song_length())
+        Some(self.sequencer.get_play_length())
     }
 
     fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
@@ -113,3 +189,140 @@ impl rodio::Source for MidiSource {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        assert_golden, micro_soundfont, note_off, note_on, program_change, song, tempo,
+        DEFAULT_BEAT_SECS,
+    };
+    use midi_msg::MidiMsg;
+
+    /// Louder than this is sound
+    const THRESHOLD: f32 = 0.001;
+    /// The synth starts notes at its 64-frame blocks, and seeks land on the next tick, which is
+    /// 230 frames at 120 bpm.
+    const ONSET_TOLERANCE: usize = 320;
+
+    fn source(events: &[(f32, MidiMsg)]) -> MidiSource {
+        let soundfont = SoundFont::new(&mut micro_soundfont().as_slice()).expect("soundfont");
+        MidiSource::new(
+            &Arc::new(soundfont),
+            song(events),
+            ProgramLocks::default(),
+            None,
+            ChannelActivity::default(),
+            false,
+        )
+    }
+
+    /// Left channel, at most `secs` long
+    fn render(source: MidiSource, secs: f32) -> Vec<f32> {
+        let frames = (secs * SAMPLERATE as f32) as usize;
+        source.take(frames * 2).step_by(2).collect()
+    }
+
+    /// First frame with sound from `from` on
+    fn onset(samples: &[f32], from: usize) -> Option<usize> {
+        samples[from..]
+            .iter()
+            .position(|s| s.abs() > THRESHOLD)
+            .map(|i| i + from)
+    }
+
+    fn assert_near(frame: Option<usize>, secs: f32) {
+        let expected = (secs * SAMPLERATE as f32) as usize;
+        let frame = frame.expect("no sound");
+        assert!(
+            frame.abs_diff(expected) <= ONSET_TOLERANCE,
+            "sound at frame {frame}, expected about {expected}"
+        );
+    }
+
+    /// Rendered to the end, give or take a frame of rounding
+    fn assert_length(samples: &[f32], secs: f32) {
+        let expected = (secs * SAMPLERATE as f32) as usize;
+        assert!(
+            samples.len().abs_diff(expected) <= 1,
+            "{} frames, expected {expected}",
+            samples.len()
+        );
+    }
+
+    #[test]
+    fn test_render_timing() {
+        let samples = render(
+            source(&[
+                (1., note_on(0, 77, 100)),
+                (2., note_off(0, 77)),
+                (3., tempo(120)),
+            ]),
+            10.,
+        );
+        // Plays to the end and no further
+        assert_length(&samples, 3. * DEFAULT_BEAT_SECS);
+        assert_near(onset(&samples, 0), DEFAULT_BEAT_SECS);
+        assert_golden("timing", &samples);
+    }
+
+    #[test]
+    fn test_render_tempo_change() {
+        let samples = render(
+            source(&[
+                (0., tempo(240)),
+                (1., note_on(0, 77, 100)),
+                (2., note_off(0, 77)),
+                (3., tempo(240)),
+            ]),
+            10.,
+        );
+        assert_near(onset(&samples, 0), 0.25);
+        assert_length(&samples, 0.75);
+        assert_golden("tempo_change", &samples);
+    }
+
+    #[test]
+    fn test_render_presets() {
+        let notes = |channel| [(0., note_on(channel, 77, 100)), (1., note_off(channel, 77))];
+        let sine = render(source(&notes(0)), 10.);
+        let mut square_events = vec![(0., program_change(0, 1))];
+        square_events.extend(notes(0));
+        let square = render(source(&square_events), 10.);
+        // Channel 10 plays the drum kit
+        let drums = render(source(&notes(9)), 10.);
+
+        assert_ne!(sine, square);
+        assert_ne!(sine, drums);
+        assert_ne!(square, drums);
+        // Same amplitude, but the square wave carries twice the power of the sine.
+        let rms = |samples: &[f32]| {
+            (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+        };
+        assert!(rms(&square) > rms(&sine) * 1.3);
+        assert_golden("preset_sine", &sine);
+        assert_golden("preset_square", &square);
+        assert_golden("preset_drums", &drums);
+    }
+
+    #[test]
+    fn test_render_loop() {
+        // Note at 0.5 s, silence from 0.75 s to the end at 2 s
+        let mut source = source(&[
+            (1., note_on(0, 77, 100)),
+            (1.5, note_off(0, 77)),
+            (4., tempo(120)),
+        ]);
+        source.set_loop(LoopRegion::new(Some((
+            Duration::from_secs_f32(0.25),
+            Duration::from_secs_f32(1.),
+        ))));
+        let samples = render(source, 3.);
+
+        // Looping doesn't end
+        assert_length(&samples, 3.);
+        // Back to 0.25 s at 1 s, so the note comes again 0.25 s later.
+        assert_near(onset(&samples, SAMPLERATE as usize), 1.25);
+        assert_golden("loop", &samples);
+    }
+}
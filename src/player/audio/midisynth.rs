@@ -1,11 +1,32 @@
 //! `RustySynth` integration: This makes [`rustysynth::Synthesizer`] compatible
 //! with `MidiSequencer` and `midi_msg` crate's event format.
+//! Song playback and live input set up the synthesizer the same way.
 //!
 
+use std::sync::Arc;
+
 use midi_msg::MidiMsg;
-use rustysynth::Synthesizer;
+use rustysynth::{SoundFont, Synthesizer, SynthesizerSettings};
+
+use super::{midisequencer::MidiSink, midisource::SAMPLERATE};
 
-use super::midisequencer::MidiSink;
+/// `synth_effects` enables the synthesizer's reverb and chorus.
+#[allow(clippy::cast_possible_wrap)] // It's ok to cast here
+pub fn new_synthesizer(sf: &Arc<SoundFont>, synth_effects: bool) -> Synthesizer {
+    let mut settings = SynthesizerSettings::new(SAMPLERATE as i32);
+    settings.enable_reverb_and_chorus = synth_effects;
+    let mut synthesizer = Synthesizer::new(sf, &settings).expect("Could not create synthesizer");
+    synthesizer.set_master_volume(1.0);
+    synthesizer
+}
+
+/// Generate one stereo sample
+pub fn render_frame(synthesizer: &mut Synthesizer) -> (f32, f32) {
+    let mut left = [0.];
+    let mut right = [0.];
+    synthesizer.render(&mut left, &mut right);
+    (left[0] / 10., right[0] / 10.)
+}
 
 impl MidiSink for Synthesizer {
     fn receive_midi(&mut self, msg: &MidiMsg) -> Result<(), ()> {
@@ -0,0 +1,482 @@
+//! Output over HTTP, to listen on another device in the house.
+//!
+//! Instead of an audio device, a pump thread pulls the playing source in real time and sends it
+//! to every connected client. Each client encodes its own endless OGG Vorbis stream, so everyone
+//! gets the headers first. It plays in VLC, browsers and such.
+//!
+//! Only this computer can listen, unless other devices are allowed in the settings. Then the
+//! URL has to carry the token shown in the settings, e.g. `http://192.168.1.2:8000/?token=abc`.
+
+use std::{
+    collections::VecDeque,
+    io::{ErrorKind, Read, Write},
+    net::{IpAddr, Ipv4Addr, TcpListener, TcpStream, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use web_time::Instant;
+
+use parking_lot::Mutex;
+use rodio::{source::UniformSourceIterator, Source};
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::vorbis::VorbisWriter;
+use super::{
+    backend::{AudioBackend, BoxedSource},
+    midisource::{CHANNELS, SAMPLERATE},
+};
+use crate::player::remote::is_same_token;
+
+pub const DEFAULT_PORT: u16 = 8000;
+const PUMP_INTERVAL: Duration = Duration::from_millis(20);
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+/// Blocks a client may fall behind before it's dropped. About five seconds.
+const CLIENT_BACKLOG: usize = 250;
+/// More connections are refused
+const MAX_CLIENTS: usize = 8;
+/// Longer requests are refused, and the client disconnected
+const MAX_REQUEST: usize = 4096;
+/// Vorbis quality of the stream, about 160 kbit/s
+#[cfg(not(target_arch = "wasm32"))]
+const STREAM_QUALITY: f32 = 0.5;
+
+type StreamSource = UniformSourceIterator<BoxedSource, f32>;
+
+struct NetState {
+    sources: VecDeque<StreamSource>,
+    paused: bool,
+    volume: f32,
+    /// Samples played of the first source
+    samples_played: u64,
+    clients: Vec<SyncSender<Arc<[f32]>>>,
+}
+impl Default for NetState {
+    fn default() -> Self {
+        Self {
+            sources: VecDeque::new(),
+            paused: false,
+            volume: 1.,
+            samples_played: 0,
+            clients: vec![],
+        }
+    }
+}
+impl NetState {
+    fn next_sample(&mut self) -> Option<f32> {
+        while let Some(source) = self.sources.front_mut() {
+            if let Some(sample) = source.next() {
+                self.samples_played += 1;
+                return Some(sample);
+            }
+            self.sources.pop_front();
+            self.samples_played = 0;
+        }
+        None
+    }
+
+    /// Play some frames. Silence when paused or empty.
+    fn render(&mut self, frames: usize) -> Vec<f32> {
+        let len = frames * usize::from(CHANNELS);
+        (0..len)
+            .map(|_| {
+                if self.paused {
+                    0.
+                } else {
+                    self.next_sample().unwrap_or_default() * self.volume
+                }
+            })
+            .collect()
+    }
+
+    fn get_pos(&self) -> Duration {
+        let frames = self.samples_played / u64::from(CHANNELS);
+        Duration::from_secs(frames / u64::from(SAMPLERATE))
+            + Duration::from_secs_f64(
+                (frames % u64::from(SAMPLERATE)) as f64 / f64::from(SAMPLERATE),
+            )
+    }
+}
+
+/// Backend that streams to network clients instead of playing. Stops serving when dropped.
+pub struct NetSink {
+    state: Arc<Mutex<NetState>>,
+    running: Arc<AtomicBool>,
+    port: u16,
+}
+impl NetSink {
+    /// Start serving on a port of this computer. Port 0 picks a free one. With a token, other
+    /// devices can listen too, but their URL has to include it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the port can't be bound, e.g. because another program is using it.
+    pub fn start(port: u16, token: Option<String>) -> anyhow::Result<Self> {
+        let host = if token.is_some() {
+            Ipv4Addr::UNSPECIFIED
+        } else {
+            Ipv4Addr::LOCALHOST
+        };
+        let listener = TcpListener::bind((host, port))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let state = Arc::new(Mutex::new(NetState::default()));
+        let running = Arc::new(AtomicBool::new(true));
+        {
+            let state = Arc::clone(&state);
+            let running = Arc::clone(&running);
+            thread::spawn(move || accept_clients(&listener, &state, &running, token.as_deref()));
+        }
+        {
+            let state = Arc::clone(&state);
+            let running = Arc::clone(&running);
+            thread::spawn(move || pump(&state, &running));
+        }
+        Ok(Self {
+            state,
+            running,
+            port,
+        })
+    }
+    #[must_use]
+    pub const fn get_port(&self) -> u16 {
+        self.port
+    }
+    #[must_use]
+    pub fn get_client_count(&self) -> usize {
+        self.state.lock().clients.len()
+    }
+}
+impl Drop for NetSink {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+impl AudioBackend for NetSink {
+    fn append(&self, source: BoxedSource) {
+        let source = UniformSourceIterator::new(source, CHANNELS, SAMPLERATE);
+        self.state.lock().sources.push_back(source);
+    }
+    fn play(&self) {
+        self.state.lock().paused = false;
+    }
+    fn pause(&self) {
+        self.state.lock().paused = true;
+    }
+    fn is_paused(&self) -> bool {
+        self.state.lock().paused
+    }
+    fn clear(&self) {
+        let mut state = self.state.lock();
+        state.sources.clear();
+        state.samples_played = 0;
+    }
+    fn empty(&self) -> bool {
+        self.state.lock().sources.is_empty()
+    }
+    fn queue_len(&self) -> usize {
+        self.state.lock().sources.len()
+    }
+    fn set_volume(&self, volume: f32) {
+        self.state.lock().volume = volume;
+    }
+    fn get_pos(&self) -> Duration {
+        self.state.lock().get_pos()
+    }
+    fn try_seek(&self, pos: Duration) {
+        let mut state = self.state.lock();
+        let Some(source) = state.sources.front_mut() else {
+            return;
+        };
+        if source.try_seek(pos).is_ok() {
+            let frames = (pos.as_secs_f64() * f64::from(SAMPLERATE)) as u64;
+            state.samples_played = frames * u64::from(CHANNELS);
+        }
+    }
+    fn get_name(&self) -> String {
+        format!("HTTP stream on port {}", self.port)
+    }
+}
+
+/// Plays in real time, whether anyone listens or not
+fn pump(state: &Mutex<NetState>, running: &AtomicBool) {
+    let start = Instant::now();
+    let mut frames_sent = 0;
+    while running.load(Ordering::Relaxed) {
+        let frames_due = (start.elapsed().as_secs_f64() * f64::from(SAMPLERATE)) as usize;
+        let mut state = state.lock();
+        let block: Arc<[f32]> = state.render(frames_due - frames_sent).into();
+        frames_sent = frames_due;
+        // Slow and disconnected clients are dropped
+        state
+            .clients
+            .retain(|client| client.try_send(Arc::clone(&block)).is_ok());
+        drop(state);
+        thread::sleep(PUMP_INTERVAL);
+    }
+}
+
+fn accept_clients(
+    listener: &TcpListener,
+    state: &Arc<Mutex<NetState>>,
+    running: &AtomicBool,
+    token: Option<&str>,
+) {
+    // Open connections, listening or not yet
+    let connections = Arc::new(AtomicUsize::new(0));
+    while running.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((mut stream, _)) => {
+                if connections.load(Ordering::Relaxed) >= MAX_CLIENTS {
+                    respond(&mut stream, "503 Service Unavailable");
+                    continue;
+                }
+                connections.fetch_add(1, Ordering::Relaxed);
+                let state = Arc::clone(state);
+                let connections = Arc::clone(&connections);
+                let token = token.map(str::to_owned);
+                thread::spawn(move || {
+                    serve_client(stream, &state, token.as_deref());
+                    connections.fetch_sub(1, Ordering::Relaxed);
+                });
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+            Err(e) => println!("Network stream: {e}"),
+        }
+    }
+}
+
+/// Any request with the token, if one is needed, gets the stream. Ends when the client leaves or
+/// is dropped.
+fn serve_client(mut stream: TcpStream, state: &Mutex<NetState>, token: Option<&str>) {
+    if stream.set_nonblocking(false).is_err() {
+        return;
+    }
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+    let Some(request) = read_request(&mut stream) else {
+        respond(&mut stream, "431 Request Header Fields Too Large");
+        return;
+    };
+    if let Some(token) = token {
+        if !request_token(&request).is_some_and(|given| is_same_token(given, token)) {
+            respond(&mut stream, "403 Forbidden");
+            return;
+        }
+    }
+    let (blocks_tx, blocks_rx) = mpsc::sync_channel(CLIENT_BACKLOG);
+    state.lock().clients.push(blocks_tx);
+    stream_to_client(stream, &blocks_rx);
+}
+
+/// Read the request up to its end, or until it's too long. Only the first line is kept.
+fn read_request(stream: &mut TcpStream) -> Option<String> {
+    let mut request = vec![];
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        if request.len() >= MAX_REQUEST {
+            return None;
+        }
+        let limit = buf.len().min(MAX_REQUEST - request.len());
+        match stream.read(&mut buf[..limit]) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+    let request = String::from_utf8_lossy(&request);
+    Some(request.lines().next().unwrap_or_default().to_owned())
+}
+
+/// The `token` query parameter of a request line, e.g. `GET /?token=abc HTTP/1.1`
+fn request_token(request_line: &str) -> Option<&str> {
+    let target = request_line.split_whitespace().nth(1)?;
+    let (_, query) = target.split_once('?')?;
+    query
+        .split('&')
+        .find_map(|param| param.strip_prefix("token="))
+}
+
+/// Answer without a stream, and close
+fn respond(stream: &mut TcpStream, status: &str) {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_write_timeout(Some(ACCEPT_INTERVAL));
+    let _ = write!(stream, "HTTP/1.0 {status}\r\nConnection: close\r\n\r\n");
+}
+
+#[cfg(target_arch = "wasm32")]
+fn stream_to_client(mut stream: TcpStream, _blocks_rx: &Receiver<Arc<[f32]>>) {
+    // No encoder in the web build. There are no sockets there either, so this isn't reached.
+    respond(&mut stream, "501 Not Implemented");
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn stream_to_client(mut stream: TcpStream, blocks_rx: &Receiver<Arc<[f32]>>) {
+    let head = "HTTP/1.0 200 OK\r\n\
+        Content-Type: audio/ogg\r\n\
+        Cache-Control: no-cache\r\n\
+        Connection: close\r\n\r\n";
+    if stream.write_all(head.as_bytes()).is_err() {
+        return;
+    }
+    let tags = [("TITLE", "SfontPlayer".into())];
+    let Ok(mut writer) = VorbisWriter::new(stream, CHANNELS, SAMPLERATE, STREAM_QUALITY, &tags)
+    else {
+        return;
+    };
+    for block in blocks_rx {
+        for &sample in block.iter() {
+            if writer.write_sample(sample).is_err() {
+                return;
+            }
+        }
+    }
+    // Dropped for falling behind. The end of the stream may still get through.
+    let _ = writer.finalize();
+}
+
+/// Address the stream can be listened at. With a token, other devices can use it too.
+#[must_use]
+pub fn stream_url(port: u16, token: Option<&str>) -> String {
+    let Some(token) = token else {
+        return format!("http://localhost:{port}/");
+    };
+    let host = local_ip().map_or_else(|| "localhost".into(), |ip| ip.to_string());
+    format!("http://{host}:{port}/?token={token}")
+}
+
+/// Address of the interface that the default route goes through. Nothing is actually sent.
+#[must_use]
+pub fn local_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("192.0.2.1:9").ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn source(frames: usize) -> StreamSource {
+        let samples = vec![0.5; frames * usize::from(CHANNELS)];
+        let source: BoxedSource = Box::new(SamplesBuffer::new(CHANNELS, SAMPLERATE, samples));
+        UniformSourceIterator::new(source, CHANNELS, SAMPLERATE)
+    }
+
+    #[test]
+    fn test_render() {
+        let mut state = NetState::default();
+        state.sources.push_back(source(SAMPLERATE as usize / 2));
+        state.volume = 0.5;
+
+        let samples = state.render(SAMPLERATE as usize / 4);
+        assert_eq!(samples.len(), SAMPLERATE as usize / 4 * 2);
+        assert!(samples
+            .iter()
+            .all(|&sample| (sample - 0.25).abs() < f32::EPSILON));
+        assert_eq!(state.get_pos(), Duration::from_millis(250));
+
+        // Paused: silence, and the position stays
+        state.paused = true;
+        assert!(state.render(100).iter().all(|&sample| sample == 0.));
+        assert_eq!(state.get_pos(), Duration::from_millis(250));
+
+        // Plays to the end, then silence
+        state.paused = false;
+        let samples = state.render(SAMPLERATE as usize / 2);
+        assert!(state.sources.is_empty());
+        assert_eq!(state.get_pos(), Duration::ZERO);
+        assert!(samples[samples.len() / 2..]
+            .iter()
+            .all(|&sample| sample == 0.));
+    }
+
+    /// Connection and the status line of the response
+    fn get(sink: &NetSink, request: &[u8]) -> (TcpStream, Vec<u8>) {
+        let mut stream = TcpStream::connect(("127.0.0.1", sink.get_port())).expect("connect");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .expect("timeout");
+        stream.write_all(request).expect("request");
+        let mut response = vec![];
+        let mut buf = [0; 1024];
+        while !response.windows(2).any(|w| w == b"\r\n") {
+            let n = stream.read(&mut buf).expect("response");
+            assert_ne!(n, 0, "disconnected");
+            response.extend_from_slice(&buf[..n]);
+        }
+        (stream, response)
+    }
+
+    #[test]
+    fn test_serve() {
+        let sink = NetSink::start(0, None).expect("free port");
+        let (mut stream, mut response) = get(&sink, b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let mut buf = [0; 1024];
+        while response.len() < 256 {
+            let n = stream.read(&mut buf).expect("response");
+            assert_ne!(n, 0);
+            response.extend_from_slice(&buf[..n]);
+        }
+        assert!(response.starts_with(b"HTTP/1.0 200 OK"));
+        assert!(response.windows(4).any(|w| w == b"OggS"));
+        assert!(response.windows(6).any(|w| w == b"vorbis"));
+        assert_eq!(sink.get_client_count(), 1);
+    }
+
+    #[test]
+    fn test_request_token() {
+        assert_eq!(request_token("GET /?token=abc HTTP/1.1"), Some("abc"));
+        assert_eq!(
+            request_token("GET /stream?a=1&token=abc HTTP/1.1"),
+            Some("abc")
+        );
+        assert_eq!(request_token("GET / HTTP/1.1"), None);
+        assert_eq!(request_token(""), None);
+    }
+
+    #[test]
+    fn test_token() {
+        let sink = NetSink::start(0, Some("secret".into())).expect("free port");
+        let (_, response) = get(&sink, b"GET / HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with(b"HTTP/1.0 403"));
+        let (_, response) = get(&sink, b"GET /?token=wrong HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with(b"HTTP/1.0 403"));
+        let (_, response) = get(&sink, b"GET /?token=secret HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with(b"HTTP/1.0 200"));
+    }
+
+    #[test]
+    fn test_long_request() {
+        let sink = NetSink::start(0, None).expect("free port");
+        let (_, response) = get(&sink, &[b'a'; MAX_REQUEST + 1]);
+        assert!(response.starts_with(b"HTTP/1.0 431"));
+        assert_eq!(sink.get_client_count(), 0);
+    }
+
+    #[test]
+    fn test_too_many_clients() {
+        let sink = NetSink::start(0, None).expect("free port");
+        let request = b"GET / HTTP/1.1\r\n\r\n";
+        let mut clients: Vec<_> = (0..MAX_CLIENTS).map(|_| get(&sink, request)).collect();
+        let (_, response) = get(&sink, request);
+        assert!(response.starts_with(b"HTTP/1.0 503"));
+
+        // Leaving makes room, once the stream notices
+        let (stream, _) = clients.pop().expect("client");
+        drop(stream);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let (_, response) = get(&sink, request);
+            if response.starts_with(b"HTTP/1.0 200") {
+                break;
+            }
+            assert!(Instant::now() < deadline, "no room made");
+            thread::sleep(PUMP_INTERVAL * 10);
+        }
+    }
+}
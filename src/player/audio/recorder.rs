@@ -0,0 +1,207 @@
+//! Recording of the live output into a wav file.
+//!
+//! The playing source is teed into the recorder, so font switches and mixer changes end up in
+//! the file just like they were heard. Samples are written from a background thread, so the
+//! audio thread never waits for the disk.
+
+use std::{
+    mem,
+    path::Path,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+
+/// Samples collected in the audio thread before they're handed to the writer
+const BLOCK_LEN: usize = 4096;
+
+/// Shared between the audio player and every playing source. Clones record into the same file.
+#[derive(Clone, Default)]
+pub struct Recorder(Arc<Mutex<Option<Sender<Vec<f32>>>>>);
+
+impl Recorder {
+    /// Create the file and start writing everything that plays into it. The receiver gets
+    /// write errors, and disconnects when the file has been finalized.
+    pub fn start(
+        &self,
+        path: &Path,
+        channels: u16,
+        sample_rate: u32,
+    ) -> anyhow::Result<Receiver<String>> {
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let writer = hound::WavWriter::create(path, spec)?;
+        let (samples_tx, samples_rx) = mpsc::channel();
+        let (errors_tx, errors_rx) = mpsc::channel();
+        thread::Builder::new().spawn(move || write_file(writer, &samples_rx, &errors_tx))?;
+        *self.0.lock() = Some(samples_tx);
+        Ok(errors_rx)
+    }
+    /// Stop recording. The file is finalized once the writer has caught up.
+    pub fn stop(&self) {
+        *self.0.lock() = None;
+    }
+    pub fn is_recording(&self) -> bool {
+        self.0.lock().is_some()
+    }
+
+    fn send(&self, block: Vec<f32>) {
+        if let Some(samples) = &*self.0.lock() {
+            let _ = samples.send(block);
+        }
+    }
+}
+
+/// Ends when the recorder stops
+fn write_file<W>(
+    mut writer: hound::WavWriter<W>,
+    samples_rx: &Receiver<Vec<f32>>,
+    errors_tx: &Sender<String>,
+) where
+    W: std::io::Write + std::io::Seek,
+{
+    for block in samples_rx {
+        for sample in block {
+            if let Err(e) = writer.write_sample(sample) {
+                let _ = errors_tx.send(format!("Recording failed: {e}"));
+                return;
+            }
+        }
+    }
+    if let Err(e) = writer.finalize() {
+        let _ = errors_tx.send(format!("Couldn't finish the recording: {e}"));
+    }
+}
+
+/// Source wrapper that passes samples through and copies them to the recorder.
+pub struct Tee<S> {
+    source: S,
+    recorder: Recorder,
+    channels: u16,
+    /// Samples not yet sent
+    block: Vec<f32>,
+}
+impl<S> Tee<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    pub fn new(source: S, recorder: Recorder) -> Self {
+        let channels = source.channels();
+        Self {
+            source,
+            recorder,
+            channels,
+            block: Vec::with_capacity(BLOCK_LEN),
+        }
+    }
+}
+impl<S> Tee<S> {
+    fn flush(&mut self) {
+        if !self.recorder.is_recording() {
+            self.block.clear();
+            return;
+        }
+        let block = mem::replace(&mut self.block, Vec::with_capacity(BLOCK_LEN));
+        self.recorder.send(block);
+    }
+}
+
+impl<S> Drop for Tee<S> {
+    /// The source may be cleared between the left and right sample, which would swap the
+    /// channels of the rest of the file.
+    fn drop(&mut self) {
+        let channels = usize::from(self.channels.max(1));
+        while !self.block.len().is_multiple_of(channels) {
+            self.block.push(0.);
+        }
+        self.flush();
+    }
+}
+
+impl<S> Iterator for Tee<S>
+where
+    S: Iterator<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        self.block.push(sample);
+        if self.block.len() >= BLOCK_LEN {
+            self.flush();
+        }
+        Some(sample)
+    }
+}
+
+impl<S> rodio::Source for Tee<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.source.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn play(samples: Vec<f32>, recorder: &Recorder) -> Vec<f32> {
+        Tee::new(SamplesBuffer::new(2, 44100, samples), recorder.clone()).collect()
+    }
+
+    #[test]
+    fn test_record() {
+        let path = std::env::temp_dir().join(format!("sfontplayer_rec_{}.wav", std::process::id()));
+        let recorder = Recorder::default();
+        let samples = vec![0.1_f32, -0.1, 0.5, -0.5];
+
+        // Not recording: passes through, nothing is written
+        assert_eq!(play(samples.clone(), &recorder), samples);
+
+        let errors = recorder
+            .start(&path, 2, 44100)
+            .expect("file can be created");
+        assert!(recorder.is_recording());
+        assert_eq!(play(samples.clone(), &recorder), samples);
+        // A source that ends between channels is padded to a whole frame
+        play(vec![0.25], &recorder);
+        recorder.stop();
+        assert!(!recorder.is_recording());
+        // Disconnects after finalizing
+        assert!(errors.iter().next().is_none());
+
+        let mut reader = hound::WavReader::open(&path).expect("recording can be read");
+        assert_eq!(reader.spec().channels, 2);
+        let recorded: Vec<f32> = reader.samples::<f32>().map_while(Result::ok).collect();
+        assert_eq!(recorded, [0.1, -0.1, 0.5, -0.5, 0.25, 0.]);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,238 @@
+//! Output channel routing for multi-channel interfaces: the player can play to outputs 3/4
+//! instead of 1/2, e.g. to reach a mixer channel or a second pair of monitors.
+//!
+//! Rodio fills the extra channels of a wide stream with silence, but always puts the audio on
+//! the first two. So the stream is opened wide enough, and sources are widened to the stream's
+//! channel count with the audio moved to the chosen pair.
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use rodio::{
+    cpal::{
+        self,
+        traits::{DeviceTrait, HostTrait},
+    },
+    OutputStream, OutputStreamHandle, Sink, Source,
+};
+
+use super::{
+    backend::{AudioBackend, BoxedSource},
+    device_watch::find_output_device,
+};
+
+/// Outputs of a stereo pair, counting from 1 like on the interface
+#[must_use]
+pub fn pair_name(pair: u16) -> String {
+    format!("{}/{}", pair * 2 + 1, pair * 2 + 2)
+}
+
+/// Number of stereo pairs an output device has. None is the default device.
+pub fn pair_count(device: Option<&str>) -> u16 {
+    let max_channels = device
+        .and_then(find_output_device)
+        .or_else(|| cpal::default_host().default_output_device())
+        .and_then(|device| device.supported_output_configs().ok())
+        .and_then(|configs| configs.map(|config| config.channels()).max())
+        .unwrap_or(2);
+    (max_channels / 2).max(1)
+}
+
+/// Open a stream with enough channels for the pair. Also returns its channel count.
+///
+/// # Errors
+///
+/// Fails if the device doesn't have enough output channels for the pair, or the stream can't be
+/// opened.
+pub fn open_stream(
+    device: &cpal::Device,
+    pair: u16,
+) -> anyhow::Result<(OutputStream, OutputStreamHandle, u16)> {
+    let default_config = device.default_output_config()?;
+    let needed = (pair + 1) * 2;
+    let config = if default_config.channels() >= needed {
+        default_config
+    } else {
+        device
+            .supported_output_configs()?
+            .filter(|config| config.channels() >= needed)
+            .min_by_key(cpal::SupportedStreamConfigRange::channels)
+            .map(|config| {
+                config
+                    .try_with_sample_rate(default_config.sample_rate())
+                    .unwrap_or_else(|| config.with_max_sample_rate())
+            })
+            .ok_or_else(|| anyhow!("The device has no outputs {}", pair_name(pair)))?
+    };
+    let channels = config.channels();
+    let (stream, stream_handle) = OutputStream::try_from_device_config(device, config)?;
+    Ok((stream, stream_handle, channels))
+}
+
+/// Rodio sink that plays to a stereo pair of a stream
+pub struct RoutedSink {
+    sink: Sink,
+    /// Channel count of the stream
+    channels: u16,
+    pair: u16,
+}
+impl RoutedSink {
+    pub const fn new(sink: Sink, channels: u16, pair: u16) -> Self {
+        Self {
+            sink,
+            channels,
+            pair,
+        }
+    }
+}
+
+impl AudioBackend for RoutedSink {
+    fn append(&self, source: BoxedSource) {
+        if self.pair == 0 {
+            // Rodio puts audio on the first pair anyway
+            self.sink.append(source);
+        } else {
+            let first_channel = self.pair * 2;
+            self.sink
+                .append(ChannelRouter::new(source, self.channels, first_channel));
+        }
+    }
+    fn play(&self) {
+        self.sink.play();
+    }
+    fn pause(&self) {
+        self.sink.pause();
+    }
+    fn is_paused(&self) -> bool {
+        self.sink.is_paused()
+    }
+    fn clear(&self) {
+        self.sink.clear();
+    }
+    fn empty(&self) -> bool {
+        self.sink.empty()
+    }
+    fn queue_len(&self) -> usize {
+        self.sink.len()
+    }
+    fn set_volume(&self, volume: f32) {
+        self.sink.set_volume(volume);
+    }
+    fn get_pos(&self) -> Duration {
+        self.sink.get_pos()
+    }
+    fn try_seek(&self, pos: Duration) {
+        let _ = self.sink.try_seek(pos);
+    }
+    fn get_name(&self) -> String {
+        format!(
+            "{}, outputs {}",
+            AudioBackend::get_name(&self.sink),
+            pair_name(self.pair)
+        )
+    }
+}
+
+/// Widens a source to `channels` channels, with its own channels starting at `first_channel`.
+/// The rest are silent.
+pub struct ChannelRouter<S> {
+    source: S,
+    channels: u16,
+    first_channel: u16,
+    /// Input samples of the frame being output
+    frame: Vec<f32>,
+    /// Next output channel
+    channel: u16,
+}
+impl<S> ChannelRouter<S>
+where
+    S: Source<Item = f32>,
+{
+    pub const fn new(source: S, channels: u16, first_channel: u16) -> Self {
+        Self {
+            source,
+            channels,
+            first_channel,
+            frame: Vec::new(),
+            channel: 0,
+        }
+    }
+}
+
+impl<S> Iterator for ChannelRouter<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.channel == 0 {
+            self.frame.clear();
+            for _ in 0..self.source.channels() {
+                self.frame.push(self.source.next()?);
+            }
+        }
+        let sample = self
+            .channel
+            .checked_sub(self.first_channel)
+            .and_then(|index| self.frame.get(usize::from(index)))
+            .copied()
+            .unwrap_or_default();
+        self.channel = (self.channel + 1) % self.channels;
+        Some(sample)
+    }
+}
+
+impl<S> Source for ChannelRouter<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        // Counted in whole input frames, each of which becomes one output frame
+        let input_channels = usize::from(self.source.channels());
+        self.source
+            .current_frame_len()
+            .map(|len| len / input_channels * usize::from(self.channels))
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.channel = 0;
+        self.source.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    #[test]
+    fn test_channel_router() {
+        let source = SamplesBuffer::new(2, 44100, vec![0.1, 0.2, 0.3, 0.4]);
+        let router = ChannelRouter::new(source, 6, 2);
+        assert_eq!(router.channels(), 6);
+        let samples: Vec<f32> = router.collect();
+        assert_eq!(
+            samples,
+            vec![0., 0., 0.1, 0.2, 0., 0., 0., 0., 0.3, 0.4, 0., 0.]
+        );
+    }
+
+    #[test]
+    fn test_pair_name() {
+        assert_eq!(pair_name(0), "1/2");
+        assert_eq!(pair_name(1), "3/4");
+    }
+}
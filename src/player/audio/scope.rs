@@ -0,0 +1,116 @@
+//! Latest output samples, shared from the audio thread to the GUI for visualization.
+//!
+
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use parking_lot::Mutex;
+
+/// Frames kept for visualizers
+pub const SCOPE_LEN: usize = 2048;
+/// Samples collected in the audio thread before they're handed over
+const BLOCK_LEN: usize = 512;
+
+/// Shared between the audio player and the playing source. Stereo.
+#[derive(Debug, Clone, Default)]
+pub struct Scope(Arc<Mutex<VecDeque<[f32; 2]>>>);
+
+impl Scope {
+    /// Latest frames, oldest first
+    pub fn get_frames(&self) -> Vec<[f32; 2]> {
+        self.0.lock().iter().copied().collect()
+    }
+    pub fn clear(&self) {
+        self.0.lock().clear();
+    }
+
+    fn push(&self, samples: &[f32]) {
+        let mut frames = self.0.lock();
+        frames.extend(samples.chunks_exact(2).map(|frame| [frame[0], frame[1]]));
+        let excess = frames.len().saturating_sub(SCOPE_LEN);
+        frames.drain(..excess);
+    }
+}
+
+/// Source wrapper that passes stereo samples through and copies them to the scope.
+pub struct ScopeTap<S> {
+    source: S,
+    scope: Scope,
+    /// Samples not yet handed over
+    block: Vec<f32>,
+}
+impl<S> ScopeTap<S> {
+    pub fn new(source: S, scope: Scope) -> Self {
+        Self {
+            source,
+            scope,
+            block: Vec::with_capacity(BLOCK_LEN),
+        }
+    }
+}
+
+impl<S> Iterator for ScopeTap<S>
+where
+    S: Iterator<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        self.block.push(sample);
+        if self.block.len() >= BLOCK_LEN {
+            self.scope.push(&self.block);
+            self.block.clear();
+        }
+        Some(sample)
+    }
+}
+
+impl<S> rodio::Source for ScopeTap<S>
+where
+    S: rodio::Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.source.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope() {
+        let scope = Scope::default();
+        let samples: Vec<f32> = (0..SCOPE_LEN * 3).map(|i| i as f32).collect();
+        let played: Vec<f32> = ScopeTap::new(samples.clone().into_iter(), scope.clone()).collect();
+        assert_eq!(played, samples);
+
+        let frames = scope.get_frames();
+        assert_eq!(frames.len(), SCOPE_LEN);
+        // Newest last
+        let last = samples.len() - 1;
+        assert_eq!(
+            frames[SCOPE_LEN - 1].map(|sample| sample as usize),
+            [last - 1, last]
+        );
+
+        scope.clear();
+        assert!(scope.get_frames().is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+//! Playback speed: the sequencer moves through the song faster or slower, while the synth
+//! renders at the normal rate, so the pitch stays the same.
+//!
+//! The sink counts output time since its last seek. The song moves at a different rate, so
+//! its position is worked out from where the sink was when the speed last changed.
+
+use std::{
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+pub const SPEED_RANGE: RangeInclusive<f32> = 0.25..=4.0;
+
+/// Speed multiplier shared between the audio player and the playing source. Stored as f32
+/// bits.
+#[derive(Debug, Clone)]
+pub struct PlaybackSpeed(Arc<AtomicU32>);
+impl Default for PlaybackSpeed {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU32::new(1.0_f32.to_bits())))
+    }
+}
+impl PlaybackSpeed {
+    #[must_use]
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+    /// Kept within `SPEED_RANGE`
+    pub fn set(&self, speed: f32) {
+        let speed = speed.clamp(*SPEED_RANGE.start(), *SPEED_RANGE.end());
+        self.0.store(speed.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Sink position and song position at the same moment
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpeedAnchor {
+    sink_pos: Duration,
+    song_pos: Duration,
+}
+impl SpeedAnchor {
+    #[must_use]
+    pub const fn new(sink_pos: Duration, song_pos: Duration) -> Self {
+        Self { sink_pos, song_pos }
+    }
+    /// After a seek, the sink and the song are at the same position.
+    #[must_use]
+    pub const fn at(pos: Duration) -> Self {
+        Self::new(pos, pos)
+    }
+    /// Song position when the sink is at `sink_pos`, if the speed hasn't changed since
+    #[must_use]
+    pub fn song_position(self, sink_pos: Duration, speed: f32) -> Duration {
+        self.song_pos
+            + sink_pos
+                .saturating_sub(self.sink_pos)
+                .mul_f64(f64::from(speed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speed_anchor() {
+        let secs = Duration::from_secs;
+        assert_eq!(
+            SpeedAnchor::at(secs(10)).song_position(secs(14), 1.),
+            secs(14)
+        );
+        assert_eq!(
+            SpeedAnchor::at(secs(10)).song_position(secs(14), 0.5),
+            secs(12)
+        );
+        // Sped up at 10 s into the song, when the sink was at 20 s
+        let anchor = SpeedAnchor::new(secs(20), secs(10));
+        assert_eq!(anchor.song_position(secs(22), 2.), secs(14));
+    }
+
+    #[test]
+    fn test_speed_range() {
+        let speed = PlaybackSpeed::default();
+        assert!((speed.get() - 1.).abs() < f32::EPSILON);
+        speed.set(10.);
+        assert!((speed.get() - 4.).abs() < f32::EPSILON);
+    }
+}
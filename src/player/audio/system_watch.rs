@@ -0,0 +1,150 @@
+//! Notices the computer going to sleep and headphones being unplugged, so playback can pause
+//! instead of carrying on from the speakers.
+//!
+//! On Linux, logind announces suspend over D-Bus, and headphone jacks are ALSA controls. Other
+//! platforms aren't watched yet. A lost output device is noticed by the device watcher instead.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemChange {
+    Suspending,
+    HeadphonesUnplugged,
+}
+impl SystemChange {
+    #[must_use]
+    pub const fn get_reason(self) -> &'static str {
+        match self {
+            Self::Suspending => "the computer went to sleep",
+            Self::HeadphonesUnplugged => "headphones were unplugged",
+        }
+    }
+}
+
+/// Background threads that listen to the system. They stop when this is dropped.
+pub struct SystemWatcher {
+    stop: Arc<AtomicBool>,
+    changes: Receiver<SystemChange>,
+}
+impl SystemWatcher {
+    #[must_use]
+    pub fn start() -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (changes_tx, changes_rx) = mpsc::channel();
+        {
+            let stop = Arc::clone(&stop);
+            let changes_tx = changes_tx.clone();
+            // Without threads (web build), nothing is watched.
+            let _ = thread::Builder::new().spawn(move || watch_suspend(&stop, &changes_tx));
+        }
+        {
+            let stop = Arc::clone(&stop);
+            let _ = thread::Builder::new().spawn(move || watch_jacks(&stop, &changes_tx));
+        }
+        Self {
+            stop,
+            changes: changes_rx,
+        }
+    }
+
+    /// Changes since last call
+    #[must_use]
+    pub fn take_changes(&self) -> Vec<SystemChange> {
+        self.changes.try_iter().collect()
+    }
+}
+impl Drop for SystemWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn watch_suspend(stop: &AtomicBool, changes_tx: &Sender<SystemChange>) {
+    use dbus::{blocking::Connection, message::MatchRule};
+    use std::time::Duration;
+
+    /// How often the stop flag is checked
+    const PROCESS_TIMEOUT: Duration = Duration::from_millis(500);
+
+    let Ok(connection) = Connection::new_system() else {
+        println!("Couldn't connect to the system bus, suspend isn't watched.");
+        return;
+    };
+    let sender = changes_tx.clone();
+    let rule = MatchRule::new_signal("org.freedesktop.login1.Manager", "PrepareForSleep");
+    // The signal comes with true before sleeping, and with false after waking up.
+    let added = connection.add_match(rule, move |(sleeping,): (bool,), _, _| {
+        if sleeping {
+            let _ = sender.send(SystemChange::Suspending);
+        }
+        true
+    });
+    if let Err(e) = added {
+        println!("Couldn't listen to logind, suspend isn't watched: {e}");
+        return;
+    }
+    while !stop.load(Ordering::Relaxed) {
+        if connection.process(PROCESS_TIMEOUT).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn watch_jacks(stop: &AtomicBool, changes_tx: &Sender<SystemChange>) {
+    use alsa::{card, hctl::HCtl};
+    use std::time::Duration;
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Headphone jack controls, like "Headphone Jack" or "Front Headphone Jack"
+    fn is_headphone_jack(name: &str) -> bool {
+        name.ends_with(" Jack") && (name.contains("Headphone") || name.contains("Headset"))
+    }
+
+    let controls: Vec<HCtl> = card::Iter::new()
+        .filter_map(Result::ok)
+        .filter_map(|card| HCtl::from_card(&card, false).ok())
+        .filter(|hctl| hctl.load().is_ok())
+        .collect();
+    let read_jacks = || -> Vec<bool> {
+        controls
+            .iter()
+            .flat_map(HCtl::elem_iter)
+            .filter(|elem| {
+                elem.get_id()
+                    .is_ok_and(|id| id.get_name().is_ok_and(is_headphone_jack))
+            })
+            .map(|elem| {
+                elem.read()
+                    .ok()
+                    .and_then(|value| value.get_boolean(0))
+                    .unwrap_or_default()
+            })
+            .collect()
+    };
+
+    let mut plugged = read_jacks();
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(POLL_INTERVAL);
+        let now = read_jacks();
+        if plugged.iter().zip(&now).any(|(&was, &is)| was && !is) {
+            let _ = changes_tx.send(SystemChange::HeadphonesUnplugged);
+        }
+        plugged = now;
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+const fn watch_suspend(_stop: &AtomicBool, _changes_tx: &Sender<SystemChange>) {}
+
+#[cfg(not(target_os = "linux"))]
+const fn watch_jacks(_stop: &AtomicBool, _changes_tx: &Sender<SystemChange>) {}
@@ -0,0 +1,182 @@
+//! Ogg Vorbis export and the network stream, on top of libvorbis through `vorbis_rs`.
+//!
+//! Nothing is rewritten afterwards, so the output doesn't have to be seekable.
+
+use std::{
+    io::{self, Write},
+    num::{NonZeroU32, NonZeroU8},
+};
+
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoder, VorbisEncoderBuilder};
+
+/// Samples per channel handed to the encoder at a time
+const BLOCK_SIZE: usize = 1024;
+
+/// Like `FlacWriter`, takes interleaved samples
+pub struct VorbisWriter<W: Write> {
+    encoder: VorbisEncoder<W>,
+    /// Samples of each channel collected for the next block
+    buffers: Vec<Vec<f32>>,
+    /// Channel of the next interleaved sample
+    next_channel: usize,
+}
+
+impl<W: Write> VorbisWriter<W> {
+    /// Writes the headers right away. `quality` is 0.0..=1.0, higher makes bigger files.
+    /// `tags` go into the comment header, e.g. `TITLE`.
+    pub fn new(
+        out: W,
+        channels: u16,
+        sample_rate: u32,
+        quality: f32,
+        tags: &[(&str, String)],
+    ) -> io::Result<Self> {
+        let (Some(channel_count), Some(sample_rate)) = (
+            u8::try_from(channels).ok().and_then(NonZeroU8::new),
+            NonZeroU32::new(sample_rate),
+        ) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unsupported Vorbis format",
+            ));
+        };
+        let mut builder =
+            VorbisEncoderBuilder::new(sample_rate, channel_count, out).map_err(io::Error::other)?;
+        builder
+            .bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
+                target_quality: quality.clamp(0., 1.),
+            })
+            .comment_tags(tags.iter().map(|(key, value)| (*key, value.as_str())))
+            .map_err(io::Error::other)?;
+        let encoder = builder.build().map_err(io::Error::other)?;
+        Ok(Self {
+            encoder,
+            buffers: vec![Vec::with_capacity(BLOCK_SIZE); usize::from(channels)],
+            next_channel: 0,
+        })
+    }
+
+    /// Add one interleaved sample, -1.0..=1.0
+    pub fn write_sample(&mut self, sample: f32) -> io::Result<()> {
+        self.buffers[self.next_channel].push(sample);
+        self.next_channel = (self.next_channel + 1) % self.buffers.len();
+        if self.next_channel == 0 && self.buffers[0].len() == BLOCK_SIZE {
+            self.write_block()?;
+        }
+        Ok(())
+    }
+
+    /// Encode the rest, and end the stream.
+    pub fn finalize(mut self) -> io::Result<W> {
+        // The source may end between channels
+        while self.next_channel != 0 {
+            self.write_sample(0.)?;
+        }
+        if !self.buffers[0].is_empty() {
+            self.write_block()?;
+        }
+        self.encoder.finish().map_err(io::Error::other)
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        self.encoder
+            .encode_audio_block(&self.buffers)
+            .map_err(io::Error::other)?;
+        for buffer in &mut self.buffers {
+            buffer.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f32::consts::PI, io::Cursor};
+
+    use lewton::inside_ogg::OggStreamReader;
+
+    use super::*;
+
+    fn encode(channels: u16, quality: f32, input: &[f32]) -> Vec<u8> {
+        let tags = [("TITLE", "Song".into())];
+        let mut writer = VorbisWriter::new(Cursor::new(vec![]), channels, 44100, quality, &tags)
+            .expect("valid format");
+        for &sample in input {
+            writer.write_sample(sample).expect("write");
+        }
+        writer.finalize().expect("finalize").into_inner()
+    }
+
+    /// Interleaved samples
+    fn decode(bytes: Vec<u8>) -> (OggStreamReader<Cursor<Vec<u8>>>, Vec<f32>) {
+        let mut reader = OggStreamReader::new(Cursor::new(bytes)).expect("valid headers");
+        let mut samples = vec![];
+        while let Some(packet) = reader
+            .read_dec_packet_generic::<lewton::samples::InterleavedSamples<f32>>()
+            .expect("valid packet")
+        {
+            samples.extend(packet.samples);
+        }
+        (reader, samples)
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let len = BLOCK_SIZE * 5 + 100;
+        let input: Vec<f32> = (0..len)
+            .flat_map(|i| {
+                let t = i as f32 / 44100.;
+                let left = (t * 440. * 2. * PI)
+                    .sin()
+                    .mul_add(0.5, (t * 3000. * 2. * PI).sin() * 0.1);
+                // Silent for the first blocks
+                let right = if i < BLOCK_SIZE * 2 {
+                    0.
+                } else {
+                    (t * 220. * 2. * PI).sin() * 0.8
+                };
+                [left, right]
+            })
+            .collect();
+        let (reader, output) = decode(encode(2, 0.5, &input));
+
+        assert_eq!(reader.ident_hdr.audio_channels, 2);
+        assert_eq!(reader.ident_hdr.audio_sample_rate, 44100);
+        assert_eq!(
+            reader.comment_hdr.comment_list,
+            [("TITLE".into(), "Song".into())]
+        );
+        // lewton doesn't cut the padding off the last packet, the granule position has the length
+        assert_eq!(reader.get_last_absgp(), Some(len as u64));
+        let noise: f32 = input
+            .iter()
+            .zip(&output)
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum();
+        let signal: f32 = input.iter().map(|a| a * a).sum();
+        let snr = 10. * (signal / noise).log10();
+        assert!(snr > 30., "SNR {snr} dB");
+        assert!(output[1..BLOCK_SIZE]
+            .iter()
+            .step_by(2)
+            .all(|sample| sample.abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_short_and_empty() {
+        for len in [0, 1, 100, BLOCK_SIZE, BLOCK_SIZE * 3 + 1] {
+            let input = vec![0.25; len];
+            let (reader, output) = decode(encode(1, 0.5, &input));
+            assert_eq!(reader.get_last_absgp().unwrap_or(0), len as u64);
+            assert!(output.len() >= len);
+        }
+    }
+
+    #[test]
+    fn test_quality() {
+        let input: Vec<f32> = (0..44100)
+            .map(|i| ((i * 7919 % 1000) as f32 / 500.) - 1.)
+            .collect();
+        assert!(encode(1, 0., &input).len() < encode(1, 1., &input).len());
+    }
+}
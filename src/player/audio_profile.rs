@@ -0,0 +1,86 @@
+//! Named audio profiles: reverb, EQ and gain, assignable per playlist.
+
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+/// Bass, mid, treble
+pub const EQ_BANDS: usize = 3;
+pub const EQ_BAND_NAMES: [&str; EQ_BANDS] = ["Bass", "Mid", "Treble"];
+/// Decibels, for both EQ bands and gain
+pub const GAIN_RANGE: RangeInclusive<f32> = -12.0..=12.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioProfile {
+    /// Playlists refer to profiles by name
+    pub name: String,
+    /// Soundfont's own reverb and chorus, controlled by the song
+    pub synth_effects: bool,
+    /// Added reverb, 0.0 is dry and 1.0 is fully wet
+    pub reverb_mix: f32,
+    /// Reverb decay, 0.0..=1.0
+    pub room_size: f32,
+    /// Band gains in dB
+    pub eq: [f32; EQ_BANDS],
+    /// Output gain in dB
+    pub gain: f32,
+}
+impl Default for AudioProfile {
+    fn default() -> Self {
+        Self {
+            name: "Default".to_owned(),
+            synth_effects: true,
+            reverb_mix: 0.,
+            room_size: 0.5,
+            eq: [0.; EQ_BANDS],
+            gain: 0.,
+        }
+    }
+}
+impl AudioProfile {
+    #[must_use]
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+
+    /// Post-processing would do nothing
+    #[must_use]
+    pub fn is_neutral(&self) -> bool {
+        self.reverb_mix <= 0. && self.gain == 0. && self.eq.iter().all(|band| *band == 0.)
+    }
+
+    /// Same settings, ignoring the name
+    #[must_use]
+    pub fn sounds_like(&self, other: &Self) -> bool {
+        Self {
+            name: String::new(),
+            ..self.clone()
+        } == Self {
+            name: String::new(),
+            ..other.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_neutral() {
+        let mut profile = AudioProfile::new("Dry".to_owned());
+        assert!(profile.is_neutral());
+        profile.synth_effects = false;
+        assert!(profile.is_neutral());
+        assert!(profile.sounds_like(&AudioProfile {
+            synth_effects: false,
+            ..Default::default()
+        }));
+        profile.eq[1] = 3.;
+        assert!(!profile.is_neutral());
+    }
+}
@@ -0,0 +1,120 @@
+//! Auto-DJ: endless random play from a directory. Songs that haven't been played for the
+//! longest time are preferred, so repeats stay rare.
+
+use std::path::{Path, PathBuf};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use walkdir::WalkDir;
+
+use super::play_stats::PlayStats;
+
+/// How many songs are kept queued after the playing one
+pub const AUTO_DJ_LOOKAHEAD: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct AutoDj {
+    dir: PathBuf,
+    songs: Vec<PathBuf>,
+    /// Playlist the songs are added to
+    playlist_id: usize,
+}
+
+impl AutoDj {
+    /// Find all midi files in a directory and its subdirectories.
+    pub fn new(dir: PathBuf) -> Self {
+        let mut songs: Vec<PathBuf> = WalkDir::new(&dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(walkdir::DirEntry::into_path)
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("mid"))
+            })
+            .collect();
+        songs.sort();
+        Self {
+            dir,
+            songs,
+            playlist_id: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn get_dir(&self) -> &Path {
+        &self.dir
+    }
+    #[must_use]
+    pub const fn get_playlist_id(&self) -> usize {
+        self.playlist_id
+    }
+    pub const fn set_playlist_id(&mut self, playlist_id: usize) {
+        self.playlist_id = playlist_id;
+    }
+    #[must_use]
+    pub const fn get_song_count(&self) -> usize {
+        self.songs.len()
+    }
+
+    /// Choose a random song that hasn't been played yet, or from the least recently played half.
+    /// Songs in `exclude` are skipped, unless there's nothing else.
+    #[must_use]
+    pub fn pick(&self, stats: &PlayStats, exclude: &[PathBuf], seed: u64) -> Option<PathBuf> {
+        let mut candidates: Vec<&PathBuf> = self
+            .songs
+            .iter()
+            .filter(|song| !exclude.contains(song))
+            .collect();
+        if candidates.is_empty() {
+            candidates = self.songs.iter().collect();
+        }
+        if candidates.iter().any(|song| stats.get(song).is_none()) {
+            candidates.retain(|song| stats.get(song).is_none());
+        } else {
+            candidates.sort_by_key(|song| stats.get_last_played(song));
+            candidates.truncate(candidates.len().div_ceil(2));
+        }
+        candidates
+            .choose(&mut StdRng::seed_from_u64(seed))
+            .map(|song| (*song).clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auto_dj(songs: &[&str]) -> AutoDj {
+        AutoDj {
+            dir: PathBuf::new(),
+            songs: songs.iter().map(PathBuf::from).collect(),
+            playlist_id: 0,
+        }
+    }
+
+    #[test]
+    fn test_pick_avoids_recent() {
+        let dj = auto_dj(&["a.mid", "b.mid", "c.mid", "d.mid"]);
+        let mut stats = PlayStats::default();
+        stats.record_play(Path::new("a.mid"), 10);
+        stats.record_play(Path::new("b.mid"), 20);
+        for seed in 0..20 {
+            let song = dj.pick(&stats, &[], seed).expect("pick");
+            assert!(song == Path::new("c.mid") || song == Path::new("d.mid"));
+        }
+        // Already queued
+        let exclude = [PathBuf::from("c.mid"), PathBuf::from("d.mid")];
+        assert_eq!(dj.pick(&stats, &exclude, 0), Some(PathBuf::from("a.mid")));
+    }
+
+    #[test]
+    fn test_pick_single_song() {
+        let dj = auto_dj(&["a.mid"]);
+        let exclude = [PathBuf::from("a.mid")];
+        assert_eq!(
+            dj.pick(&PlayStats::default(), &exclude, 0),
+            Some(PathBuf::from("a.mid"))
+        );
+        assert_eq!(auto_dj(&[]).pick(&PlayStats::default(), &[], 0), None);
+    }
+}
@@ -0,0 +1,31 @@
+//! Pausing by itself when the computer goes to sleep or the sound would move to the speakers.
+//! Playback doesn't resume by itself; that's for the user to do.
+
+use super::{audio::system_watch::SystemWatcher, Player, PlayerEvent};
+
+impl Player {
+    pub const fn get_auto_pause(&self) -> bool {
+        self.system_watcher.is_some()
+    }
+    pub fn set_auto_pause(&mut self, enabled: bool) {
+        self.system_watcher = enabled.then(SystemWatcher::start);
+    }
+
+    /// Pause because of something outside the player, and tell why.
+    pub fn auto_pause(&mut self, reason: &str) {
+        if !self.is_playing() || self.is_paused() {
+            return;
+        }
+        self.pause();
+        self.emit(&PlayerEvent::AutoPaused(reason.into()));
+    }
+
+    pub(super) fn update_auto_pause(&mut self) {
+        let Some(watcher) = &self.system_watcher else {
+            return;
+        };
+        for change in watcher.take_changes() {
+            self.auto_pause(change.get_reason());
+        }
+    }
+}
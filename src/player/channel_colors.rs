@@ -0,0 +1,190 @@
+//! Colors of MIDI channels in visualizers: Now Playing meters and piano roll export.
+
+use serde::{Deserialize, Serialize};
+
+use super::program_lock::CHANNEL_COUNT;
+
+type Palette = [[u8; 3]; CHANNEL_COUNT];
+
+const RAINBOW: Palette = [
+    [230, 80, 80],
+    [230, 150, 60],
+    [220, 210, 70],
+    [140, 210, 70],
+    [60, 200, 110],
+    [60, 200, 190],
+    [70, 160, 230],
+    [90, 110, 230],
+    [150, 90, 230],
+    [210, 210, 210],
+    [220, 90, 200],
+    [230, 110, 150],
+    [170, 120, 80],
+    [120, 170, 150],
+    [150, 150, 200],
+    [200, 170, 120],
+];
+const PASTEL: Palette = [
+    [244, 170, 170],
+    [244, 200, 160],
+    [240, 232, 170],
+    [200, 236, 170],
+    [166, 230, 190],
+    [166, 230, 226],
+    [170, 206, 244],
+    [182, 190, 244],
+    [208, 180, 244],
+    [230, 230, 230],
+    [236, 180, 228],
+    [244, 190, 210],
+    [214, 190, 166],
+    [190, 214, 204],
+    [204, 204, 228],
+    [228, 214, 190],
+];
+const WARM: Palette = [
+    [250, 90, 60],
+    [250, 130, 60],
+    [250, 170, 60],
+    [250, 210, 80],
+    [220, 100, 90],
+    [220, 140, 90],
+    [220, 180, 100],
+    [200, 80, 120],
+    [230, 120, 140],
+    [240, 230, 200],
+    [180, 70, 60],
+    [180, 110, 60],
+    [180, 150, 80],
+    [160, 80, 100],
+    [210, 160, 130],
+    [230, 200, 150],
+];
+const COOL: Palette = [
+    [60, 130, 250],
+    [60, 170, 250],
+    [60, 210, 240],
+    [80, 230, 200],
+    [100, 110, 230],
+    [130, 150, 240],
+    [120, 200, 220],
+    [90, 210, 160],
+    [150, 120, 230],
+    [220, 230, 240],
+    [70, 100, 180],
+    [70, 140, 180],
+    [80, 170, 160],
+    [110, 90, 180],
+    [150, 180, 210],
+    [170, 210, 200],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ChannelPalette {
+    #[default]
+    Rainbow,
+    Pastel,
+    Warm,
+    Cool,
+    /// User picked colors
+    Custom,
+}
+impl ChannelPalette {
+    pub const ALL: [Self; 5] = [
+        Self::Rainbow,
+        Self::Pastel,
+        Self::Warm,
+        Self::Cool,
+        Self::Custom,
+    ];
+
+    #[must_use]
+    pub const fn get_name(self) -> &'static str {
+        match self {
+            Self::Rainbow => "Rainbow",
+            Self::Pastel => "Pastel",
+            Self::Warm => "Warm",
+            Self::Cool => "Cool",
+            Self::Custom => "Custom",
+        }
+    }
+
+    /// Colors of a built-in palette
+    const fn get_colors(self) -> Option<&'static Palette> {
+        match self {
+            Self::Rainbow => Some(&RAINBOW),
+            Self::Pastel => Some(&PASTEL),
+            Self::Warm => Some(&WARM),
+            Self::Cool => Some(&COOL),
+            Self::Custom => None,
+        }
+    }
+}
+
+/// Channel to color mapping, shared by all visualizers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelColors {
+    palette: ChannelPalette,
+    /// Used when palette is `Custom`. Kept when switching to another palette.
+    custom: Palette,
+}
+impl Default for ChannelColors {
+    fn default() -> Self {
+        Self {
+            palette: ChannelPalette::default(),
+            custom: RAINBOW,
+        }
+    }
+}
+impl ChannelColors {
+    #[must_use]
+    pub const fn get_palette(&self) -> ChannelPalette {
+        self.palette
+    }
+    pub const fn set_palette(&mut self, palette: ChannelPalette) {
+        self.palette = palette;
+    }
+
+    /// RGB color of a channel, 0-indexed
+    #[must_use]
+    pub const fn get_color(&self, channel: usize) -> [u8; 3] {
+        let colors = match self.palette.get_colors() {
+            Some(colors) => colors,
+            None => &self.custom,
+        };
+        colors[channel % CHANNEL_COUNT]
+    }
+
+    /// Change one channel. A built-in palette becomes the starting point of custom colors.
+    pub const fn set_color(&mut self, channel: usize, color: [u8; 3]) {
+        if let Some(colors) = self.palette.get_colors() {
+            self.custom = *colors;
+            self.palette = ChannelPalette::Custom;
+        }
+        self.custom[channel % CHANNEL_COUNT] = color;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_color() {
+        let mut colors = ChannelColors::default();
+        colors.set_palette(ChannelPalette::Cool);
+        colors.set_color(2, [1, 2, 3]);
+
+        assert_eq!(colors.get_palette(), ChannelPalette::Custom);
+        assert_eq!(colors.get_color(2), [1, 2, 3]);
+        // Rest of the channels come from the previous palette
+        assert_eq!(colors.get_color(0), COOL[0]);
+
+        // Custom colors survive a palette switch
+        colors.set_palette(ChannelPalette::Pastel);
+        assert_eq!(colors.get_color(2), PASTEL[2]);
+        colors.set_palette(ChannelPalette::Custom);
+        assert_eq!(colors.get_color(2), [1, 2, 3]);
+    }
+}
@@ -0,0 +1,174 @@
+//! Generated cover art for media controls: a colored square with the song's initials.
+//!
+//! MIDI files have no artwork, and a blank cover makes songs hard to tell apart in the OS
+//! media widget. The color comes from the title, so a song always gets the same cover.
+
+use std::{fs, path::PathBuf};
+
+use image::{Rgb, RgbImage};
+
+use super::serialize_player::cache_dir;
+
+const COVER_SIZE: u32 = 128;
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+/// Pixels per glyph dot
+const GLYPH_SCALE: u32 = 8;
+const TEXT_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
+
+/// 5x7 glyphs of A-Z and 0-9. Each row is 5 bits, the highest bit on the left.
+#[rustfmt::skip]
+const GLYPHS: [[u8; 7]; 36] = [
+    [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // A
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // B
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // C
+    [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E], // D
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // E
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // F
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // G
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // H
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // I
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // L
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // O
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // P
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // Q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // R
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // S
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // W
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // X
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // Y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // Z
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // 0
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // 1
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // 2
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // 3
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // 4
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // 5
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // 6
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // 8
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // 9
+];
+
+/// `file://` URL of the cover for a title. The image is written on first use.
+pub fn cover_url(title: &str) -> Option<String> {
+    let dir = cache_dir().join("covers");
+    let path: PathBuf = dir.join(format!("{:016x}.png", title_hash(title)));
+    if !path.exists() {
+        fs::create_dir_all(&dir).ok()?;
+        render_cover(title).save(&path).ok()?;
+    }
+    Some(format!("file://{}", path.display()))
+}
+
+/// First letters of the first two words. Characters without a glyph are left out.
+fn initials(title: &str) -> String {
+    title
+        .split(|c: char| c.is_whitespace() || c == '_' || c == '-')
+        .filter_map(|word| word.chars().find(char::is_ascii_alphanumeric))
+        .take(2)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn glyph(c: char) -> Option<&'static [u8; 7]> {
+    let index = match c {
+        'A'..='Z' => c as usize - 'A' as usize,
+        '0'..='9' => 26 + c as usize - '0' as usize,
+        _ => return None,
+    };
+    GLYPHS.get(index)
+}
+
+fn render_cover(title: &str) -> RgbImage {
+    let mut image = RgbImage::from_pixel(COVER_SIZE, COVER_SIZE, title_color(title));
+
+    let text: Vec<_> = initials(title).chars().filter_map(glyph).collect();
+    let Some(count) = u32::try_from(text.len()).ok().filter(|&count| count > 0) else {
+        return image;
+    };
+    // One dot of space between letters
+    let text_width = (count * (GLYPH_WIDTH + 1) - 1) * GLYPH_SCALE;
+    let left = (COVER_SIZE - text_width) / 2;
+    let top = (COVER_SIZE - GLYPH_HEIGHT * GLYPH_SCALE) / 2;
+
+    for (index, rows) in (0..).zip(text) {
+        let glyph_left = left + index * (GLYPH_WIDTH + 1) * GLYPH_SCALE;
+        for (y, row) in (0..).zip(rows) {
+            for x in 0..GLYPH_WIDTH {
+                if row & (0x10 >> x) == 0 {
+                    continue;
+                }
+                for dy in 0..GLYPH_SCALE {
+                    for dx in 0..GLYPH_SCALE {
+                        image.put_pixel(
+                            glyph_left + x * GLYPH_SCALE + dx,
+                            top + y * GLYPH_SCALE + dy,
+                            TEXT_COLOR,
+                        );
+                    }
+                }
+            }
+        }
+    }
+    image
+}
+
+/// FNV-1a, stable between runs unlike the std hasher
+fn title_hash(title: &str) -> u64 {
+    title.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Muted color with a hue picked by the title, dark enough for white text
+fn title_color(title: &str) -> Rgb<u8> {
+    let hue = (title_hash(title) % 360) as f32 / 60.;
+    let (value, saturation) = (0.6, 0.55);
+    let chroma = value * saturation;
+    // Second largest component
+    let mid = chroma * (1. - (hue % 2. - 1.).abs());
+    let (red, green, blue) = match hue as u32 {
+        0 => (chroma, mid, 0.),
+        1 => (mid, chroma, 0.),
+        2 => (0., chroma, mid),
+        3 => (0., mid, chroma),
+        4 => (mid, 0., chroma),
+        _ => (chroma, 0., mid),
+    };
+    let channel = |component: f32| ((component + value - chroma) * 255.).round() as u8;
+    Rgb([channel(red), channel(green), channel(blue)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initials() {
+        assert_eq!(initials("Never Gonna Give You Up"), "NG");
+        assert_eq!(initials("passport.mid"), "P");
+        assert_eq!(initials("  (intro) - 2nd_take"), "I2");
+        assert_eq!(initials("äö"), "");
+    }
+
+    #[test]
+    fn test_render_cover() {
+        let image = render_cover("Title");
+        assert_eq!(image.dimensions(), (COVER_SIZE, COVER_SIZE));
+        let background = title_color("Title");
+        assert_eq!(*image.get_pixel(0, 0), background);
+        assert!(image.pixels().any(|pixel| *pixel == TEXT_COLOR));
+        assert_eq!(render_cover("Title"), image);
+
+        let blank = render_cover("ö");
+        assert!(blank.pixels().all(|pixel| *pixel == title_color("ö")));
+    }
+}
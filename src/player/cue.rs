@@ -0,0 +1,95 @@
+//! DJ-style cue: the next song plays on the preview player while the main one keeps going,
+//! until it's crossfaded in.
+//!
+//! There's no separate headphones output, so the cue is mixed quietly into the same output.
+
+use std::{f32::consts::FRAC_PI_2, path::PathBuf, time::Duration};
+use web_time::Instant;
+
+/// Cue volume relative to the main player, before crossfading
+pub const CUE_LEVEL: f32 = 0.5;
+pub const CROSSFADE_DURATION: Duration = Duration::from_secs(4);
+
+/// Song on the preview player, waiting to take over. Refers to the playlist by id, since
+/// indices change.
+#[derive(Debug, Clone)]
+pub struct Cue {
+    pub playlist_id: usize,
+    pub song: PathBuf,
+    name: String,
+    /// When the crossfade was started
+    crossfade_start: Option<Instant>,
+}
+
+impl Cue {
+    #[must_use]
+    pub const fn new(playlist_id: usize, song: PathBuf, name: String) -> Self {
+        Self {
+            playlist_id,
+            song,
+            name,
+            crossfade_start: None,
+        }
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    #[must_use]
+    pub const fn is_crossfading(&self) -> bool {
+        self.crossfade_start.is_some()
+    }
+    pub fn start_crossfade(&mut self) {
+        self.crossfade_start.get_or_insert_with(Instant::now);
+    }
+    /// Crossfade completion, 0.0..=1.0
+    #[must_use]
+    pub fn get_progress(&self) -> f32 {
+        self.crossfade_start.map_or(0., |start| {
+            (start.elapsed().as_secs_f32() / CROSSFADE_DURATION.as_secs_f32()).min(1.)
+        })
+    }
+    /// Volume multipliers of the main and preview player
+    #[must_use]
+    pub fn get_gains(&self) -> (f32, f32) {
+        crossfade_gains(self.get_progress())
+    }
+}
+
+/// Equal-power fade out of the main player, and the cue up from `CUE_LEVEL`.
+fn crossfade_gains(progress: f32) -> (f32, f32) {
+    let angle = progress * FRAC_PI_2;
+    let cue = (1. - CUE_LEVEL).mul_add(angle.sin(), CUE_LEVEL);
+    (angle.cos(), cue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossfade_gains() {
+        let (main, cue) = crossfade_gains(0.);
+        assert!((main - 1.).abs() < f32::EPSILON);
+        assert!((cue - CUE_LEVEL).abs() < f32::EPSILON);
+
+        let (main, cue) = crossfade_gains(0.5);
+        assert!(main > 0. && main < 1.);
+        assert!(cue > CUE_LEVEL && cue < 1.);
+
+        let (main, cue) = crossfade_gains(1.);
+        assert!(main.abs() < 0.001);
+        assert!((cue - 1.).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_progress() {
+        let mut cue = Cue::new(0, PathBuf::new(), String::new());
+        assert!(!cue.is_crossfading());
+        assert!(cue.get_progress().abs() < f32::EPSILON);
+        cue.start_crossfade();
+        assert!(cue.is_crossfading());
+        assert!(cue.get_progress() < 1.);
+    }
+}
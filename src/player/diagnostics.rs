@@ -0,0 +1,187 @@
+//! Diagnostics for bug reports: app and system info, what's playing, and recent events.
+//!
+
+use std::{collections::VecDeque, fmt::Write as _, time::Duration};
+use web_time::Instant;
+
+use midi_msg::Division;
+
+use super::{
+    playlist::midi_meta::{parse_midi, MidiMeta},
+    Player, PlayerEvent,
+};
+
+/// How many log lines are kept
+const LOG_CAPACITY: usize = 100;
+/// How many log lines go into diagnostics
+const DIAGNOSTICS_LOG_LINES: usize = 30;
+
+/// Recent player events, oldest first
+#[derive(Debug)]
+pub struct EventLog {
+    started: Instant,
+    lines: VecDeque<(Duration, String)>,
+}
+impl Default for EventLog {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            lines: VecDeque::new(),
+        }
+    }
+}
+impl EventLog {
+    pub fn push(&mut self, line: String) {
+        if self.lines.len() == LOG_CAPACITY {
+            self.lines.pop_front();
+        }
+        self.lines.push_back((self.started.elapsed(), line));
+    }
+    /// Newest `count` lines, timestamped with time since start
+    #[must_use]
+    pub fn get_lines(&self, count: usize) -> Vec<String> {
+        self.lines
+            .iter()
+            .skip(self.lines.len().saturating_sub(count))
+            .map(|(time, line)| format!("[{:>9.1}s] {line}", time.as_secs_f64()))
+            .collect()
+    }
+
+    /// Keep events worth reading in a bug report
+    pub fn observe(&mut self, event: &PlayerEvent) {
+        match event {
+            PlayerEvent::NotifyError(message) => self.push(format!("Error: {message}")),
+            PlayerEvent::ProblemFound(message) => self.push(format!("Problem: {message}")),
+            PlayerEvent::SongChanged { song, soundfont } => self.push(format!(
+                "Song: {song}, soundfont: {}",
+                soundfont.as_deref().unwrap_or("none")
+            )),
+            PlayerEvent::FontChanged { soundfont } => self.push(format!(
+                "Soundfont: {}",
+                soundfont.as_deref().unwrap_or("none")
+            )),
+            PlayerEvent::SongReloaded { song } => self.push(format!("Reloaded: {song}")),
+            _ => (),
+        }
+    }
+}
+
+impl Player {
+    /// Text blob for pasting into bug reports
+    pub fn get_diagnostics(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "SfontPlayer {}", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(
+            text,
+            "OS: {} {}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+        let _ = writeln!(
+            text,
+            "Audio backend: {}",
+            self.audioplayer
+                .get_backend_name()
+                .unwrap_or_else(|| "none".into())
+        );
+
+        text += "\n[Soundfont]\n";
+        match &self.active_font {
+            Some((link, font)) => {
+                let _ = writeln!(text, "Path: {}", font.get_path().display());
+                let _ = writeln!(text, "From: {link:?}");
+                let _ = writeln!(text, "Size: {}", format_size(font.get_size()));
+                let _ = writeln!(text, "Presets: {}", font.get_presets().len());
+                if let Err(e) = font.get_status() {
+                    let _ = writeln!(text, "Status: {e}");
+                }
+            }
+            None => text += "None\n",
+        }
+
+        text += "\n[Song]\n";
+        match self
+            .get_playing_song_idx()
+            .and_then(|idx| self.get_playing_playlist().get_songs().get(idx))
+        {
+            Some(song) => write_song_info(&mut text, song),
+            None => text += "Not playing\n",
+        }
+
+        text += "\n[Log]\n";
+        let lines = self.log.get_lines(DIAGNOSTICS_LOG_LINES);
+        if lines.is_empty() {
+            text += "Empty\n";
+        }
+        for line in lines {
+            text += &line;
+            text.push('\n');
+        }
+        text
+    }
+}
+
+fn write_song_info(text: &mut String, song: &MidiMeta) {
+    let _ = writeln!(text, "Path: {}", song.get_path().display());
+    let _ = writeln!(text, "Size: {}", format_size(song.get_size()));
+    let midifile = song
+        .read_bytes()
+        .map_err(anyhow::Error::from)
+        .and_then(|bytes| parse_midi(&bytes));
+    match midifile {
+        Ok(midifile) => {
+            let header = &midifile.header;
+            let _ = writeln!(text, "Format: {:?}", header.format);
+            let _ = writeln!(text, "Tracks: {}", midifile.tracks.len());
+            let division = match header.division {
+                Division::TicksPerQuarterNote(ticks) => format!("{ticks} ticks per quarter note"),
+                Division::TimeCode {
+                    frames_per_second,
+                    ticks_per_frame,
+                } => format!("{frames_per_second:?}, {ticks_per_frame} ticks per frame"),
+            };
+            let _ = writeln!(text, "Division: {division}");
+        }
+        Err(e) => {
+            let _ = writeln!(text, "Can't read: {e}");
+        }
+    }
+}
+
+fn format_size(size: Option<u64>) -> String {
+    size.map_or_else(|| "unknown".into(), |size| format!("{size} bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_log() {
+        let mut log = EventLog::default();
+        for i in 0..LOG_CAPACITY + 5 {
+            log.push(i.to_string());
+        }
+        let lines = log.get_lines(LOG_CAPACITY * 2);
+        assert_eq!(lines.len(), LOG_CAPACITY);
+        assert!(lines[0].ends_with("] 5"));
+
+        let lines = log.get_lines(2);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].ends_with(&format!("] {}", LOG_CAPACITY + 4)));
+
+        log.observe(&PlayerEvent::QueueChanged);
+        log.observe(&PlayerEvent::NotifyError("Oops".into()));
+        assert!(log.get_lines(1)[0].ends_with("Error: Oops"));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut player = Player::headless();
+        player.push_error("Something broke".into());
+        let text = player.get_diagnostics();
+        assert!(text.starts_with(&format!("SfontPlayer {}", env!("CARGO_PKG_VERSION"))));
+        assert!(text.contains("Not playing"));
+        assert!(text.contains("Error: Something broke"));
+    }
+}
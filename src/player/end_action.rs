@@ -0,0 +1,196 @@
+//! What happens when a song or the queue finishes: keep going, stop, quit, suspend the computer,
+//! or run a command of the user's.
+//!
+//! Quitting, suspending and commands wait a while before they run, so the user can cancel them.
+
+use std::{io, process::Command, thread, time::Duration};
+use web_time::Instant;
+
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use super::{Player, PlayerEvent};
+
+/// How long destructive actions wait to be cancelled
+pub const END_ACTION_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Eq, Default, Clone, Copy, Debug)]
+#[repr(u8)]
+pub enum EndActionKind {
+    /// Play on as usual
+    #[default]
+    Continue = 0,
+    Stop = 1,
+    Quit = 2,
+    /// Suspend the computer
+    Suspend = 3,
+    /// Run the user's command
+    Command = 4,
+}
+impl EndActionKind {
+    pub const ALL: [Self; 5] = [
+        Self::Continue,
+        Self::Stop,
+        Self::Quit,
+        Self::Suspend,
+        Self::Command,
+    ];
+
+    #[must_use]
+    pub const fn get_name(self) -> &'static str {
+        match self {
+            Self::Continue => "Nothing",
+            Self::Stop => "Stop",
+            Self::Quit => "Quit the app",
+            Self::Suspend => "Suspend the computer",
+            Self::Command => "Run a command",
+        }
+    }
+
+    /// Asks first, with a countdown
+    #[must_use]
+    pub const fn is_destructive(self) -> bool {
+        matches!(self, Self::Quit | Self::Suspend | Self::Command)
+    }
+}
+
+/// When the action runs
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Eq, Default, Clone, Copy, Debug)]
+#[repr(u8)]
+pub enum EndTrigger {
+    /// The queue runs out. Never happens while repeating the queue.
+    #[default]
+    Queue = 0,
+    /// The playing song finishes
+    Song = 1,
+}
+impl EndTrigger {
+    #[must_use]
+    pub const fn get_name(self) -> &'static str {
+        match self {
+            Self::Queue => "the queue finishes",
+            Self::Song => "the song finishes",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EndAction {
+    pub kind: EndActionKind,
+    pub trigger: EndTrigger,
+    /// Run through the shell for [`EndActionKind::Command`]
+    pub command: String,
+}
+
+impl Player {
+    /// Destructive action counting down, and when it runs
+    pub const fn get_pending_end_action(&self) -> Option<(EndActionKind, Instant)> {
+        self.pending_end_action
+    }
+    /// Run the pending action without waiting
+    pub fn confirm_end_action(&mut self) {
+        if let Some((kind, _)) = self.pending_end_action.take() {
+            self.run_end_action(kind);
+        }
+    }
+    pub const fn cancel_end_action(&mut self) {
+        self.pending_end_action = None;
+    }
+
+    /// Playback reached the end of a song or the queue. True if the end action took over, and
+    /// playback was stopped.
+    pub(super) fn trigger_end_action(&mut self, trigger: EndTrigger) -> bool {
+        let kind = self.end_action.kind;
+        if kind == EndActionKind::Continue || self.end_action.trigger != trigger {
+            return false;
+        }
+        self.stop();
+        if kind.is_destructive() {
+            self.pending_end_action = Some((kind, Instant::now() + END_ACTION_DELAY));
+            self.emit(&PlayerEvent::EndActionPending(kind.get_name().into()));
+        }
+        true
+    }
+
+    /// Run the pending action once its countdown is over
+    pub(super) fn update_end_action(&mut self) {
+        if self
+            .pending_end_action
+            .is_some_and(|(_, deadline)| Instant::now() >= deadline)
+        {
+            self.confirm_end_action();
+        }
+        let errors: Vec<String> = self.end_action_errors.try_iter().collect();
+        for error in errors {
+            self.push_error(error);
+        }
+    }
+
+    fn run_end_action(&mut self, kind: EndActionKind) {
+        let result = match kind {
+            EndActionKind::Continue | EndActionKind::Stop => Ok(()),
+            EndActionKind::Quit => {
+                self.emit(&PlayerEvent::Quit);
+                Ok(())
+            }
+            EndActionKind::Suspend => self.spawn_end_action(kind, suspend_command()),
+            EndActionKind::Command => {
+                let command = shell_command(&self.end_action.command);
+                self.spawn_end_action(kind, command)
+            }
+        };
+        if let Err(e) = result {
+            self.push_error(format!("Couldn't {}: {e}", kind.get_name().to_lowercase()));
+        }
+    }
+
+    /// Start the process, and wait for it on another thread. A failed exit is reported on the
+    /// next update.
+    fn spawn_end_action(&self, kind: EndActionKind, mut command: Command) -> io::Result<()> {
+        let mut child = command.spawn()?;
+        let errors = self.end_action_sender.clone();
+        thread::spawn(move || {
+            let name = kind.get_name().to_lowercase();
+            let error = match child.wait() {
+                Ok(status) if status.success() => return,
+                Ok(status) => format!("Couldn't {name}: {status}"),
+                Err(e) => format!("Couldn't {name}: {e}"),
+            };
+            let _ = errors.send(error);
+        });
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn suspend_command() -> Command {
+    let mut command = Command::new("systemctl");
+    command.arg("suspend");
+    command
+}
+#[cfg(target_os = "macos")]
+fn suspend_command() -> Command {
+    let mut command = Command::new("pmset");
+    command.arg("sleepnow");
+    command
+}
+#[cfg(target_os = "windows")]
+fn suspend_command() -> Command {
+    let mut command = Command::new("rundll32.exe");
+    command.arg("powrprof.dll,SetSuspendState").arg("0,1,0");
+    command
+}
+
+#[cfg(not(target_os = "windows"))]
+fn shell_command(line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(line);
+    command
+}
+#[cfg(target_os = "windows")]
+fn shell_command(line: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(line);
+    command
+}
@@ -0,0 +1,207 @@
+//! Soundfont fallback chain
+//!
+//! Decides where the soundfont for a song comes from. Links are tried in order, and the first one
+//! that provides a usable soundfont wins.
+
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use std::{error, fmt};
+
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Eq, Clone, Copy, Debug)]
+#[repr(u8)]
+pub enum FontChainLink {
+    /// Soundfont assigned to the song itself
+    Song = 0,
+    /// Soundfont selected in the playlist
+    Playlist = 1,
+    /// Default soundfont selected in the library
+    Library = 2,
+    /// Soundfont packaged with the player
+    BuiltIn = 3,
+}
+impl FontChainLink {
+    pub const ALL: [Self; 4] = [Self::Song, Self::Playlist, Self::Library, Self::BuiltIn];
+}
+impl fmt::Display for FontChainLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Song => write!(f, "Song soundfont"),
+            Self::Playlist => write!(f, "Playlist soundfont"),
+            Self::Library => write!(f, "Library default"),
+            Self::BuiltIn => write!(f, "Built-in basic tones"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FontChainError {
+    IndexOutOfRange { index: usize },
+}
+impl error::Error for FontChainError {}
+impl fmt::Display for FontChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IndexOutOfRange { index } => {
+                write!(f, "Font chain index out of range: {index}")
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct FontChainEntry {
+    pub link: FontChainLink,
+    pub enabled: bool,
+}
+
+/// Ordered list of soundfont sources. Every link appears exactly once.
+#[derive(Debug, Clone)]
+pub struct FontChain {
+    entries: Vec<FontChainEntry>,
+}
+impl Default for FontChain {
+    fn default() -> Self {
+        Self::from_entries(vec![])
+    }
+}
+
+impl FontChain {
+    /// Build a chain from (possibly incomplete or malformed) saved entries.
+    /// Duplicates are dropped and missing links are appended in default order.
+    #[must_use]
+    pub fn from_entries(entries: Vec<FontChainEntry>) -> Self {
+        let mut chain = Self { entries: vec![] };
+        for entry in entries {
+            if !chain.contains(entry.link) {
+                chain.entries.push(entry);
+            }
+        }
+        for link in FontChainLink::ALL {
+            if !chain.contains(link) {
+                chain.entries.push(FontChainEntry {
+                    link,
+                    enabled: true,
+                });
+            }
+        }
+        chain
+    }
+
+    #[must_use]
+    pub const fn get_entries(&self) -> &Vec<FontChainEntry> {
+        &self.entries
+    }
+    /// Enabled links in the order they should be tried
+    #[must_use]
+    pub fn get_enabled_links(&self) -> Vec<FontChainLink> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.enabled)
+            .map(|entry| entry.link)
+            .collect()
+    }
+    /// Turn a link on or off without removing it
+    ///
+    /// # Errors
+    ///
+    /// Returns `IndexOutOfRange` if there's no link at `index`.
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> Result<(), FontChainError> {
+        if index >= self.entries.len() {
+            return Err(FontChainError::IndexOutOfRange { index });
+        }
+        self.entries[index].enabled = enabled;
+        Ok(())
+    }
+    /// Rearrange links
+    ///
+    /// # Errors
+    ///
+    /// Returns `IndexOutOfRange` if either index is past the end of the chain.
+    pub fn move_entry(&mut self, old_index: usize, new_index: usize) -> Result<(), FontChainError> {
+        if old_index >= self.entries.len() {
+            return Err(FontChainError::IndexOutOfRange { index: old_index });
+        }
+        if new_index >= self.entries.len() {
+            return Err(FontChainError::IndexOutOfRange { index: new_index });
+        }
+        let entry = self.entries.remove(old_index);
+        self.entries.insert(new_index, entry);
+        Ok(())
+    }
+
+    fn contains(&self, link: FontChainLink) -> bool {
+        self.entries.iter().any(|entry| entry.link == link)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_default_order() {
+        let chain = FontChain::default();
+        assert_eq!(
+            chain.get_enabled_links(),
+            vec![
+                FontChainLink::Song,
+                FontChainLink::Playlist,
+                FontChainLink::Library,
+                FontChainLink::BuiltIn,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_entries_fills_missing_and_drops_duplicates() {
+        let chain = FontChain::from_entries(vec![
+            FontChainEntry {
+                link: FontChainLink::Library,
+                enabled: false,
+            },
+            FontChainEntry {
+                link: FontChainLink::Library,
+                enabled: true,
+            },
+        ]);
+        assert_eq!(chain.get_entries().len(), 4);
+        assert_eq!(chain.get_entries()[0].link, FontChainLink::Library);
+        assert!(!chain.get_entries()[0].enabled);
+        assert_eq!(
+            chain.get_enabled_links(),
+            vec![
+                FontChainLink::Song,
+                FontChainLink::Playlist,
+                FontChainLink::BuiltIn
+            ]
+        );
+    }
+
+    #[test]
+    fn test_move_entry() {
+        let mut chain = FontChain::default();
+        chain.move_entry(2, 0).unwrap();
+        assert_eq!(chain.get_entries()[0].link, FontChainLink::Library);
+        assert_eq!(chain.get_entries()[1].link, FontChainLink::Song);
+        assert_eq!(
+            chain.move_entry(0, 4).unwrap_err(),
+            FontChainError::IndexOutOfRange { index: 4 }
+        );
+    }
+
+    #[test]
+    fn test_set_enabled() {
+        let mut chain = FontChain::default();
+        chain.set_enabled(0, false).unwrap();
+        assert_eq!(
+            chain.get_enabled_links(),
+            vec![
+                FontChainLink::Playlist,
+                FontChainLink::Library,
+                FontChainLink::BuiltIn
+            ]
+        );
+        chain.set_enabled(4, false).unwrap_err();
+    }
+}
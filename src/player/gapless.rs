@@ -0,0 +1,121 @@
+//! Gapless playback: the next song of the queue is loaded and queued to the audio player a
+//! moment before the current one ends, instead of after it. Loading a big soundfont can take a
+//! while, and the end of a song is only noticed on the next update.
+//!
+//! The combined queue of all playlists and one-off soundfonts still start songs the usual way.
+
+use std::time::Duration;
+
+use super::{font_chain::FontChainLink, playlist::font_meta::FontMeta, Player, RepeatMode};
+
+/// How long before the end of a song the next one is queued
+const GAPLESS_LEAD: Duration = Duration::from_secs(3);
+
+/// Next song, once it's been tried
+pub struct GaplessNext {
+    song_idx: usize,
+    /// None if it couldn't be queued, and starts the usual way
+    font: Option<(FontChainLink, FontMeta)>,
+}
+
+impl Player {
+    /// Hand over to the queued song when it starts, and queue the next one in time.
+    pub(super) fn update_gapless(&mut self) {
+        if self.audioplayer.take_started_next() {
+            self.scripts.queue_song_ended();
+            self.gapless_song_started();
+            return;
+        }
+
+        let next_idx = self.peek_gapless_song();
+        if let Some(next) = &self.gapless_next {
+            // A restart drops the queued song, e.g. when a setting changes
+            let dropped = next.font.is_some() && !self.audioplayer.has_next();
+            if !dropped && Some(next.song_idx) == next_idx {
+                return;
+            }
+            if !self.audioplayer.cancel_next() {
+                // Playing already, handed over on the next update
+                return;
+            }
+            self.gapless_next = None;
+        }
+
+        let Some(song_idx) = next_idx else {
+            return;
+        };
+        if self.is_paused() || self.is_empty() {
+            return;
+        }
+        let Some(length) = self.audioplayer.get_midi_length() else {
+            return;
+        };
+        if length.saturating_sub(self.audioplayer.get_midi_position()) > GAPLESS_LEAD {
+            return;
+        }
+
+        let font = self
+            .resolve_soundfont_in(self.playing_playlist_idx, Some(song_idx))
+            .ok();
+        // Skipped huge songs are left for the queue to skip
+        let limits = self.huge_song_limits;
+        let Some(song) = self
+            .get_playing_playlist_mut()
+            .get_songs_mut()
+            .get_mut(song_idx)
+        else {
+            return;
+        };
+        song.refresh();
+        let skipped = limits.skip_in_queue && limits.check(song).is_some();
+        let song = (song.get_status().is_ok() && !skipped).then(|| song.clone());
+        let font = match (song, font) {
+            (Some(song), Some((font_link, font))) => self
+                .audioplayer
+                .queue_next(song, font.clone())
+                .is_ok()
+                .then_some((font_link, font)),
+            _ => None,
+        };
+        self.gapless_next = Some(GaplessNext { song_idx, font });
+    }
+
+    /// Song of the playing playlist that comes next, if it can follow without a gap
+    fn peek_gapless_song(&self) -> Option<usize> {
+        if !self.is_playing || self.party_queue.is_some() || self.font_override.is_some() {
+            return None;
+        }
+        let queue = self.get_playing_playlist().get_queue();
+        match self.repeat {
+            RepeatMode::Song => queue.get_current(),
+            repeat => queue.peek_next(repeat == RepeatMode::Queue),
+        }
+    }
+
+    /// The queued song took over. Same as `advance_queue`, without restarting the audio.
+    fn gapless_song_started(&mut self) {
+        let next = self.gapless_next.take();
+        let repeat = self.repeat;
+        let queue = self.get_playing_playlist_mut().get_queue_mut();
+        if repeat != RepeatMode::Song {
+            queue.advance(repeat == RepeatMode::Queue);
+        }
+        let Some(current) = queue.get_current() else {
+            self.stop();
+            return;
+        };
+        let Some((song_idx, font)) = next
+            .and_then(|next| Some((next.song_idx, next.font?)))
+            .filter(|(song_idx, _)| *song_idx == current)
+        else {
+            // The queue changed at the last moment
+            if let Err(e) = self.play_selected_song() {
+                self.push_playback_error(&e);
+            }
+            return;
+        };
+        let _ = self.get_playing_playlist_mut().set_song_idx(Some(song_idx));
+        self.set_active_font(Some(font));
+        self.song_started(song_idx);
+    }
+}
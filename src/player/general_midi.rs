@@ -0,0 +1,357 @@
+//! General MIDI instrument and drum kit names, for showing program numbers to humans.
+//!
+
+use midi_msg::{ChannelVoiceMsg, MidiFile, MidiMsg};
+
+use super::{
+    playlist::font_meta::PresetInfo,
+    program_lock::{CHANNEL_COUNT, DRUM_BANK_OFFSET, DRUM_CHANNEL},
+};
+
+const PROGRAM_NAMES: [&str; 128] = [
+    // Piano
+    "Acoustic Grand Piano",
+    "Bright Acoustic Piano",
+    "Electric Grand Piano",
+    "Honky-tonk Piano",
+    "Electric Piano 1",
+    "Electric Piano 2",
+    "Harpsichord",
+    "Clavinet",
+    // Chromatic percussion
+    "Celesta",
+    "Glockenspiel",
+    "Music Box",
+    "Vibraphone",
+    "Marimba",
+    "Xylophone",
+    "Tubular Bells",
+    "Dulcimer",
+    // Organ
+    "Drawbar Organ",
+    "Percussive Organ",
+    "Rock Organ",
+    "Church Organ",
+    "Reed Organ",
+    "Accordion",
+    "Harmonica",
+    "Tango Accordion",
+    // Guitar
+    "Acoustic Guitar (nylon)",
+    "Acoustic Guitar (steel)",
+    "Electric Guitar (jazz)",
+    "Electric Guitar (clean)",
+    "Electric Guitar (muted)",
+    "Overdriven Guitar",
+    "Distortion Guitar",
+    "Guitar Harmonics",
+    // Bass
+    "Acoustic Bass",
+    "Electric Bass (finger)",
+    "Electric Bass (pick)",
+    "Fretless Bass",
+    "Slap Bass 1",
+    "Slap Bass 2",
+    "Synth Bass 1",
+    "Synth Bass 2",
+    // Strings
+    "Violin",
+    "Viola",
+    "Cello",
+    "Contrabass",
+    "Tremolo Strings",
+    "Pizzicato Strings",
+    "Orchestral Harp",
+    "Timpani",
+    // Ensemble
+    "String Ensemble 1",
+    "String Ensemble 2",
+    "Synth Strings 1",
+    "Synth Strings 2",
+    "Choir Aahs",
+    "Voice Oohs",
+    "Synth Voice",
+    "Orchestra Hit",
+    // Brass
+    "Trumpet",
+    "Trombone",
+    "Tuba",
+    "Muted Trumpet",
+    "French Horn",
+    "Brass Section",
+    "Synth Brass 1",
+    "Synth Brass 2",
+    // Reed
+    "Soprano Sax",
+    "Alto Sax",
+    "Tenor Sax",
+    "Baritone Sax",
+    "Oboe",
+    "English Horn",
+    "Bassoon",
+    "Clarinet",
+    // Pipe
+    "Piccolo",
+    "Flute",
+    "Recorder",
+    "Pan Flute",
+    "Blown Bottle",
+    "Shakuhachi",
+    "Whistle",
+    "Ocarina",
+    // Synth lead
+    "Lead 1 (square)",
+    "Lead 2 (sawtooth)",
+    "Lead 3 (calliope)",
+    "Lead 4 (chiff)",
+    "Lead 5 (charang)",
+    "Lead 6 (voice)",
+    "Lead 7 (fifths)",
+    "Lead 8 (bass + lead)",
+    // Synth pad
+    "Pad 1 (new age)",
+    "Pad 2 (warm)",
+    "Pad 3 (polysynth)",
+    "Pad 4 (choir)",
+    "Pad 5 (bowed)",
+    "Pad 6 (metallic)",
+    "Pad 7 (halo)",
+    "Pad 8 (sweep)",
+    // Synth effects
+    "FX 1 (rain)",
+    "FX 2 (soundtrack)",
+    "FX 3 (crystal)",
+    "FX 4 (atmosphere)",
+    "FX 5 (brightness)",
+    "FX 6 (goblins)",
+    "FX 7 (echoes)",
+    "FX 8 (sci-fi)",
+    // Ethnic
+    "Sitar",
+    "Banjo",
+    "Shamisen",
+    "Koto",
+    "Kalimba",
+    "Bagpipe",
+    "Fiddle",
+    "Shanai",
+    // Percussive
+    "Tinkle Bell",
+    "Agogo",
+    "Steel Drums",
+    "Woodblock",
+    "Taiko Drum",
+    "Melodic Tom",
+    "Synth Drum",
+    "Reverse Cymbal",
+    // Sound effects
+    "Guitar Fret Noise",
+    "Breath Noise",
+    "Seashore",
+    "Bird Tweet",
+    "Telephone Ring",
+    "Helicopter",
+    "Applause",
+    "Gunshot",
+];
+
+/// GS drum kits by program. Other programs fall back to the kit below them.
+const DRUM_KIT_NAMES: [(u8, &str); 9] = [
+    (0, "Standard Kit"),
+    (8, "Room Kit"),
+    (16, "Power Kit"),
+    (24, "Electronic Kit"),
+    (25, "TR-808 Kit"),
+    (32, "Jazz Kit"),
+    (40, "Brush Kit"),
+    (48, "Orchestra Kit"),
+    (56, "SFX Kit"),
+];
+
+/// General MIDI instrument of a 0-based program number
+#[must_use]
+pub fn program_name(program: u8) -> &'static str {
+    PROGRAM_NAMES.get(program as usize).copied().unwrap_or("")
+}
+
+/// GS drum kit of a program number on the percussion channel
+#[must_use]
+pub fn drum_kit_name(program: u8) -> &'static str {
+    DRUM_KIT_NAMES
+        .iter()
+        .rev()
+        .find(|(kit_program, _)| *kit_program <= program)
+        .map_or("", |(_, name)| name)
+}
+
+/// Name of what a channel plays: the soundfont's preset if it has one for the bank and program,
+/// otherwise the General MIDI name. `bank` is the bank select value sent on the channel.
+#[must_use]
+pub fn preset_name(presets: &[PresetInfo], bank: u8, program: u8, percussion: bool) -> String {
+    let font_bank = if percussion {
+        DRUM_BANK_OFFSET + u16::from(bank)
+    } else {
+        u16::from(bank)
+    };
+    if let Some(preset) = presets
+        .iter()
+        .find(|preset| preset.bank == font_bank && preset.program == program)
+    {
+        return preset.name.clone();
+    }
+    if percussion {
+        drum_kit_name(program).to_owned()
+    } else {
+        program_name(program).to_owned()
+    }
+}
+
+/// Presets of a soundfont that look most like the General MIDI instruments of the programs.
+///
+/// For mapping songs onto a soundfont with its own layout. Drum kits and programs with no
+/// likely preset are left out.
+#[must_use]
+pub fn auto_map<'a>(programs: &[u8], presets: &'a [PresetInfo]) -> Vec<(u8, &'a PresetInfo)> {
+    programs
+        .iter()
+        .filter_map(|&program| closest_preset(program, presets).map(|preset| (program, preset)))
+        .collect()
+}
+
+/// First program change of each melodic channel in a song. The drum channel is left out.
+#[must_use]
+pub fn song_programs(midi: &MidiFile) -> [Option<u8>; CHANNEL_COUNT] {
+    let mut programs = [None; CHANNEL_COUNT];
+    for track in &midi.tracks {
+        for track_event in track.events() {
+            let (MidiMsg::ChannelVoice { channel, msg }
+            | MidiMsg::RunningChannelVoice { channel, msg }) = &track_event.event
+            else {
+                continue;
+            };
+            let ChannelVoiceMsg::ProgramChange { program } = msg else {
+                continue;
+            };
+            let channel = *channel as usize;
+            if channel != DRUM_CHANNEL && programs[channel].is_none() {
+                programs[channel] = Some(*program);
+            }
+        }
+    }
+    programs
+}
+
+/// Melodic preset with the most similar name to the program's instrument
+#[must_use]
+pub fn closest_preset(program: u8, presets: &[PresetInfo]) -> Option<&PresetInfo> {
+    /// Below this, names have little more than a few letters in common
+    const MIN_SIMILARITY: f32 = 0.4;
+
+    let target = bigrams(program_name(program));
+    presets
+        .iter()
+        .filter(|preset| preset.bank < DRUM_BANK_OFFSET)
+        .map(|preset| (preset, similarity(&target, &bigrams(&preset.name))))
+        .filter(|(_, score)| *score >= MIN_SIMILARITY)
+        // Earlier preset wins ties, they're usually in bank order
+        .rev()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(preset, _)| preset)
+}
+
+/// Letter pairs of each word, lowercase
+fn bigrams(name: &str) -> Vec<[char; 2]> {
+    let name = name.to_lowercase();
+    name.split(|c: char| !c.is_alphanumeric())
+        .flat_map(|word| {
+            let chars: Vec<char> = word.chars().collect();
+            chars
+                .windows(2)
+                .map(|pair| [pair[0], pair[1]])
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Dice coefficient: shared pairs out of all, 0.0..=1.0
+fn similarity(a: &[[char; 2]], b: &[[char; 2]]) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return 0.;
+    }
+    let mut unmatched = b.to_vec();
+    let mut shared = 0;
+    for pair in a {
+        if let Some(i) = unmatched.iter().position(|other| other == pair) {
+            unmatched.swap_remove(i);
+            shared += 1;
+        }
+    }
+    2. * shared as f32 / (a.len() + b.len()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(program_name(0), "Acoustic Grand Piano");
+        assert_eq!(program_name(40), "Violin");
+        assert_eq!(program_name(127), "Gunshot");
+        assert_eq!(program_name(128), "");
+
+        assert_eq!(drum_kit_name(0), "Standard Kit");
+        assert_eq!(drum_kit_name(25), "TR-808 Kit");
+        assert_eq!(drum_kit_name(30), "TR-808 Kit");
+        assert_eq!(drum_kit_name(127), "SFX Kit");
+    }
+
+    #[test]
+    fn test_preset_name() {
+        let presets = vec![
+            PresetInfo {
+                bank: 0,
+                program: 0,
+                name: "My Piano".into(),
+            },
+            PresetInfo {
+                bank: 128,
+                program: 0,
+                name: "My Drums".into(),
+            },
+        ];
+        assert_eq!(preset_name(&presets, 0, 0, false), "My Piano");
+        assert_eq!(preset_name(&presets, 0, 0, true), "My Drums");
+        assert_eq!(preset_name(&presets, 8, 0, false), "Acoustic Grand Piano");
+        assert_eq!(preset_name(&[], 0, 32, true), "Jazz Kit");
+    }
+
+    #[test]
+    fn test_auto_map() {
+        let preset = |bank, program, name: &str| PresetInfo {
+            bank,
+            program,
+            name: name.into(),
+        };
+        let presets = vec![
+            preset(0, 0, "Grand Piano"),
+            preset(0, 1, "Nylon Guitar"),
+            preset(0, 2, "Steel Guitar"),
+            preset(0, 3, "Strings"),
+            preset(128, 0, "Standard"),
+        ];
+        let mapped: Vec<(u8, &str)> = auto_map(&[0, 25, 48, 127], &presets)
+            .into_iter()
+            .map(|(program, preset)| (program, preset.name.as_str()))
+            .collect();
+        assert_eq!(
+            mapped,
+            [
+                (0, "Grand Piano"),
+                (25, "Steel Guitar"),
+                (48, "Strings"),
+                // Gunshot has no lookalike
+            ]
+        );
+    }
+}
@@ -0,0 +1,354 @@
+//! Global hotkeys: control playback while another app is focused.
+//!
+//! The keys are grabbed from the X server, so this needs X11 or `XWayland`.
+//! Pressed hotkeys are fed into the media control event queue.
+
+use std::{
+    fmt,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::mediacontrols::ControlEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HotkeyAction {
+    PlayPause,
+    Next,
+    Previous,
+    Stop,
+}
+impl HotkeyAction {
+    pub const ALL: [Self; 4] = [Self::PlayPause, Self::Next, Self::Previous, Self::Stop];
+
+    #[must_use]
+    pub const fn get_name(self) -> &'static str {
+        match self {
+            Self::PlayPause => "Play / Pause",
+            Self::Next => "Next song",
+            Self::Previous => "Previous song",
+            Self::Stop => "Stop",
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "gui"))]
+    const fn get_event(self) -> ControlEvent {
+        match self {
+            Self::PlayPause => ControlEvent::Toggle,
+            Self::Next => ControlEvent::Next,
+            Self::Previous => ControlEvent::Previous,
+            Self::Stop => ControlEvent::Stop,
+        }
+    }
+}
+
+/// Key combination. Key is a letter, a digit, F1-F12, or "Space".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hotkey {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub key: String,
+}
+impl Hotkey {
+    fn ctrl_alt(key: &str) -> Self {
+        Self {
+            ctrl: true,
+            alt: true,
+            shift: false,
+            key: key.into(),
+        }
+    }
+
+    /// Is the key one that can be registered
+    #[must_use]
+    pub fn is_supported_key(key: &str) -> bool {
+        get_keysym(key).is_some()
+    }
+}
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// Global hotkey settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GlobalHotkeys {
+    pub enabled: bool,
+    play_pause: Option<Hotkey>,
+    next: Option<Hotkey>,
+    previous: Option<Hotkey>,
+    stop: Option<Hotkey>,
+}
+impl Default for GlobalHotkeys {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            play_pause: Some(Hotkey::ctrl_alt("P")),
+            next: Some(Hotkey::ctrl_alt("N")),
+            previous: Some(Hotkey::ctrl_alt("B")),
+            stop: None,
+        }
+    }
+}
+impl GlobalHotkeys {
+    #[must_use]
+    pub const fn get_binding(&self, action: HotkeyAction) -> Option<&Hotkey> {
+        match action {
+            HotkeyAction::PlayPause => self.play_pause.as_ref(),
+            HotkeyAction::Next => self.next.as_ref(),
+            HotkeyAction::Previous => self.previous.as_ref(),
+            HotkeyAction::Stop => self.stop.as_ref(),
+        }
+    }
+    pub fn set_binding(&mut self, action: HotkeyAction, hotkey: Option<Hotkey>) {
+        let binding = match action {
+            HotkeyAction::PlayPause => &mut self.play_pause,
+            HotkeyAction::Next => &mut self.next,
+            HotkeyAction::Previous => &mut self.previous,
+            HotkeyAction::Stop => &mut self.stop,
+        };
+        *binding = hotkey;
+    }
+    fn get_bindings(&self) -> Vec<(Hotkey, HotkeyAction)> {
+        HotkeyAction::ALL
+            .into_iter()
+            .filter_map(|action| {
+                self.get_binding(action)
+                    .map(|hotkey| (hotkey.clone(), action))
+            })
+            .collect()
+    }
+}
+
+/// Background thread that holds the key grabs. Grabs are released when this is dropped.
+pub struct HotkeyListener {
+    bindings: Sender<Vec<(Hotkey, HotkeyAction)>>,
+    errors: Receiver<String>,
+}
+impl HotkeyListener {
+    #[must_use]
+    pub fn start(event_queue: Sender<ControlEvent>) -> Self {
+        let (bindings_tx, bindings_rx) = mpsc::channel();
+        let (errors_tx, errors_rx) = mpsc::channel();
+        let thread_errors = errors_tx.clone();
+        if let Err(e) =
+            thread::Builder::new().spawn(move || listen(&bindings_rx, &thread_errors, &event_queue))
+        {
+            let _ = errors_tx.send(format!("Couldn't start listening to hotkeys: {e}"));
+        }
+        Self {
+            bindings: bindings_tx,
+            errors: errors_rx,
+        }
+    }
+
+    /// Replace registered hotkeys
+    pub fn set_bindings(&self, config: &GlobalHotkeys) {
+        let _ = self.bindings.send(config.get_bindings());
+    }
+
+    /// Registration problems since last call
+    #[must_use]
+    pub fn take_errors(&self) -> Vec<String> {
+        self.errors.try_iter().collect()
+    }
+}
+
+/// X11 keysym of a key name
+fn get_keysym(key: &str) -> Option<u32> {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        // Latin-1 keysyms are the same as the lowercase character.
+        return c
+            .is_ascii_alphanumeric()
+            .then(|| u32::from(c.to_ascii_lowercase()));
+    }
+    if key == "Space" {
+        return Some(0x20);
+    }
+    let number: u32 = key.strip_prefix('F')?.parse().ok()?;
+    // XK_F1..XK_F12
+    (1..=12).contains(&number).then(|| 0xffbe + number - 1)
+}
+
+#[cfg(all(target_os = "linux", feature = "gui"))]
+fn listen(
+    bindings_rx: &Receiver<Vec<(Hotkey, HotkeyAction)>>,
+    errors_tx: &Sender<String>,
+    event_queue: &Sender<ControlEvent>,
+) {
+    use std::{sync::mpsc::TryRecvError, time::Duration};
+    use x11rb::{
+        connection::Connection,
+        protocol::{
+            xproto::{ConnectionExt, Grab, GrabMode, ModMask},
+            Event,
+        },
+    };
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(20);
+    // Caps lock and num lock shouldn't stop the hotkeys from working
+    let (caps_lock, num_lock) = (u16::from(ModMask::LOCK), u16::from(ModMask::M2));
+    let ignored_mods = [0, caps_lock, num_lock, caps_lock | num_lock];
+    let used_mods = u16::from(ModMask::CONTROL | ModMask::M1 | ModMask::SHIFT);
+
+    let Ok((conn, screen)) = x11rb::connect(None) else {
+        let _ = errors_tx.send("Global hotkeys need an X11 display.".into());
+        return;
+    };
+    let root = conn.setup().roots[screen].root;
+    let min_keycode = conn.setup().min_keycode;
+    let max_keycode = conn.setup().max_keycode;
+    let Some(mapping) = conn
+        .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+    else {
+        let _ = errors_tx.send("Couldn't read keyboard mapping for global hotkeys.".into());
+        return;
+    };
+    let keycode_of = |keysym: u32| {
+        mapping
+            .keysyms
+            .chunks(usize::from(mapping.keysyms_per_keycode).max(1))
+            .position(|syms| syms.first() == Some(&keysym))
+            .map(|index| min_keycode + index as u8)
+    };
+
+    // (keycode, modifiers, action)
+    let mut grabbed: Vec<(u8, u16, HotkeyAction)> = vec![];
+    loop {
+        match bindings_rx.try_recv() {
+            Ok(bindings) => {
+                let _ = conn.ungrab_key(Grab::ANY, root, ModMask::ANY);
+                grabbed.clear();
+                for (hotkey, action) in bindings {
+                    let Some(keycode) = get_keysym(&hotkey.key).and_then(keycode_of) else {
+                        let _ = errors_tx.send(format!("Unsupported global hotkey: {hotkey}"));
+                        continue;
+                    };
+                    let mut modifiers = 0;
+                    if hotkey.ctrl {
+                        modifiers |= u16::from(ModMask::CONTROL);
+                    }
+                    if hotkey.alt {
+                        modifiers |= u16::from(ModMask::M1);
+                    }
+                    if hotkey.shift {
+                        modifiers |= u16::from(ModMask::SHIFT);
+                    }
+                    let registered = ignored_mods.iter().all(|ignored| {
+                        conn.grab_key(
+                            false,
+                            root,
+                            ModMask::from(modifiers | ignored),
+                            keycode,
+                            GrabMode::ASYNC,
+                            GrabMode::ASYNC,
+                        )
+                        .is_ok_and(|cookie| cookie.check().is_ok())
+                    });
+                    if registered {
+                        grabbed.push((keycode, modifiers, action));
+                    } else {
+                        let _ = errors_tx.send(format!(
+                            "Couldn't register global hotkey {hotkey}. Another app may be using it."
+                        ));
+                    }
+                }
+                let _ = conn.flush();
+            }
+            Err(TryRecvError::Empty) => (),
+            // Listener was dropped
+            Err(TryRecvError::Disconnected) => return,
+        }
+
+        match conn.poll_for_event() {
+            Ok(Some(Event::KeyPress(event))) => {
+                let state = u16::from(event.state) & used_mods;
+                if let Some((_, _, action)) = grabbed
+                    .iter()
+                    .find(|(keycode, modifiers, _)| *keycode == event.detail && *modifiers == state)
+                {
+                    let _ = event_queue.send(action.get_event());
+                }
+            }
+            Ok(Some(_)) => (),
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(_) => {
+                let _ = errors_tx.send("Lost connection to X11, global hotkeys stopped.".into());
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "linux", feature = "gui")))]
+fn listen(
+    _bindings_rx: &Receiver<Vec<(Hotkey, HotkeyAction)>>,
+    errors_tx: &Sender<String>,
+    _event_queue: &Sender<ControlEvent>,
+) {
+    let _ = errors_tx.send("Global hotkeys aren't supported on this platform or build.".into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keysyms() {
+        assert_eq!(get_keysym("P"), Some(u32::from(b'p')));
+        assert_eq!(get_keysym("7"), Some(u32::from(b'7')));
+        assert_eq!(get_keysym("Space"), Some(0x20));
+        assert_eq!(get_keysym("F1"), Some(0xffbe));
+        assert_eq!(get_keysym("F12"), Some(0xffc9));
+        assert_eq!(get_keysym("F13"), None);
+        assert_eq!(get_keysym("Enter"), None);
+        assert_eq!(get_keysym("+"), None);
+    }
+
+    #[test]
+    fn test_bindings() {
+        let mut config = GlobalHotkeys::default();
+        assert_eq!(
+            config
+                .get_binding(HotkeyAction::PlayPause)
+                .map(ToString::to_string),
+            Some("Ctrl+Alt+P".into())
+        );
+        config.set_binding(HotkeyAction::PlayPause, None);
+        config.set_binding(
+            HotkeyAction::Stop,
+            Some(Hotkey {
+                ctrl: false,
+                alt: false,
+                shift: true,
+                key: "F9".into(),
+            }),
+        );
+        let bindings = config.get_bindings();
+        assert_eq!(bindings.len(), 3);
+        assert!(!bindings
+            .iter()
+            .any(|(_, action)| *action == HotkeyAction::PlayPause));
+        assert_eq!(
+            bindings.last().map(|(hotkey, _)| hotkey.to_string()),
+            Some("Shift+F9".into())
+        );
+    }
+}
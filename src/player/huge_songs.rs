@@ -0,0 +1,163 @@
+//! Guard against songs too heavy for the synth, like black MIDI with millions of notes.
+//!
+//! The GUI asks before playing one. When playback moves on by itself, they can be skipped.
+
+use midi_msg::{ChannelVoiceMsg, MidiMsg};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    playlist::midi_meta::{parse_midi, MidiMeta},
+    Player, RepeatMode,
+};
+
+const MEGABYTE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HugeSongLimits {
+    /// Files larger than this are huge. 0 turns the check off.
+    pub max_size_mb: u32,
+    /// Songs with more notes are huge. 0 turns the check off.
+    pub max_notes: u32,
+    /// Skip huge songs when the queue advances by itself, and in party mode
+    pub skip_in_queue: bool,
+}
+impl Default for HugeSongLimits {
+    fn default() -> Self {
+        Self {
+            max_size_mb: 10,
+            max_notes: 1_000_000,
+            skip_in_queue: false,
+        }
+    }
+}
+
+/// Why a song counts as huge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HugeSong {
+    Size { bytes: u64 },
+    Notes { count: usize },
+}
+
+impl HugeSongLimits {
+    /// Size is checked first: counting the notes of a huge file would take a while too.
+    #[must_use]
+    pub fn check(&self, song: &MidiMeta) -> Option<HugeSong> {
+        if self.max_size_mb > 0 {
+            if let Some(bytes) = song.get_size() {
+                if bytes > u64::from(self.max_size_mb) * MEGABYTE {
+                    return Some(HugeSong::Size { bytes });
+                }
+            }
+        }
+        if self.max_notes > 0 {
+            let count = song
+                .read_bytes()
+                .ok()
+                .and_then(|bytes| count_notes(&bytes))?;
+            if count > self.max_notes as usize {
+                return Some(HugeSong::Notes { count });
+            }
+        }
+        None
+    }
+}
+
+fn count_notes(bytes: &[u8]) -> Option<usize> {
+    let midifile = parse_midi(bytes).ok()?;
+    let count = midifile
+        .tracks
+        .iter()
+        .flat_map(midi_msg::Track::events)
+        .filter(|track_event| {
+            matches!(
+                track_event.event,
+                MidiMsg::ChannelVoice {
+                    msg: ChannelVoiceMsg::NoteOn { velocity: 1.., .. },
+                    ..
+                } | MidiMsg::RunningChannelVoice {
+                    msg: ChannelVoiceMsg::NoteOn { velocity: 1.., .. },
+                    ..
+                }
+            )
+        })
+        .count();
+    Some(count)
+}
+
+impl Player {
+    /// Why a song of a playlist is huge, if it is
+    pub fn check_huge_song(&self, playlist: usize, index: usize) -> Option<HugeSong> {
+        let song = self.playlists.get(playlist)?.get_songs().get(index)?;
+        self.huge_song_limits.check(song)
+    }
+
+    /// Move the queue on, over huge songs if they're skipped. False at the end of the queue.
+    pub(super) fn advance_past_huge_songs(&mut self) -> bool {
+        let limits = self.huge_song_limits;
+        let wrap = self.repeat == RepeatMode::Queue;
+        let playlist = self.get_playing_playlist_mut();
+        // Every song is tried at most once
+        for _ in 0..playlist.get_queue().len().max(1) {
+            if !playlist.get_queue_mut().advance(wrap) {
+                return false;
+            }
+            let huge = limits.skip_in_queue
+                && playlist
+                    .get_queue()
+                    .get_current()
+                    .and_then(|index| playlist.get_songs().get(index))
+                    .is_some_and(|song| limits.check(song).is_some());
+            if !huge {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// One track with the given number of notes
+    fn midi_with_notes(notes: usize) -> Vec<u8> {
+        let mut track = vec![];
+        for _ in 0..notes {
+            // Note on and off, running status for the off
+            track.extend_from_slice(&[0x00, 0x90, 60, 100, 0x10, 60, 0]);
+        }
+        track.extend_from_slice(&[0x00, 0xff, 0x2f, 0x00]);
+
+        let mut bytes = b"MThd".to_vec();
+        bytes.extend_from_slice(&[0, 0, 0, 6, 0, 0, 0, 1, 0, 96]);
+        bytes.extend_from_slice(b"MTrk");
+        bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&track);
+        bytes
+    }
+
+    #[test]
+    fn test_count_notes() {
+        assert_eq!(count_notes(&midi_with_notes(3)), Some(3));
+        assert_eq!(count_notes(b"not midi"), None);
+    }
+
+    #[test]
+    fn test_check() {
+        let mut song = MidiMeta::from_bytes(PathBuf::from("a.mid"), midi_with_notes(5));
+        song.refresh();
+        let mut limits = HugeSongLimits {
+            max_size_mb: 1,
+            max_notes: 5,
+            skip_in_queue: true,
+        };
+        assert_eq!(limits.check(&song), None);
+        limits.max_notes = 4;
+        assert_eq!(limits.check(&song), Some(HugeSong::Notes { count: 5 }));
+        limits.max_notes = 0;
+        assert_eq!(limits.check(&song), None);
+    }
+}
@@ -0,0 +1,151 @@
+//! Live input: MIDI from the input port, e.g. a keyboard, played with the current soundfont.
+//!
+//! The live source has a sink of its own, so it plays over songs and previews. It follows the
+//! soundfont of the playing song, and keeps the one it started with when nothing plays.
+
+use std::{
+    path::PathBuf,
+    sync::mpsc::{self, Sender},
+};
+
+use super::{
+    audio::{
+        backend::AudioBackend,
+        livesource::{LiveMessage, LiveSource},
+        load_soundfont,
+        midi_in::MidiInPort,
+    },
+    playlist::font_meta::FontMeta,
+    volume_to_amplitude, Player,
+};
+
+#[derive(Default)]
+pub struct LiveInput {
+    sink: Option<Box<dyn AudioBackend>>,
+    /// Open while live input is on
+    port: Option<MidiInPort>,
+    /// To the live source
+    messages: Option<Sender<LiveMessage>>,
+    /// Soundfont being played
+    font: Option<FontMeta>,
+    /// Path of the song soundfont that was last followed, whether it loaded or not
+    followed: Option<PathBuf>,
+}
+
+impl Player {
+    pub const fn is_live_input(&self) -> bool {
+        self.live_input.port.is_some()
+    }
+    /// Start or stop listening to the MIDI input port
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no audio output or no soundfont to play with, the soundfont can't be
+    /// loaded, or the MIDI input port can't be opened.
+    pub fn set_live_input(&mut self, enabled: bool) -> anyhow::Result<()> {
+        self.stop_live_input();
+        if enabled {
+            let font = match &self.active_font {
+                Some((_, font)) => font.clone(),
+                None => self.resolve_soundfont(None)?.1,
+            };
+            self.start_live_input(font)?;
+        }
+        Ok(())
+    }
+    /// Soundfont that live input plays with
+    pub const fn get_live_font(&self) -> Option<&FontMeta> {
+        self.live_input.font.as_ref()
+    }
+    /// Sink for live input. Should be separate from the others, so it can play over them.
+    pub fn set_live_sink(&mut self, sink: Option<Box<dyn AudioBackend>>) {
+        let font = self.live_input.font.clone();
+        let was_on = self.is_live_input();
+        self.stop_live_input();
+        self.live_input.sink = sink;
+        if let Some(font) = font.filter(|_| was_on) {
+            if let Err(e) = self.start_live_input(font) {
+                self.push_error(format!("Couldn't restart live input: {e}"));
+            }
+        }
+    }
+
+    fn start_live_input(&mut self, font: FontMeta) -> anyhow::Result<()> {
+        let Some(sink) = &self.live_input.sink else {
+            anyhow::bail!("No audio output for live input");
+        };
+        let soundfont = load_soundfont(&font, &mut None)?;
+        let synth_effects = self
+            .resolve_audio_profile(self.playing_playlist_idx)
+            .synth_effects;
+
+        let (sender, receiver) = mpsc::channel();
+        let port_sender = sender.clone();
+        let port = MidiInPort::open(move |raw| {
+            let _ = port_sender.send(LiveMessage::Midi(raw.to_vec()));
+        })?;
+        sink.clear();
+        sink.append(Box::new(LiveSource::new(
+            &soundfont,
+            synth_effects,
+            receiver,
+        )));
+        sink.set_volume(volume_to_amplitude(self.volume));
+        sink.play();
+
+        self.live_input.port = Some(port);
+        self.live_input.messages = Some(sender);
+        self.live_input.followed = self.active_font.as_ref().map(|(_, font)| font.get_path());
+        self.live_input.font = Some(font);
+        Ok(())
+    }
+
+    fn stop_live_input(&mut self) {
+        // The source ends once the port and this sender are gone
+        self.live_input.port = None;
+        self.live_input.messages = None;
+        if let Some(sink) = &self.live_input.sink {
+            sink.clear();
+        }
+    }
+
+    /// Switch to the soundfont of the playing song.
+    pub(super) fn update_live_input(&mut self) {
+        if self.live_input.port.is_none() {
+            return;
+        }
+        let Some((_, active_font)) = &self.active_font else {
+            return;
+        };
+        let path = active_font.get_path();
+        if self.live_input.followed.as_ref() == Some(&path) {
+            return;
+        }
+        let font = active_font.clone();
+        // Tried once, not again every frame
+        self.live_input.followed = Some(path);
+        let soundfont = match load_soundfont(&font, &mut None) {
+            Ok(soundfont) => soundfont,
+            Err(e) => {
+                self.push_error(format!("Live input can't use {}: {e}", font.get_name()));
+                return;
+            }
+        };
+        let synth_effects = self
+            .resolve_audio_profile(self.playing_playlist_idx)
+            .synth_effects;
+        if let Some(messages) = &self.live_input.messages {
+            let _ = messages.send(LiveMessage::Font {
+                soundfont,
+                synth_effects,
+            });
+        }
+        self.live_input.font = Some(font);
+    }
+
+    pub(super) fn update_live_volume(&self) {
+        if let Some(sink) = &self.live_input.sink {
+            sink.set_volume(volume_to_amplitude(self.volume));
+        }
+    }
+}
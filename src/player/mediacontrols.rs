@@ -1,21 +1,79 @@
-//! OS integration for media controls and metadata
+//! OS integration for media controls and metadata. Needs the `gui` feature, without it only
+//! global hotkeys send control events.
 //!
 //! TODO: Make this work on Windows.
 //! <https://github.com/sevonj/sfontplayer/issues/82>
 
-use std::sync::Arc;
+#[cfg(feature = "gui")]
+use std::sync::mpsc::Sender;
+use std::time::Duration;
 
-use eframe::egui::mutex::Mutex;
+#[cfg(feature = "gui")]
 use souvlaki::{
     MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig,
+    SeekDirection,
 };
 
+#[cfg(feature = "gui")]
+use super::cover_art::cover_url;
 use super::{Player, PlayerEvent};
 
-#[cfg(not(target_os = "windows"))]
+/// Seek step of media keys that don't say how far
+const SEEK_STEP: Duration = Duration::from_secs(5);
+
+/// Playback command from the OS media controls or a global hotkey
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlEvent {
+    Play,
+    Pause,
+    Toggle,
+    Next,
+    Previous,
+    Stop,
+    /// 1.0 is 100%
+    SetVolume(f64),
+    /// Step is `SEEK_STEP` if not given
+    Seek {
+        forward: bool,
+        step: Option<Duration>,
+    },
+    SetPosition(Duration),
+    Raise,
+    Quit,
+    OpenUri,
+}
+#[cfg(feature = "gui")]
+impl From<MediaControlEvent> for ControlEvent {
+    fn from(event: MediaControlEvent) -> Self {
+        match event {
+            MediaControlEvent::Play => Self::Play,
+            MediaControlEvent::Pause => Self::Pause,
+            MediaControlEvent::Toggle => Self::Toggle,
+            MediaControlEvent::Next => Self::Next,
+            MediaControlEvent::Previous => Self::Previous,
+            MediaControlEvent::Stop => Self::Stop,
+            MediaControlEvent::SetVolume(volume) => Self::SetVolume(volume),
+            MediaControlEvent::Seek(direction) => Self::Seek {
+                forward: direction == SeekDirection::Forward,
+                step: None,
+            },
+            MediaControlEvent::SeekBy(direction, step) => Self::Seek {
+                forward: direction == SeekDirection::Forward,
+                step: Some(step),
+            },
+            MediaControlEvent::SetPosition(MediaPosition(position)) => Self::SetPosition(position),
+            MediaControlEvent::Raise => Self::Raise,
+            MediaControlEvent::Quit => Self::Quit,
+            MediaControlEvent::OpenUri(_) => Self::OpenUri,
+        }
+    }
+}
+
+/// Fails e.g. when there's no D-Bus session. The player works without media controls.
+#[cfg(all(not(target_os = "windows"), feature = "gui"))]
 pub(super) fn create_mediacontrols(
-    event_queue: Arc<Mutex<Vec<MediaControlEvent>>>,
-) -> MediaControls {
+    event_queue: Sender<ControlEvent>,
+) -> Result<MediaControls, souvlaki::Error> {
     #[cfg(not(target_os = "windows"))]
     let hwnd = None;
 
@@ -28,45 +86,77 @@ pub(super) fn create_mediacontrols(
         hwnd,
     };
 
-    let mut controls = MediaControls::new(config).expect("Failed to create MediaControls!");
-    // controls
-    //     .attach(|event: MediaControlEvent| println!("Event received: {event:?}"))
-    //     .expect("MediaControls Attach failed.");
-    controls
-        .attach(move |event: MediaControlEvent| {
-            event_queue.lock().push(event);
-        })
-        .expect("MediaControls Attach failed.");
-    controls
+    let mut controls = MediaControls::new(config)?;
+    controls.attach(move |event: MediaControlEvent| {
+        let _ = event_queue.send(event.into());
+    })?;
+    Ok(controls)
 }
 
 impl Player {
+    // Without the gui feature there are no media controls to update
+    #[cfg_attr(
+        not(feature = "gui"),
+        allow(
+            clippy::unused_self,
+            clippy::needless_pass_by_ref_mut,
+            clippy::missing_const_for_fn
+        )
+    )]
     pub(super) fn mediacontrol_update_song(&mut self) {
-        #[cfg(not(target_os = "windows"))]
+        #[cfg(all(not(target_os = "windows"), feature = "gui"))]
         {
-            let Some(midi_index) = self.get_playlist().get_song_idx() else {
+            if self.mediacontrol.is_none() {
+                return;
+            }
+            let playlist = self.get_playing_playlist();
+            let Some(midi) = playlist
+                .get_song_idx()
+                .and_then(|index| playlist.get_songs().get(index))
+            else {
                 // Clear song
-                let _ = self.mediacontrol.set_metadata(MediaMetadata::default());
+                if let Some(controls) = &mut self.mediacontrol {
+                    let _ = controls.set_metadata(MediaMetadata::default());
+                }
                 return;
             };
-            let midi = &self.get_playlist().get_songs()[midi_index];
 
-            let filename = midi.get_name();
-            let _ = self.mediacontrol.set_metadata(MediaMetadata {
-                title: Some(&filename),
-                // Give an empty name to hide "Unknown Artist"
-                artist: Some(""),
-                duration: midi.get_duration(),
-                ..MediaMetadata::default()
-            });
+            let title = midi.read_title().unwrap_or_else(|| midi.get_name());
+            let album = playlist.name.clone();
+            let cover = cover_url(&title);
+            // Playback length accounts for the song's start and end points
+            let duration = Some(self.get_playback_length())
+                .filter(|length| !length.is_zero())
+                .or_else(|| midi.get_duration());
+            if let Some(controls) = &mut self.mediacontrol {
+                let _ = controls.set_metadata(MediaMetadata {
+                    title: Some(&title),
+                    album: Some(&album),
+                    // Give an empty name to hide "Unknown Artist"
+                    artist: Some(""),
+                    cover_url: cover.as_deref(),
+                    duration,
+                });
+            }
 
             self.mediacontrol_update_playback();
         }
     }
 
+    #[cfg_attr(
+        not(feature = "gui"),
+        allow(
+            clippy::unused_self,
+            clippy::needless_pass_by_ref_mut,
+            clippy::missing_const_for_fn
+        )
+    )]
     pub(super) fn mediacontrol_update_playback(&mut self) {
-        #[cfg(not(target_os = "windows"))]
+        #[cfg(all(not(target_os = "windows"), feature = "gui"))]
         {
+            if self.mediacontrol.is_none() {
+                return;
+            }
             let playback = if self.is_empty() {
                 MediaPlayback::Stopped
             } else if self.is_paused() {
@@ -79,49 +169,84 @@ impl Player {
                 }
             };
 
-            let _ = self.mediacontrol.set_playback(playback);
+            if let Some(controls) = &mut self.mediacontrol {
+                let _ = controls.set_playback(playback);
+            }
         }
     }
 
+    #[cfg_attr(
+        not(all(target_os = "linux", feature = "gui")),
+        allow(
+            clippy::unused_self,
+            clippy::needless_pass_by_ref_mut,
+            clippy::missing_const_for_fn
+        )
+    )]
     pub(super) fn mediacontrol_update_volume(&mut self) {
-        #[cfg(target_os = "linux")]
-        let _ = self.mediacontrol.set_volume(f64::from(self.volume) / 100.0);
+        #[cfg(all(target_os = "linux", feature = "gui"))]
+        if let Some(controls) = &mut self.mediacontrol {
+            // 1.0 is 100%. MPRIS allows going above that, which is where the boost range goes.
+            let _ = controls.set_volume(f64::from(self.volume) / 100.0);
+        }
+    }
+
+    fn mediacontrol_seek(&mut self, forward: bool, step: Duration) {
+        let position = self.get_playback_position();
+        let target = if forward {
+            (position + step).min(self.get_playback_length())
+        } else {
+            position.saturating_sub(step)
+        };
+        self.seek_to(target);
+        self.mediacontrol_update_playback();
     }
 
+    #[cfg(feature = "gui")]
     fn get_media_position(&self) -> MediaPosition {
         MediaPosition(self.get_playback_position())
     }
 
+    /// Keep OS metadata in sync with the player
+    pub(super) fn mediacontrol_handle_player_event(&mut self, event: &PlayerEvent) {
+        match event {
+            PlayerEvent::SongChanged { .. } => self.mediacontrol_update_song(),
+            PlayerEvent::PositionTick { .. } => self.mediacontrol_update_playback(),
+            _ => (),
+        }
+    }
+
     pub(super) fn mediacontrol_handle_events(&mut self) {
         #[cfg(not(target_os = "windows"))]
         {
-            let mut event_queue = self.mediacontrol_events.lock().clone();
-            self.mediacontrol_events.lock().clear();
-
-            while !event_queue.is_empty() {
-                match event_queue.remove(0) {
-                    MediaControlEvent::Play => self.play(),
-                    MediaControlEvent::Pause => self.pause(),
-                    MediaControlEvent::Toggle => {
+            while let Ok(event) = self.mediacontrol_events.try_recv() {
+                match event {
+                    ControlEvent::Play => self.play(),
+                    ControlEvent::Pause => self.pause(),
+                    ControlEvent::Toggle => {
                         if self.is_paused() {
                             self.play();
                         } else {
                             self.pause();
                         }
                     }
-                    MediaControlEvent::Next => self.skip(),
-                    MediaControlEvent::Previous => self.skip_back(),
-                    MediaControlEvent::Stop => self.stop(),
-                    MediaControlEvent::SetVolume(vol) => self.set_volume(vol as f32 * 100.0),
+                    ControlEvent::Next => self.skip(),
+                    ControlEvent::Previous => self.skip_back(),
+                    ControlEvent::Stop => self.stop(),
+                    ControlEvent::SetVolume(vol) => self.set_volume(vol as f32 * 100.0),
 
-                    MediaControlEvent::Seek(_)
-                    | MediaControlEvent::SeekBy(_, _)
-                    | MediaControlEvent::SetPosition(_) => self.push_error("Todo".into()),
+                    ControlEvent::Seek { forward, step } => {
+                        self.mediacontrol_seek(forward, step.unwrap_or(SEEK_STEP));
+                    }
+                    ControlEvent::SetPosition(position) => {
+                        self.seek_to(position.min(self.get_playback_length()));
+                        self.mediacontrol_update_playback();
+                    }
 
-                    MediaControlEvent::Raise => self.player_events.push(PlayerEvent::Raise),
-                    MediaControlEvent::Quit => self.player_events.push(PlayerEvent::Quit),
+                    ControlEvent::Raise => self.emit(&PlayerEvent::Raise),
+                    ControlEvent::Quit => self.emit(&PlayerEvent::Quit),
 
-                    MediaControlEvent::OpenUri(_) => {
+                    ControlEvent::OpenUri => {
                         self.push_error("SfontPlayer doesn't support opening URIs.".into());
                     }
                 }
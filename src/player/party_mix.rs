@@ -0,0 +1,133 @@
+//! "Play all playlists" mode: one queue across every open playlist.
+//!
+
+use std::path::PathBuf;
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use super::playlist::Playlist;
+
+/// Song in a combined queue. Refers to playlists by id, since indices change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartyEntry {
+    pub playlist_id: usize,
+    pub song: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct PartyQueue {
+    entries: Vec<PartyEntry>,
+    index: usize,
+}
+
+impl PartyQueue {
+    /// Combine songs of all playlists, in playlist order or shuffled.
+    #[must_use]
+    pub fn build(playlists: &[Playlist], shuffle_seed: Option<u64>) -> Self {
+        let mut entries: Vec<PartyEntry> = playlists
+            .iter()
+            .flat_map(|playlist| {
+                playlist.get_songs().iter().map(|song| PartyEntry {
+                    playlist_id: playlist.get_id(),
+                    song: song.get_path(),
+                })
+            })
+            .collect();
+        if let Some(seed) = shuffle_seed {
+            entries.shuffle(&mut StdRng::seed_from_u64(seed));
+        }
+        Self { entries, index: 0 }
+    }
+
+    #[must_use]
+    pub fn get_current(&self) -> Option<&PartyEntry> {
+        self.entries.get(self.index)
+    }
+    #[must_use]
+    pub const fn get_index(&self) -> usize {
+        self.index
+    }
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Move to next entry. Returns false at the end, unless wrapping around.
+    pub const fn advance(&mut self, wrap: bool) -> bool {
+        if self.index + 1 < self.entries.len() {
+            self.index += 1;
+            true
+        } else if wrap && !self.entries.is_empty() {
+            self.index = 0;
+            true
+        } else {
+            false
+        }
+    }
+    /// Move to previous entry. Returns false at the start, unless wrapping around.
+    pub const fn go_back(&mut self, wrap: bool) -> bool {
+        if self.index > 0 {
+            self.index -= 1;
+            true
+        } else if wrap && !self.entries.is_empty() {
+            self.index = self.entries.len() - 1;
+            true
+        } else {
+            false
+        }
+    }
+    /// Drop current entry, e.g. when its song or playlist is gone.
+    pub fn remove_current(&mut self) {
+        if self.index < self.entries.len() {
+            self.entries.remove(self.index);
+        }
+        if self.index >= self.entries.len() {
+            self.index = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_and_advance() {
+        let mut first = Playlist::default();
+        first.add_song("a.mid".into()).unwrap();
+        first.add_song("b.mid".into()).unwrap();
+        let mut second = Playlist::default();
+        second.add_song("c.mid".into()).unwrap();
+        let playlists = [first, second];
+
+        let mut queue = PartyQueue::build(&playlists, None);
+        assert_eq!(queue.len(), 3);
+        assert_eq!(
+            queue.get_current(),
+            Some(&PartyEntry {
+                playlist_id: playlists[0].get_id(),
+                song: "a.mid".into()
+            })
+        );
+        assert!(queue.advance(false));
+        assert!(queue.advance(false));
+        assert_eq!(
+            queue.get_current().unwrap().playlist_id,
+            playlists[1].get_id()
+        );
+        assert!(!queue.advance(false));
+        assert!(queue.advance(true));
+        assert_eq!(queue.get_index(), 0);
+        assert!(!queue.go_back(false));
+        assert!(queue.go_back(true));
+        assert_eq!(queue.get_index(), 2);
+
+        queue.remove_current();
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.get_index(), 0);
+    }
+}
@@ -0,0 +1,94 @@
+//! Songs played this session, and how they were played.
+//!
+
+use std::{collections::VecDeque, path::PathBuf};
+
+use super::{font_chain::FontChainLink, playlist::font_meta::FontMeta};
+
+/// Oldest entries are dropped after this
+const PLAY_HISTORY_LEN: usize = 50;
+
+/// One play of a song. Refers to the playlist by id, since indices change.
+///
+/// The player has no transpose setting, so there's none to record.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub playlist_id: usize,
+    pub song: PathBuf,
+    /// Soundfont that was heard, and where it came from
+    pub font: FontMeta,
+    pub font_link: FontChainLink,
+    /// Playback speed when the song started
+    pub speed: f32,
+}
+impl HistoryEntry {
+    #[must_use]
+    pub fn get_song_name(&self) -> String {
+        self.song.file_name().map_or_else(
+            || self.song.to_string_lossy().to_string(),
+            |name| name.to_string_lossy().to_string(),
+        )
+    }
+}
+
+/// Newest first
+#[derive(Debug, Clone, Default)]
+pub struct PlayHistory {
+    entries: VecDeque<HistoryEntry>,
+}
+impl PlayHistory {
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.entries.push_front(entry);
+        self.entries.truncate(PLAY_HISTORY_LEN);
+    }
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&HistoryEntry> {
+        self.entries.get(index)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn entry(song: &str) -> HistoryEntry {
+        HistoryEntry {
+            playlist_id: 0,
+            song: song.into(),
+            font: FontMeta::builtin(),
+            font_link: FontChainLink::BuiltIn,
+            speed: 1.,
+        }
+    }
+
+    #[test]
+    fn test_newest_first() {
+        let mut history = PlayHistory::default();
+        assert!(history.is_empty());
+        history.push(entry("dir/a.mid"));
+        history.push(entry("dir/b.mid"));
+        assert_eq!(
+            history.get(0).map(HistoryEntry::get_song_name).as_deref(),
+            Some("b.mid")
+        );
+        assert_eq!(history.iter().count(), 2);
+
+        for _ in 0..PLAY_HISTORY_LEN {
+            history.push(entry("c.mid"));
+        }
+        assert_eq!(history.iter().count(), PLAY_HISTORY_LEN);
+        assert!(history.iter().all(|entry| entry.song == Path::new("c.mid")));
+    }
+}
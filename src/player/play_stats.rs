@@ -0,0 +1,73 @@
+//! Per-song playback statistics: how many times and when each song was last played.
+//!
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SongStats {
+    pub play_count: u32,
+    /// Unix time in seconds
+    pub last_played: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PlayStats {
+    songs: HashMap<PathBuf, SongStats>,
+}
+impl PlayStats {
+    #[must_use]
+    pub fn get(&self, path: &Path) -> Option<&SongStats> {
+        self.songs.get(path)
+    }
+    /// Unix time in seconds. Never played songs are 0.
+    #[must_use]
+    pub fn get_last_played(&self, path: &Path) -> u64 {
+        self.get(path).map_or(0, |stats| stats.last_played)
+    }
+    /// Count a play of a song at `time` (unix seconds)
+    pub fn record_play(&mut self, path: &Path, time: u64) {
+        let stats = self.songs.entry(path.to_owned()).or_default();
+        stats.play_count += 1;
+        stats.last_played = time;
+    }
+}
+
+/// Current unix time in seconds
+#[must_use]
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_play() {
+        let mut stats = PlayStats::default();
+        let path = Path::new("song.mid");
+        assert_eq!(stats.get_last_played(path), 0);
+        stats.record_play(path, 100);
+        stats.record_play(path, 200);
+        assert_eq!(
+            stats.get(path),
+            Some(&SongStats {
+                play_count: 2,
+                last_played: 200
+            })
+        );
+
+        let json = serde_json::to_value(&stats).expect("serialize");
+        assert_eq!(PlayStats::deserialize(&json).expect("deserialize"), stats);
+    }
+}
@@ -1,19 +1,33 @@
-use super::soundfont_list::FontSort;
+use super::{program_lock::ProgramLocks, queue::Queue, soundfont_list::FontSort};
 
 use anyhow::bail;
 use enums::{FileListMode, SongSort};
 use error::PlaylistError;
 use font_meta::FontMeta;
 use midi_meta::MidiMeta;
-use rand::seq::SliceRandom;
-use std::{fs, path::PathBuf, time::Duration, vec};
+use rand::Rng;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+    vec,
+};
 use walkdir::WalkDir;
 
+pub mod ending_check;
 pub mod enums;
 pub mod font_meta;
+pub mod import;
 pub mod midi_meta;
+pub mod song_list;
 
+mod archive;
 mod error;
+mod paste;
 mod serialize_playlist;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -25,8 +39,15 @@ pub enum DeletionStatus {
     QueuedDiscard,
 }
 
+/// Runtime-only playlist identity, so playlists can be found even after they're rearranged.
+fn next_playlist_id() -> usize {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
 #[derive(Clone)]
 pub struct Playlist {
+    id: usize,
     pub name: String,
     /// If None, this is a normal playlist. If Some, this is a portable playlist.
     portable_filepath: Option<PathBuf>,
@@ -46,11 +67,38 @@ pub struct Playlist {
     midi_dir: Option<PathBuf>,
     song_sort: SongSort,
 
-    pub queue: Vec<usize>,
-    pub queue_idx: Option<usize>,
+    queue: Queue,
+    /// Queue was loaded from app state and hasn't been played yet
+    queue_restored: bool,
+    /// Overrides the player's global program locks, per channel
+    program_locks: ProgramLocks,
+    /// Name of the audio profile used by this playlist
+    audio_profile: Option<String>,
+    /// Songs that appear in the song directory are queued to play next
+    autoplay_new_files: bool,
 }
 impl Playlist {
+    #[must_use]
+    pub const fn get_id(&self) -> usize {
+        self.id
+    }
+    /// Copy of this playlist that counts as a separate playlist
+    #[must_use]
+    pub fn duplicate(&self) -> Self {
+        let mut playlist = self.clone();
+        playlist.id = next_playlist_id();
+        playlist
+    }
+
+    /// Add a soundfont or a midi file, depending on what the file turns out to be
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file is neither, or if the list it belongs to is filled from a directory.
     pub fn add_file(&mut self, path: PathBuf) -> Result<(), PlaylistError> {
+        if archive::is_archive(&path) {
+            return self.add_song(path);
+        }
         // Fast quess
         if path.ends_with(".mid") {
             let midimeta = MidiMeta::new(path.clone());
@@ -70,18 +118,64 @@ impl Playlist {
 
         Err(PlaylistError::UnknownFileFormat { path })
     }
+    /// Add a file from memory, e.g. one dropped in a browser. The path is only used as a name,
+    /// and the contents aren't saved with the playlist.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the data is neither a soundfont nor a midi file, or if its list is filled from a
+    /// directory.
+    pub fn add_file_data(&mut self, path: PathBuf, data: Arc<[u8]>) -> Result<(), PlaylistError> {
+        let fontmeta = FontMeta::from_bytes(path.clone(), data.clone());
+        if fontmeta.get_status().is_ok() {
+            if self.font_list_mode != FileListMode::Manual {
+                return Err(PlaylistError::ModifyAutoFontList {
+                    mode: self.font_list_mode,
+                });
+            }
+            if !self.contains_font(&path) {
+                self.fonts.push(fontmeta);
+            }
+            self.unsaved_changes = true;
+            self.refresh_font_list();
+            return Ok(());
+        }
+        let midimeta = MidiMeta::from_bytes(path.clone(), data);
+        if midimeta.get_status().is_ok() {
+            if self.song_list_mode != FileListMode::Manual {
+                return Err(PlaylistError::ModifyAutoSongList {
+                    mode: self.song_list_mode,
+                });
+            }
+            if !self.contains_song(&path) {
+                self.midis.push(midimeta);
+            }
+            self.unsaved_changes = true;
+            self.refresh_song_list();
+            return Ok(());
+        }
+
+        Err(PlaylistError::UnknownFileFormat { path })
+    }
 
     // --- Soundfonts
 
+    #[must_use]
     pub const fn get_fonts(&self) -> &Vec<FontMeta> {
         &self.fonts
     }
     pub fn get_fonts_mut(&mut self) -> &mut Vec<FontMeta> {
         &mut self.fonts
     }
+    #[must_use]
     pub const fn get_font_idx(&self) -> Option<usize> {
         self.font_idx
     }
+    /// Select a font, refreshing its metadata
+    ///
+    /// # Errors
+    ///
+    /// Fails if the index is out of range. The selection is left as it was.
     pub fn set_font_idx(&mut self, value: Option<usize>) -> anyhow::Result<()> {
         match value {
             Some(index) => {
@@ -96,6 +190,11 @@ impl Playlist {
         }
         Ok(())
     }
+    /// Add a soundfont by path
+    ///
+    /// # Errors
+    ///
+    /// Fails if the font list isn't in manual mode.
     pub fn add_font(&mut self, path: PathBuf) -> Result<(), PlaylistError> {
         if self.font_list_mode != FileListMode::Manual {
             return Err(PlaylistError::ModifyAutoFontList {
@@ -113,6 +212,11 @@ impl Playlist {
         }
         self.unsaved_changes = true;
     }
+    /// Queue a soundfont for removal
+    ///
+    /// # Errors
+    ///
+    /// Fails if the font list isn't in manual mode, or the index is out of range.
     pub fn remove_font(&mut self, index: usize) -> Result<(), PlaylistError> {
         if self.font_list_mode != FileListMode::Manual {
             return Err(PlaylistError::ModifyAutoFontList {
@@ -135,6 +239,17 @@ impl Playlist {
         self.font_idx = None;
         self.unsaved_changes = true;
     }
+    /// Put back fonts removed by `clear_fonts`
+    pub fn restore_fonts(&mut self, fonts: Vec<FontMeta>) {
+        for font in fonts {
+            if !self.contains_font(&font.get_path()) {
+                self.fonts.push(font);
+            }
+        }
+        self.unsaved_changes = true;
+        self.refresh_font_list();
+    }
+    #[must_use]
     pub fn contains_font(&self, filepath: &PathBuf) -> bool {
         for i in 0..self.fonts.len() {
             if self.fonts[i].get_path() == *filepath {
@@ -143,9 +258,11 @@ impl Playlist {
         }
         false
     }
+    #[must_use]
     pub const fn get_font_list_mode(&self) -> FileListMode {
         self.font_list_mode
     }
+    #[must_use]
     pub const fn get_font_dir(&self) -> Option<&PathBuf> {
         self.font_dir.as_ref()
     }
@@ -173,25 +290,20 @@ impl Playlist {
         for i in 0..self.fonts.len() {
             let filepath = self.fonts[i].get_path();
             // File doesn't exist anymore
-            if !filepath.exists() {
-                self.force_remove_font(i).expect("refresh: Font rm failed‽");
-            }
-            match self.font_list_mode {
-                FileListMode::Directory => {
+            let remove = !filepath.exists()
+                || match self.font_list_mode {
                     // Delete if dir is not immediate parent
-                    if filepath.parent() != self.font_dir.as_deref() {
-                        self.force_remove_font(i).expect("refresh: Font rm failed‽");
-                    }
-                }
-                FileListMode::Subdirectories => {
+                    FileListMode::Directory => filepath.parent() != self.font_dir.as_deref(),
                     // Delete if dir is not a parent
-                    if let Some(dir) = &self.font_dir {
-                        if !filepath.starts_with(dir) {
-                            self.force_remove_font(i).expect("refresh: Font rm failed‽");
-                        }
-                    }
-                }
-                FileListMode::Manual => unreachable!(),
+                    FileListMode::Subdirectories => self
+                        .font_dir
+                        .as_ref()
+                        .is_some_and(|dir| !filepath.starts_with(dir)),
+                    FileListMode::Manual => unreachable!(),
+                };
+            if remove {
+                self.fonts[i].is_queued_for_deletion = true;
+                self.unsaved_changes = true;
             }
         }
         self.delete_queued();
@@ -209,7 +321,7 @@ impl Playlist {
                         if self.contains_font(&path) {
                             continue;
                         }
-                        if path.is_file() && path.extension().is_some_and(|s| s == "sf2") {
+                        if path.is_file() && font_meta::is_font_path(&path) {
                             self.force_add_font(path);
                         }
                     }
@@ -221,7 +333,7 @@ impl Playlist {
                     .filter_map(std::result::Result::ok)
                 {
                     let path = entry.path();
-                    if path.is_file() && path.extension().is_some_and(|s| s == "sf2") {
+                    if path.is_file() && font_meta::is_font_path(path) {
                         self.force_add_font(path.into());
                     }
                 }
@@ -251,6 +363,7 @@ impl Playlist {
                 self.fonts.sort_by_key(font_meta::FontMeta::get_size);
                 self.fonts.reverse();
             }
+            FontSort::Custom => (),
         };
 
         // Find the selected again
@@ -262,6 +375,7 @@ impl Playlist {
             }
         }
     }
+    #[must_use]
     pub const fn get_font_sort(&self) -> FontSort {
         self.font_sort
     }
@@ -269,18 +383,53 @@ impl Playlist {
         self.font_sort = sort;
         self.refresh_font_list();
     }
+    /// Restore saved selection and order without reading any files. Use `refresh` later.
+    pub fn restore_font_view(&mut self, index: Option<usize>, sort: FontSort) {
+        self.font_idx = index.filter(|&index| index < self.fonts.len());
+        self.font_sort = sort;
+        self.sort_fonts();
+    }
+    /// Move a font to another position. The list switches to custom order, starting from the
+    /// current one.
+    ///
+    /// # Errors
+    ///
+    /// Fails if either index is out of range.
+    pub fn move_font(&mut self, from: usize, to: usize) -> Result<(), PlaylistError> {
+        for index in [from, to] {
+            if index >= self.fonts.len() {
+                return Err(PlaylistError::InvalidFontIndex { index });
+            }
+        }
+        let selected = self.font_idx.map(|index| self.fonts[index].get_path());
+        let font = self.fonts.remove(from);
+        self.fonts.insert(to, font);
+        if let Some(selected) = selected {
+            self.font_idx = self.fonts.iter().position(|f| f.get_path() == selected);
+        }
+        self.font_sort = FontSort::Custom;
+        self.unsaved_changes = true;
+        Ok(())
+    }
 
     // --- Midi files
 
+    #[must_use]
     pub const fn get_songs(&self) -> &Vec<MidiMeta> {
         &self.midis
     }
     pub fn get_songs_mut(&mut self) -> &mut Vec<MidiMeta> {
         &mut self.midis
     }
+    #[must_use]
     pub const fn get_song_idx(&self) -> Option<usize> {
         self.midi_idx
     }
+    /// Select a song, refreshing its metadata
+    ///
+    /// # Errors
+    ///
+    /// Fails if the index is out of range. The selection is left as it was.
     pub fn set_song_idx(&mut self, value: Option<usize>) -> anyhow::Result<()> {
         match value {
             Some(index) => {
@@ -295,16 +444,63 @@ impl Playlist {
         }
         Ok(())
     }
+    /// Add a midi file. Zip archives add every midi file they contain.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the song list isn't in manual mode, or an archive can't be read.
     pub fn add_song(&mut self, path: PathBuf) -> Result<(), PlaylistError> {
         if self.song_list_mode != FileListMode::Manual {
             return Err(PlaylistError::ModifyAutoSongList {
                 mode: self.song_list_mode,
             });
         }
+        if archive::is_archive(&path) {
+            let songs = archive::list_songs(&path).map_err(|e| PlaylistError::CantReadArchive {
+                path: path.clone(),
+                message: e.to_string(),
+            })?;
+            for song in songs {
+                self.force_add_song(song);
+            }
+            self.refresh_song_list();
+            return Ok(());
+        }
         self.force_add_song(path);
         self.refresh_song_list();
         Ok(())
     }
+    /// Add songs from pasted text: paths or `file://` URLs, one per line. Lines that aren't
+    /// midi files or archives are skipped. Returns how many songs were added.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the song list isn't in manual mode.
+    pub fn paste_songs(&mut self, text: &str) -> Result<usize, PlaylistError> {
+        if self.song_list_mode != FileListMode::Manual {
+            return Err(PlaylistError::ModifyAutoSongList {
+                mode: self.song_list_mode,
+            });
+        }
+        let song_count = self.midis.len();
+        for path in paste::parse_paths(text) {
+            if !path.is_file() {
+                continue;
+            }
+            if archive::is_archive(&path) {
+                for song in archive::list_songs(&path).unwrap_or_default() {
+                    self.force_add_song(song);
+                }
+            } else if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("mid"))
+            {
+                self.force_add_song(path);
+            }
+        }
+        self.refresh_song_list();
+        Ok(self.midis.len() - song_count)
+    }
     /// Bypasses extra correctness checks meant for gui.
     fn force_add_song(&mut self, path: PathBuf) {
         if !self.contains_song(&path) {
@@ -312,6 +508,59 @@ impl Playlist {
         }
         self.unsaved_changes = true;
     }
+    /// Look for songs that end abruptly. Returns how many were found.
+    pub fn check_song_endings(&mut self) -> usize {
+        for song in &mut self.midis {
+            song.check_ending();
+        }
+        self.midis
+            .iter()
+            .filter(|song| song.get_ending_issue().is_some())
+            .count()
+    }
+    /// Add a song and put it at the end of the queue. The song may already be in the playlist.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the song can't be added, see [`Self::add_song`].
+    pub fn enqueue_song(&mut self, path: &Path) -> Result<(), PlaylistError> {
+        let song_paths = self.get_song_paths();
+        self.add_song(path.to_path_buf())?;
+        // Adding may have re-sorted the songs
+        self.reindex_queue(&song_paths);
+        if let Some(index) = self.find_song(path) {
+            self.queue.push(index);
+        }
+        Ok(())
+    }
+    /// Put songs already in the playlist right after the current queue position.
+    pub fn enqueue_next(&mut self, paths: &[PathBuf]) {
+        let songs: Vec<usize> = paths
+            .iter()
+            .filter_map(|path| self.find_song(path))
+            .collect();
+        self.queue.insert_next(&songs);
+    }
+    fn find_song(&self, path: &Path) -> Option<usize> {
+        self.midis.iter().position(|song| song.get_path() == path)
+    }
+    fn get_song_paths(&self) -> Vec<PathBuf> {
+        self.midis.iter().map(MidiMeta::get_path).collect()
+    }
+    /// Point the queue to the same songs after the song list has changed. `song_paths` is the
+    /// song list from before the change. Songs that are gone are left out.
+    fn reindex_queue(&mut self, song_paths: &[PathBuf]) {
+        let midis = &self.midis;
+        self.queue.remap(|index| {
+            let path = song_paths.get(index)?;
+            midis.iter().position(|song| song.get_path() == *path)
+        });
+    }
+    /// Queue a song for removal
+    ///
+    /// # Errors
+    ///
+    /// Fails if the song list isn't in manual mode, or the index is out of range.
     pub fn remove_song(&mut self, index: usize) -> Result<(), PlaylistError> {
         if self.song_list_mode != FileListMode::Manual {
             return Err(PlaylistError::ModifyAutoSongList {
@@ -329,11 +578,84 @@ impl Playlist {
         self.unsaved_changes = true;
         Ok(())
     }
+    /// Assign a soundfont to a single song. None removes the assignment.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no song at `index`.
+    pub fn set_song_soundfont(
+        &mut self,
+        index: usize,
+        soundfont: Option<PathBuf>,
+    ) -> Result<(), PlaylistError> {
+        if index >= self.midis.len() {
+            return Err(PlaylistError::InvalidSongIndex { index });
+        }
+        self.midis[index].set_soundfont(soundfont);
+        self.unsaved_changes = true;
+        Ok(())
+    }
+    /// Play only part of a song. None plays from the beginning or to the end.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no song at `index`, or the end isn't after the start.
+    pub fn set_song_range(
+        &mut self,
+        index: usize,
+        start: Option<Duration>,
+        end: Option<Duration>,
+    ) -> Result<(), PlaylistError> {
+        if index >= self.midis.len() {
+            return Err(PlaylistError::InvalidSongIndex { index });
+        }
+        if let (Some(start), Some(end)) = (start, end) {
+            if end <= start {
+                return Err(PlaylistError::InvalidSongRange { start, end });
+            }
+        }
+        self.midis[index].set_range(start, end);
+        self.unsaved_changes = true;
+        Ok(())
+    }
+    /// Loop part of a song. None turns the loop off.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no song at `index`, or the loop ends before it starts.
+    pub fn set_song_loop(
+        &mut self,
+        index: usize,
+        points: Option<(Duration, Duration)>,
+    ) -> Result<(), PlaylistError> {
+        if index >= self.midis.len() {
+            return Err(PlaylistError::InvalidSongIndex { index });
+        }
+        if let Some((start, end)) = points {
+            if end <= start {
+                return Err(PlaylistError::InvalidSongRange { start, end });
+            }
+        }
+        self.midis[index].set_loop_points(points);
+        self.unsaved_changes = true;
+        Ok(())
+    }
     pub fn clear_songs(&mut self) {
         self.midis.clear();
         self.midi_idx = None;
         self.unsaved_changes = true;
     }
+    /// Put back songs removed by `clear_songs`
+    pub fn restore_songs(&mut self, songs: Vec<MidiMeta>) {
+        for song in songs {
+            if !self.contains_song(&song.get_path()) {
+                self.midis.push(song);
+            }
+        }
+        self.unsaved_changes = true;
+        self.refresh_song_list();
+    }
+    #[must_use]
     pub fn contains_song(&self, filepath: &PathBuf) -> bool {
         for i in 0..self.midis.len() {
             if self.midis[i].get_path() == *filepath {
@@ -342,9 +664,11 @@ impl Playlist {
         }
         false
     }
+    #[must_use]
     pub const fn get_song_list_mode(&self) -> FileListMode {
         self.song_list_mode
     }
+    #[must_use]
     pub const fn get_song_dir(&self) -> Option<&PathBuf> {
         self.midi_dir.as_ref()
     }
@@ -361,6 +685,29 @@ impl Playlist {
         self.refresh_song_list();
         self.unsaved_changes = true;
     }
+    #[must_use]
+    pub const fn get_autoplay_new_files(&self) -> bool {
+        self.autoplay_new_files
+    }
+    pub const fn set_autoplay_new_files(&mut self, value: bool) {
+        self.autoplay_new_files = value;
+        self.unsaved_changes = true;
+    }
+    /// Refresh the song list and return songs that weren't in it before.
+    /// Keeps the queue pointing to the same songs.
+    pub fn scan_new_songs(&mut self) -> Vec<PathBuf> {
+        if self.song_list_mode == FileListMode::Manual {
+            return vec![];
+        }
+        let known = self.get_song_paths();
+        self.refresh_song_list();
+        self.reindex_queue(&known);
+        self.midis
+            .iter()
+            .map(MidiMeta::get_path)
+            .filter(|path| !known.contains(path))
+            .collect()
+    }
     /// Refresh midi file list
     pub fn refresh_song_list(&mut self) {
         if self.song_list_mode == FileListMode::Manual {
@@ -372,25 +719,20 @@ impl Playlist {
         for i in 0..self.midis.len() {
             let filepath = self.midis[i].get_path();
             // File doesn't exist anymore
-            if !filepath.exists() {
-                self.force_remove_song(i).expect("refresh: Song rm failed‽");
-            }
-            match self.song_list_mode {
-                FileListMode::Directory => {
+            let remove = !filepath.exists()
+                || match self.song_list_mode {
                     // Delete if dir is not immediate parent
-                    if filepath.parent() != self.midi_dir.as_deref() {
-                        self.force_remove_song(i).expect("refresh: Song rm failed‽");
-                    }
-                }
-                FileListMode::Subdirectories => {
+                    FileListMode::Directory => filepath.parent() != self.midi_dir.as_deref(),
                     // Delete if dir is not a parent
-                    if let Some(dir) = &self.midi_dir {
-                        if !filepath.starts_with(dir) {
-                            self.force_remove_song(i).expect("refresh: Song rm failed‽");
-                        }
-                    }
-                }
-                FileListMode::Manual => unreachable!(),
+                    FileListMode::Subdirectories => self
+                        .midi_dir
+                        .as_ref()
+                        .is_some_and(|dir| !filepath.starts_with(dir)),
+                    FileListMode::Manual => unreachable!(),
+                };
+            if remove {
+                self.midis[i].is_queued_for_deletion = true;
+                self.unsaved_changes = true;
             }
         }
         self.delete_queued();
@@ -469,6 +811,23 @@ impl Playlist {
             }
         }
     }
+    #[must_use]
+    pub const fn get_program_locks(&self) -> ProgramLocks {
+        self.program_locks
+    }
+    pub const fn set_program_locks(&mut self, program_locks: ProgramLocks) {
+        self.program_locks = program_locks;
+        self.unsaved_changes = true;
+    }
+    #[must_use]
+    pub fn get_audio_profile(&self) -> Option<&str> {
+        self.audio_profile.as_deref()
+    }
+    pub fn set_audio_profile(&mut self, name: Option<String>) {
+        self.audio_profile = name;
+        self.unsaved_changes = true;
+    }
+    #[must_use]
     pub const fn get_song_sort(&self) -> SongSort {
         self.song_sort
     }
@@ -476,40 +835,97 @@ impl Playlist {
         self.song_sort = sort;
         self.refresh_song_list();
     }
+    /// Restore saved selection and order without reading any files. Use `refresh` later.
+    pub fn restore_song_view(&mut self, index: Option<usize>, sort: SongSort) {
+        self.midi_idx = index.filter(|&index| index < self.midis.len());
+        self.song_sort = sort;
+        self.sort_songs();
+    }
+
+    /// Refresh file lists, and the metadata of selected files.
+    pub fn refresh(&mut self) {
+        self.refresh_font_list();
+        self.refresh_song_list();
+        if let Some(index) = self.font_idx {
+            self.fonts[index].refresh();
+        }
+        if let Some(index) = self.midi_idx {
+            self.midis[index].refresh();
+        }
+    }
 
     // --- Playback Queue
 
     /// Create a new song queue from currently available songs.
     /// To be called when song list changes, or shuffle is toggled
     pub fn rebuild_queue(&mut self, shuffle: bool) {
-        self.queue.clear();
-
-        // Create a sequential queue from all songs
-        for i in 0..self.midis.len() {
-            self.queue.push(i);
-        }
-
-        // Start from currently selected song, if any
-        self.queue_idx = Some(self.midi_idx.unwrap_or(0));
-
-        if shuffle {
-            self.queue.shuffle(&mut rand::thread_rng());
+        self.rebuild_queue_with_rng(shuffle, &mut rand::thread_rng());
+    }
+    /// Like `rebuild_queue`, but the shuffle seed is drawn from `rng`.
+    pub fn rebuild_queue_with_rng(&mut self, shuffle: bool, rng: &mut impl Rng) {
+        self.queue_restored = false;
+        self.queue = if shuffle {
+            Queue::shuffled(self.midis.len(), self.midi_idx, rng)
+        } else {
+            Queue::build(self.midis.len(), self.midi_idx, None)
+        };
+    }
+    /// Like `rebuild_queue`, but a shuffled queue is built from a known seed.
+    /// The same seed, song list, and selected song give the same order.
+    pub fn rebuild_queue_seeded(&mut self, shuffle_seed: Option<u64>) {
+        self.queue_restored = false;
+        self.queue = Queue::build(self.midis.len(), self.midi_idx, shuffle_seed);
+    }
+    #[must_use]
+    pub const fn get_queue(&self) -> &Queue {
+        &self.queue
+    }
+    pub const fn get_queue_mut(&mut self) -> &mut Queue {
+        &mut self.queue
+    }
 
-            // Make selected song the first in queue
-            if let Some(song_idx) = self.midi_idx {
-                self.queue.retain(|&x| x != song_idx); // Remove song from queue
-                self.queue.insert(0, song_idx); // Insert it to the beginning.
-            }
-            // Because selected song always becomes first on shuffle
-            self.queue_idx = Some(0);
+    #[must_use]
+    pub const fn get_shuffle_seed(&self) -> Option<u64> {
+        self.queue.get_shuffle_seed()
+    }
+    /// Queue as song paths, for saving
+    pub fn get_queue_paths(&self) -> Vec<PathBuf> {
+        self.queue
+            .get_entries()
+            .iter()
+            .filter_map(|&index| self.midis.get(index))
+            .map(MidiMeta::get_path)
+            .collect()
+    }
+    /// Restore a saved queue. Songs that are no longer in the playlist are left out.
+    pub fn restore_queue(
+        &mut self,
+        paths: &[PathBuf],
+        queue_idx: Option<usize>,
+        shuffle_seed: Option<u64>,
+    ) {
+        let entries: Vec<Option<usize>> = paths.iter().map(|path| self.find_song(path)).collect();
+        let queue = Queue::from_entries(&entries, queue_idx, shuffle_seed);
+        if queue.is_empty() {
+            return;
         }
+        self.queue = queue;
+        self.queue_restored = true;
+    }
+    /// Should a restored queue be continued? Only if it's still at the selected song.
+    /// Consumes the restored status.
+    pub fn take_restored_queue(&mut self) -> bool {
+        let restored = std::mem::take(&mut self.queue_restored);
+        restored && self.midi_idx.is_some() && self.queue.get_current() == self.midi_idx
     }
 
     // --- Misc.
 
+    #[must_use]
     pub const fn is_portable(&self) -> bool {
         self.portable_filepath.is_some()
     }
+    #[must_use]
     pub fn get_portable_path(&self) -> Option<PathBuf> {
         self.portable_filepath.clone()
     }
@@ -517,6 +933,7 @@ impl Playlist {
         self.portable_filepath = portable_filepath;
         self.unsaved_changes = true;
     }
+    #[must_use]
     pub const fn has_unsaved_changes(&self) -> bool {
         self.is_portable() && self.unsaved_changes
     }
@@ -563,6 +980,7 @@ impl Playlist {
 impl Default for Playlist {
     fn default() -> Self {
         Self {
+            id: next_playlist_id(),
             name: "Playlist".to_owned(),
             portable_filepath: None,
             unsaved_changes: true,
@@ -580,8 +998,11 @@ impl Default for Playlist {
             midi_dir: None,
             song_sort: SongSort::default(),
 
-            queue: vec![],
-            queue_idx: None,
+            queue: Queue::default(),
+            queue_restored: false,
+            program_locks: ProgramLocks::new(),
+            audio_profile: None,
+            autoplay_new_files: false,
         }
     }
 }
@@ -590,6 +1011,7 @@ impl Default for Playlist {
 mod tests {
 
     use super::*;
+    use crate::test_support::short_song;
 
     #[test]
     fn test_addfont_listmodes() {
@@ -755,6 +1177,127 @@ mod tests {
         assert!(playlist.unsaved_changes);
     }
 
+    #[test]
+    fn test_unsaved_flag_song_soundfont() {
+        let mut playlist = Playlist::default();
+        playlist.add_song("fakepath".into()).unwrap();
+        playlist.unsaved_changes = false;
+        playlist
+            .set_song_soundfont(0, Some("fakefont".into()))
+            .unwrap();
+        assert!(playlist.unsaved_changes);
+        assert!(matches!(
+            playlist.set_song_soundfont(1, None).unwrap_err(),
+            PlaylistError::InvalidSongIndex { index: 1 }
+        ));
+    }
+
+    #[test]
+    fn test_move_font() {
+        let mut playlist = Playlist::default();
+        for path in ["a.sf2", "b.sf2", "c.sf2"] {
+            playlist.add_font(path.into()).unwrap();
+        }
+        playlist.set_font_idx(Some(0)).unwrap();
+        playlist.unsaved_changes = false;
+
+        playlist.move_font(2, 0).unwrap();
+        let names: Vec<String> = playlist.fonts.iter().map(FontMeta::get_name).collect();
+        assert_eq!(names, ["c.sf2", "a.sf2", "b.sf2"]);
+        assert_eq!(playlist.get_font_idx(), Some(1));
+        assert_eq!(playlist.get_font_sort(), FontSort::Custom);
+        assert!(playlist.unsaved_changes);
+
+        // Custom order survives adding and refreshing
+        playlist.add_font("0.sf2".into()).unwrap();
+        let names: Vec<String> = playlist.fonts.iter().map(FontMeta::get_name).collect();
+        assert_eq!(names, ["c.sf2", "a.sf2", "b.sf2", "0.sf2"]);
+
+        assert!(matches!(
+            playlist.move_font(0, 4).unwrap_err(),
+            PlaylistError::InvalidFontIndex { index: 4 }
+        ));
+    }
+
+    #[test]
+    fn test_restore_view() {
+        let mut playlist = Playlist::default();
+        for path in ["a.sf2", "b.sf2", "c.sf2"] {
+            playlist.add_font(path.into()).unwrap();
+        }
+        playlist.add_song("a.mid".into()).unwrap();
+        playlist.add_song("b.mid".into()).unwrap();
+
+        // Selection follows the file
+        playlist.restore_font_view(Some(0), FontSort::NameDesc);
+        assert_eq!(playlist.get_font_idx(), Some(2));
+        assert_eq!(playlist.get_font_sort(), FontSort::NameDesc);
+        playlist.restore_song_view(Some(1), SongSort::NameDesc);
+        assert_eq!(playlist.get_song_idx(), Some(0));
+
+        playlist.restore_font_view(Some(3), FontSort::NameAsc);
+        assert_eq!(playlist.get_font_idx(), None);
+    }
+
+    #[test]
+    fn test_restore_songs() {
+        let mut playlist = Playlist::default();
+        playlist.add_song("a.mid".into()).unwrap();
+        playlist.add_song("b.mid".into()).unwrap();
+        let songs = playlist.get_songs().clone();
+        playlist.clear_songs();
+        playlist.add_song("b.mid".into()).unwrap();
+        playlist.restore_songs(songs);
+        assert_eq!(playlist.get_songs().len(), 2);
+    }
+
+    #[test]
+    fn test_restore_queue() {
+        let mut playlist = Playlist::default();
+        playlist.add_song("a.mid".into()).unwrap();
+        playlist.add_song("b.mid".into()).unwrap();
+        playlist.add_song("c.mid".into()).unwrap();
+        let paths: Vec<PathBuf> = vec!["c.mid".into(), "gone.mid".into(), "a.mid".into()];
+        playlist.restore_queue(&paths, Some(2), None);
+        assert_eq!(playlist.get_queue().get_entries(), [2, 0]);
+        assert_eq!(playlist.get_queue().get_position(), Some(1));
+        assert_eq!(
+            playlist.get_queue_paths(),
+            vec![paths[0].clone(), paths[2].clone()]
+        );
+
+        // Selection moved elsewhere: the restored queue is not used.
+        playlist.set_song_idx(Some(1)).unwrap();
+        assert!(!playlist.take_restored_queue());
+
+        playlist.restore_queue(&paths, Some(2), None);
+        playlist.set_song_idx(Some(0)).unwrap();
+        assert!(playlist.take_restored_queue());
+        assert!(!playlist.take_restored_queue());
+    }
+
+    #[test]
+    fn test_shuffle_seed_reproducible() {
+        let mut playlist = Playlist::default();
+        for i in 0..20 {
+            playlist.add_song(format!("{i}.mid").into()).unwrap();
+        }
+        playlist.rebuild_queue(true);
+        let seed = playlist.get_shuffle_seed().unwrap();
+        let queue = playlist.get_queue().clone();
+        playlist.rebuild_queue(false);
+        assert_eq!(playlist.get_shuffle_seed(), None);
+        playlist.rebuild_queue_seeded(Some(seed));
+        assert_eq!(*playlist.get_queue(), queue);
+    }
+
+    #[test]
+    fn test_duplicate_id() {
+        let playlist = Playlist::default();
+        assert_eq!(playlist.clone().get_id(), playlist.get_id());
+        assert_ne!(playlist.duplicate().get_id(), playlist.get_id());
+    }
+
     #[test]
     fn test_unsaved_flag_fontsong_clear() {
         let mut playlist = Playlist::default();
@@ -833,4 +1376,51 @@ mod tests {
         playlist.set_portable_path(None);
         assert!(playlist.unsaved_changes);
     }
+
+    #[test]
+    fn test_paste_songs() {
+        let dir = std::env::temp_dir().join(format!("sfontplayer_paste_{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("temp dir");
+        fs::write(dir.join("a.mid"), b"").expect("write");
+        fs::write(dir.join("b c.mid"), b"").expect("write");
+        fs::write(dir.join("notes.txt"), b"").expect("write");
+        let text = format!(
+            "{}\nfile://{}\n{}\n{}",
+            dir.join("a.mid").display(),
+            dir.join("b%20c.mid").display(),
+            dir.join("notes.txt").display(),
+            dir.join("missing.mid").display(),
+        );
+
+        let mut playlist = Playlist::default();
+        let added = playlist.paste_songs(&text).expect("paste");
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(added, 2);
+        assert_eq!(playlist.get_songs().len(), 2);
+
+        playlist.song_list_mode = FileListMode::Directory;
+        assert!(playlist.paste_songs(&text).is_err());
+    }
+
+    #[test]
+    fn test_add_file_data() {
+        let mut playlist = Playlist::default();
+        playlist
+            .add_file_data("font.sf2".into(), font_meta::BUILTIN_SOUNDFONT.into())
+            .expect("font");
+        playlist
+            .add_file_data("song.mid".into(), short_song().into())
+            .expect("song");
+        assert!(playlist
+            .add_file_data("notes.txt".into(), b"hello".as_slice().into())
+            .is_err());
+        assert_eq!(playlist.get_fonts().len(), 1);
+        assert_eq!(playlist.get_songs().len(), 1);
+        assert!(playlist.get_songs()[0].get_status().is_ok());
+
+        playlist.song_list_mode = FileListMode::Directory;
+        assert!(playlist
+            .add_file_data("other.mid".into(), short_song().into())
+            .is_err());
+    }
 }
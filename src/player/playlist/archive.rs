@@ -0,0 +1,191 @@
+//! Zip archive support
+//!
+//! Songs inside an archive are referred to with a virtual path: the archive path joined with the
+//! entry name, e.g. `/music/pack.zip/folder/song.mid`. Songs are read straight from the archive,
+//! so nothing needs to be extracted. The last few archives are kept open, so their index is
+//! parsed once rather than for every song.
+
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use parking_lot::{const_mutex, Mutex};
+use zip::ZipArchive;
+
+const MAX_OPEN_ARCHIVES: usize = 4;
+/// Most recently used last
+static OPEN_ARCHIVES: Mutex<Vec<OpenArchive>> = const_mutex(Vec::new());
+
+struct OpenArchive {
+    path: PathBuf,
+    /// Modification time and size when opened. If they differ, the file has changed.
+    stamp: (Option<SystemTime>, u64),
+    archive: ZipArchive<File>,
+}
+
+/// Is this path a zip archive?
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+/// List virtual paths of all midi files in an archive. Entries whose names would point outside
+/// the archive, like `../song.mid` or `/song.mid`, are skipped.
+pub fn list_songs(archive_path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut songs: Vec<PathBuf> = with_archive(archive_path, |archive| {
+        let mut songs = vec![];
+        for index in 0..archive.len() {
+            let Some(name) = archive.by_index_raw(index)?.enclosed_name() else {
+                continue;
+            };
+            if name
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("mid"))
+            {
+                songs.push(archive_path.join(name));
+            }
+        }
+        Ok(songs)
+    })?;
+    songs.sort();
+    Ok(songs)
+}
+
+/// Split a virtual path into archive path and entry name. None if the path isn't inside an
+/// archive.
+pub fn split_path(path: &Path) -> Option<(PathBuf, String)> {
+    let archive_path = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| is_archive(ancestor) && ancestor.is_file())?;
+    let entry = path.strip_prefix(archive_path).ok()?;
+    let entry_name = entry
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    Some((archive_path.to_owned(), entry_name))
+}
+
+/// Read an archive entry into memory.
+pub fn read_entry(archive_path: &Path, entry_name: &str) -> io::Result<Vec<u8>> {
+    with_archive(archive_path, |archive| {
+        let mut entry = archive.by_name(entry_name)?;
+        let mut bytes = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    })
+}
+
+/// Use an open archive, or open it if it isn't open or has changed since.
+fn with_archive<T>(
+    archive_path: &Path,
+    f: impl FnOnce(&mut ZipArchive<File>) -> io::Result<T>,
+) -> io::Result<T> {
+    let metadata = fs::metadata(archive_path)?;
+    let stamp = (metadata.modified().ok(), metadata.len());
+
+    let mut open_archives = OPEN_ARCHIVES.lock();
+    let cached = open_archives
+        .iter()
+        .position(|open| open.path == archive_path)
+        .map(|index| open_archives.remove(index))
+        .filter(|open| open.stamp == stamp);
+    let open = match cached {
+        Some(open) => open,
+        None => OpenArchive {
+            path: archive_path.to_owned(),
+            stamp,
+            archive: ZipArchive::new(File::open(archive_path)?)?,
+        },
+    };
+    if open_archives.len() >= MAX_OPEN_ARCHIVES {
+        open_archives.remove(0);
+    }
+    open_archives.push(open);
+    let last = open_archives.len() - 1;
+    f(&mut open_archives[last].archive)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    fn create_test_archive(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        writer
+            .start_file("folder/song.mid", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"not really a midi file").unwrap();
+        writer
+            .start_file("readme.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"hello").unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_list_and_read() {
+        let archive_path = PathBuf::from("temp/archive_test/pack.zip");
+        create_test_archive(&archive_path);
+
+        let songs = list_songs(&archive_path).unwrap();
+        assert_eq!(songs, vec![archive_path.join("folder/song.mid")]);
+
+        let (archive, entry) = split_path(&songs[0]).unwrap();
+        assert_eq!(archive, archive_path);
+        assert_eq!(entry, "folder/song.mid");
+        assert_eq!(
+            read_entry(&archive, &entry).unwrap(),
+            b"not really a midi file"
+        );
+    }
+
+    #[test]
+    fn test_changed_archive_is_reopened() {
+        let archive_path = PathBuf::from("temp/archive_test/changing.zip");
+        create_test_archive(&archive_path);
+        assert_eq!(read_entry(&archive_path, "readme.txt").unwrap(), b"hello");
+
+        let mut writer = ZipWriter::new(File::create(&archive_path).unwrap());
+        writer
+            .start_file("readme.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"hello again").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            read_entry(&archive_path, "readme.txt").unwrap(),
+            b"hello again"
+        );
+        assert!(read_entry(&archive_path, "folder/song.mid").is_err());
+    }
+
+    #[test]
+    fn test_hostile_names_are_skipped() {
+        let archive_path = PathBuf::from("temp/archive_test/hostile.zip");
+        fs::create_dir_all(archive_path.parent().unwrap()).unwrap();
+        let mut writer = ZipWriter::new(File::create(&archive_path).unwrap());
+        for name in ["../../escape.mid", "/etc/absolute.mid", "fine.mid"] {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(b"song").unwrap();
+        }
+        writer.finish().unwrap();
+
+        let songs = list_songs(&archive_path).unwrap();
+        assert_eq!(songs, vec![archive_path.join("fine.mid")]);
+    }
+
+    #[test]
+    fn test_split_path_not_archive() {
+        assert_eq!(split_path(Path::new("some/normal/song.mid")), None);
+    }
+}
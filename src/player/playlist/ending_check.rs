@@ -0,0 +1,133 @@
+//! Find songs that end abruptly: notes that are never released, or a track that ends right at
+//! the last release, cutting off its tail.
+
+use std::{collections::HashSet, fmt};
+
+use midi_msg::{ChannelVoiceMsg, Division, MidiFile, MidiMsg, TimeCodeType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndingIssue {
+    /// Notes still on at end of track
+    HangingNotes { count: usize },
+    /// The song ends right as the last note is released
+    NoTail,
+}
+impl fmt::Display for EndingIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HangingNotes { count: 1 } => write!(f, "1 note is never released"),
+            Self::HangingNotes { count } => write!(f, "{count} notes are never released"),
+            Self::NoTail => write!(f, "Song ends right at the last note, cutting off its tail"),
+        }
+    }
+}
+
+/// Shortest tail that's not considered cut off: an eighth note, or a quarter second.
+const fn min_tail_ticks(division: Division) -> u64 {
+    match division {
+        Division::TicksPerQuarterNote(ticks) => ticks as u64 / 2,
+        Division::TimeCode {
+            frames_per_second,
+            ticks_per_frame,
+        } => {
+            let fps = match frames_per_second {
+                TimeCodeType::FPS24 => 24,
+                TimeCodeType::FPS25 => 25,
+                TimeCodeType::DF30 | TimeCodeType::NDF30 => 30,
+            };
+            fps * ticks_per_frame as u64 / 4
+        }
+    }
+}
+
+/// Problem with how the song ends, if any
+#[must_use]
+pub fn check_ending(midifile: &MidiFile) -> Option<EndingIssue> {
+    let mut hanging = 0;
+    let mut notes = 0;
+    let mut last_release = 0;
+    let mut song_end = 0;
+    for track in &midifile.tracks {
+        // Notes don't carry over to other tracks
+        let mut held = HashSet::new();
+        let mut tick: u64 = 0;
+        for track_event in track.events() {
+            tick += u64::from(track_event.delta_time);
+            song_end = song_end.max(tick);
+            let (MidiMsg::ChannelVoice { channel, msg }
+            | MidiMsg::RunningChannelVoice { channel, msg }) = &track_event.event
+            else {
+                continue;
+            };
+            match *msg {
+                ChannelVoiceMsg::NoteOn { note, velocity } if velocity > 0 => {
+                    held.insert((*channel as u8, note));
+                    notes += 1;
+                }
+                ChannelVoiceMsg::NoteOn { note, .. } | ChannelVoiceMsg::NoteOff { note, .. } => {
+                    held.remove(&(*channel as u8, note));
+                    last_release = last_release.max(tick);
+                }
+                _ => (),
+            }
+        }
+        hanging += held.len();
+    }
+
+    if hanging > 0 {
+        return Some(EndingIssue::HangingNotes { count: hanging });
+    }
+    if notes > 0 && song_end - last_release < min_tail_ticks(midifile.header.division) {
+        return Some(EndingIssue::NoTail);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midi_msg::{Channel, Header, Meta, SMFFormat, Track, TrackEvent};
+
+    fn midifile(events: &[(u32, MidiMsg)]) -> MidiFile {
+        let events = events
+            .iter()
+            .map(|(delta_time, event)| TrackEvent {
+                delta_time: *delta_time,
+                event: event.clone(),
+                beat_or_frame: 0.,
+            })
+            .collect();
+        MidiFile {
+            header: Header {
+                format: SMFFormat::SingleTrack,
+                num_tracks: 1,
+                division: Division::TicksPerQuarterNote(96),
+            },
+            tracks: vec![Track::Midi(events)],
+        }
+    }
+    fn note(note: u8, velocity: u8) -> MidiMsg {
+        MidiMsg::ChannelVoice {
+            channel: Channel::Ch1,
+            msg: ChannelVoiceMsg::NoteOn { note, velocity },
+        }
+    }
+    const END: MidiMsg = MidiMsg::Meta {
+        msg: Meta::EndOfTrack,
+    };
+
+    #[test]
+    fn test_check_ending() {
+        let good = midifile(&[(0, note(60, 100)), (96, note(60, 0)), (96, END)]);
+        assert_eq!(check_ending(&good), None);
+
+        let no_tail = midifile(&[(0, note(60, 100)), (96, note(60, 0)), (0, END)]);
+        assert_eq!(check_ending(&no_tail), Some(EndingIssue::NoTail));
+
+        let hanging = midifile(&[(0, note(60, 100)), (0, note(64, 100)), (96, END)]);
+        assert_eq!(
+            check_ending(&hanging),
+            Some(EndingIssue::HangingNotes { count: 2 })
+        );
+    }
+}
@@ -1,7 +1,9 @@
 use std::fmt::Display;
 
+use serde::{Deserialize, Serialize};
+
 /// Option for how soundfonts or midis are managed
-#[derive(PartialEq, Eq, Default, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Default, Clone, Copy, Debug, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum FileListMode {
     /// The contents are added and removed manually.
@@ -12,6 +14,9 @@ pub enum FileListMode {
     /// The contents are fetched automatically from a directory and subdirectories.
     Subdirectories = 2,
 }
+impl FileListMode {
+    pub const ALL: [Self; 3] = [Self::Manual, Self::Directory, Self::Subdirectories];
+}
 impl Display for FileListMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -35,7 +40,7 @@ impl TryFrom<u8> for FileListMode {
 }
 
 /// Option for how songs are sorted
-#[derive(PartialEq, Eq, Default, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Default, Clone, Copy, Debug, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum SongSort {
     #[default]
@@ -46,6 +51,57 @@ pub enum SongSort {
     SizeAsc = 4,
     SizeDesc = 5,
 }
+impl SongSort {
+    pub const ALL: [Self; 6] = [
+        Self::NameAsc,
+        Self::NameDesc,
+        Self::TimeAsc,
+        Self::TimeDesc,
+        Self::SizeAsc,
+        Self::SizeDesc,
+    ];
+}
+impl Display for SongSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NameAsc => write!(f, "Name ascending"),
+            Self::NameDesc => write!(f, "Name descending"),
+            Self::TimeAsc => write!(f, "Time ascending"),
+            Self::TimeDesc => write!(f, "Time descending"),
+            Self::SizeAsc => write!(f, "Size ascending"),
+            Self::SizeDesc => write!(f, "Size descending"),
+        }
+    }
+}
+/// Text formats of an exported song list
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum SongListFormat {
+    Text,
+    Markdown,
+    Csv,
+}
+impl SongListFormat {
+    pub const ALL: [Self; 3] = [Self::Text, Self::Markdown, Self::Csv];
+
+    #[must_use]
+    pub const fn get_extension(self) -> &'static str {
+        match self {
+            Self::Text => "txt",
+            Self::Markdown => "md",
+            Self::Csv => "csv",
+        }
+    }
+}
+impl Display for SongListFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text => write!(f, "Plain text"),
+            Self::Markdown => write!(f, "Markdown table"),
+            Self::Csv => write!(f, "CSV"),
+        }
+    }
+}
+
 impl TryFrom<u8> for SongSort {
     type Error = ();
 
@@ -61,3 +117,32 @@ impl TryFrom<u8> for SongSort {
         }
     }
 }
+
+/// How a playlist is exported with its files
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum BundleFormat {
+    /// The playlist file, and a folder of the same name next to it
+    Folder,
+    /// The same, packed into one zip file
+    Zip,
+}
+impl BundleFormat {
+    pub const ALL: [Self; 2] = [Self::Folder, Self::Zip];
+
+    /// Extension of the file that's picked when exporting
+    #[must_use]
+    pub const fn get_extension(self) -> &'static str {
+        match self {
+            Self::Folder => "midpl",
+            Self::Zip => "zip",
+        }
+    }
+}
+impl Display for BundleFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Folder => write!(f, "Folder"),
+            Self::Zip => write!(f, "Zip file"),
+        }
+    }
+}
@@ -1,6 +1,6 @@
 //! Playlist errors
 
-use std::{error::Error, fmt, path::PathBuf};
+use std::{error::Error, fmt, path::PathBuf, time::Duration};
 
 use super::enums::FileListMode;
 
@@ -8,9 +8,11 @@ use super::enums::FileListMode;
 pub enum PlaylistError {
     InvalidFontIndex { index: usize },
     InvalidSongIndex { index: usize },
+    InvalidSongRange { start: Duration, end: Duration },
     ModifyAutoFontList { mode: FileListMode },
     ModifyAutoSongList { mode: FileListMode },
     UnknownFileFormat { path: PathBuf },
+    CantReadArchive { path: PathBuf, message: String },
 }
 
 impl Error for PlaylistError {}
@@ -24,6 +26,9 @@ impl fmt::Display for PlaylistError {
             Self::InvalidSongIndex { index } => {
                 write!(f, "Song index out of range: {index}")
             }
+            Self::InvalidSongRange { start, end } => {
+                write!(f, "Song range ends before it starts: {start:?}..{end:?}")
+            }
             Self::ModifyAutoFontList { mode } => {
                 write!(
                     f,
@@ -37,6 +42,9 @@ impl fmt::Display for PlaylistError {
                 )
             }
             Self::UnknownFileFormat { path } => write!(f, "Unknown file format: {path:?}"),
+            Self::CantReadArchive { path, message } => {
+                write!(f, "Can't read archive {}: {message}", path.display())
+            }
         }
     }
 }
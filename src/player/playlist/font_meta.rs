@@ -1,9 +1,17 @@
-use std::{error, fmt, fs, path::PathBuf};
+use std::{
+    borrow::Cow,
+    error, fmt, fs,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use anyhow::bail;
 use rustysynth::SoundFont;
 use serde::Serialize;
 
+pub mod sf3;
+
 #[derive(Debug, Clone, Serialize)]
 pub enum FontMetaError {
     CantAccessFile { filename: String, message: String },
@@ -23,73 +31,184 @@ impl fmt::Display for FontMetaError {
     }
 }
 
+/// Extensions of soundfont files. SF3 is SF2 with compressed samples.
+pub const FONT_EXTENSIONS: [&str; 2] = ["sf2", "sf3"];
+
+#[must_use]
+pub fn is_font_path(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        FONT_EXTENSIONS
+            .iter()
+            .any(|font_extension| extension.eq_ignore_ascii_case(font_extension))
+    })
+}
+
+/// Small soundfont embedded in the binary, so there's always something to play with.
+///
+/// Not a real GM font: one simple synthesized instrument per GM family, and a basic drum kit.
+/// Generated by `assets/builtin_sf2.py`, which makes it public domain. Rerun it after editing.
+pub const BUILTIN_SOUNDFONT: &[u8] = include_bytes!("../../assets/builtin.sf2");
+const BUILTIN_SOUNDFONT_NAME: &str = "Built-in basic tones";
+
+/// Presets of a soundfont, sorted by bank and program
+fn read_presets(bytes: &[u8]) -> Result<Vec<PresetInfo>, String> {
+    // Presets are all that's needed, so compressed samples aren't decoded
+    let soundfont = if sf3::is_compressed(bytes) {
+        sf3::to_sf2(bytes, false)
+            .map_err(|e| e.to_string())
+            .and_then(|sf2| SoundFont::new(&mut Cursor::new(sf2)).map_err(|e| e.to_string()))
+    } else {
+        SoundFont::new(&mut Cursor::new(bytes)).map_err(|e| e.to_string())
+    }?;
+    let mut presets: Vec<PresetInfo> = soundfont
+        .get_presets()
+        .iter()
+        .map(|preset| PresetInfo {
+            bank: preset.get_bank_number() as u16,
+            program: preset.get_patch_number() as u8,
+            name: preset.get_name().to_owned(),
+        })
+        .collect();
+    presets.sort_by_key(|preset| (preset.bank, preset.program));
+    Ok(presets)
+}
+
+/// Preset found in a soundfont
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresetInfo {
+    pub bank: u16,
+    pub program: u8,
+    pub name: String,
+}
+
 /// Reference to a font file with metadata
 #[derive(Debug, Default, Clone, Serialize)]
 pub struct FontMeta {
     filepath: PathBuf,
     filesize: Option<u64>,
     error: Option<FontMetaError>,
+    /// In-memory contents. If set, these are used instead of reading `filepath`.
+    #[serde(skip)]
+    data: Option<Arc<[u8]>>,
+    /// Found on last refresh, sorted by bank and program
+    #[serde(skip)]
+    presets: Vec<PresetInfo>,
     pub is_queued_for_deletion: bool,
 }
 
 impl FontMeta {
     /// Create from file path
+    #[must_use]
     pub fn new(filepath: PathBuf) -> Self {
         let mut this = Self {
             filepath,
             filesize: None,
             error: None,
+            data: None,
+            presets: vec![],
+            is_queued_for_deletion: false,
+        };
+        this.refresh();
+        this
+    }
+
+    /// Create from in-memory data. The path is only used as a name.
+    pub fn from_bytes<D: Into<Arc<[u8]>>>(filepath: PathBuf, data: D) -> Self {
+        let mut this = Self {
+            filepath,
+            filesize: None,
+            error: None,
+            data: Some(data.into()),
+            presets: vec![],
             is_queued_for_deletion: false,
         };
         this.refresh();
         this
     }
 
+    /// The built-in fallback soundfont
+    #[must_use]
+    pub fn builtin() -> Self {
+        Self::from_bytes(BUILTIN_SOUNDFONT_NAME.into(), BUILTIN_SOUNDFONT)
+    }
+
     /// Refresh file metadata
     pub fn refresh(&mut self) {
-        self.filesize =
-            fs::metadata(&self.filepath).map_or(None, |file_meta| Some(file_meta.len()));
-
-        let error;
-        match fs::File::open(&self.filepath) {
-            Ok(mut file) => match SoundFont::new(&mut file) {
-                Ok(_) => error = None,
-                Err(e) => {
-                    error = Some(FontMetaError::InvalidFile {
-                        filename: self.get_name(),
-                        message: e.to_string(),
-                    });
-                }
-            },
+        let read = self
+            .read_bytes()
+            .map(|bytes| (bytes.len() as u64, read_presets(&bytes)));
+        let presets = match read {
+            Ok((filesize, presets)) => {
+                self.filesize = Some(filesize);
+                presets
+            }
             Err(e) => {
-                error = Some(FontMetaError::CantAccessFile {
+                self.filesize = None;
+                self.presets.clear();
+                self.error = Some(FontMetaError::CantAccessFile {
                     filename: self.get_name(),
                     message: e.to_string(),
                 });
+                return;
+            }
+        };
+        match presets {
+            Ok(presets) => {
+                self.error = None;
+                self.presets = presets;
+            }
+            Err(e) => {
+                self.presets.clear();
+                self.error = Some(FontMetaError::InvalidFile {
+                    filename: self.get_name(),
+                    message: e,
+                });
             }
         }
-        self.error = error;
+    }
+
+    /// Read file contents from disk, or borrow them if they're in memory
+    ///
+    /// # Errors
+    ///
+    /// Passes on the IO error if the file can't be read.
+    pub fn read_bytes(&self) -> io::Result<Cow<'_, [u8]>> {
+        self.data.as_deref().map_or_else(
+            || fs::read(&self.filepath).map(Cow::Owned),
+            |data| Ok(Cow::Borrowed(data)),
+        )
     }
 
     // --- Getters
 
+    #[must_use]
     pub fn get_path(&self) -> PathBuf {
         self.filepath.clone()
     }
     pub fn set_path(&mut self, filepath: PathBuf) {
         self.filepath = filepath;
     }
+    /// File name, or the whole path if it has none
+    #[must_use]
     pub fn get_name(&self) -> String {
-        self.filepath
-            .file_name()
-            .expect("No filename")
-            .to_str()
-            .expect("Invalid filename")
-            .to_owned()
+        self.filepath.file_name().map_or_else(
+            || self.filepath.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        )
     }
+    #[must_use]
     pub const fn get_size(&self) -> Option<u64> {
         self.filesize
     }
+    #[must_use]
+    pub fn get_presets(&self) -> &[PresetInfo] {
+        &self.presets
+    }
+    /// Whether the font loaded fine last time it was read
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from the last refresh, if there was one.
     pub fn get_status(&self) -> anyhow::Result<()> {
         if let Some(e) = &self.error {
             bail!(e.clone())
@@ -111,6 +230,8 @@ impl TryFrom<&serde_json::Value> for FontMeta {
             filepath: path_str.into(),
             filesize,
             error: None,
+            data: None,
+            presets: vec![],
             is_queued_for_deletion: false,
         })
     }
@@ -161,4 +282,59 @@ mod tests {
         assert_eq!(new_playlist.fonts[0].get_size(), None);
         assert_eq!(new_playlist.fonts[1].get_size().unwrap(), 420);
     }
+
+    #[test]
+    fn test_builtin_is_valid() {
+        let font = FontMeta::builtin();
+        assert!(font.get_status().is_ok());
+        assert_eq!(font.get_size(), Some(BUILTIN_SOUNDFONT.len() as u64));
+    }
+
+    #[test]
+    fn test_from_bytes_invalid() {
+        let font = FontMeta::from_bytes("garbage.sf2".into(), vec![1, 2, 3]);
+        assert_eq!(font.get_size(), Some(3));
+        assert!(font.get_status().is_err());
+        assert_eq!(font.read_bytes().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_builtin_presets() {
+        let font = FontMeta::builtin();
+        let presets: Vec<(u16, u8)> = font
+            .get_presets()
+            .iter()
+            .map(|preset| (preset.bank, preset.program))
+            .collect();
+        let mut expected: Vec<(u16, u8)> = (0..128).map(|program| (0, program)).collect();
+        expected.push((128, 0));
+        assert_eq!(presets, expected);
+    }
+
+    #[test]
+    fn test_builtin_families_differ() {
+        let soundfont = Arc::new(SoundFont::new(&mut Cursor::new(BUILTIN_SOUNDFONT)).unwrap());
+        let render = |program: u8| {
+            let settings = rustysynth::SynthesizerSettings::new(44100);
+            let mut synth = rustysynth::Synthesizer::new(&soundfont, &settings).unwrap();
+            synth.process_midi_message(0, 0xc0, i32::from(program), 0);
+            synth.note_on(0, 60, 100);
+            let mut left = vec![0.; 4410];
+            let mut right = vec![0.; 4410];
+            synth.render(&mut left, &mut right);
+            left
+        };
+        // Programs of a family share an instrument, families don't
+        assert_eq!(render(0), render(7));
+        let families: Vec<Vec<f32>> = (0..16).map(|family| render(family * 8)).collect();
+        for (i, a) in families.iter().enumerate() {
+            assert!(
+                a.iter().any(|sample| sample.abs() > 0.01),
+                "family {i} is silent"
+            );
+            for b in &families[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
 }
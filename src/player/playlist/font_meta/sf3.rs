@@ -0,0 +1,278 @@
+//! SF3: soundfonts with Ogg Vorbis compressed samples, as written by the sftools of `MuseScore`.
+//!
+//! The synth only reads SF2, so the samples are decoded and the file is rebuilt as SF2 in
+//! memory. Headers of compressed samples have byte offsets into the Ogg data, and loop points
+//! relative to the sample start.
+
+use std::{error, fmt, io::Cursor};
+
+use lewton::inside_ogg::OggStreamReader;
+
+/// Size of a sample header record
+const SHDR_LEN: usize = 46;
+/// Sample type flag of Ogg Vorbis samples
+const COMPRESSED: u16 = 0x10;
+/// Zero samples after each sample, as SF2 requires
+const SAMPLE_PADDING: usize = 46;
+
+#[derive(Debug, Clone)]
+pub enum Sf3Error {
+    /// A chunk is missing or cut short
+    Malformed(&'static str),
+    Vorbis {
+        sample: String,
+        message: String,
+    },
+    TooLarge,
+}
+impl error::Error for Sf3Error {}
+impl fmt::Display for Sf3Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(what) => write!(f, "malformed SF3: {what}"),
+            Self::Vorbis { sample, message } => {
+                write!(f, "can't decode sample {sample}: {message}")
+            }
+            Self::TooLarge => write!(f, "decoded samples don't fit in an SF2"),
+        }
+    }
+}
+
+struct Chunk<'a> {
+    id: &'a [u8],
+    data: &'a [u8],
+}
+
+/// RIFF chunks one after another
+fn read_chunks(mut data: &[u8]) -> Result<Vec<Chunk<'_>>, Sf3Error> {
+    let mut chunks = vec![];
+    while data.len() >= 8 {
+        let size = read_u32(data, 4) as usize;
+        let Some(body) = data.get(8..8 + size) else {
+            return Err(Sf3Error::Malformed("truncated chunk"));
+        };
+        chunks.push(Chunk {
+            id: &data[..4],
+            data: body,
+        });
+        // Odd sizes are padded
+        data = data.get(8 + size + size % 2..).unwrap_or_default();
+    }
+    Ok(chunks)
+}
+
+/// Subchunks of the LIST chunk of some type
+fn read_list<'a>(chunks: &[Chunk<'a>], list_type: &[u8]) -> Result<Vec<Chunk<'a>>, Sf3Error> {
+    let list = chunks
+        .iter()
+        .find(|chunk| chunk.id == b"LIST" && chunk.data.starts_with(list_type))
+        .ok_or(Sf3Error::Malformed("missing list"))?;
+    read_chunks(&list.data[4..])
+}
+
+fn find<'a>(chunks: &[Chunk<'a>], id: &[u8]) -> Option<&'a [u8]> {
+    chunks
+        .iter()
+        .find(|chunk| chunk.id == id)
+        .map(|chunk| chunk.data)
+}
+
+/// Top level chunks of a soundfont
+fn read_soundfont(bytes: &[u8]) -> Result<Vec<Chunk<'_>>, Sf3Error> {
+    if bytes.len() < 12 || &bytes[..4] != b"RIFF" || &bytes[8..12] != b"sfbk" {
+        return Err(Sf3Error::Malformed("not a soundfont"));
+    }
+    read_chunks(&bytes[12..])
+}
+
+fn read_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+fn write_u32(data: &mut [u8], at: usize, value: u32) {
+    data[at..at + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn push_chunk(out: &mut Vec<u8>, id: &[u8], data: &[u8]) -> Result<(), Sf3Error> {
+    let size = u32::try_from(data.len()).map_err(|_| Sf3Error::TooLarge)?;
+    out.extend_from_slice(id);
+    out.extend_from_slice(&size.to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+    Ok(())
+}
+
+/// The sample data is Ogg instead of PCM
+#[must_use]
+pub fn is_compressed(bytes: &[u8]) -> bool {
+    read_soundfont(bytes)
+        .and_then(|chunks| read_list(&chunks, b"sdta"))
+        .is_ok_and(|sdta| find(&sdta, b"smpl").is_some_and(|smpl| smpl.starts_with(b"OggS")))
+}
+
+fn decode_vorbis(ogg: &[u8]) -> Result<Vec<i16>, String> {
+    let mut reader = OggStreamReader::new(Cursor::new(ogg)).map_err(|e| e.to_string())?;
+    let mut pcm = vec![];
+    loop {
+        match reader.read_dec_packet_itl() {
+            Ok(Some(packet)) => pcm.extend(packet),
+            Ok(None) => return Ok(pcm),
+            // The end offset may include the first byte of the next sample
+            Err(_) if !pcm.is_empty() => return Ok(pcm),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
+
+/// Rebuild as SF2. Without `decode`, the samples are left out, which is enough for reading
+/// presets and much faster.
+///
+/// # Errors
+///
+/// Fails if a chunk is missing or cut short, a sample doesn't decode, or the decoded font would be
+/// too large for sf2.
+pub fn to_sf2(bytes: &[u8], decode: bool) -> Result<Vec<u8>, Sf3Error> {
+    let chunks = read_soundfont(bytes)?;
+    let info = chunks
+        .iter()
+        .find(|chunk| chunk.id == b"LIST" && chunk.data.starts_with(b"INFO"))
+        .ok_or(Sf3Error::Malformed("missing INFO"))?;
+    let smpl =
+        find(&read_list(&chunks, b"sdta")?, b"smpl").ok_or(Sf3Error::Malformed("missing smpl"))?;
+    let pdta = read_list(&chunks, b"pdta")?;
+    let shdr = find(&pdta, b"shdr").ok_or(Sf3Error::Malformed("missing shdr"))?;
+
+    let mut headers = shdr.to_vec();
+    let mut wave: Vec<i16> = vec![];
+    if decode {
+        let count = headers.len() / SHDR_LEN;
+        // The last one is the terminator
+        for record in headers
+            .chunks_exact_mut(SHDR_LEN)
+            .take(count.saturating_sub(1))
+        {
+            decode_sample(record, smpl, &mut wave)?;
+        }
+    }
+    wave.resize(wave.len() + SAMPLE_PADDING, 0);
+
+    let mut sdta = b"sdta".to_vec();
+    let wave_bytes: Vec<u8> = wave
+        .iter()
+        .flat_map(|sample| sample.to_le_bytes())
+        .collect();
+    push_chunk(&mut sdta, b"smpl", &wave_bytes)?;
+    let mut pdta_list = b"pdta".to_vec();
+    for chunk in &pdta {
+        let data = if chunk.id == b"shdr" {
+            &headers
+        } else {
+            chunk.data
+        };
+        push_chunk(&mut pdta_list, chunk.id, data)?;
+    }
+
+    let mut body = b"sfbk".to_vec();
+    push_chunk(&mut body, b"LIST", info.data)?;
+    push_chunk(&mut body, b"LIST", &sdta)?;
+    push_chunk(&mut body, b"LIST", &pdta_list)?;
+    let mut out = vec![];
+    push_chunk(&mut out, b"RIFF", &body)?;
+    Ok(out)
+}
+
+/// Append one sample to the new sample data, and point its header there
+fn decode_sample(record: &mut [u8], smpl: &[u8], wave: &mut Vec<i16>) -> Result<(), Sf3Error> {
+    let sample_type = read_u16(record, 44);
+    let start = read_u32(record, 20) as usize;
+    let end = read_u32(record, 24) as usize;
+    let (loop_start, loop_end) = (read_u32(record, 28), read_u32(record, 32));
+
+    let (pcm, loop_start, loop_end) = if sample_type & COMPRESSED == 0 {
+        // Uncompressed sample among compressed ones: offsets are in samples
+        let pcm = smpl
+            .get(start * 2..end * 2)
+            .unwrap_or_default()
+            .chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        let start = u32::try_from(start).unwrap_or_default();
+        (
+            pcm,
+            loop_start.saturating_sub(start),
+            loop_end.saturating_sub(start),
+        )
+    } else {
+        let ogg = smpl.get(start..=end.min(smpl.len().saturating_sub(1)));
+        let pcm = decode_vorbis(ogg.unwrap_or_default()).map_err(|message| Sf3Error::Vorbis {
+            sample: String::from_utf8_lossy(&record[..20])
+                .trim_end_matches('\0')
+                .to_owned(),
+            message,
+        })?;
+        (pcm, loop_start, loop_end)
+    };
+
+    let new_start = u32::try_from(wave.len()).map_err(|_| Sf3Error::TooLarge)?;
+    let len = u32::try_from(pcm.len()).map_err(|_| Sf3Error::TooLarge)?;
+    write_u32(record, 20, new_start);
+    write_u32(record, 24, new_start + len);
+    write_u32(record, 28, new_start + loop_start.min(len));
+    write_u32(record, 32, new_start + loop_end.min(len));
+    record[44..46].copy_from_slice(&(sample_type & !COMPRESSED).to_le_bytes());
+
+    wave.extend(pcm);
+    wave.resize(wave.len() + SAMPLE_PADDING, 0);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::playlist::font_meta::BUILTIN_SOUNDFONT;
+    use rustysynth::SoundFont;
+
+    fn preset_names(bytes: &[u8]) -> Vec<String> {
+        let soundfont = SoundFont::new(&mut Cursor::new(bytes)).expect("valid soundfont");
+        soundfont
+            .get_presets()
+            .iter()
+            .map(|preset| preset.get_name().to_owned())
+            .collect()
+    }
+
+    /// Builtin font with its sample data swapped for something that starts like Ogg
+    fn fake_sf3() -> Vec<u8> {
+        let sf2 = to_sf2(BUILTIN_SOUNDFONT, false).expect("rebuild");
+        let at = sf2
+            .windows(4)
+            .position(|w| w == b"smpl")
+            .expect("smpl chunk");
+        let mut sf3 = sf2;
+        sf3[at + 8..at + 12].copy_from_slice(b"OggS");
+        sf3
+    }
+
+    #[test]
+    fn test_is_compressed() {
+        assert!(!is_compressed(BUILTIN_SOUNDFONT));
+        assert!(!is_compressed(b"garbage"));
+        assert!(is_compressed(&fake_sf3()));
+    }
+
+    #[test]
+    fn test_rebuild_uncompressed() {
+        let rebuilt = to_sf2(BUILTIN_SOUNDFONT, true).expect("rebuild");
+        assert_eq!(preset_names(&rebuilt), preset_names(BUILTIN_SOUNDFONT));
+    }
+
+    #[test]
+    fn test_presets_without_decoding() {
+        let rebuilt = to_sf2(&fake_sf3(), false).expect("rebuild");
+        assert_eq!(preset_names(&rebuilt), preset_names(BUILTIN_SOUNDFONT));
+    }
+}
@@ -0,0 +1,177 @@
+//! Playlists of other players
+//!
+//! Only the file list is imported. Relative paths are relative to the playlist file, and
+//! Windows separators work on other systems too.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::bail;
+
+use super::{
+    archive,
+    font_meta::{is_font_path, FontMeta},
+    paste, Playlist,
+};
+
+/// Playlist file formats that can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// `.pls`: INI-style `FileN=path` entries, as written by foobar2000, Winamp and others
+    Pls,
+    /// One path per line: `.vpl` from Vanbasco, `.m3u`, or a plain directory listing. `#`
+    /// lines are comments.
+    Listing,
+}
+impl ImportFormat {
+    pub const EXTENSIONS: [&str; 6] = ["pls", "vpl", "m3u", "m3u8", "txt", "lst"];
+
+    /// Detect the format by file extension
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "pls" => Some(Self::Pls),
+            "vpl" | "m3u" | "m3u8" | "txt" | "lst" => Some(Self::Listing),
+            _ => None,
+        }
+    }
+
+    /// Paths listed in the playlist, in order
+    fn parse(self, text: &str) -> Vec<PathBuf> {
+        match self {
+            Self::Pls => parse_pls(text),
+            Self::Listing => paste::parse_paths(text),
+        }
+    }
+}
+
+fn parse_pls(text: &str) -> Vec<PathBuf> {
+    let mut entries: Vec<(u32, PathBuf)> = text
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            let key = key.trim().to_lowercase();
+            let number = key.strip_prefix("file")?.parse().ok()?;
+            Some((number, paste::parse_line(value)?))
+        })
+        .collect();
+    // Entries are numbered, but not necessarily written in order
+    entries.sort_by_key(|(number, _)| *number);
+    entries.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Absolute path of a playlist entry
+fn resolve(dir: &Path, path: &Path) -> PathBuf {
+    #[cfg(not(target_os = "windows"))]
+    let path = &PathBuf::from(path.to_string_lossy().replace('\\', "/"));
+    if path.is_absolute() {
+        path.clone()
+    } else {
+        dir.join(path)
+    }
+}
+
+impl Playlist {
+    /// New playlist from another player's playlist file. Soundfonts in it are added as fonts, the
+    /// rest as songs, even if they're missing.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the extension isn't a known playlist format, or the file can't be read.
+    pub fn import(filepath: &Path) -> anyhow::Result<Self> {
+        let Some(format) = ImportFormat::from_path(filepath) else {
+            bail!("Unknown playlist format: {}", filepath.display())
+        };
+        // Older players write in the system code page, so don't insist on UTF-8
+        let text = String::from_utf8_lossy(&fs::read(filepath)?).into_owned();
+        let dir = filepath.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut playlist = Self::default();
+        if let Some(stem) = filepath.file_stem() {
+            playlist.name = stem.to_string_lossy().into_owned();
+        }
+        for path in format.parse(&text) {
+            let path = resolve(dir, &path);
+            if is_font_path(&path) {
+                if !playlist.contains_font(&path) {
+                    playlist.fonts.push(FontMeta::new(path));
+                }
+            } else if archive::is_archive(&path) {
+                for song in archive::list_songs(&path).unwrap_or_default() {
+                    playlist.force_add_song(song);
+                }
+            } else {
+                playlist.force_add_song(path);
+            }
+        }
+        playlist.refresh_font_list();
+        playlist.refresh_song_list();
+        Ok(playlist)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::playlist::midi_meta::MidiMeta;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            ImportFormat::from_path(Path::new("a.PLS")),
+            Some(ImportFormat::Pls)
+        );
+        assert_eq!(
+            ImportFormat::from_path(Path::new("a.vpl")),
+            Some(ImportFormat::Listing)
+        );
+        assert_eq!(ImportFormat::from_path(Path::new("a.midpl")), None);
+        assert_eq!(ImportFormat::from_path(Path::new("pls")), None);
+    }
+
+    #[test]
+    fn test_parse_pls() {
+        let text = "\
+[playlist]
+File2=b.mid
+Title2=B
+file1=/music/a.mid
+File3=http://example.com/c.mid
+NumberOfEntries=3
+Version=2";
+        assert_eq!(
+            ImportFormat::Pls.parse(text),
+            vec![PathBuf::from("/music/a.mid"), PathBuf::from("b.mid")]
+        );
+    }
+
+    #[test]
+    fn test_import() {
+        fs::create_dir_all("temp").unwrap();
+        let filepath = Path::new("temp/import_test.vpl");
+        fs::write(filepath, "songs\\a.mid\r\n/music/b.mid\r\nfont.sf2\r\n").unwrap();
+        let playlist = Playlist::import(filepath).unwrap();
+        fs::remove_file(filepath).unwrap();
+
+        assert_eq!(playlist.name, "import_test");
+        let songs: Vec<PathBuf> = playlist
+            .get_songs()
+            .iter()
+            .map(MidiMeta::get_path)
+            .collect();
+        assert_eq!(
+            songs,
+            vec![
+                PathBuf::from("temp/songs/a.mid"),
+                PathBuf::from("/music/b.mid")
+            ]
+        );
+        assert_eq!(
+            playlist.get_fonts()[0].get_path(),
+            Path::new("temp/font.sf2")
+        );
+    }
+}
@@ -1,8 +1,24 @@
-use std::{error, fmt, fs, path::PathBuf, time::Duration};
+use std::{
+    any::Any,
+    borrow::Cow,
+    cell::Cell,
+    error, fmt, fs,
+    io::{self, Cursor},
+    panic::{self, UnwindSafe},
+    path::PathBuf,
+    sync::{Arc, Once},
+    time::Duration,
+};
 
 use anyhow::bail;
+
+use super::{
+    archive,
+    ending_check::{self, EndingIssue},
+};
+use midi_msg::{Meta, MidiMsg};
 use rustysynth::MidiFile;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize)]
 pub enum MidiMetaError {
@@ -29,18 +45,58 @@ pub struct MidiMeta {
     filepath: PathBuf,
     filesize: Option<u64>,
     duration: Option<Duration>,
+    /// Soundfont assigned to this song specifically
+    soundfont: Option<PathBuf>,
+    /// Playback begins here instead of the beginning
+    start: Option<Duration>,
+    /// Playback ends here instead of the end
+    end: Option<Duration>,
+    /// A-B loop
+    loop_points: Option<(Duration, Duration)>,
     error: Option<MidiMetaError>,
+    /// Found by `check_ending`
+    #[serde(skip)]
+    ending_issue: Option<EndingIssue>,
+    /// In-memory contents. If set, these are used instead of reading `filepath`.
+    #[serde(skip)]
+    data: Option<Arc<[u8]>>,
     pub is_queued_for_deletion: bool,
 }
 
 impl MidiMeta {
     /// Create from file path
+    #[must_use]
     pub fn new(filepath: PathBuf) -> Self {
         let mut this = Self {
             filepath,
             filesize: None,
             duration: None,
+            soundfont: None,
+            start: None,
+            end: None,
+            loop_points: None,
             error: None,
+            ending_issue: None,
+            data: None,
+            is_queued_for_deletion: false,
+        };
+        this.refresh();
+        this
+    }
+
+    /// Create from in-memory data. The path is only used as a name.
+    pub fn from_bytes<D: Into<Arc<[u8]>>>(filepath: PathBuf, data: D) -> Self {
+        let mut this = Self {
+            filepath,
+            filesize: None,
+            duration: None,
+            soundfont: None,
+            start: None,
+            end: None,
+            loop_points: None,
+            error: None,
+            ending_issue: None,
+            data: Some(data.into()),
             is_queued_for_deletion: false,
         };
         this.refresh();
@@ -49,58 +105,143 @@ impl MidiMeta {
 
     /// Refresh file metadata
     pub fn refresh(&mut self) {
-        let error;
-        let mut duration = None;
-
-        self.filesize =
-            fs::metadata(&self.filepath).map_or(None, |file_meta| Some(file_meta.len()));
-
-        match fs::File::open(&self.filepath) {
-            Ok(mut file) => match MidiFile::new(&mut file) {
-                Ok(midifile) => {
-                    duration = Some(Duration::from_secs_f64(midifile.get_length()));
-                    error = None;
-                }
-                Err(e) => {
-                    error = Some(MidiMetaError::InvalidFile {
-                        filename: self.get_name(),
-                        message: e.to_string(),
-                    });
-                }
-            },
+        self.duration = None;
+        let read = self
+            .read_bytes()
+            .map(|bytes| (bytes.len() as u64, read_length(&bytes)));
+        let length = match read {
+            Ok((filesize, length)) => {
+                self.filesize = Some(filesize);
+                length
+            }
             Err(e) => {
-                error = Some(MidiMetaError::CantAccessFile {
+                self.filesize = None;
+                self.error = Some(MidiMetaError::CantAccessFile {
                     filename: self.get_name(),
                     message: e.to_string(),
                 });
+                return;
+            }
+        };
+
+        match length {
+            Ok(duration) => {
+                self.duration = Some(duration);
+                self.error = None;
+            }
+            Err(message) => {
+                self.error = Some(MidiMetaError::InvalidFile {
+                    filename: self.get_name(),
+                    message,
+                });
             }
         }
-        self.duration = duration;
-        self.error = error;
+    }
+
+    /// Look for an abrupt ending. Unreadable files are left for `refresh` to report.
+    pub fn check_ending(&mut self) {
+        self.ending_issue = self
+            .read_bytes()
+            .ok()
+            .and_then(|bytes| parse_midi(&bytes).ok())
+            .and_then(|midifile| ending_check::check_ending(&midifile));
+    }
+    #[must_use]
+    pub const fn get_ending_issue(&self) -> Option<EndingIssue> {
+        self.ending_issue
+    }
+
+    /// Title embedded in the file: by convention, the name of the first track
+    #[must_use]
+    pub fn read_title(&self) -> Option<String> {
+        let bytes = self.read_bytes().ok()?;
+        let midifile = parse_midi(&bytes).ok()?;
+        let title = midifile
+            .tracks
+            .first()?
+            .events()
+            .iter()
+            .find_map(|track_event| match &track_event.event {
+                MidiMsg::Meta {
+                    msg: Meta::TrackName(name),
+                } => Some(name.trim().to_owned()),
+                _ => None,
+            })?;
+        Some(title).filter(|title| !title.is_empty())
+    }
+
+    /// Read file contents from disk or from inside an archive, or borrow them if they're in memory
+    ///
+    /// # Errors
+    ///
+    /// Passes on the IO error if the file or the archive entry can't be read.
+    pub fn read_bytes(&self) -> io::Result<Cow<'_, [u8]>> {
+        if let Some(data) = &self.data {
+            return Ok(Cow::Borrowed(data));
+        }
+        if let Some((archive_path, entry_name)) = archive::split_path(&self.filepath) {
+            return archive::read_entry(&archive_path, &entry_name).map(Cow::Owned);
+        }
+        fs::read(&self.filepath).map(Cow::Owned)
     }
 
     // --- Getters
 
+    #[must_use]
     pub fn get_path(&self) -> PathBuf {
         self.filepath.clone()
     }
     pub fn set_path(&mut self, filepath: PathBuf) {
         self.filepath = filepath;
     }
+    /// File name, or the whole path if it has none
+    #[must_use]
     pub fn get_name(&self) -> String {
-        self.filepath
-            .file_name()
-            .expect("No filename")
-            .to_str()
-            .expect("Invalid filename")
-            .to_owned()
+        self.filepath.file_name().map_or_else(
+            || self.filepath.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        )
     }
+    #[must_use]
     pub const fn get_duration(&self) -> Option<Duration> {
         self.duration
     }
+    #[must_use]
     pub const fn get_size(&self) -> Option<u64> {
         self.filesize
     }
+    #[must_use]
+    pub const fn get_soundfont(&self) -> Option<&PathBuf> {
+        self.soundfont.as_ref()
+    }
+    pub fn set_soundfont(&mut self, soundfont: Option<PathBuf>) {
+        self.soundfont = soundfont;
+    }
+    #[must_use]
+    pub const fn get_start(&self) -> Option<Duration> {
+        self.start
+    }
+    #[must_use]
+    pub const fn get_end(&self) -> Option<Duration> {
+        self.end
+    }
+    #[must_use]
+    pub const fn get_loop_points(&self) -> Option<(Duration, Duration)> {
+        self.loop_points
+    }
+    pub const fn set_loop_points(&mut self, loop_points: Option<(Duration, Duration)>) {
+        self.loop_points = loop_points;
+    }
+    /// Part of the song to play. None plays from the beginning or to the end.
+    pub const fn set_range(&mut self, start: Option<Duration>, end: Option<Duration>) {
+        self.start = start;
+        self.end = end;
+    }
+    /// Whether the song parsed fine last time it was read
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from the last refresh, if there was one.
     pub fn get_status(&self) -> anyhow::Result<()> {
         if let Some(e) = &self.error {
             bail!(e.clone())
@@ -109,6 +250,59 @@ impl MidiMeta {
     }
 }
 
+thread_local! {
+    /// Set while a parser runs in `catch_parser_panic`
+    static IN_PARSER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run a parser that panics on some malformed files, and return the panic as an error. The
+/// panic isn't printed, but panics elsewhere still go to the hook that was set before.
+fn catch_parser_panic<T>(parse: impl FnOnce() -> T + UnwindSafe) -> Result<T, String> {
+    static QUIET_HOOK: Once = Once::new();
+    QUIET_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !IN_PARSER.get() {
+                previous(info);
+            }
+        }));
+    });
+    IN_PARSER.set(true);
+    let result = panic::catch_unwind(parse);
+    IN_PARSER.set(false);
+    result.map_err(|payload| format!("Malformed file: {}", panic_message(payload.as_ref())))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("parser panicked")
+}
+
+/// Parse a midi file. The parser panics on some malformed files, which is returned as an error
+/// instead.
+///
+/// # Errors
+///
+/// Fails on malformed files, including ones that would make the parser panic.
+pub fn parse_midi(bytes: &[u8]) -> anyhow::Result<midi_msg::MidiFile> {
+    match catch_parser_panic(|| midi_msg::MidiFile::from_midi(bytes)) {
+        Ok(result) => Ok(result?),
+        Err(e) => bail!(e),
+    }
+}
+
+/// Song length. `rustysynth` panics on some malformed files, like `midi_msg`.
+fn read_length(bytes: &[u8]) -> Result<Duration, String> {
+    let length = catch_parser_panic(|| {
+        MidiFile::new(&mut Cursor::new(bytes)).map(|midifile| midifile.get_length())
+    })?
+    .map_err(|e| e.to_string())?;
+    Duration::try_from_secs_f64(length).map_err(|e| e.to_string())
+}
+
 impl TryFrom<&serde_json::Value> for MidiMeta {
     type Error = anyhow::Error;
 
@@ -117,13 +311,28 @@ impl TryFrom<&serde_json::Value> for MidiMeta {
             bail!("No filepath.")
         };
         let filesize = json["filesize"].as_u64();
-        let duration = json["duration"]["secs"].as_u64().map(Duration::from_secs);
+        // Nanoseconds are optional
+        let duration = json["duration"]["secs"].as_u64().and_then(|secs| {
+            let nanos = json["duration"]["nanos"].as_u64().unwrap_or_default();
+            Duration::from_secs(secs).checked_add(Duration::from_nanos(nanos))
+        });
+        let soundfont = json["soundfont"].as_str().map(Into::into);
+        let start = Option::<Duration>::deserialize(&json["start"]).unwrap_or_default();
+        let end = Option::<Duration>::deserialize(&json["end"]).unwrap_or_default();
+        let loop_points =
+            Option::<(Duration, Duration)>::deserialize(&json["loop_points"]).unwrap_or_default();
 
         Ok(Self {
             filepath: path_str.into(),
             filesize,
             duration,
+            soundfont,
+            start,
+            end,
+            loop_points,
             error: None,
+            ending_issue: None,
+            data: None,
             is_queued_for_deletion: false,
         })
     }
@@ -133,7 +342,8 @@ impl TryFrom<&serde_json::Value> for MidiMeta {
 mod tests {
 
     use super::*;
-    use crate::player::playlist::Playlist;
+    use crate::{player::playlist::Playlist, test_support::random_song};
+    use proptest::{collection, prelude::*, sample::Index};
     use serde_json::Value;
 
     fn run_serialize(playlist: Playlist) -> Playlist {
@@ -197,4 +407,141 @@ mod tests {
             Duration::from_secs(420)
         );
     }
+
+    #[test]
+    fn test_serialize_soundfont() {
+        let mut playlist = Playlist::default();
+        let song_none = MidiMeta {
+            filepath: "unused".into(),
+            soundfont: None,
+            ..Default::default()
+        };
+        let song_font = MidiMeta {
+            filepath: "unused".into(),
+            soundfont: Some("Fakefont".into()),
+            ..Default::default()
+        };
+        playlist.midis.push(song_none);
+        playlist.midis.push(song_font);
+        let new_playlist = run_serialize(playlist);
+        assert_eq!(new_playlist.midis[0].get_soundfont(), None);
+        assert_eq!(
+            new_playlist.midis[1]
+                .get_soundfont()
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            "Fakefont"
+        );
+    }
+
+    #[test]
+    fn test_serialize_range() {
+        let mut playlist = Playlist::default();
+        let mut song = MidiMeta {
+            filepath: "unused".into(),
+            ..Default::default()
+        };
+        song.set_range(Some(Duration::from_millis(1500)), None);
+        playlist.midis.push(song);
+        let new_playlist = run_serialize(playlist);
+        assert_eq!(
+            new_playlist.midis[0].get_start(),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(new_playlist.midis[0].get_end(), None);
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let song = MidiMeta::from_bytes("garbage.mid".into(), vec![1, 2, 3]);
+        assert_eq!(song.get_size(), Some(3));
+        assert_eq!(song.get_duration(), None);
+        assert!(song.get_status().is_err());
+        assert_eq!(song.read_bytes().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_read_title() {
+        let midi_with_track = |track: &[u8]| {
+            let mut bytes = b"MThd".to_vec();
+            bytes.extend_from_slice(&[0, 0, 0, 6, 0, 0, 0, 1, 0, 96]);
+            bytes.extend_from_slice(b"MTrk");
+            bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(track);
+            MidiMeta::from_bytes("a.mid".into(), bytes)
+        };
+        let named = midi_with_track(&[
+            0x00, 0xff, 0x03, 6, b' ', b'T', b'i', b't', b'l', b'e', 0x00, 0xff, 0x2f, 0x00,
+        ]);
+        assert_eq!(named.read_title(), Some("Title".into()));
+        let unnamed = midi_with_track(&[0x00, 0xff, 0x2f, 0x00]);
+        assert_eq!(unnamed.read_title(), None);
+    }
+
+    proptest! {
+        #[test]
+        fn test_random_durations((midifile, length) in random_song()) {
+            let song = MidiMeta::from_bytes("random.mid".into(), midifile.to_midi());
+            let duration = song.get_duration().unwrap();
+            prop_assert!(
+                duration.abs_diff(length) < Duration::from_millis(1),
+                "{duration:?}, expected {length:?}"
+            );
+        }
+    }
+
+    /// Change a byte, insert a byte, or cut the file short
+    #[derive(Debug, Clone)]
+    enum Damage {
+        Set(Index, u8),
+        Insert(Index, u8),
+        Truncate(Index),
+    }
+    fn damage() -> impl Strategy<Value = Damage> {
+        prop_oneof![
+            (any::<Index>(), any::<u8>()).prop_map(|(i, byte)| Damage::Set(i, byte)),
+            (any::<Index>(), any::<u8>()).prop_map(|(i, byte)| Damage::Insert(i, byte)),
+            any::<Index>().prop_map(Damage::Truncate),
+        ]
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2000))]
+
+        #[test]
+        fn test_malformed_files(
+            (midifile, _) in random_song(),
+            damages in collection::vec(damage(), 1..4),
+        ) {
+            let mut bytes = midifile.to_midi();
+            for damage in damages {
+                let len = bytes.len();
+                match damage {
+                    Damage::Set(i, byte) => bytes[i.index(len)] = byte,
+                    Damage::Insert(i, byte) => bytes.insert(i.index(len), byte),
+                    Damage::Truncate(i) => bytes.truncate(i.index(len).max(1)),
+                }
+            }
+            // Mustn't panic, and has either a length or an error
+            let mut song = MidiMeta::from_bytes("broken.mid".into(), bytes.clone());
+            prop_assert_ne!(song.get_duration().is_some(), song.get_status().is_err());
+            song.check_ending();
+            let _ = song.read_title();
+            let _ = parse_midi(&bytes);
+        }
+    }
+
+    #[test]
+    fn test_parser_panic() {
+        // Header of an unknown format, then a track chunk without its data
+        let bytes = [
+            b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 236, 1, 0, 96, b'M', b'T', b'r', b'k', 0, 0,
+            0, 36,
+        ];
+        let error = read_length(&bytes).unwrap_err();
+        assert!(error.starts_with("Malformed file: "), "{error}");
+        let song = MidiMeta::from_bytes("broken.mid".into(), bytes.to_vec());
+        assert!(song.get_status().is_err());
+    }
 }
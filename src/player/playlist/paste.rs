@@ -0,0 +1,79 @@
+//! Song paths pasted as text
+//!
+//! One path per line. Lines can be plain paths or `file://` URLs, as copied from a terminal or
+//! a file manager. Empty lines and `#` comments (as in `text/uri-list`) are skipped.
+
+use std::path::PathBuf;
+
+/// Parse pasted text into paths. Unsupported URLs are left out.
+pub fn parse_paths(text: &str) -> Vec<PathBuf> {
+    text.lines().filter_map(parse_line).collect()
+}
+
+pub fn parse_line(line: &str) -> Option<PathBuf> {
+    let line = line.trim();
+    // Shells and file managers may quote paths with spaces
+    let line = line
+        .strip_prefix(['"', '\''])
+        .and_then(|line| line.strip_suffix(['"', '\'']))
+        .unwrap_or(line);
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let Some(url_path) = line.strip_prefix("file://") else {
+        // Some other scheme
+        if line.contains("://") {
+            return None;
+        }
+        return Some(PathBuf::from(line));
+    };
+    let url_path = url_path.strip_prefix("localhost").unwrap_or(url_path);
+    let path = percent_decode(url_path)?;
+    // file:///C:/dir → C:/dir
+    #[cfg(target_os = "windows")]
+    let path = path.strip_prefix('/').unwrap_or(&path).to_owned();
+    Some(PathBuf::from(path))
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut iter = text.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_paths() {
+        let text = "\
+/music/a.mid
+  \"/music/with space.mid\"  
+
+# comment
+file:///music/b%20c.mid
+file://localhost/music/d.mid
+https://example.com/e.mid
+file:///broken%2";
+        let paths = parse_paths(text);
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/music/a.mid"),
+                PathBuf::from("/music/with space.mid"),
+                PathBuf::from("/music/b c.mid"),
+                PathBuf::from("/music/d.mid"),
+            ]
+        );
+    }
+}
@@ -1,12 +1,26 @@
 //! Playlist (de)serialization Into / From JSON.
 //!
 
-use std::{convert::Into, fs::File, io::Write, path::PathBuf};
-
-use super::{enums::FileListMode, font_meta::FontMeta, midi_meta::MidiMeta, Playlist};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Into,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{
+    enums::{BundleFormat, FileListMode, SongSort},
+    font_meta::FontMeta,
+    midi_meta::MidiMeta,
+    Playlist,
+};
+use crate::player::{program_lock::ProgramLocks, soundfont_list::FontSort};
 use anyhow::bail;
 use relative_path::{PathExt, RelativePath};
+use serde::Deserialize;
 use serde_json::{json, Value};
+use zip::{write::SimpleFileOptions, ZipWriter};
 
 // Reference because we don't want to consume the playlist during autosave.
 impl From<&Playlist> for Value {
@@ -19,10 +33,16 @@ impl From<&Playlist> for Value {
                      "fonts": playlist.fonts,
                      "font_list_mode": playlist.font_list_mode as u8,
                      "font_dir": playlist.font_dir,
+                     "font_sort": playlist.font_sort as u8,
 
                      "songs": playlist.midis,
                      "song_list_mode": playlist.song_list_mode as u8,
                      "song_dir": playlist.midi_dir,
+                     "song_sort": playlist.song_sort as u8,
+
+                     "program_locks": playlist.program_locks,
+                     "audio_profile": playlist.audio_profile,
+                     "autoplay_new_files": playlist.autoplay_new_files,
                     }
                 )
             },
@@ -42,6 +62,13 @@ impl From<&Playlist> for Value {
                     if let Ok(relative_path) = absolute_path.relative_to(&root) {
                         song.set_path(relative_path.to_path("."));
                     }
+                    let soundfont = song.get_soundfont().and_then(|font| {
+                        font.relative_to(&root)
+                            .map_or(None, |relative_path| Some(relative_path.to_path(".")))
+                    });
+                    if soundfont.is_some() {
+                        song.set_soundfont(soundfont);
+                    }
                 }
                 let font_dir = playlist.font_dir.as_ref().and_then(|dir| {
                     dir.relative_to(&root)
@@ -58,10 +85,16 @@ impl From<&Playlist> for Value {
                      "fonts": fonts,
                      "font_list_mode": playlist.font_list_mode as u8,
                      "font_dir": font_dir,
+                     "font_sort": playlist.font_sort as u8,
 
                      "songs": songs,
                      "song_list_mode": playlist.song_list_mode as u8,
                      "song_dir": song_dir,
+                     "song_sort": playlist.song_sort as u8,
+
+                     "program_locks": playlist.program_locks,
+                     "audio_profile": playlist.audio_profile,
+                     "autoplay_new_files": playlist.autoplay_new_files,
                     }
                 )
             },
@@ -82,6 +115,11 @@ impl From<Value> for Playlist {
                     FileListMode::try_from(int as u8).unwrap_or_default()
                 }),
             font_dir: value["font_dir"].as_str().map(Into::into),
+            font_sort: value["font_sort"]
+                .as_u64()
+                .map_or_else(FontSort::default, |int| {
+                    FontSort::try_from(int as u8).unwrap_or_default()
+                }),
 
             midis: vec![],
             song_list_mode: value["song_list_mode"]
@@ -90,6 +128,15 @@ impl From<Value> for Playlist {
                     FileListMode::try_from(int as u8).unwrap_or_default()
                 }),
             midi_dir: value["song_dir"].as_str().map(Into::into),
+            song_sort: value["song_sort"]
+                .as_u64()
+                .map_or_else(SongSort::default, |int| {
+                    SongSort::try_from(int as u8).unwrap_or_default()
+                }),
+
+            program_locks: ProgramLocks::deserialize(&value["program_locks"]).unwrap_or_default(),
+            audio_profile: value["audio_profile"].as_str().map(Into::into),
+            autoplay_new_files: value["autoplay_new_files"].as_bool().unwrap_or_default(),
 
             ..Default::default()
         };
@@ -135,6 +182,11 @@ impl From<Value> for Playlist {
 }
 
 impl Playlist {
+    /// Open a portable playlist file. Relative paths are resolved against the file.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read or isn't valid JSON.
     pub fn open_portable(filepath: PathBuf) -> anyhow::Result<Self> {
         let json_str = std::fs::read_to_string(&filepath)?;
         let data: Value = serde_json::from_str(&json_str)?;
@@ -151,6 +203,12 @@ impl Playlist {
             if let Ok(relative_path) = RelativePath::from_path(&song.get_path()) {
                 song.set_path(relative_path.to_logical_path(root));
             };
+            if let Some(font) = song.get_soundfont() {
+                if let Ok(relative_path) = RelativePath::from_path(font) {
+                    let absolute_path = relative_path.to_logical_path(root);
+                    song.set_soundfont(Some(absolute_path));
+                };
+            }
         }
         if let Some(dir) = &playlist.font_dir {
             if let Ok(relative_path) = RelativePath::from_path(dir) {
@@ -184,15 +242,182 @@ impl Playlist {
         self.unsaved_changes = false;
         Ok(())
     }
+
+    /// Throw away unsaved changes by reading the portable file again. Stays the same playlist.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the playlist has no portable file, or it can't be opened anymore.
+    pub fn revert_portable(&mut self) -> anyhow::Result<()> {
+        let Some(filepath) = self.get_portable_path() else {
+            bail!("Playlist isn't saved in a file.")
+        };
+        let saved = Self::open_portable(filepath)?;
+        *self = Self {
+            id: self.id,
+            deletion_status: self.deletion_status,
+            ..saved
+        };
+        self.refresh();
+        Ok(())
+    }
+}
+
+impl Playlist {
+    /// Save a portable copy together with all its songs and soundfonts, so it can be moved to
+    /// another machine. The files are copied into a folder named after the playlist file, and
+    /// referred to relatively. With `BundleFormat::Zip`, `filepath` is a zip file that holds the
+    /// playlist file and the folder.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the path has no file name, or copying a file or writing the archive fails.
+    pub fn export_bundle(&self, filepath: &Path, format: BundleFormat) -> anyhow::Result<()> {
+        let Some(stem) = filepath.file_stem() else {
+            bail!("No file name.")
+        };
+        let stem = stem.to_string_lossy().into_owned();
+        let root = filepath.parent().unwrap_or_else(|| Path::new(""));
+        let writer = match format {
+            BundleFormat::Folder => BundleWriter::Folder(root.to_owned()),
+            BundleFormat::Zip => {
+                BundleWriter::Zip(Box::new(ZipWriter::new(File::create(filepath)?)))
+            }
+        };
+        let mut bundle = Bundle {
+            root: root.to_owned(),
+            dir: stem.clone(),
+            names: HashMap::new(),
+            taken: HashSet::new(),
+            writer,
+        };
+
+        // Everything is listed one by one, from the copied files.
+        let mut playlist = self.duplicate();
+        playlist.font_list_mode = FileListMode::Manual;
+        playlist.font_dir = None;
+        playlist.song_list_mode = FileListMode::Manual;
+        playlist.midi_dir = None;
+        for font in &mut playlist.fonts {
+            let path = bundle.add("fonts", &font.get_path(), || font.read_bytes())?;
+            font.set_path(path);
+        }
+        for song in &mut playlist.midis {
+            let path = bundle.add("songs", &song.get_path(), || song.read_bytes())?;
+            song.set_path(path);
+            if let Some(font) = song.get_soundfont().cloned() {
+                let path = bundle.add("fonts", &font, || fs::read(&font))?;
+                song.set_soundfont(Some(path));
+            }
+        }
+
+        let playlist_name = format!("{stem}.midpl");
+        playlist.set_portable_path(Some(root.join(&playlist_name)));
+        let json = Value::from(&playlist).to_string();
+        bundle.writer.write(&playlist_name, json.as_bytes())?;
+        bundle.writer.finish()
+    }
+}
+
+/// Files being copied into an exported playlist bundle
+struct Bundle {
+    /// Where the playlist file goes
+    root: PathBuf,
+    /// Folder of the copied files, under `root`
+    dir: String,
+    /// Name in the bundle for each copied file, so files used twice are copied once
+    names: HashMap<PathBuf, String>,
+    taken: HashSet<String>,
+    writer: BundleWriter,
+}
+impl Bundle {
+    /// Copy a file into `subdir`, unless it's already in. Returns where it will be, once the
+    /// bundle is in place. Files of the same name are numbered.
+    fn add<B: AsRef<[u8]>>(
+        &mut self,
+        subdir: &str,
+        source: &Path,
+        read: impl FnOnce() -> io::Result<B>,
+    ) -> anyhow::Result<PathBuf> {
+        if let Some(name) = self.names.get(source) {
+            return Ok(self.root.join(name));
+        }
+        let file_name = source.file_name().map_or_else(
+            || "file".to_owned(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let (file_stem, extension) = file_name
+            .rsplit_once('.')
+            .map_or((file_name.as_str(), String::new()), |(stem, extension)| {
+                (stem, format!(".{extension}"))
+            });
+        let dir = format!("{}/{subdir}", self.dir);
+        let mut name = format!("{dir}/{file_name}");
+        let mut number = 2;
+        while self.taken.contains(&name) {
+            name = format!("{dir}/{file_stem} ({number}){extension}");
+            number += 1;
+        }
+
+        let bytes = match read() {
+            Ok(bytes) => bytes,
+            Err(e) => bail!("Can't read {}: {e}", source.display()),
+        };
+        self.writer.write(&name, bytes.as_ref())?;
+        self.taken.insert(name.clone());
+        self.names.insert(source.to_owned(), name.clone());
+        Ok(self.root.join(name))
+    }
+}
+
+enum BundleWriter {
+    /// Files are written under this directory
+    Folder(PathBuf),
+    Zip(Box<ZipWriter<File>>),
+}
+impl BundleWriter {
+    /// `name` is relative, with `/` separators
+    fn write(&mut self, name: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        match self {
+            Self::Folder(root) => {
+                let path = root.join(name);
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(path, bytes)?;
+            }
+            Self::Zip(zip) => {
+                zip.start_file(name, SimpleFileOptions::default())?;
+                zip.write_all(bytes)?;
+            }
+        }
+        Ok(())
+    }
+    fn finish(self) -> anyhow::Result<()> {
+        if let Self::Zip(zip) = self {
+            zip.finish()?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     //! These tests convert data into JSON and back, and then assert that it's unchanged.
 
-    use std::fs;
+    use std::{fs, time::Duration};
+
+    use proptest::{
+        collection, option,
+        prelude::*,
+        sample::{select, Index},
+    };
 
     use super::*;
+    use crate::{
+        player::playlist::font_meta::BUILTIN_SOUNDFONT,
+        test_support::{note_on, random_song, song},
+    };
 
     fn run_serialize(playlist: Playlist) -> Playlist {
         Playlist::from(Value::from(&playlist))
@@ -236,6 +461,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_custom_font_order() {
+        let mut playlist = Playlist::default();
+        for path in ["a.sf2", "b.sf2"] {
+            playlist.add_font(path.into()).unwrap();
+        }
+        playlist.move_font(1, 0).unwrap();
+        let new_playlist = run_serialize(playlist);
+        assert_eq!(new_playlist.font_sort, FontSort::Custom);
+        assert_eq!(new_playlist.fonts[0].get_name(), "b.sf2");
+    }
+
+    #[test]
+    fn test_sort() {
+        for sort in SongSort::ALL {
+            let playlist = Playlist {
+                song_sort: sort,
+                ..Default::default()
+            };
+            assert_eq!(run_serialize(playlist).song_sort, sort);
+        }
+        for sort in FontSort::ALL {
+            let playlist = Playlist {
+                font_sort: sort,
+                ..Default::default()
+            };
+            assert_eq!(run_serialize(playlist).font_sort, sort);
+        }
+    }
+
     #[test]
     fn test_fontdir() {
         let mut playlist_non = Playlist::default();
@@ -262,6 +517,67 @@ mod tests {
         assert_eq!(dir_path.to_str().unwrap(), "Fakepath");
     }
 
+    #[test]
+    fn test_portable_round_trip() {
+        fs::create_dir_all("temp").unwrap();
+        let filepath = PathBuf::from("temp/roundtrip.midpl");
+        let mut playlist = Playlist::default();
+        playlist.set_portable_path(Some(filepath.clone()));
+        for path in ["temp/a.sf2", "temp/b.sf2"] {
+            playlist.add_font(path.into()).unwrap();
+        }
+        playlist.move_font(1, 0).unwrap();
+        playlist.song_list_mode = FileListMode::Directory;
+        playlist.midi_dir = Some("temp/songs".into());
+        playlist.song_sort = SongSort::SizeDesc;
+        let mut song = MidiMeta::new("temp/songs/a.mid".into());
+        song.set_soundfont(Some("temp/a.sf2".into()));
+        song.set_range(Some(Duration::from_secs(2)), Some(Duration::from_secs(30)));
+        song.set_loop_points(Some((Duration::from_secs(5), Duration::from_secs(10))));
+        playlist.midis.push(song);
+        playlist.save_portable().unwrap();
+
+        let opened = Playlist::open_portable(filepath.clone()).unwrap();
+        fs::remove_file(filepath).unwrap();
+        assert!(!opened.has_unsaved_changes());
+        assert_eq!(opened.font_sort, FontSort::Custom);
+        assert_eq!(opened.fonts[0].get_path(), PathBuf::from("temp/b.sf2"));
+        assert_eq!(opened.song_list_mode, FileListMode::Directory);
+        assert_eq!(opened.midi_dir, Some("temp/songs".into()));
+        assert_eq!(opened.song_sort, SongSort::SizeDesc);
+        let song = &opened.midis[0];
+        assert_eq!(song.get_path(), PathBuf::from("temp/songs/a.mid"));
+        assert_eq!(song.get_soundfont(), Some(&"temp/a.sf2".into()));
+        assert_eq!(song.get_start(), Some(Duration::from_secs(2)));
+        assert_eq!(song.get_end(), Some(Duration::from_secs(30)));
+        assert_eq!(
+            song.get_loop_points(),
+            Some((Duration::from_secs(5), Duration::from_secs(10)))
+        );
+    }
+
+    #[test]
+    fn test_revert_portable() {
+        fs::create_dir_all("temp").unwrap();
+        let mut playlist = Playlist::default();
+        let id = playlist.get_id();
+        playlist.set_portable_path(Some("temp/revert.midpl".into()));
+        playlist.name = "Saved".into();
+        playlist.save_portable().unwrap();
+
+        playlist.name = "Changed".into();
+        playlist.add_font("temp/a.sf2".into()).unwrap();
+        assert!(playlist.has_unsaved_changes());
+        playlist.revert_portable().unwrap();
+        assert!(!playlist.has_unsaved_changes());
+        assert_eq!(playlist.name, "Saved");
+        assert!(playlist.fonts.is_empty());
+        assert_eq!(playlist.get_id(), id);
+        fs::remove_file("temp/revert.midpl").unwrap();
+
+        assert!(Playlist::default().revert_portable().is_err());
+    }
+
     #[test]
     fn test_save_portable_unchecks_flag() {
         fs::create_dir_all("temp").unwrap();
@@ -271,4 +587,322 @@ mod tests {
         playlist.save_portable().unwrap();
         assert!(!playlist.has_unsaved_changes());
     }
+
+    /// Text with characters that need escaping
+    fn text() -> impl Strategy<Value = String> {
+        const CHARS: &[char] = &[
+            'a', 'Z', '0', ' ', '"', '\\', '/', '.', 'ä', '音', '\n', '🎵',
+        ];
+        collection::vec(select(CHARS), 0..12).prop_map(String::from_iter)
+    }
+    fn duration() -> impl Strategy<Value = Duration> {
+        (0_u64..100_000, 0_u32..1_000_000_000).prop_map(|(secs, nanos)| Duration::new(secs, nanos))
+    }
+
+    /// What a song in a random playlist is made of
+    #[derive(Debug, Clone)]
+    struct RandomSong {
+        bytes: Vec<u8>,
+        path: String,
+        soundfont: Option<String>,
+        range: (Option<Duration>, Option<Duration>),
+        loop_points: Option<(Duration, Duration)>,
+    }
+    fn random_midi_meta() -> impl Strategy<Value = RandomSong> {
+        (
+            random_song(),
+            text(),
+            option::of(text()),
+            (option::of(duration()), option::of(duration())),
+            option::of((duration(), duration())),
+        )
+            .prop_map(
+                |((midifile, _), path, soundfont, range, loop_points)| RandomSong {
+                    bytes: midifile.to_midi(),
+                    path,
+                    soundfont,
+                    range,
+                    loop_points,
+                },
+            )
+    }
+
+    /// What a random playlist is made of. `Playlist` isn't `Debug`, so proptest can't show it.
+    #[derive(Debug, Clone)]
+    struct RandomPlaylist {
+        name: String,
+        font_list_mode: FileListMode,
+        font_sort: FontSort,
+        font_dir: Option<String>,
+        song_list_mode: FileListMode,
+        song_sort: SongSort,
+        midi_dir: Option<String>,
+        autoplay_new_files: bool,
+        fonts: Vec<String>,
+        songs: Vec<RandomSong>,
+        program_lock: (usize, u8),
+    }
+    impl RandomPlaylist {
+        fn build(&self) -> Playlist {
+            let mut playlist = Playlist {
+                name: self.name.clone(),
+                font_list_mode: self.font_list_mode,
+                font_sort: self.font_sort,
+                font_dir: self.font_dir.clone().map(Into::into),
+                song_list_mode: self.song_list_mode,
+                song_sort: self.song_sort,
+                midi_dir: self.midi_dir.clone().map(Into::into),
+                autoplay_new_files: self.autoplay_new_files,
+                ..Default::default()
+            };
+            for path in &self.fonts {
+                playlist.fonts.push(FontMeta::new(path.into()));
+            }
+            for random_song in &self.songs {
+                let mut song = MidiMeta::from_bytes(
+                    random_song.path.clone().into(),
+                    random_song.bytes.clone(),
+                );
+                song.set_soundfont(random_song.soundfont.clone().map(Into::into));
+                song.set_range(random_song.range.0, random_song.range.1);
+                song.set_loop_points(random_song.loop_points);
+                playlist.midis.push(song);
+            }
+            let (channel, program) = self.program_lock;
+            playlist.program_locks.set(channel, Some(program));
+            playlist
+        }
+    }
+    fn random_playlist() -> impl Strategy<Value = RandomPlaylist> {
+        (
+            text(),
+            (select(&FileListMode::ALL[..]), select(&FontSort::ALL[..])),
+            option::of(text()),
+            (select(&FileListMode::ALL[..]), select(&SongSort::ALL[..])),
+            option::of(text()),
+            any::<bool>(),
+            collection::vec(text(), 0..4),
+            collection::vec(random_midi_meta(), 0..4),
+            (0_usize..16, 0_u8..128),
+        )
+            .prop_map(
+                |(
+                    name,
+                    (font_list_mode, font_sort),
+                    font_dir,
+                    (song_list_mode, song_sort),
+                    midi_dir,
+                    autoplay_new_files,
+                    fonts,
+                    songs,
+                    program_lock,
+                )| RandomPlaylist {
+                    name,
+                    font_list_mode,
+                    font_sort,
+                    font_dir,
+                    song_list_mode,
+                    song_sort,
+                    midi_dir,
+                    autoplay_new_files,
+                    fonts,
+                    songs,
+                    program_lock,
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn test_random_round_trip(random in random_playlist()) {
+            let playlist = random.build();
+            let json = Value::from(&playlist).to_string();
+            let new_playlist = Playlist::from(serde_json::from_str::<Value>(&json).unwrap());
+
+            prop_assert_eq!(&new_playlist.name, &playlist.name);
+            prop_assert_eq!(new_playlist.font_list_mode, playlist.font_list_mode);
+            prop_assert_eq!(new_playlist.font_sort, playlist.font_sort);
+            prop_assert_eq!(&new_playlist.font_dir, &playlist.font_dir);
+            prop_assert_eq!(new_playlist.song_list_mode, playlist.song_list_mode);
+            prop_assert_eq!(new_playlist.song_sort, playlist.song_sort);
+            prop_assert_eq!(&new_playlist.midi_dir, &playlist.midi_dir);
+            prop_assert_eq!(&new_playlist.program_locks, &playlist.program_locks);
+            prop_assert_eq!(new_playlist.autoplay_new_files, playlist.autoplay_new_files);
+            let font_paths = |playlist: &Playlist| {
+                playlist
+                    .fonts
+                    .iter()
+                    .map(FontMeta::get_path)
+                    .collect::<Vec<_>>()
+            };
+            prop_assert_eq!(font_paths(&new_playlist), font_paths(&playlist));
+            prop_assert_eq!(new_playlist.midis.len(), playlist.midis.len());
+            for (new_song, song) in new_playlist.midis.iter().zip(&playlist.midis) {
+                prop_assert_eq!(new_song.get_path(), song.get_path());
+                prop_assert_eq!(new_song.get_size(), song.get_size());
+                prop_assert_eq!(new_song.get_duration(), song.get_duration());
+                prop_assert_eq!(new_song.get_soundfont(), song.get_soundfont());
+                prop_assert_eq!(new_song.get_start(), song.get_start());
+                prop_assert_eq!(new_song.get_end(), song.get_end());
+                prop_assert_eq!(new_song.get_loop_points(), song.get_loop_points());
+            }
+        }
+    }
+
+    /// Any json value, nested a little. Objects use keys that playlists have.
+    fn json_value() -> impl Strategy<Value = Value> {
+        const KEYS: &[&str] = &["filepath", "secs", "nanos", "duration", "start", "x"];
+        let leaf = prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::from),
+            any::<i64>().prop_map(Value::from),
+            (0_f64..1e20).prop_map(Value::from),
+            Just(json!(u64::MAX)),
+            text().prop_map(Value::from),
+        ];
+        leaf.prop_recursive(2, 16, 6, |inner| {
+            prop_oneof![
+                collection::vec(inner.clone(), 0..3).prop_map(Value::Array),
+                collection::btree_map(select(KEYS), inner, 0..6).prop_map(|map| {
+                    Value::Object(map.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+                }),
+            ]
+        })
+    }
+    fn count_values(value: &Value) -> usize {
+        1 + match value {
+            Value::Object(map) => map.values().map(count_values).sum(),
+            Value::Array(items) => items.iter().map(count_values).sum(),
+            _ => 0,
+        }
+    }
+    /// Replace the nth value of the tree, counting depth first, with `junk`. Returns the count
+    /// left if the tree is smaller.
+    fn replace_nth(value: &mut Value, n: usize, junk: &Value) -> Result<(), usize> {
+        if n == 0 {
+            *value = junk.clone();
+            return Ok(());
+        }
+        let mut n = n - 1;
+        let children: Vec<&mut Value> = match value {
+            Value::Object(map) => map.values_mut().collect(),
+            Value::Array(items) => items.iter_mut().collect(),
+            _ => vec![],
+        };
+        for child in children {
+            n = match replace_nth(child, n, junk) {
+                Ok(()) => return Ok(()),
+                Err(left) => left,
+            };
+        }
+        Err(n)
+    }
+
+    proptest! {
+        #[test]
+        fn test_malformed_playlists(
+            random in random_playlist(),
+            junk in collection::vec((any::<Index>(), json_value()), 1..8),
+        ) {
+            let playlist = random.build();
+            let mut json = Value::from(&playlist);
+            for (n, junk) in &junk {
+                let n = n.index(count_values(&json));
+                replace_nth(&mut json, n, junk).unwrap();
+            }
+            // Mustn't panic, and doesn't invent songs
+            let new_playlist = Playlist::from(json);
+            prop_assert!(new_playlist.midis.len() <= playlist.midis.len().max(3));
+            let _ = Value::from(&new_playlist);
+        }
+    }
+
+    /// A soundfont, and two songs of the same name in different folders. The second song uses
+    /// the soundfont too.
+    fn bundle_source(dir: &Path) -> (Playlist, Vec<Vec<u8>>) {
+        let _ = fs::remove_dir_all(dir);
+        let font_path = dir.join("font.sf2");
+        fs::create_dir_all(dir).unwrap();
+        fs::write(&font_path, BUILTIN_SOUNDFONT).unwrap();
+        let mut playlist = Playlist::default();
+        playlist.add_font(font_path.clone()).unwrap();
+        let mut songs = vec![];
+        for (i, folder) in ["a", "b"].iter().enumerate() {
+            let path = dir.join(folder).join("song.mid");
+            let bytes = song(&[(i as f32 + 1., note_on(0, 60, 100))]).to_midi();
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &bytes).unwrap();
+            playlist.add_song(path).unwrap();
+            songs.push(bytes);
+        }
+        playlist.midis[1].set_soundfont(Some(font_path));
+        (playlist, songs)
+    }
+
+    /// Everything in the bundle can be found
+    fn assert_bundle(filepath: &Path, mut songs: Vec<Vec<u8>>) {
+        let opened = Playlist::open_portable(filepath.to_owned()).unwrap();
+        let mut bundled: Vec<_> = opened
+            .midis
+            .iter()
+            .map(|song| song.read_bytes().unwrap())
+            .collect();
+        bundled.sort();
+        songs.sort();
+        assert_eq!(bundled, songs);
+        assert_eq!(opened.fonts.len(), 1);
+        assert_eq!(opened.fonts[0].read_bytes().unwrap(), BUILTIN_SOUNDFONT);
+        // The song's own soundfont is the same copy
+        let song_font = opened.midis.iter().find_map(MidiMeta::get_soundfont);
+        assert_eq!(song_font, Some(&opened.fonts[0].get_path()));
+    }
+
+    #[test]
+    fn test_export_bundle_folder() {
+        let source = Path::new("temp/bundle_folder_source");
+        let (playlist, songs) = bundle_source(source);
+        let out = Path::new("temp/bundle_folder_out");
+        let _ = fs::remove_dir_all(out);
+        fs::create_dir_all(out).unwrap();
+        playlist
+            .export_bundle(&out.join("Set.midpl"), BundleFormat::Folder)
+            .unwrap();
+        assert!(out.join("Set/songs/song (2).mid").is_file());
+
+        // Moved elsewhere, and the originals are gone
+        fs::remove_dir_all(source).unwrap();
+        let moved = Path::new("temp/bundle_folder_moved");
+        let _ = fs::remove_dir_all(moved);
+        fs::rename(out, moved).unwrap();
+        assert_bundle(&moved.join("Set.midpl"), songs);
+        fs::remove_dir_all(moved).unwrap();
+    }
+
+    #[test]
+    fn test_export_bundle_zip() {
+        let source = Path::new("temp/bundle_zip_source");
+        let (playlist, songs) = bundle_source(source);
+        let zip_path = Path::new("temp/bundle_zip.zip");
+        playlist.export_bundle(zip_path, BundleFormat::Zip).unwrap();
+
+        fs::remove_dir_all(source).unwrap();
+        let extracted = Path::new("temp/bundle_zip_extracted");
+        let _ = fs::remove_dir_all(extracted);
+        let mut archive = zip::ZipArchive::new(File::open(zip_path).unwrap()).unwrap();
+        let mut names: Vec<_> = archive.file_names().collect();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            [
+                "bundle_zip.midpl",
+                "bundle_zip/fonts/font.sf2",
+                "bundle_zip/songs/song (2).mid",
+                "bundle_zip/songs/song.mid",
+            ]
+        );
+        archive.extract(extracted).unwrap();
+        assert_bundle(&extracted.join("bundle_zip.midpl"), songs);
+        fs::remove_dir_all(extracted).unwrap();
+        fs::remove_file(zip_path).unwrap();
+    }
 }
@@ -0,0 +1,171 @@
+//! Song list summary and export for sharing set lists.
+//!
+
+use std::{fmt::Write as _, time::Duration};
+
+use super::{enums::SongListFormat, midi_meta::MidiMeta, Playlist};
+
+/// Song count and total length of a group of songs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SongListSummary {
+    pub count: usize,
+    /// Sum of known durations
+    pub duration: Duration,
+    /// Songs without a known duration
+    pub unknown: usize,
+}
+impl SongListSummary {
+    pub fn new<'a>(songs: impl IntoIterator<Item = &'a MidiMeta>) -> Self {
+        let mut summary = Self::default();
+        for song in songs {
+            summary.count += 1;
+            match song.get_duration() {
+                Some(duration) => summary.duration += duration,
+                None => summary.unknown += 1,
+            }
+        }
+        summary
+    }
+}
+
+impl Playlist {
+    #[must_use]
+    pub fn get_song_summary(&self) -> SongListSummary {
+        SongListSummary::new(&self.midis)
+    }
+
+    /// Song names, durations and paths as text
+    #[must_use]
+    pub fn export_song_list(&self, format: SongListFormat) -> String {
+        let mut text = String::new();
+        match format {
+            SongListFormat::Text => {
+                let _ = writeln!(text, "{}", self.name);
+                for (i, song) in self.midis.iter().enumerate() {
+                    let _ = writeln!(
+                        text,
+                        "{}. {} [{}] {}",
+                        i + 1,
+                        song.get_name(),
+                        format_duration(song.get_duration()),
+                        song.get_path().display()
+                    );
+                }
+            }
+            SongListFormat::Markdown => {
+                text.push_str("| # | Name | Duration | Path |\n|---|---|---|---|\n");
+                for (i, song) in self.midis.iter().enumerate() {
+                    let _ = writeln!(
+                        text,
+                        "| {} | {} | {} | {} |",
+                        i + 1,
+                        markdown_cell(&song.get_name()),
+                        format_duration(song.get_duration()),
+                        markdown_cell(&song.get_path().to_string_lossy())
+                    );
+                }
+            }
+            SongListFormat::Csv => {
+                text.push_str("name,duration,path\n");
+                for song in &self.midis {
+                    let _ = writeln!(
+                        text,
+                        "{},{},{}",
+                        csv_field(&song.get_name()),
+                        song.get_duration()
+                            .map(|duration| format!("{:.3}", duration.as_secs_f64()))
+                            .unwrap_or_default(),
+                        csv_field(&song.get_path().to_string_lossy())
+                    );
+                }
+            }
+        }
+        text
+    }
+}
+
+/// "07:32", or "--:--" if unknown
+fn format_duration(duration: Option<Duration>) -> String {
+    duration.map_or_else(
+        || "--:--".to_owned(),
+        |duration| {
+            let secs = duration.as_secs();
+            if secs < 3600 {
+                format!("{:02}:{:02}", secs / 60, secs % 60)
+            } else {
+                format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+            }
+        },
+    )
+}
+
+fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|")
+}
+
+/// Quote a field if it contains separators or quotes
+#[must_use]
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn playlist() -> Playlist {
+        let mut playlist = Playlist {
+            name: "Set".to_owned(),
+            ..Default::default()
+        };
+        playlist
+            .add_song(PathBuf::from("/music/a|b.mid"))
+            .expect("add song");
+        playlist
+            .add_song(PathBuf::from("/music/c,d.mid"))
+            .expect("add song");
+        playlist
+    }
+
+    #[test]
+    fn test_song_summary() {
+        let mut playlist = playlist();
+        let json = serde_json::json!({"filepath": "/music/e.mid", "duration": {"secs": 90}});
+        playlist
+            .midis
+            .push(MidiMeta::try_from(&json).expect("midi meta"));
+        let summary = playlist.get_song_summary();
+        assert_eq!(summary.count, 3);
+        assert_eq!(summary.duration, Duration::from_secs(90));
+        assert_eq!(summary.unknown, 2);
+    }
+
+    #[test]
+    fn test_export_song_list() {
+        let playlist = playlist();
+        assert_eq!(
+            playlist.export_song_list(SongListFormat::Text),
+            "Set\n1. a|b.mid [--:--] /music/a|b.mid\n2. c,d.mid [--:--] /music/c,d.mid\n"
+        );
+        assert!(playlist
+            .export_song_list(SongListFormat::Markdown)
+            .contains("| 1 | a\\|b.mid | --:-- | /music/a\\|b.mid |"));
+        assert_eq!(
+            playlist.export_song_list(SongListFormat::Csv),
+            "name,duration,path\na|b.mid,,/music/a|b.mid\n\"c,d.mid\",,\"/music/c,d.mid\"\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
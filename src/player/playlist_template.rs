@@ -0,0 +1,105 @@
+//! Settings applied to newly created playlists.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    playlist::{
+        enums::{FileListMode, SongSort},
+        Playlist,
+    },
+    soundfont_list::FontSort,
+};
+
+/// Replaced with a number in the name pattern
+pub const NAME_COUNTER: &str = "{n}";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaylistTemplate {
+    /// `{n}` becomes the lowest number not used by another playlist
+    pub name_pattern: String,
+    pub song_sort: SongSort,
+    pub font_sort: FontSort,
+    pub song_list_mode: FileListMode,
+    pub font_list_mode: FileListMode,
+    /// Added and selected when the soundfont list is manual
+    pub default_font: Option<PathBuf>,
+}
+impl Default for PlaylistTemplate {
+    fn default() -> Self {
+        Self {
+            name_pattern: format!("Playlist {NAME_COUNTER}"),
+            song_sort: SongSort::default(),
+            font_sort: FontSort::default(),
+            song_list_mode: FileListMode::Manual,
+            font_list_mode: FileListMode::Manual,
+            default_font: None,
+        }
+    }
+}
+impl PlaylistTemplate {
+    /// Name for a new playlist, given the names already in use
+    #[must_use]
+    pub fn get_name(&self, taken: &[&str]) -> String {
+        if !self.name_pattern.contains(NAME_COUNTER) {
+            return self.name_pattern.clone();
+        }
+        // One of these is always free
+        (1..=taken.len() + 1)
+            .map(|n| self.name_pattern.replace(NAME_COUNTER, &n.to_string()))
+            .find(|name| !taken.contains(&name.as_str()))
+            .unwrap_or_else(|| self.name_pattern.clone())
+    }
+
+    #[must_use]
+    pub fn build(&self, taken_names: &[&str]) -> Playlist {
+        let mut playlist = Playlist::default();
+        playlist.name = self.get_name(taken_names);
+        playlist.set_song_list_mode(self.song_list_mode);
+        playlist.set_font_list_mode(self.font_list_mode);
+        playlist.set_song_sort(self.song_sort);
+        playlist.set_font_sort(self.font_sort);
+        if let Some(font) = &self.default_font {
+            if playlist.add_font(font.clone()).is_ok() {
+                let _ = playlist.set_font_idx(Some(0));
+            }
+        }
+        playlist
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_counter() {
+        let template = PlaylistTemplate::default();
+        let taken = ["Playlist 1", "Playlist 3"];
+        assert_eq!(template.get_name(&taken), "Playlist 2");
+
+        let template = PlaylistTemplate {
+            name_pattern: "Mix".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(template.get_name(&taken), "Mix");
+    }
+
+    #[test]
+    fn test_build() {
+        let template = PlaylistTemplate {
+            song_sort: SongSort::TimeDesc,
+            font_sort: FontSort::SizeAsc,
+            default_font: Some(PathBuf::from("default.sf2")),
+            ..Default::default()
+        };
+        let playlist = template.build(&[]);
+        assert_eq!(playlist.name, "Playlist 1");
+        assert_eq!(playlist.get_song_sort(), SongSort::TimeDesc);
+        assert_eq!(playlist.get_font_sort(), FontSort::SizeAsc);
+        assert_eq!(playlist.get_fonts().len(), 1);
+        assert_eq!(playlist.get_font_idx(), Some(0));
+    }
+}
@@ -0,0 +1,200 @@
+//! Everything wrong with individual files, in one list.
+//!
+//! Missing and unreadable files are known from their status. Files that load but fail to play
+//! are remembered here when it happens, until they play fine or the list is cleared.
+
+use std::path::{Path, PathBuf};
+
+use midi_msg::MidiFileParseError;
+
+use super::{
+    audio::error::PlayerError as AudioError,
+    playlist::{font_meta::FontMetaError, midi_meta::MidiMetaError},
+    Player, PlayerEvent,
+};
+
+/// Table row of the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemLocation {
+    Song { playlist: usize, index: usize },
+    Font { playlist: usize, index: usize },
+    Library { index: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct Problem {
+    /// None if the file isn't listed anywhere, like a soundfont assigned to a song.
+    pub location: Option<ProblemLocation>,
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl Player {
+    /// Problems of the open playlists and the font library, in that order
+    pub fn get_problems(&self) -> Vec<Problem> {
+        let mut problems = vec![];
+        for (playlist, list) in self.playlists.iter().enumerate() {
+            for (index, song) in list.get_songs().iter().enumerate() {
+                if let Err(e) = song.get_status() {
+                    problems.push(Problem {
+                        location: Some(ProblemLocation::Song { playlist, index }),
+                        path: song.get_path(),
+                        message: e.to_string(),
+                    });
+                }
+            }
+            for (index, font) in list.get_fonts().iter().enumerate() {
+                if let Err(e) = font.get_status() {
+                    problems.push(Problem {
+                        location: Some(ProblemLocation::Font { playlist, index }),
+                        path: font.get_path(),
+                        message: e.to_string(),
+                    });
+                }
+            }
+        }
+        for (index, font) in self.font_lib.get_fonts().iter().enumerate() {
+            if let Err(e) = font.get_status() {
+                problems.push(Problem {
+                    location: Some(ProblemLocation::Library { index }),
+                    path: font.get_path(),
+                    message: e.to_string(),
+                });
+            }
+        }
+        for (path, message) in &self.render_failures {
+            let locations = self.find_file(path);
+            if locations.is_empty() {
+                problems.push(Problem {
+                    location: None,
+                    path: path.clone(),
+                    message: message.clone(),
+                });
+            }
+            for location in locations {
+                problems.push(Problem {
+                    location: Some(location),
+                    path: path.clone(),
+                    message: message.clone(),
+                });
+            }
+        }
+        problems
+    }
+
+    /// Forget files that failed to play. Status problems stay until the files are fixed.
+    pub fn clear_render_failures(&mut self) {
+        self.render_failures.clear();
+    }
+
+    /// Every row that has this file
+    fn find_file(&self, path: &Path) -> Vec<ProblemLocation> {
+        let mut locations = vec![];
+        for (playlist, list) in self.playlists.iter().enumerate() {
+            for (index, song) in list.get_songs().iter().enumerate() {
+                if song.get_path() == path {
+                    locations.push(ProblemLocation::Song { playlist, index });
+                }
+            }
+            for (index, font) in list.get_fonts().iter().enumerate() {
+                if font.get_path() == path {
+                    locations.push(ProblemLocation::Font { playlist, index });
+                }
+            }
+        }
+        for (index, font) in self.font_lib.get_fonts().iter().enumerate() {
+            if font.get_path() == path {
+                locations.push(ProblemLocation::Library { index });
+            }
+        }
+        locations
+    }
+
+    /// Remember which file made playback fail, if it was a file's fault.
+    pub(super) fn record_render_failure(&mut self, e: &anyhow::Error, song: &Path, font: &Path) {
+        let path = match e.downcast_ref::<AudioError>() {
+            Some(AudioError::InvalidFont { .. } | AudioError::InvalidCompressedFont { .. }) => {
+                font.to_owned()
+            }
+            Some(AudioError::CantAccessFile { path, .. }) => path.clone(),
+            Some(AudioError::InvalidMidi { .. }) => song.to_owned(),
+            None if e.is::<MidiFileParseError>() => song.to_owned(),
+            _ => return,
+        };
+        self.render_failures.retain(|(failed, _)| *failed != path);
+        self.render_failures.push((path, e.to_string()));
+    }
+
+    /// Both files played fine
+    pub(super) fn clear_render_failure(&mut self, song: &Path, font: &Path) {
+        self.render_failures
+            .retain(|(failed, _)| failed != song && failed != font);
+    }
+
+    /// Errors about a file go to the problem list, others are shown as usual.
+    pub(super) fn push_playback_error(&mut self, e: &anyhow::Error) {
+        if is_file_problem(e) {
+            self.emit(&PlayerEvent::ProblemFound(e.to_string()));
+        } else {
+            self.push_error(e.to_string());
+        }
+    }
+}
+
+fn is_file_problem(e: &anyhow::Error) -> bool {
+    e.is::<MidiMetaError>()
+        || e.is::<FontMetaError>()
+        || e.is::<MidiFileParseError>()
+        || matches!(
+            e.downcast_ref::<AudioError>(),
+            Some(
+                AudioError::InvalidFont { .. }
+                    | AudioError::InvalidCompressedFont { .. }
+                    | AudioError::CantAccessFile { .. }
+                    | AudioError::InvalidMidi { .. }
+            )
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_file_problem() {
+        assert!(is_file_problem(&anyhow::anyhow!(
+            MidiMetaError::InvalidFile {
+                filename: "a.mid".into(),
+                message: "Not midi".into(),
+            }
+        )));
+        assert!(!is_file_problem(&anyhow::anyhow!(AudioError::NoSink)));
+        assert!(!is_file_problem(&anyhow::anyhow!("Something else")));
+    }
+
+    #[test]
+    fn test_render_failures() {
+        let mut player = Player::headless();
+        let song = PathBuf::from("a.mid");
+        let font = PathBuf::from("b.sf2");
+
+        let error = anyhow::anyhow!(AudioError::NoSink);
+        player.record_render_failure(&error, &song, &font);
+        assert!(player.render_failures.is_empty());
+
+        let error = anyhow::anyhow!(AudioError::CantAccessFile {
+            path: font.clone(),
+            source: std::io::ErrorKind::NotFound.into(),
+        });
+        player.record_render_failure(&error, &song, &font);
+        player.record_render_failure(&error, &song, &font);
+        let problems = player.get_problems();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].path, font);
+        // Not in any list
+        assert_eq!(problems[0].location, None);
+
+        player.clear_render_failure(&song, &font);
+        assert!(player.get_problems().is_empty());
+    }
+}
@@ -0,0 +1,106 @@
+//! Per-channel program locks: a locked channel ignores the song's program changes.
+//! Also the drum kit override for the percussion channel.
+//!
+
+use serde::{Deserialize, Serialize};
+
+pub const CHANNEL_COUNT: usize = 16;
+/// Channel 10, 0-based
+pub const DRUM_CHANNEL: usize = 9;
+/// Soundfonts keep percussion presets in banks from 128 up.
+pub const DRUM_BANK_OFFSET: u16 = 128;
+
+/// Percussion preset forced on the drum channel, ignoring the song's bank and program changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DrumKit {
+    /// Soundfont bank number, `DRUM_BANK_OFFSET` and up
+    pub bank: u16,
+    pub program: u8,
+}
+impl DrumKit {
+    /// Bank select value to send on the drum channel. The synth adds the offset back.
+    #[must_use]
+    pub const fn get_bank_select(self) -> u8 {
+        let bank = self.bank.saturating_sub(DRUM_BANK_OFFSET);
+        if bank > 127 {
+            127
+        } else {
+            bank as u8
+        }
+    }
+}
+
+/// Locked program number for each MIDI channel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgramLocks([Option<u8>; CHANNEL_COUNT]);
+
+impl ProgramLocks {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self([None; CHANNEL_COUNT])
+    }
+    /// Channel is 0-based
+    #[must_use]
+    pub fn get(&self, channel: usize) -> Option<u8> {
+        self.0.get(channel).copied().flatten()
+    }
+    /// Channel is 0-based. Program is clamped to 0..=127.
+    pub fn set(&mut self, channel: usize, program: Option<u8>) {
+        if let Some(lock) = self.0.get_mut(channel) {
+            *lock = program.map(|program| program.min(127));
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(Option::is_none)
+    }
+    /// Per channel: this lock if set, otherwise the fallback's.
+    #[must_use]
+    pub fn or(mut self, fallback: Self) -> Self {
+        for (lock, fallback) in self.0.iter_mut().zip(fallback.0) {
+            *lock = lock.or(fallback);
+        }
+        self
+    }
+    /// (channel, program) of each locked channel
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u8)> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter_map(|(channel, lock)| lock.map(|program| (channel, program)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_or() {
+        let mut global = ProgramLocks::default();
+        global.set(0, Some(1));
+        global.set(1, Some(2));
+        let mut playlist = ProgramLocks::default();
+        playlist.set(1, Some(40));
+        playlist.set(99, Some(40));
+
+        let merged = playlist.or(global);
+        assert_eq!(merged.get(0), Some(1));
+        assert_eq!(merged.get(1), Some(40));
+        assert_eq!(merged.get(2), None);
+        assert_eq!(merged.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_drum_kit_bank_select() {
+        let standard = DrumKit {
+            bank: 128,
+            program: 0,
+        };
+        let other = DrumKit {
+            bank: 136,
+            program: 16,
+        };
+        assert_eq!(standard.get_bank_select(), 0);
+        assert_eq!(other.get_bank_select(), 8);
+    }
+}
@@ -0,0 +1,295 @@
+//! Play order of a playlist.
+//!
+//! Entries are indices into the playlist's song list. The queue doesn't see the songs, so when
+//! the list changes, the playlist maps the entries over with `remap`.
+
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Queue {
+    entries: Vec<usize>,
+    /// Current entry. None before playback starts.
+    position: Option<usize>,
+    /// RNG seed of the order. None if the queue isn't shuffled.
+    shuffle_seed: Option<u64>,
+}
+
+impl Queue {
+    /// Every song in list order, or shuffled from a known seed. Starts from the selected song,
+    /// which comes first when shuffled. The same inputs always give the same order.
+    #[must_use]
+    pub fn build(song_count: usize, selected: Option<usize>, shuffle_seed: Option<u64>) -> Self {
+        let mut entries: Vec<usize> = (0..song_count).collect();
+        if let Some(seed) = shuffle_seed {
+            shuffle(&mut entries, seed);
+            if let Some(selected) = selected {
+                entries.retain(|&entry| entry != selected);
+                entries.insert(0, selected);
+            }
+        }
+        let position = if shuffle_seed.is_some() {
+            0
+        } else {
+            selected.unwrap_or(0)
+        };
+        Self {
+            position: (position < entries.len()).then_some(position),
+            entries,
+            shuffle_seed,
+        }
+    }
+    /// Shuffled with a seed drawn from `rng`
+    pub fn shuffled(song_count: usize, selected: Option<usize>, rng: &mut impl Rng) -> Self {
+        Self::build(song_count, selected, Some(rng.gen()))
+    }
+    /// Queue of one song, which is playing
+    #[must_use]
+    pub fn single(song: usize) -> Self {
+        Self {
+            entries: vec![song],
+            position: Some(0),
+            shuffle_seed: None,
+        }
+    }
+    /// Entries that are None are left out. The position stays on the same entry, or the last
+    /// one kept before it.
+    #[must_use]
+    pub fn from_entries(
+        entries: &[Option<usize>],
+        position: Option<usize>,
+        shuffle_seed: Option<u64>,
+    ) -> Self {
+        let mut kept = vec![];
+        let mut new_position = None;
+        for (i, entry) in entries.iter().enumerate() {
+            let Some(entry) = *entry else {
+                continue;
+            };
+            if position.is_some_and(|position| i <= position) {
+                new_position = Some(kept.len());
+            }
+            kept.push(entry);
+        }
+        Self {
+            entries: kept,
+            position: new_position,
+            shuffle_seed,
+        }
+    }
+    /// Point the entries to new song indices, e.g. after the song list was sorted. Songs that
+    /// map to None are dropped.
+    pub fn remap(&mut self, map: impl Fn(usize) -> Option<usize>) {
+        let entries: Vec<Option<usize>> = self.entries.iter().map(|&entry| map(entry)).collect();
+        *self = Self::from_entries(&entries, self.position, self.shuffle_seed);
+    }
+
+    #[must_use]
+    pub fn get_entries(&self) -> &[usize] {
+        &self.entries
+    }
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    #[must_use]
+    pub const fn get_position(&self) -> Option<usize> {
+        self.position
+    }
+    /// Out of range positions are ignored
+    pub fn set_position(&mut self, position: Option<usize>) {
+        self.position = position.filter(|&position| position < self.entries.len());
+    }
+    #[must_use]
+    pub const fn get_shuffle_seed(&self) -> Option<u64> {
+        self.shuffle_seed
+    }
+    /// Song index of the current entry
+    #[must_use]
+    pub fn get_current(&self) -> Option<usize> {
+        self.position
+            .and_then(|position| self.entries.get(position))
+            .copied()
+    }
+    /// Entries before the current one
+    #[must_use]
+    pub fn get_history(&self) -> &[usize] {
+        &self.entries[..self.position.unwrap_or(0)]
+    }
+    /// Entries after the current one. Everything, if playback hasn't started.
+    #[must_use]
+    pub fn get_upcoming(&self) -> &[usize] {
+        &self.entries[self.position.map_or(0, |position| position + 1)..]
+    }
+
+    /// Move to next entry. Returns false at the end, unless wrapping around.
+    pub const fn advance(&mut self, wrap: bool) -> bool {
+        let Some(position) = self.position else {
+            return false;
+        };
+        if position + 1 < self.entries.len() {
+            self.position = Some(position + 1);
+            true
+        } else if wrap {
+            self.position = Some(0);
+            true
+        } else {
+            false
+        }
+    }
+    /// Song index that `advance` would move to
+    #[must_use]
+    pub fn peek_next(&self, wrap: bool) -> Option<usize> {
+        let position = self.position?;
+        if position + 1 < self.entries.len() {
+            Some(self.entries[position + 1])
+        } else if wrap {
+            self.entries.first().copied()
+        } else {
+            None
+        }
+    }
+    /// Move to previous entry. Returns false at the start, unless wrapping around.
+    pub const fn go_back(&mut self, wrap: bool) -> bool {
+        let Some(position) = self.position else {
+            return false;
+        };
+        if position > 0 {
+            self.position = Some(position - 1);
+            true
+        } else if wrap {
+            self.position = Some(self.entries.len() - 1);
+            true
+        } else {
+            false
+        }
+    }
+    /// Add a song to the end
+    pub fn push(&mut self, song: usize) {
+        self.entries.push(song);
+    }
+    /// Put songs right after the current entry, or at the end if playback hasn't started.
+    pub fn insert_next(&mut self, songs: &[usize]) {
+        let insert_at = self
+            .position
+            .map_or(self.entries.len(), |position| position + 1);
+        self.entries
+            .splice(insert_at..insert_at, songs.iter().copied());
+    }
+    /// Remove an entry. If it was the current one, the next entry becomes current.
+    pub fn remove(&mut self, index: usize) -> Option<usize> {
+        if index >= self.entries.len() {
+            return None;
+        }
+        let song = self.entries.remove(index);
+        self.position = match self.position {
+            Some(position) if index < position => Some(position - 1),
+            Some(position) if position >= self.entries.len() => None,
+            position => position,
+        };
+        Some(song)
+    }
+}
+
+/// Fisher-Yates with a fixed generator. Seeds are saved and shared, so the order must not change
+/// with rand versions or platforms.
+fn shuffle(entries: &mut [usize], seed: u64) {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    for i in (1..entries.len()).rev() {
+        // Index in 0..=i from the high bits of a 64-bit product
+        let j = ((u128::from(rng.next_u64()) * (i as u128 + 1)) >> 64) as usize;
+        entries.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let queue = Queue::build(3, Some(1), None);
+        assert_eq!(queue.get_entries(), [0, 1, 2]);
+        assert_eq!(queue.get_current(), Some(1));
+        assert_eq!(queue.get_history(), [0]);
+        assert_eq!(queue.get_upcoming(), [2]);
+
+        let queue = Queue::build(20, Some(7), Some(1234));
+        assert_eq!(queue, Queue::build(20, Some(7), Some(1234)));
+        assert_eq!(queue.get_current(), Some(7));
+        assert_eq!(queue.get_position(), Some(0));
+        assert_eq!(queue.len(), 20);
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let queue = Queue::shuffled(20, None, &mut rng);
+        let seed = queue.get_shuffle_seed().expect("shuffled");
+        assert_eq!(queue, Queue::build(20, None, Some(seed)));
+
+        assert_eq!(Queue::build(0, None, None).get_position(), None);
+    }
+
+    #[test]
+    fn test_pinned_order() {
+        // Saved queues depend on this. If it changes, old seeds give a different order.
+        let queue = Queue::build(10, None, Some(42));
+        assert_eq!(queue.get_entries(), [9, 7, 2, 5, 0, 1, 4, 3, 8, 6]);
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut queue = Queue::build(2, None, None);
+        assert!(!queue.go_back(false));
+        assert_eq!(queue.peek_next(false), Some(1));
+        assert!(queue.advance(false));
+        assert_eq!(queue.peek_next(false), None);
+        assert_eq!(queue.peek_next(true), Some(0));
+        assert!(!queue.advance(false));
+        assert_eq!(queue.get_current(), Some(1));
+        assert!(queue.advance(true));
+        assert_eq!(queue.get_current(), Some(0));
+        assert!(queue.go_back(true));
+        assert_eq!(queue.get_current(), Some(1));
+
+        queue.set_position(None);
+        assert_eq!(queue.peek_next(true), None);
+        assert!(!queue.advance(true));
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut queue = Queue::build(3, Some(1), None);
+        queue.insert_next(&[5, 6]);
+        assert_eq!(queue.get_entries(), [0, 1, 5, 6, 2]);
+        queue.push(7);
+        assert_eq!(queue.get_upcoming(), [5, 6, 2, 7]);
+
+        // Before current
+        assert_eq!(queue.remove(0), Some(0));
+        assert_eq!(queue.get_current(), Some(1));
+        // Current: the next one takes its place
+        assert_eq!(queue.remove(0), Some(1));
+        assert_eq!(queue.get_current(), Some(5));
+        assert_eq!(queue.remove(10), None);
+    }
+
+    #[test]
+    fn test_remap() {
+        let mut queue = Queue::build(3, Some(2), None);
+        // Song 2 is gone, the others swap places
+        queue.remap(|song| match song {
+            0 => Some(1),
+            1 => Some(0),
+            _ => None,
+        });
+        assert_eq!(queue.get_entries(), [1, 0]);
+        assert_eq!(queue.get_position(), Some(1));
+
+        let queue = Queue::from_entries(&[None, Some(4)], Some(0), None);
+        assert_eq!(queue.get_entries(), [4]);
+        assert_eq!(queue.get_position(), None);
+    }
+}
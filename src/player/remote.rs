@@ -0,0 +1,580 @@
+//! Remote control over TCP, for footswitch bridges and home automation scripts.
+//!
+//! The protocol is plain text, one command per line, so `nc` or any scripting language can talk
+//! to it. Each line is answered with `ok` or `error <reason>`:
+//!
+//! ```text
+//! play | pause | toggle | stop | next | previous
+//! volume <0-200>
+//! playlist <number from 1, or name>
+//! ```
+//!
+//! Player events are sent to every client as they happen, e.g. `song <name>`, `font <name>`,
+//! `position <seconds> <length>` and `error <message>`.
+//!
+//! Only this computer can connect, unless other computers are allowed in the settings. Then
+//! clients have to send `auth <token>` first, with the token shown in the settings.
+
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Read, Write},
+    net::{Ipv4Addr, Shutdown, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use rand::{distributions::Alphanumeric, Rng};
+
+use super::{Player, PlayerEvent, MAX_VOLUME};
+
+pub const DEFAULT_PORT: u16 = 7770;
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Longer lines are refused, and the client disconnected
+const MAX_LINE: usize = 1024;
+/// More connections are refused
+const MAX_CLIENTS: usize = 8;
+const TOKEN_LEN: usize = 16;
+
+/// Random token for clients on other computers
+pub fn new_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LEN)
+        .map(char::from)
+        .collect()
+}
+
+/// Compares every byte, so the time taken doesn't tell how much of a guess was right
+pub(crate) fn is_same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteCommand {
+    Play,
+    Pause,
+    Toggle,
+    Stop,
+    Next,
+    Previous,
+    Volume(f32),
+    /// Switch to a playlist and play it
+    Playlist(PlaylistTarget),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlaylistTarget {
+    /// Index from zero
+    Index(usize),
+    /// Case insensitive
+    Name(String),
+}
+
+impl RemoteCommand {
+    /// Parse one line of the text protocol
+    ///
+    /// # Errors
+    ///
+    /// Returns a message for the client if the command is unknown or its argument is invalid.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+        match command.to_lowercase().as_str() {
+            "play" => Ok(Self::Play),
+            "pause" => Ok(Self::Pause),
+            "toggle" => Ok(Self::Toggle),
+            "stop" => Ok(Self::Stop),
+            "next" => Ok(Self::Next),
+            "previous" | "prev" => Ok(Self::Previous),
+            "volume" => match arg.parse::<f32>() {
+                Ok(volume) if (0. ..=MAX_VOLUME).contains(&volume) => Ok(Self::Volume(volume)),
+                _ => Err(format!("volume should be 0-{MAX_VOLUME}")),
+            },
+            "playlist" if arg.is_empty() => Err("playlist needs a number or name".into()),
+            "playlist" => Ok(Self::Playlist(match arg.parse::<usize>() {
+                Ok(0) => return Err("playlists are numbered from 1".into()),
+                Ok(number) => PlaylistTarget::Index(number - 1),
+                Err(_) => PlaylistTarget::Name(arg.to_owned()),
+            })),
+            "" => Err("empty command".into()),
+            _ => Err(format!("unknown command: {command}")),
+        }
+    }
+}
+
+/// Line sent to clients, if the event is of interest outside the app
+fn format_event(event: &PlayerEvent) -> Option<String> {
+    let line = match event {
+        PlayerEvent::SongChanged { song, .. } => format!("song {song}"),
+        PlayerEvent::FontChanged { soundfont } => {
+            format!("font {}", soundfont.as_deref().unwrap_or("-"))
+        }
+        PlayerEvent::PositionTick { position, length } => {
+            format!("position {} {}", position.as_secs(), length.as_secs())
+        }
+        PlayerEvent::QueueChanged => "queue".into(),
+        PlayerEvent::PlaylistListChanged => "playlists".into(),
+        PlayerEvent::SongReloaded { song } => format!("reloaded {song}"),
+        PlayerEvent::NotifyError(message) => format!("error {message}"),
+        PlayerEvent::NotifyInfo(message) => format!("info {message}"),
+        PlayerEvent::ProblemFound(message) => format!("problem {message}"),
+        PlayerEvent::EndActionPending(action) => format!("end_action {action}"),
+        PlayerEvent::AutoPaused(reason) => format!("paused {reason}"),
+        PlayerEvent::Raise | PlayerEvent::Quit | PlayerEvent::FilesRefreshed => return None,
+    };
+    // One event per line
+    Some(line.replace(['\r', '\n'], " "))
+}
+
+/// Writers of clients that get events
+type Clients = Arc<Mutex<Vec<TcpStream>>>;
+
+/// What the clients' threads share
+struct Shared {
+    clients: Clients,
+    commands: Sender<RemoteCommand>,
+    running: Arc<AtomicBool>,
+    /// Connections open, authorized or not
+    connections: AtomicUsize,
+    /// Required from clients if set
+    token: Option<String>,
+}
+
+/// Listens on a port of this computer, or of every network interface if a token is given.
+/// Stops when dropped.
+pub struct RemoteServer {
+    commands: Receiver<RemoteCommand>,
+    running: Arc<AtomicBool>,
+    port: u16,
+}
+impl RemoteServer {
+    /// Port 0 picks a free one. Player events from the receiver are forwarded to clients.
+    /// With a token, other computers can connect too, but they have to send the token first.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the port can't be bound.
+    pub fn start(
+        port: u16,
+        token: Option<String>,
+        events: Receiver<PlayerEvent>,
+    ) -> anyhow::Result<Self> {
+        let host = if token.is_some() {
+            Ipv4Addr::UNSPECIFIED
+        } else {
+            Ipv4Addr::LOCALHOST
+        };
+        let listener = TcpListener::bind((host, port))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let running = Arc::new(AtomicBool::new(true));
+        let clients = Clients::default();
+        let (commands_tx, commands_rx) = mpsc::channel();
+        {
+            let shared = Arc::new(Shared {
+                clients: Arc::clone(&clients),
+                commands: commands_tx,
+                running: Arc::clone(&running),
+                connections: AtomicUsize::new(0),
+                token,
+            });
+            thread::spawn(move || accept_clients(&listener, &shared));
+        }
+        {
+            let running = Arc::clone(&running);
+            thread::spawn(move || forward_events(&events, &clients, &running));
+        }
+        Ok(Self {
+            commands: commands_rx,
+            running,
+            port,
+        })
+    }
+    #[must_use]
+    pub const fn get_port(&self) -> u16 {
+        self.port
+    }
+    /// Commands received since last call
+    #[must_use]
+    pub fn take_commands(&self) -> Vec<RemoteCommand> {
+        self.commands.try_iter().collect()
+    }
+}
+impl Drop for RemoteServer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+fn accept_clients(listener: &TcpListener, shared: &Arc<Shared>) {
+    while shared.running.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((mut stream, _)) => {
+                if shared.connections.load(Ordering::Relaxed) >= MAX_CLIENTS {
+                    let _ = stream.set_nonblocking(false);
+                    let _ = stream.set_write_timeout(Some(POLL_INTERVAL));
+                    let _ = writeln!(stream, "error too many clients");
+                    continue;
+                }
+                shared.connections.fetch_add(1, Ordering::Relaxed);
+                let shared = Arc::clone(shared);
+                thread::spawn(move || {
+                    serve_client(stream, &shared);
+                    shared.connections.fetch_sub(1, Ordering::Relaxed);
+                });
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => println!("Remote control: {e}"),
+        }
+    }
+}
+
+/// Ends when the client leaves, misbehaves, or the server stops
+fn serve_client(stream: TcpStream, shared: &Shared) {
+    if stream.set_nonblocking(false).is_err()
+        || stream.set_read_timeout(Some(POLL_INTERVAL)).is_err()
+        // A client that doesn't read shouldn't hold up the others
+        || stream.set_write_timeout(Some(POLL_INTERVAL)).is_err()
+    {
+        return;
+    }
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    read_commands(BufReader::new(stream), &mut writer, shared);
+    // Also closes the copy events are written to, which drops it from the clients
+    let _ = writer.shutdown(Shutdown::Both);
+}
+
+fn read_commands(mut reader: BufReader<TcpStream>, writer: &mut TcpStream, shared: &Shared) {
+    let mut authorized = shared.token.is_none();
+    if authorized {
+        add_client(writer, &shared.clients);
+    }
+    let mut line = String::new();
+    while shared.running.load(Ordering::Relaxed) {
+        let limit = (MAX_LINE - line.len()) as u64;
+        match (&mut reader).take(limit).read_line(&mut line) {
+            Ok(0) => return,
+            Ok(_) if !line.ends_with('\n') => {
+                if line.len() >= MAX_LINE {
+                    let _ = writeln!(writer, "error line too long");
+                    return;
+                }
+            }
+            Ok(_) => {
+                let reply = if authorized {
+                    run_line(&line, &shared.commands)
+                } else {
+                    match (line.trim().strip_prefix("auth "), &shared.token) {
+                        (Some(given), Some(token)) if is_same_token(given.trim(), token) => {
+                            authorized = true;
+                            add_client(writer, &shared.clients);
+                            "ok".into()
+                        }
+                        _ => {
+                            let _ = writeln!(writer, "error not authorized");
+                            return;
+                        }
+                    }
+                };
+                if writeln!(writer, "{reply}").is_err() {
+                    return;
+                }
+                line.clear();
+            }
+            // Timed out; a partial line stays in the buffer
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+            Err(_) => return,
+        }
+    }
+}
+
+/// Start sending events to the client
+fn add_client(writer: &TcpStream, clients: &Clients) {
+    if let Ok(writer) = writer.try_clone() {
+        clients.lock().push(writer);
+    }
+}
+
+/// Reply to a command line
+fn run_line(line: &str, commands: &Sender<RemoteCommand>) -> String {
+    // Already authorized
+    if line.trim().starts_with("auth ") {
+        return "ok".into();
+    }
+    match RemoteCommand::parse(line) {
+        Ok(command) => {
+            let _ = commands.send(command);
+            "ok".into()
+        }
+        Err(e) => format!("error {e}"),
+    }
+}
+
+fn forward_events(events: &Receiver<PlayerEvent>, clients: &Clients, running: &AtomicBool) {
+    while running.load(Ordering::Relaxed) {
+        let event = match events.recv_timeout(POLL_INTERVAL) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        let Some(line) = format_event(&event) else {
+            continue;
+        };
+        clients
+            .lock()
+            .retain_mut(|client| writeln!(client, "{line}").is_ok());
+    }
+}
+
+impl Player {
+    pub const fn get_remote_control(&self) -> bool {
+        self.remote.is_some()
+    }
+    /// Start or stop the remote control server
+    ///
+    /// # Errors
+    ///
+    /// Fails if the server can't listen on the configured port.
+    pub fn set_remote_control(&mut self, enabled: bool) -> anyhow::Result<()> {
+        if enabled == self.remote.is_some() {
+            return Ok(());
+        }
+        self.remote = if enabled {
+            let token = if self.remote_lan {
+                if self.remote_token.is_empty() {
+                    self.remote_token = new_token();
+                }
+                Some(self.remote_token.clone())
+            } else {
+                None
+            };
+            let events = self.subscribe();
+            Some(RemoteServer::start(self.remote_port, token, events)?)
+        } else {
+            None
+        };
+        Ok(())
+    }
+    pub const fn get_remote_port(&self) -> u16 {
+        self.remote_port
+    }
+    /// Takes effect when the server starts
+    pub const fn set_remote_port(&mut self, port: u16) {
+        self.remote_port = port;
+    }
+    /// Whether other computers can connect, with the token
+    pub const fn get_remote_lan(&self) -> bool {
+        self.remote_lan
+    }
+    /// Takes effect when the server starts
+    pub const fn set_remote_lan(&mut self, enabled: bool) {
+        self.remote_lan = enabled;
+    }
+    /// Clients on other computers need this. Empty until first needed.
+    pub fn get_remote_token(&self) -> &str {
+        &self.remote_token
+    }
+    /// Clients that used the old token can't connect anymore. Takes effect when the server
+    /// starts.
+    pub fn renew_remote_token(&mut self) {
+        self.remote_token = new_token();
+    }
+
+    pub(super) fn handle_remote_commands(&mut self) {
+        let Some(remote) = &self.remote else {
+            return;
+        };
+        for command in remote.take_commands() {
+            self.run_remote_command(command);
+        }
+    }
+
+    fn run_remote_command(&mut self, command: RemoteCommand) {
+        match command {
+            RemoteCommand::Play | RemoteCommand::Toggle if self.is_empty() => self.start(),
+            RemoteCommand::Play => self.play(),
+            RemoteCommand::Pause => self.pause(),
+            RemoteCommand::Toggle => {
+                if self.is_paused() {
+                    self.play();
+                } else {
+                    self.pause();
+                }
+            }
+            RemoteCommand::Stop => self.stop(),
+            RemoteCommand::Next => self.skip(),
+            RemoteCommand::Previous => self.skip_back(),
+            RemoteCommand::Volume(volume) => self.set_volume(volume),
+            RemoteCommand::Playlist(target) => {
+                let index = match target {
+                    PlaylistTarget::Index(index) => Some(index),
+                    PlaylistTarget::Name(name) => self
+                        .playlists
+                        .iter()
+                        .position(|playlist| playlist.name.eq_ignore_ascii_case(&name)),
+                };
+                let result = index
+                    .ok_or_else(|| anyhow::anyhow!("No such playlist"))
+                    .and_then(|index| self.switch_to_playlist(index));
+                match result {
+                    Ok(()) => self.start(),
+                    Err(e) => self.push_error(format!("Remote control: {e}")),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(RemoteCommand::parse("PLAY\r\n"), Ok(RemoteCommand::Play));
+        assert_eq!(RemoteCommand::parse("prev"), Ok(RemoteCommand::Previous));
+        assert_eq!(
+            RemoteCommand::parse("volume 55.5"),
+            Ok(RemoteCommand::Volume(55.5))
+        );
+        assert!(RemoteCommand::parse("volume 500").is_err());
+        assert!(RemoteCommand::parse("volume").is_err());
+        assert_eq!(
+            RemoteCommand::parse("playlist 2"),
+            Ok(RemoteCommand::Playlist(PlaylistTarget::Index(1)))
+        );
+        assert_eq!(
+            RemoteCommand::parse("playlist Work music"),
+            Ok(RemoteCommand::Playlist(PlaylistTarget::Name(
+                "Work music".into()
+            )))
+        );
+        assert!(RemoteCommand::parse("playlist 0").is_err());
+        assert!(RemoteCommand::parse("").is_err());
+        assert!(RemoteCommand::parse("dance").is_err());
+    }
+
+    #[test]
+    fn test_format_event() {
+        assert_eq!(
+            format_event(&PlayerEvent::PositionTick {
+                position: Duration::from_secs(3),
+                length: Duration::from_secs(90),
+            }),
+            Some("position 3 90".into())
+        );
+        assert_eq!(
+            format_event(&PlayerEvent::NotifyError("a\nb".into())),
+            Some("error a b".into())
+        );
+        assert_eq!(format_event(&PlayerEvent::Raise), None);
+    }
+
+    #[test]
+    fn test_server() {
+        let (events_tx, events_rx) = mpsc::channel();
+        let server = RemoteServer::start(0, None, events_rx).expect("free port");
+        let (mut stream, mut reader) = connect(&server);
+
+        stream.write_all(b"next\nbogus\n").expect("write");
+        let mut line = String::new();
+        reader.read_line(&mut line).expect("reply");
+        assert_eq!(line, "ok\n");
+        line.clear();
+        reader.read_line(&mut line).expect("reply");
+        assert!(line.starts_with("error"));
+        assert_eq!(server.take_commands(), vec![RemoteCommand::Next]);
+
+        events_tx
+            .send(PlayerEvent::SongChanged {
+                song: "a.mid".into(),
+                soundfont: None,
+            })
+            .expect("send");
+        line.clear();
+        reader.read_line(&mut line).expect("event");
+        assert_eq!(line, "song a.mid\n");
+    }
+
+    fn connect(server: &RemoteServer) -> (TcpStream, BufReader<TcpStream>) {
+        let stream = TcpStream::connect(("127.0.0.1", server.get_port())).expect("connect");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .expect("timeout");
+        let reader = BufReader::new(stream.try_clone().expect("clone"));
+        (stream, reader)
+    }
+
+    fn read_reply(reader: &mut BufReader<TcpStream>) -> String {
+        let mut line = String::new();
+        reader.read_line(&mut line).expect("reply");
+        line
+    }
+
+    #[test]
+    fn test_token() {
+        let (_events_tx, events_rx) = mpsc::channel();
+        let server = RemoteServer::start(0, Some("secret".into()), events_rx).expect("free port");
+
+        let (mut stream, mut reader) = connect(&server);
+        stream.write_all(b"next\n").expect("write");
+        assert_eq!(read_reply(&mut reader), "error not authorized\n");
+        assert_eq!(read_reply(&mut reader), "", "disconnected");
+
+        let (mut stream, mut reader) = connect(&server);
+        stream.write_all(b"auth wrong\n").expect("write");
+        assert_eq!(read_reply(&mut reader), "error not authorized\n");
+
+        let (mut stream, mut reader) = connect(&server);
+        stream.write_all(b"auth secret\nnext\n").expect("write");
+        assert_eq!(read_reply(&mut reader), "ok\n");
+        assert_eq!(read_reply(&mut reader), "ok\n");
+        assert_eq!(server.take_commands(), vec![RemoteCommand::Next]);
+    }
+
+    #[test]
+    fn test_long_line() {
+        let (_events_tx, events_rx) = mpsc::channel();
+        let server = RemoteServer::start(0, None, events_rx).expect("free port");
+        let (mut stream, mut reader) = connect(&server);
+
+        stream.write_all(&[b'a'; MAX_LINE]).expect("write");
+        assert_eq!(read_reply(&mut reader), "error line too long\n");
+        assert_eq!(read_reply(&mut reader), "", "disconnected");
+    }
+
+    #[test]
+    fn test_too_many_clients() {
+        let (_events_tx, events_rx) = mpsc::channel();
+        let server = RemoteServer::start(0, None, events_rx).expect("free port");
+        let mut clients: Vec<_> = (0..MAX_CLIENTS).map(|_| connect(&server)).collect();
+        for (stream, reader) in &mut clients {
+            stream.write_all(b"next\n").expect("write");
+            assert_eq!(read_reply(reader), "ok\n");
+        }
+
+        let (_stream, mut reader) = connect(&server);
+        assert_eq!(read_reply(&mut reader), "error too many clients\n");
+        assert_eq!(read_reply(&mut reader), "", "disconnected");
+
+        // Leaving makes room
+        clients.pop();
+        thread::sleep(POLL_INTERVAL * 3);
+        let (mut stream, mut reader) = connect(&server);
+        stream.write_all(b"next\n").expect("write");
+        assert_eq!(read_reply(&mut reader), "ok\n");
+    }
+}
@@ -0,0 +1,362 @@
+//! User script with hooks on player events.
+//!
+//! The script is a Rhai file in the config dir. It may define any of these functions:
+//! - `on_song_started(song, soundfont)`: paths of the song and the soundfont it plays with
+//! - `on_song_ended(song)`: the song played to the end
+//! - `on_playlist_loaded(name)`: a playlist file was opened
+//!
+//! Scripts can't touch the player directly. API calls are collected while a hook runs, and the
+//! player carries them out afterwards. Hooks run from the player update, never in the middle of
+//! another operation.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use parking_lot::Mutex;
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Scope, AST};
+
+use super::{serialize_player::config_dir, Player, PlayerEvent};
+
+const SCRIPT_FILE: &str = "script.rhai";
+/// Keeps runaway loops from freezing the app
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+
+/// Written when scripting is turned on and there's no script yet
+const SCRIPT_TEMPLATE: &str = r#"// SfontPlayer user script (Rhai, https://rhai.rs)
+//
+// Hooks:
+//   on_song_started(song, soundfont)
+//   on_song_ended(song)
+//   on_playlist_loaded(name)
+//
+// API:
+//   skip()
+//   set_volume(percent)
+//   toast(text)
+//   select_font(path)        soundfont from the library
+//   write_file(path, text)
+//   append_file(path, text)
+
+fn on_song_started(song, soundfont) {
+    // append_file("/home/me/listens.txt", `${song}\n`);
+}
+"#;
+
+#[must_use]
+pub fn script_path() -> PathBuf {
+    config_dir().join(SCRIPT_FILE)
+}
+
+/// Something the script asked the player to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptCommand {
+    Skip,
+    SetVolume(f32),
+    Toast(String),
+    SelectFont(PathBuf),
+}
+
+/// Player event that a script function is called for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptHook {
+    SongStarted { song: String, soundfont: String },
+    SongEnded { song: String },
+    PlaylistLoaded { name: String },
+}
+impl ScriptHook {
+    const fn get_fn_name(&self) -> &'static str {
+        match self {
+            Self::SongStarted { .. } => "on_song_started",
+            Self::SongEnded { .. } => "on_song_ended",
+            Self::PlaylistLoaded { .. } => "on_playlist_loaded",
+        }
+    }
+    fn get_args(&self) -> Vec<Dynamic> {
+        match self {
+            Self::SongStarted { song, soundfont } => vec![song.into(), soundfont.into()],
+            Self::SongEnded { song } => vec![song.into()],
+            Self::PlaylistLoaded { name } => vec![name.into()],
+        }
+    }
+}
+
+pub struct ScriptHost {
+    engine: Engine,
+    /// None while scripting is off
+    ast: Option<AST>,
+    /// API calls of the running hook
+    commands: Arc<Mutex<Vec<ScriptCommand>>>,
+    /// Hooks waiting for the next update
+    pending: Vec<ScriptHook>,
+    /// Path of the song that was started, for the song ended hook
+    playing_song: Option<String>,
+}
+
+impl Default for ScriptHost {
+    fn default() -> Self {
+        let commands = Arc::new(Mutex::new(vec![]));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        register_api(&mut engine, &commands);
+        Self {
+            engine,
+            ast: None,
+            commands,
+            pending: vec![],
+            playing_song: None,
+        }
+    }
+}
+
+impl ScriptHost {
+    /// Compile a script and run its top level statements
+    ///
+    /// # Errors
+    ///
+    /// Returns the message of a syntax error, or of an error in the top level statements.
+    pub fn load(&mut self, source: &str) -> Result<(), String> {
+        self.ast = None;
+        let ast = self.engine.compile(source).map_err(|e| e.to_string())?;
+        self.engine.run_ast(&ast).map_err(|e| e.to_string())?;
+        // Top level API calls are ignored
+        self.commands.lock().clear();
+        self.ast = Some(ast);
+        Ok(())
+    }
+    /// Read the script file, creating it from the template if there isn't one.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be created or read, or the script doesn't load.
+    pub fn load_file(&mut self) -> anyhow::Result<()> {
+        let path = script_path();
+        if !path.exists() {
+            fs::create_dir_all(config_dir())?;
+            fs::write(&path, SCRIPT_TEMPLATE)?;
+        }
+        let source = fs::read_to_string(&path)?;
+        self.load(&source).map_err(anyhow::Error::msg)
+    }
+    pub fn unload(&mut self) {
+        self.ast = None;
+        self.pending.clear();
+    }
+    pub const fn is_loaded(&self) -> bool {
+        self.ast.is_some()
+    }
+
+    /// Call a hook on the next update. Ignored if no script is loaded.
+    pub fn queue(&mut self, hook: ScriptHook) {
+        if let ScriptHook::SongStarted { song, .. } = &hook {
+            self.playing_song = Some(song.clone());
+        }
+        if self.is_loaded() {
+            self.pending.push(hook);
+        }
+    }
+    /// The started song played to the end
+    pub fn queue_song_ended(&mut self) {
+        if let Some(song) = self.playing_song.take() {
+            self.queue(ScriptHook::SongEnded { song });
+        }
+    }
+    pub fn take_pending(&mut self) -> Vec<ScriptHook> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Run a hook, if the script defines it. Returns the API calls it made.
+    ///
+    /// # Errors
+    ///
+    /// Returns the message if the hook throws or hits a runtime error.
+    pub fn run(&self, hook: &ScriptHook) -> Result<Vec<ScriptCommand>, String> {
+        let Some(ast) = &self.ast else {
+            return Ok(vec![]);
+        };
+        let fn_name = hook.get_fn_name();
+        let args = hook.get_args();
+        let defined = ast
+            .iter_functions()
+            .any(|function| function.name == fn_name && function.params.len() == args.len());
+        if !defined {
+            return Ok(vec![]);
+        }
+
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            CallFnOptions::new().eval_ast(false),
+            &mut Scope::new(),
+            ast,
+            fn_name,
+            args,
+        );
+        let commands = std::mem::take(&mut *self.commands.lock());
+        result
+            .map(|_| commands)
+            .map_err(|e| format!("{fn_name}: {e}"))
+    }
+}
+
+fn register_api(engine: &mut Engine, commands: &Arc<Mutex<Vec<ScriptCommand>>>) {
+    let queue = commands.clone();
+    engine.register_fn("skip", move || queue.lock().push(ScriptCommand::Skip));
+    let queue = commands.clone();
+    engine.register_fn("set_volume", move |volume: f64| {
+        queue.lock().push(ScriptCommand::SetVolume(volume as f32));
+    });
+    let queue = commands.clone();
+    engine.register_fn("set_volume", move |volume: i64| {
+        queue.lock().push(ScriptCommand::SetVolume(volume as f32));
+    });
+    let queue = commands.clone();
+    engine.register_fn("toast", move |text: &str| {
+        queue.lock().push(ScriptCommand::Toast(text.into()));
+    });
+    let queue = commands.clone();
+    engine.register_fn("select_font", move |path: &str| {
+        queue.lock().push(ScriptCommand::SelectFont(path.into()));
+    });
+
+    engine.register_fn(
+        "write_file",
+        |path: &str, text: &str| -> Result<(), Box<EvalAltResult>> {
+            fs::write(path, text).map_err(|e| format!("{path}: {e}").into())
+        },
+    );
+    engine.register_fn(
+        "append_file",
+        |path: &str, text: &str| -> Result<(), Box<EvalAltResult>> {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(text.as_bytes()))
+                .map_err(|e| format!("{path}: {e}").into())
+        },
+    );
+}
+
+impl Player {
+    pub const fn get_scripting(&self) -> bool {
+        self.scripting
+    }
+    /// Turn the user script on or off. Turning on loads the script file.
+    pub fn set_scripting(&mut self, value: bool) {
+        self.scripting = value;
+        if value {
+            self.reload_script();
+        } else {
+            self.scripts.unload();
+        }
+    }
+    /// Read the script file again, e.g. after editing it
+    pub fn reload_script(&mut self) {
+        if let Err(e) = self.scripts.load_file() {
+            self.push_error(format!("Script: {e}"));
+        }
+    }
+
+    /// Run the hooks queued since the last update. Hooks queued by their API calls wait for
+    /// the next update, so a script can't keep the player busy forever.
+    pub(super) fn run_script_hooks(&mut self) {
+        for hook in self.scripts.take_pending() {
+            match self.scripts.run(&hook) {
+                Ok(commands) => {
+                    for command in commands {
+                        self.apply_script_command(command);
+                    }
+                }
+                Err(e) => self.push_error(format!("Script: {e}")),
+            }
+        }
+    }
+    fn apply_script_command(&mut self, command: ScriptCommand) {
+        match command {
+            ScriptCommand::Skip => self.skip(),
+            ScriptCommand::SetVolume(volume) => self.set_volume(volume),
+            ScriptCommand::Toast(text) => self.emit(&PlayerEvent::NotifyInfo(text)),
+            ScriptCommand::SelectFont(path) => {
+                if let Err(e) = self.select_library_font(path) {
+                    self.push_error(format!("Script: {e}"));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks() {
+        let mut scripts = ScriptHost::default();
+        scripts.queue(ScriptHook::PlaylistLoaded { name: "a".into() });
+        assert!(scripts.take_pending().is_empty(), "no script loaded");
+
+        scripts
+            .load(
+                r#"
+                skip(); // ignored
+                fn on_song_started(song, soundfont) {
+                    if song.ends_with("loud.mid") {
+                        set_volume(50);
+                    }
+                    toast(`${song} with ${soundfont}`);
+                }
+                fn on_song_ended(song) {
+                    skip();
+                }
+                "#,
+            )
+            .expect("valid script");
+
+        scripts.queue(ScriptHook::SongStarted {
+            song: "dir/loud.mid".into(),
+            soundfont: "font.sf2".into(),
+        });
+        scripts.queue(ScriptHook::PlaylistLoaded { name: "a".into() });
+        scripts.queue_song_ended();
+        scripts.queue_song_ended();
+        let hooks = scripts.take_pending();
+        assert_eq!(hooks.len(), 3, "song ended once");
+
+        assert_eq!(
+            scripts.run(&hooks[0]),
+            Ok(vec![
+                ScriptCommand::SetVolume(50.),
+                ScriptCommand::Toast("dir/loud.mid with font.sf2".into()),
+            ])
+        );
+        // Not defined
+        assert_eq!(scripts.run(&hooks[1]), Ok(vec![]));
+        assert_eq!(scripts.run(&hooks[2]), Ok(vec![ScriptCommand::Skip]));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut scripts = ScriptHost::default();
+        assert!(scripts.load("fn on_song_ended(song {").is_err());
+        assert!(!scripts.is_loaded());
+
+        scripts
+            .load("fn on_song_ended(song) { loop {} }")
+            .expect("valid script");
+        let hook = ScriptHook::SongEnded {
+            song: String::new(),
+        };
+        assert!(scripts.run(&hook).is_err(), "operation limit");
+
+        scripts
+            .load(r#"fn on_song_ended(song) { toast("x"); throw "oops"; }"#)
+            .expect("valid script");
+        assert!(scripts.run(&hook).is_err());
+        // Calls of a failed hook aren't left behind
+        assert!(scripts.commands.lock().is_empty());
+    }
+}
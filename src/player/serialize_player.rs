@@ -2,9 +2,11 @@
 //!
 
 use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
     fs::{self, remove_file, File},
-    io::Write,
-    path::PathBuf,
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    path::{Path, PathBuf},
 };
 
 use anyhow::bail;
@@ -13,11 +15,22 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 use super::{
+    audio::export::AudioFormat,
+    audio_profile::AudioProfile,
+    channel_colors::ChannelColors,
+    end_action::EndAction,
+    font_chain::{FontChain, FontChainEntry},
+    global_hotkeys::GlobalHotkeys,
+    huge_songs::HugeSongLimits,
+    play_stats::PlayStats,
     playlist::{enums::SongSort, Playlist},
+    playlist_template::PlaylistTemplate,
+    program_lock::{DrumKit, ProgramLocks},
+    soundfont_library::FontLibrary,
     soundfont_list::FontSort,
-    Player, RepeatMode,
+    FontChangeMode, Player, RepeatMode,
 };
-use crate::player::{playlist::font_meta::FontMeta, PlayerError};
+use crate::player::PlayerError;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct PlaylistListEntry {
@@ -28,93 +41,422 @@ struct PlaylistListEntry {
     pub state: String,
 }
 
+/// Contents of the playlist list file
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct PlaylistList {
+    pub playlist_idx: Option<usize>,
+    pub playing_playlist_idx: Option<usize>,
+    pub playlists: Vec<PlaylistListEntry>,
+}
+
+/// A state file that exists, but couldn't be loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenStateFile {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Settings, font library, and playlist list live in separate files in the config dir, so one
+/// broken file doesn't take the others with it.
+const SETTINGS_FILE: &str = "settings.json";
+const LIBRARY_FILE: &str = "library.json";
+const PLAYLISTS_FILE: &str = "playlists.json";
+const PLAY_STATS_FILE: &str = "play_stats.json";
+/// Before the split, everything but the playlist list was in this one file in the state dir.
+const LEGACY_STATE_FILE: &str = "state.json";
+const LEGACY_FONTLIB_FILE: &str = "fontlib.json";
+/// The playlist list used to be a bare array in the data dir.
+const LEGACY_PLAYLISTS_FILE: &str = "playlists.json";
+
+/// Files of the main state, relative to their directory. Backups are kept of these.
+const CONFIG_FILES: [&str; 3] = [SETTINGS_FILE, LIBRARY_FILE, PLAYLISTS_FILE];
+const STATE_FILES: [&str; 1] = [PLAY_STATS_FILE];
+/// Last state that loaded without problems
+const BACKUP_DIR: &str = "backup";
+
+type StateLoader = fn(&mut Player, &Path) -> anyhow::Result<()>;
+
+/// Hashes of what was last written to each state file. Saving skips files whose contents
+/// haven't changed, so a save only touches what was edited.
+#[derive(Debug, Default)]
+pub struct WrittenFiles(HashMap<PathBuf, u64>);
+
+impl WrittenFiles {
+    /// Write the file, unless it already has these contents. Returns whether it was written.
+    fn write(&mut self, path: &Path, contents: &str) -> io::Result<bool> {
+        let mut hasher = DefaultHasher::new();
+        contents.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.0.get(path) == Some(&hash) && path.exists() {
+            return Ok(false);
+        }
+        let mut file = File::create(path)?;
+        file.write_all(contents.as_bytes())?;
+        self.0.insert(path.to_owned(), hash);
+        Ok(true)
+    }
+    fn forget(&mut self, path: &Path) {
+        self.0.remove(path);
+    }
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
 impl Player {
+    /// Save playlists, settings, the soundfont library and play stats
+    ///
+    /// # Errors
+    ///
+    /// Fails if saving is blocked, a state file failed to load and hasn't been dealt with, or a
+    /// file can't be written.
     pub fn save_state(&mut self) -> anyhow::Result<()> {
         if self.debug_block_saving {
             bail!(PlayerError::DebugBlockSaving)
         }
+        if !self.broken_state.is_empty() {
+            bail!(PlayerError::StateRecoveryPending)
+        }
         if let Err(e) = self.save_playlists() {
             bail!(format!("save_playlists(): {e}"))
         }
-        if let Err(e) = self.save_config() {
-            bail!(format!("save_config(): {e}"))
+        if let Err(e) = self.save_settings() {
+            bail!(format!("save_settings(): {e}"))
+        }
+        if let Err(e) = self.save_library() {
+            bail!(format!("save_library(): {e}"))
         }
-        if let Err(e) = self.save_fontlib() {
-            bail!(format!("save_fontlib(): {e}"))
+        if let Err(e) = self.save_play_stats() {
+            bail!(format!("save_play_stats(): {e}"))
         }
+        retire_legacy_state();
 
         Ok(())
     }
 
-    pub fn load_state(&mut self) -> anyhow::Result<()> {
-        if let Err(e) = self.load_playlists() {
-            bail!(format!("load_playlists(): {e}"))
+    /// Periodic save. Unless `autosave_app` is on, in-app playlists are left for `save_state` on
+    /// exit.
+    ///
+    /// # Errors
+    ///
+    /// Fails for the same reasons as [`Self::save_state`].
+    pub fn autosave_state(&mut self) -> anyhow::Result<()> {
+        if self.autosave_app {
+            return self.save_state();
+        }
+        if self.debug_block_saving {
+            bail!(PlayerError::DebugBlockSaving)
+        }
+        if !self.broken_state.is_empty() {
+            bail!(PlayerError::StateRecoveryPending)
+        }
+        if self.autosave_portable {
+            for playlist in &mut self.playlists {
+                if playlist.has_unsaved_changes() {
+                    let _ = playlist.save_portable();
+                }
+            }
+        }
+        if let Err(e) = self.save_settings() {
+            bail!(format!("save_settings(): {e}"))
         }
-        if let Err(e) = self.load_config() {
-            bail!(format!("load_config(): {e}"))
+        if let Err(e) = self.save_library() {
+            bail!(format!("save_library(): {e}"))
         }
-        if let Err(e) = self.load_fontlib() {
-            bail!(format!("load_fontlib(): {e}"))
+        if let Err(e) = self.save_play_stats() {
+            bail!(format!("save_play_stats(): {e}"))
         }
 
         Ok(())
     }
 
-    fn save_config(&self) -> Result<(), anyhow::Error> {
-        let state_dir = state_dir();
-        fs::create_dir_all(&state_dir)?;
+    /// Load everything that can be loaded. Files that can't be read are copied aside as
+    /// `.corrupt`, and saving is blocked until the user decides what to do with them.
+    /// Files that haven't been migrated to the split layout yet are read from the old location.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any of the files can't be loaded. They're listed in
+    /// [`Self::get_broken_state_files`]. Also fails if the backup of a good state can't be written.
+    pub fn load_state(&mut self) -> anyhow::Result<()> {
+        self.broken_state.clear();
+        let loaders: [(PathBuf, Option<PathBuf>, StateLoader); 4] = [
+            (
+                config_dir().join(PLAYLISTS_FILE),
+                Some(data_dir().join(LEGACY_PLAYLISTS_FILE)),
+                Self::load_playlists,
+            ),
+            (
+                config_dir().join(SETTINGS_FILE),
+                Some(state_dir().join(LEGACY_STATE_FILE)),
+                Self::load_settings,
+            ),
+            (
+                config_dir().join(LIBRARY_FILE),
+                Some(state_dir().join(LEGACY_FONTLIB_FILE)),
+                Self::load_library,
+            ),
+            (
+                state_dir().join(PLAY_STATS_FILE),
+                None,
+                Self::load_play_stats,
+            ),
+        ];
+        for (path, legacy_path, load) in loaders {
+            let path = match legacy_path {
+                Some(legacy_path) if !path.exists() && legacy_path.exists() => legacy_path,
+                _ => path,
+            };
+            // First launch
+            if !path.exists() {
+                continue;
+            }
+            if let Err(e) = load(self, &path) {
+                self.broken_state.push(BrokenStateFile {
+                    path,
+                    error: e.to_string(),
+                });
+            }
+        }
+
+        if self.broken_state.is_empty() {
+            if !self.debug_block_saving {
+                backup_state()?;
+            }
+            return Ok(());
+        }
+        for file in &self.broken_state {
+            let mut corrupt = file.path.clone().into_os_string();
+            corrupt.push(".corrupt");
+            let _ = fs::copy(&file.path, corrupt);
+        }
+        bail!(
+            "Couldn't load {}",
+            self.broken_state
+                .iter()
+                .map(|file| format!("{}: {}", file.path.display(), file.error))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    /// State files that failed to load. Saving is blocked while there are any.
+    pub fn get_broken_state_files(&self) -> &[BrokenStateFile] {
+        &self.broken_state
+    }
+    /// Is there a backup of the last state that loaded fine
+    #[must_use]
+    pub fn has_state_backup() -> bool {
+        config_dir().join(BACKUP_DIR).is_dir()
+            || state_dir().join(BACKUP_DIR).is_dir()
+            || data_dir().join(BACKUP_DIR).is_dir()
+    }
+    /// Try loading again, e.g. after fixing the files by hand
+    ///
+    /// # Errors
+    ///
+    /// See [`Self::load_state`].
+    pub fn retry_load_state(&mut self) -> anyhow::Result<()> {
+        self.clear_loaded_state();
+        self.load_state()
+    }
+    /// Replace the state files with the last ones that loaded fine, and load them
+    ///
+    /// # Errors
+    ///
+    /// Fails if the backup can't be copied back, or it doesn't load either.
+    pub fn restore_state_backup(&mut self) -> anyhow::Result<()> {
+        restore_backup()?;
+        self.retry_load_state()
+    }
+    /// Drop what was recovered, and let the fresh state overwrite the broken files
+    pub fn start_fresh_state(&mut self) {
+        self.clear_loaded_state();
+        self.broken_state.clear();
+    }
+    /// Continue with what was recovered, and let it overwrite the broken files
+    pub fn keep_recovered_state(&mut self) {
+        self.broken_state.clear();
+    }
+
+    fn clear_loaded_state(&mut self) {
+        self.stop();
+        self.playlists.clear();
+        self.playlist_idx = 0;
+        self.playing_playlist_idx = 0;
+        self.pending_refresh.clear();
+        self.deferred_loading_started = false;
+        // Files may have been replaced, e.g. by a backup
+        self.written_files.clear();
+        self.font_lib = FontLibrary::default();
+        self.font_chain = FontChain::default();
+        self.play_stats = PlayStats::default();
+    }
+
+    fn save_settings(&mut self) -> anyhow::Result<()> {
+        let config_dir = config_dir();
+        fs::create_dir_all(&config_dir)?;
 
         let data = json! ({
             "shuffle": self.shuffle,
             "repeat": self.repeat,
-            "playlist_idx": self.playlist_idx,
-            "autosave": self.autosave,
+            "autosave": self.autosave_portable,
+            "autosave_app": self.autosave_app,
+            "autosave_interval": self.autosave_interval,
+            "font_change_mode": self.font_change_mode,
+            "auto_limiter": self.auto_limiter,
+            "midi_clock": self.get_midi_clock(),
+            "midi_thru": self.get_midi_thru(),
+            "output_device": self.output_device,
+            "output_pair": self.output_pair,
+            "stream_output": self.stream_output,
+            "stream_port": self.stream_port,
+            "stream_lan": self.stream_lan,
+            "stream_token": self.stream_token,
+            "remote_control": self.get_remote_control(),
+            "remote_port": self.remote_port,
+            "remote_lan": self.remote_lan,
+            "remote_token": self.remote_token,
+            "program_locks": self.program_locks,
+            "drum_kit": self.drum_kit,
+            "audio_profiles": self.audio_profiles,
+            "global_hotkeys": self.global_hotkeys,
+            "channel_colors": self.channel_colors,
+            "playlist_template": self.playlist_template,
+            "follow_file": self.follow_file,
+            "export_format": self.export_format,
+            "huge_song_limits": self.huge_song_limits,
+            "end_action": self.end_action,
+            "auto_pause": self.get_auto_pause(),
+            "playback_speed": self.get_speed(),
+            "scripting": self.scripting,
         });
-        let config_file = state_dir.join("state.json");
-        let mut file = File::create(config_file)?;
-        file.write_all(data.to_string().as_bytes())?;
+        self.written_files
+            .write(&config_dir.join(SETTINGS_FILE), &data.to_string())?;
 
         Ok(())
     }
 
-    fn load_config(&mut self) -> anyhow::Result<()> {
-        let state_filepath = state_dir().join("state.json");
-        let data_string = std::fs::read_to_string(state_filepath)?;
+    fn load_settings(&mut self, path: &Path) -> anyhow::Result<()> {
+        let data_string = std::fs::read_to_string(path)?;
         let data: Value = serde_json::from_str(&data_string)?;
 
         self.shuffle = data["shuffle"].as_bool().is_some_and(|value| value);
         if let Some(repeat) = data["repeat"].as_u64() {
             self.repeat = RepeatMode::try_from(repeat as u8).unwrap_or_default();
         }
-        self.playlist_idx = match data["playlist_idx"].as_u64() {
-            Some(x) if (x as usize) < self.playlists.len() => x as usize,
-            _ => 0,
-        };
-        self.autosave = data["autosave"].as_bool().is_some_and(|value| value);
+        self.autosave_portable = data["autosave"].as_bool().is_some_and(|value| value);
+        self.autosave_app = data["autosave_app"].as_bool().unwrap_or(true);
+        if let Some(interval) = data["autosave_interval"].as_u64() {
+            self.set_autosave_interval(interval);
+        }
+        if let Ok(program_locks) = ProgramLocks::deserialize(&data["program_locks"]) {
+            self.program_locks = program_locks;
+        }
+        self.drum_kit = Option::<DrumKit>::deserialize(&data["drum_kit"]).unwrap_or_default();
+        if let Ok(audio_profiles) = Vec::<AudioProfile>::deserialize(&data["audio_profiles"]) {
+            self.audio_profiles = audio_profiles;
+        }
+        if let Ok(global_hotkeys) = GlobalHotkeys::deserialize(&data["global_hotkeys"]) {
+            self.set_global_hotkeys(global_hotkeys);
+        }
+        if let Ok(font_change_mode) = FontChangeMode::deserialize(&data["font_change_mode"]) {
+            self.font_change_mode = font_change_mode;
+        }
+        self.set_auto_limiter(data["auto_limiter"].as_bool().is_some_and(|value| value));
+        self.set_midi_clock(data["midi_clock"].as_bool().is_some_and(|value| value));
+        self.set_midi_thru(data["midi_thru"].as_bool().is_some_and(|value| value));
+        if let Some(port) = data["stream_port"]
+            .as_u64()
+            .and_then(|port| port.try_into().ok())
+        {
+            self.stream_port = port;
+        }
+        if let Some(port) = data["remote_port"]
+            .as_u64()
+            .and_then(|port| port.try_into().ok())
+        {
+            self.remote_port = port;
+        }
+        self.stream_lan = data["stream_lan"].as_bool().is_some_and(|value| value);
+        if let Some(token) = data["stream_token"].as_str() {
+            token.clone_into(&mut self.stream_token);
+        }
+        self.remote_lan = data["remote_lan"].as_bool().is_some_and(|value| value);
+        if let Some(token) = data["remote_token"].as_str() {
+            token.clone_into(&mut self.remote_token);
+        }
+        if data["remote_control"].as_bool().is_some_and(|value| value) {
+            if let Err(e) = self.set_remote_control(true) {
+                self.push_error(format!("Couldn't start remote control: {e}"));
+            }
+        }
+        if data["stream_output"].as_bool().is_some_and(|value| value) {
+            if let Err(e) = self.set_stream_output(true) {
+                self.push_error(format!("Couldn't start the network stream: {e}"));
+            }
+        }
+        if let Ok(channel_colors) = ChannelColors::deserialize(&data["channel_colors"]) {
+            self.channel_colors = channel_colors;
+        }
+        if let Ok(template) = PlaylistTemplate::deserialize(&data["playlist_template"]) {
+            self.playlist_template = template;
+        }
+        self.follow_file = data["follow_file"].as_bool().is_some_and(|value| value);
+        self.export_format = AudioFormat::deserialize(&data["export_format"]).unwrap_or_default();
+        self.huge_song_limits =
+            HugeSongLimits::deserialize(&data["huge_song_limits"]).unwrap_or_default();
+        self.end_action = EndAction::deserialize(&data["end_action"]).unwrap_or_default();
+        self.set_auto_pause(data["auto_pause"].as_bool().unwrap_or(true));
+        if let Some(speed) = data["playback_speed"].as_f64() {
+            self.set_speed(speed as f32);
+        }
+        self.output_device = data["output_device"].as_str().map(Into::into);
+        self.output_pair = u16::deserialize(&data["output_pair"]).unwrap_or_default();
+        if data["scripting"].as_bool().is_some_and(|value| value) {
+            self.set_scripting(true);
+        }
 
         Ok(())
     }
 
-    fn save_fontlib(&self) -> anyhow::Result<()> {
+    fn save_play_stats(&mut self) -> anyhow::Result<()> {
         let state_dir = state_dir();
         fs::create_dir_all(&state_dir)?;
 
-        let filepath = state_dir.join("fontlib.json");
-        let mut file = File::create(filepath)?;
+        let filepath = state_dir.join(PLAY_STATS_FILE);
+        let data = serde_json::to_string(&self.play_stats)?;
+        self.written_files.write(&filepath, &data)?;
+
+        Ok(())
+    }
+
+    fn load_play_stats(&mut self, path: &Path) -> anyhow::Result<()> {
+        let data_string = std::fs::read_to_string(path)?;
+        self.play_stats = serde_json::from_str(&data_string)?;
+
+        Ok(())
+    }
+
+    fn save_library(&mut self) -> anyhow::Result<()> {
+        let config_dir = config_dir();
+        fs::create_dir_all(&config_dir)?;
 
         let data = json!({
             "paths": self.font_lib.get_paths(),
-            "selected": self.font_lib.get_selected().map(FontMeta::get_path)
+            "selected": self.font_lib.get_selected_path(),
+            "favorites": self.font_lib.get_favorites(),
+            "font_chain": self.font_chain.get_entries(),
         });
-
-        file.write_all(data.to_string().as_bytes())?;
+        self.written_files
+            .write(&config_dir.join(LIBRARY_FILE), &data.to_string())?;
 
         Ok(())
     }
 
-    fn load_fontlib(&mut self) -> anyhow::Result<()> {
-        let filepath = state_dir().join("fontlib.json");
-        let data_string = std::fs::read_to_string(filepath)?;
+    fn load_library(&mut self, path: &Path) -> anyhow::Result<()> {
+        let data_string = std::fs::read_to_string(path)?;
         let data: Value = serde_json::from_str(&data_string)?;
         let Some(paths) = data["paths"].as_array() else {
             bail!("Couldn't parse paths");
@@ -123,12 +465,25 @@ impl Player {
             let Some(path_str) = value.as_str() else {
                 bail!("Couldn't parse path: {value}")
             };
-            let _ = self.font_lib.add_path(PathBuf::from(path_str));
+            // Fonts are found after startup
+            let _ = self
+                .font_lib
+                .add_path_without_refresh(PathBuf::from(path_str));
+        }
+        for path in Vec::<PathBuf>::deserialize(&data["favorites"]).unwrap_or_default() {
+            self.font_lib.set_favorite(path, true);
+        }
+        // No selection is saved as null.
+        if let Some(selected) = data["selected"].as_str() {
+            self.font_lib.restore_selection(selected.into());
+        }
+        // Not migrated yet: the chain was stored with the settings.
+        let font_chain = data
+            .get("font_chain")
+            .map_or_else(|| read_legacy_state()["font_chain"].take(), Clone::clone);
+        if let Ok(entries) = Vec::<FontChainEntry>::deserialize(&font_chain) {
+            self.font_chain = FontChain::from_entries(entries);
         }
-        let Some(selected) = data["selected"].as_str().map(std::convert::Into::into) else {
-            bail!("Couldn't parse paths");
-        };
-        let _ = self.font_lib.select_by_path(selected);
 
         Ok(())
     }
@@ -139,11 +494,7 @@ impl Player {
         let playlist_dir_rel = PathBuf::from(".").join("playlists");
         fs::create_dir_all(&playlist_dir)?;
 
-        for file in fs::read_dir(&playlist_dir)? {
-            let filepath = file?.path();
-            remove_file(filepath)?;
-        }
-
+        let mut playlist_files = vec![];
         let mut playlist_list = vec![];
         for i in 0..self.playlists.len() {
             let playlist = &mut self.playlists[i];
@@ -157,13 +508,7 @@ impl Player {
                 .expect("Playlist filepath string conversion failed.")
                 .to_owned();
 
-            let state = json!({
-                "font_idx": playlist.get_font_idx(),
-                "font_sort": playlist.get_font_sort() as u8,
-                "song_idx": playlist.get_song_idx(),
-                "song_sort": playlist.get_song_sort() as u8,
-            })
-            .to_string();
+            let state = view_state(playlist).to_string();
 
             playlist_list.push(PlaylistListEntry {
                 filepath,
@@ -172,68 +517,254 @@ impl Player {
             });
 
             if !playlist.is_portable() {
-                let abs_path = playlist
-                    .get_portable_path()
-                    .unwrap_or_else(|| playlist_dir.join(filename));
-                let mut playlist_file = File::create(&abs_path)?;
-                playlist_file.write_all(Value::from(&*playlist).to_string().as_bytes())?;
-            } else if self.autosave {
+                let abs_path = playlist_dir.join(filename);
+                self.written_files
+                    .write(&abs_path, &Value::from(&*playlist).to_string())?;
+                playlist_files.push(abs_path);
+            } else if self.autosave_portable && playlist.has_unsaved_changes() {
                 let _ = playlist.save_portable();
             }
         }
+        // Closed, renamed, or moved playlists
+        for file in fs::read_dir(&playlist_dir)? {
+            let filepath = file?.path();
+            if !playlist_files.contains(&filepath) {
+                remove_file(&filepath)?;
+                self.written_files.forget(&filepath);
+            }
+        }
 
-        let filepath = data_dir.join("playlists.json");
-        let data = json!(playlist_list);
-        let mut file = File::create(filepath)?;
-        file.write_all(data.to_string().as_bytes())?;
+        let config_dir = config_dir();
+        fs::create_dir_all(&config_dir)?;
+        let data = PlaylistList {
+            playlist_idx: Some(self.playlist_idx),
+            playing_playlist_idx: Some(self.playing_playlist_idx),
+            playlists: playlist_list,
+        };
+        self.written_files.write(
+            &config_dir.join(PLAYLISTS_FILE),
+            &serde_json::to_string(&data)?,
+        )?;
 
         Ok(())
     }
 
-    fn load_playlists(&mut self) -> anyhow::Result<()> {
+    fn load_playlists(&mut self, path: &Path) -> anyhow::Result<()> {
         let data_dir = data_dir();
 
-        let filepath = data_dir.join("playlists.json");
-        let data_string = std::fs::read_to_string(filepath)?;
-        let data: Vec<PlaylistListEntry> = serde_json::from_str(&data_string)?;
+        let data_string = std::fs::read_to_string(path)?;
+        let (data, parse_error) = match parse_playlist_list(&data_string, &read_legacy_state()) {
+            Ok(data) => (data, None),
+            Err(e) => {
+                let data = PlaylistList {
+                    playlists: salvage_playlist_entries(&data_string),
+                    ..Default::default()
+                };
+                (data, Some(e))
+            }
+        };
 
-        for entry in data {
+        for entry in data.playlists {
             let mut playlist = if entry.portable {
-                Playlist::open_portable(entry.filepath.into())?
+                match Playlist::open_portable(entry.filepath.clone().into()) {
+                    Ok(playlist) => playlist,
+                    Err(e) => {
+                        // The file may have been moved. That's not a problem with the state.
+                        self.push_error(format!("Couldn't open {}: {e}", entry.filepath));
+                        continue;
+                    }
+                }
             } else {
-                let wksp_path = data_dir.join(entry.filepath);
-                let wksp_str = std::fs::read_to_string(wksp_path)?;
-                let wksp_data: Value = serde_json::from_str(&wksp_str)?;
-                Playlist::from(wksp_data)
+                let wksp_path = data_dir.join(&entry.filepath);
+                let wksp_data = std::fs::read_to_string(&wksp_path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|wksp_str| Ok(serde_json::from_str::<Value>(&wksp_str)?));
+                match wksp_data {
+                    Ok(wksp_data) => Playlist::from(wksp_data),
+                    Err(e) => {
+                        self.broken_state.push(BrokenStateFile {
+                            path: wksp_path,
+                            error: e.to_string(),
+                        });
+                        continue;
+                    }
+                }
             };
 
             let entry_state: Result<Value, serde_json::Error> = serde_json::from_str(&entry.state);
             if let Ok(state) = entry_state {
-                if let Some(font_idx) = state["font_idx"].as_u64() {
-                    let _ = playlist.set_font_idx(Some(font_idx as usize));
-                }
-                if let Some(font_sort) = state["font_sort"].as_u64() {
-                    if let Ok(sort) = FontSort::try_from(font_sort as u8) {
-                        playlist.set_font_sort(sort);
-                    }
-                }
-                if let Some(song_idx) = state["song_idx"].as_u64() {
-                    let _ = playlist.set_song_idx(Some(song_idx as usize));
-                }
-                if let Some(song_sort) = state["song_sort"].as_u64() {
-                    if let Ok(sort) = SongSort::try_from(song_sort as u8) {
-                        playlist.set_song_sort(sort);
-                    }
-                }
+                restore_view_state(&mut playlist, &state);
             }
 
+            self.pending_refresh.push(playlist.get_id());
             self.playlists.push(playlist);
         }
+        self.playlist_idx = match data.playlist_idx {
+            Some(idx) if idx < self.playlists.len() => idx,
+            _ => 0,
+        };
+        self.playing_playlist_idx = match data.playing_playlist_idx {
+            Some(idx) if idx < self.playlists.len() => idx,
+            _ => self.playlist_idx,
+        };
 
+        if let Some(e) = parse_error {
+            bail!("{e}. Recovered {} playlists.", self.playlists.len());
+        }
         Ok(())
     }
 }
 
+/// Selection, sort and queue of a playlist. These aren't part of the playlist file.
+pub(super) fn view_state(playlist: &Playlist) -> Value {
+    json!({
+        "font_idx": playlist.get_font_idx(),
+        "font_sort": playlist.get_font_sort() as u8,
+        "song_idx": playlist.get_song_idx(),
+        "song_sort": playlist.get_song_sort() as u8,
+        "queue": playlist.get_queue_paths(),
+        "queue_idx": playlist.get_queue().get_position(),
+        "shuffle_seed": playlist.get_shuffle_seed(),
+    })
+}
+
+/// Put back what `view_state` saved. Files are checked later.
+pub(super) fn restore_view_state(playlist: &mut Playlist, state: &Value) {
+    let font_sort = state["font_sort"]
+        .as_u64()
+        .and_then(|sort| FontSort::try_from(sort as u8).ok());
+    playlist.restore_font_view(
+        state["font_idx"].as_u64().map(|index| index as usize),
+        font_sort.unwrap_or_else(|| playlist.get_font_sort()),
+    );
+    let song_sort = state["song_sort"]
+        .as_u64()
+        .and_then(|sort| SongSort::try_from(sort as u8).ok());
+    playlist.restore_song_view(
+        state["song_idx"].as_u64().map(|index| index as usize),
+        song_sort.unwrap_or_else(|| playlist.get_song_sort()),
+    );
+    if let Ok(queue) = Vec::<PathBuf>::deserialize(&state["queue"]) {
+        let queue_idx = state["queue_idx"].as_u64().map(|x| x as usize);
+        let shuffle_seed = state["shuffle_seed"].as_u64();
+        playlist.restore_queue(&queue, queue_idx, shuffle_seed);
+    }
+}
+
+/// Accepts the bare entry array from before the split too. Its tab indices were stored in the
+/// legacy state file.
+fn parse_playlist_list(text: &str, legacy_state: &Value) -> serde_json::Result<PlaylistList> {
+    match serde_json::from_str(text)? {
+        Value::Array(entries) => Ok(PlaylistList {
+            playlist_idx: Option::deserialize(&legacy_state["playlist_idx"])?,
+            playing_playlist_idx: Option::deserialize(&legacy_state["playing_playlist_idx"])?,
+            playlists: Vec::deserialize(Value::Array(entries))?,
+        }),
+        data => PlaylistList::deserialize(data),
+    }
+}
+
+/// Contents of the pre-split state file, or null if it's gone.
+fn read_legacy_state() -> Value {
+    std::fs::read_to_string(state_dir().join(LEGACY_STATE_FILE))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// Rename migrated pre-split files out of the way, so they won't be read over the new ones.
+fn retire_legacy_state() {
+    let legacy_files = [
+        (state_dir().join(LEGACY_STATE_FILE), None),
+        (state_dir().join(LEGACY_FONTLIB_FILE), None),
+        (
+            data_dir().join(LEGACY_PLAYLISTS_FILE),
+            Some(config_dir().join(PLAYLISTS_FILE)),
+        ),
+    ];
+    for (path, new_path) in legacy_files {
+        // Config and data dirs are the same on some platforms.
+        if !path.exists() || new_path.is_some_and(|new_path| new_path == path) {
+            continue;
+        }
+        let mut migrated = path.clone().into_os_string();
+        migrated.push(".migrated");
+        let _ = fs::rename(&path, migrated);
+    }
+}
+
+/// Best effort: find whole playlist entries in a broken or truncated playlist list.
+fn salvage_playlist_entries(text: &str) -> Vec<PlaylistListEntry> {
+    let mut entries = vec![];
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find('{') {
+        let start = pos + offset;
+        let mut stream =
+            serde_json::Deserializer::from_str(&text[start..]).into_iter::<PlaylistListEntry>();
+        if let Some(Ok(entry)) = stream.next() {
+            entries.push(entry);
+            pos = start + stream.byte_offset();
+        } else {
+            pos = start + 1;
+        }
+    }
+    entries
+}
+
+/// Keep a copy of state that loaded fine
+fn backup_state() -> anyhow::Result<()> {
+    copy_files(&config_dir(), &config_dir().join(BACKUP_DIR), &CONFIG_FILES)?;
+    copy_files(&state_dir(), &state_dir().join(BACKUP_DIR), &STATE_FILES)?;
+    let data_dir = data_dir();
+    copy_dir(
+        &data_dir.join("playlists"),
+        &data_dir.join(BACKUP_DIR).join("playlists"),
+    )
+}
+
+fn restore_backup() -> anyhow::Result<()> {
+    if !Player::has_state_backup() {
+        bail!("There's no backup.");
+    }
+    copy_files(&config_dir().join(BACKUP_DIR), &config_dir(), &CONFIG_FILES)?;
+    copy_files(&state_dir().join(BACKUP_DIR), &state_dir(), &STATE_FILES)?;
+    let data_dir = data_dir();
+    copy_dir(
+        &data_dir.join(BACKUP_DIR).join("playlists"),
+        &data_dir.join("playlists"),
+    )
+}
+
+/// Copy the files that exist
+fn copy_files(from: &Path, to: &Path, filenames: &[&str]) -> anyhow::Result<()> {
+    fs::create_dir_all(to)?;
+    for filename in filenames {
+        let source = from.join(filename);
+        if source.exists() {
+            fs::copy(source, to.join(filename))?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace contents of a directory with files of another
+fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    if to.exists() {
+        fs::remove_dir_all(to)?;
+    }
+    fs::create_dir_all(to)?;
+    if !from.is_dir() {
+        return Ok(());
+    }
+    for file in fs::read_dir(from)? {
+        let path = file?.path();
+        if let Some(filename) = path.file_name() {
+            fs::copy(&path, to.join(filename))?;
+        }
+    }
+    Ok(())
+}
+
 fn generate_playlist_filename(playlist: &Playlist, idx: usize) -> String {
     format!(
         "{idx:02}_{}.json",
@@ -244,21 +775,112 @@ fn generate_playlist_filename(playlist: &Playlist, idx: usize) -> String {
     )
 }
 
+#[must_use]
+pub fn config_dir() -> PathBuf {
+    project_dir("config", |dirs| dirs.config_dir().into())
+}
+
+#[must_use]
 pub fn data_dir() -> PathBuf {
-    project_dirs().data_dir().into()
+    project_dir("data", |dirs| dirs.data_dir().into())
 }
 
+#[must_use]
 pub fn state_dir() -> PathBuf {
-    project_dirs()
-        .state_dir()
-        .map(std::borrow::ToOwned::to_owned)
-        .map_or_else(
-            || project_dirs().data_dir().join("fallback_state_dir"),
-            |path| path,
+    project_dir("state", |dirs| {
+        dirs.state_dir().map_or_else(
+            || dirs.data_dir().join("fallback_state_dir"),
+            Path::to_path_buf,
         )
+    })
+}
+
+/// Generated files that can be deleted any time
+#[must_use]
+pub fn cache_dir() -> PathBuf {
+    project_dir("cache", |dirs| dirs.cache_dir().into())
 }
 
-fn project_dirs() -> ProjectDirs {
-    ProjectDirs::from("fi", "sevonj", env!("CARGO_PKG_NAME"))
-        .expect("Failed to create project dirs.")
+/// Without a home directory (as in the browser), a relative path stands in. Nothing gets saved
+/// there in the browser, but reading and writing fails like any missing file instead of panicking.
+fn project_dir(name: &str, pick: impl FnOnce(&ProjectDirs) -> PathBuf) -> PathBuf {
+    ProjectDirs::from("fi", "sevonj", env!("CARGO_PKG_NAME")).map_or_else(
+        || PathBuf::from(env!("CARGO_PKG_NAME")).join(name),
+        |dirs| pick(&dirs),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_written_files() {
+        let path =
+            std::env::temp_dir().join(format!("sfontplayer_state_{}.json", std::process::id()));
+        let mut written_files = WrittenFiles::default();
+        assert!(written_files.write(&path, "{}").unwrap());
+        assert!(!written_files.write(&path, "{}").unwrap());
+        assert!(written_files.write(&path, "[]").unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+
+        // Deleted behind our back
+        remove_file(&path).unwrap();
+        assert!(written_files.write(&path, "[]").unwrap());
+        written_files.forget(&path);
+        assert!(written_files.write(&path, "[]").unwrap());
+        let _ = remove_file(path);
+    }
+
+    #[test]
+    fn test_salvage_playlist_entries() {
+        let entries = [
+            PlaylistListEntry {
+                filepath: "./playlists/00_first.json".into(),
+                portable: false,
+                state: json!({ "song_idx": 1 }).to_string(),
+            },
+            PlaylistListEntry {
+                filepath: "/music/second.midpl".into(),
+                portable: true,
+                state: "{}".into(),
+            },
+        ];
+        let text = serde_json::to_string(&entries).expect("serialize");
+        // Cut off in the middle of a third entry
+        let broken = format!("{},{{\"filepath\":\"./play", &text[..text.len() - 1]);
+        assert!(serde_json::from_str::<Vec<PlaylistListEntry>>(&broken).is_err());
+
+        let salvaged = salvage_playlist_entries(&broken);
+        assert_eq!(salvaged.len(), 2);
+        assert_eq!(salvaged[0].filepath, entries[0].filepath);
+        assert_eq!(salvaged[0].state, entries[0].state);
+        assert!(salvaged[1].portable);
+
+        assert!(salvage_playlist_entries("garbage {{ }").is_empty());
+    }
+
+    #[test]
+    fn test_parse_playlist_list() {
+        let entry =
+            json!({ "filepath": "./playlists/00_a.json", "portable": false, "state": "{}" });
+        let legacy_state = json!({ "playlist_idx": 2, "playing_playlist_idx": 1 });
+
+        let text = json!([entry, entry]).to_string();
+        let list = parse_playlist_list(&text, &legacy_state).expect("legacy list");
+        assert_eq!(list.playlists.len(), 2);
+        assert_eq!(list.playlist_idx, Some(2));
+        assert_eq!(list.playing_playlist_idx, Some(1));
+        // Legacy state file already gone
+        let list = parse_playlist_list(&text, &Value::Null).expect("legacy list");
+        assert_eq!(list.playlist_idx, None);
+
+        let text = json!({ "playlist_idx": 0, "playlists": [entry] }).to_string();
+        let list = parse_playlist_list(&text, &legacy_state).expect("list");
+        assert_eq!(list.playlists.len(), 1);
+        assert_eq!(list.playlist_idx, Some(0));
+        assert_eq!(list.playing_playlist_idx, None);
+
+        assert!(parse_playlist_list("{ \"playlists\": [", &Value::Null).is_err());
+    }
 }
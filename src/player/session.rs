@@ -0,0 +1,243 @@
+//! Named snapshots of the open playlists, to switch between sets of them.
+//!
+//! Playlists in app memory are stored whole, portable ones by path. Either way, the snapshot
+//! has the selection, queue and playback position, like the app state does.
+
+use std::{fs, path::PathBuf, time::Duration};
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    playlist::Playlist,
+    serialize_player::{data_dir, restore_view_state, view_state},
+    Player, PlayerError, PlayerEvent,
+};
+
+const SESSIONS_DIR: &str = "sessions";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionPlaylist {
+    /// None for playlists in app memory
+    portable_path: Option<PathBuf>,
+    /// Contents of a playlist in app memory
+    playlist: Value,
+    /// Selection, sort and queue
+    state: Value,
+}
+impl SessionPlaylist {
+    fn new(playlist: &Playlist) -> Self {
+        let portable_path = playlist.get_portable_path();
+        Self {
+            playlist: if portable_path.is_some() {
+                Value::Null
+            } else {
+                Value::from(playlist)
+            },
+            portable_path,
+            state: view_state(playlist),
+        }
+    }
+
+    fn open(&self) -> anyhow::Result<Playlist> {
+        let mut playlist = match &self.portable_path {
+            Some(path) => Playlist::open_portable(path.clone())?,
+            None => Playlist::from(self.playlist.clone()),
+        };
+        restore_view_state(&mut playlist, &self.state);
+        Ok(playlist)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct PlayingSong {
+    playlist: usize,
+    song: usize,
+    position: Duration,
+    paused: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Session {
+    playlist_idx: usize,
+    playing: Option<PlayingSong>,
+    playlists: Vec<SessionPlaylist>,
+}
+
+impl Player {
+    /// Names of saved sessions, alphabetically
+    #[must_use]
+    pub fn list_sessions() -> Vec<String> {
+        let Ok(entries) = fs::read_dir(sessions_dir()) else {
+            return vec![];
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_owned())
+            })
+            .collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        names
+    }
+
+    /// Snapshot the open playlists under a name. Overwrites a session with the same name.
+    /// Unsaved changes of portable playlists aren't included.
+    ///
+    /// # Errors
+    ///
+    /// Fails if saving is blocked, the name can't be used as a file name, or the file can't be
+    /// written.
+    pub fn save_session(&self, name: &str) -> anyhow::Result<()> {
+        if self.debug_block_saving {
+            bail!(PlayerError::DebugBlockSaving)
+        }
+        let path = session_path(name)?;
+        let playing = self
+            .get_playing_song_idx()
+            .map(|song| PlayingSong {
+                playlist: self.playing_playlist_idx,
+                song,
+                position: self.get_playback_position(),
+                paused: self.is_paused(),
+            })
+            .filter(|_| self.party_queue.is_none());
+        let session = Session {
+            playlist_idx: self.playlist_idx,
+            playing,
+            playlists: self.playlists.iter().map(SessionPlaylist::new).collect(),
+        };
+        fs::create_dir_all(sessions_dir())?;
+        fs::write(path, serde_json::to_string(&session)?)?;
+        Ok(())
+    }
+
+    /// Replace the open playlists with a saved session, and continue where it was playing.
+    /// Refuses if that would lose unsaved changes.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a playlist has unsaved changes, or the session file can't be read.
+    pub fn load_session(&mut self, name: &str) -> anyhow::Result<()> {
+        if self.playlists.iter().any(Playlist::has_unsaved_changes) {
+            bail!("Save or reset playlists with unsaved changes first.")
+        }
+        let session: Session = serde_json::from_str(&fs::read_to_string(session_path(name)?)?)?;
+
+        // Indices of the session, in case some playlists can't be opened anymore
+        let mut new_indices = vec![];
+        let mut playlists = vec![];
+        for entry in &session.playlists {
+            match entry.open() {
+                Ok(playlist) => {
+                    new_indices.push(Some(playlists.len()));
+                    playlists.push(playlist);
+                }
+                Err(e) => {
+                    new_indices.push(None);
+                    let name = entry
+                        .portable_path
+                        .as_ref()
+                        .map_or_else(|| "a playlist".into(), |path| path.display().to_string());
+                    self.push_error(format!("Couldn't open {name}: {e}"));
+                }
+            }
+        }
+        if playlists.is_empty() {
+            bail!("Session {name} has no playlists that could be opened.")
+        }
+
+        self.stop();
+        self.pending_refresh = playlists.iter().map(Playlist::get_id).collect();
+        self.playlists = playlists;
+        self.playlist_idx = new_indices
+            .get(session.playlist_idx)
+            .copied()
+            .flatten()
+            .unwrap_or_default();
+        self.playing_playlist_idx = self.playlist_idx;
+        self.emit(&PlayerEvent::PlaylistListChanged);
+
+        let Some(playing) = session.playing else {
+            return Ok(());
+        };
+        let Some(index) = new_indices.get(playing.playlist).copied().flatten() else {
+            return Ok(());
+        };
+        if self.playlists[index]
+            .set_song_idx(Some(playing.song))
+            .is_err()
+        {
+            return Ok(());
+        }
+        self.start_playlist(index);
+        self.seek_to(playing.position);
+        if playing.paused {
+            self.pause();
+        }
+        Ok(())
+    }
+
+    /// Delete a saved session
+    ///
+    /// # Errors
+    ///
+    /// Fails if the name is invalid or the file can't be removed.
+    pub fn delete_session(name: &str) -> anyhow::Result<()> {
+        fs::remove_file(session_path(name)?)?;
+        Ok(())
+    }
+}
+
+fn sessions_dir() -> PathBuf {
+    data_dir().join(SESSIONS_DIR)
+}
+
+/// Names are used as file names as they are, so they can't have path separators.
+fn session_path(name: &str) -> anyhow::Result<PathBuf> {
+    let name = name.trim();
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        bail!("Invalid session name: {name:?}")
+    }
+    Ok(sessions_dir().join(format!("{name}.json")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_path() {
+        assert!(session_path("Work music").is_ok());
+        assert!(session_path(" ").is_err());
+        assert!(session_path("../settings").is_err());
+        assert!(session_path("a/b").is_err());
+        assert!(session_path(".hidden").is_err());
+    }
+
+    #[test]
+    fn test_session_playlist() {
+        let mut playlist = Playlist::default();
+        playlist.name = "Background".into();
+        for path in ["a.mid", "b.mid"] {
+            playlist.add_song(path.into()).unwrap();
+        }
+        playlist.set_song_idx(Some(1)).unwrap();
+
+        let entry = SessionPlaylist::new(&playlist);
+        let text = serde_json::to_string(&entry).unwrap();
+        let opened = serde_json::from_str::<SessionPlaylist>(&text)
+            .unwrap()
+            .open()
+            .unwrap();
+        assert_eq!(opened.name, "Background");
+        assert_eq!(opened.get_songs().len(), 2);
+        assert_eq!(opened.get_song_idx(), Some(1));
+        assert_ne!(opened.get_id(), playlist.get_id());
+    }
+}
@@ -2,11 +2,15 @@
 //!
 
 use serde::Serialize;
-use std::{error, fmt, fs, path::PathBuf};
+use std::{
+    error, fmt, fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, TryRecvError},
+};
 use walkdir::WalkDir;
 
 use super::{
-    playlist::font_meta::FontMeta,
+    playlist::font_meta::{is_font_path, FontMeta},
     soundfont_list::{FontList, FontListError, FontSort},
 };
 
@@ -44,6 +48,12 @@ pub struct FontLibrary {
     delet: Vec<bool>,
     pub crawl_subdirs: bool,
     fontlist: FontList,
+    /// Starred fonts for quick picking. Kept even if the file goes missing.
+    favorites: Vec<PathBuf>,
+    /// Results of a background refresh
+    scan: Option<Receiver<Vec<FontMeta>>>,
+    /// Selection to restore once the scan has found the font
+    pending_selection: Option<PathBuf>,
 }
 #[allow(clippy::derivable_impls)]
 impl Default for FontLibrary {
@@ -53,6 +63,9 @@ impl Default for FontLibrary {
             delet: vec![],
             crawl_subdirs: false,
             fontlist: FontList::default(),
+            favorites: vec![],
+            scan: None,
+            pending_selection: None,
         }
     }
 }
@@ -63,42 +76,93 @@ impl FontLibrary {
     pub fn sort(&mut self) {
         self.fontlist.sort();
     }
+    #[must_use]
     pub const fn get_sort(&self) -> FontSort {
         self.fontlist.get_sort()
     }
     pub fn set_sort(&mut self, sort: FontSort) {
         self.fontlist.set_sort(sort);
     }
+    #[must_use]
     pub const fn get_fonts(&self) -> &Vec<FontMeta> {
         self.fontlist.get_fonts()
     }
+    /// Get a font by index
+    ///
+    /// # Errors
+    ///
+    /// Fails if the index is out of range.
     pub fn get_font(&self, index: usize) -> Result<&FontMeta, FontListError> {
         self.fontlist.get_font(index)
     }
+    /// Get a font by index, for modifying
+    ///
+    /// # Errors
+    ///
+    /// Fails if the index is out of range.
     pub fn get_font_mut(&mut self, index: usize) -> Result<&mut FontMeta, FontListError> {
         self.fontlist.get_font_mut(index)
     }
+    #[must_use]
     pub fn get_selected(&self) -> Option<&FontMeta> {
         self.fontlist.get_selected()
     }
     pub fn get_selected_mut(&mut self) -> Option<&mut FontMeta> {
         self.fontlist.get_selected_mut()
     }
+    #[must_use]
     pub const fn get_selected_index(&self) -> Option<usize> {
         self.fontlist.get_selected_index()
     }
+    /// Select a font, or none
+    ///
+    /// # Errors
+    ///
+    /// Fails if the index is out of range.
     pub fn select(&mut self, value: Option<usize>) -> Result<(), FontListError> {
         self.fontlist.select(value)
     }
+    #[must_use]
     pub fn contains_font(&self, filepath: &PathBuf) -> bool {
         self.fontlist.contains(filepath)
     }
 
+    // --- Favorites --- //
+
+    #[must_use]
+    pub const fn get_favorites(&self) -> &Vec<PathBuf> {
+        &self.favorites
+    }
+    #[must_use]
+    pub fn is_favorite(&self, path: &Path) -> bool {
+        self.favorites.iter().any(|favorite| favorite == path)
+    }
+    pub fn set_favorite(&mut self, path: PathBuf, favorite: bool) {
+        if favorite == self.is_favorite(&path) {
+            return;
+        }
+        if favorite {
+            self.favorites.push(path);
+        } else {
+            self.favorites.retain(|existing| *existing != path);
+        }
+    }
+    /// Favorites that are in the library, in the library's order
+    #[must_use]
+    pub fn get_favorite_fonts(&self) -> Vec<&FontMeta> {
+        self.get_fonts()
+            .iter()
+            .filter(|font| self.is_favorite(&font.get_path()))
+            .collect()
+    }
+
     // --- Paths --- //
 
+    #[must_use]
     pub const fn get_paths(&self) -> &Vec<PathBuf> {
         &self.paths
     }
+    #[must_use]
     pub fn contains_path(&self, path: &PathBuf) -> bool {
         for existing_path in &self.paths {
             if *existing_path == *path {
@@ -107,6 +171,26 @@ impl FontLibrary {
         }
         false
     }
+    /// Selected font's path. Until the next refresh, this is the selection that will be
+    /// restored.
+    #[must_use]
+    pub fn get_selected_path(&self) -> Option<PathBuf> {
+        self.pending_selection
+            .clone()
+            .or_else(|| self.get_selected().map(FontMeta::get_path))
+    }
+    /// Select a font by path. If the library doesn't have it (yet), it's selected on the next
+    /// refresh.
+    pub fn restore_selection(&mut self, path: PathBuf) {
+        if self.select_by_path(path.clone()).is_err() {
+            self.pending_selection = Some(path);
+        }
+    }
+    /// Select the font with this path
+    ///
+    /// # Errors
+    ///
+    /// Returns `NoSuchFont` if the library doesn't have it.
     pub fn select_by_path(&mut self, path: PathBuf) -> Result<(), FontLibraryError> {
         for (i, font) in self.get_fonts().iter().enumerate() {
             if font.get_path() == path {
@@ -116,7 +200,22 @@ impl FontLibrary {
         }
         Err(FontLibraryError::NoSuchFont { path })
     }
+    /// Add a file or a directory to look for fonts in
+    ///
+    /// # Errors
+    ///
+    /// Fails if the path is already in the library or doesn't exist.
     pub fn add_path(&mut self, path: PathBuf) -> Result<(), FontLibraryError> {
+        self.add_path_without_refresh(path)?;
+        self.refresh();
+        Ok(())
+    }
+    /// Add a path, but leave finding the fonts for a later refresh.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the path is already in the library or doesn't exist.
+    pub fn add_path_without_refresh(&mut self, path: PathBuf) -> Result<(), FontLibraryError> {
         if self.contains_path(&path) {
             return Err(FontLibraryError::PathAlreadyExists { path });
         }
@@ -125,9 +224,13 @@ impl FontLibrary {
         }
         self.paths.push(path);
         self.delet.push(false);
-        self.refresh();
         Ok(())
     }
+    /// Queue a library path for removal
+    ///
+    /// # Errors
+    ///
+    /// Fails if there's no path at `index`.
     pub fn remove_path(&mut self, index: usize) -> Result<(), FontLibraryError> {
         if index >= self.paths.len() {
             return Err(FontLibraryError::IndexOutOfRange);
@@ -140,45 +243,45 @@ impl FontLibrary {
         self.paths.clear();
         self.delet.clear();
         self.fontlist.clear();
+        self.scan = None;
+        self.pending_selection = None;
     }
     pub fn refresh(&mut self) {
-        let mut found_files = vec![];
-        let selected_font_path = self.get_selected().map(FontMeta::get_path);
-
-        self.fontlist.clear();
-        for input_path in &self.paths {
-            if !input_path.exists() {
-                continue;
-            }
-            if input_path.is_dir() {
-                if self.crawl_subdirs {
-                    for entry in WalkDir::new(input_path)
-                        .into_iter()
-                        .filter_map(std::result::Result::ok)
-                    {
-                        let filepath = entry.path().to_owned();
-                        if filepath.is_file() && filepath.extension().is_some_and(|s| s == "sf2") {
-                            found_files.push(filepath);
-                        }
-                    }
-                } else if let Ok(paths) = fs::read_dir(input_path) {
-                    for entry in paths.filter_map(std::result::Result::ok) {
-                        let filepath = entry.path().clone();
-                        if self.contains_font(&filepath) {
-                            continue;
-                        }
-                        if filepath.is_file() && filepath.extension().is_some_and(|s| s == "sf2") {
-                            found_files.push(filepath);
-                        }
-                    }
-                }
-            } else if input_path.is_file() {
-                found_files.push(input_path.to_owned());
-            }
+        self.scan = None;
+        let fonts = scan_fonts(&self.paths, self.crawl_subdirs);
+        self.set_found_fonts(fonts);
+    }
+    /// Refresh in a background thread. Every font is read to get its presets, which is slow
+    /// for a big library. The results replace the list in `update`.
+    pub fn refresh_in_background(&mut self) {
+        if self.pending_selection.is_none() {
+            self.pending_selection = self.get_selected().map(FontMeta::get_path);
         }
+        let paths = self.paths.clone();
+        let crawl_subdirs = self.crawl_subdirs;
+        let (tx, rx) = mpsc::channel();
+        // The web build has no threads, but it has no folders to scan either.
+        #[cfg(target_arch = "wasm32")]
+        let _ = tx.send(scan_fonts(&paths, crawl_subdirs));
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(move || {
+            let _ = tx.send(scan_fonts(&paths, crawl_subdirs));
+        });
+        self.scan = Some(rx);
+    }
+    #[must_use]
+    pub const fn is_scanning(&self) -> bool {
+        self.scan.is_some()
+    }
+    fn set_found_fonts(&mut self, fonts: Vec<FontMeta>) {
+        let selected_font_path = self
+            .pending_selection
+            .take()
+            .or_else(|| self.get_selected().map(FontMeta::get_path));
 
-        for filepath in found_files {
-            let _ = self.fontlist.add(FontMeta::new(filepath));
+        self.fontlist.clear();
+        for font in fonts {
+            let _ = self.fontlist.add(font);
         }
 
         if let Some(path) = selected_font_path {
@@ -188,9 +291,23 @@ impl FontLibrary {
 
         self.sort();
     }
+    fn receive_scan(&mut self) {
+        let Some(scan) = &self.scan else {
+            return;
+        };
+        match scan.try_recv() {
+            Ok(fonts) => {
+                self.scan = None;
+                self.set_found_fonts(fonts);
+            }
+            Err(TryRecvError::Empty) => (),
+            Err(TryRecvError::Disconnected) => self.scan = None,
+        }
+    }
     pub fn update(&mut self) {
         self.assert_delete_queue_len();
         self.delete_queued();
+        self.receive_scan();
     }
     fn delete_queued(&mut self) {
         for index in (0..self.paths.len()).rev() {
@@ -209,3 +326,78 @@ impl FontLibrary {
         );
     }
 }
+
+/// Find and read the soundfont files in library paths
+fn scan_fonts(paths: &[PathBuf], crawl_subdirs: bool) -> Vec<FontMeta> {
+    let mut found_files = vec![];
+    for input_path in paths {
+        if !input_path.exists() {
+            continue;
+        }
+        if input_path.is_dir() {
+            if crawl_subdirs {
+                for entry in WalkDir::new(input_path)
+                    .into_iter()
+                    .filter_map(std::result::Result::ok)
+                {
+                    let filepath = entry.path().to_owned();
+                    if filepath.is_file() && is_font_path(&filepath) {
+                        found_files.push(filepath);
+                    }
+                }
+            } else if let Ok(paths) = fs::read_dir(input_path) {
+                for entry in paths.filter_map(std::result::Result::ok) {
+                    let filepath = entry.path().clone();
+                    if filepath.is_file() && is_font_path(&filepath) {
+                        found_files.push(filepath);
+                    }
+                }
+            }
+        } else if input_path.is_file() {
+            found_files.push(input_path.to_owned());
+        }
+    }
+    found_files.into_iter().map(FontMeta::new).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_favorites() {
+        let mut font_lib = FontLibrary::default();
+        let path = PathBuf::from("/fonts/a.sf2");
+        font_lib.set_favorite(path.clone(), true);
+        font_lib.set_favorite(path.clone(), true);
+        assert_eq!(font_lib.get_favorites().len(), 1);
+        assert!(font_lib.is_favorite(&path));
+        // Not in the library
+        assert!(font_lib.get_favorite_fonts().is_empty());
+
+        font_lib.set_favorite(path.clone(), false);
+        assert!(!font_lib.is_favorite(&path));
+    }
+
+    #[test]
+    fn test_background_refresh() {
+        let mut font_lib = FontLibrary::default();
+        font_lib
+            .add_path_without_refresh(std::env::temp_dir())
+            .expect("temp dir exists");
+        let selected = PathBuf::from("/fonts/a.sf2");
+        font_lib.restore_selection(selected.clone());
+        // Saved as selected until the scan is done
+        assert_eq!(font_lib.get_selected_path(), Some(selected.clone()));
+        font_lib.refresh_in_background();
+        assert!(font_lib.is_scanning());
+        assert_eq!(font_lib.get_selected_path(), Some(selected));
+
+        while font_lib.is_scanning() {
+            font_lib.update();
+            std::thread::yield_now();
+        }
+        // Not found
+        assert_eq!(font_lib.get_selected_path(), None);
+    }
+}
@@ -1,11 +1,11 @@
 use core::{error, fmt};
 use std::path::PathBuf;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use super::playlist::font_meta::FontMeta;
 
-#[derive(PartialEq, Eq, Default, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Default, Clone, Copy, Debug, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum FontSort {
     #[default]
@@ -13,6 +13,28 @@ pub enum FontSort {
     NameDesc = 1,
     SizeAsc = 2,
     SizeDesc = 3,
+    /// Arranged by hand
+    Custom = 4,
+}
+impl FontSort {
+    pub const ALL: [Self; 5] = [
+        Self::NameAsc,
+        Self::NameDesc,
+        Self::SizeAsc,
+        Self::SizeDesc,
+        Self::Custom,
+    ];
+}
+impl fmt::Display for FontSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NameAsc => write!(f, "Name ascending"),
+            Self::NameDesc => write!(f, "Name descending"),
+            Self::SizeAsc => write!(f, "Size ascending"),
+            Self::SizeDesc => write!(f, "Size descending"),
+            Self::Custom => write!(f, "Custom order"),
+        }
+    }
 }
 impl TryFrom<u8> for FontSort {
     type Error = ();
@@ -23,6 +45,7 @@ impl TryFrom<u8> for FontSort {
             x if x == Self::NameDesc as u8 => Ok(Self::NameDesc),
             x if x == Self::SizeAsc as u8 => Ok(Self::SizeAsc),
             x if x == Self::SizeDesc as u8 => Ok(Self::SizeDesc),
+            x if x == Self::Custom as u8 => Ok(Self::Custom),
             _ => Err(()),
         }
     }
@@ -74,6 +97,7 @@ impl FontList {
                 self.fonts.sort_by_key(FontMeta::get_size);
                 self.fonts.reverse();
             }
+            FontSort::Custom => (),
         };
         // Find the selected again
         if let Some(selected) = selected {
@@ -84,6 +108,7 @@ impl FontList {
             }
         }
     }
+    #[must_use]
     pub const fn get_sort(&self) -> FontSort {
         self.sort
     }
@@ -91,6 +116,7 @@ impl FontList {
         self.sort = sort;
         self.sort();
     }
+    #[must_use]
     pub fn contains(&self, filepath: &PathBuf) -> bool {
         for i in 0..self.fonts.len() {
             if self.fonts[i].get_path() == *filepath {
@@ -99,6 +125,11 @@ impl FontList {
         }
         false
     }
+    /// Add a font, unless one with the same path is already listed
+    ///
+    /// # Errors
+    ///
+    /// Returns `AlreadyExists` for a duplicate path.
     pub fn add(&mut self, font: FontMeta) -> Result<(), FontListError> {
         if self.contains(&font.get_path()) {
             return Err(FontListError::AlreadyExists);
@@ -109,21 +140,33 @@ impl FontList {
     pub fn clear(&mut self) {
         self.fonts.clear();
     }
+    #[must_use]
     pub const fn get_fonts(&self) -> &Vec<FontMeta> {
         &self.fonts
     }
+    /// Get a font by index
+    ///
+    /// # Errors
+    ///
+    /// Returns `IndexOutOfRange` if there's no font at `index`.
     pub fn get_font(&self, index: usize) -> Result<&FontMeta, FontListError> {
         if index >= self.fonts.len() {
             return Err(FontListError::IndexOutOfRange);
         }
         Ok(&self.fonts[index])
     }
+    /// Get a font by index, for modifying
+    ///
+    /// # Errors
+    ///
+    /// Returns `IndexOutOfRange` if there's no font at `index`.
     pub fn get_font_mut(&mut self, index: usize) -> Result<&mut FontMeta, FontListError> {
         if index >= self.fonts.len() {
             return Err(FontListError::IndexOutOfRange);
         }
         Ok(&mut self.fonts[index])
     }
+    #[must_use]
     pub fn get_selected(&self) -> Option<&FontMeta> {
         let index = self.selected?;
         Some(&self.fonts[index])
@@ -132,9 +175,15 @@ impl FontList {
         let index = self.selected?;
         Some(&mut self.fonts[index])
     }
+    #[must_use]
     pub const fn get_selected_index(&self) -> Option<usize> {
         self.selected
     }
+    /// Select a font, or none
+    ///
+    /// # Errors
+    ///
+    /// Returns `IndexOutOfRange` if there's no font at `index`.
     pub fn select(&mut self, value: Option<usize>) -> Result<(), FontListError> {
         let Some(index) = value else {
             self.selected = None;
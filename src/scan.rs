@@ -0,0 +1,243 @@
+//! Headless library scan: `sfontplayer scan <dir> [--csv] [--output <file>]`
+//!
+//! Validates every MIDI file and soundfont under a directory and reports unreadable or corrupt
+//! files, durations and preset counts, without opening the GUI.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::player::playlist::{
+    font_meta::{is_font_path, FontMeta},
+    midi_meta::MidiMeta,
+    song_list::csv_field,
+};
+
+const USAGE: &str = "Usage: sfontplayer scan <dir> [--csv] [--output <file>]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    Midi,
+    Soundfont,
+}
+impl FileKind {
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?;
+        if extension.eq_ignore_ascii_case("mid") {
+            Some(Self::Midi)
+        } else if is_font_path(path) {
+            Some(Self::Soundfont)
+        } else {
+            None
+        }
+    }
+
+    const fn get_name(self) -> &'static str {
+        match self {
+            Self::Midi => "midi",
+            Self::Soundfont => "soundfont",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScanEntry {
+    pub path: PathBuf,
+    pub kind: FileKind,
+    pub size: Option<u64>,
+    /// Song length in seconds
+    pub duration: Option<f64>,
+    /// Soundfont preset count
+    pub presets: Option<usize>,
+    pub error: Option<String>,
+}
+impl ScanEntry {
+    fn new(path: PathBuf, kind: FileKind) -> Self {
+        match kind {
+            FileKind::Midi => {
+                let mut meta = MidiMeta::new(path.clone());
+                meta.refresh();
+                Self {
+                    path,
+                    kind,
+                    size: meta.get_size(),
+                    duration: meta.get_duration().map(|duration| duration.as_secs_f64()),
+                    presets: None,
+                    error: meta.get_status().err().map(|e| e.to_string()),
+                }
+            }
+            FileKind::Soundfont => {
+                let mut meta = FontMeta::new(path.clone());
+                meta.refresh();
+                let error = meta.get_status().err().map(|e| e.to_string());
+                Self {
+                    path,
+                    kind,
+                    size: meta.get_size(),
+                    duration: None,
+                    presets: error.is_none().then(|| meta.get_presets().len()),
+                    error,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScanReport {
+    pub dir: PathBuf,
+    pub midis: usize,
+    pub soundfonts: usize,
+    /// Files that couldn't be read or parsed
+    pub problems: usize,
+    pub files: Vec<ScanEntry>,
+}
+impl ScanReport {
+    pub fn scan(dir: &Path) -> Self {
+        let mut paths: Vec<(PathBuf, FileKind)> = WalkDir::new(dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| {
+                FileKind::from_path(entry.path()).map(|kind| (entry.into_path(), kind))
+            })
+            .collect();
+        paths.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let files: Vec<ScanEntry> = paths
+            .into_iter()
+            .map(|(path, kind)| ScanEntry::new(path, kind))
+            .collect();
+        Self {
+            dir: dir.to_owned(),
+            midis: files.iter().filter(|f| f.kind == FileKind::Midi).count(),
+            soundfonts: files
+                .iter()
+                .filter(|f| f.kind == FileKind::Soundfont)
+                .count(),
+            problems: files.iter().filter(|f| f.error.is_some()).count(),
+            files,
+        }
+    }
+
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("path,kind,size,duration,presets,error\n");
+        for file in &self.files {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{}",
+                csv_field(&file.path.to_string_lossy()),
+                file.kind.get_name(),
+                file.size.map(|size| size.to_string()).unwrap_or_default(),
+                file.duration
+                    .map(|duration| format!("{duration:.3}"))
+                    .unwrap_or_default(),
+                file.presets
+                    .map(|presets| presets.to_string())
+                    .unwrap_or_default(),
+                csv_field(file.error.as_deref().unwrap_or_default()),
+            );
+        }
+        csv
+    }
+}
+
+/// Run a CLI subcommand if one was given. Returns exit code, or None to start the GUI.
+pub fn run(args: &[String]) -> Option<i32> {
+    if args.get(1).map(String::as_str) != Some("scan") {
+        return None;
+    }
+
+    let mut dir = None;
+    let mut csv = false;
+    let mut output = None;
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--csv" => csv = true,
+            "--output" | "-o" => output = rest.next().map(PathBuf::from),
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                return Some(0);
+            }
+            _ if dir.is_none() => dir = Some(PathBuf::from(arg)),
+            _ => {
+                eprintln!("Unexpected argument: {arg}\n{USAGE}");
+                return Some(2);
+            }
+        }
+    }
+    let Some(dir) = dir else {
+        eprintln!("{USAGE}");
+        return Some(2);
+    };
+    if !dir.is_dir() {
+        eprintln!("Not a directory: {}", dir.display());
+        return Some(2);
+    }
+
+    let report = ScanReport::scan(&dir);
+    let text = if csv {
+        report.to_csv()
+    } else {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => json + "\n",
+            Err(e) => {
+                eprintln!("{e}");
+                return Some(1);
+            }
+        }
+    };
+    match output {
+        Some(path) => {
+            if let Err(e) = fs::write(&path, text) {
+                eprintln!("Can't write {}: {e}", path.display());
+                return Some(1);
+            }
+        }
+        None => print!("{text}"),
+    }
+    eprintln!(
+        "Scanned {} midi files and {} soundfonts, {} problems.",
+        report.midis, report.soundfonts, report.problems
+    );
+    Some(i32::from(report.problems > 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::song;
+
+    #[test]
+    fn test_scan() {
+        let dir = std::env::temp_dir().join(format!("sfontplayer_scan_{}", std::process::id()));
+        let subdir = dir.join("sub");
+        fs::create_dir_all(&subdir).expect("temp dir");
+        fs::write(dir.join("good.mid"), song(&[]).to_midi()).expect("write");
+        fs::write(subdir.join("bad.MID"), b"not a midi").expect("write");
+        fs::write(dir.join("bad.sf2"), b"not a font").expect("write");
+        fs::write(dir.join("notes.txt"), b"ignored").expect("write");
+
+        let report = ScanReport::scan(&dir);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(report.midis, 2);
+        assert_eq!(report.soundfonts, 1);
+        assert_eq!(report.problems, 2);
+        let good = report
+            .files
+            .iter()
+            .find(|file| file.path.ends_with("good.mid"))
+            .expect("good.mid");
+        assert!(good.error.is_none());
+        assert!(good.duration.is_some());
+    }
+}
@@ -0,0 +1,315 @@
+//! Fixtures for tests: small MIDI files and a micro soundfont built in code, and golden files
+//! for checking rendered audio.
+//!
+//! Golden hashes are kept in `golden_renders.txt`. When a change to the audio path is meant to
+//! change the output, rerun the tests with `UPDATE_GOLDEN=1` to write the new hashes, and check
+//! the diff.
+
+use std::{f32::consts::TAU, fmt::Write, fs, path::Path, sync::Mutex, time::Duration};
+
+use midi_msg::{
+    Channel, ChannelVoiceMsg, Division, Header, Meta, MidiFile, MidiMsg, SMFFormat, Track,
+};
+use proptest::{collection, prelude::*};
+
+pub const TICKS_PER_BEAT: u16 = 96;
+/// Without tempo events, a beat lasts this long.
+pub const DEFAULT_BEAT_SECS: f32 = 0.5;
+
+// --- MIDI
+
+/// Single track song from events at absolute beats. Ends at the last event.
+pub fn song(events: &[(f32, MidiMsg)]) -> MidiFile {
+    let mut midifile = MidiFile {
+        header: Header {
+            format: SMFFormat::SingleTrack,
+            num_tracks: 0,
+            division: Division::TicksPerQuarterNote(TICKS_PER_BEAT),
+        },
+        tracks: vec![],
+    };
+    midifile.add_track(Track::default());
+    let mut end = 0.;
+    for (beat, event) in events {
+        midifile.extend_track(0, event.clone(), *beat);
+        end = f32::max(end, *beat);
+    }
+    midifile.extend_track(
+        0,
+        MidiMsg::Meta {
+            msg: Meta::EndOfTrack,
+        },
+        end,
+    );
+    midifile
+}
+
+/// File bytes of a song with one note, a beat long. For tests that need any valid song.
+pub fn short_song() -> Vec<u8> {
+    song(&[(0., note_on(0, 60, 100)), (1., note_off(0, 60))]).to_midi()
+}
+
+/// `channel` is 0-based
+pub fn note_on(channel: u8, note: u8, velocity: u8) -> MidiMsg {
+    MidiMsg::ChannelVoice {
+        channel: Channel::from_u8(channel),
+        msg: ChannelVoiceMsg::NoteOn { note, velocity },
+    }
+}
+pub fn note_off(channel: u8, note: u8) -> MidiMsg {
+    MidiMsg::ChannelVoice {
+        channel: Channel::from_u8(channel),
+        msg: ChannelVoiceMsg::NoteOff { note, velocity: 0 },
+    }
+}
+pub fn program_change(channel: u8, program: u8) -> MidiMsg {
+    MidiMsg::ChannelVoice {
+        channel: Channel::from_u8(channel),
+        msg: ChannelVoiceMsg::ProgramChange { program },
+    }
+}
+pub fn tempo(bpm: u32) -> MidiMsg {
+    MidiMsg::Meta {
+        msg: Meta::SetTempo(60_000_000 / bpm),
+    }
+}
+
+/// Songs of random notes at random tempos, and how long they should be. For property tests.
+pub fn random_song() -> impl Strategy<Value = (MidiFile, Duration)> {
+    // Tempo, quarter beats, note, channel, velocity. Quarter beats land on whole ticks.
+    let note = (30_u32..300, 1_u8..32, 0_u8..128, 0_u8..16, 1_u8..128);
+    collection::vec(note, 1..6).prop_map(|notes| {
+        let mut events = vec![];
+        let mut beat = 0.;
+        let mut length = Duration::ZERO;
+        for (bpm, quarters, note, channel, velocity) in notes {
+            let beats = f32::from(quarters) / 4.;
+            events.push((beat, tempo(bpm)));
+            events.push((beat, note_on(channel, note, velocity)));
+            beat += beats;
+            events.push((beat, note_off(channel, note)));
+            let tempo = Duration::from_micros(u64::from(60_000_000 / bpm));
+            length += tempo.mul_f32(beats);
+        }
+        (song(&events), length)
+    })
+}
+
+// --- Soundfont
+
+/// Preset 0: looped sine. Preset 1: looped square. Bank 128 preset 0: unlooped noise, for
+/// drums.
+pub fn micro_soundfont() -> Vec<u8> {
+    /// One cycle at 44.1 kHz is about 689 Hz, which is key 77 minus 23 cents.
+    const CYCLE: usize = 64;
+    /// The spec wants at least this many zeros after each sample.
+    const PADDING: usize = 46;
+
+    let sine = (0..CYCLE).map(|i| (f32::sin(i as f32 / CYCLE as f32 * TAU) * 16000.) as i16);
+    let square = (0..CYCLE).map(|i| if i < CYCLE / 2 { 16000 } else { -16000 });
+    let mut seed: u32 = 1;
+    let noise = (0..4096).map(move |_| {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        (seed >> 16) as i16 / 2
+    });
+    let waves: [(&str, Vec<i16>, bool); 3] = [
+        ("sine", sine.collect(), true),
+        ("square", square.collect(), true),
+        ("noise", noise.collect(), false),
+    ];
+
+    let mut smpl = vec![];
+    let mut shdr = vec![];
+    let mut inst = vec![];
+    let mut ibag = vec![];
+    let mut igen = vec![];
+    for (i, (name, wave, looped)) in waves.iter().enumerate() {
+        let start = (smpl.len() / 2) as u32;
+        smpl.extend(wave.iter().flat_map(|s| s.to_le_bytes()));
+        smpl.extend([0; PADDING * 2]);
+        let end = start + wave.len() as u32;
+
+        shdr.extend(name_bytes(name));
+        for value in [start, end, start, end, 44100] {
+            shdr.extend(value.to_le_bytes());
+        }
+        shdr.extend([77, (-23_i8).to_le_bytes()[0]]);
+        shdr.extend(0_u16.to_le_bytes()); // Link
+        shdr.extend(1_u16.to_le_bytes()); // Mono
+
+        inst.extend(name_bytes(name));
+        inst.extend((i as u16).to_le_bytes());
+        ibag.extend(bag(i as u16 * 2));
+        igen.extend(generator(54, u16::from(*looped))); // Sample modes
+        igen.extend(generator(53, i as u16)); // Sample ID
+    }
+    shdr.extend([0; 46]);
+    inst.extend(name_bytes("EOI"));
+    inst.extend((waves.len() as u16).to_le_bytes());
+    ibag.extend(bag(waves.len() as u16 * 2));
+    igen.extend(generator(0, 0));
+
+    let presets = [("sine", 0, 0), ("square", 1, 0), ("noise kit", 0, 128)];
+    let mut phdr = vec![];
+    let mut pbag = vec![];
+    let mut pgen = vec![];
+    for (i, (name, program, bank)) in presets.iter().enumerate() {
+        phdr.extend(preset_header(name, *program, *bank, i as u16));
+        pbag.extend(bag(i as u16));
+        pgen.extend(generator(41, i as u16)); // Instrument
+    }
+    phdr.extend(preset_header("EOP", 0, 0, presets.len() as u16));
+    pbag.extend(bag(presets.len() as u16));
+    pgen.extend(generator(0, 0));
+
+    let info = list(
+        "INFO",
+        &[
+            chunk("ifil", &[2, 0, 1, 0]),
+            chunk("isng", b"EMU8000\0"),
+            chunk("INAM", b"micro\0"),
+        ],
+    );
+    let sdta = list("sdta", &[chunk("smpl", &smpl)]);
+    let pdta = list(
+        "pdta",
+        &[
+            chunk("phdr", &phdr),
+            chunk("pbag", &pbag),
+            chunk("pmod", &[0; 10]),
+            chunk("pgen", &pgen),
+            chunk("inst", &inst),
+            chunk("ibag", &ibag),
+            chunk("imod", &[0; 10]),
+            chunk("igen", &igen),
+            chunk("shdr", &shdr),
+        ],
+    );
+    let mut body = b"sfbk".to_vec();
+    body.extend(info);
+    body.extend(sdta);
+    body.extend(pdta);
+    chunk("RIFF", &body)
+}
+
+fn chunk(id: &str, data: &[u8]) -> Vec<u8> {
+    let mut bytes = id.as_bytes().to_vec();
+    bytes.extend((data.len() as u32).to_le_bytes());
+    bytes.extend(data);
+    bytes
+}
+fn list(kind: &str, chunks: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = kind.as_bytes().to_vec();
+    data.extend(chunks.concat());
+    chunk("LIST", &data)
+}
+fn name_bytes(name: &str) -> [u8; 20] {
+    let mut bytes = [0; 20];
+    bytes[..name.len()].copy_from_slice(name.as_bytes());
+    bytes
+}
+fn preset_header(name: &str, program: u16, bank: u16, bag: u16) -> Vec<u8> {
+    let mut bytes = name_bytes(name).to_vec();
+    for value in [program, bank, bag] {
+        bytes.extend(value.to_le_bytes());
+    }
+    // Library, genre, morphology
+    bytes.extend([0; 12]);
+    bytes
+}
+fn bag(generator_index: u16) -> [u8; 4] {
+    let [a, b] = generator_index.to_le_bytes();
+    // No modulators
+    [a, b, 0, 0]
+}
+fn generator(kind: u16, amount: u16) -> [u8; 4] {
+    let [a, b] = kind.to_le_bytes();
+    let [c, d] = amount.to_le_bytes();
+    [a, b, c, d]
+}
+
+// --- Golden files
+
+const GOLDEN_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/golden_renders.txt");
+/// Tests run in parallel, and may update the file at the same time.
+static GOLDEN_LOCK: Mutex<()> = Mutex::new(());
+
+/// FNV-1a of the samples at 16 bits, so tiny float differences don't count.
+pub fn hash_samples(samples: &[f32]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for sample in samples {
+        let sample = (sample.clamp(-1., 1.) * f32::from(i16::MAX)).round() as i16;
+        for byte in sample.to_le_bytes() {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// Compare samples against the hash stored for `name`. With `UPDATE_GOLDEN` set, store the
+/// hash instead.
+pub fn assert_golden(name: &str, samples: &[f32]) {
+    let hash = format!("{:016x}", hash_samples(samples));
+    let _lock = GOLDEN_LOCK
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let contents = fs::read_to_string(GOLDEN_PATH).unwrap_or_default();
+    let mut entries: Vec<(String, String)> = contents
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(name, hash)| (name.to_owned(), hash.to_owned()))
+        .collect();
+    let stored = entries.iter().find(|(n, _)| n == name).map(|(_, h)| h);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        if stored != Some(&hash) {
+            entries.retain(|(n, _)| n != name);
+            entries.push((name.to_owned(), hash));
+            entries.sort();
+            let mut lines = String::new();
+            for (n, h) in &entries {
+                let _ = writeln!(lines, "{n} {h}");
+            }
+            fs::write(Path::new(GOLDEN_PATH), lines).expect("write golden file");
+        }
+        return;
+    }
+    let stored =
+        stored.unwrap_or_else(|| panic!("No golden hash for {name}. Run with UPDATE_GOLDEN=1."));
+    assert_eq!(
+        stored, &hash,
+        "Render of {name} changed. If that's intended, rerun with UPDATE_GOLDEN=1."
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustysynth::SoundFont;
+
+    #[test]
+    fn test_micro_soundfont() {
+        let soundfont = SoundFont::new(&mut micro_soundfont().as_slice()).expect("parse");
+        let presets: Vec<_> = soundfont
+            .get_presets()
+            .iter()
+            .map(|p| (p.get_name(), p.get_bank_number(), p.get_patch_number()))
+            .collect();
+        assert_eq!(
+            presets,
+            [("sine", 0, 0), ("square", 0, 1), ("noise kit", 128, 0)]
+        );
+    }
+
+    #[test]
+    fn test_song() {
+        let midifile = song(&[(1., note_on(0, 60, 100)), (2., note_off(0, 60))]);
+        let parsed = MidiFile::from_midi(&midifile.to_midi()).expect("parse");
+        let Track::Midi(events) = &parsed.tracks[0] else {
+            panic!("not a midi track")
+        };
+        let deltas: Vec<_> = events.iter().map(|e| e.delta_time).collect();
+        assert_eq!(deltas, [96, 96, 0]);
+    }
+}